@@ -19,7 +19,7 @@ fn main() {
 
 fn on_service_discovered(
     result: zeroconf::Result<ServiceDiscovery>,
-    _context: Option<Arc<dyn Any>>,
+    _context: Option<Arc<dyn Any + Send + Sync>>,
 ) {
     println!("Service discovered: {:?}", result.unwrap());
 
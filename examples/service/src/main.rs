@@ -31,18 +31,13 @@ fn main() {
 
 fn on_service_registered(
     result: zeroconf::Result<ServiceRegistration>,
-    context: Option<Arc<dyn Any>>,
+    context: Option<Arc<dyn Any + Send + Sync>>,
 ) {
     let service = result.unwrap();
 
     println!("Service registered: {:?}", service);
 
-    let context = context
-        .as_ref()
-        .unwrap()
-        .downcast_ref::<Arc<Mutex<Context>>>()
-        .unwrap()
-        .clone();
+    let context = context.get::<Arc<Mutex<Context>>>().unwrap().clone();
 
     context.lock().unwrap().service_name = service.name().clone();
 
@@ -52,6 +52,20 @@ fn impl_as_raw(ast: &DeriveInput) -> TokenStream {
     gen.into()
 }
 
+/// Implements `BuilderDelegate` for a type that derives `derive_builder::Builder`, adding a
+/// `builder()` function that returns a new `<Type>Builder`.
+///
+/// The builder is generated by `derive_builder`, so the fields and the struct may use its
+/// attributes to not require every field when building:
+///
+/// * `#[builder(default)]` (or `#[builder(default = "expr")]`) on a field that may be omitted,
+///   e.g. `#[builder(default = "ptr::null()")]` for an optional pointer
+/// * `#[builder(default, setter(strip_option))]` on an `Option<T>` field, which is `None` unless
+///   set with a `T`
+/// * `#[builder(build_fn(validate = "Self::validate"))]` on the struct, to check the builder
+///   with `fn validate(&self) -> Result<(), String>` when `build()` is called
+///
+/// Types with lifetimes, type parameters and `where` clauses are supported.
 #[proc_macro_derive(BuilderDelegate)]
 pub fn builder_delegate_macro_derive(input: TokenStream) -> TokenStream {
     impl_builder_delegate(&syn::parse(input).unwrap())
@@ -59,11 +73,12 @@ pub fn builder_delegate_macro_derive(input: TokenStream) -> TokenStream {
 
 fn impl_builder_delegate(ast: &DeriveInput) -> TokenStream {
     let name = &ast.ident;
-    let builder: Ident = syn::parse_str(&format!("{}Builder", name)).unwrap();
-    let generics = &ast.generics;
+    let builder = Ident::new(&format!("{}Builder", name), name.span());
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
 
     let gen = quote! {
-        impl #generics crate::prelude::BuilderDelegate<#builder #generics> for #name #generics {}
+        impl #impl_generics crate::prelude::BuilderDelegate<#builder #ty_generics>
+            for #name #ty_generics #where_clause {}
     };
 
     gen.into()
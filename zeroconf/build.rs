@@ -0,0 +1,86 @@
+//! Selects the mDNS backend of this build from the crate features and the target platform.
+//!
+//! Each backend is then compiled in with a single `cfg` (e.g. `#[cfg(backend_avahi)]`) instead of
+//! repeating the feature and platform predicate it is selected by wherever it is referred to.
+
+use std::env;
+
+/// The `cfg`s defined by this script, one for each backend
+const BACKENDS: &[&str] = &[
+    "backend_avahi",
+    "backend_avahi_dbus",
+    "backend_resolved",
+    "backend_avahi_core",
+    "backend_bonjour",
+    "backend_bonjour_sdk",
+    "backend_win32",
+    "backend_embedded",
+    "backend_mock",
+    "backend_plugin",
+];
+
+fn main() {
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let target_vendor = env::var("CARGO_CFG_TARGET_VENDOR").unwrap_or_default();
+
+    let linux = target_os == "linux";
+    let apple = target_vendor == "apple";
+    let bsd = target_os == "freebsd" || target_os == "openbsd";
+    let windows = target_os == "windows";
+    let android = target_os == "android";
+
+    // the test doubles replace the backend of the platform
+    let mock = feature("mock");
+    let plugin = feature("plugin") && !mock;
+    let replaced = feature("mock") || feature("plugin");
+    let native = !replaced && !feature("embedded");
+
+    let mdnsresponder = linux && native && feature("mdnsresponder");
+    let resolved = linux && native && feature("systemd-resolved") && !feature("mdnsresponder");
+    let avahi_dbus = linux
+        && native
+        && feature("avahi-dbus")
+        && !feature("mdnsresponder")
+        && !feature("systemd-resolved");
+    let avahi_core = linux
+        && native
+        && feature("avahi-core")
+        && !feature("mdnsresponder")
+        && !feature("systemd-resolved")
+        && !feature("avahi-dbus");
+
+    let backends = [
+        (linux
+            && native
+            && !feature("mdnsresponder")
+            && !feature("systemd-resolved")
+            && !feature("avahi-dbus")
+            && !feature("avahi-core"))
+            || (apple && native && feature("force-avahi"))
+            || (bsd && native),
+        avahi_dbus,
+        resolved,
+        avahi_core,
+        (apple && native && !feature("force-avahi")) || mdnsresponder,
+        windows && !replaced && feature("bonjour-sdk") && !feature("windows-native"),
+        windows && !replaced && feature("windows-native"),
+        // the mDNS daemon of Android is only accessible through the Java `NsdManager` API, so
+        // the embedded implementation is always used
+        !replaced && (feature("embedded") || android),
+        mock,
+        plugin,
+    ];
+
+    for (name, selected) in BACKENDS.iter().zip(backends.iter()) {
+        println!("cargo:rustc-check-cfg=cfg({})", name);
+        if *selected {
+            println!("cargo:rustc-cfg={}", name);
+        }
+    }
+}
+
+/// Returns true if the specified crate feature is enabled.
+fn feature(name: &str) -> bool {
+    let var = format!("CARGO_FEATURE_{}", name.to_uppercase().replace('-', "_"));
+    env::var_os(var).is_some()
+}
@@ -0,0 +1,92 @@
+//! Utilities for reusing the addresses of a host for every service it provides
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// How long the addresses of a host are reused at most, regardless of their TTL, so that the
+/// addresses are kept for the services of a burst (e.g. the 30 services of a NAS) but a host that
+/// has changed its addresses is looked up again soon after.
+const MAX_LIFETIME: Duration = Duration::from_secs(10);
+
+/// Remembers the addresses looked up for the hosts of the services of a browser, so that the
+/// services on the same host do not each look up the same addresses.
+///
+/// The addresses of a host are forgotten once the shortest of their TTLs has elapsed, and after
+/// `MAX_LIFETIME` at most.
+#[derive(Debug)]
+pub(crate) struct AddressCache<K, V> {
+    entries: HashMap<K, (V, Instant)>,
+}
+
+impl<K: Hash + Eq, V: Clone> AddressCache<K, V> {
+    /// Creates a new empty `AddressCache`.
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Returns the addresses of the host identified by `key`, if they have not expired.
+    pub fn get(&mut self, key: &K) -> Option<V> {
+        self.expire();
+        self.entries.get(key).map(|(value, _)| value.clone())
+    }
+
+    /// Remembers the addresses of the host identified by `key` for `ttl`. Addresses with a TTL of
+    /// zero are not to be cached, and are not.
+    pub fn insert(&mut self, key: K, value: V, ttl: Duration) {
+        self.expire();
+
+        let lifetime = ttl.min(MAX_LIFETIME);
+
+        if lifetime > Duration::from_secs(0) {
+            self.entries.insert(key, (value, Instant::now() + lifetime));
+        }
+    }
+
+    /// Forgets every address, e.g. when the network configuration has changed.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    fn expire(&mut self) {
+        let now = Instant::now();
+        self.entries.retain(|_, (_, expires_at)| *expires_at > now);
+    }
+}
+
+impl<K: Hash + Eq, V: Clone> Default for AddressCache<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn addresses_are_reused_until_ttl_elapses() {
+        let mut cache = AddressCache::new();
+
+        cache.insert("nas.local", 1, Duration::from_millis(5));
+
+        assert_eq!(cache.get(&"nas.local"), Some(1));
+        assert_eq!(cache.get(&"printer.local"), None);
+
+        thread::sleep(Duration::from_millis(10));
+
+        assert_eq!(cache.get(&"nas.local"), None);
+    }
+
+    #[test]
+    fn addresses_with_zero_ttl_are_not_cached() {
+        let mut cache = AddressCache::new();
+
+        cache.insert("nas.local", 1, Duration::from_secs(0));
+
+        assert_eq!(cache.get(&"nas.local"), None);
+    }
+}
@@ -0,0 +1,11 @@
+/// Represents the IP address families to resolve discovered services to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AddressFamily {
+    /// Only resolve IPv4 addresses
+    Ipv4,
+    /// Only resolve IPv6 addresses
+    Ipv6,
+    /// Resolve both IPv4 and IPv6 addresses
+    #[default]
+    Both,
+}
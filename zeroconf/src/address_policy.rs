@@ -0,0 +1,128 @@
+use std::cmp::Reverse;
+use std::net::IpAddr;
+
+/// Represents the order in which the addresses of a discovered service are reported
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AddressPolicy {
+    /// Keep the order reported by the mDNS daemon
+    #[default]
+    Unordered,
+    /// Report IPv4 addresses before IPv6 addresses
+    PreferIpv4,
+    /// Report IPv6 addresses before IPv4 addresses
+    PreferIpv6,
+    /// Report globally routable addresses before private, link-local and loopback addresses
+    PreferGlobal,
+    /// Report addresses in the same subnet as `local` (with the specified `prefix_len`) first
+    PreferSameSubnet {
+        /// The local address the subnet is derived from
+        local: IpAddr,
+        /// The length of the subnet prefix (e.g. `24` for `255.255.255.0`)
+        prefix_len: u8,
+    },
+}
+
+impl AddressPolicy {
+    /// Sorts the specified `addresses` according to this policy. The relative order of addresses
+    /// that are equally preferred is preserved.
+    pub fn sort(&self, addresses: &mut [IpAddr]) {
+        match *self {
+            Self::Unordered => {}
+            Self::PreferIpv4 => addresses.sort_by_key(|a| !a.is_ipv4()),
+            Self::PreferIpv6 => addresses.sort_by_key(|a| !a.is_ipv6()),
+            Self::PreferGlobal => addresses.sort_by_key(|a| !is_global(a)),
+            Self::PreferSameSubnet { local, prefix_len } => {
+                addresses.sort_by_key(|a| Reverse(same_subnet(&local, a, prefix_len)))
+            }
+        }
+    }
+}
+
+fn is_global(address: &IpAddr) -> bool {
+    match address {
+        IpAddr::V4(v4) => {
+            !(v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_unspecified()
+                || v4.is_broadcast())
+        }
+        IpAddr::V6(v6) => {
+            let first = v6.segments()[0];
+            // link-local (fe80::/10) and unique local (fc00::/7) addresses
+            !(v6.is_loopback()
+                || v6.is_unspecified()
+                || (first & 0xffc0) == 0xfe80
+                || (first & 0xfe00) == 0xfc00)
+        }
+    }
+}
+
+fn same_subnet(local: &IpAddr, address: &IpAddr, prefix_len: u8) -> bool {
+    match (local, address) {
+        (IpAddr::V4(l), IpAddr::V4(a)) => {
+            let mask = prefix_mask(prefix_len.min(32), 32) as u32;
+            u32::from(*l) & mask == u32::from(*a) & mask
+        }
+        (IpAddr::V6(l), IpAddr::V6(a)) => {
+            let mask = prefix_mask(prefix_len.min(128), 128);
+            u128::from(*l) & mask == u128::from(*a) & mask
+        }
+        _ => false,
+    }
+}
+
+fn prefix_mask(prefix_len: u8, bits: u32) -> u128 {
+    match prefix_len {
+        0 => 0,
+        len => (u128::MAX << (128 - len as u32)) >> (128 - bits),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addresses(addresses: &[&str]) -> Vec<IpAddr> {
+        addresses.iter().map(|a| a.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn prefer_ipv4_sorts_ipv4_first() {
+        let mut actual = addresses(&["fe80::1", "192.168.1.2", "2001:db8::1", "10.0.0.1"]);
+        AddressPolicy::PreferIpv4.sort(&mut actual);
+        let expected = addresses(&["192.168.1.2", "10.0.0.1", "fe80::1", "2001:db8::1"]);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn prefer_global_sorts_routable_addresses_first() {
+        let mut actual = addresses(&["fe80::1", "192.168.1.2", "2001:db8::1", "8.8.8.8"]);
+        AddressPolicy::PreferGlobal.sort(&mut actual);
+        let expected = addresses(&["2001:db8::1", "8.8.8.8", "fe80::1", "192.168.1.2"]);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn prefer_same_subnet_sorts_local_subnet_first() {
+        let mut actual = addresses(&["10.0.0.1", "fe80::1", "192.168.1.2"]);
+        let policy = AddressPolicy::PreferSameSubnet {
+            local: "192.168.1.100".parse().unwrap(),
+            prefix_len: 24,
+        };
+        policy.sort(&mut actual);
+        let expected = addresses(&["192.168.1.2", "10.0.0.1", "fe80::1"]);
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn prefer_same_subnet_supports_ipv6() {
+        let mut actual = addresses(&["2001:db8:1::1", "2001:db8:2::1"]);
+        let policy = AddressPolicy::PreferSameSubnet {
+            local: "2001:db8:2::ff".parse().unwrap(),
+            prefix_len: 48,
+        };
+        policy.sort(&mut actual);
+        assert_eq!(actual, addresses(&["2001:db8:2::1", "2001:db8:1::1"]));
+    }
+}
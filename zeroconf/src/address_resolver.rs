@@ -0,0 +1,62 @@
+//! Trait definition for cross-platform address resolver
+
+use crate::{EventLoop, NetworkInterface, Result};
+use std::any::Any;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Interface for interacting with underlying mDNS implementation reverse address resolution
+/// capabilities.
+///
+/// An `AddressResolver` resolves an IP address back to the mDNS host name of the device it
+/// belongs to (e.g. `somehost.local`).
+pub trait TAddressResolver {
+    /// Creates a new `AddressResolver` that resolves the specified `address`
+    fn new(address: IpAddr) -> Self
+    where
+        Self: Sized;
+
+    /// Sets the network interface on which to resolve the address.
+    ///
+    /// Most applications will want to use the default value `NetworkInterface::Unspec` to resolve
+    /// on all available interfaces.
+    fn set_network_interface(&mut self, interface: NetworkInterface);
+
+    /// Sets the [`AddressResolvedCallback`] that is invoked when the address has been resolved to
+    /// a host name.
+    ///
+    /// [`AddressResolvedCallback`]: ../type.AddressResolvedCallback.html
+    fn set_address_resolved_callback(
+        &mut self,
+        address_resolved_callback: Box<AddressResolvedCallback>,
+    );
+
+    /// Sets the optional user context to pass through to the callback. This is useful if you need
+    /// to share state between pre and post-callback. The context type must implement `Any`, `Send`
+    /// and `Sync`.
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>);
+
+    /// Starts the resolver. Returns an `EventLoop` which can be called to keep the resolver alive.
+    fn resolve(&mut self) -> Result<EventLoop<'_>>;
+}
+
+/// Callback invoked from [`AddressResolver`] once an address has been resolved.
+///
+/// # Arguments
+/// * `resolution` - The host name the address was resolved to
+/// * `context` - The optional user context passed through
+///
+/// [`AddressResolver`]: type.AddressResolver.html
+pub type AddressResolvedCallback =
+    dyn Fn(Result<AddressResolution>, Option<Arc<dyn Any + Send + Sync>>) + Send;
+
+/// Represents a host name an address has been resolved to by an [`AddressResolver`].
+///
+/// [`AddressResolver`]: type.AddressResolver.html
+#[derive(
+    Debug, Getters, Builder, BuilderDelegate, Serialize, Deserialize, Clone, PartialEq, Eq,
+)]
+pub struct AddressResolution {
+    address: IpAddr,
+    host_name: String,
+}
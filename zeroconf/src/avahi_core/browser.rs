@@ -0,0 +1,441 @@
+//! avahi-core implementation for cross-platform browser
+
+use super::avahi_util;
+use super::core_sys::{AvahiSServiceBrowser, AvahiSServiceResolver};
+use super::lookup::{
+    ManagedAvahiSServiceBrowser, ManagedAvahiSServiceBrowserParams, ManagedAvahiSServiceResolver,
+    ManagedAvahiSServiceResolverParams, ServiceResolverSet,
+};
+use super::server::ManagedAvahiServer;
+use super::string_list::ManagedAvahiStringList;
+use crate::debounce::Debouncer;
+use crate::ffi::{self, c_str, AsRaw, FromRaw, SharedContext, UnwrapOrNull};
+use crate::prelude::*;
+use crate::resolve_queue::ResolveQueue;
+use crate::{
+    AddressFamily, AddressPolicy, BrowserControl, DaemonReconnectedCallback, ErrorCallback,
+    EventLoop, NetworkInterface, Result, ServiceDiscoveredCallback, ServiceDiscovery, ServiceFlags,
+    ServiceType, TxtRecord,
+};
+use avahi_sys::{
+    AvahiAddress, AvahiBrowserEvent, AvahiIfIndex, AvahiLookupResultFlags, AvahiProtocol,
+    AvahiResolverEvent, AvahiStringList,
+};
+use libc::{c_char, c_void};
+use std::any::Any;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub struct AvahiCoreMdnsBrowser {
+    browser: Option<ManagedAvahiSServiceBrowser>,
+    kind: CString,
+    domain: Option<CString>,
+    interface_index: AvahiIfIndex,
+    context: SharedContext<AvahiCoreBrowserContext>,
+}
+
+// SAFETY: the browser exclusively owns its context and the Avahi objects, which are only used
+// through `&mut self` or the event loop borrowing it, and the callbacks and the context of
+// the user it holds are `Send`.
+unsafe impl Send for AvahiCoreMdnsBrowser {}
+
+impl TMdnsBrowser for AvahiCoreMdnsBrowser {
+    fn new(service_type: ServiceType) -> Self {
+        let kind = AvahiCoreBrowserContext::base_kind(&service_type);
+
+        // the first sub-type is browsed if any
+        let kind = match service_type.sub_types().first() {
+            Some(sub_type) => format!("_{}._sub.{}", sub_type, kind),
+            None => kind,
+        };
+
+        Self {
+            browser: None,
+            kind: c_string!(kind),
+            domain: None,
+            interface_index: avahi_sys::AVAHI_IF_UNSPEC,
+            context: SharedContext::new(AvahiCoreBrowserContext::new(service_type)),
+        }
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface_index = avahi_util::interface_index(interface);
+    }
+
+    fn set_domain(&mut self, domain: &str) {
+        self.domain = Some(c_string!(domain));
+    }
+
+    fn set_flags(&mut self, flags: ServiceFlags) {
+        unsafe { (*self.context.as_ptr()).flags = flags };
+    }
+
+    fn set_address_family(&mut self, address_family: AddressFamily) {
+        unsafe { (*self.context.as_ptr()).address_family = address_family };
+    }
+
+    fn set_address_policy(&mut self, _address_policy: AddressPolicy) {
+        // Avahi only resolves a single address per service
+    }
+
+    /// Raw records are not queried with `avahi-core`, `ServiceDiscovery::raw_records()` is always
+    /// empty.
+    fn set_include_raw_records(&mut self, _include_raw_records: bool) {}
+
+    fn set_resolve_concurrency(&mut self, concurrency: usize) {
+        unsafe {
+            (*self.context.as_ptr())
+                .resolve_queue
+                .set_concurrency(concurrency)
+        };
+    }
+
+    fn set_debounce(&mut self, window: Duration) {
+        unsafe { (*self.context.as_ptr()).debouncer.set_window(window) };
+    }
+
+    fn set_service_discovered_callback(
+        &mut self,
+        service_discovered_callback: Box<ServiceDiscoveredCallback>,
+    ) {
+        unsafe {
+            (*self.context.as_ptr()).service_discovered_callback = Some(service_discovered_callback)
+        };
+    }
+
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>) {
+        unsafe { (*self.context.as_ptr()).error_callback = Some(error_callback) };
+    }
+
+    fn control(&self) -> BrowserControl {
+        unsafe { (*self.context.as_ptr()).control.clone() }
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        unsafe { (*self.context.as_ptr()).user_context = Some(Arc::from(context)) };
+    }
+
+    /// The server runs in this process and never needs to be reconnected to, so the callback is
+    /// never invoked.
+    fn set_reconnected_callback(&mut self, _reconnected_callback: Box<DaemonReconnectedCallback>) {}
+
+    fn browse_services(&mut self) -> Result<EventLoop<'_>> {
+        let _span = operation_span!("browse", service_type = self.kind.to_string_lossy());
+
+        debug!("Browsing services: {:?}", self);
+
+        let server = ManagedAvahiServer::get()?;
+
+        self.browser = Some(ManagedAvahiSServiceBrowser::new(
+            ManagedAvahiSServiceBrowserParams::builder()
+                .server(&server)
+                .interface(self.interface_index)
+                .protocol(avahi_sys::AVAHI_PROTO_UNSPEC)
+                .kind(self.kind.as_ptr())
+                .domain(self.domain.as_ref().map(|d| d.as_ptr()).unwrap_or_null())
+                .flags(unsafe { (*self.context.as_ptr()).flags })
+                .callback(Some(browse_callback))
+                .userdata(self.context.as_ptr() as *mut c_void)
+                .build()?,
+        )?);
+
+        let poll = server.poll().clone();
+
+        unsafe { (*self.context.as_ptr()).server = Some(server) };
+
+        Ok(EventLoop::new(poll))
+    }
+}
+
+impl Drop for AvahiCoreMdnsBrowser {
+    fn drop(&mut self) {
+        // the browser must be freed before the server, which may be owned by the context
+        self.browser = None;
+    }
+}
+
+#[derive(FromRaw, AsRaw)]
+struct AvahiCoreBrowserContext {
+    service_type: ServiceType,
+    kind: CString,
+    resolvers: ServiceResolverSet,
+    resolve_queue: ResolveQueue<DiscoveredService>,
+    debouncer: Debouncer<DiscoveredService>,
+    service_discovered_callback: Option<Box<ServiceDiscoveredCallback>>,
+    error_callback: Option<Box<ErrorCallback>>,
+    control: BrowserControl,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+    flags: ServiceFlags,
+    address_family: AddressFamily,
+    // the resolvers must be freed before the server, fields are dropped in order
+    server: Option<Rc<ManagedAvahiServer>>,
+}
+
+impl AvahiCoreBrowserContext {
+    fn new(service_type: ServiceType) -> Self {
+        Self {
+            kind: c_string!(Self::base_kind(&service_type)),
+            service_type,
+            resolvers: ServiceResolverSet::default(),
+            resolve_queue: ResolveQueue::default(),
+            debouncer: Debouncer::default(),
+            service_discovered_callback: None,
+            error_callback: None,
+            control: BrowserControl::default(),
+            user_context: None,
+            flags: ServiceFlags::empty(),
+            address_family: AddressFamily::default(),
+            server: None,
+        }
+    }
+
+    /// Returns the service type without any sub-types, which is the type services are resolved
+    /// with.
+    fn base_kind(service_type: &ServiceType) -> String {
+        format!("_{}._{}", service_type.name(), service_type.protocol())
+    }
+
+    fn invoke_callback(&self, result: Result<ServiceDiscovery>) {
+        if !self.control.is_running() {
+            return;
+        }
+
+        match (
+            result,
+            &self.error_callback,
+            &self.service_discovered_callback,
+        ) {
+            (Err(e), Some(f), _) => f(e, self.user_context.clone()),
+            (result, _, Some(f)) => f(result, self.user_context.clone()),
+            _ => warn!("attempted to invoke browser callback but none was set"),
+        }
+    }
+}
+
+impl fmt::Debug for AvahiCoreBrowserContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AvahiCoreBrowserContext")
+            .field("service_type", &self.service_type)
+            .field("resolvers", &self.resolvers)
+            .finish()
+    }
+}
+
+unsafe extern "C" fn browse_callback(
+    _browser: *mut AvahiSServiceBrowser,
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    event: AvahiBrowserEvent,
+    name: *const c_char,
+    _kind: *const c_char,
+    domain: *const c_char,
+    _flags: AvahiLookupResultFlags,
+    userdata: *mut c_void,
+) {
+    let context = AvahiCoreBrowserContext::from_raw(userdata);
+
+    let service = || DiscoveredService {
+        interface,
+        protocol,
+        name: CStr::from_ptr(name).to_owned(),
+        domain: CStr::from_ptr(domain).to_owned(),
+    };
+
+    match event {
+        avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_NEW => {
+            if context.debouncer.has_returned(service) {
+                debug!(
+                    "Ignoring service that came back within the debounce window: {}",
+                    c_str::raw_to_str(name)
+                );
+                return;
+            }
+
+            if !context.resolve_queue.try_start() {
+                // only the services that wait for a resolve to finish are copied
+                context.resolve_queue.queue(service());
+                return;
+            }
+
+            let result = resolve_service(context, interface, protocol, name, domain);
+            resolve_next_on_error(context, result);
+        }
+        avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_REMOVE => context.debouncer.went_away(service),
+        avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_FAILURE => {
+            context.invoke_callback(Err("browser failure".into()))
+        }
+        _ => {}
+    };
+}
+
+/// A service reported by the browser that waits in the `ResolveQueue` of the context, or that has
+/// gone away and is remembered by its `Debouncer`
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct DiscoveredService {
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    name: CString,
+    domain: CString,
+}
+
+/// Starts resolving `service`, which waited in the `ResolveQueue` of the context.
+fn resolve_discovered(
+    context: &mut AvahiCoreBrowserContext,
+    service: &DiscoveredService,
+) -> Result<()> {
+    resolve_service(
+        context,
+        service.interface,
+        service.protocol,
+        service.name.as_ptr(),
+        service.domain.as_ptr(),
+    )
+}
+
+/// Reports the error of a resolve that could not be started, if any, and starts resolving the
+/// next queued services in its place until one can be started.
+fn resolve_next_on_error(context: &mut AvahiCoreBrowserContext, mut result: Result<()>) {
+    while let Err(e) = result {
+        context.invoke_callback(Err(e));
+
+        result = match context.resolve_queue.finish() {
+            Some(s) => resolve_discovered(context, &s),
+            None => return,
+        };
+    }
+}
+
+/// Starts resolving the specified service, the `ServiceDiscoveredCallback` of the context is
+/// invoked once it has been resolved.
+///
+/// The reported type is the sub-type if one is browsed, which cannot be resolved, so the service
+/// is resolved with the base type instead.
+fn resolve_service(
+    context: &mut AvahiCoreBrowserContext,
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    name: *const c_char,
+    domain: *const c_char,
+) -> Result<()> {
+    let raw_context = context.as_raw();
+    context.resolvers.insert(ManagedAvahiSServiceResolver::new(
+        ManagedAvahiSServiceResolverParams::builder()
+            .server(context.server.as_ref().unwrap())
+            .interface(interface)
+            .protocol(protocol)
+            .name(name)
+            .kind(context.kind.as_ptr())
+            .domain(domain)
+            .aprotocol(avahi_util::protocol(context.address_family))
+            .flags(context.flags)
+            .callback(Some(resolve_callback))
+            .userdata(raw_context)
+            .build()?,
+    )?);
+    Ok(())
+}
+
+unsafe extern "C" fn resolve_callback(
+    resolver: *mut AvahiSServiceResolver,
+    interface: AvahiIfIndex,
+    _protocol: AvahiProtocol,
+    event: AvahiResolverEvent,
+    name: *const c_char,
+    kind: *const c_char,
+    domain: *const c_char,
+    host_name: *const c_char,
+    addr: *const AvahiAddress,
+    port: u16,
+    txt: *mut AvahiStringList,
+    _flags: AvahiLookupResultFlags,
+    userdata: *mut c_void,
+) {
+    let name = c_str::raw_to_str(name);
+    let domain = c_str::raw_to_str(domain);
+
+    let context = AvahiCoreBrowserContext::from_raw(userdata);
+
+    match event {
+        avahi_sys::AvahiResolverEvent_AVAHI_RESOLVER_FAILURE => {
+            context.invoke_callback(Err(format!(
+                "failed to resolve service `{}` of type `{}` in domain `{}`",
+                name,
+                c_str::raw_to_str(kind),
+                domain
+            )
+            .into()));
+        }
+        avahi_sys::AvahiResolverEvent_AVAHI_RESOLVER_FOUND => {
+            let result = handle_resolver_found(
+                context,
+                c_str::raw_to_str(host_name),
+                addr,
+                interface,
+                name,
+                domain,
+                port,
+                txt,
+            );
+
+            if let Err(e) = result {
+                context.invoke_callback(Err(e));
+            }
+        }
+        _ => {}
+    };
+
+    context.resolvers.remove_raw(resolver);
+
+    if let Some(service) = context.resolve_queue.finish() {
+        let result = resolve_discovered(context, &service);
+        resolve_next_on_error(context, result);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+unsafe fn handle_resolver_found(
+    context: &AvahiCoreBrowserContext,
+    host_name: &str,
+    addr: *const AvahiAddress,
+    interface: AvahiIfIndex,
+    name: &str,
+    domain: &str,
+    port: u16,
+    txt: *mut AvahiStringList,
+) -> Result<()> {
+    if port == 0 {
+        // placeholders announced by other implementations with a `SRV` record for port 0
+        debug!("Ignoring placeholder service: {}", name);
+        return Ok(());
+    }
+
+    let address = avahi_util::avahi_address_to_ip(addr);
+
+    let txt = if txt.is_null() {
+        None
+    } else {
+        Some(TxtRecord::from(ManagedAvahiStringList::clone_raw(txt)))
+    };
+
+    let result = ServiceDiscovery::builder()
+        .name(name.to_string())
+        .service_type(context.service_type.clone())
+        .domain(domain.to_string())
+        .host_name(host_name.to_string())
+        .address(address)
+        .addresses(vec![address])
+        .scope_id(ffi::link_local_scope_id(&address, interface as u32))
+        .port(port)
+        .txt(txt)
+        .build()?;
+
+    debug!("Service resolved: {:?}", result);
+
+    context.invoke_callback(Ok(result));
+
+    Ok(())
+}
@@ -0,0 +1,167 @@
+//! Raw bindings to the parts of `libavahi-core` used by this module
+//!
+//! `avahi-sys` only covers `libavahi-client`, the types shared with it (e.g. `AvahiStringList`)
+//! are reused from there.
+
+use avahi_sys::{
+    AvahiAddress, AvahiBrowserEvent, AvahiEntryGroupState, AvahiIfIndex, AvahiLookupFlags,
+    AvahiLookupResultFlags, AvahiPoll, AvahiProtocol, AvahiPublishFlags, AvahiResolverEvent,
+    AvahiServerState, AvahiStringList,
+};
+use libc::{c_char, c_int, c_void};
+
+#[repr(C)]
+pub struct AvahiServer {
+    _unused: [u8; 0],
+}
+
+#[repr(C)]
+pub struct AvahiSEntryGroup {
+    _unused: [u8; 0],
+}
+
+#[repr(C)]
+pub struct AvahiSServiceBrowser {
+    _unused: [u8; 0],
+}
+
+#[repr(C)]
+pub struct AvahiSServiceResolver {
+    _unused: [u8; 0],
+}
+
+/// Storage for an `AvahiServerConfig`.
+///
+/// The layout of the structure differs between versions of Avahi, so it is only ever initialized
+/// by `avahi_server_config_init()` and released by `avahi_server_config_free()`. The buffer is
+/// larger than the structure of any released version.
+#[repr(C, align(8))]
+pub struct AvahiServerConfig {
+    _opaque: [u8; 1024],
+}
+
+pub type AvahiServerCallback = Option<
+    unsafe extern "C" fn(s: *mut AvahiServer, state: AvahiServerState, userdata: *mut c_void),
+>;
+
+pub type AvahiSEntryGroupCallback = Option<
+    unsafe extern "C" fn(
+        s: *mut AvahiServer,
+        g: *mut AvahiSEntryGroup,
+        state: AvahiEntryGroupState,
+        userdata: *mut c_void,
+    ),
+>;
+
+pub type AvahiSServiceBrowserCallback = Option<
+    unsafe extern "C" fn(
+        b: *mut AvahiSServiceBrowser,
+        interface: AvahiIfIndex,
+        protocol: AvahiProtocol,
+        event: AvahiBrowserEvent,
+        name: *const c_char,
+        kind: *const c_char,
+        domain: *const c_char,
+        flags: AvahiLookupResultFlags,
+        userdata: *mut c_void,
+    ),
+>;
+
+pub type AvahiSServiceResolverCallback = Option<
+    unsafe extern "C" fn(
+        r: *mut AvahiSServiceResolver,
+        interface: AvahiIfIndex,
+        protocol: AvahiProtocol,
+        event: AvahiResolverEvent,
+        name: *const c_char,
+        kind: *const c_char,
+        domain: *const c_char,
+        host_name: *const c_char,
+        a: *const AvahiAddress,
+        port: u16,
+        txt: *mut AvahiStringList,
+        flags: AvahiLookupResultFlags,
+        userdata: *mut c_void,
+    ),
+>;
+
+#[link(name = "avahi-core")]
+extern "C" {
+    pub fn avahi_server_config_init(c: *mut AvahiServerConfig) -> *mut AvahiServerConfig;
+    pub fn avahi_server_config_free(c: *mut AvahiServerConfig);
+
+    pub fn avahi_server_new(
+        api: *const AvahiPoll,
+        sc: *const AvahiServerConfig,
+        callback: AvahiServerCallback,
+        userdata: *mut c_void,
+        error: *mut c_int,
+    ) -> *mut AvahiServer;
+    pub fn avahi_server_free(s: *mut AvahiServer);
+    pub fn avahi_server_get_state(s: *mut AvahiServer) -> AvahiServerState;
+    pub fn avahi_server_errno(s: *mut AvahiServer) -> c_int;
+    pub fn avahi_server_get_host_name(s: *mut AvahiServer) -> *const c_char;
+    pub fn avahi_server_get_domain_name(s: *mut AvahiServer) -> *const c_char;
+    pub fn avahi_server_set_host_name(s: *mut AvahiServer, host_name: *const c_char) -> c_int;
+
+    pub fn avahi_s_entry_group_new(
+        s: *mut AvahiServer,
+        callback: AvahiSEntryGroupCallback,
+        userdata: *mut c_void,
+    ) -> *mut AvahiSEntryGroup;
+    pub fn avahi_s_entry_group_free(g: *mut AvahiSEntryGroup);
+    pub fn avahi_s_entry_group_commit(g: *mut AvahiSEntryGroup) -> c_int;
+    pub fn avahi_s_entry_group_reset(g: *mut AvahiSEntryGroup);
+    pub fn avahi_s_entry_group_is_empty(g: *mut AvahiSEntryGroup) -> c_int;
+
+    pub fn avahi_server_add_service_strlst(
+        s: *mut AvahiServer,
+        g: *mut AvahiSEntryGroup,
+        interface: AvahiIfIndex,
+        protocol: AvahiProtocol,
+        flags: AvahiPublishFlags,
+        name: *const c_char,
+        kind: *const c_char,
+        domain: *const c_char,
+        host: *const c_char,
+        port: u16,
+        strlst: *mut AvahiStringList,
+    ) -> c_int;
+    pub fn avahi_server_add_service_subtype(
+        s: *mut AvahiServer,
+        g: *mut AvahiSEntryGroup,
+        interface: AvahiIfIndex,
+        protocol: AvahiProtocol,
+        flags: AvahiPublishFlags,
+        name: *const c_char,
+        kind: *const c_char,
+        domain: *const c_char,
+        subtype: *const c_char,
+    ) -> c_int;
+
+    pub fn avahi_s_service_browser_new(
+        server: *mut AvahiServer,
+        interface: AvahiIfIndex,
+        protocol: AvahiProtocol,
+        kind: *const c_char,
+        domain: *const c_char,
+        flags: AvahiLookupFlags,
+        callback: AvahiSServiceBrowserCallback,
+        userdata: *mut c_void,
+    ) -> *mut AvahiSServiceBrowser;
+    pub fn avahi_s_service_browser_free(b: *mut AvahiSServiceBrowser);
+
+    pub fn avahi_s_service_resolver_new(
+        server: *mut AvahiServer,
+        interface: AvahiIfIndex,
+        protocol: AvahiProtocol,
+        name: *const c_char,
+        kind: *const c_char,
+        domain: *const c_char,
+        aprotocol: AvahiProtocol,
+        flags: AvahiLookupFlags,
+        callback: AvahiSServiceResolverCallback,
+        userdata: *mut c_void,
+    ) -> *mut AvahiSServiceResolver;
+    pub fn avahi_s_service_resolver_free(r: *mut AvahiSServiceResolver);
+}
@@ -0,0 +1,183 @@
+//! Rust friendly `AvahiSEntryGroup` wrappers/helpers
+
+use super::avahi_util;
+use super::core_sys::{
+    avahi_s_entry_group_commit, avahi_s_entry_group_free, avahi_s_entry_group_is_empty,
+    avahi_s_entry_group_new, avahi_s_entry_group_reset, avahi_server_add_service_strlst,
+    avahi_server_add_service_subtype, avahi_server_errno, AvahiSEntryGroup,
+    AvahiSEntryGroupCallback, AvahiServer,
+};
+use super::string_list::ManagedAvahiStringList;
+use crate::error::Operation;
+use crate::ffi::UnwrapMutOrNull;
+use crate::{Result, ServiceFlags};
+use avahi_sys::{AvahiIfIndex, AvahiProtocol};
+use libc::{c_char, c_void};
+use std::ptr;
+
+/// Wraps the `AvahiSEntryGroup` type from the raw `avahi-core` bindings.
+///
+/// This struct allocates a new `*mut AvahiSEntryGroup` when `ManagedAvahiSEntryGroup::new()` is
+/// invoked and calls the Avahi function responsible for freeing the group on `trait Drop`.
+#[derive(Debug)]
+pub struct ManagedAvahiSEntryGroup {
+    group: *mut AvahiSEntryGroup,
+    server: *mut AvahiServer,
+}
+
+impl ManagedAvahiSEntryGroup {
+    /// Initializes the underlying `*mut AvahiSEntryGroup` and verifies it was created; returning
+    /// `Err(String)` if unsuccessful.
+    pub fn new(
+        ManagedAvahiSEntryGroupParams {
+            server,
+            callback,
+            userdata,
+        }: ManagedAvahiSEntryGroupParams,
+    ) -> Result<Self> {
+        let group = unsafe { avahi_s_entry_group_new(server, callback, userdata) };
+        if group.is_null() {
+            let err = unsafe { avahi_server_errno(server) };
+            Err(avahi_util::error(
+                err,
+                format!(
+                    "could not initialize AvahiSEntryGroup: {}",
+                    avahi_util::get_error(err)
+                ),
+            )
+            .with_operation(Operation::Register))
+        } else {
+            Ok(Self { group, server })
+        }
+    }
+
+    /// Delegate function for `avahi_s_entry_group_is_empty()`.
+    pub fn is_empty(&self) -> bool {
+        unsafe { avahi_s_entry_group_is_empty(self.group) != 0 }
+    }
+
+    /// Delegate function for `avahi_server_add_service_strlst()`.
+    ///
+    /// Also propagates any error returned into a `Result`.
+    pub fn add_service(
+        &mut self,
+        AddServiceParams {
+            interface,
+            protocol,
+            flags,
+            name,
+            kind,
+            domain,
+            host,
+            port,
+            txt,
+        }: AddServiceParams,
+    ) -> Result<()> {
+        avahi!(
+            avahi_server_add_service_strlst(
+                self.server,
+                self.group,
+                interface,
+                protocol,
+                avahi_util::publish_flags(flags),
+                name,
+                kind,
+                domain,
+                host,
+                port,
+                txt.map(|t| t.inner()).unwrap_mut_or_null()
+            ),
+            "could not register service",
+            Operation::Register
+        )
+    }
+
+    /// Delegate function for `avahi_server_add_service_subtype()`.
+    pub fn add_subtype(
+        &mut self,
+        AddSubtypeParams {
+            interface,
+            protocol,
+            name,
+            kind,
+            domain,
+            subtype,
+        }: AddSubtypeParams,
+    ) -> Result<()> {
+        avahi!(
+            avahi_server_add_service_subtype(
+                self.server,
+                self.group,
+                interface,
+                protocol,
+                0,
+                name,
+                kind,
+                domain,
+                subtype
+            ),
+            "could not register service sub-type",
+            Operation::Register
+        )
+    }
+
+    /// Delegate function for `avahi_s_entry_group_commit()`.
+    pub fn commit(&mut self) -> Result<()> {
+        avahi!(
+            avahi_s_entry_group_commit(self.group),
+            "could not commit service",
+            Operation::Register
+        )
+    }
+
+    /// Delegate function for `avahi_s_entry_group_reset()`.
+    pub fn reset(&mut self) {
+        unsafe { avahi_s_entry_group_reset(self.group) };
+    }
+}
+
+impl Drop for ManagedAvahiSEntryGroup {
+    fn drop(&mut self) {
+        unsafe { avahi_s_entry_group_free(self.group) };
+    }
+}
+
+/// Holds parameters for initializing a new `ManagedAvahiSEntryGroup` with
+/// `ManagedAvahiSEntryGroup::new()`.
+#[derive(Builder, BuilderDelegate)]
+pub struct ManagedAvahiSEntryGroupParams {
+    server: *mut AvahiServer,
+    callback: AvahiSEntryGroupCallback,
+    userdata: *mut c_void,
+}
+
+/// Holds parameters for `ManagedAvahiSEntryGroup::add_service()`.
+#[derive(Builder, BuilderDelegate)]
+pub struct AddServiceParams<'a> {
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    #[builder(default)]
+    flags: ServiceFlags,
+    name: *const c_char,
+    kind: *const c_char,
+    /// The default domain if null
+    #[builder(default = "ptr::null()")]
+    domain: *const c_char,
+    /// The host name of the machine if null
+    #[builder(default = "ptr::null()")]
+    host: *const c_char,
+    port: u16,
+    #[builder(default)]
+    txt: Option<&'a ManagedAvahiStringList>,
+}
+
+/// Holds parameters for `ManagedAvahiSEntryGroup::add_subtype()`.
+#[derive(Builder, BuilderDelegate)]
+pub struct AddSubtypeParams {
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    name: *const c_char,
+    kind: *const c_char,
+    domain: *const c_char,
+    subtype: *const c_char,
+}
@@ -0,0 +1,163 @@
+//! Rust friendly `AvahiSServiceBrowser` and `AvahiSServiceResolver` wrappers/helpers
+
+use super::avahi_util;
+use super::core_sys::{
+    avahi_s_service_browser_free, avahi_s_service_browser_new, avahi_s_service_resolver_free,
+    avahi_s_service_resolver_new, AvahiSServiceBrowser, AvahiSServiceBrowserCallback,
+    AvahiSServiceResolver, AvahiSServiceResolverCallback,
+};
+use super::server::ManagedAvahiServer;
+use crate::{Result, ServiceFlags};
+use avahi_sys::{AvahiIfIndex, AvahiProtocol};
+use libc::{c_char, c_void};
+use std::collections::HashMap;
+
+/// Wraps the `AvahiSServiceBrowser` type from the raw `avahi-core` bindings.
+///
+/// This struct allocates a new `*mut AvahiSServiceBrowser` when
+/// `ManagedAvahiSServiceBrowser::new()` is invoked and calls the Avahi function responsible for
+/// freeing the browser on `trait Drop`.
+#[derive(Debug)]
+pub struct ManagedAvahiSServiceBrowser(*mut AvahiSServiceBrowser);
+
+impl ManagedAvahiSServiceBrowser {
+    /// Initializes the underlying `*mut AvahiSServiceBrowser` and verifies it was created;
+    /// returning `Err(String)` if unsuccessful.
+    pub fn new(
+        ManagedAvahiSServiceBrowserParams {
+            server,
+            interface,
+            protocol,
+            kind,
+            domain,
+            flags,
+            callback,
+            userdata,
+        }: ManagedAvahiSServiceBrowserParams,
+    ) -> Result<Self> {
+        let browser = unsafe {
+            avahi_s_service_browser_new(
+                server.inner(),
+                interface,
+                protocol,
+                kind,
+                domain,
+                avahi_util::lookup_flags(flags),
+                callback,
+                userdata,
+            )
+        };
+
+        if browser.is_null() {
+            Err("could not initialize Avahi service browser".into())
+        } else {
+            Ok(Self(browser))
+        }
+    }
+}
+
+impl Drop for ManagedAvahiSServiceBrowser {
+    fn drop(&mut self) {
+        unsafe { avahi_s_service_browser_free(self.0) };
+    }
+}
+
+/// Holds parameters for initializing a new `ManagedAvahiSServiceBrowser` with
+/// `ManagedAvahiSServiceBrowser::new()`.
+#[derive(Builder, BuilderDelegate)]
+pub struct ManagedAvahiSServiceBrowserParams<'a> {
+    server: &'a ManagedAvahiServer,
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    kind: *const c_char,
+    domain: *const c_char,
+    flags: ServiceFlags,
+    callback: AvahiSServiceBrowserCallback,
+    userdata: *mut c_void,
+}
+
+/// Wraps the `AvahiSServiceResolver` type from the raw `avahi-core` bindings.
+///
+/// This struct allocates a new `*mut AvahiSServiceResolver` when
+/// `ManagedAvahiSServiceResolver::new()` is invoked and calls the Avahi function responsible for
+/// freeing the resolver on `trait Drop`.
+#[derive(Debug)]
+pub struct ManagedAvahiSServiceResolver(*mut AvahiSServiceResolver);
+
+impl ManagedAvahiSServiceResolver {
+    /// Initializes the underlying `*mut AvahiSServiceResolver` and verifies it was created;
+    /// returning `Err(String)` if unsuccessful.
+    pub fn new(
+        ManagedAvahiSServiceResolverParams {
+            server,
+            interface,
+            protocol,
+            name,
+            kind,
+            domain,
+            aprotocol,
+            flags,
+            callback,
+            userdata,
+        }: ManagedAvahiSServiceResolverParams,
+    ) -> Result<Self> {
+        let resolver = unsafe {
+            avahi_s_service_resolver_new(
+                server.inner(),
+                interface,
+                protocol,
+                name,
+                kind,
+                domain,
+                aprotocol,
+                avahi_util::lookup_flags(flags),
+                callback,
+                userdata,
+            )
+        };
+
+        if resolver.is_null() {
+            Err("could not initialize AvahiSServiceResolver".into())
+        } else {
+            Ok(Self(resolver))
+        }
+    }
+}
+
+impl Drop for ManagedAvahiSServiceResolver {
+    fn drop(&mut self) {
+        unsafe { avahi_s_service_resolver_free(self.0) };
+    }
+}
+
+/// Holds parameters for initializing a new `ManagedAvahiSServiceResolver` with
+/// `ManagedAvahiSServiceResolver::new()`.
+#[derive(Builder, BuilderDelegate)]
+pub struct ManagedAvahiSServiceResolverParams<'a> {
+    server: &'a ManagedAvahiServer,
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    name: *const c_char,
+    kind: *const c_char,
+    domain: *const c_char,
+    aprotocol: AvahiProtocol,
+    flags: ServiceFlags,
+    callback: AvahiSServiceResolverCallback,
+    userdata: *mut c_void,
+}
+
+/// The resolvers of a browser that have not reported yet
+#[derive(Default, Debug)]
+pub(crate) struct ServiceResolverSet {
+    resolvers: HashMap<*mut AvahiSServiceResolver, ManagedAvahiSServiceResolver>,
+}
+
+impl ServiceResolverSet {
+    pub fn insert(&mut self, resolver: ManagedAvahiSServiceResolver) {
+        self.resolvers.insert(resolver.0, resolver);
+    }
+
+    pub fn remove_raw(&mut self, raw: *mut AvahiSServiceResolver) {
+        self.resolvers.remove(&raw);
+    }
+}
@@ -0,0 +1,36 @@
+//! Linux-specific ZeroConf bindings over an in-process Avahi server
+//!
+//! This module links against `libavahi-core` to run the mDNS stack of [Avahi] inside of this
+//! process instead of talking to `avahi-daemon`, which makes it usable on systems that do not run
+//! the daemon (e.g. appliances). It is enabled with the `avahi-core` feature instead of the
+//! `avahi` feature.
+//!
+//! All services and browsers of a thread share a single server, which is driven by any of their
+//! event loops. The server is stopped once the last of them has been dropped.
+//!
+//! # Note
+//! Only registering and browsing services is supported. The resolvers and record queries are only
+//! available with `libavahi-client`, the TTL of registered services cannot be changed and the
+//! priority and weight of discovered services are always `0`. `avahi-core` should not be used
+//! while `avahi-daemon` is running on the same host, as both would answer the same queries.
+//!
+//! [Avahi]: https://en.wikipedia.org/wiki/Avahi_(software)
+
+#[path = "../linux/avahi_util.rs"]
+pub mod avahi_util;
+#[path = "../linux/event_loop.rs"]
+pub mod event_loop;
+#[path = "../linux/poll.rs"]
+pub mod poll;
+#[path = "../linux/string_list.rs"]
+pub mod string_list;
+#[path = "../linux/txt_record.rs"]
+pub mod txt_record;
+
+pub mod browser;
+pub mod core_sys;
+pub mod entry_group;
+pub mod lookup;
+pub mod registration_handle;
+pub mod server;
+pub mod service;
@@ -0,0 +1,139 @@
+//! Rust friendly `AvahiServer` wrappers/helpers
+
+use super::avahi_util;
+use super::core_sys::{
+    avahi_server_config_free, avahi_server_config_init, avahi_server_free,
+    avahi_server_get_domain_name, avahi_server_get_host_name, avahi_server_get_state,
+    avahi_server_new, avahi_server_set_host_name, AvahiServer, AvahiServerConfig,
+};
+use super::poll::ManagedAvahiSimplePoll;
+use crate::error::Operation;
+use crate::ffi::c_str;
+use crate::Result;
+use avahi_sys::{avahi_alternative_host_name, avahi_free, avahi_simple_poll_get, AvahiServerState};
+use libc::{c_int, c_void};
+use std::cell::RefCell;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::rc::{Rc, Weak};
+use std::sync::Arc;
+
+thread_local! {
+    static SERVER: RefCell<Weak<ManagedAvahiServer>> = const { RefCell::new(Weak::new()) };
+}
+
+/// Wraps the `AvahiServer` type from the raw `avahi-core` bindings.
+///
+/// A single server is shared by all services and browsers of a thread, as any additional server
+/// would announce (and conflict with) the same host name. The server is started by
+/// `ManagedAvahiServer::get()` and freed once the last `Rc` has been dropped.
+#[derive(Debug)]
+pub struct ManagedAvahiServer {
+    server: *mut AvahiServer,
+    poll: Arc<ManagedAvahiSimplePoll>,
+}
+
+impl ManagedAvahiServer {
+    /// Returns the server of the current thread, starting it if it is not running; returning
+    /// `Err(String)` if unsuccessful.
+    pub fn get() -> Result<Rc<Self>> {
+        SERVER.with(|current| {
+            if let Some(server) = current.borrow().upgrade() {
+                return Ok(server);
+            }
+
+            let server = Rc::new(Self::new()?);
+            *current.borrow_mut() = Rc::downgrade(&server);
+
+            Ok(server)
+        })
+    }
+
+    fn new() -> Result<Self> {
+        let poll = ManagedAvahiSimplePoll::new_shared()?;
+
+        let mut err: c_int = 0;
+
+        let server = unsafe {
+            let mut config = MaybeUninit::<AvahiServerConfig>::uninit();
+            avahi_server_config_init(config.as_mut_ptr());
+
+            // the server keeps a copy of the config
+            let server = avahi_server_new(
+                avahi_simple_poll_get(poll.inner()),
+                config.as_ptr(),
+                Some(server_callback),
+                ptr::null_mut(),
+                &mut err,
+            );
+
+            avahi_server_config_free(config.as_mut_ptr());
+
+            server
+        };
+
+        if server.is_null() {
+            Err(avahi_util::error(
+                err,
+                format!(
+                    "could not initialize AvahiServer: {}",
+                    avahi_util::get_error(err)
+                ),
+            )
+            .with_operation(Operation::Connect))
+        } else {
+            Ok(Self { server, poll })
+        }
+    }
+
+    /// Returns the poll driving the server.
+    pub fn poll(&self) -> &Arc<ManagedAvahiSimplePoll> {
+        &self.poll
+    }
+
+    /// Delegate function for `avahi_server_get_state()`.
+    pub fn state(&self) -> AvahiServerState {
+        unsafe { avahi_server_get_state(self.server) }
+    }
+
+    /// Delegate function for `avahi_server_get_host_name()`.
+    pub fn host_name(&self) -> String {
+        unsafe { c_str::copy_raw(avahi_server_get_host_name(self.server)) }
+    }
+
+    /// Delegate function for `avahi_server_get_domain_name()`.
+    pub fn domain_name(&self) -> String {
+        unsafe { c_str::copy_raw(avahi_server_get_domain_name(self.server)) }
+    }
+
+    pub(super) fn inner(&self) -> *mut AvahiServer {
+        self.server
+    }
+}
+
+impl Drop for ManagedAvahiServer {
+    fn drop(&mut self) {
+        unsafe { avahi_server_free(self.server) };
+    }
+}
+
+unsafe extern "C" fn server_callback(
+    server: *mut AvahiServer,
+    state: AvahiServerState,
+    _userdata: *mut c_void,
+) {
+    if state != avahi_sys::AvahiServerState_AVAHI_SERVER_COLLISION {
+        return;
+    }
+
+    // avahi-daemon picks the next alternative host name as well
+    let alternative = avahi_alternative_host_name(avahi_server_get_host_name(server));
+
+    warn!(
+        "Host name conflict, renaming host to `{}`",
+        c_str::raw_to_str(alternative)
+    );
+
+    avahi_server_set_host_name(server, alternative);
+    avahi_free(alternative as *mut c_void);
+}
@@ -0,0 +1,417 @@
+//! avahi-core implementation for cross-platform service.
+
+use super::avahi_util;
+use super::core_sys::{AvahiSEntryGroup, AvahiServer};
+use super::entry_group::{
+    AddServiceParams, AddSubtypeParams, ManagedAvahiSEntryGroup, ManagedAvahiSEntryGroupParams,
+};
+use super::registration_handle::AvahiCoreRegistrationHandle;
+use super::server::ManagedAvahiServer;
+use crate::error::{Error, ErrorKind};
+use crate::ffi::{c_str, AsRaw, FromRaw, SharedContext, UnwrapOrNull};
+use crate::network_watcher::NetworkWatcher;
+use crate::prelude::*;
+use crate::service;
+use crate::{
+    DaemonReconnectedCallback, ErrorCallback, EventLoop, NameConflictCallback, NetworkInterface,
+    RegistrationHandle, Result, ServiceFlags, ServiceRegisteredCallback, ServiceRegistration,
+    ServiceType, TxtRecord,
+};
+use avahi_sys::{AvahiEntryGroupState, AvahiIfIndex, AvahiServerState};
+use libc::c_void;
+use std::any::Any;
+use std::ffi::CString;
+use std::fmt::{self, Formatter};
+use std::rc::Rc;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub struct AvahiCoreMdnsService {
+    context: SharedContext<AvahiCoreServiceContext>,
+}
+
+// SAFETY: the service exclusively owns its context and the Avahi objects, which are only used
+// through `&mut self` or the event loop borrowing it, and the callbacks and the context of
+// the user it holds are `Send`.
+unsafe impl Send for AvahiCoreMdnsService {}
+
+impl TMdnsService for AvahiCoreMdnsService {
+    fn new(service_type: ServiceType, port: u16) -> Self {
+        Self {
+            context: SharedContext::new(AvahiCoreServiceContext::new(service_type, port)),
+        }
+    }
+
+    /// Sets the name to register this service under. If no name is set, the host name of the
+    /// server will be used instead.
+    fn set_name(&mut self, name: &str) {
+        unsafe { (*self.context.as_ptr()).name = Some(c_string!(name)) };
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        unsafe {
+            (*self.context.as_ptr()).interface_index = avahi_util::interface_index(interface)
+        };
+    }
+
+    fn set_domain(&mut self, domain: &str) {
+        unsafe { (*self.context.as_ptr()).domain = Some(c_string!(domain)) };
+    }
+
+    fn set_host(&mut self, host: &str) {
+        unsafe { (*self.context.as_ptr()).host = Some(c_string!(host)) };
+    }
+
+    fn set_txt_record(&mut self, txt_record: TxtRecord) {
+        unsafe { (*self.context.as_ptr()).txt_record = Some(txt_record) };
+    }
+
+    fn set_registered_callback(&mut self, registered_callback: Box<ServiceRegisteredCallback>) {
+        unsafe { (*self.context.as_ptr()).registered_callback = Some(registered_callback) };
+    }
+
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>) {
+        unsafe { (*self.context.as_ptr()).error_callback = Some(error_callback) };
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        unsafe { (*self.context.as_ptr()).user_context = Some(Arc::from(context)) };
+    }
+
+    fn set_flags(&mut self, flags: ServiceFlags) {
+        unsafe { (*self.context.as_ptr()).flags = flags };
+    }
+
+    /// `avahi-core` does not support setting the TTL of a service, it is ignored.
+    fn set_ttl(&mut self, _ttl: u32) {}
+
+    fn set_auto_reregister(&mut self, auto_reregister: bool) {
+        unsafe { (*self.context.as_ptr()).auto_reregister = auto_reregister };
+    }
+
+    /// The server runs in this process and never needs to be reconnected to, so the callback is
+    /// never invoked.
+    fn set_reconnected_callback(&mut self, _reconnected_callback: Box<DaemonReconnectedCallback>) {}
+
+    fn set_name_conflict_callback(&mut self, name_conflict_callback: Box<NameConflictCallback>) {
+        unsafe { (*self.context.as_ptr()).name_conflict_callback = Some(name_conflict_callback) };
+    }
+
+    fn register(&mut self) -> Result<RegistrationHandle<'_>> {
+        let _span = operation_span!(
+            "register",
+            service_type = unsafe { (*self.context.as_ptr()).kind.to_string_lossy() }
+        );
+
+        debug!("Registering service: {:?}", self);
+
+        service::check_port(unsafe { (*self.context.as_ptr()).port })?;
+
+        let server = ManagedAvahiServer::get()?;
+        let poll = server.poll().clone();
+
+        unsafe {
+            (*self.context.as_ptr()).server = Some(server);
+
+            if (*self.context.as_ptr()).auto_reregister {
+                (*self.context.as_ptr()).network_watcher = Some(NetworkWatcher::new());
+            }
+        }
+
+        let service: *mut Self = self;
+
+        // the service is added from the event loop once the server is running
+        let event_loop = EventLoop::new(poll)
+            .with_on_poll(Box::new(move || unsafe { (*service).handle_poll() }));
+
+        Ok(AvahiCoreRegistrationHandle::new(service, event_loop))
+    }
+}
+
+impl AvahiCoreMdnsService {
+    pub(crate) fn set_port(&mut self, port: u16) {
+        unsafe { (*self.context.as_ptr()).port = port };
+    }
+
+    /// Re-registers the service with its current configuration. If the server is not running,
+    /// the service is registered once it is.
+    pub(crate) fn reregister(&mut self) -> Result<()> {
+        let context = unsafe { &mut *self.context.as_ptr() };
+
+        if context.server_state != avahi_sys::AvahiServerState_AVAHI_SERVER_RUNNING {
+            return Ok(());
+        }
+
+        if let Some(g) = &mut context.group {
+            g.reset();
+        }
+
+        unsafe { create_service(context) }
+    }
+
+    unsafe fn handle_poll(&mut self) {
+        let context = &mut *self.context.as_ptr();
+
+        let state = context.server.as_ref().unwrap().state();
+
+        if state != context.server_state {
+            context.server_state = state;
+            handle_server_state(context, state);
+            return;
+        }
+
+        let changed = match &mut context.network_watcher {
+            Some(w) => w.poll_changed(),
+            None => false,
+        };
+
+        if changed {
+            debug!("Network configuration changed, re-registering service");
+
+            if let Err(e) = self.reregister() {
+                context.invoke_callback(Err(e));
+            }
+        }
+    }
+}
+
+#[derive(FromRaw, AsRaw)]
+struct AvahiCoreServiceContext {
+    name: Option<CString>,
+    service_type: ServiceType,
+    kind: CString,
+    sub_types: Vec<CString>,
+    port: u16,
+    group: Option<ManagedAvahiSEntryGroup>,
+    txt_record: Option<TxtRecord>,
+    interface_index: AvahiIfIndex,
+    domain: Option<CString>,
+    host: Option<CString>,
+    registered_callback: Option<Box<ServiceRegisteredCallback>>,
+    error_callback: Option<Box<ErrorCallback>>,
+    name_conflict_callback: Option<Box<NameConflictCallback>>,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+    flags: ServiceFlags,
+    auto_reregister: bool,
+    network_watcher: Option<NetworkWatcher>,
+    server_state: AvahiServerState,
+    // the group must be freed before the server, fields are dropped in order
+    server: Option<Rc<ManagedAvahiServer>>,
+}
+
+impl AvahiCoreServiceContext {
+    fn new(service_type: ServiceType, port: u16) -> Self {
+        let kind = format!("_{}._{}", service_type.name(), service_type.protocol());
+
+        let sub_types = service_type
+            .sub_types()
+            .iter()
+            .map(|s| c_string!(format!("_{}._sub.{}", s, kind)))
+            .collect();
+
+        Self {
+            name: None,
+            service_type,
+            kind: c_string!(kind),
+            sub_types,
+            port,
+            group: None,
+            txt_record: None,
+            interface_index: avahi_sys::AVAHI_IF_UNSPEC,
+            domain: None,
+            host: None,
+            registered_callback: None,
+            error_callback: None,
+            name_conflict_callback: None,
+            user_context: None,
+            flags: ServiceFlags::empty(),
+            auto_reregister: false,
+            network_watcher: None,
+            server_state: avahi_sys::AvahiServerState_AVAHI_SERVER_INVALID,
+            server: None,
+        }
+    }
+
+    fn server(&self) -> &ManagedAvahiServer {
+        self.server.as_ref().unwrap()
+    }
+
+    /// Returns the configured domain, or the default domain of the server if none was set
+    unsafe fn domain(&self) -> String {
+        match &self.domain {
+            Some(d) => c_str::copy_raw(d.as_ptr()),
+            None => self.server().domain_name(),
+        }
+    }
+
+    fn invoke_callback(&self, result: Result<ServiceRegistration>) {
+        match (result, &self.error_callback, &self.registered_callback) {
+            (Err(e), Some(f), _) => f(e, self.user_context.clone()),
+            (result, _, Some(f)) => f(result, self.user_context.clone()),
+            _ => warn!("attempted to invoke service callback but none was set"),
+        }
+    }
+}
+
+impl fmt::Debug for AvahiCoreServiceContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AvahiCoreServiceContext")
+            .field("name", &self.name)
+            .field("kind", &self.kind)
+            .field("port", &self.port)
+            .field("group", &self.group)
+            .finish()
+    }
+}
+
+/// Like `avahi-daemon`, the services are withdrawn while the server registers its host name
+/// (e.g. after a host name conflict) and added again once it is running.
+unsafe fn handle_server_state(context: &mut AvahiCoreServiceContext, state: AvahiServerState) {
+    match state {
+        avahi_sys::AvahiServerState_AVAHI_SERVER_RUNNING => {
+            if let Err(e) = create_service(context) {
+                context.invoke_callback(Err(e));
+            }
+        }
+        avahi_sys::AvahiServerState_AVAHI_SERVER_REGISTERING
+        | avahi_sys::AvahiServerState_AVAHI_SERVER_COLLISION => {
+            if let Some(g) = &mut context.group {
+                debug!("Group reset");
+                g.reset();
+            }
+        }
+        avahi_sys::AvahiServerState_AVAHI_SERVER_FAILURE => {
+            context.invoke_callback(Err("server failure".into()))
+        }
+        _ => {}
+    };
+}
+
+unsafe fn create_service(context: &mut AvahiCoreServiceContext) -> Result<()> {
+    if context.name.is_none() {
+        context.name = Some(c_string!(context.server().host_name()));
+    }
+
+    if context.group.is_none() {
+        debug!("Creating group");
+
+        context.group = Some(ManagedAvahiSEntryGroup::new(
+            ManagedAvahiSEntryGroupParams::builder()
+                .server(context.server().inner())
+                .callback(Some(entry_group_callback))
+                .userdata(context.as_raw())
+                .build()?,
+        )?);
+    }
+
+    let group = context.group.as_mut().unwrap();
+
+    if !group.is_empty() {
+        return Ok(());
+    }
+
+    debug!("Adding service");
+
+    let name = context.name.as_ref().unwrap().as_ptr();
+    let domain = context.domain.as_ref().map(|d| d.as_ptr()).unwrap_or_null();
+
+    group.add_service(
+        AddServiceParams::builder()
+            .interface(context.interface_index)
+            .protocol(avahi_sys::AVAHI_PROTO_UNSPEC)
+            .flags(context.flags)
+            .name(name)
+            .kind(context.kind.as_ptr())
+            .domain(domain)
+            .host(context.host.as_ref().map(|h| h.as_ptr()).unwrap_or_null())
+            .port(context.port)
+            .txt(context.txt_record.as_ref().map(|t| t.inner()))
+            .build()?,
+    )?;
+
+    for sub_type in &context.sub_types {
+        group.add_subtype(
+            AddSubtypeParams::builder()
+                .interface(context.interface_index)
+                .protocol(avahi_sys::AVAHI_PROTO_UNSPEC)
+                .name(name)
+                .kind(context.kind.as_ptr())
+                .domain(domain)
+                .subtype(sub_type.as_ptr())
+                .build()?,
+        )?;
+    }
+
+    group.commit()
+}
+
+unsafe extern "C" fn entry_group_callback(
+    _server: *mut AvahiServer,
+    _group: *mut AvahiSEntryGroup,
+    state: AvahiEntryGroupState,
+    userdata: *mut c_void,
+) {
+    let context = AvahiCoreServiceContext::from_raw(userdata);
+
+    let result = match state {
+        avahi_sys::AvahiEntryGroupState_AVAHI_ENTRY_GROUP_ESTABLISHED => {
+            handle_group_established(context)
+        }
+        avahi_sys::AvahiEntryGroupState_AVAHI_ENTRY_GROUP_COLLISION => {
+            handle_group_collision(context)
+        }
+        avahi_sys::AvahiEntryGroupState_AVAHI_ENTRY_GROUP_FAILURE => {
+            Err("could not register service".into())
+        }
+        _ => Ok(()),
+    };
+
+    if let Err(e) = result {
+        context.invoke_callback(Err(e));
+    }
+}
+
+unsafe fn handle_group_collision(context: &mut AvahiCoreServiceContext) -> Result<()> {
+    let name = c_str::copy_raw(context.name.as_ref().unwrap().as_ptr());
+
+    debug!("Group collision: {}", name);
+
+    let candidate = match &context.name_conflict_callback {
+        Some(f) => f(&name, context.user_context.clone()).map(|c| c_string!(c)),
+        None if !context.flags.contains(ServiceFlags::NO_AUTO_RENAME) => Some(
+            avahi_util::alternative_service_name(context.name.as_ref().unwrap()),
+        ),
+        None => None,
+    };
+
+    let candidate = match candidate {
+        Some(c) => c,
+        None => {
+            return Err(Error::with_kind(
+                ErrorKind::NameConflict,
+                format!("service name `{}` conflicts with another service", name),
+            ))
+        }
+    };
+
+    debug!("Renaming service to: {}", candidate.to_string_lossy());
+
+    context.name = Some(candidate);
+    context.group.as_mut().unwrap().reset();
+
+    create_service(context)
+}
+
+unsafe fn handle_group_established(context: &AvahiCoreServiceContext) -> Result<()> {
+    debug!("Group established");
+
+    let result = ServiceRegistration::builder()
+        .name(c_str::copy_raw(context.name.as_ref().unwrap().as_ptr()))
+        .service_type(context.service_type.clone())
+        .domain(context.domain())
+        .build()?;
+
+    context.invoke_callback(Ok(result));
+
+    Ok(())
+}
@@ -0,0 +1,256 @@
+//! Utilities related to Avahi's D-Bus API
+
+use crate::error::{Error, ErrorKind};
+use crate::txt_record;
+use crate::{AddressFamily, NetworkInterface, ServiceFlags};
+use std::borrow::Cow;
+use std::net::IpAddr;
+
+/// The well-known bus name of `avahi-daemon`
+pub const AVAHI_SERVICE: &str = "org.freedesktop.Avahi";
+
+pub const SERVER_INTERFACE: &str = "org.freedesktop.Avahi.Server";
+pub const ENTRY_GROUP_INTERFACE: &str = "org.freedesktop.Avahi.EntryGroup";
+pub const SERVICE_BROWSER_INTERFACE: &str = "org.freedesktop.Avahi.ServiceBrowser";
+pub const SERVICE_RESOLVER_INTERFACE: &str = "org.freedesktop.Avahi.ServiceResolver";
+
+/// `AVAHI_IF_UNSPEC`
+pub const IF_UNSPEC: i32 = -1;
+/// `AVAHI_PROTO_UNSPEC`
+pub const PROTO_UNSPEC: i32 = -1;
+/// `AVAHI_PROTO_INET`
+pub const PROTO_INET: i32 = 0;
+/// `AVAHI_PROTO_INET6`
+pub const PROTO_INET6: i32 = 1;
+
+/// `AVAHI_ENTRY_GROUP_ESTABLISHED`
+pub const ENTRY_GROUP_ESTABLISHED: i32 = 2;
+/// `AVAHI_ENTRY_GROUP_COLLISION`
+pub const ENTRY_GROUP_COLLISION: i32 = 3;
+/// `AVAHI_ENTRY_GROUP_FAILURE`
+pub const ENTRY_GROUP_FAILURE: i32 = 4;
+
+/// `AVAHI_PUBLISH_UPDATE`
+pub const PUBLISH_UPDATE: u32 = 64;
+const PUBLISH_USE_MULTICAST: u32 = 256;
+
+const LOOKUP_USE_WIDE_AREA: u32 = 1;
+const LOOKUP_USE_MULTICAST: u32 = 2;
+const LOOKUP_NO_ADDRESS: u32 = 8;
+
+/// The D-Bus names of the Avahi errors and their `AVAHI_ERR_*` codes
+const ERRORS: &[(&str, i32)] = &[
+    ("org.freedesktop.Avahi.Failure", -1),
+    ("org.freedesktop.Avahi.BadStateError", -2),
+    ("org.freedesktop.Avahi.InvalidHostNameError", -3),
+    ("org.freedesktop.Avahi.InvalidDomainNameError", -4),
+    ("org.freedesktop.Avahi.NoNetworkError", -5),
+    ("org.freedesktop.Avahi.InvalidTTLError", -6),
+    ("org.freedesktop.Avahi.IsPatternError", -7),
+    ("org.freedesktop.Avahi.CollisionError", -8),
+    ("org.freedesktop.Avahi.InvalidRecordError", -9),
+    ("org.freedesktop.Avahi.InvalidServiceNameError", -10),
+    ("org.freedesktop.Avahi.InvalidServiceTypeError", -11),
+    ("org.freedesktop.Avahi.InvalidPortError", -12),
+    ("org.freedesktop.Avahi.InvalidKeyError", -13),
+    ("org.freedesktop.Avahi.InvalidAddressError", -14),
+    ("org.freedesktop.Avahi.TimeoutError", -15),
+    ("org.freedesktop.Avahi.TooManyClientsError", -16),
+    ("org.freedesktop.Avahi.TooManyObjectsError", -17),
+    ("org.freedesktop.Avahi.TooManyEntriesError", -18),
+    ("org.freedesktop.Avahi.OSError", -19),
+    ("org.freedesktop.Avahi.AccessDeniedError", -20),
+    ("org.freedesktop.Avahi.InvalidOperationError", -21),
+    ("org.freedesktop.Avahi.DBusError", -22),
+    ("org.freedesktop.Avahi.DisconnectedError", -23),
+    ("org.freedesktop.Avahi.NoMemoryError", -24),
+    ("org.freedesktop.Avahi.InvalidObjectError", -25),
+    ("org.freedesktop.Avahi.NoDaemonError", -26),
+    ("org.freedesktop.Avahi.InvalidInterfaceError", -27),
+    ("org.freedesktop.Avahi.InvalidProtocolError", -28),
+    ("org.freedesktop.Avahi.InvalidFlagsError", -29),
+    ("org.freedesktop.Avahi.NotFoundError", -30),
+    ("org.freedesktop.Avahi.InvalidConfigError", -31),
+    ("org.freedesktop.Avahi.VersionMismatchError", -32),
+    ("org.freedesktop.Avahi.InvalidServiceSubtypeError", -33),
+    ("org.freedesktop.Avahi.InvalidPacketError", -34),
+    ("org.freedesktop.Avahi.InvalidDnsError", -35),
+    ("org.freedesktop.Avahi.NotSupportedError", -49),
+    ("org.freedesktop.Avahi.NotPermittedError", -50),
+    ("org.freedesktop.Avahi.InvalidArgumentError", -51),
+    ("org.freedesktop.Avahi.IsEmptyError", -52),
+    ("org.freedesktop.Avahi.NoChangeError", -53),
+];
+
+/// Returns an [`Error`] with the specified `description` of the Avahi error `name` (e.g.
+/// `org.freedesktop.Avahi.CollisionError`), as reported by failed calls and by the `Failure` and
+/// `StateChanged` signals.
+///
+/// The error has the `AVAHI_ERR_*` code of `name` and is of the same kind as the errors of the
+/// `avahi` backend, see `linux::avahi_util::error()`.
+///
+/// [`Error`]: ../../error/struct.Error.html
+pub fn error(name: &str, description: String) -> Error {
+    let code = match ERRORS.iter().find(|(n, _)| *n == name) {
+        Some((_, code)) => *code,
+        None => return Error::with_kind(ErrorKind::Other, description),
+    };
+
+    let kind = match code {
+        -8 => ErrorKind::NameConflict,
+        -15 => ErrorKind::Timeout,
+        -23 | -26 => ErrorKind::DaemonNotRunning,
+        -3 | -4 | -6 | -14..=-9 | -29..=-27 | -33 | -51 => ErrorKind::InvalidArgument,
+        _ => ErrorKind::Other,
+    };
+
+    Error::with_kind(kind, description).with_code(code)
+}
+
+/// Converts the specified [`NetworkInterface`] to the Avahi expected value.
+///
+/// [`NetworkInterface`]: ../../enum.NetworkInterface.html
+pub fn interface_index(interface: NetworkInterface) -> i32 {
+    match interface {
+        NetworkInterface::Unspec | NetworkInterface::PeerToPeer => IF_UNSPEC,
+        NetworkInterface::AtIndex(i) => i as i32,
+    }
+}
+
+/// Converts the specified [`AddressFamily`] to the Avahi expected value.
+///
+/// [`AddressFamily`]: ../../enum.AddressFamily.html
+pub fn protocol(address_family: AddressFamily) -> i32 {
+    match address_family {
+        AddressFamily::Ipv4 => PROTO_INET,
+        AddressFamily::Ipv6 => PROTO_INET6,
+        AddressFamily::Both => PROTO_UNSPEC,
+    }
+}
+
+/// Converts the specified [`ServiceFlags`] to the Avahi expected value for publishing services
+/// and updating their records.
+///
+/// `ServiceFlags::UNIQUE` is left out, Avahi rejects it for services.
+///
+/// [`ServiceFlags`]: ../../flags/struct.ServiceFlags.html
+pub fn publish_flags(flags: ServiceFlags) -> u32 {
+    let mut publish_flags = 0;
+
+    if flags.contains(ServiceFlags::FORCE_MULTICAST) {
+        publish_flags |= PUBLISH_USE_MULTICAST;
+    }
+
+    publish_flags
+}
+
+/// Converts the specified [`ServiceFlags`] to the Avahi expected value for browsing and
+/// resolving services.
+///
+/// [`ServiceFlags`]: ../../flags/struct.ServiceFlags.html
+pub fn lookup_flags(flags: ServiceFlags) -> u32 {
+    let mut lookup_flags = 0;
+
+    if flags.contains(ServiceFlags::FORCE_MULTICAST) {
+        lookup_flags |= LOOKUP_USE_MULTICAST;
+    }
+
+    if flags.contains(ServiceFlags::WIDE_AREA) {
+        lookup_flags |= LOOKUP_USE_WIDE_AREA;
+    }
+
+    lookup_flags
+}
+
+/// Converts the specified [`ServiceFlags`] to the Avahi expected value for resolving services,
+/// which unlike browsing may skip looking up addresses (`ServiceFlags::NO_ADDRESS`), see
+/// `linux::avahi_util::resolve_flags()`.
+///
+/// [`ServiceFlags`]: ../../flags/struct.ServiceFlags.html
+pub fn resolve_flags(flags: ServiceFlags) -> u32 {
+    let mut resolve_flags = lookup_flags(flags);
+
+    if flags.contains(ServiceFlags::NO_ADDRESS) {
+        resolve_flags |= LOOKUP_NO_ADDRESS;
+    }
+
+    resolve_flags
+}
+
+/// Encodes the specified `key=value` entries as the array of byte strings expected by Avahi.
+pub fn txt_to_bytes(entries: impl IntoIterator<Item = (String, String)>) -> Vec<Vec<u8>> {
+    entries
+        .into_iter()
+        .map(|(key, value)| format!("{}={}", key, value).into_bytes())
+        .collect()
+}
+
+/// Decodes the specified array of byte strings reported by Avahi into `key=value` entries.
+/// Entries without a `'='` have an empty value, empty entries and entries without a key are
+/// skipped. The entries borrow from `txt` unless they are not valid UTF-8.
+pub fn txt_from_bytes(txt: &[Vec<u8>]) -> impl Iterator<Item = (Cow<'_, str>, Cow<'_, str>)> {
+    txt.iter().filter_map(|e| txt_record::parse_entry(e))
+}
+
+/// Parses the textual address reported by Avahi. Link-local IPv6 addresses may include the zone
+/// (e.g. `fe80::1%eth0`), which is stripped.
+pub fn parse_address(address: &str) -> Option<IpAddr> {
+    address.split('%').next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn publish_flags_ignores_unsupported_flags() {
+        let flags =
+            ServiceFlags::FORCE_MULTICAST | ServiceFlags::NO_AUTO_RENAME | ServiceFlags::UNIQUE;
+        assert_eq!(publish_flags(flags), PUBLISH_USE_MULTICAST);
+    }
+
+    #[test]
+    fn error_maps_avahi_error_names() {
+        let conflict = error(
+            "org.freedesktop.Avahi.CollisionError",
+            "name taken".to_string(),
+        );
+        assert_eq!(conflict.kind(), ErrorKind::NameConflict);
+        assert_eq!(conflict.code(), Some(-8));
+
+        let invalid = error("org.freedesktop.Avahi.InvalidPortError", String::new());
+        assert_eq!(invalid.kind(), ErrorKind::InvalidArgument);
+        assert_eq!(invalid.code(), Some(-12));
+
+        let unknown = error("org.example.Unknown", String::new());
+        assert_eq!(unknown.kind(), ErrorKind::Other);
+        assert_eq!(unknown.code(), None);
+    }
+
+    #[test]
+    fn txt_round_trip() {
+        let entries = vec![
+            ("foo".to_string(), "bar".to_string()),
+            ("baz".to_string(), "".to_string()),
+        ];
+
+        let txt = txt_to_bytes(entries.clone());
+        let decoded: Vec<(String, String)> = txt_from_bytes(&txt)
+            .map(|(k, v)| (k.into_owned(), v.into_owned()))
+            .collect();
+
+        assert_eq!(decoded, entries);
+    }
+
+    #[test]
+    fn parse_address_strips_zone() {
+        assert_eq!(
+            parse_address("fe80::1%eth0"),
+            Some("fe80::1".parse().unwrap())
+        );
+        assert_eq!(
+            parse_address("192.168.1.2"),
+            Some("192.168.1.2".parse().unwrap())
+        );
+    }
+}
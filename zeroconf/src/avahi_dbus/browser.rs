@@ -0,0 +1,446 @@
+//! Avahi D-Bus implementation for cross-platform browser
+
+use super::avahi_dbus_util::{
+    self, SERVER_INTERFACE, SERVICE_BROWSER_INTERFACE, SERVICE_RESOLVER_INTERFACE,
+};
+use super::client::{self, AvahiDbusClient};
+use crate::error::{Error, ErrorKind, Operation};
+use crate::prelude::*;
+use crate::resolve_queue::ResolveQueue;
+use crate::txt_record;
+use crate::{
+    ffi, AddressFamily, AddressPolicy, BrowserControl, DaemonReconnectedCallback, ErrorCallback,
+    EventLoop, NetworkInterface, Result, ServiceType,
+};
+use crate::{ServiceDiscoveredCallback, ServiceDiscovery, ServiceFlags};
+use std::any::Any;
+use std::fmt::{self, Formatter};
+use std::mem;
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+use std::time::Duration;
+use zbus::zvariant::OwnedObjectPath;
+use zbus::Message;
+
+/// The body of the `ItemNew` signal of a service browser
+type ItemNew = (i32, i32, String, String, String, u32);
+
+/// The body of the `Found` signal of a service resolver
+type Found = (
+    i32,
+    i32,
+    String,
+    String,
+    String,
+    String,
+    i32,
+    String,
+    u16,
+    Vec<Vec<u8>>,
+    u32,
+);
+
+pub struct AvahiDbusMdnsBrowser {
+    client: Option<AvahiDbusClient>,
+    /// The object path of the service browser, once created
+    browser: Option<String>,
+    /// The object paths of the service resolvers that have not reported yet
+    resolvers: Vec<String>,
+    /// The discovered services that wait for a resolver to report
+    resolve_queue: ResolveQueue<ItemNew>,
+    service_type: ServiceType,
+    interface_index: i32,
+    domain: Option<String>,
+    flags: ServiceFlags,
+    address_family: AddressFamily,
+    service_discovered_callback: Option<Box<ServiceDiscoveredCallback>>,
+    error_callback: Option<Box<ErrorCallback>>,
+    control: BrowserControl,
+    reconnected_callback: Option<Box<DaemonReconnectedCallback>>,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl TMdnsBrowser for AvahiDbusMdnsBrowser {
+    fn new(service_type: ServiceType) -> Self {
+        Self {
+            client: None,
+            browser: None,
+            resolvers: vec![],
+            resolve_queue: ResolveQueue::default(),
+            service_type,
+            interface_index: avahi_dbus_util::IF_UNSPEC,
+            domain: None,
+            flags: ServiceFlags::empty(),
+            address_family: AddressFamily::default(),
+            service_discovered_callback: None,
+            error_callback: None,
+            control: BrowserControl::default(),
+            reconnected_callback: None,
+            user_context: None,
+        }
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface_index = avahi_dbus_util::interface_index(interface);
+    }
+
+    fn set_domain(&mut self, domain: &str) {
+        self.domain = Some(domain.to_string());
+    }
+
+    fn set_flags(&mut self, flags: ServiceFlags) {
+        self.flags = flags;
+    }
+
+    fn set_address_family(&mut self, address_family: AddressFamily) {
+        self.address_family = address_family;
+    }
+
+    fn set_address_policy(&mut self, _address_policy: AddressPolicy) {
+        // Avahi only resolves a single address per service
+    }
+
+    /// Raw records are not available over D-Bus, `ServiceDiscovery::raw_records()` is always
+    /// empty.
+    fn set_include_raw_records(&mut self, _include_raw_records: bool) {}
+
+    fn set_resolve_concurrency(&mut self, concurrency: usize) {
+        self.resolve_queue.set_concurrency(concurrency);
+    }
+
+    fn set_debounce(&mut self, _window: Duration) {
+        // services that have gone away are not tracked over D-Bus
+    }
+
+    fn set_service_discovered_callback(
+        &mut self,
+        service_discovered_callback: Box<ServiceDiscoveredCallback>,
+    ) {
+        self.service_discovered_callback = Some(service_discovered_callback);
+    }
+
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>) {
+        self.error_callback = Some(error_callback);
+    }
+
+    fn control(&self) -> BrowserControl {
+        self.control.clone()
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        self.user_context = Some(Arc::from(context));
+    }
+
+    fn set_reconnected_callback(&mut self, reconnected_callback: Box<DaemonReconnectedCallback>) {
+        self.reconnected_callback = Some(reconnected_callback);
+    }
+
+    fn browse_services(&mut self) -> Result<EventLoop<'_>> {
+        let _span = operation_span!("browse", service_type = self.service_type.to_string());
+
+        debug!("Browsing services: {:?}", self);
+
+        let client = AvahiDbusClient::new()?;
+
+        // subscribe before creating the browser so that none of its signals are missed
+        let signals = client.subscribe();
+
+        self.client = Some(client);
+        self.create_browser()?;
+
+        let browser: *mut Self = self;
+
+        Ok(EventLoop::new(
+            signals,
+            Box::new(move |signal| unsafe { (*browser).handle_signal(signal) }),
+        ))
+    }
+
+    fn close(mut self) -> Result<()> {
+        let result = self.release();
+        self.client = None;
+        result
+    }
+}
+
+impl AvahiDbusMdnsBrowser {
+    fn kind(&self) -> String {
+        format!(
+            "_{}._{}",
+            self.service_type.name(),
+            self.service_type.protocol()
+        )
+    }
+
+    /// Returns the service type to browse, which is the first sub-type if any.
+    fn browse_kind(&self) -> String {
+        match self.service_type.sub_types().first() {
+            Some(sub_type) => format!("_{}._sub.{}", sub_type, self.kind()),
+            None => self.kind(),
+        }
+    }
+
+    fn client(&self) -> Result<&AvahiDbusClient> {
+        self.client
+            .as_ref()
+            .ok_or_else(|| "browser has not been started".into())
+    }
+
+    fn create_browser(&mut self) -> Result<()> {
+        let browser: OwnedObjectPath = self.client()?.call(
+            "/",
+            SERVER_INTERFACE,
+            "ServiceBrowserNew",
+            &(
+                self.interface_index,
+                avahi_dbus_util::PROTO_UNSPEC,
+                self.browse_kind(),
+                self.domain.clone().unwrap_or_default(),
+                avahi_dbus_util::lookup_flags(self.flags),
+            ),
+        )?;
+
+        self.browser = Some(browser.as_str().to_string());
+
+        Ok(())
+    }
+
+    fn handle_signal(&mut self, signal: &Message) {
+        if client::is_daemon_started(signal) {
+            self.handle_reconnect();
+            return;
+        }
+
+        let result = if self.is_browser_signal(signal, "ItemNew") {
+            match signal.body::<ItemNew>() {
+                Ok(item) => {
+                    if let Some(item) = self.resolve_queue.push(item) {
+                        self.resolve_queued(item);
+                    }
+                    Ok(())
+                }
+                Err(e) => Err(Error::with_kind(
+                    ErrorKind::Other,
+                    format!("unexpected ItemNew signal: {}", e),
+                )
+                .with_operation(Operation::Browse)),
+            }
+        } else if self.is_browser_signal(signal, "Failure") {
+            let error = signal.body::<(String,)>().map(|b| b.0).unwrap_or_default();
+            Err(
+                avahi_dbus_util::error(&error, format!("browser failure: {}", error))
+                    .with_operation(Operation::Browse),
+            )
+        } else {
+            self.handle_resolver_signal(signal)
+        };
+
+        if let Err(e) = result {
+            self.invoke_callback(Err(e));
+        }
+    }
+
+    fn is_browser_signal(&self, signal: &Message, member: &str) -> bool {
+        match &self.browser {
+            Some(b) => client::is_signal(signal, b, SERVICE_BROWSER_INTERFACE, member),
+            None => false,
+        }
+    }
+
+    /// Starts resolving `item`, or the next queued services if it cannot be resolved.
+    fn resolve_queued(&mut self, mut item: ItemNew) {
+        loop {
+            match self.resolve_service(item) {
+                Ok(()) => return,
+                Err(e) => self.invoke_callback(Err(e)),
+            }
+
+            match self.resolve_queue.finish() {
+                Some(i) => item = i,
+                None => return,
+            }
+        }
+    }
+
+    fn resolve_service(&mut self, item: ItemNew) -> Result<()> {
+        let (interface, protocol, name, _, domain, _) = item;
+
+        // the reported type is the sub-type if one is browsed, which cannot be resolved
+        let resolver: OwnedObjectPath = self.client()?.call(
+            "/",
+            SERVER_INTERFACE,
+            "ServiceResolverNew",
+            &(
+                interface,
+                protocol,
+                name,
+                self.kind(),
+                domain,
+                avahi_dbus_util::protocol(self.address_family),
+                avahi_dbus_util::resolve_flags(self.flags),
+            ),
+        )?;
+
+        self.resolvers.push(resolver.as_str().to_string());
+
+        Ok(())
+    }
+
+    fn handle_resolver_signal(&mut self, signal: &Message) -> Result<()> {
+        let is_resolver_signal =
+            |r: &String, member| client::is_signal(signal, r, SERVICE_RESOLVER_INTERFACE, member);
+
+        let position = self
+            .resolvers
+            .iter()
+            .position(|r| is_resolver_signal(r, "Found") || is_resolver_signal(r, "Failure"));
+
+        let resolver = match position {
+            Some(i) => self.resolvers.remove(i),
+            None => return Ok(()),
+        };
+
+        if let Err(e) = self.free(&resolver, SERVICE_RESOLVER_INTERFACE) {
+            warn!("{}", e);
+        }
+
+        if let Some(item) = self.resolve_queue.finish() {
+            self.resolve_queued(item);
+        }
+
+        if is_resolver_signal(&resolver, "Failure") {
+            let error = signal.body::<(String,)>().map(|b| b.0).unwrap_or_default();
+            return Err(avahi_dbus_util::error(
+                &error,
+                format!("failed to resolve service: {}", error),
+            )
+            .with_operation(Operation::Resolve));
+        }
+
+        match signal.body::<Found>() {
+            Ok(found) => self.handle_resolver_found(found),
+            Err(e) => Err(Error::with_kind(
+                ErrorKind::Other,
+                format!("unexpected Found signal: {}", e),
+            )
+            .with_operation(Operation::Resolve)),
+        }
+    }
+
+    fn handle_resolver_found(&self, found: Found) -> Result<()> {
+        let (interface, _, name, _, domain, host_name, _, address, port, txt, _) = found;
+
+        if port == 0 {
+            // placeholders announced by other implementations with a `SRV` record for port 0
+            debug!("Ignoring placeholder service: {}", name);
+            return Ok(());
+        }
+
+        // the address is not looked up with `ServiceFlags::NO_ADDRESS`
+        let address = match address.as_str() {
+            "" => None,
+            a => Some(
+                avahi_dbus_util::parse_address(a)
+                    .ok_or_else(|| format!("invalid address of service `{}`: {}", name, a))?,
+            ),
+        };
+
+        let txt = txt_record::from_entries(avahi_dbus_util::txt_from_bytes(&txt))?;
+        let txt = if txt.is_empty() { None } else { Some(txt) };
+
+        let result = ServiceDiscovery::builder()
+            .name(name)
+            .service_type(self.service_type.clone())
+            .domain(domain)
+            .host_name(host_name)
+            .address(address.unwrap_or_else(|| Ipv4Addr::UNSPECIFIED.into()))
+            .addresses(address.into_iter().collect())
+            .scope_id(address.map_or(0, |a| ffi::link_local_scope_id(&a, interface as u32)))
+            .port(port)
+            .txt(txt)
+            .build()?;
+
+        self.invoke_callback(Ok(result));
+
+        Ok(())
+    }
+
+    /// The objects of the previous daemon are gone once it has restarted, so the browser is
+    /// created again.
+    fn handle_reconnect(&mut self) {
+        debug!("avahi-daemon started, browsing services again");
+
+        self.browser = None;
+        self.resolvers.clear();
+        self.resolve_queue.clear();
+
+        match self.create_browser() {
+            Ok(()) => self.invoke_reconnected_callback(),
+            Err(e) => self.invoke_callback(Err(e)),
+        }
+    }
+
+    /// Frees the browser and the resolvers that have not reported yet, returning the first error.
+    fn release(&mut self) -> Result<()> {
+        let mut result = Ok(());
+
+        for resolver in mem::take(&mut self.resolvers) {
+            result = result.and(self.free(&resolver, SERVICE_RESOLVER_INTERFACE));
+        }
+
+        if let Some(browser) = self.browser.take() {
+            result = result.and(self.free(&browser, SERVICE_BROWSER_INTERFACE));
+        }
+
+        result
+    }
+
+    /// Frees the object at `path`.
+    fn free(&self, path: &str, interface: &str) -> Result<()> {
+        self.client()?
+            .call::<_, ()>(path, interface, "Free", &())
+            .map_err(|e| format!("could not free {}: {}", path, e).into())
+    }
+
+    fn invoke_callback(&self, result: Result<ServiceDiscovery>) {
+        if !self.control.is_running() {
+            return;
+        }
+
+        match (
+            result,
+            &self.error_callback,
+            &self.service_discovered_callback,
+        ) {
+            (Err(e), Some(f), _) => f(e, self.user_context.clone()),
+            (result, _, Some(f)) => f(result, self.user_context.clone()),
+            _ => warn!("attempted to invoke callback but none was set"),
+        }
+    }
+
+    fn invoke_reconnected_callback(&self) {
+        if let Some(f) = &self.reconnected_callback {
+            f(self.user_context.clone());
+        }
+    }
+}
+
+impl fmt::Debug for AvahiDbusMdnsBrowser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AvahiDbusMdnsBrowser")
+            .field("service_type", &self.service_type)
+            .field("interface_index", &self.interface_index)
+            .field("domain", &self.domain)
+            .field("address_family", &self.address_family)
+            .field("browser", &self.browser)
+            .finish()
+    }
+}
+
+impl Drop for AvahiDbusMdnsBrowser {
+    fn drop(&mut self) {
+        if let Err(e) = self.release() {
+            warn!("{}", e);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+//! Connection to `avahi-daemon` over the system bus
+
+use super::avahi_dbus_util::{self, AVAHI_SERVICE, SERVER_INTERFACE};
+use crate::error::{Error, ErrorKind};
+use crate::Result;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread;
+use zbus::blocking::{Connection, MessageIterator};
+use zbus::zvariant::{DynamicType, Type};
+use zbus::{Message, MessageType};
+
+const DBUS_SERVICE: &str = "org.freedesktop.DBus";
+const DBUS_PATH: &str = "/org/freedesktop/DBus";
+/// The error replied to calls to a service that is not connected to the bus
+const ERROR_SERVICE_UNKNOWN: &str = "org.freedesktop.DBus.Error.ServiceUnknown";
+
+/// A signal received from the system bus
+pub type Signal = Arc<Message>;
+
+/// A connection to the system bus that calls methods of `avahi-daemon`.
+#[derive(Debug, Clone)]
+pub struct AvahiDbusClient {
+    connection: Connection,
+}
+
+impl AvahiDbusClient {
+    /// Connects to the system bus and subscribes to the signals emitted by `avahi-daemon`, and to
+    /// changes of its owner (i.e. restarts of the daemon).
+    pub fn new() -> Result<Self> {
+        let connection = Connection::system()
+            .map_err(|e| format!("could not connect to the system bus: {}", e))?;
+
+        let rules = [
+            format!("type='signal',sender='{}'", AVAHI_SERVICE),
+            format!(
+                "type='signal',sender='{}',member='NameOwnerChanged',arg0='{}'",
+                DBUS_SERVICE, AVAHI_SERVICE
+            ),
+        ];
+
+        for rule in &rules {
+            connection
+                .call_method(
+                    Some(DBUS_SERVICE),
+                    DBUS_PATH,
+                    Some(DBUS_SERVICE),
+                    "AddMatch",
+                    &(rule.as_str(),),
+                )
+                .map_err(|e| format!("could not subscribe to Avahi signals: {}", e))?;
+        }
+
+        Ok(Self { connection })
+    }
+
+    /// Calls `method` of `interface` on the object at `path` of `avahi-daemon` and returns the
+    /// body of the reply.
+    pub fn call<B, R>(&self, path: &str, interface: &str, method: &str, body: &B) -> Result<R>
+    where
+        B: Serialize + DynamicType,
+        R: DeserializeOwned + Type,
+    {
+        let reply = self
+            .connection
+            .call_method(Some(AVAHI_SERVICE), path, Some(interface), method, body)
+            .map_err(|e| call_error(interface, method, e))?;
+
+        reply
+            .body()
+            .map_err(|e| format!("unexpected reply to {}.{}: {}", interface, method, e).into())
+    }
+
+    /// Calls `method` of the `org.freedesktop.Avahi.Server` interface that takes no arguments.
+    pub fn call_server<R>(&self, method: &str) -> Result<R>
+    where
+        R: DeserializeOwned + Type,
+    {
+        self.call("/", SERVER_INTERFACE, method, &())
+    }
+
+    /// Returns the host name of this computer without any domain.
+    pub fn host_name(&self) -> Result<String> {
+        self.call_server("GetHostName")
+    }
+
+    /// Returns the fully qualified host name of this computer (e.g. `hostname.local`).
+    pub fn host_name_fqdn(&self) -> Result<String> {
+        self.call_server("GetHostNameFqdn")
+    }
+
+    /// Returns the default domain of `avahi-daemon`.
+    pub fn domain_name(&self) -> Result<String> {
+        self.call_server("GetDomainName")
+    }
+
+    /// Returns the name `avahi-daemon` suggests to register a service under after `name` has
+    /// conflicted with another service (e.g. `name #2` for `name`).
+    pub fn alternative_service_name(&self, name: &str) -> Result<String> {
+        self.call("/", SERVER_INTERFACE, "GetAlternativeServiceName", &(name,))
+    }
+
+    /// Returns a channel receiving the signals received from now on.
+    ///
+    /// The signals are received on a background thread, because the blocking API of `zbus` does
+    /// not support waiting for messages with a timeout. The thread exits once the receiver has
+    /// been dropped and the next signal is received.
+    pub fn subscribe(&self) -> Receiver<Signal> {
+        let (tx, rx) = mpsc::channel();
+
+        // the iterator only yields the messages received after it has been created
+        let messages = MessageIterator::from(&self.connection);
+
+        thread::spawn(move || {
+            for message in messages {
+                let message = match message {
+                    Ok(m) => m,
+                    Err(e) => {
+                        warn!("could not receive D-Bus message: {}", e);
+                        continue;
+                    }
+                };
+
+                if message.message_type() != MessageType::Signal {
+                    continue;
+                }
+
+                if tx.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        rx
+    }
+}
+
+/// Returns the error of a failed call, which is of kind `ErrorKind::DaemonNotRunning` if
+/// `avahi-daemon` is not connected to the system bus. The errors reported by `avahi-daemon` have
+/// the kind and code of their Avahi error, see `avahi_dbus_util::error()`.
+fn call_error(interface: &str, method: &str, error: zbus::Error) -> Error {
+    match &error {
+        zbus::Error::MethodError(name, _, _) if name.as_str() == ERROR_SERVICE_UNKNOWN => {
+            Error::with_kind(
+                ErrorKind::DaemonNotRunning,
+                format!(
+                    "could not call {}.{}: avahi-daemon is not connected to the system bus. Start \
+                     avahi-daemon or, in a container, mount the system bus socket of the host \
+                     (e.g. `-v /run/dbus/system_bus_socket:/run/dbus/system_bus_socket`)",
+                    interface, method
+                ),
+            )
+        }
+        zbus::Error::MethodError(name, _, _) => avahi_dbus_util::error(
+            name.as_str(),
+            format!("could not call {}.{}: {}", interface, method, error),
+        ),
+        _ => Error::with_kind(
+            ErrorKind::Other,
+            format!("could not call {}.{}: {}", interface, method, error),
+        ),
+    }
+}
+
+/// Returns true if `avahi-daemon` is reachable over the system bus.
+pub fn is_daemon_running() -> bool {
+    daemon_version().is_ok()
+}
+
+/// Connects to `avahi-daemon` over the system bus and returns its version string (e.g.
+/// `avahi 0.8`).
+pub fn daemon_version() -> Result<String> {
+    AvahiDbusClient::new()?.call_server("GetVersionString")
+}
+
+/// Returns true if `signal` is the signal `member` of `interface` emitted by the object at `path`.
+pub fn is_signal(signal: &Message, path: &str, interface: &str, member: &str) -> bool {
+    matches!(signal.path(), Some(p) if p.as_str() == path)
+        && matches!(signal.interface(), Some(i) if i.as_str() == interface)
+        && matches!(signal.member(), Some(m) if m.as_str() == member)
+}
+
+/// Returns true if `signal` announces that `avahi-daemon` has (re)started, in which case the
+/// objects created on the previous daemon no longer exist.
+pub fn is_daemon_started(signal: &Message) -> bool {
+    if !is_signal(signal, DBUS_PATH, DBUS_SERVICE, "NameOwnerChanged") {
+        return false;
+    }
+
+    match signal.body::<(String, String, String)>() {
+        Ok((name, _, new_owner)) => name == AVAHI_SERVICE && !new_owner.is_empty(),
+        Err(_) => false,
+    }
+}
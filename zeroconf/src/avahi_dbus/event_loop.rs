@@ -0,0 +1,60 @@
+//! Event loop for running a `MdnsService` or `MdnsBrowser`.
+
+use super::client::Signal;
+use crate::event_loop::TEventLoop;
+use crate::Result;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, TryRecvError};
+use std::time::Duration;
+use zbus::Message;
+
+/// Handles a signal received from the system bus
+type SignalHandler<'a> = dyn Fn(&Message) + 'a;
+
+#[derive(new)]
+pub struct AvahiDbusEventLoop<'a> {
+    signals: Receiver<Signal>,
+    on_signal: Box<SignalHandler<'a>>,
+    #[new(default)]
+    on_poll: Option<Box<dyn Fn() + 'a>>,
+}
+
+impl<'a> AvahiDbusEventLoop<'a> {
+    /// Sets a function to invoke after each iteration of the event loop. This is used to perform
+    /// periodic work that is not driven by signals.
+    pub(crate) fn with_on_poll(mut self, on_poll: Box<dyn Fn() + 'a>) -> Self {
+        self.on_poll = Some(on_poll);
+        self
+    }
+}
+
+impl<'a> TEventLoop for AvahiDbusEventLoop<'a> {
+    /// Polls for new events.
+    ///
+    /// Waits up to the specified timeout for a signal from `avahi-daemon` and handles it along
+    /// with any other signals that have already been received.
+    fn poll(&self, timeout: Duration) -> Result<()> {
+        match self.signals.recv_timeout(timeout) {
+            Ok(signal) => (self.on_signal)(&signal),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err("the connection to the system bus was closed".into())
+            }
+        }
+
+        loop {
+            match self.signals.try_recv() {
+                Ok(signal) => (self.on_signal)(&signal),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    return Err("the connection to the system bus was closed".into())
+                }
+            }
+        }
+
+        if let Some(f) = &self.on_poll {
+            f();
+        }
+
+        Ok(())
+    }
+}
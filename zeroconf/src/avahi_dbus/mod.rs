@@ -0,0 +1,20 @@
+//! Linux-specific ZeroConf bindings over Avahi's D-Bus API
+//!
+//! This module talks to `avahi-daemon` over its [D-Bus API] instead of linking against
+//! `libavahi-client`, so neither the C library nor its headers are required to build. It is
+//! enabled with the `avahi-dbus` feature instead of the `avahi` feature.
+//!
+//! # Note
+//! Only registering and browsing services is supported over D-Bus. The resolvers, record queries
+//! and `ServiceDiscovery::raw_records()` are only available with `libavahi-client`, and the
+//! priority and weight of discovered services are always `0`.
+//!
+//! [D-Bus API]: https://github.com/lathiat/avahi/tree/master/avahi-daemon
+
+pub mod avahi_dbus_util;
+pub mod browser;
+pub mod client;
+pub mod event_loop;
+pub mod registration_handle;
+pub mod service;
+pub mod txt_record;
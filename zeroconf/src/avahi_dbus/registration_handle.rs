@@ -0,0 +1,39 @@
+//! Avahi D-Bus implementation for cross-platform registration handle
+
+use super::event_loop::AvahiDbusEventLoop;
+use super::service::AvahiDbusMdnsService;
+use crate::prelude::*;
+use crate::registration_handle::TRegistrationHandle;
+use crate::service;
+use crate::Result;
+use std::time::Duration;
+
+#[derive(new)]
+pub struct AvahiDbusRegistrationHandle<'a> {
+    service: *mut AvahiDbusMdnsService,
+    event_loop: AvahiDbusEventLoop<'a>,
+}
+
+impl<'a> TEventLoop for AvahiDbusRegistrationHandle<'a> {
+    fn poll(&self, timeout: Duration) -> Result<()> {
+        self.event_loop.poll(timeout)
+    }
+}
+
+impl<'a> TRegistrationHandle for AvahiDbusRegistrationHandle<'a> {
+    fn set_name(&mut self, name: &str) -> Result<()> {
+        unsafe {
+            (*self.service).set_name(name);
+            (*self.service).reregister()
+        }
+    }
+
+    fn set_port(&mut self, port: u16) -> Result<()> {
+        service::check_port(port)?;
+
+        unsafe {
+            (*self.service).set_port(port);
+            (*self.service).reregister()
+        }
+    }
+}
@@ -0,0 +1,476 @@
+//! Avahi D-Bus implementation for cross-platform service.
+
+use super::avahi_dbus_util::{self, ENTRY_GROUP_INTERFACE, SERVER_INTERFACE};
+use super::client::{self, AvahiDbusClient};
+use super::registration_handle::AvahiDbusRegistrationHandle;
+use crate::dns;
+use crate::error::{Error, ErrorKind, Operation};
+use crate::network_watcher::NetworkWatcher;
+use crate::prelude::*;
+use crate::service;
+use crate::{
+    DaemonReconnectedCallback, ErrorCallback, EventLoop, NameConflictCallback, NetworkInterface,
+    RegistrationHandle, Result, ServiceFlags, ServiceRegisteredCallback, ServiceRegistration,
+    ServiceType, TxtRecord,
+};
+use std::any::Any;
+use std::fmt::{self, Formatter};
+use std::sync::Arc;
+use zbus::zvariant::OwnedObjectPath;
+use zbus::Message;
+
+pub struct AvahiDbusMdnsService {
+    client: Option<AvahiDbusClient>,
+    /// The object path of the entry group of the service, once created
+    group: Option<String>,
+    service_type: ServiceType,
+    port: u16,
+    name: Option<String>,
+    interface_index: i32,
+    domain: Option<String>,
+    host: Option<String>,
+    txt_record: Option<TxtRecord>,
+    flags: ServiceFlags,
+    ttl: Option<u32>,
+    auto_reregister: bool,
+    network_watcher: Option<NetworkWatcher>,
+    registered_callback: Option<Box<ServiceRegisteredCallback>>,
+    error_callback: Option<Box<ErrorCallback>>,
+    reconnected_callback: Option<Box<DaemonReconnectedCallback>>,
+    name_conflict_callback: Option<Box<NameConflictCallback>>,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl TMdnsService for AvahiDbusMdnsService {
+    fn new(service_type: ServiceType, port: u16) -> Self {
+        Self {
+            client: None,
+            group: None,
+            service_type,
+            port,
+            name: None,
+            interface_index: avahi_dbus_util::IF_UNSPEC,
+            domain: None,
+            host: None,
+            txt_record: None,
+            flags: ServiceFlags::empty(),
+            ttl: None,
+            auto_reregister: false,
+            network_watcher: None,
+            registered_callback: None,
+            error_callback: None,
+            reconnected_callback: None,
+            name_conflict_callback: None,
+            user_context: None,
+        }
+    }
+
+    /// Sets the name to register this service under. If no name is set, the host name is used.
+    fn set_name(&mut self, name: &str) {
+        self.name = Some(name.to_string());
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface_index = avahi_dbus_util::interface_index(interface);
+    }
+
+    fn set_domain(&mut self, domain: &str) {
+        self.domain = Some(domain.to_string());
+    }
+
+    fn set_host(&mut self, host: &str) {
+        self.host = Some(host.to_string());
+    }
+
+    fn set_txt_record(&mut self, txt_record: TxtRecord) {
+        self.txt_record = Some(txt_record);
+    }
+
+    fn set_registered_callback(&mut self, registered_callback: Box<ServiceRegisteredCallback>) {
+        self.registered_callback = Some(registered_callback);
+    }
+
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>) {
+        self.error_callback = Some(error_callback);
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        self.user_context = Some(Arc::from(context));
+    }
+
+    fn set_flags(&mut self, flags: ServiceFlags) {
+        self.flags = flags;
+    }
+
+    fn set_ttl(&mut self, ttl: u32) {
+        self.ttl = Some(ttl);
+    }
+
+    fn set_auto_reregister(&mut self, auto_reregister: bool) {
+        self.auto_reregister = auto_reregister;
+    }
+
+    fn set_reconnected_callback(&mut self, reconnected_callback: Box<DaemonReconnectedCallback>) {
+        self.reconnected_callback = Some(reconnected_callback);
+    }
+
+    fn set_name_conflict_callback(&mut self, name_conflict_callback: Box<NameConflictCallback>) {
+        self.name_conflict_callback = Some(name_conflict_callback);
+    }
+
+    fn register(&mut self) -> Result<RegistrationHandle<'_>> {
+        let _span = operation_span!("register", service_type = self.service_type.to_string());
+
+        debug!("Registering service: {:?}", self);
+
+        service::check_port(self.port)?;
+
+        let client = AvahiDbusClient::new()?;
+        let signals = client.subscribe();
+
+        self.client = Some(client);
+        self.create_service()?;
+
+        if self.auto_reregister {
+            self.network_watcher = Some(NetworkWatcher::new());
+        }
+
+        let service: *mut Self = self;
+
+        let event_loop = EventLoop::new(
+            signals,
+            Box::new(move |signal| unsafe { (*service).handle_signal(signal) }),
+        )
+        .with_on_poll(Box::new(move || unsafe { (*service).handle_poll() }));
+
+        Ok(AvahiDbusRegistrationHandle::new(service, event_loop))
+    }
+
+    fn close(mut self) -> Result<()> {
+        let result = self.free_group();
+        self.client = None;
+        result
+    }
+}
+
+impl AvahiDbusMdnsService {
+    fn kind(&self) -> String {
+        format!(
+            "_{}._{}",
+            self.service_type.name(),
+            self.service_type.protocol()
+        )
+    }
+
+    fn client(&self) -> Result<&AvahiDbusClient> {
+        self.client
+            .as_ref()
+            .ok_or_else(|| "service has not been registered".into())
+    }
+
+    /// Returns the configured domain, or the default domain of the daemon if none was set
+    fn domain(&self) -> Result<String> {
+        match &self.domain {
+            Some(d) => Ok(d.clone()),
+            None => self.client()?.domain_name(),
+        }
+    }
+
+    /// Calls `method` of the entry group of the service.
+    fn call_group<B>(&self, method: &str, body: &B) -> Result<()>
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        let group = self
+            .group
+            .as_deref()
+            .ok_or("entry group has not been created")?;
+
+        self.client()?
+            .call(group, ENTRY_GROUP_INTERFACE, method, body)
+    }
+
+    /// Frees the entry group of the service, if created, which withdraws the service.
+    fn free_group(&mut self) -> Result<()> {
+        if self.group.is_none() {
+            return Ok(());
+        }
+
+        let result = self.call_group("Free", &());
+        self.group = None;
+        result
+    }
+
+    fn create_service(&mut self) -> Result<()> {
+        let client = self.client()?.clone();
+
+        if self.name.is_none() {
+            self.name = Some(client.host_name()?);
+        }
+
+        if self.group.is_none() {
+            debug!("Creating group");
+
+            let group: OwnedObjectPath =
+                client.call("/", SERVER_INTERFACE, "EntryGroupNew", &())?;
+            self.group = Some(group.as_str().to_string());
+        }
+
+        debug!("Adding service");
+
+        let name = self.name.clone().unwrap();
+        let kind = self.kind();
+        let domain = self.domain.clone().unwrap_or_default();
+        let flags = avahi_dbus_util::publish_flags(self.flags);
+
+        let txt = avahi_dbus_util::txt_to_bytes(self.txt_record.iter().flat_map(|t| t.iter()));
+
+        self.call_group(
+            "AddService",
+            &(
+                self.interface_index,
+                avahi_dbus_util::PROTO_UNSPEC,
+                flags,
+                &name,
+                &kind,
+                &domain,
+                self.host.clone().unwrap_or_default(),
+                self.port,
+                txt,
+            ),
+        )?;
+
+        for sub_type in self.service_type.sub_types() {
+            self.call_group(
+                "AddServiceSubtype",
+                &(
+                    self.interface_index,
+                    avahi_dbus_util::PROTO_UNSPEC,
+                    flags,
+                    &name,
+                    &kind,
+                    &domain,
+                    format!("_{}._sub.{}", sub_type, kind),
+                ),
+            )?;
+        }
+
+        if let Some(ttl) = self.ttl {
+            self.update_ttl(ttl)?;
+        }
+
+        self.call_group("Commit", &())
+    }
+
+    /// Avahi does not support setting the TTL when adding a service, so the service's PTR, SRV and
+    /// TXT records (including the PTR records of its sub-types and of the service type
+    /// enumeration) are updated with the configured TTL after it has been added.
+    fn update_ttl(&self, ttl: u32) -> Result<()> {
+        let name = self.name.clone().unwrap();
+        let kind = self.kind();
+        let domain = self.domain()?;
+
+        let host = match &self.host {
+            Some(h) => h.clone(),
+            None => self.client()?.host_name_fqdn()?,
+        };
+
+        let txt: Vec<_> = self
+            .txt_record
+            .as_ref()
+            .map(|t| t.iter().collect())
+            .unwrap_or_default();
+
+        let records = dns::service_records(
+            &name,
+            &kind,
+            self.service_type.sub_types().iter().map(String::as_str),
+            &domain,
+            &host,
+            self.port,
+            txt,
+        )?;
+
+        let flags = avahi_dbus_util::publish_flags(self.flags) | avahi_dbus_util::PUBLISH_UPDATE;
+
+        for (record_name, kind, rdata) in &records {
+            self.call_group(
+                "AddRecord",
+                &(
+                    self.interface_index,
+                    avahi_dbus_util::PROTO_UNSPEC,
+                    flags,
+                    record_name.as_str(),
+                    dns::CLASS_IN,
+                    *kind,
+                    ttl,
+                    rdata,
+                ),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn handle_signal(&mut self, signal: &Message) {
+        if client::is_daemon_started(signal) {
+            self.handle_reconnect();
+            return;
+        }
+
+        let group = match &self.group {
+            Some(g) => g,
+            None => return,
+        };
+
+        if !client::is_signal(signal, group, ENTRY_GROUP_INTERFACE, "StateChanged") {
+            return;
+        }
+
+        let (state, error) = match signal.body::<(i32, String)>() {
+            Ok(b) => b,
+            Err(e) => {
+                warn!("unexpected StateChanged signal: {}", e);
+                return;
+            }
+        };
+
+        let result = match state {
+            avahi_dbus_util::ENTRY_GROUP_ESTABLISHED => self.handle_group_established(),
+            avahi_dbus_util::ENTRY_GROUP_COLLISION => self.handle_group_collision(),
+            avahi_dbus_util::ENTRY_GROUP_FAILURE => Err(avahi_dbus_util::error(
+                &error,
+                format!("could not register service: {}", error),
+            )
+            .with_operation(Operation::Register)),
+            _ => Ok(()),
+        };
+
+        if let Err(e) = result {
+            self.invoke_callback(Err(e));
+        }
+    }
+
+    fn handle_group_established(&self) -> Result<()> {
+        debug!("Group established");
+
+        let result = ServiceRegistration::builder()
+            .name(self.name.clone().unwrap())
+            .service_type(self.service_type.clone())
+            .domain(self.domain()?)
+            .build()?;
+
+        self.invoke_callback(Ok(result));
+
+        Ok(())
+    }
+
+    fn handle_group_collision(&mut self) -> Result<()> {
+        let name = self.name.clone().unwrap();
+
+        debug!("Group collision: {}", name);
+
+        let candidate = match &self.name_conflict_callback {
+            Some(f) => f(&name, self.user_context.clone()),
+            None if !self.flags.contains(ServiceFlags::NO_AUTO_RENAME) => {
+                Some(self.client()?.alternative_service_name(&name)?)
+            }
+            None => None,
+        };
+
+        let candidate = match candidate {
+            Some(c) => c,
+            None => {
+                return Err(Error::with_kind(
+                    ErrorKind::NameConflict,
+                    format!("service name `{}` conflicts with another service", name),
+                ))
+            }
+        };
+
+        debug!("Renaming service to: {}", candidate);
+
+        self.name = Some(candidate);
+        self.reregister()
+    }
+
+    /// The objects of the previous daemon are gone once it has restarted, so the service is
+    /// registered again from scratch.
+    fn handle_reconnect(&mut self) {
+        debug!("avahi-daemon started, re-registering service");
+
+        self.group = None;
+
+        match self.create_service() {
+            Ok(()) => self.invoke_reconnected_callback(),
+            Err(e) => self.invoke_callback(Err(e)),
+        }
+    }
+
+    fn handle_poll(&mut self) {
+        let changed = match &mut self.network_watcher {
+            Some(w) => w.poll_changed(),
+            None => false,
+        };
+
+        if changed {
+            debug!("Network configuration changed, re-registering service");
+
+            if let Err(e) = self.reregister() {
+                self.invoke_callback(Err(e));
+            }
+        }
+    }
+
+    fn invoke_callback(&self, result: Result<ServiceRegistration>) {
+        match (result, &self.error_callback, &self.registered_callback) {
+            (Err(e), Some(f), _) => f(e, self.user_context.clone()),
+            (result, _, Some(f)) => f(result, self.user_context.clone()),
+            _ => warn!("attempted to invoke callback but none was set"),
+        }
+    }
+
+    fn invoke_reconnected_callback(&self) {
+        if let Some(f) = &self.reconnected_callback {
+            f(self.user_context.clone());
+        }
+    }
+
+    pub(crate) fn set_port(&mut self, port: u16) {
+        self.port = port;
+    }
+
+    /// Re-registers the service with its current configuration.
+    pub(crate) fn reregister(&mut self) -> Result<()> {
+        if self.client.is_none() {
+            return Ok(());
+        }
+
+        if self.group.is_some() {
+            self.call_group("Reset", &())?;
+        }
+
+        self.create_service()
+    }
+}
+
+impl fmt::Debug for AvahiDbusMdnsService {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AvahiDbusMdnsService")
+            .field("service_type", &self.service_type)
+            .field("port", &self.port)
+            .field("name", &self.name)
+            .field("domain", &self.domain)
+            .field("host", &self.host)
+            .field("group", &self.group)
+            .finish()
+    }
+}
+
+impl Drop for AvahiDbusMdnsService {
+    fn drop(&mut self) {
+        if let Err(e) = self.free_group() {
+            warn!("could not free entry group: {}", e);
+        }
+    }
+}
@@ -0,0 +1,68 @@
+//! Avahi D-Bus implementation for cross-platform TXT record.
+
+use crate::txt_record::TTxtRecord;
+use crate::Result;
+
+/// Interface for interfacting with the TXT record properties of Avahi's D-Bus API.
+///
+/// Avahi accepts TXT records as an array of `key=value` byte strings, so the entries are stored in
+/// the order they were inserted.
+#[derive(Clone)]
+pub struct AvahiDbusTxtRecord(Vec<(String, String)>);
+
+impl TTxtRecord for AvahiDbusTxtRecord {
+    fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    fn insert(&mut self, key: &str, value: &str) -> Result<()> {
+        if key.is_empty() || key.contains('=') {
+            return Err(format!("invalid TXT record key: `{}`", key).into());
+        }
+
+        match self.0.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value.to_string(),
+            None => self.0.push((key.to_string(), value.to_string())),
+        }
+
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.0
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<()> {
+        self.0.retain(|(k, _)| k != key);
+        Ok(())
+    }
+
+    fn contains_key(&self, key: &str) -> bool {
+        self.0.iter().any(|(k, _)| k == key)
+    }
+
+    fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    fn iter<'a>(&'a self) -> Box<dyn Iterator<Item = (String, String)> + 'a> {
+        Box::new(self.0.iter().cloned())
+    }
+
+    fn keys<'a>(&'a self) -> Box<dyn Iterator<Item = String> + 'a> {
+        Box::new(self.0.iter().map(|(k, _)| k.clone()))
+    }
+
+    fn values<'a>(&'a self) -> Box<dyn Iterator<Item = String> + 'a> {
+        Box::new(self.0.iter().map(|(_, v)| v.clone()))
+    }
+}
+
+impl PartialEq for AvahiDbusTxtRecord {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_map() == other.to_map()
+    }
+}
@@ -0,0 +1,487 @@
+//! Utilities regarding the mDNS backend compiled into this build
+//!
+//! The backends are selected at compile time with the crate features (e.g. `avahi-dbus` or
+//! `embedded`). [`Mdns`] probes at runtime which of the backends compiled into the build can be
+//! used on the current host, in the [fallback order], or uses the one selected by the
+//! application.
+//!
+//! [fallback order]: enum.Backend.html#associatedconstant.FALLBACK_ORDER
+//!
+//! [`Mdns`]: struct.Mdns.html
+
+use crate::error::{Error, ErrorKind};
+use crate::prelude::*;
+use crate::{MdnsBrowser, MdnsService, Result, ServiceType};
+use std::fmt;
+
+/// The mDNS implementations wrapped by this crate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    /// `avahi-daemon` through `libavahi-client` (the `avahi` feature)
+    Avahi,
+    /// `avahi-daemon` over D-Bus (the `avahi-dbus` feature)
+    AvahiDbus,
+    /// systemd-resolved over D-Bus (the `systemd-resolved` feature)
+    SystemdResolved,
+    /// The mDNS stack of Avahi running in-process through `libavahi-core` (the `avahi-core`
+    /// feature)
+    AvahiCore,
+    /// `mDNSResponder` on Apple platforms and on Linux (the `mdnsresponder` feature), or the
+    /// Bonjour service on Windows (the `bonjour-sdk` feature)
+    Bonjour,
+    /// The DNS-SD API built into Windows 10 and later (the `windows-native` feature)
+    WindowsNative,
+    /// The pure-Rust mDNS implementation of this crate (the `embedded` feature)
+    Embedded,
+    /// The in-memory implementation for unit tests (the `mock` feature)
+    Mock,
+    /// The implementation of another crate set with `plugin::set_backend()` (the `plugin` feature)
+    Plugin,
+}
+
+bitflags! {
+    /// The optional features supported by a [`Backend`], see [`Backend::capabilities()`].
+    ///
+    /// Applications that run on several platforms can check these up front and, e.g., fall back
+    /// to browsing without sub-types instead of failing once a service is registered.
+    ///
+    /// [`Backend`]: enum.Backend.html
+    /// [`Backend::capabilities()`]: enum.Backend.html#method.capabilities
+    #[derive(Default)]
+    pub struct Capabilities: u32 {
+        /// Registers and browses the sub-types of a `ServiceType`.
+        const SUB_TYPES = 1 << 0;
+        /// Announces and discovers services over IPv6 in addition to IPv4.
+        const IPV6 = 1 << 1;
+        /// Registers and browses services in unicast DNS-SD domains other than `local`.
+        const WIDE_AREA = 1 << 2;
+        /// Resolves service instances without browsing for them with a `MdnsResolver`.
+        const RESOLVER = 1 << 3;
+        /// Resolves host names with a `HostNameResolver` and addresses with an
+        /// `AddressResolver`.
+        const HOST_RESOLUTION = 1 << 4;
+        /// Queries arbitrary resource records with a `RecordQuery`.
+        const RECORD_QUERIES = 1 << 5;
+        /// Maps ports on the network gateway with NAT-PMP or UPnP with a `PortMapping`.
+        const PORT_MAPPING = 1 << 6;
+    }
+}
+
+/// The backend compiled into this build
+#[cfg(backend_avahi)]
+const CURRENT: Backend = Backend::Avahi;
+/// The backend compiled into this build
+#[cfg(backend_avahi_dbus)]
+const CURRENT: Backend = Backend::AvahiDbus;
+/// The backend compiled into this build
+#[cfg(backend_resolved)]
+const CURRENT: Backend = Backend::SystemdResolved;
+/// The backend compiled into this build
+#[cfg(backend_avahi_core)]
+const CURRENT: Backend = Backend::AvahiCore;
+/// The backend compiled into this build
+#[cfg(backend_bonjour)]
+const CURRENT: Backend = Backend::Bonjour;
+/// The backend compiled into this build
+#[cfg(backend_bonjour_sdk)]
+const CURRENT: Backend = Backend::Bonjour;
+/// The backend compiled into this build
+#[cfg(backend_win32)]
+const CURRENT: Backend = Backend::WindowsNative;
+/// The backend compiled into this build
+#[cfg(backend_embedded)]
+const CURRENT: Backend = Backend::Embedded;
+/// The backend compiled into this build
+#[cfg(backend_mock)]
+const CURRENT: Backend = Backend::Mock;
+/// The backend compiled into this build
+#[cfg(backend_plugin)]
+const CURRENT: Backend = Backend::Plugin;
+
+impl Backend {
+    /// The order in which [`Mdns::new()`] probes the backends compiled into a build: the mDNS
+    /// daemons of the system (Bonjour, then Avahi), then the implementations that run in this
+    /// process, like the pure-Rust one.
+    ///
+    /// [`Mdns::new()`]: struct.Mdns.html#method.new
+    pub const FALLBACK_ORDER: &'static [Backend] = &[
+        Backend::Bonjour,
+        Backend::WindowsNative,
+        Backend::Avahi,
+        Backend::AvahiDbus,
+        Backend::SystemdResolved,
+        Backend::AvahiCore,
+        Backend::Embedded,
+        Backend::Plugin,
+        Backend::Mock,
+    ];
+
+    /// Returns the backend compiled into this build, which is selected at compile time with the
+    /// crate features.
+    pub fn current() -> Backend {
+        CURRENT
+    }
+
+    /// Returns the backends compiled into this build, in the [fallback order].
+    ///
+    /// [fallback order]: #associatedconstant.FALLBACK_ORDER
+    pub fn compiled() -> Vec<Backend> {
+        Self::FALLBACK_ORDER
+            .iter()
+            .copied()
+            .filter(|b| b.is_compiled())
+            .collect()
+    }
+
+    /// Returns true if this backend is the one compiled into this build.
+    pub fn is_compiled(self) -> bool {
+        self == CURRENT
+    }
+
+    /// Returns true if this backend is compiled into this build and can currently be used, i.e.
+    /// its mDNS daemon is running, for the embedded implementation, the mDNS port can be bound
+    /// and, for the plugin backend, an implementation has been set.
+    pub fn is_available(self) -> bool {
+        self.is_compiled() && probe()
+    }
+
+    /// Returns the optional features this backend supports.
+    ///
+    /// The capabilities of the plugin backend are reported by the implementation set with
+    /// `plugin::set_backend()`, and are empty if none has been set.
+    pub fn capabilities(self) -> Capabilities {
+        match self {
+            Backend::Avahi => {
+                Capabilities::IPV6
+                    | Capabilities::WIDE_AREA
+                    | Capabilities::RESOLVER
+                    | Capabilities::HOST_RESOLUTION
+                    | Capabilities::RECORD_QUERIES
+            }
+            Backend::AvahiDbus | Backend::AvahiCore => {
+                Capabilities::SUB_TYPES | Capabilities::IPV6 | Capabilities::WIDE_AREA
+            }
+            Backend::SystemdResolved | Backend::WindowsNative => Capabilities::IPV6,
+            Backend::Bonjour => {
+                Capabilities::SUB_TYPES
+                    | Capabilities::IPV6
+                    | Capabilities::WIDE_AREA
+                    | Capabilities::RESOLVER
+                    | Capabilities::HOST_RESOLUTION
+                    | Capabilities::RECORD_QUERIES
+                    | Capabilities::PORT_MAPPING
+            }
+            // the embedded implementation only uses IPv4 multicast
+            Backend::Embedded | Backend::Mock => Capabilities::SUB_TYPES,
+            Backend::Plugin => plugin_capabilities(),
+        }
+    }
+}
+
+#[cfg(feature = "plugin")]
+fn plugin_capabilities() -> Capabilities {
+    match crate::plugin::backend() {
+        Some(b) => b.capabilities(),
+        None => Capabilities::empty(),
+    }
+}
+
+#[cfg(not(feature = "plugin"))]
+fn plugin_capabilities() -> Capabilities {
+    Capabilities::empty()
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Backend::Avahi => "Avahi",
+            Backend::AvahiDbus => "Avahi (D-Bus)",
+            Backend::SystemdResolved => "systemd-resolved",
+            Backend::AvahiCore => "Avahi (in-process)",
+            Backend::Bonjour => "Bonjour",
+            Backend::WindowsNative => "Windows DNS-SD",
+            Backend::Embedded => "embedded",
+            Backend::Mock => "mock",
+            Backend::Plugin => "plugin",
+        };
+
+        f.write_str(name)
+    }
+}
+
+/// Returns true if the backend compiled into this build can currently be used.
+#[cfg(backend_avahi)]
+fn probe() -> bool {
+    crate::linux::client::is_daemon_running()
+}
+/// Returns true if the backend compiled into this build can currently be used.
+#[cfg(backend_avahi_dbus)]
+fn probe() -> bool {
+    crate::avahi_dbus::client::is_daemon_running()
+}
+/// Returns true if the backend compiled into this build can currently be used.
+#[cfg(backend_resolved)]
+fn probe() -> bool {
+    crate::resolved::client::is_daemon_running()
+}
+/// Returns true if the backend compiled into this build can currently be used.
+#[cfg(backend_avahi_core)]
+fn probe() -> bool {
+    // the mDNS stack runs in this process
+    true
+}
+/// Returns true if the backend compiled into this build can currently be used.
+#[cfg(backend_bonjour)]
+fn probe() -> bool {
+    crate::macos::bonjour_util::is_daemon_running()
+}
+/// Returns true if the backend compiled into this build can currently be used.
+#[cfg(backend_bonjour_sdk)]
+fn probe() -> bool {
+    crate::windows::bonjour_util::is_daemon_running()
+}
+/// Returns true if the backend compiled into this build can currently be used.
+#[cfg(backend_win32)]
+fn probe() -> bool {
+    // the API is part of the operating system
+    true
+}
+/// Returns true if the backend compiled into this build can currently be used.
+#[cfg(backend_embedded)]
+fn probe() -> bool {
+    crate::embedded::socket::MdnsSocket::new().is_ok()
+}
+/// Returns true if the backend compiled into this build can currently be used.
+#[cfg(backend_mock)]
+fn probe() -> bool {
+    true
+}
+/// Returns true if the backend compiled into this build can currently be used.
+#[cfg(backend_plugin)]
+fn probe() -> bool {
+    crate::plugin::backend().is_some()
+}
+
+/// Creates browsers and services of the first backend compiled into this build that has been
+/// probed to be available, or of the backend selected by the application.
+///
+/// This is useful for binaries deployed to hosts that may not run the expected mDNS daemon:
+/// [`Mdns::new()`] falls back to the next backend of the build in the [fallback order], e.g. from
+/// the Bonjour daemon to the Avahi daemon and then to the pure-Rust implementation, and reports
+/// up front if none is usable instead of failing once services are browsed or registered.
+/// [`Mdns::with_backends()`] probes the backends in another order and [`Mdns::select()`]
+/// overrides the probing.
+///
+/// # Examples
+/// ```no_run
+/// use zeroconf::{Mdns, ServiceType};
+///
+/// let mdns = Mdns::new().unwrap();
+/// println!("Using the {} backend", mdns.backend());
+///
+/// let browser = mdns.browser(ServiceType::new("http", "tcp").unwrap());
+/// ```
+///
+/// [`Mdns::new()`]: #method.new
+/// [`Mdns::with_backends()`]: #method.with_backends
+/// [`Mdns::select()`]: #method.select
+/// [fallback order]: enum.Backend.html#associatedconstant.FALLBACK_ORDER
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mdns {
+    backend: Backend,
+}
+
+impl Mdns {
+    /// Probes the backends compiled into this build in the [fallback order] and selects the first
+    /// one that is available.
+    ///
+    /// Returns an error of kind `ErrorKind::DaemonNotRunning` if none of them is available.
+    ///
+    /// [fallback order]: enum.Backend.html#associatedconstant.FALLBACK_ORDER
+    pub fn new() -> Result<Self> {
+        Self::with_backends(Backend::FALLBACK_ORDER)
+    }
+
+    /// Probes the specified `backends` in order and selects the first one that is compiled into
+    /// this build and available.
+    ///
+    /// Returns an error of kind `ErrorKind::InvalidArgument` if none of `backends` is compiled
+    /// into this build, or of kind `ErrorKind::DaemonNotRunning` if none of those that are is
+    /// available.
+    pub fn with_backends(backends: &[Backend]) -> Result<Self> {
+        let compiled = backends
+            .iter()
+            .copied()
+            .filter(|b| b.is_compiled())
+            .collect::<Vec<_>>();
+
+        if compiled.is_empty() {
+            return Err(not_compiled_error(backends));
+        }
+
+        match compiled.iter().find(|b| b.is_available()) {
+            Some(&backend) => Ok(Self { backend }),
+            None => Err(Error::with_kind(
+                ErrorKind::DaemonNotRunning,
+                format!(
+                    "none of the mDNS backends {} is available",
+                    join_backends(&compiled)
+                ),
+            )),
+        }
+    }
+
+    /// Selects the specified `backend` without probing it, e.g. to use a daemon that is started
+    /// after the application.
+    ///
+    /// Returns an error of kind `ErrorKind::InvalidArgument` if `backend` is not compiled into
+    /// this build.
+    pub fn select(backend: Backend) -> Result<Self> {
+        if backend.is_compiled() {
+            Ok(Self { backend })
+        } else {
+            Err(not_compiled_error(&[backend]))
+        }
+    }
+
+    /// Returns the selected backend.
+    pub fn backend(&self) -> Backend {
+        self.backend
+    }
+
+    /// Creates a new `MdnsBrowser` for the specified `service_type`.
+    pub fn browser(&self, service_type: ServiceType) -> MdnsBrowser {
+        MdnsBrowser::new(service_type)
+    }
+
+    /// Creates a new `MdnsService` for the specified `service_type` and `port`.
+    pub fn service(&self, service_type: ServiceType, port: u16) -> MdnsService {
+        MdnsService::new(service_type, port)
+    }
+
+    /// Same as [`browser()`] but returns a trait object, e.g. to keep browsers of this crate and
+    /// test doubles of an application in the same collection.
+    ///
+    /// [`browser()`]: #method.browser
+    pub fn boxed_browser(&self, service_type: ServiceType) -> Box<dyn TMdnsBrowser> {
+        Box::new(self.browser(service_type))
+    }
+
+    /// Same as [`service()`] but returns a trait object, e.g. to keep services of this crate and
+    /// test doubles of an application in the same collection.
+    ///
+    /// [`service()`]: #method.service
+    pub fn boxed_service(&self, service_type: ServiceType, port: u16) -> Box<dyn TMdnsService> {
+        Box::new(self.service(service_type, port))
+    }
+}
+
+fn not_compiled_error(backends: &[Backend]) -> Error {
+    Error::with_kind(
+        ErrorKind::InvalidArgument,
+        format!(
+            "none of the mDNS backends {} is compiled into this build, which contains {}",
+            join_backends(backends),
+            join_backends(&Backend::compiled())
+        ),
+    )
+}
+
+fn join_backends(backends: &[Backend]) -> String {
+    backends
+        .iter()
+        .map(|b| format!("`{}`", b))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_current_backend_is_compiled() {
+        let others = [
+            Backend::Avahi,
+            Backend::AvahiDbus,
+            Backend::SystemdResolved,
+            Backend::AvahiCore,
+            Backend::Bonjour,
+            Backend::WindowsNative,
+            Backend::Embedded,
+            Backend::Mock,
+            Backend::Plugin,
+        ];
+
+        assert!(Backend::current().is_compiled());
+
+        for backend in others.iter().filter(|&&b| b != Backend::current()) {
+            assert!(!backend.is_compiled());
+            assert!(!backend.is_available());
+        }
+    }
+
+    #[test]
+    fn compiled_backends_are_in_fallback_order() {
+        assert_eq!(Backend::compiled(), vec![Backend::current()]);
+        assert!(Backend::FALLBACK_ORDER.contains(&Backend::current()));
+    }
+
+    #[test]
+    fn with_backends_skips_backends_that_are_not_compiled() {
+        let other = Backend::FALLBACK_ORDER
+            .iter()
+            .copied()
+            .find(|&b| b != Backend::current())
+            .unwrap();
+
+        let error = Mdns::with_backends(&[other]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidArgument);
+
+        if Backend::current().is_available() {
+            let mdns = Mdns::with_backends(&[other, Backend::current()]).unwrap();
+            assert_eq!(mdns.backend(), Backend::current());
+        }
+    }
+
+    #[test]
+    fn select_overrides_probing() {
+        assert_eq!(
+            Mdns::select(Backend::current()).unwrap().backend(),
+            Backend::current()
+        );
+
+        for &backend in Backend::FALLBACK_ORDER {
+            if backend != Backend::current() {
+                assert!(Mdns::select(backend).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn boxed_browser_and_service_are_trait_objects() {
+        let mdns = Mdns {
+            backend: Backend::current(),
+        };
+        let service_type = ServiceType::new("boxed", "tcp").unwrap();
+
+        let mut browser = mdns.boxed_browser(service_type.clone());
+        browser.set_domain("local");
+
+        let mut service = mdns.boxed_service(service_type, 8080);
+        service.set_name("boxed");
+    }
+
+    #[test]
+    fn only_bonjour_maps_ports() {
+        assert!(Backend::Bonjour
+            .capabilities()
+            .contains(Capabilities::PORT_MAPPING));
+        assert!(!Backend::Avahi
+            .capabilities()
+            .contains(Capabilities::PORT_MAPPING));
+        assert!(!Backend::Embedded
+            .capabilities()
+            .contains(Capabilities::PORT_MAPPING));
+    }
+}
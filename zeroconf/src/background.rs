@@ -0,0 +1,380 @@
+//! A crate-managed event loop that drives browsers and services on a single background thread.
+//!
+//! Each `MdnsBrowser` and `MdnsService` is normally kept running by polling its own event loop,
+//! which applications with many of them typically do on a thread each. Browsers and services
+//! passed to [`spawn_browser()`] and [`spawn_service()`] are instead moved to a single worker
+//! thread, started with the first of them, that polls all of them in turn every
+//! `PollIntervals::background_poll_interval()`.
+//!
+//! The callbacks of the browsers and services are invoked on the worker thread, and must not
+//! block as they would delay the events of every other browser and service.
+//!
+//! [`spawn_browser()`]: fn.spawn_browser.html
+//! [`spawn_service()`]: fn.spawn_service.html
+
+use crate::error::Error;
+use crate::prelude::*;
+use crate::{MdnsBrowser, MdnsService, Result};
+use std::mem;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+static WORKER: Mutex<Option<mpsc::Sender<Command>>> = Mutex::new(None);
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// A handle on a browser or service driven by the background event loop, returned by
+/// [`spawn_browser()`] and [`spawn_service()`].
+///
+/// Dropping the handle stops the browser or service, see [`stop()`] to wait for it to be
+/// released.
+///
+/// [`spawn_browser()`]: fn.spawn_browser.html
+/// [`spawn_service()`]: fn.spawn_service.html
+/// [`stop()`]: #method.stop
+#[derive(Debug)]
+pub struct BackgroundHandle {
+    id: u64,
+    /// The error its event loop has returned, after which it is no longer polled
+    failure: Arc<Mutex<Option<Error>>>,
+    stopped: bool,
+}
+
+impl BackgroundHandle {
+    /// Returns true unless polling the browser or service has failed, in which case the
+    /// background event loop has released it and [`stop()`] returns the error.
+    ///
+    /// [`stop()`]: #method.stop
+    pub fn is_running(&self) -> bool {
+        self.failure.lock().unwrap().is_none()
+    }
+
+    /// Stops the browser or service and waits for the background event loop to close it, see
+    /// `TMdnsBrowser::close()` and `TMdnsService::close()`.
+    ///
+    /// Returns the error polling it has failed with, or else the error closing it, if any.
+    pub fn stop(mut self) -> Result<()> {
+        self.stopped = true;
+
+        let (tx, rx) = mpsc::channel();
+        send(Command::Stop(self.id, Some(tx)))?;
+
+        // the worker only hangs up without answering if a callback has panicked
+        let result = rx.recv().unwrap_or(Ok(()));
+
+        match self.failure.lock().unwrap().take() {
+            Some(e) => Err(e),
+            None => result,
+        }
+    }
+}
+
+impl Drop for BackgroundHandle {
+    fn drop(&mut self) {
+        if !self.stopped {
+            send(Command::Stop(self.id, None)).ok();
+        }
+    }
+}
+
+/// Browses for services with the specified browser on the background event loop, which owns it
+/// from now on.
+///
+/// Returns an error if browsing could not be started.
+///
+/// # Examples
+/// ```no_run
+/// use zeroconf::prelude::*;
+/// use zeroconf::{MdnsBrowser, ServiceType};
+///
+/// let handles: Vec<_> = ["http", "ipp", "ssh"]
+///     .iter()
+///     .map(|name| {
+///         let mut browser = MdnsBrowser::new(ServiceType::new(name, "tcp").unwrap());
+///         browser.on_service_discovered(|result, _| println!("{:?}", result));
+///
+///         // all of the browsers are polled by the same thread
+///         zeroconf::background::spawn_browser(browser).unwrap()
+///     })
+///     .collect();
+///
+/// // ...
+///
+/// for handle in handles {
+///     handle.stop().unwrap();
+/// }
+/// ```
+pub fn spawn_browser(browser: MdnsBrowser) -> Result<BackgroundHandle> {
+    spawn(Pending::Browser(browser))
+}
+
+/// Registers the specified service on the background event loop, which owns it from now on.
+///
+/// Returns an error if the registration could not be started. The registration cannot be changed
+/// once spawned, poll the `RegistrationHandle` of the service instead if it needs to be.
+pub fn spawn_service(service: MdnsService) -> Result<BackgroundHandle> {
+    spawn(Pending::Service(service))
+}
+
+fn spawn(pending: Pending) -> Result<BackgroundHandle> {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    let failure = Arc::default();
+
+    let (tx, rx) = mpsc::channel();
+    send(Command::Start(
+        id,
+        Box::new(pending),
+        Arc::clone(&failure),
+        tx,
+    ))?;
+
+    rx.recv()
+        .unwrap_or_else(|_| Err("the background event loop has stopped".into()))?;
+
+    Ok(BackgroundHandle {
+        id,
+        failure,
+        stopped: false,
+    })
+}
+
+/// Sends `command` to the worker thread, which is started if it is not running yet (or no longer,
+/// if a callback has panicked).
+fn send(command: Command) -> Result<()> {
+    let mut worker = WORKER.lock().unwrap_or_else(|e| e.into_inner());
+
+    let command = match worker.as_ref() {
+        Some(tx) => match tx.send(command) {
+            Ok(()) => return Ok(()),
+            Err(mpsc::SendError(command)) => command,
+        },
+        None => command,
+    };
+
+    // a stopped worker has released its browsers and services already
+    if let Command::Stop(..) = command {
+        return Ok(());
+    }
+
+    let (tx, rx) = mpsc::channel();
+
+    thread::Builder::new()
+        .name("zeroconf-background".to_string())
+        .spawn(move || run(rx))
+        .map_err(|e| format!("could not start the background event loop: {}", e))?;
+
+    tx.send(command).ok();
+    *worker = Some(tx);
+
+    Ok(())
+}
+
+enum Command {
+    Start(
+        u64,
+        // boxed so that `Stop` commands are not as large as browsers and services
+        Box<Pending>,
+        Arc<Mutex<Option<Error>>>,
+        mpsc::Sender<Result<()>>,
+    ),
+    Stop(u64, Option<mpsc::Sender<Result<()>>>),
+}
+
+/// A browser or service sent to the worker thread, where it is started.
+enum Pending {
+    Browser(MdnsBrowser),
+    Service(MdnsService),
+}
+
+/// Closes a browser or service, see `TMdnsBrowser::close()` and `TMdnsService::close()`
+type Release = Box<dyn FnOnce() -> Result<()>>;
+
+/// A browser or service owned by the worker thread, along with the event loop borrowing it.
+struct Member {
+    id: u64,
+    event_loop: Option<Box<dyn TEventLoop>>,
+    /// Closes the boxed browser or service once its event loop has been dropped
+    release: Option<Release>,
+    failure: Arc<Mutex<Option<Error>>>,
+}
+
+impl Member {
+    fn start(id: u64, pending: Pending, failure: Arc<Mutex<Option<Error>>>) -> Result<Self> {
+        let (event_loop, release) = match pending {
+            Pending::Browser(browser) => Self::own(browser, browse, MdnsBrowser::close)?,
+            Pending::Service(service) => Self::own(service, register, MdnsService::close)?,
+        };
+
+        Ok(Self {
+            id,
+            event_loop: Some(event_loop),
+            release: Some(release),
+            failure,
+        })
+    }
+
+    /// Boxes `owner` so that it does not move while its event loop started with `start` is
+    /// running, and returns the event loop along with the function releasing `owner` with
+    /// `close`.
+    fn own<T: 'static>(
+        owner: T,
+        start: fn(&mut T) -> Result<Box<dyn TEventLoop + '_>>,
+        close: fn(T) -> Result<()>,
+    ) -> Result<(Box<dyn TEventLoop>, Release)> {
+        let owner: *mut T = Box::into_raw(Box::new(owner));
+
+        let event_loop = match start(unsafe { &mut *owner }) {
+            Ok(event_loop) => event_loop,
+            Err(e) => {
+                drop(unsafe { Box::from_raw(owner) });
+                return Err(e);
+            }
+        };
+
+        // SAFETY: the event loop borrows `*owner`, which is neither moved nor freed until the
+        // release is called. A `Member` always drops its event loop before calling its release,
+        // see `close()`, so the event loop does not outlive the borrow although its lifetime is
+        // erased to store it next to the release.
+        let event_loop =
+            unsafe { mem::transmute::<Box<dyn TEventLoop + '_>, Box<dyn TEventLoop>>(event_loop) };
+
+        Ok((
+            event_loop,
+            Box::new(move || close(*unsafe { Box::from_raw(owner) })),
+        ))
+    }
+
+    /// Polls the event loop once, returns false if it has failed.
+    fn poll(&self) -> bool {
+        let result = match &self.event_loop {
+            Some(event_loop) => event_loop.poll(Duration::from_secs(0)),
+            None => Ok(()),
+        };
+
+        match result {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("background event loop failed, releasing it: {}", e);
+                *self.failure.lock().unwrap() = Some(e);
+                false
+            }
+        }
+    }
+
+    /// Drops the event loop and closes the browser or service, in this order as the event loop
+    /// borrows it, see `own()`.
+    fn close(&mut self) -> Result<()> {
+        self.event_loop.take();
+
+        match self.release.take() {
+            Some(release) => release(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Member {
+    fn drop(&mut self) {
+        if let Err(e) = self.close() {
+            warn!("could not close background event loop: {}", e);
+        }
+    }
+}
+
+fn browse(browser: &mut MdnsBrowser) -> Result<Box<dyn TEventLoop + '_>> {
+    Ok(Box::new(browser.browse_services()?))
+}
+
+fn register(service: &mut MdnsService) -> Result<Box<dyn TEventLoop + '_>> {
+    Ok(Box::new(service.register()?))
+}
+
+/// Runs the worker thread, polling its members until every `Sender` of `commands` is gone.
+fn run(commands: mpsc::Receiver<Command>) {
+    let mut members: Vec<Member> = Vec::new();
+
+    loop {
+        // an idle worker waits for its next member without waking up
+        let command = if members.is_empty() {
+            match commands.recv() {
+                Ok(command) => Some(command),
+                Err(_) => return,
+            }
+        } else {
+            match commands.recv_timeout(*crate::poll_intervals().background_poll_interval()) {
+                Ok(command) => Some(command),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        };
+
+        for command in command.into_iter().chain(commands.try_iter()) {
+            match command {
+                Command::Start(id, pending, failure, reply) => {
+                    let result = Member::start(id, *pending, failure).map(|m| members.push(m));
+                    reply.send(result).ok();
+                }
+                Command::Stop(id, reply) => {
+                    let result = match members.iter().position(|m| m.id == id) {
+                        Some(i) => members.remove(i).close(),
+                        None => Ok(()),
+                    };
+
+                    match (result, reply) {
+                        (result, Some(reply)) => {
+                            reply.send(result).ok();
+                        }
+                        (Err(e), None) => warn!("could not close background event loop: {}", e),
+                        (Ok(()), None) => {}
+                    }
+                }
+            }
+        }
+
+        members.retain(Member::poll);
+    }
+}
+
+#[cfg(test)]
+#[cfg(feature = "mock")]
+mod tests {
+    use super::*;
+    use crate::ServiceType;
+
+    #[test]
+    fn spawned_browser_discovers_spawned_service_until_stopped() {
+        let service_type = ServiceType::new("mock-background", "tcp").unwrap();
+
+        let mut service = MdnsService::new(service_type.clone(), 8080);
+        service.set_name("background");
+        let service = spawn_service(service).unwrap();
+
+        let (tx, rx) = mpsc::channel();
+        let tx = Arc::new(Mutex::new(tx));
+        let callback_tx = tx.clone();
+
+        let mut browser = MdnsBrowser::new(service_type);
+        browser.on_service_discovered(move |s, _| {
+            callback_tx
+                .lock()
+                .unwrap()
+                .send(s.unwrap().name().clone())
+                .unwrap();
+        });
+        let browser = spawn_browser(browser).unwrap();
+
+        assert_eq!(
+            rx.recv_timeout(Duration::from_secs(5)).unwrap(),
+            "background"
+        );
+        assert!(browser.is_running());
+
+        browser.stop().unwrap();
+        service.stop().unwrap();
+
+        // the browser and its callback have been released
+        assert_eq!(Arc::strong_count(&tx), 1);
+    }
+}
@@ -1,13 +1,40 @@
 //! Trait definition for cross-platform browser
 
-use crate::{EventLoop, NetworkInterface, Result, ServiceType, TxtRecord};
+use crate::channel::EventSender;
+use crate::error::Error;
+use crate::ffi;
+use crate::prelude::TEventLoop;
+use crate::{
+    AddressFamily, AddressPolicy, DaemonReconnectedCallback, ErrorCallback, EventLoop, EventMeta,
+    NetworkInterface, Record, Result, ServiceFlags, ServiceType, TxtRecord,
+};
 use std::any::Any;
-use std::sync::Arc;
+use std::cell::RefCell;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::net::{IpAddr, SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use std::{io, vec};
 
 /// Interface for interacting with underlying mDNS implementation service browsing capabilities.
 pub trait TMdnsBrowser {
     /// Creates a new `MdnsBrowser` that browses for the specified `kind` (e.g. `_http._tcp`)
-    fn new(service_type: ServiceType) -> Self;
+    fn new(service_type: ServiceType) -> Self
+    where
+        Self: Sized;
+
+    /// Returns a [`BrowserBuilder`] that creates a browser for the specified `service_type` with
+    /// the options set on the builder.
+    ///
+    /// [`BrowserBuilder`]: struct.BrowserBuilder.html
+    fn builder(service_type: ServiceType) -> BrowserBuilder<Self>
+    where
+        Self: Sized + 'static,
+    {
+        BrowserBuilder::new(service_type)
+    }
 
     /// Sets the network interface on which to browse for services on.
     ///
@@ -15,6 +42,59 @@ pub trait TMdnsBrowser {
     /// on all available interfaces.
     fn set_network_interface(&mut self, interface: NetworkInterface);
 
+    /// Sets the domain in which to browse for services.
+    ///
+    /// Most applications will want to use the default domain (usually `local`). Set this to a
+    /// unicast DNS-SD domain (e.g. `example.com`) to browse a wide-area zone.
+    fn set_domain(&mut self, domain: &str);
+
+    /// Sets the [`AddressFamily`] to resolve discovered services to.
+    ///
+    /// Most applications will want to use the default value `AddressFamily::Both`. Applications
+    /// that can only connect using one protocol may restrict resolution to it.
+    ///
+    /// [`AddressFamily`]: ../enum.AddressFamily.html
+    fn set_address_family(&mut self, address_family: AddressFamily);
+
+    /// Sets the [`AddressPolicy`] that orders the addresses a service has been resolved to.
+    ///
+    /// # Note
+    /// Avahi only resolves a single address per service, the policy has no effect.
+    ///
+    /// [`AddressPolicy`]: ../enum.AddressPolicy.html
+    fn set_address_policy(&mut self, address_policy: AddressPolicy);
+
+    /// Sets the [`ServiceFlags`] to browse and resolve services with.
+    ///
+    /// [`ServiceFlags`]: ../flags/struct.ServiceFlags.html
+    fn set_flags(&mut self, flags: ServiceFlags);
+
+    /// Sets whether the raw resource records of a service instance (e.g. its SRV and TXT records,
+    /// as well as any nonstandard records) are reported in `ServiceDiscovery::raw_records`.
+    ///
+    /// This is intended for protocols that announce records this crate does not parse. Defaults
+    /// to `false`.
+    fn set_include_raw_records(&mut self, include_raw_records: bool);
+
+    /// Sets how many of the discovered services are resolved at once, at least one. The services
+    /// discovered while as many are being resolved are queued, and resolved in the order they
+    /// were discovered once the others have been. Defaults to 16.
+    ///
+    /// # Note
+    /// Bonjour and systemd-resolved resolve one service at a time, and the embedded
+    /// implementation resolves services from the announcements it receives, the concurrency has
+    /// no effect on them.
+    fn set_resolve_concurrency(&mut self, concurrency: usize);
+
+    /// Sets how long the browser remembers a service that has gone away. A service that comes
+    /// back within the window (e.g. a device with poor Wi-Fi) is not reported again, so that the
+    /// services reported by the browser remain stable. Defaults to zero, which reports every
+    /// service that comes back.
+    ///
+    /// # Note
+    /// Avahi over D-Bus, systemd-resolved and the native Windows API are not debounced.
+    fn set_debounce(&mut self, window: Duration);
+
     /// Sets the [`ServiceDiscoveredCallback`] that is invoked when the browser has discovered and
     /// resolved a service.
     ///
@@ -24,12 +104,455 @@ pub trait TMdnsBrowser {
         service_discovered_callback: Box<ServiceDiscoveredCallback>,
     );
 
+    /// Same as [`set_service_discovered_callback()`] but accepts any closure, including ones that
+    /// mutate the state they capture.
+    ///
+    /// [`set_service_discovered_callback()`]: #tymethod.set_service_discovered_callback
+    fn on_service_discovered<F>(&mut self, callback: F)
+    where
+        F: FnMut(Result<ServiceDiscovery>, Option<Arc<dyn Any + Send + Sync>>) + Send + 'static,
+        Self: Sized,
+    {
+        // callbacks are never invoked while another one is running
+        let callback = RefCell::new(callback);
+        self.set_service_discovered_callback(Box::new(move |r, c| (callback.borrow_mut())(r, c)));
+    }
+
+    /// Sets the [`ServiceBatchCallback`] that is invoked instead of the
+    /// [`ServiceDiscoveredCallback`] with the services discovered during a burst of results,
+    /// once the burst has ended. This saves invoking a callback (e.g. one that updates a UI) for
+    /// each of the services of a network with many instances.
+    ///
+    /// Errors are delivered in the batch as well, unless an [`ErrorCallback`] is set. Setting a
+    /// `ServiceDiscoveredCallback` afterwards replaces the batch callback, and vice versa.
+    ///
+    /// # Note
+    /// Bonjour ends a burst once it no longer reports `kDNSServiceFlagsMoreComing`, Avahi once it
+    /// has reported `AVAHI_BROWSER_ALL_FOR_NOW` and resolved all of the services discovered in the
+    /// meantime, and the mock implementation at the end of each poll. The other implementations
+    /// deliver each event in a batch of its own.
+    ///
+    /// [`ServiceBatchCallback`]: ../type.ServiceBatchCallback.html
+    /// [`ServiceDiscoveredCallback`]: ../type.ServiceDiscoveredCallback.html
+    /// [`ErrorCallback`]: ../error/type.ErrorCallback.html
+    fn set_service_batch_callback(&mut self, service_batch_callback: Box<ServiceBatchCallback>) {
+        self.set_service_discovered_callback(Box::new(move |result, context| {
+            service_batch_callback(vec![BrowserEvent::from(result)], context)
+        }));
+    }
+
+    /// Sends the events of the browser to the specified `sender` of an [`event_channel()`]
+    /// instead of invoking a [`ServiceDiscoveredCallback`], which this replaces. The events can
+    /// then be received on another thread without the buffer growing without bounds if that
+    /// thread cannot keep up, see [`OverflowPolicy`].
+    ///
+    /// Errors are sent as well, unless an [`ErrorCallback`] is set.
+    ///
+    /// [`event_channel()`]: ../channel/fn.event_channel.html
+    /// [`ServiceDiscoveredCallback`]: ../type.ServiceDiscoveredCallback.html
+    /// [`OverflowPolicy`]: ../channel/enum.OverflowPolicy.html
+    /// [`ErrorCallback`]: ../error/type.ErrorCallback.html
+    fn set_event_sender(&mut self, sender: EventSender<BrowserEvent>) {
+        self.set_service_discovered_callback(Box::new(move |result, _| {
+            // the events are not wanted anymore once the receiver has been dropped
+            sender.send(result.into()).ok();
+        }));
+    }
+
     /// Sets the optional user context to pass through to the callback. This is useful if you need
-    /// to share state between pre and post-callback. The context type must implement `Any`.
-    fn set_context(&mut self, context: Box<dyn Any>);
+    /// to share state between pre and post-callback. The context type must implement `Any`, `Send`
+    /// and `Sync`.
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>);
+
+    /// Sets the optional [`DaemonReconnectedCallback`] that is invoked when the browser has
+    /// reconnected to the mDNS daemon after it was restarted.
+    ///
+    /// The browser reconnects and resumes browsing transparently, services that are still
+    /// available are discovered again.
+    ///
+    /// [`DaemonReconnectedCallback`]: ../daemon/type.DaemonReconnectedCallback.html
+    fn set_reconnected_callback(&mut self, reconnected_callback: Box<DaemonReconnectedCallback>);
+
+    /// Sets the optional [`ErrorCallback`] that is invoked with the errors of the browser (e.g. a
+    /// service that could not be resolved) instead of the [`ServiceDiscoveredCallback`], which
+    /// then only receives discovered services.
+    ///
+    /// [`ErrorCallback`]: ../error/type.ErrorCallback.html
+    /// [`ServiceDiscoveredCallback`]: ../type.ServiceDiscoveredCallback.html
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>);
+
+    /// Returns a [`BrowserControl`] to pause, resume or stop this browser from another component
+    /// than the one that owns it.
+    ///
+    /// [`BrowserControl`]: struct.BrowserControl.html
+    fn control(&self) -> BrowserControl;
 
     /// Starts the browser. Returns an `EventLoop` which can be called to keep the browser alive.
-    fn browse_services(&mut self) -> Result<EventLoop>;
+    fn browse_services(&mut self) -> Result<EventLoop<'_>>;
+
+    /// Stops the browser and releases what it holds (e.g. its `DNSServiceRef`s or Avahi objects)
+    /// before returning, unlike dropping it which only logs the errors of releasing them.
+    ///
+    /// Returns the first error reported while releasing the browser, the rest is released
+    /// regardless. A connection to the mDNS daemon shared with other browsers or services of the
+    /// thread is closed once the last of them is.
+    fn close(self) -> Result<()>
+    where
+        Self: Sized,
+    {
+        drop(self);
+        Ok(())
+    }
+}
+
+/// The status of a browser, reported by [`BrowserControl::status()`].
+///
+/// [`BrowserControl::status()`]: struct.BrowserControl.html#method.status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BrowserStatus {
+    /// The browser reports the services it discovers
+    Running,
+    /// The browser does not report services until it is resumed
+    Paused,
+    /// The browser no longer reports services
+    Stopped,
+}
+
+const STATUS_RUNNING: u8 = 0;
+const STATUS_PAUSED: u8 = 1;
+const STATUS_STOPPED: u8 = 2;
+
+/// A handle to pause, resume or stop a browser, returned by [`TMdnsBrowser::control()`].
+///
+/// Unlike the browser, the handle is cheap to clone and `Send` and `Sync`, so one component can
+/// start the browser and hand its control to another. All clones control the same browser.
+///
+/// A paused or stopped browser does not invoke its callbacks, the services discovered while it is
+/// paused are not reported once it is resumed. The browser is only freed once it is dropped by
+/// its owner, which keeps polling its event loop until then.
+///
+/// # Examples
+/// ```no_run
+/// use std::thread;
+/// use std::time::Duration;
+/// use zeroconf::prelude::*;
+/// use zeroconf::{MdnsBrowser, ServiceType};
+///
+/// let mut browser = MdnsBrowser::new(ServiceType::new("http", "tcp").unwrap());
+/// browser.set_service_discovered_callback(Box::new(|service, _| println!("{:?}", service)));
+///
+/// let control = browser.control();
+/// let remote_control = control.clone();
+///
+/// thread::spawn(move || {
+///     thread::sleep(Duration::from_secs(10));
+///     remote_control.stop();
+/// });
+///
+/// let event_loop = browser.browse_services().unwrap();
+///
+/// while !control.is_stopped() {
+///     event_loop.poll(Duration::from_millis(100)).unwrap();
+/// }
+/// ```
+///
+/// [`TMdnsBrowser::control()`]: trait.TMdnsBrowser.html#tymethod.control
+#[derive(Debug, Clone, Default)]
+pub struct BrowserControl {
+    status: Arc<AtomicU8>,
+}
+
+impl BrowserControl {
+    /// Pauses the browser if it is running.
+    pub fn pause(&self) {
+        self.transition(STATUS_RUNNING, STATUS_PAUSED);
+    }
+
+    /// Resumes the browser if it is paused.
+    pub fn resume(&self) {
+        self.transition(STATUS_PAUSED, STATUS_RUNNING);
+    }
+
+    /// Stops the browser. A stopped browser cannot be resumed.
+    pub fn stop(&self) {
+        self.status.store(STATUS_STOPPED, Ordering::SeqCst);
+    }
+
+    /// Returns the status of the browser.
+    pub fn status(&self) -> BrowserStatus {
+        match self.status.load(Ordering::SeqCst) {
+            STATUS_RUNNING => BrowserStatus::Running,
+            STATUS_PAUSED => BrowserStatus::Paused,
+            _ => BrowserStatus::Stopped,
+        }
+    }
+
+    /// Returns `true` if the browser has been stopped.
+    pub fn is_stopped(&self) -> bool {
+        self.status() == BrowserStatus::Stopped
+    }
+
+    /// Returns `true` if the browser reports the services it discovers.
+    pub(crate) fn is_running(&self) -> bool {
+        self.status() == BrowserStatus::Running
+    }
+
+    fn transition(&self, from: u8, to: u8) {
+        // the status is left unchanged if it is not `from`
+        self.status
+            .compare_exchange(from, to, Ordering::SeqCst, Ordering::SeqCst)
+            .ok();
+    }
+}
+
+/// Creates a browser with the options set on it, see [`TMdnsBrowser::builder()`].
+///
+/// The options are applied when the browser is built, in any order.
+///
+/// # Examples
+/// ```no_run
+/// use std::time::Duration;
+/// use zeroconf::prelude::*;
+/// use zeroconf::{MdnsBrowser, NetworkInterface, ServiceType};
+///
+/// let mut browser = MdnsBrowser::builder(ServiceType::new("http", "tcp").unwrap())
+///     .network_interface(NetworkInterface::Unspec)
+///     .domain("local")
+///     .on_service_discovered(|service, _| println!("Service discovered: {:?}", service))
+///     .build();
+///
+/// let event_loop = browser.browse_services().unwrap();
+///
+/// loop {
+///     event_loop.poll(Duration::from_secs(0)).unwrap();
+/// }
+/// ```
+///
+/// [`TMdnsBrowser::builder()`]: trait.TMdnsBrowser.html#method.builder
+pub struct BrowserBuilder<B> {
+    service_type: ServiceType,
+    options: Vec<BuilderOption<B>>,
+}
+
+/// An option of a `BrowserBuilder`, applied to the browser it builds
+type BuilderOption<B> = Box<dyn FnOnce(&mut B)>;
+
+impl<B: TMdnsBrowser + 'static> BrowserBuilder<B> {
+    /// Creates a new builder of a browser for the specified `service_type`.
+    pub fn new(service_type: ServiceType) -> Self {
+        Self {
+            service_type,
+            options: vec![],
+        }
+    }
+
+    /// See [`TMdnsBrowser::set_network_interface()`].
+    ///
+    /// [`TMdnsBrowser::set_network_interface()`]: trait.TMdnsBrowser.html#tymethod.set_network_interface
+    pub fn network_interface(self, interface: NetworkInterface) -> Self {
+        self.option(move |b| b.set_network_interface(interface))
+    }
+
+    /// See [`TMdnsBrowser::set_domain()`].
+    ///
+    /// [`TMdnsBrowser::set_domain()`]: trait.TMdnsBrowser.html#tymethod.set_domain
+    pub fn domain(self, domain: &str) -> Self {
+        let domain = domain.to_string();
+        self.option(move |b| b.set_domain(&domain))
+    }
+
+    /// See [`TMdnsBrowser::set_address_family()`].
+    ///
+    /// [`TMdnsBrowser::set_address_family()`]: trait.TMdnsBrowser.html#tymethod.set_address_family
+    pub fn address_family(self, address_family: AddressFamily) -> Self {
+        self.option(move |b| b.set_address_family(address_family))
+    }
+
+    /// See [`TMdnsBrowser::set_address_policy()`].
+    ///
+    /// [`TMdnsBrowser::set_address_policy()`]: trait.TMdnsBrowser.html#tymethod.set_address_policy
+    pub fn address_policy(self, address_policy: AddressPolicy) -> Self {
+        self.option(move |b| b.set_address_policy(address_policy))
+    }
+
+    /// See [`TMdnsBrowser::set_flags()`].
+    ///
+    /// [`TMdnsBrowser::set_flags()`]: trait.TMdnsBrowser.html#tymethod.set_flags
+    pub fn flags(self, flags: ServiceFlags) -> Self {
+        self.option(move |b| b.set_flags(flags))
+    }
+
+    /// See [`TMdnsBrowser::set_include_raw_records()`].
+    ///
+    /// [`TMdnsBrowser::set_include_raw_records()`]: trait.TMdnsBrowser.html#tymethod.set_include_raw_records
+    pub fn include_raw_records(self, include_raw_records: bool) -> Self {
+        self.option(move |b| b.set_include_raw_records(include_raw_records))
+    }
+
+    /// See [`TMdnsBrowser::set_resolve_concurrency()`].
+    ///
+    /// [`TMdnsBrowser::set_resolve_concurrency()`]: trait.TMdnsBrowser.html#tymethod.set_resolve_concurrency
+    pub fn resolve_concurrency(self, concurrency: usize) -> Self {
+        self.option(move |b| b.set_resolve_concurrency(concurrency))
+    }
+
+    /// See [`TMdnsBrowser::set_debounce()`].
+    ///
+    /// [`TMdnsBrowser::set_debounce()`]: trait.TMdnsBrowser.html#tymethod.set_debounce
+    pub fn debounce(self, window: Duration) -> Self {
+        self.option(move |b| b.set_debounce(window))
+    }
+
+    /// See [`TMdnsBrowser::on_service_discovered()`].
+    ///
+    /// [`TMdnsBrowser::on_service_discovered()`]: trait.TMdnsBrowser.html#method.on_service_discovered
+    pub fn on_service_discovered<F>(self, callback: F) -> Self
+    where
+        F: FnMut(Result<ServiceDiscovery>, Option<Arc<dyn Any + Send + Sync>>) + Send + 'static,
+    {
+        self.option(move |b| b.on_service_discovered(callback))
+    }
+
+    /// See [`TMdnsBrowser::set_service_batch_callback()`].
+    ///
+    /// [`TMdnsBrowser::set_service_batch_callback()`]: trait.TMdnsBrowser.html#method.set_service_batch_callback
+    pub fn on_service_batch<F>(self, callback: F) -> Self
+    where
+        F: Fn(Vec<BrowserEvent>, Option<Arc<dyn Any + Send + Sync>>) + Send + 'static,
+    {
+        self.option(move |b| b.set_service_batch_callback(Box::new(callback)))
+    }
+
+    /// See [`TMdnsBrowser::set_event_sender()`].
+    ///
+    /// [`TMdnsBrowser::set_event_sender()`]: trait.TMdnsBrowser.html#method.set_event_sender
+    pub fn event_sender(self, sender: EventSender<BrowserEvent>) -> Self {
+        self.option(move |b| b.set_event_sender(sender))
+    }
+
+    /// See [`TMdnsBrowser::set_context()`].
+    ///
+    /// [`TMdnsBrowser::set_context()`]: trait.TMdnsBrowser.html#tymethod.set_context
+    pub fn context<T: Any + Send + Sync>(self, context: T) -> Self {
+        self.option(move |b| b.set_context(Box::new(context)))
+    }
+
+    /// See [`TMdnsBrowser::set_reconnected_callback()`].
+    ///
+    /// [`TMdnsBrowser::set_reconnected_callback()`]: trait.TMdnsBrowser.html#tymethod.set_reconnected_callback
+    pub fn on_reconnected<F>(self, callback: F) -> Self
+    where
+        F: Fn(Option<Arc<dyn Any + Send + Sync>>) + Send + 'static,
+    {
+        self.option(move |b| b.set_reconnected_callback(Box::new(callback)))
+    }
+
+    /// See [`TMdnsBrowser::set_error_callback()`].
+    ///
+    /// [`TMdnsBrowser::set_error_callback()`]: trait.TMdnsBrowser.html#tymethod.set_error_callback
+    pub fn on_error<F>(self, callback: F) -> Self
+    where
+        F: Fn(Error, Option<Arc<dyn Any + Send + Sync>>) + Send + 'static,
+    {
+        self.option(move |b| b.set_error_callback(Box::new(callback)))
+    }
+
+    /// Creates the browser with the options set on this builder.
+    pub fn build(self) -> B {
+        let mut browser = B::new(self.service_type);
+
+        for option in self.options {
+            option(&mut browser);
+        }
+
+        browser
+    }
+
+    fn option(mut self, option: impl FnOnce(&mut B) + 'static) -> Self {
+        self.options.push(Box::new(option));
+        self
+    }
+}
+
+/// Browses for services of the specified `service_type` for `duration`, then stops browsing and
+/// returns.
+///
+/// `callback` is invoked on the calling thread with each service that is discovered. An error of
+/// the browser (e.g. a service that could not be resolved) is passed to `callback` as well and
+/// stops browsing, after which this returns early. Unlike the callbacks of a [`MdnsBrowser`], it does not need to be
+/// `'static` or `Send` and may borrow local state, which avoids sharing it through an
+/// `Arc<Mutex<_>>` for short-lived discovery tasks.
+///
+/// # Examples
+/// ```no_run
+/// use std::time::Duration;
+/// use zeroconf::ServiceType;
+///
+/// let mut names = vec![];
+///
+/// zeroconf::browse_scoped(
+///     ServiceType::new("http", "tcp").unwrap(),
+///     Duration::from_secs(5),
+///     |event| match event {
+///         Ok(service) => names.push(service.name().clone()),
+///         Err(e) => eprintln!("could not resolve service: {}", e),
+///     },
+/// )
+/// .unwrap();
+///
+/// println!("Discovered services: {:?}", names);
+/// ```
+///
+/// [`MdnsBrowser`]: type.MdnsBrowser.html
+pub fn browse_scoped<F>(
+    service_type: ServiceType,
+    duration: Duration,
+    mut callback: F,
+) -> Result<()>
+where
+    F: FnMut(Result<ServiceDiscovery>),
+{
+    let (tx, rx) = mpsc::channel();
+    // taken by the error callback to close the channel once an error has been sent
+    let tx = Arc::new(Mutex::new(Some(tx)));
+    let error_tx = tx.clone();
+
+    let mut browser = crate::MdnsBrowser::new(service_type);
+
+    browser.set_service_discovered_callback(Box::new(move |result, _| {
+        // the receiver outlives the browser
+        if let Some(tx) = &*tx.lock().unwrap() {
+            tx.send(result).ok();
+        }
+    }));
+
+    browser.set_error_callback(Box::new(move |error, _| {
+        if let Some(tx) = error_tx.lock().unwrap().take() {
+            tx.send(Err(error)).ok();
+        }
+    }));
+
+    let event_loop = browser.browse_services()?;
+    let deadline = Instant::now() + duration;
+    let poll_timeout = *crate::poll_intervals().scoped_poll_timeout();
+
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+
+        if remaining == Duration::from_secs(0) {
+            return Ok(());
+        }
+
+        event_loop.poll(remaining.min(poll_timeout))?;
+
+        loop {
+            match rx.try_recv() {
+                Ok(event) => callback(event),
+                Err(mpsc::TryRecvError::Empty) => break,
+                Err(mpsc::TryRecvError::Disconnected) => return Ok(()),
+            }
+        }
+    }
 }
 
 /// Callback invoked from [`MdnsBrowser`] once a service has been discovered and resolved.
@@ -39,20 +562,211 @@ pub trait TMdnsBrowser {
 /// * `context` - The optional user context passed through
 ///
 /// [`MdnsBrowser`]: type.MdnsBrowser.html
-pub type ServiceDiscoveredCallback = dyn Fn(Result<ServiceDiscovery>, Option<Arc<dyn Any>>);
+pub type ServiceDiscoveredCallback =
+    dyn Fn(Result<ServiceDiscovery>, Option<Arc<dyn Any + Send + Sync>>) + Send;
+
+/// Callback invoked from [`MdnsBrowser`] with the events of a burst once it has ended, see
+/// [`TMdnsBrowser::set_service_batch_callback()`].
+///
+/// # Arguments
+/// * `events` - The events of the burst, in the order they occurred
+/// * `context` - The optional user context passed through
+///
+/// [`MdnsBrowser`]: type.MdnsBrowser.html
+/// [`TMdnsBrowser::set_service_batch_callback()`]: browser/trait.TMdnsBrowser.html#method.set_service_batch_callback
+pub type ServiceBatchCallback =
+    dyn Fn(Vec<BrowserEvent>, Option<Arc<dyn Any + Send + Sync>>) + Send;
+
+/// An event of a [`MdnsBrowser`], delivered to the [`ServiceBatchCallback`].
+///
+/// [`MdnsBrowser`]: type.MdnsBrowser.html
+/// [`ServiceBatchCallback`]: type.ServiceBatchCallback.html
+#[derive(Debug)]
+// errors are rare, boxing the discoveries would cost an allocation for each of them instead
+#[allow(clippy::large_enum_variant)]
+pub enum BrowserEvent {
+    /// A service has been discovered and resolved
+    Discovered(ServiceDiscovery),
+    /// The browser reported an error, e.g. a service that could not be resolved
+    Error(Error),
+}
+
+impl From<Result<ServiceDiscovery>> for BrowserEvent {
+    fn from(result: Result<ServiceDiscovery>) -> Self {
+        match result {
+            Ok(service) => Self::Discovered(service),
+            Err(e) => Self::Error(e),
+        }
+    }
+}
 
 /// Represents a service that has been discovered by a [`MdnsBrowser`].
 ///
+/// Discoveries are equal if they are of the same service instance, i.e. have the same name,
+/// service type and domain, regardless of the sub-types they were browsed with or of where the
+/// instance was resolved to. This allows tracking the presence of services in a `HashSet` or
+/// `HashMap`.
+///
+/// The fields can be moved out without being cloned with `into_parts()`, which returns them in
+/// the order they are declared in.
+///
 /// [`MdnsBrowser`]: type.MdnsBrowser.html
-#[derive(
-    Debug, Getters, Builder, BuilderDelegate, Serialize, Deserialize, Clone, PartialEq, Eq,
-)]
+#[derive(Debug, Getters, Dissolve, Builder, BuilderDelegate, Serialize, Deserialize, Clone)]
+#[dissolve(rename = "into_parts")]
 pub struct ServiceDiscovery {
     name: String,
+    /// The type of the service, parsed from the registration type reported by the mDNS
+    /// implementation (e.g. `_http._tcp.`)
     service_type: ServiceType,
     domain: String,
     host_name: String,
-    address: String,
+    address: IpAddr,
+    /// All addresses the service has been resolved to, ordered by the browser's
+    /// `AddressPolicy`. `address` is the first of them.
+    #[builder(default)]
+    addresses: Vec<IpAddr>,
+    /// The scope ID (i.e. interface index) of a link-local IPv6 address, `0` otherwise
+    #[builder(default)]
+    scope_id: u32,
     port: u16,
+    /// The priority of the service's SRV record, lower values are preferred
+    ///
+    /// With Avahi, the priority and weight are only reported if raw records are included with
+    /// `set_include_raw_records()`, they are `0` otherwise.
+    #[builder(default)]
+    priority: u16,
+    /// The relative weight of the service's SRV record among services with the same priority
+    #[builder(default)]
+    weight: u16,
     txt: Option<TxtRecord>,
+    /// The raw resource records of the service instance, if enabled with
+    /// `set_include_raw_records()`
+    #[builder(default)]
+    raw_records: Vec<Record>,
+    /// The details of the discovery specific to the mDNS implementation
+    #[builder(default)]
+    meta: EventMeta,
+    /// When the service was discovered, on the monotonic clock (see `age()`). Deserialized
+    /// discoveries are stamped with the time they were deserialized at.
+    #[builder(default = "Instant::now()")]
+    #[serde(skip, default = "Instant::now")]
+    instant: Instant,
+    /// When the service was discovered, according to the system clock
+    #[builder(default = "SystemTime::now()")]
+    timestamp: SystemTime,
+}
+
+impl ServiceDiscovery {
+    /// Returns the `SocketAddr` to connect to the discovered service at.
+    ///
+    /// Link-local IPv6 addresses include the scope ID of the interface they were discovered on.
+    pub fn socket_addr(&self) -> SocketAddr {
+        self.socket_addr_of(self.address)
+    }
+
+    /// Returns the textual representation of the address, including the zone of link-local IPv6
+    /// addresses (e.g. `fe80::1%en0`).
+    pub fn scoped_address(&self) -> String {
+        ffi::format_scoped_ip(&self.address, self.scope_id)
+    }
+
+    /// Returns how long ago the service was discovered, e.g. to expire services that have not
+    /// been discovered again for a while.
+    pub fn age(&self) -> Duration {
+        self.instant.elapsed()
+    }
+
+    /// Stamps the service as discovered now, for backends that report the same discovery
+    /// multiple times.
+    #[cfg(feature = "mock")]
+    pub(crate) fn touch(&mut self) {
+        self.instant = Instant::now();
+        self.timestamp = SystemTime::now();
+    }
+
+    /// Returns the URI of the discovered service with the specified `scheme`, e.g.
+    /// `http://192.168.1.5:8080`.
+    ///
+    /// IPv6 addresses are enclosed in brackets, and the zone of link-local addresses is
+    /// percent-encoded as specified by RFC 6874 (e.g. `http://[fe80::1%25en0]:8080`).
+    pub fn to_uri(&self, scheme: &str) -> String {
+        match self.address {
+            IpAddr::V4(ip) => format!("{}://{}:{}", scheme, ip, self.port),
+            IpAddr::V6(_) => format!(
+                "{}://[{}]:{}",
+                scheme,
+                self.scoped_address().replace('%', "%25"),
+                self.port
+            ),
+        }
+    }
+
+    /// Returns the `SocketAddr` of `address` at the port of the service. Only link-local IPv6
+    /// addresses are scoped to the interface the service was discovered on.
+    fn socket_addr_of(&self, address: IpAddr) -> SocketAddr {
+        let scope_id = ffi::link_local_scope_id(&address, self.scope_id);
+
+        match address {
+            IpAddr::V4(ip) => SocketAddrV4::new(ip, self.port).into(),
+            IpAddr::V6(ip) => SocketAddrV6::new(ip, self.port, 0, scope_id).into(),
+        }
+    }
+
+    /// Returns the fields identifying the service instance.
+    fn identity(&self) -> (&str, &str, &str, &str) {
+        (
+            &self.name,
+            self.service_type.name(),
+            self.service_type.protocol(),
+            &self.domain,
+        )
+    }
+}
+
+impl PartialEq for ServiceDiscovery {
+    fn eq(&self, other: &Self) -> bool {
+        self.identity() == other.identity()
+    }
+}
+
+impl Eq for ServiceDiscovery {}
+
+impl Hash for ServiceDiscovery {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.identity().hash(state);
+    }
+}
+
+impl From<&ServiceDiscovery> for SocketAddr {
+    fn from(service: &ServiceDiscovery) -> Self {
+        service.socket_addr()
+    }
+}
+
+impl ToSocketAddrs for ServiceDiscovery {
+    type Iter = vec::IntoIter<SocketAddr>;
+
+    /// Returns the socket addresses of all addresses the service has been resolved to, in the
+    /// order of the browser's `AddressPolicy`, so that e.g. `TcpStream::connect()` falls back to
+    /// the next address if the service cannot be reached at the first one.
+    fn to_socket_addrs(&self) -> io::Result<Self::Iter> {
+        let addresses = if self.addresses.is_empty() {
+            vec![self.socket_addr()]
+        } else {
+            self.addresses
+                .iter()
+                .map(|a| self.socket_addr_of(*a))
+                .collect()
+        };
+
+        Ok(addresses.into_iter())
+    }
+}
+
+impl fmt::Display for ServiceDiscovery {
+    /// Formats the URI of the discovered service, using the name of its service type as the
+    /// scheme (e.g. `http://192.168.1.5:8080` for a `_http._tcp` service).
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_uri(self.service_type.name()))
+    }
 }
@@ -0,0 +1,381 @@
+//! Lock-free implementation of the channels created with `lock_free_event_channel()`
+
+use super::OverflowPolicy;
+use std::cell::UnsafeCell;
+use std::hint;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{RecvTimeoutError, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The state shared by the halves of a lock-free channel. Neither sending nor receiving ever
+/// takes a lock, so that an event is handed off by the callbacks of a browser without contending
+/// with the consumer, at the cost of waiting by spinning and sleeping instead of being woken up.
+pub(super) struct Channel<T> {
+    ring: Ring<T>,
+    /// The number of events the channel buffers at most, as the ring may have room for more
+    capacity: usize,
+    /// The number of events in the ring, including those being pushed or popped
+    len: AtomicUsize,
+    policy: OverflowPolicy,
+    senders: AtomicUsize,
+    receiving: AtomicBool,
+    dropped: AtomicU64,
+}
+
+impl<T> Channel<T> {
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            ring: Ring::new(capacity),
+            capacity: capacity.max(1),
+            len: AtomicUsize::new(0),
+            policy,
+            senders: AtomicUsize::new(1),
+            receiving: AtomicBool::new(true),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn policy(&self) -> OverflowPolicy {
+        self.policy
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn add_sender(&self) {
+        self.senders.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn remove_sender(&self) {
+        // releases the events pushed by the sender to a receiver that observes it was the last
+        self.senders.fetch_sub(1, Ordering::Release);
+    }
+
+    pub fn send(&self, mut event: T) -> Result<(), T> {
+        let mut backoff = Backoff::new();
+
+        loop {
+            if !self.receiving.load(Ordering::Acquire) {
+                return Err(event);
+            }
+
+            match self.push(event) {
+                Ok(()) => return Ok(()),
+                Err(e) => event = e,
+            }
+
+            match self.policy {
+                OverflowPolicy::DropOldest => {
+                    // the receiver may have made room meanwhile, in which case nothing is dropped
+                    if self.pop().is_some() {
+                        self.dropped.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                OverflowPolicy::DropNewest => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return Ok(());
+                }
+                OverflowPolicy::Block => backoff.wait(),
+            }
+        }
+    }
+
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        if let Some(event) = self.pop() {
+            return Ok(event);
+        }
+
+        if self.senders.load(Ordering::Acquire) == 0 {
+            // the last sender may have pushed an event after the ring was found empty
+            return self.pop().ok_or(TryRecvError::Disconnected);
+        }
+
+        Err(TryRecvError::Empty)
+    }
+
+    pub fn recv_deadline(&self, deadline: Option<Instant>) -> Result<T, RecvTimeoutError> {
+        let mut backoff = Backoff::new();
+
+        loop {
+            match self.try_recv() {
+                Ok(event) => return Ok(event),
+                Err(TryRecvError::Disconnected) => return Err(RecvTimeoutError::Disconnected),
+                Err(TryRecvError::Empty) => {}
+            }
+
+            if matches!(deadline, Some(deadline) if Instant::now() >= deadline) {
+                return Err(RecvTimeoutError::Timeout);
+            }
+
+            backoff.wait();
+        }
+    }
+
+    /// Called when the receiver is dropped, so that the senders stop sending and the buffered
+    /// events are dropped right away rather than with the last sender.
+    pub fn close(&self) {
+        self.receiving.store(false, Ordering::Release);
+        while self.pop().is_some() {}
+    }
+
+    /// Pushes `event` unless `capacity` events are buffered (or the ring is full while other
+    /// threads are popping from it), in which case it is returned back.
+    fn push(&self, event: T) -> Result<(), T> {
+        let mut len = self.len.load(Ordering::Relaxed);
+
+        loop {
+            if len >= self.capacity {
+                return Err(event);
+            }
+
+            match self
+                .len
+                .compare_exchange_weak(len, len + 1, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => break,
+                Err(current) => len = current,
+            }
+        }
+
+        self.ring.push(event).inspect_err(|_| {
+            self.len.fetch_sub(1, Ordering::Relaxed);
+        })
+    }
+
+    fn pop(&self) -> Option<T> {
+        let event = self.ring.pop()?;
+        self.len.fetch_sub(1, Ordering::Relaxed);
+        Some(event)
+    }
+}
+
+/// Waits increasingly long between the attempts to send to a full ring or receive from an empty
+/// one: spinning first so that an event arriving shortly is handed off without a context switch,
+/// then yielding, then sleeping for at most a millisecond so that an idle consumer does not keep
+/// a core busy.
+struct Backoff {
+    step: u32,
+}
+
+impl Backoff {
+    const SPIN_STEPS: u32 = 6;
+    const YIELD_STEPS: u32 = 10;
+    const MAX_SLEEP: Duration = Duration::from_millis(1);
+
+    fn new() -> Self {
+        Self { step: 0 }
+    }
+
+    fn wait(&mut self) {
+        if self.step < Self::SPIN_STEPS {
+            (0..1 << self.step).for_each(|_| hint::spin_loop());
+        } else if self.step < Self::YIELD_STEPS {
+            thread::yield_now();
+        } else {
+            let sleep = Duration::from_micros(50) * (1 << (self.step - Self::YIELD_STEPS).min(5));
+            thread::sleep(sleep.min(Self::MAX_SLEEP));
+        }
+
+        self.step = self.step.saturating_add(1);
+    }
+}
+
+/// A bounded queue that many threads can push to and pop from without locking, implemented as
+/// Dmitry Vyukov's bounded MPMC queue.
+///
+/// Each slot has a sequence number telling whether it can be written to for a given position
+/// (`seq == pos`) or read from (`seq == pos + 1`), so that a push or pop only claims its position
+/// with a compare-and-swap and never waits for another thread.
+struct Ring<T> {
+    slots: Box<[Slot<T>]>,
+    mask: usize,
+    push_pos: AtomicUsize,
+    pop_pos: AtomicUsize,
+}
+
+struct Slot<T> {
+    seq: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+// SAFETY: a value is only accessed by the thread that has claimed its slot, see `push()` and
+// `pop()`, and is moved between threads, which requires `T: Send`.
+unsafe impl<T: Send> Send for Ring<T> {}
+unsafe impl<T: Send> Sync for Ring<T> {}
+
+impl<T> Ring<T> {
+    /// Creates a new `Ring` with room for `capacity` values, rounded up to a power of two so that
+    /// positions map to slots across the overflow of the counters. There are at least two slots,
+    /// as the sequence number of a single slot would be the same whether it is full or empty.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(2).next_power_of_two();
+
+        let slots = (0..capacity)
+            .map(|i| Slot {
+                seq: AtomicUsize::new(i),
+                value: UnsafeCell::new(MaybeUninit::uninit()),
+            })
+            .collect();
+
+        Self {
+            slots,
+            mask: capacity - 1,
+            push_pos: AtomicUsize::new(0),
+            pop_pos: AtomicUsize::new(0),
+        }
+    }
+
+    #[cfg(test)]
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Pushes `value` unless the ring is full, in which case it is returned back.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let mut pos = self.push_pos.load(Ordering::Relaxed);
+
+        loop {
+            let slot = &self.slots[pos & self.mask];
+            let seq = slot.seq.load(Ordering::Acquire);
+
+            match (seq as isize).wrapping_sub(pos as isize) {
+                0 => match self.push_pos.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        unsafe { (*slot.value.get()).write(value) };
+                        slot.seq.store(pos.wrapping_add(1), Ordering::Release);
+                        return Ok(());
+                    }
+                    Err(current) => pos = current,
+                },
+                // the slot still holds the value of the previous lap
+                d if d < 0 => return Err(value),
+                // another thread has pushed to the slot since `pos` was loaded
+                _ => pos = self.push_pos.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Pops the oldest value, or returns `None` if the ring is empty.
+    pub fn pop(&self) -> Option<T> {
+        let mut pos = self.pop_pos.load(Ordering::Relaxed);
+
+        loop {
+            let slot = &self.slots[pos & self.mask];
+            let seq = slot.seq.load(Ordering::Acquire);
+
+            match (seq as isize).wrapping_sub(pos.wrapping_add(1) as isize) {
+                0 => match self.pop_pos.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let value = unsafe { (*slot.value.get()).assume_init_read() };
+                        slot.seq
+                            .store(pos.wrapping_add(self.mask + 1), Ordering::Release);
+                        return Some(value);
+                    }
+                    Err(current) => pos = current,
+                },
+                // nothing has been pushed to the slot yet
+                d if d < 0 => return None,
+                // another thread has popped from the slot since `pos` was loaded
+                _ => pos = self.pop_pos.load(Ordering::Relaxed),
+            }
+        }
+    }
+}
+
+impl<T> Drop for Ring<T> {
+    fn drop(&mut self) {
+        while self.pop().is_some() {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    #[test]
+    fn values_are_popped_in_order_until_empty() {
+        assert_eq!(Ring::<()>::new(1).capacity(), 2);
+
+        let ring = Ring::new(3);
+        assert_eq!(ring.capacity(), 4);
+
+        for i in 0..4 {
+            ring.push(i).unwrap();
+        }
+
+        assert_eq!(ring.push(4), Err(4));
+        assert_eq!(ring.pop(), Some(0));
+
+        ring.push(4).unwrap();
+
+        assert_eq!(
+            (0..4).map(|_| ring.pop().unwrap()).collect::<Vec<_>>(),
+            vec![1, 2, 3, 4]
+        );
+        assert_eq!(ring.pop(), None);
+    }
+
+    #[test]
+    fn values_of_many_producers_are_popped_once() {
+        let ring = Arc::new(Ring::new(8));
+
+        let producers: Vec<_> = (0..4)
+            .map(|p| {
+                let ring = ring.clone();
+                thread::spawn(move || {
+                    for i in 0..1000 {
+                        let mut value = p * 1000 + i;
+                        while let Err(v) = ring.push(value) {
+                            value = v;
+                            thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        let mut popped = Vec::new();
+
+        while popped.len() < 4000 {
+            match ring.pop() {
+                Some(v) => popped.push(v),
+                None => thread::yield_now(),
+            }
+        }
+
+        producers.into_iter().for_each(|p| p.join().unwrap());
+
+        popped.sort_unstable();
+        assert_eq!(popped, (0..4000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn values_left_are_dropped_with_ring() {
+        let value = Arc::new(());
+        let ring = Ring::new(2);
+        ring.push(value.clone()).unwrap();
+
+        drop(ring);
+
+        assert_eq!(Arc::strong_count(&value), 1);
+    }
+}
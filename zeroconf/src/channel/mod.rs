@@ -0,0 +1,571 @@
+//! Bounded channel for delivering the events of a browser to another thread
+
+mod lock_free;
+
+use std::collections::VecDeque;
+use std::fmt::{self, Formatter};
+use std::sync::mpsc::{RecvError, RecvTimeoutError, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// What an [`EventSender`] does with an event while the buffer of its channel is full, e.g.
+/// because the consumer cannot keep up with a burst of hundreds of services.
+///
+/// [`EventSender`]: struct.EventSender.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OverflowPolicy {
+    /// Discards the oldest buffered event to make room for the new one, so that the consumer
+    /// sees the most recent events
+    DropOldest,
+    /// Discards the new event, so that the consumer sees the events that occurred first
+    DropNewest,
+    /// Waits for the consumer to make room for the new event. As events are sent from the
+    /// callbacks of a browser, this stops the event loop from polling meanwhile, which leaves
+    /// the events to be buffered by the mDNS implementation instead.
+    Block,
+}
+
+/// Creates a new channel that buffers at most `capacity` events (at least one), handling the
+/// events that do not fit according to `policy`.
+///
+/// Unlike a `std::sync::mpsc` channel, the buffer of the channel cannot grow without bounds while
+/// the consumer is slower than the events are sent. [`EventReceiver::dropped()`] returns how many
+/// events have been discarded.
+///
+/// The buffer is guarded by a mutex, which the callbacks of a browser contend on with the
+/// consumer. See [`lock_free_event_channel()`] for latency-sensitive consumers.
+///
+/// # Examples
+/// ```no_run
+/// use std::thread;
+/// use std::time::Duration;
+/// use zeroconf::channel::{self, OverflowPolicy};
+/// use zeroconf::prelude::*;
+/// use zeroconf::{BrowserEvent, MdnsBrowser, ServiceType};
+///
+/// let (sender, receiver) = channel::event_channel(256, OverflowPolicy::DropOldest);
+///
+/// let mut browser = MdnsBrowser::builder(ServiceType::new("http", "tcp").unwrap())
+///     .event_sender(sender)
+///     .build();
+///
+/// thread::spawn(move || {
+///     for event in receiver.iter() {
+///         if let BrowserEvent::Discovered(service) = event {
+///             println!("Service discovered: {:?}", service);
+///         }
+///     }
+/// });
+///
+/// let event_loop = browser.browse_services().unwrap();
+///
+/// loop {
+///     event_loop.poll(Duration::from_secs(1)).unwrap();
+/// }
+/// ```
+///
+/// [`EventReceiver::dropped()`]: struct.EventReceiver.html#method.dropped
+/// [`lock_free_event_channel()`]: fn.lock_free_event_channel.html
+pub fn event_channel<T>(
+    capacity: usize,
+    policy: OverflowPolicy,
+) -> (EventSender<T>, EventReceiver<T>) {
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            buffer: VecDeque::new(),
+            senders: 1,
+            receiving: true,
+            dropped: 0,
+        }),
+        capacity: capacity.max(1),
+        policy,
+        not_empty: Condvar::new(),
+        not_full: Condvar::new(),
+    });
+
+    (
+        EventSender {
+            flavor: Flavor::Locked(shared.clone()),
+        },
+        EventReceiver {
+            flavor: Flavor::Locked(shared),
+        },
+    )
+}
+
+/// Creates a new channel like [`event_channel()`] whose halves never take a lock, so that the
+/// callbacks of a browser hand off an event without contending with the consumer (e.g. for audio
+/// discovery or games, where the consumer polls for events every frame).
+///
+/// As with `event_channel()`, at most `capacity` events (at least one) are buffered. As there is
+/// nothing to be woken up by, [`EventReceiver::recv()`] and a sender blocked by
+/// [`OverflowPolicy::Block`] wait by spinning, then yielding, then sleeping for up to a
+/// millisecond between attempts, so that latency-sensitive consumers are best served by
+/// [`EventReceiver::try_recv()`].
+///
+/// [`event_channel()`]: fn.event_channel.html
+/// [`EventReceiver::recv()`]: struct.EventReceiver.html#method.recv
+/// [`EventReceiver::try_recv()`]: struct.EventReceiver.html#method.try_recv
+/// [`OverflowPolicy::Block`]: enum.OverflowPolicy.html#variant.Block
+pub fn lock_free_event_channel<T>(
+    capacity: usize,
+    policy: OverflowPolicy,
+) -> (EventSender<T>, EventReceiver<T>) {
+    let channel = Arc::new(lock_free::Channel::new(capacity, policy));
+
+    (
+        EventSender {
+            flavor: Flavor::LockFree(channel.clone()),
+        },
+        EventReceiver {
+            flavor: Flavor::LockFree(channel),
+        },
+    )
+}
+
+enum Flavor<T> {
+    Locked(Arc<Shared<T>>),
+    LockFree(Arc<lock_free::Channel<T>>),
+}
+
+impl<T> Flavor<T> {
+    fn capacity(&self) -> usize {
+        match self {
+            Self::Locked(shared) => shared.capacity,
+            Self::LockFree(channel) => channel.capacity(),
+        }
+    }
+
+    fn policy(&self) -> OverflowPolicy {
+        match self {
+            Self::Locked(shared) => shared.policy,
+            Self::LockFree(channel) => channel.policy(),
+        }
+    }
+
+    fn recv_deadline(&self, deadline: Option<Instant>) -> Result<T, RecvTimeoutError> {
+        match self {
+            Self::Locked(shared) => shared.recv_deadline(deadline),
+            Self::LockFree(channel) => channel.recv_deadline(deadline),
+        }
+    }
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    capacity: usize,
+    policy: OverflowPolicy,
+    not_empty: Condvar,
+    not_full: Condvar,
+}
+
+struct State<T> {
+    buffer: VecDeque<T>,
+    senders: usize,
+    receiving: bool,
+    dropped: u64,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        // the state is consistent between each statement, so it is usable after a panic
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn send(&self, event: T) -> Result<(), T> {
+        let mut state = self.lock();
+
+        while state.receiving && state.buffer.len() >= self.capacity {
+            match self.policy {
+                OverflowPolicy::DropOldest => {
+                    state.buffer.pop_front();
+                    state.dropped += 1;
+                }
+                OverflowPolicy::DropNewest => {
+                    state.dropped += 1;
+                    return Ok(());
+                }
+                OverflowPolicy::Block => {
+                    state = self.not_full.wait(state).unwrap_or_else(|e| e.into_inner());
+                }
+            }
+        }
+
+        if !state.receiving {
+            return Err(event);
+        }
+
+        state.buffer.push_back(event);
+        self.not_empty.notify_one();
+
+        Ok(())
+    }
+
+    fn try_recv(&self) -> Result<T, TryRecvError> {
+        let mut state = self.lock();
+
+        match self.take(&mut state) {
+            Some(event) => Ok(event),
+            None if state.senders == 0 => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    fn recv_deadline(&self, deadline: Option<Instant>) -> Result<T, RecvTimeoutError> {
+        let mut state = self.lock();
+
+        loop {
+            if let Some(event) = self.take(&mut state) {
+                return Ok(event);
+            }
+
+            if state.senders == 0 {
+                return Err(RecvTimeoutError::Disconnected);
+            }
+
+            state = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+
+                    if remaining == Duration::from_secs(0) {
+                        return Err(RecvTimeoutError::Timeout);
+                    }
+
+                    self.not_empty
+                        .wait_timeout(state, remaining)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                None => self
+                    .not_empty
+                    .wait(state)
+                    .unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+    }
+
+    fn take(&self, state: &mut State<T>) -> Option<T> {
+        let event = state.buffer.pop_front()?;
+        self.not_full.notify_one();
+        Some(event)
+    }
+}
+
+/// The sending half of an [`event_channel()`] or [`lock_free_event_channel()`], which may be
+/// cloned to send from many browsers.
+///
+/// [`event_channel()`]: fn.event_channel.html
+/// [`lock_free_event_channel()`]: fn.lock_free_event_channel.html
+pub struct EventSender<T> {
+    flavor: Flavor<T>,
+}
+
+impl<T> EventSender<T> {
+    /// Sends the specified `event`, handling it according to the [`OverflowPolicy`] of the channel
+    /// if its buffer is full. Returns the event back if the [`EventReceiver`] has been dropped.
+    ///
+    /// An event that is discarded by the policy is not an error.
+    ///
+    /// [`OverflowPolicy`]: enum.OverflowPolicy.html
+    /// [`EventReceiver`]: struct.EventReceiver.html
+    pub fn send(&self, event: T) -> Result<(), T> {
+        match &self.flavor {
+            Flavor::Locked(shared) => shared.send(event),
+            Flavor::LockFree(channel) => channel.send(event),
+        }
+    }
+}
+
+impl<T> Clone for EventSender<T> {
+    fn clone(&self) -> Self {
+        let flavor = match &self.flavor {
+            Flavor::Locked(shared) => {
+                shared.lock().senders += 1;
+                Flavor::Locked(shared.clone())
+            }
+            Flavor::LockFree(channel) => {
+                channel.add_sender();
+                Flavor::LockFree(channel.clone())
+            }
+        };
+
+        Self { flavor }
+    }
+}
+
+impl<T> Drop for EventSender<T> {
+    fn drop(&mut self) {
+        match &self.flavor {
+            Flavor::Locked(shared) => {
+                let mut state = shared.lock();
+                state.senders -= 1;
+
+                if state.senders == 0 {
+                    shared.not_empty.notify_all();
+                }
+            }
+            Flavor::LockFree(channel) => channel.remove_sender(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for EventSender<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventSender")
+            .field("capacity", &self.flavor.capacity())
+            .field("policy", &self.flavor.policy())
+            .finish()
+    }
+}
+
+/// The receiving half of an [`event_channel()`] or [`lock_free_event_channel()`].
+///
+/// Receiving fails once every [`EventSender`] has been dropped (e.g. with the browsers they were
+/// given to) and the buffered events have been received.
+///
+/// [`event_channel()`]: fn.event_channel.html
+/// [`lock_free_event_channel()`]: fn.lock_free_event_channel.html
+/// [`EventSender`]: struct.EventSender.html
+pub struct EventReceiver<T> {
+    flavor: Flavor<T>,
+}
+
+impl<T> EventReceiver<T> {
+    /// Waits for an event to be sent and returns it.
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.flavor.recv_deadline(None).map_err(|_| RecvError)
+    }
+
+    /// Waits at most `timeout` for an event to be sent and returns it.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        self.flavor.recv_deadline(Some(Instant::now() + timeout))
+    }
+
+    /// Returns a buffered event without waiting.
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        match &self.flavor {
+            Flavor::Locked(shared) => shared.try_recv(),
+            Flavor::LockFree(channel) => channel.try_recv(),
+        }
+    }
+
+    /// Returns an iterator that waits for the events to be sent, until every sender has been
+    /// dropped.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || self.recv().ok())
+    }
+
+    /// Returns an iterator over the buffered events that does not wait for more events.
+    pub fn try_iter(&self) -> impl Iterator<Item = T> + '_ {
+        std::iter::from_fn(move || self.try_recv().ok())
+    }
+
+    /// Returns how many events have been discarded by the [`OverflowPolicy`] of the channel so
+    /// far.
+    ///
+    /// [`OverflowPolicy`]: enum.OverflowPolicy.html
+    pub fn dropped(&self) -> u64 {
+        match &self.flavor {
+            Flavor::Locked(shared) => shared.lock().dropped,
+            Flavor::LockFree(channel) => channel.dropped(),
+        }
+    }
+}
+
+impl<T> Drop for EventReceiver<T> {
+    fn drop(&mut self) {
+        match &self.flavor {
+            Flavor::Locked(shared) => {
+                let mut state = shared.lock();
+                state.receiving = false;
+                state.buffer.clear();
+
+                // senders that are waiting for room will not get any
+                shared.not_full.notify_all();
+            }
+            Flavor::LockFree(channel) => channel.close(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for EventReceiver<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventReceiver")
+            .field("capacity", &self.flavor.capacity())
+            .field("policy", &self.flavor.policy())
+            .field("dropped", &self.dropped())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn drop_oldest_keeps_most_recent_events() {
+        let (sender, receiver) = event_channel(2, OverflowPolicy::DropOldest);
+
+        for i in 0..5 {
+            sender.send(i).unwrap();
+        }
+
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![3, 4]);
+        assert_eq!(receiver.dropped(), 3);
+    }
+
+    #[test]
+    fn drop_newest_keeps_first_events() {
+        let (sender, receiver) = event_channel(2, OverflowPolicy::DropNewest);
+
+        for i in 0..5 {
+            sender.send(i).unwrap();
+        }
+
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(receiver.dropped(), 3);
+    }
+
+    #[test]
+    fn block_waits_for_room() {
+        let (sender, receiver) = event_channel(1, OverflowPolicy::Block);
+
+        let producer = thread::spawn(move || {
+            for i in 0..100 {
+                sender.send(i).unwrap();
+            }
+        });
+
+        assert_eq!(
+            receiver.iter().collect::<Vec<_>>(),
+            (0..100).collect::<Vec<_>>()
+        );
+        assert_eq!(receiver.dropped(), 0);
+
+        producer.join().unwrap();
+    }
+
+    #[test]
+    fn dropping_receiver_unblocks_senders() {
+        let (sender, receiver) = event_channel(1, OverflowPolicy::Block);
+        sender.send(0).unwrap();
+
+        let producer = thread::spawn(move || sender.send(1));
+
+        thread::sleep(Duration::from_millis(10));
+        drop(receiver);
+
+        assert_eq!(producer.join().unwrap(), Err(1));
+    }
+
+    #[test]
+    fn receiving_fails_once_senders_are_dropped() {
+        let (sender, receiver) = event_channel(4, OverflowPolicy::Block);
+        let other = sender.clone();
+
+        sender.send(0).unwrap();
+        drop(sender);
+
+        assert_eq!(receiver.try_recv(), Ok(0));
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Empty));
+
+        drop(other);
+
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_millis(1)),
+            Err(RecvTimeoutError::Disconnected)
+        );
+    }
+
+    #[test]
+    fn lock_free_overflow_policies_discard_events() {
+        let (sender, receiver) = lock_free_event_channel(2, OverflowPolicy::DropOldest);
+
+        for i in 0..5 {
+            sender.send(i).unwrap();
+        }
+
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![3, 4]);
+        assert_eq!(receiver.dropped(), 3);
+
+        let (sender, receiver) = lock_free_event_channel(2, OverflowPolicy::DropNewest);
+
+        for i in 0..5 {
+            sender.send(i).unwrap();
+        }
+
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(receiver.dropped(), 3);
+    }
+
+    #[test]
+    fn lock_free_buffers_requested_capacity() {
+        let (sender, receiver) = lock_free_event_channel(3, OverflowPolicy::DropNewest);
+
+        for i in 0..5 {
+            sender.send(i).unwrap();
+        }
+
+        assert_eq!(receiver.try_iter().collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(receiver.dropped(), 2);
+    }
+
+    #[test]
+    fn lock_free_block_waits_for_room() {
+        let (sender, receiver) = lock_free_event_channel(1, OverflowPolicy::Block);
+
+        let producers: Vec<_> = (0..2)
+            .map(|p| {
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    for i in 0..100 {
+                        sender.send(p * 100 + i).unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        drop(sender);
+
+        let mut events = receiver.iter().collect::<Vec<_>>();
+        events.sort_unstable();
+
+        assert_eq!(events, (0..200).collect::<Vec<_>>());
+        assert_eq!(receiver.dropped(), 0);
+
+        producers.into_iter().for_each(|p| p.join().unwrap());
+    }
+
+    #[test]
+    fn lock_free_dropping_receiver_unblocks_senders() {
+        let (sender, receiver) = lock_free_event_channel(2, OverflowPolicy::Block);
+        sender.send(0).unwrap();
+        sender.send(1).unwrap();
+
+        let producer = thread::spawn(move || sender.send(2));
+
+        thread::sleep(Duration::from_millis(10));
+        drop(receiver);
+
+        assert_eq!(producer.join().unwrap(), Err(2));
+    }
+
+    #[test]
+    fn lock_free_receiving_fails_once_senders_are_dropped() {
+        let (sender, receiver) = lock_free_event_channel(4, OverflowPolicy::Block);
+
+        assert_eq!(
+            receiver.recv_timeout(Duration::from_millis(1)),
+            Err(RecvTimeoutError::Timeout)
+        );
+
+        sender.send(0).unwrap();
+        drop(sender);
+
+        assert_eq!(receiver.recv(), Ok(0));
+        assert_eq!(receiver.try_recv(), Err(TryRecvError::Disconnected));
+        assert_eq!(receiver.recv(), Err(RecvError));
+    }
+}
@@ -0,0 +1,29 @@
+//! Utilities regarding the user context passed through to callbacks
+
+use std::any::Any;
+use std::sync::Arc;
+
+/// Typed access to the optional user context that callbacks receive, without the `downcast_ref`
+/// boilerplate.
+///
+/// # Examples
+/// ```
+/// use std::any::Any;
+/// use std::sync::{Arc, Mutex};
+/// use zeroconf::prelude::*;
+///
+/// let context: Option<Arc<dyn Any + Send + Sync>> = Some(Arc::new(Mutex::new(42_u32)));
+///
+/// assert_eq!(*context.get::<Mutex<u32>>().unwrap().lock().unwrap(), 42);
+/// assert!(context.get::<String>().is_none());
+/// ```
+pub trait ContextExt {
+    /// Returns the context if it has been set and is of type `T`.
+    fn get<T: Any>(&self) -> Option<&T>;
+}
+
+impl ContextExt for Option<Arc<dyn Any + Send + Sync>> {
+    fn get<T: Any>(&self) -> Option<&T> {
+        self.as_ref()?.downcast_ref::<T>()
+    }
+}
@@ -0,0 +1,156 @@
+//! Utilities regarding the underlying mDNS daemon
+
+use crate::error::Error;
+use crate::{Backend, Result};
+use std::any::Any;
+use std::fmt;
+use std::sync::Arc;
+
+/// Callback invoked from [`MdnsService`] or [`MdnsBrowser`] once it has transparently reconnected
+/// to the mDNS daemon (e.g. after `mDNSResponder` or `avahi-daemon` was restarted) and
+/// re-established its registration or browser.
+///
+/// # Arguments
+/// * `context` - The optional user context passed through
+///
+/// [`MdnsService`]: type.MdnsService.html
+/// [`MdnsBrowser`]: type.MdnsBrowser.html
+pub type DaemonReconnectedCallback = dyn Fn(Option<Arc<dyn Any + Send + Sync>>) + Send;
+
+/// Reachability and version of the mDNS daemon of the backend compiled into this build, as
+/// returned by [`daemon_info()`].
+///
+/// [`daemon_info()`]: fn.daemon_info.html
+#[derive(Debug, Getters, Clone, PartialEq, Eq)]
+pub struct DaemonInfo {
+    /// The backend compiled into this build
+    backend: Backend,
+    /// The version reported by the daemon (e.g. `avahi 0.8` or `1310.4` for `mDNSResponder`), or
+    /// `None` if the daemon is not reachable or the backend does not depend on a daemon
+    version: Option<String>,
+    /// Why the daemon is not reachable, if it is not
+    error: Option<Error>,
+}
+
+impl DaemonInfo {
+    /// Returns true if the daemon is reachable, i.e. services can be registered and browsed.
+    pub fn is_reachable(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+impl fmt::Display for DaemonInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.version, &self.error) {
+            (_, Some(e)) => write!(
+                f,
+                "the {} mDNS daemon is not reachable: {}",
+                self.backend, e
+            ),
+            (Some(v), None) => write!(f, "the {} mDNS daemon is reachable ({})", self.backend, v),
+            (None, None) => write!(f, "the {} mDNS backend is available", self.backend),
+        }
+    }
+}
+
+/// Connects to the mDNS daemon of the backend compiled into this build and reports whether it is
+/// reachable and its version, so applications can tell users why mDNS is unavailable (e.g. when
+/// `avahi-daemon` is not running) up front instead of failing once services are browsed or
+/// registered.
+///
+/// The version is queried from `avahi-daemon`, `mDNSResponder` or systemd-resolved, the other
+/// backends do not depend on a daemon and report no version.
+///
+/// # Examples
+/// ```no_run
+/// let info = zeroconf::daemon_info();
+///
+/// if !info.is_reachable() {
+///     eprintln!("mDNS is disabled: {}", info);
+/// }
+/// ```
+pub fn daemon_info() -> DaemonInfo {
+    let backend = Backend::current();
+
+    match daemon_version() {
+        Ok(version) => DaemonInfo {
+            backend,
+            version,
+            error: None,
+        },
+        Err(e) => DaemonInfo {
+            backend,
+            version: None,
+            error: Some(e),
+        },
+    }
+}
+
+/// Returns the version of the mDNS daemon of the backend compiled into this build, if any.
+#[cfg(backend_avahi)]
+fn daemon_version() -> Result<Option<String>> {
+    crate::linux::client::daemon_version().map(Some)
+}
+/// Returns the version of the mDNS daemon of the backend compiled into this build, if any.
+#[cfg(backend_avahi_dbus)]
+fn daemon_version() -> Result<Option<String>> {
+    crate::avahi_dbus::client::daemon_version().map(Some)
+}
+/// Returns the version of the mDNS daemon of the backend compiled into this build, if any.
+#[cfg(backend_resolved)]
+fn daemon_version() -> Result<Option<String>> {
+    crate::resolved::client::daemon_version().map(Some)
+}
+/// Returns the version of the mDNS daemon of the backend compiled into this build, if any.
+#[cfg(backend_avahi_core)]
+fn daemon_version() -> Result<Option<String>> {
+    // the mDNS stack runs in this process
+    Ok(None)
+}
+/// Returns the version of the mDNS daemon of the backend compiled into this build, if any.
+#[cfg(backend_bonjour)]
+fn daemon_version() -> Result<Option<String>> {
+    crate::macos::bonjour_util::daemon_version().map(Some)
+}
+/// Returns the version of the mDNS daemon of the backend compiled into this build, if any.
+#[cfg(backend_bonjour_sdk)]
+fn daemon_version() -> Result<Option<String>> {
+    crate::windows::bonjour_util::daemon_version().map(Some)
+}
+/// Returns the version of the mDNS daemon of the backend compiled into this build, if any.
+#[cfg(backend_win32)]
+fn daemon_version() -> Result<Option<String>> {
+    // the API is part of the operating system
+    Ok(None)
+}
+/// Returns the version of the mDNS daemon of the backend compiled into this build, if any.
+#[cfg(backend_embedded)]
+fn daemon_version() -> Result<Option<String>> {
+    crate::embedded::socket::MdnsSocket::new().map(|_| None)
+}
+/// Returns the version of the mDNS daemon of the backend compiled into this build, if any.
+#[cfg(backend_mock)]
+fn daemon_version() -> Result<Option<String>> {
+    Ok(None)
+}
+/// Returns the version of the mDNS daemon of the backend compiled into this build, if any.
+#[cfg(backend_plugin)]
+fn daemon_version() -> Result<Option<String>> {
+    match crate::plugin::backend() {
+        Some(_) => Ok(None),
+        None => Err(crate::plugin::backend::no_backend_error()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn daemon_info_reports_compiled_backend() {
+        let info = daemon_info();
+
+        assert_eq!(*info.backend(), Backend::current());
+        assert_eq!(info.is_reachable(), info.backend().is_available());
+    }
+}
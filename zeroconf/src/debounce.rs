@@ -0,0 +1,100 @@
+//! Utilities for suppressing services that repeatedly go away and come back
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// Remembers the services that have gone away for the debounce window of a browser, so that a
+/// service that comes back within the window (e.g. a device with poor Wi-Fi) is not reported
+/// again.
+///
+/// Debouncing is disabled as long as the window is zero, which is the default.
+#[derive(Debug)]
+pub(crate) struct Debouncer<K> {
+    window: Duration,
+    gone: HashMap<K, Instant>,
+}
+
+impl<K: Hash + Eq> Debouncer<K> {
+    /// Creates a new `Debouncer` with a window of zero.
+    pub fn new() -> Self {
+        Self {
+            window: Duration::from_secs(0),
+            gone: HashMap::new(),
+        }
+    }
+
+    /// Sets how long a service that has gone away is remembered.
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window;
+        self.expire();
+    }
+
+    /// Records that the service identified by the key returned by `key` has gone away. `key` is
+    /// only invoked if debouncing is enabled.
+    pub fn went_away(&mut self, key: impl FnOnce() -> K) {
+        self.expire();
+
+        if self.window > Duration::from_secs(0) {
+            self.gone.insert(key(), Instant::now());
+        }
+    }
+
+    /// Returns true if the service identified by the key returned by `key` has come back within
+    /// the window since it went away, in which case it is not to be reported again. `key` is only
+    /// invoked if a service has gone away recently, so that no key is built otherwise.
+    pub fn has_returned(&mut self, key: impl FnOnce() -> K) -> bool {
+        self.expire();
+
+        if self.gone.is_empty() {
+            return false;
+        }
+
+        self.gone.remove(&key()).is_some()
+    }
+
+    fn expire(&mut self) {
+        let window = self.window;
+        self.gone.retain(|_, gone_at| gone_at.elapsed() < window);
+    }
+}
+
+impl<K: Hash + Eq> Default for Debouncer<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn services_returning_within_window_are_suppressed_once() {
+        let mut debouncer = Debouncer::new();
+        debouncer.set_window(Duration::from_secs(60));
+
+        assert!(!debouncer.has_returned(|| "a"));
+
+        debouncer.went_away(|| "a");
+
+        assert!(!debouncer.has_returned(|| "b"));
+        assert!(debouncer.has_returned(|| "a"));
+        assert!(!debouncer.has_returned(|| "a"));
+    }
+
+    #[test]
+    fn services_are_forgotten_after_window() {
+        let mut debouncer = Debouncer::new();
+
+        debouncer.went_away(|| "a");
+        assert!(!debouncer.has_returned(|| "a"));
+
+        debouncer.set_window(Duration::from_millis(1));
+        debouncer.went_away(|| "a");
+        thread::sleep(Duration::from_millis(5));
+
+        assert!(!debouncer.has_returned(|| "a"));
+    }
+}
@@ -0,0 +1,372 @@
+//! Utilities for encoding and decoding DNS names and resource record data
+
+use crate::error::{Error, ErrorKind};
+use crate::Result;
+use std::convert::TryFrom;
+use std::iter;
+use std::net::IpAddr;
+
+/// The `A` (IPv4 address) record type
+pub const TYPE_A: u16 = 1;
+/// The `PTR` (domain name pointer) record type
+pub const TYPE_PTR: u16 = 12;
+/// The `TXT` (text strings) record type
+pub const TYPE_TXT: u16 = 16;
+/// The `AAAA` (IPv6 address) record type
+pub const TYPE_AAAA: u16 = 28;
+/// The `SRV` (service locator) record type
+pub const TYPE_SRV: u16 = 33;
+/// Matches records of any type in queries
+pub const TYPE_ANY: u16 = 255;
+/// The `IN` (Internet) record class
+pub const CLASS_IN: u16 = 1;
+
+/// The name under which the types of the services of a domain are enumerated (RFC 6763, section
+/// 9), without the domain
+pub const SERVICES_NAME: &str = "_services._dns-sd._udp";
+
+/// Escapes the specified label so that it can be used as a single label in a textual domain
+/// name (e.g. a service instance name containing a `'.'`).
+pub fn escape_label(label: &str) -> String {
+    let mut escaped = String::with_capacity(label.len());
+    push_escaped_label(&mut escaped, label);
+    escaped
+}
+
+/// Appends the escaped `label` to `name`, e.g. to build a domain name without allocating each of
+/// its labels. See [`escape_label()`].
+///
+/// [`escape_label()`]: fn.escape_label.html
+pub fn push_escaped_label(name: &mut String, label: &str) {
+    for c in label.chars() {
+        if c == '.' || c == '\\' {
+            name.push('\\');
+        }
+        name.push(c);
+    }
+}
+
+/// Encodes the specified labels into a domain name in DNS wire format.
+pub fn encode_labels<'a>(labels: impl IntoIterator<Item = &'a str>) -> Vec<u8> {
+    let mut buf = vec![];
+    for label in labels.into_iter().filter(|l| !l.is_empty()) {
+        let bytes = label.as_bytes();
+        let len = bytes.len().min(63);
+        buf.push(len as u8);
+        buf.extend_from_slice(&bytes[..len]);
+    }
+    buf.push(0);
+    buf
+}
+
+/// Encodes the specified unescaped, `'.'` separated domain name in DNS wire format.
+pub fn encode_name(name: &str) -> Vec<u8> {
+    encode_labels(name.split('.'))
+}
+
+/// Returns the RDATA of a SRV record.
+pub fn srv_rdata(priority: u16, weight: u16, port: u16, target: &str) -> Vec<u8> {
+    let mut buf = vec![];
+    buf.extend_from_slice(&priority.to_be_bytes());
+    buf.extend_from_slice(&weight.to_be_bytes());
+    buf.extend_from_slice(&port.to_be_bytes());
+    buf.extend(encode_name(target));
+    buf
+}
+
+/// Returns the RDATA of a TXT record containing the specified `key=value` entries.
+///
+/// An empty TXT record consists of a single empty string, as required by RFC 6763.
+///
+/// Returns an error of kind `ErrorKind::InvalidArgument` if an entry exceeds the 255 bytes a
+/// string of a TXT record can hold.
+pub fn txt_rdata(entries: impl IntoIterator<Item = (String, String)>) -> Result<Vec<u8>> {
+    let mut buf = vec![];
+    for (key, value) in entries {
+        let entry = format!("{}={}", key, value);
+        let len = u8::try_from(entry.len()).map_err(|_| {
+            Error::with_kind(
+                ErrorKind::InvalidArgument,
+                format!("TXT record entry `{}` exceeds 255 bytes", key),
+            )
+        })?;
+        buf.push(len);
+        buf.extend_from_slice(entry.as_bytes());
+    }
+    if buf.is_empty() {
+        buf.push(0);
+    }
+    Ok(buf)
+}
+
+/// Returns the name, type and RDATA of each record published for the service instance `name` of
+/// type `kind` (e.g. `_http._tcp`) in `domain`: the PTR records of its type, of each of its
+/// `sub_types` and of the service type enumeration, followed by its SRV and TXT records.
+///
+/// Returns an error of kind `ErrorKind::InvalidArgument` if a TXT entry exceeds 255 bytes.
+pub fn service_records<'a>(
+    name: &str,
+    kind: &str,
+    sub_types: impl IntoIterator<Item = &'a str>,
+    domain: &str,
+    host: &str,
+    port: u16,
+    txt: impl IntoIterator<Item = (String, String)>,
+) -> Result<Vec<(String, u16, Vec<u8>)>> {
+    let kind_name = format!("{}.{}", kind, domain);
+    let full_name = format!("{}.{}", escape_label(name), kind_name);
+
+    let instance = encode_labels(
+        iter::once(name)
+            .chain(kind.split('.'))
+            .chain(domain.split('.')),
+    );
+
+    let mut records = vec![(kind_name.clone(), TYPE_PTR, instance.clone())];
+
+    for sub_type in sub_types {
+        records.push((
+            format!("_{}._sub.{}", sub_type, kind_name),
+            TYPE_PTR,
+            instance.clone(),
+        ));
+    }
+
+    records.push((
+        format!("{}.{}", SERVICES_NAME, domain),
+        TYPE_PTR,
+        encode_name(&kind_name),
+    ));
+    records.push((full_name.clone(), TYPE_SRV, srv_rdata(0, 0, port, host)));
+    records.push((full_name, TYPE_TXT, txt_rdata(txt)?));
+
+    Ok(records)
+}
+
+/// The decoded RDATA of a SRV record.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Srv {
+    /// The priority of the target host, lower values are preferred
+    pub priority: u16,
+    /// The relative weight of targets with the same priority
+    pub weight: u16,
+    /// The port of the service on the target host
+    pub port: u16,
+    /// The host name of the target host
+    pub target: String,
+}
+
+/// Decodes the RDATA of a SRV record.
+///
+/// Returns `None` if `rdata` is not a valid SRV record.
+pub fn parse_srv_rdata(rdata: &[u8]) -> Option<Srv> {
+    if rdata.len() < 6 {
+        return None;
+    }
+
+    Some(Srv {
+        priority: u16::from_be_bytes([rdata[0], rdata[1]]),
+        weight: u16::from_be_bytes([rdata[2], rdata[3]]),
+        port: u16::from_be_bytes([rdata[4], rdata[5]]),
+        target: decode_name(&rdata[6..])?,
+    })
+}
+
+/// Decodes the domain name in DNS wire format at the start of `data` (e.g. the RDATA of a PTR
+/// record) into a `'.'` separated name without trailing dot.
+///
+/// Returns `None` if `data` is not a valid, uncompressed domain name.
+pub fn decode_name(data: &[u8]) -> Option<String> {
+    let mut labels = vec![];
+    let mut i = 0;
+
+    loop {
+        let len = *data.get(i)? as usize;
+        if len == 0 {
+            break;
+        }
+        if len > 63 {
+            // compression pointers are not supported
+            return None;
+        }
+        let label = data.get(i + 1..i + 1 + len)?;
+        labels.push(String::from_utf8_lossy(label).into_owned());
+        i += 1 + len;
+    }
+
+    Some(labels.join("."))
+}
+
+/// Returns the domain name used to look up the host name of the specified address (i.e. in
+/// `in-addr.arpa` for IPv4 and `ip6.arpa` for IPv6).
+pub fn reverse_name(ip: &IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let o = ip.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", o[3], o[2], o[1], o[0])
+        }
+        IpAddr::V6(ip) => {
+            let mut name = String::with_capacity(72);
+            for b in ip.octets().iter().rev() {
+                name.push_str(&format!("{:x}.{:x}.", b & 0xf, b >> 4));
+            }
+            name.push_str("ip6.arpa");
+            name
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_label_escapes_dots_and_backslashes() {
+        assert_eq!(escape_label("My.Printer\\2"), "My\\.Printer\\\\2");
+    }
+
+    #[test]
+    fn push_escaped_label_appends_to_name() {
+        let mut name = String::from("_http._tcp.");
+        push_escaped_label(&mut name, "a.b");
+        assert_eq!(name, "_http._tcp.a\\.b");
+    }
+
+    #[test]
+    fn encode_labels_success() {
+        assert_eq!(
+            encode_labels(vec!["My.Printer", "_http", "_tcp", "local"]),
+            b"\x0aMy.Printer\x05_http\x04_tcp\x05local\x00".to_vec()
+        );
+    }
+
+    #[test]
+    fn encode_name_ignores_trailing_dot() {
+        assert_eq!(
+            encode_name("host.local."),
+            b"\x04host\x05local\x00".to_vec()
+        );
+    }
+
+    #[test]
+    fn srv_rdata_success() {
+        assert_eq!(
+            srv_rdata(0, 0, 8080, "host.local"),
+            b"\x00\x00\x00\x00\x1f\x90\x04host\x05local\x00".to_vec()
+        );
+    }
+
+    #[test]
+    fn txt_rdata_success() {
+        assert_eq!(
+            txt_rdata(vec![("foo".to_string(), "bar".to_string())]).unwrap(),
+            b"\x07foo=bar".to_vec()
+        );
+    }
+
+    #[test]
+    fn txt_rdata_empty() {
+        assert_eq!(txt_rdata(vec![]).unwrap(), vec![0]);
+    }
+
+    #[test]
+    fn txt_rdata_rejects_oversized_entries() {
+        let error = txt_rdata(vec![("foo".to_string(), "x".repeat(252))]).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidArgument);
+        assert_eq!(
+            txt_rdata(vec![("foo".to_string(), "x".repeat(251))])
+                .unwrap()
+                .len(),
+            256
+        );
+    }
+
+    #[test]
+    fn service_records_include_sub_type_and_enumeration_ptrs() {
+        let records = service_records(
+            "My.Printer",
+            "_ipp._tcp",
+            vec!["color"],
+            "local",
+            "host.local",
+            631,
+            vec![],
+        )
+        .unwrap();
+
+        let instance = b"\x0aMy.Printer\x04_ipp\x04_tcp\x05local\x00".to_vec();
+
+        assert_eq!(
+            records,
+            vec![
+                ("_ipp._tcp.local".to_string(), TYPE_PTR, instance.clone()),
+                (
+                    "_color._sub._ipp._tcp.local".to_string(),
+                    TYPE_PTR,
+                    instance
+                ),
+                (
+                    "_services._dns-sd._udp.local".to_string(),
+                    TYPE_PTR,
+                    b"\x04_ipp\x04_tcp\x05local\x00".to_vec()
+                ),
+                (
+                    "My\\.Printer._ipp._tcp.local".to_string(),
+                    TYPE_SRV,
+                    srv_rdata(0, 0, 631, "host.local")
+                ),
+                (
+                    "My\\.Printer._ipp._tcp.local".to_string(),
+                    TYPE_TXT,
+                    vec![0]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_name_success() {
+        assert_eq!(
+            decode_name(b"\x04host\x05local\x00"),
+            Some("host.local".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_name_rejects_truncated_names() {
+        assert_eq!(decode_name(b"\x04host\x05loc"), None);
+    }
+
+    #[test]
+    fn reverse_name_ipv4() {
+        let ip: IpAddr = "192.168.1.20".parse().unwrap();
+        assert_eq!(reverse_name(&ip), "20.1.168.192.in-addr.arpa");
+    }
+
+    #[test]
+    fn reverse_name_ipv6() {
+        let ip: IpAddr = "2001:db8::567:89ab".parse().unwrap();
+        assert_eq!(
+            reverse_name(&ip),
+            "b.a.9.8.7.6.5.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa"
+        );
+    }
+
+    #[test]
+    fn parse_srv_rdata_success() {
+        assert_eq!(
+            parse_srv_rdata(&srv_rdata(10, 5, 8080, "host.local")),
+            Some(Srv {
+                priority: 10,
+                weight: 5,
+                port: 8080,
+                target: "host.local".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_srv_rdata_rejects_short_rdata() {
+        assert_eq!(parse_srv_rdata(b"\x00\x01"), None);
+    }
+}
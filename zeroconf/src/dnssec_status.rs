@@ -0,0 +1,12 @@
+/// Represents the outcome of the DNSSEC validation of a result
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DnssecStatus {
+    /// The result has a valid chain of trust to a trust anchor
+    Secure,
+    /// The result was proven to not be signed (i.e. its zone is not signed)
+    Insecure,
+    /// The result failed validation
+    Bogus,
+    /// The validation could not be completed (e.g. because of missing records)
+    Indeterminate,
+}
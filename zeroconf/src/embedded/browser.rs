@@ -0,0 +1,514 @@
+//! Embedded implementation for cross-platform browser
+
+use super::embedded_util;
+use super::message::{self, Message, Question, RecordData, ResourceRecord};
+use super::socket::MdnsSocket;
+use crate::debounce::Debouncer;
+use crate::error::{Error, Operation};
+use crate::prelude::*;
+use crate::{
+    dns, ffi, AddressFamily, AddressPolicy, BrowserControl, DaemonReconnectedCallback,
+    ErrorCallback, EventLoop, NetworkInterface, Result, ServiceType, TxtRecord,
+};
+use crate::{ServiceDiscoveredCallback, ServiceDiscovery, ServiceFlags};
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::{self, Formatter};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The interval between the first two queries for the service type, doubled after each query
+const INITIAL_QUERY_INTERVAL: Duration = Duration::from_secs(1);
+/// The maximum interval between two queries for the service type (RFC 6762, section 5.2)
+const MAX_QUERY_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// The interval between two queries for the missing records of a service instance
+const RESOLVE_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct EmbeddedMdnsBrowser {
+    socket: Option<Arc<MdnsSocket>>,
+    service_type: ServiceType,
+    interface_index: u32,
+    address_family: AddressFamily,
+    address_policy: AddressPolicy,
+    include_raw_records: bool,
+    /// The discovered service instances, by lowercase instance name
+    instances: HashMap<String, Instance>,
+    /// The A and AAAA records received, by lowercase host name
+    hosts: HashMap<String, Vec<ResourceRecord>>,
+    /// The lowercase names of the reported instances that have gone away
+    debouncer: Debouncer<String>,
+    query_interval: Duration,
+    next_query: Instant,
+    service_discovered_callback: Option<Box<ServiceDiscoveredCallback>>,
+    error_callback: Option<Box<ErrorCallback>>,
+    control: BrowserControl,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl TMdnsBrowser for EmbeddedMdnsBrowser {
+    fn new(service_type: ServiceType) -> Self {
+        Self {
+            socket: None,
+            service_type,
+            interface_index: embedded_util::interface_index(NetworkInterface::Unspec),
+            address_family: AddressFamily::default(),
+            address_policy: AddressPolicy::default(),
+            include_raw_records: false,
+            instances: HashMap::new(),
+            hosts: HashMap::new(),
+            debouncer: Debouncer::default(),
+            query_interval: INITIAL_QUERY_INTERVAL,
+            next_query: Instant::now(),
+            service_discovered_callback: None,
+            error_callback: None,
+            control: BrowserControl::default(),
+            user_context: None,
+        }
+    }
+
+    /// Services are browsed on all interfaces, the interface is only used as the scope ID of
+    /// discovered link-local IPv6 addresses.
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface_index = embedded_util::interface_index(interface);
+    }
+
+    /// mDNS only supports the `local` domain, other domains are ignored.
+    fn set_domain(&mut self, domain: &str) {
+        if domain.trim_end_matches('.') != embedded_util::LOCAL_DOMAIN {
+            warn!("ignoring unsupported domain: {}", domain);
+        }
+    }
+
+    /// The embedded implementation does not support any flags, they are ignored.
+    fn set_flags(&mut self, _flags: ServiceFlags) {}
+
+    fn set_address_family(&mut self, address_family: AddressFamily) {
+        self.address_family = address_family;
+    }
+
+    fn set_address_policy(&mut self, address_policy: AddressPolicy) {
+        self.address_policy = address_policy;
+    }
+
+    fn set_include_raw_records(&mut self, include_raw_records: bool) {
+        self.include_raw_records = include_raw_records;
+    }
+
+    fn set_resolve_concurrency(&mut self, _concurrency: usize) {
+        // services are resolved from the announcements that have been received
+    }
+
+    fn set_debounce(&mut self, window: Duration) {
+        self.debouncer.set_window(window);
+    }
+
+    fn set_service_discovered_callback(
+        &mut self,
+        service_discovered_callback: Box<ServiceDiscoveredCallback>,
+    ) {
+        self.service_discovered_callback = Some(service_discovered_callback);
+    }
+
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>) {
+        self.error_callback = Some(error_callback);
+    }
+
+    fn control(&self) -> BrowserControl {
+        self.control.clone()
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        self.user_context = Some(Arc::from(context));
+    }
+
+    /// The embedded implementation does not depend on a daemon, so the callback is never invoked.
+    fn set_reconnected_callback(&mut self, _reconnected_callback: Box<DaemonReconnectedCallback>) {}
+
+    fn browse_services(&mut self) -> Result<EventLoop<'_>> {
+        let _span = operation_span!("browse", service_type = self.service_type.to_string());
+
+        debug!("Browsing services: {:?}", self);
+
+        let socket = match &self.socket {
+            Some(s) => s.clone(),
+            None => Arc::new(MdnsSocket::new()?),
+        };
+
+        self.socket = Some(socket.clone());
+        self.query_interval = INITIAL_QUERY_INTERVAL;
+        self.next_query = Instant::now();
+        self.send_queries()?;
+
+        let browser: *mut Self = self;
+
+        Ok(EventLoop::new(socket, Operation::Browse)
+            .with_on_message(Box::new(move |message, addr| unsafe {
+                (*browser).handle_message(message, addr)
+            }))
+            .with_on_poll(Box::new(move || unsafe { (*browser).handle_poll() })))
+    }
+}
+
+impl EmbeddedMdnsBrowser {
+    fn kind(&self) -> String {
+        format!(
+            "_{}._{}",
+            self.service_type.name(),
+            self.service_type.protocol()
+        )
+    }
+
+    /// Returns the name to query PTR records of, which is the first sub-type if any.
+    fn browse_name(&self) -> String {
+        let kind_name = embedded_util::kind_name(&self.kind());
+
+        match self.service_type.sub_types().first() {
+            Some(sub_type) => format!("_{}._sub.{}", sub_type, kind_name),
+            None => kind_name,
+        }
+    }
+
+    fn invoke_callback(&self, result: Result<ServiceDiscovery>) {
+        if !self.control.is_running() {
+            return;
+        }
+
+        match (
+            result,
+            &self.error_callback,
+            &self.service_discovered_callback,
+        ) {
+            (Err(e), Some(f), _) => f(e, self.user_context.clone()),
+            (result, _, Some(f)) => f(result, self.user_context.clone()),
+            _ => warn!("attempted to invoke callback but none was set"),
+        }
+    }
+
+    fn handle_poll(&mut self) {
+        if let Err(e) = self.send_queries() {
+            self.invoke_callback(Err(e));
+        }
+    }
+
+    /// Queries the service type and the missing records of the discovered instances, if due.
+    fn send_queries(&mut self) -> Result<()> {
+        let now = Instant::now();
+        let mut questions = vec![];
+
+        if now >= self.next_query {
+            questions.push(Question {
+                name: self.browse_name(),
+                qtype: dns::TYPE_PTR,
+                unicast_response: false,
+            });
+
+            self.next_query = now + self.query_interval;
+            self.query_interval = (self.query_interval * 2).min(MAX_QUERY_INTERVAL);
+        }
+
+        let hosts = &self.hosts;
+
+        for instance in self.instances.values_mut() {
+            if instance.reported || now < instance.next_resolve {
+                continue;
+            }
+
+            instance.next_resolve = now + RESOLVE_INTERVAL;
+
+            let srv_target = match &instance.srv {
+                Some(ResourceRecord {
+                    data: RecordData::Srv(srv),
+                    ..
+                }) => Some(srv.target.clone()),
+                _ => None,
+            };
+
+            match srv_target {
+                Some(target) => {
+                    if !matches!(hosts.get(&target.to_ascii_lowercase()), Some(a) if !a.is_empty())
+                    {
+                        for qtype in &[dns::TYPE_A, dns::TYPE_AAAA] {
+                            questions.push(Question {
+                                name: target.clone(),
+                                qtype: *qtype,
+                                unicast_response: false,
+                            });
+                        }
+                    }
+                }
+                None => questions.push(Question {
+                    name: instance.name.clone(),
+                    qtype: dns::TYPE_SRV,
+                    unicast_response: false,
+                }),
+            }
+
+            if instance.txt.is_none() {
+                questions.push(Question {
+                    name: instance.name.clone(),
+                    qtype: dns::TYPE_TXT,
+                    unicast_response: false,
+                });
+            }
+        }
+
+        if questions.is_empty() {
+            return Ok(());
+        }
+
+        match &self.socket {
+            Some(socket) => socket
+                .send(&Message::query(questions))
+                .map_err(|e| e.with_operation(Operation::Browse)),
+            None => Ok(()),
+        }
+    }
+
+    fn handle_message(&mut self, message: &Message, _addr: SocketAddr) {
+        if !message.response {
+            return;
+        }
+
+        let browse_name = self.browse_name();
+        let kind = self.kind();
+
+        for record in message.records() {
+            match &record.data {
+                RecordData::Ptr(instance_name) if message::name_eq(&record.name, &browse_name) => {
+                    if embedded_util::split_instance_name(instance_name, &kind).is_none() {
+                        continue;
+                    }
+
+                    let key = instance_name.to_ascii_lowercase();
+
+                    if record.ttl == 0 {
+                        // a goodbye announces that the service has gone away
+                        if matches!(self.instances.remove(&key), Some(i) if i.reported) {
+                            self.debouncer.went_away(|| key);
+                        }
+                    } else {
+                        let debouncer = &mut self.debouncer;
+
+                        self.instances
+                            .entry(key)
+                            .or_insert_with_key(|key| {
+                                let mut instance = Instance::new(instance_name);
+                                // an instance that came back is not resolved and reported again
+                                instance.reported = debouncer.has_returned(|| key.clone());
+                                instance
+                            })
+                            .ptr = Some(record.clone());
+                    }
+                }
+                RecordData::Srv(_) | RecordData::Txt(_) => {
+                    let instance = match self.instances.get_mut(&record.name.to_ascii_lowercase()) {
+                        Some(i) => i,
+                        None => continue,
+                    };
+
+                    let slot = if record.rrtype == dns::TYPE_SRV {
+                        &mut instance.srv
+                    } else {
+                        &mut instance.txt
+                    };
+
+                    *slot = if record.ttl == 0 {
+                        None
+                    } else {
+                        Some(record.clone())
+                    };
+                }
+                RecordData::A(_) | RecordData::Aaaa(_) => {
+                    let addresses = self
+                        .hosts
+                        .entry(record.name.to_ascii_lowercase())
+                        .or_default();
+
+                    if record.cache_flush {
+                        addresses.retain(|r| r.rrtype != record.rrtype);
+                    } else {
+                        addresses.retain(|r| r.data != record.data);
+                    }
+
+                    if record.ttl > 0 {
+                        addresses.push(record.clone());
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.report_resolved();
+    }
+
+    /// Reports the instances that have been resolved since the last call.
+    fn report_resolved(&mut self) {
+        let mut results = vec![];
+
+        for instance in self.instances.values_mut() {
+            if instance.reported {
+                continue;
+            }
+
+            if let Some(result) = resolve(
+                instance,
+                &self.hosts,
+                &self.service_type,
+                self.address_family,
+                &self.address_policy,
+                self.interface_index,
+                self.include_raw_records,
+            ) {
+                instance.reported = true;
+                results.push(result);
+            }
+        }
+
+        for result in results {
+            self.invoke_callback(result);
+        }
+    }
+}
+
+impl fmt::Debug for EmbeddedMdnsBrowser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EmbeddedMdnsBrowser")
+            .field("service_type", &self.service_type)
+            .field("interface_index", &self.interface_index)
+            .field("address_family", &self.address_family)
+            .field("address_policy", &self.address_policy)
+            .finish()
+    }
+}
+
+/// The records received for a discovered service instance
+#[derive(Debug)]
+struct Instance {
+    /// The escaped, fully qualified instance name
+    name: String,
+    ptr: Option<ResourceRecord>,
+    srv: Option<ResourceRecord>,
+    txt: Option<ResourceRecord>,
+    next_resolve: Instant,
+    reported: bool,
+}
+
+impl Instance {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            ptr: None,
+            srv: None,
+            txt: None,
+            // give the responder a chance to send the records along with the PTR record
+            next_resolve: Instant::now() + RESOLVE_INTERVAL,
+            reported: false,
+        }
+    }
+}
+
+/// Returns the `ServiceDiscovery` of the specified `instance`, or `None` if its SRV, TXT or
+/// address records have not been received yet.
+fn resolve(
+    instance: &Instance,
+    hosts: &HashMap<String, Vec<ResourceRecord>>,
+    service_type: &ServiceType,
+    address_family: AddressFamily,
+    address_policy: &AddressPolicy,
+    interface_index: u32,
+    include_raw_records: bool,
+) -> Option<Result<ServiceDiscovery>> {
+    let srv_record = instance.srv.as_ref()?;
+    let txt_record = instance.txt.as_ref()?;
+
+    let srv = match &srv_record.data {
+        RecordData::Srv(srv) => srv,
+        _ => return None,
+    };
+
+    let address_records = hosts
+        .get(&srv.target.to_ascii_lowercase())?
+        .iter()
+        .filter(|r| match r.data {
+            RecordData::A(_) => address_family != AddressFamily::Ipv6,
+            RecordData::Aaaa(_) => address_family != AddressFamily::Ipv4,
+            _ => false,
+        })
+        .collect::<Vec<_>>();
+
+    let mut addresses = address_records
+        .iter()
+        .filter_map(|r| match r.data {
+            RecordData::A(v4) => Some(IpAddr::V4(v4)),
+            RecordData::Aaaa(v6) => Some(IpAddr::V6(v6)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    address_policy.sort(&mut addresses);
+
+    let address = *addresses.first()?;
+
+    let kind = format!("_{}._{}", service_type.name(), service_type.protocol());
+
+    let name = match embedded_util::split_instance_name(&instance.name, &kind) {
+        Some(n) => n,
+        None => {
+            return Some(Err(format!(
+                "unexpected service instance name: {}",
+                instance.name
+            )
+            .into()))
+        }
+    };
+
+    let txt = match &txt_record.data {
+        RecordData::Txt(entries) if !entries.is_empty() => {
+            let mut txt = TxtRecord::new();
+            for (key, value) in entries {
+                if let Err(e) = txt.insert(key, value) {
+                    return Some(Err(e));
+                }
+            }
+            Some(txt)
+        }
+        _ => None,
+    };
+
+    let raw_records = if include_raw_records {
+        let raw_records = instance
+            .ptr
+            .iter()
+            .chain(Some(srv_record))
+            .chain(Some(txt_record))
+            .chain(address_records)
+            .map(|r| r.to_record())
+            .collect::<Result<_>>();
+
+        match raw_records {
+            Ok(r) => r,
+            Err(e) => return Some(Err(e)),
+        }
+    } else {
+        vec![]
+    };
+
+    let result = ServiceDiscovery::builder()
+        .name(name)
+        .service_type(service_type.clone())
+        .domain(embedded_util::LOCAL_DOMAIN.to_string())
+        .host_name(srv.target.trim_end_matches('.').to_string())
+        .address(address)
+        .addresses(addresses)
+        .scope_id(ffi::link_local_scope_id(&address, interface_index))
+        .port(srv.port)
+        .priority(srv.priority)
+        .weight(srv.weight)
+        .txt(txt)
+        .raw_records(raw_records)
+        .build()
+        .map_err(Error::from);
+
+    Some(result)
+}
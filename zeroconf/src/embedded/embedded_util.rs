@@ -0,0 +1,125 @@
+//! Utilities related to the embedded mDNS implementation
+
+use super::message;
+use crate::{dns, ffi, NetworkInterface};
+use std::ffi::CStr;
+use std::net::IpAddr;
+
+/// The only domain supported by mDNS
+pub const LOCAL_DOMAIN: &str = "local";
+
+/// Converts the specified [`NetworkInterface`] to an interface index, `0` meaning all interfaces.
+/// Peer-to-peer interfaces are not supported and are treated as `Unspec`.
+///
+/// [`NetworkInterface`]: ../../enum.NetworkInterface.html
+pub fn interface_index(interface: NetworkInterface) -> u32 {
+    match interface {
+        NetworkInterface::AtIndex(i) => i,
+        NetworkInterface::Unspec | NetworkInterface::PeerToPeer => 0,
+    }
+}
+
+/// Returns the host name of this computer without any domain.
+pub fn host_name() -> String {
+    let mut buf = [0 as libc::c_char; 256];
+
+    let name = unsafe {
+        if libc::gethostname(buf.as_mut_ptr(), buf.len()) != 0 {
+            return fallback_host_name();
+        }
+
+        // the name is not guaranteed to be null-terminated if it was truncated
+        buf[buf.len() - 1] = 0;
+        CStr::from_ptr(buf.as_ptr()).to_string_lossy().into_owned()
+    };
+
+    match name.split('.').next() {
+        Some(n) if !n.is_empty() && n != "localhost" => n.to_string(),
+        _ => fallback_host_name(),
+    }
+}
+
+/// Returns the host name to use if the system does not have one, which is the norm on Android.
+///
+/// The name is derived from the first IPv4 address of this computer (e.g. `android-192-168-1-2`)
+/// so that it is unique on the local network.
+#[cfg(target_os = "android")]
+fn fallback_host_name() -> String {
+    local_addresses(0)
+        .into_iter()
+        .find(|ip| ip.is_ipv4())
+        .map(|ip| format!("android-{}", ip.to_string().replace('.', "-")))
+        .unwrap_or_else(|| String::from("android"))
+}
+
+#[cfg(not(target_os = "android"))]
+fn fallback_host_name() -> String {
+    String::from("localhost")
+}
+
+/// Returns the host name of this computer on the `local` domain (e.g. `hostname.local`).
+pub fn local_host_name() -> String {
+    format!("{}.{}", dns::escape_label(&host_name()), LOCAL_DOMAIN)
+}
+
+/// Returns the addresses assigned to the network interface at `interface_index` (all interfaces
+/// if `0`), excluding loopback addresses.
+pub fn local_addresses(interface_index: u32) -> Vec<IpAddr> {
+    let interface = match interface_index {
+        0 => None,
+        i => Some(ffi::interface_name(i).unwrap_or_default()),
+    };
+
+    crate::network_watcher::snapshot()
+        .into_iter()
+        .filter(|(name, ip)| !ip.is_loopback() && interface.iter().all(|i| i == name))
+        .map(|(_, ip)| ip)
+        .collect()
+}
+
+/// Returns the escaped, fully qualified name of the service instance `name` of the service type
+/// `kind` (e.g. `_http._tcp`).
+pub fn instance_name(name: &str, kind: &str) -> String {
+    format!("{}.{}.{}", dns::escape_label(name), kind, LOCAL_DOMAIN)
+}
+
+/// Returns the unescaped name of the service instance of the specified escaped, fully qualified
+/// `instance_name`, provided it is an instance of the service type `kind` (e.g. `_http._tcp`).
+pub fn split_instance_name(instance_name: &str, kind: &str) -> Option<String> {
+    let labels = message::split_name(instance_name);
+
+    match labels.as_slice() {
+        [name, rest @ ..] if message::name_eq(&rest.join("."), &kind_name(kind)) => {
+            Some(name.clone())
+        }
+        _ => None,
+    }
+}
+
+/// Returns the fully qualified name of the service type `kind` (e.g. `_http._tcp.local`).
+pub fn kind_name(kind: &str) -> String {
+    format!("{}.{}", kind, LOCAL_DOMAIN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instance_name_round_trip() {
+        let instance_name = instance_name("My.Service", "_http._tcp");
+        assert_eq!(instance_name, "My\\.Service._http._tcp.local");
+        assert_eq!(
+            split_instance_name(&instance_name, "_http._tcp"),
+            Some("My.Service".to_string())
+        );
+    }
+
+    #[test]
+    fn split_instance_name_rejects_other_service_types() {
+        assert_eq!(
+            split_instance_name("My Service._ipp._tcp.local", "_http._tcp"),
+            None
+        );
+    }
+}
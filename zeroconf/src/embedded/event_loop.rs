@@ -0,0 +1,86 @@
+//! Event loop for running a `MdnsService` or `MdnsBrowser`.
+
+use super::message::Message;
+use super::responder;
+use super::socket::MdnsSocket;
+use crate::error::Operation;
+use crate::event_loop::TEventLoop;
+use crate::Result;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Maximum number of messages handled by a single call to `poll()`
+const MAX_MESSAGES_PER_POLL: usize = 64;
+
+/// Handles a message received from the specified address
+type MessageHandler<'a> = dyn Fn(&Message, SocketAddr) + 'a;
+
+#[derive(new)]
+pub struct EmbeddedEventLoop<'a> {
+    socket: Arc<MdnsSocket>,
+    /// The operation of the errors returned by `poll()`
+    operation: Operation,
+    #[new(default)]
+    on_message: Option<Box<MessageHandler<'a>>>,
+    #[new(default)]
+    on_poll: Option<Box<dyn Fn() + 'a>>,
+}
+
+impl<'a> EmbeddedEventLoop<'a> {
+    /// Sets a function to invoke for each message received.
+    pub(crate) fn with_on_message(mut self, on_message: Box<MessageHandler<'a>>) -> Self {
+        self.on_message = Some(on_message);
+        self
+    }
+
+    /// Sets a function to invoke after each iteration of the event loop. This is used to perform
+    /// periodic work that is not driven by received messages (e.g. retransmitting queries).
+    pub(crate) fn with_on_poll(mut self, on_poll: Box<dyn Fn() + 'a>) -> Self {
+        self.on_poll = Some(on_poll);
+        self
+    }
+}
+
+impl<'a> TEventLoop for EmbeddedEventLoop<'a> {
+    /// Polls for new events.
+    ///
+    /// Waits up to the specified timeout for an mDNS message to be received and handles it along
+    /// with any other messages that have already been received. Queries are answered on behalf of
+    /// all services registered in this process.
+    fn poll(&self, timeout: Duration) -> Result<()> {
+        let recv = |timeout| {
+            self.socket
+                .recv(timeout)
+                .map_err(|e| e.with_operation(self.operation))
+        };
+
+        let mut next = recv(timeout)?;
+        let mut handled = 0;
+
+        while let Some((message, addr)) = next {
+            if !message.response {
+                if let Err(e) = responder::respond(&self.socket, &message, addr) {
+                    warn!("could not respond to query: {}", e);
+                }
+            }
+
+            if let Some(f) = &self.on_message {
+                f(&message, addr);
+            }
+
+            handled += 1;
+            if handled == MAX_MESSAGES_PER_POLL {
+                break;
+            }
+
+            next = recv(Duration::from_secs(0))?;
+        }
+
+        if let Some(f) = &self.on_poll {
+            f();
+        }
+
+        Ok(())
+    }
+}
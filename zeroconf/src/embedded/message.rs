@@ -0,0 +1,513 @@
+//! Encoding and decoding of mDNS messages (RFC 1035 and RFC 6762)
+
+use crate::dns::{self, Srv};
+use crate::prelude::*;
+use crate::txt_record::TxtRecordView;
+use crate::{Record, Result};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+/// The header flags of a response (`QR` and `AA` set)
+const FLAGS_RESPONSE: u16 = 0x8400;
+/// The `QR` bit of the header flags
+const FLAG_QR: u16 = 0x8000;
+/// The top bit of the class of a question requests a unicast response (`QU`), the top bit of the
+/// class of a record flushes cached records of the same name and type
+const CLASS_TOP_BIT: u16 = 0x8000;
+/// Maximum number of compression pointers followed while decoding a single name
+const MAX_POINTERS: usize = 32;
+
+/// Returns true if the specified names are equal, ignoring ASCII case and trailing dots.
+pub fn name_eq(a: &str, b: &str) -> bool {
+    a.trim_end_matches('.')
+        .eq_ignore_ascii_case(b.trim_end_matches('.'))
+}
+
+/// Splits the specified escaped name (see [`dns::escape_label()`]) into its unescaped labels.
+///
+/// [`dns::escape_label()`]: ../../dns/fn.escape_label.html
+pub fn split_name(name: &str) -> Vec<String> {
+    let mut labels = vec![];
+    let mut label = String::new();
+    let mut chars = name.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => label.extend(chars.next()),
+            '.' => labels.push(std::mem::take(&mut label)),
+            c => label.push(c),
+        }
+    }
+
+    labels.push(label);
+    labels.retain(|l| !l.is_empty());
+    labels
+}
+
+/// A question of a query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Question {
+    pub name: String,
+    pub qtype: u16,
+    /// Whether the querier prefers a unicast response (the `QU` bit)
+    pub unicast_response: bool,
+}
+
+impl Question {
+    /// Returns true if a record of the specified `name` and `rrtype` answers this question.
+    pub fn matches(&self, name: &str, rrtype: u16) -> bool {
+        (self.qtype == rrtype || self.qtype == dns::TYPE_ANY) && name_eq(&self.name, name)
+    }
+}
+
+/// The decoded RDATA of a resource record
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecordData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Ptr(String),
+    Srv(Srv),
+    /// The `key=value` entries of a TXT record
+    Txt(Vec<(String, String)>),
+    Other(Vec<u8>),
+}
+
+/// A resource record of a message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceRecord {
+    pub name: String,
+    pub rrtype: u16,
+    /// Whether cached records of the same name and type are to be flushed (the cache-flush bit)
+    pub cache_flush: bool,
+    pub ttl: u32,
+    pub data: RecordData,
+}
+
+impl ResourceRecord {
+    /// Creates a new record of the specified `name` and `ttl`. The type of the record is derived
+    /// from `data`.
+    pub fn new(name: &str, ttl: u32, data: RecordData) -> Self {
+        let (rrtype, cache_flush) = match &data {
+            RecordData::A(_) => (dns::TYPE_A, true),
+            RecordData::Aaaa(_) => (dns::TYPE_AAAA, true),
+            // a PTR record is shared by all instances of a service type
+            RecordData::Ptr(_) => (dns::TYPE_PTR, false),
+            RecordData::Srv(_) => (dns::TYPE_SRV, true),
+            RecordData::Txt(_) => (dns::TYPE_TXT, true),
+            RecordData::Other(_) => (0, false),
+        };
+
+        Self {
+            name: name.to_string(),
+            rrtype,
+            cache_flush,
+            ttl,
+            data,
+        }
+    }
+
+    /// Returns the uncompressed RDATA of this record.
+    ///
+    /// Returns an error of kind `ErrorKind::InvalidArgument` if a TXT entry exceeds 255 bytes.
+    pub fn rdata(&self) -> Result<Vec<u8>> {
+        Ok(match &self.data {
+            RecordData::A(ip) => ip.octets().to_vec(),
+            RecordData::Aaaa(ip) => ip.octets().to_vec(),
+            RecordData::Ptr(name) => encode_name(name),
+            RecordData::Srv(srv) => {
+                let mut buf = vec![];
+                buf.extend_from_slice(&srv.priority.to_be_bytes());
+                buf.extend_from_slice(&srv.weight.to_be_bytes());
+                buf.extend_from_slice(&srv.port.to_be_bytes());
+                buf.extend(encode_name(&srv.target));
+                buf
+            }
+            RecordData::Txt(entries) => dns::txt_rdata(entries.iter().cloned())?,
+            RecordData::Other(rdata) => rdata.clone(),
+        })
+    }
+
+    /// Converts this record to the public [`Record`] type.
+    ///
+    /// [`Record`]: ../../struct.Record.html
+    pub fn to_record(&self) -> Result<Record> {
+        Ok(Record::builder()
+            .name(self.name.clone())
+            .rrtype(self.rrtype)
+            .rrclass(dns::CLASS_IN)
+            .rdata(self.rdata()?)
+            .ttl(self.ttl)
+            .build()?)
+    }
+}
+
+/// An mDNS query or response
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub id: u16,
+    pub response: bool,
+    pub questions: Vec<Question>,
+    pub answers: Vec<ResourceRecord>,
+    pub additionals: Vec<ResourceRecord>,
+}
+
+impl Message {
+    /// Creates a new query for the specified `questions`.
+    pub fn query(questions: Vec<Question>) -> Self {
+        Self {
+            questions,
+            ..Default::default()
+        }
+    }
+
+    /// Creates a new response with the specified `answers` and `additionals`.
+    pub fn response(answers: Vec<ResourceRecord>, additionals: Vec<ResourceRecord>) -> Self {
+        Self {
+            response: true,
+            answers,
+            additionals,
+            ..Default::default()
+        }
+    }
+
+    /// Returns an iterator over the answers and additional records of this message.
+    pub fn records(&self) -> impl Iterator<Item = &ResourceRecord> {
+        self.answers.iter().chain(self.additionals.iter())
+    }
+
+    /// Encodes this message in DNS wire format. Names are not compressed.
+    ///
+    /// Returns an error of kind `ErrorKind::InvalidArgument` if a TXT entry exceeds 255 bytes.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut buf = vec![];
+        let flags = if self.response { FLAGS_RESPONSE } else { 0 };
+
+        for n in &[
+            self.id,
+            flags,
+            self.questions.len() as u16,
+            self.answers.len() as u16,
+            0,
+            self.additionals.len() as u16,
+        ] {
+            buf.extend_from_slice(&n.to_be_bytes());
+        }
+
+        for question in &self.questions {
+            let class = if question.unicast_response {
+                dns::CLASS_IN | CLASS_TOP_BIT
+            } else {
+                dns::CLASS_IN
+            };
+
+            buf.extend(encode_name(&question.name));
+            buf.extend_from_slice(&question.qtype.to_be_bytes());
+            buf.extend_from_slice(&class.to_be_bytes());
+        }
+
+        for record in self.records() {
+            let class = if record.cache_flush {
+                dns::CLASS_IN | CLASS_TOP_BIT
+            } else {
+                dns::CLASS_IN
+            };
+
+            let rdata = record.rdata()?;
+
+            buf.extend(encode_name(&record.name));
+            buf.extend_from_slice(&record.rrtype.to_be_bytes());
+            buf.extend_from_slice(&class.to_be_bytes());
+            buf.extend_from_slice(&record.ttl.to_be_bytes());
+            buf.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            buf.extend(rdata);
+        }
+
+        Ok(buf)
+    }
+
+    /// Decodes the specified message in DNS wire format. Records of the authority section are
+    /// skipped.
+    ///
+    /// Returns `None` if `data` is not a valid message.
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        let mut reader = Reader { data, pos: 0 };
+
+        let id = reader.u16()?;
+        let flags = reader.u16()?;
+        let qdcount = reader.u16()?;
+        let ancount = reader.u16()?;
+        let nscount = reader.u16()?;
+        let arcount = reader.u16()?;
+
+        let mut message = Self {
+            id,
+            response: flags & FLAG_QR != 0,
+            ..Default::default()
+        };
+
+        for _ in 0..qdcount {
+            let name = reader.name()?;
+            let qtype = reader.u16()?;
+            let class = reader.u16()?;
+
+            message.questions.push(Question {
+                name,
+                qtype,
+                unicast_response: class & CLASS_TOP_BIT != 0,
+            });
+        }
+
+        for _ in 0..ancount {
+            message.answers.push(reader.record()?);
+        }
+
+        for _ in 0..nscount {
+            reader.record()?;
+        }
+
+        for _ in 0..arcount {
+            message.additionals.push(reader.record()?);
+        }
+
+        Some(message)
+    }
+}
+
+/// Encodes the specified escaped name in DNS wire format.
+fn encode_name(name: &str) -> Vec<u8> {
+    let labels = split_name(name);
+    dns::encode_labels(labels.iter().map(|l| l.as_str()))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos + len)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        let b = self.bytes(2)?;
+        Some(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        let b = self.bytes(4)?;
+        Some(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /// Decodes the possibly compressed name at the current position into an escaped name.
+    fn name(&mut self) -> Option<String> {
+        let mut name = String::new();
+        let mut pos = self.pos;
+        let mut end = None;
+        let mut pointers = 0;
+
+        loop {
+            let len = *self.data.get(pos)? as usize;
+
+            match len {
+                0 => {
+                    pos += 1;
+                    break;
+                }
+                len if len & 0xc0 == 0xc0 => {
+                    pointers += 1;
+                    if pointers > MAX_POINTERS {
+                        return None;
+                    }
+
+                    let next = *self.data.get(pos + 1)? as usize;
+                    // the name continues after the first pointer
+                    end.get_or_insert(pos + 2);
+                    pos = ((len & 0x3f) << 8) | next;
+                }
+                len if len > 63 => return None,
+                len => {
+                    let label = self.data.get(pos + 1..pos + 1 + len)?;
+                    if !name.is_empty() {
+                        name.push('.');
+                    }
+                    dns::push_escaped_label(&mut name, &String::from_utf8_lossy(label));
+                    pos += 1 + len;
+                }
+            }
+        }
+
+        self.pos = end.unwrap_or(pos);
+
+        Some(name)
+    }
+
+    fn record(&mut self) -> Option<ResourceRecord> {
+        let name = self.name()?;
+        let rrtype = self.u16()?;
+        let class = self.u16()?;
+        let ttl = self.u32()?;
+        let rdlen = self.u16()? as usize;
+
+        let start = self.pos;
+        let rdata = self.bytes(rdlen)?;
+
+        // names in the RDATA may be compressed, so they are decoded from the whole message
+        let mut rdata_reader = Reader {
+            data: &self.data[..start + rdlen],
+            pos: start,
+        };
+
+        let data = match rrtype {
+            dns::TYPE_A if rdlen == 4 => {
+                RecordData::A(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3]))
+            }
+            dns::TYPE_AAAA if rdlen == 16 => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(rdata);
+                RecordData::Aaaa(Ipv6Addr::from(octets))
+            }
+            dns::TYPE_PTR => RecordData::Ptr(rdata_reader.name()?),
+            dns::TYPE_SRV => RecordData::Srv(Srv {
+                priority: rdata_reader.u16()?,
+                weight: rdata_reader.u16()?,
+                port: rdata_reader.u16()?,
+                target: rdata_reader.name()?,
+            }),
+            dns::TYPE_TXT => RecordData::Txt(parse_txt(rdata)),
+            _ => RecordData::Other(rdata.to_vec()),
+        };
+
+        Some(ResourceRecord {
+            name,
+            rrtype,
+            cache_flush: class & CLASS_TOP_BIT != 0,
+            ttl,
+            data,
+        })
+    }
+}
+
+/// Decodes the `key=value` entries of the specified TXT record RDATA. Entries without a `'='` have
+/// an empty value, empty entries and entries without a key are skipped.
+fn parse_txt(rdata: &[u8]) -> Vec<(String, String)> {
+    TxtRecordView::new(rdata)
+        .iter()
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_name_unescapes_labels() {
+        assert_eq!(
+            split_name("My\\.Printer._ipp._tcp.local."),
+            vec!["My.Printer", "_ipp", "_tcp", "local"]
+        );
+    }
+
+    #[test]
+    fn name_eq_ignores_case_and_trailing_dot() {
+        assert!(name_eq("_http._tcp.local.", "_HTTP._tcp.local"));
+        assert!(!name_eq("_http._tcp.local", "_ipp._tcp.local"));
+    }
+
+    #[test]
+    fn encode_decode_response_round_trip() {
+        let srv = Srv {
+            priority: 0,
+            weight: 0,
+            port: 8080,
+            target: "host.local".to_string(),
+        };
+
+        let txt = vec![("foo".to_string(), "bar".to_string())];
+
+        let message = Message::response(
+            vec![
+                ResourceRecord::new(
+                    "_http._tcp.local",
+                    4500,
+                    RecordData::Ptr("My\\.Service._http._tcp.local".to_string()),
+                ),
+                ResourceRecord::new("My\\.Service._http._tcp.local", 120, RecordData::Srv(srv)),
+                ResourceRecord::new("My\\.Service._http._tcp.local", 4500, RecordData::Txt(txt)),
+            ],
+            vec![ResourceRecord::new(
+                "host.local",
+                120,
+                RecordData::A(Ipv4Addr::new(192, 168, 1, 2)),
+            )],
+        );
+
+        assert_eq!(Message::decode(&message.encode().unwrap()), Some(message));
+    }
+
+    #[test]
+    fn encode_decode_query_round_trip() {
+        let message = Message::query(vec![Question {
+            name: "_http._tcp.local".to_string(),
+            qtype: dns::TYPE_PTR,
+            unicast_response: true,
+        }]);
+
+        assert_eq!(Message::decode(&message.encode().unwrap()), Some(message));
+    }
+
+    #[test]
+    fn decode_follows_compression_pointers() {
+        let mut data = vec![0, 0, 0x84, 0, 0, 0, 0, 1, 0, 0, 0, 0];
+        // `_http._tcp.local` PTR `a._http._tcp.local`, compressed to a pointer to offset 12
+        data.extend(dns::encode_name("_http._tcp.local"));
+        data.extend_from_slice(&[0, 12, 0, 1, 0, 0, 0x11, 0x94, 0, 4, 1, b'a', 0xc0, 12]);
+
+        let message = Message::decode(&data).unwrap();
+
+        assert_eq!(
+            message.answers[0].data,
+            RecordData::Ptr("a._http._tcp.local".to_string())
+        );
+    }
+
+    #[test]
+    fn decode_rejects_pointer_loops() {
+        let mut data = vec![0, 0, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+        data.extend_from_slice(&[0xc0, 12, 0, 12, 0, 1]);
+
+        assert_eq!(Message::decode(&data), None);
+    }
+
+    #[test]
+    fn decode_rejects_truncated_messages() {
+        let message = Message::query(vec![Question {
+            name: "_http._tcp.local".to_string(),
+            qtype: dns::TYPE_PTR,
+            unicast_response: false,
+        }]);
+
+        let data = message.encode().unwrap();
+
+        assert_eq!(Message::decode(&data[..data.len() - 1]), None);
+    }
+
+    #[test]
+    fn parse_txt_success() {
+        let rdata = dns::txt_rdata(vec![
+            ("foo".to_string(), "bar".to_string()),
+            ("baz".to_string(), "".to_string()),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            parse_txt(&rdata),
+            vec![
+                ("foo".to_string(), "bar".to_string()),
+                ("baz".to_string(), "".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,33 @@
+//! ZeroConf bindings over a pure-Rust mDNS implementation
+//!
+//! This module implements mDNS and DNS-SD ([RFC 6762] and [RFC 6763]) directly over UDP sockets,
+//! so neither Avahi nor mDNSResponder needs to be installed or running. This is useful in
+//! containers, minimal Linux systems and CI environments. It is enabled with the `embedded`
+//! feature, which replaces the system's mDNS implementation on all unix platforms.
+//!
+//! # Note
+//! The embedded implementation only supports the `local` domain over IPv4 multicast, although
+//! IPv6 addresses are published and resolved. Services are announced without probing for name
+//! conflicts first. The resolvers, record queries and port mappings are not available.
+//!
+//! # Android
+//! The embedded implementation is always used on Android. Android filters out multicast packets
+//! unless the app holds a [`WifiManager.MulticastLock`], so the app must acquire one (which
+//! requires the `CHANGE_WIFI_MULTICAST_STATE` permission) before registering or browsing
+//! services, and release it once the event loops are no longer polled. The `INTERNET` permission
+//! is required as well.
+//!
+//! [RFC 6762]: https://www.rfc-editor.org/rfc/rfc6762
+//! [RFC 6763]: https://www.rfc-editor.org/rfc/rfc6763
+//! [`WifiManager.MulticastLock`]: https://developer.android.com/reference/android/net/wifi/WifiManager.MulticastLock
+
+pub(crate) mod message;
+pub(crate) mod responder;
+pub(crate) mod socket;
+
+pub mod browser;
+pub mod embedded_util;
+pub mod event_loop;
+pub mod registration_handle;
+pub mod service;
+pub mod txt_record;
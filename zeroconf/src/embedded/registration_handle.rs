@@ -0,0 +1,39 @@
+//! Embedded implementation for cross-platform registration handle
+
+use super::event_loop::EmbeddedEventLoop;
+use super::service::EmbeddedMdnsService;
+use crate::prelude::*;
+use crate::registration_handle::TRegistrationHandle;
+use crate::service;
+use crate::Result;
+use std::time::Duration;
+
+#[derive(new)]
+pub struct EmbeddedRegistrationHandle<'a> {
+    service: *mut EmbeddedMdnsService,
+    event_loop: EmbeddedEventLoop<'a>,
+}
+
+impl<'a> TEventLoop for EmbeddedRegistrationHandle<'a> {
+    fn poll(&self, timeout: Duration) -> Result<()> {
+        self.event_loop.poll(timeout)
+    }
+}
+
+impl<'a> TRegistrationHandle for EmbeddedRegistrationHandle<'a> {
+    fn set_name(&mut self, name: &str) -> Result<()> {
+        unsafe {
+            (*self.service).set_name(name);
+            (*self.service).reregister()
+        }
+    }
+
+    fn set_port(&mut self, port: u16) -> Result<()> {
+        service::check_port(port)?;
+
+        unsafe {
+            (*self.service).set_port(port);
+            (*self.service).reregister()
+        }
+    }
+}
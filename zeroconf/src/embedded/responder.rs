@@ -0,0 +1,220 @@
+//! Responder answering queries for the services registered in this process
+//!
+//! Without a system daemon, the records of a service are only published for as long as an event
+//! loop is polled. Services therefore publish their records here, and every event loop (including
+//! those of browsers) answers the queries it receives on behalf of all services. This allows a
+//! service to be discovered while its own event loop is not being polled, e.g. from within its
+//! `ServiceRegisteredCallback`.
+
+use super::message::{self, Message, Question, ResourceRecord};
+use super::socket::{MdnsSocket, MDNS_PORT};
+use crate::Result;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// The maximum TTL of records in responses to legacy unicast queries
+const LEGACY_UNICAST_TTL: u32 = 10;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+static PUBLISHED: Mutex<Vec<Published>> = Mutex::new(Vec::new());
+
+/// The records of a registered service
+struct Published {
+    id: u64,
+    /// The records announced for the service
+    records: Vec<ResourceRecord>,
+    /// Records that are only sent in response to queries (e.g. for service type enumeration)
+    on_demand: Vec<ResourceRecord>,
+}
+
+/// Returns a new identifier to publish records under.
+pub fn next_id() -> u64 {
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Publishes the specified records under `id`, replacing the records previously published under
+/// the same `id`.
+pub fn publish(id: u64, records: Vec<ResourceRecord>, on_demand: Vec<ResourceRecord>) {
+    let mut published = PUBLISHED.lock().expect("could not lock published records");
+    published.retain(|p| p.id != id);
+    published.push(Published {
+        id,
+        records,
+        on_demand,
+    });
+}
+
+/// Removes the records published under `id`.
+pub fn unpublish(id: u64) {
+    PUBLISHED
+        .lock()
+        .expect("could not lock published records")
+        .retain(|p| p.id != id);
+}
+
+/// Answers the specified `query` received from `addr` with the published records, if any of them
+/// match.
+pub fn respond(socket: &MdnsSocket, query: &Message, addr: SocketAddr) -> Result<()> {
+    let (answers, additionals, unicast) = {
+        let published = PUBLISHED.lock().expect("could not lock published records");
+        answer(&published, query)
+    };
+
+    if answers.is_empty() {
+        return Ok(());
+    }
+
+    if addr.port() != MDNS_PORT {
+        // legacy unicast queries expect a conventional DNS response (RFC 6762, section 6.7)
+        let cap = |r: ResourceRecord| ResourceRecord {
+            ttl: r.ttl.min(LEGACY_UNICAST_TTL),
+            cache_flush: false,
+            ..r
+        };
+
+        let mut response = Message::response(
+            answers.into_iter().map(cap).collect(),
+            additionals.into_iter().map(cap).collect(),
+        );
+
+        response.id = query.id;
+        response.questions = query
+            .questions
+            .iter()
+            .map(|q| Question {
+                unicast_response: false,
+                ..q.clone()
+            })
+            .collect();
+
+        socket.send_to(&response, addr)
+    } else if unicast {
+        socket.send_to(&Message::response(answers, additionals), addr)
+    } else {
+        socket.send(&Message::response(answers, additionals))
+    }
+}
+
+/// Returns the answers and additional records for the specified `query`, and whether a unicast
+/// response was requested.
+fn answer(
+    published: &[Published],
+    query: &Message,
+) -> (Vec<ResourceRecord>, Vec<ResourceRecord>, bool) {
+    let mut answers: Vec<ResourceRecord> = vec![];
+    let mut additionals: Vec<ResourceRecord> = vec![];
+    let mut unicast = false;
+
+    for service in published {
+        let mut answered = false;
+
+        for question in &query.questions {
+            let matching = service
+                .records
+                .iter()
+                .chain(&service.on_demand)
+                .filter(|r| question.matches(&r.name, r.rrtype))
+                .filter(|r| !is_known_answer(query, r))
+                .filter(|r| !answers.contains(r))
+                .cloned()
+                .collect::<Vec<_>>();
+
+            if !matching.is_empty() {
+                unicast |= question.unicast_response;
+                answered |= matching.iter().any(|r| service.records.contains(r));
+                answers.extend(matching);
+            }
+        }
+
+        if answered {
+            // the remaining records of the service are likely to be queried next
+            additionals.extend(
+                service
+                    .records
+                    .iter()
+                    .filter(|r| !answers.contains(r) && !additionals.contains(r))
+                    .cloned()
+                    .collect::<Vec<_>>(),
+            );
+        }
+    }
+
+    (answers, additionals, unicast)
+}
+
+/// Returns true if the querier already knows the specified `record` with at least half of its TTL
+/// remaining (RFC 6762, section 7.1).
+fn is_known_answer(query: &Message, record: &ResourceRecord) -> bool {
+    query.answers.iter().any(|known| {
+        known.rrtype == record.rrtype
+            && known.ttl >= record.ttl / 2
+            && matches!((known.rdata(), record.rdata()), (Ok(k), Ok(r)) if k == r)
+            && message::name_eq(&known.name, &record.name)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns;
+    use crate::embedded::message::RecordData;
+
+    fn published() -> Vec<Published> {
+        vec![Published {
+            id: 0,
+            records: vec![
+                ResourceRecord::new(
+                    "_http._tcp.local",
+                    4500,
+                    RecordData::Ptr("a._http._tcp.local".to_string()),
+                ),
+                ResourceRecord::new("a._http._tcp.local", 4500, RecordData::Txt(vec![])),
+            ],
+            on_demand: vec![ResourceRecord::new(
+                "_services._dns-sd._udp.local",
+                4500,
+                RecordData::Ptr("_http._tcp.local".to_string()),
+            )],
+        }]
+    }
+
+    fn query(name: &str, qtype: u16) -> Message {
+        Message::query(vec![Question {
+            name: name.to_string(),
+            qtype,
+            unicast_response: false,
+        }])
+    }
+
+    #[test]
+    fn answer_includes_remaining_records_as_additionals() {
+        let (answers, additionals, _) =
+            answer(&published(), &query("_HTTP._tcp.local", dns::TYPE_PTR));
+
+        assert_eq!(answers.len(), 1);
+        assert_eq!(additionals.len(), 1);
+        assert_eq!(additionals[0].rrtype, dns::TYPE_TXT);
+    }
+
+    #[test]
+    fn answer_service_type_enumeration_without_additionals() {
+        let (answers, additionals, _) = answer(
+            &published(),
+            &query("_services._dns-sd._udp.local", dns::TYPE_PTR),
+        );
+
+        assert_eq!(answers.len(), 1);
+        assert!(additionals.is_empty());
+    }
+
+    #[test]
+    fn answer_suppresses_known_answers() {
+        let mut query = query("_http._tcp.local", dns::TYPE_PTR);
+        query.answers = vec![published()[0].records[0].clone()];
+
+        let (answers, _, _) = answer(&published(), &query);
+
+        assert!(answers.is_empty());
+    }
+}
@@ -0,0 +1,428 @@
+//! Embedded implementation for cross-platform service.
+
+use super::embedded_util;
+use super::message::{Message, RecordData, ResourceRecord};
+use super::registration_handle::EmbeddedRegistrationHandle;
+use super::responder;
+use super::socket::MdnsSocket;
+use crate::dns::Srv;
+use crate::error::{Error, Operation};
+use crate::network_watcher::NetworkWatcher;
+use crate::prelude::*;
+use crate::service;
+use crate::{
+    DaemonReconnectedCallback, ErrorCallback, EventLoop, NameConflictCallback, NetworkInterface,
+    RegistrationHandle, Result, ServiceFlags, ServiceRegisteredCallback, ServiceRegistration,
+    ServiceType, TxtRecord,
+};
+use std::any::Any;
+use std::fmt::{self, Formatter};
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The TTL of records referencing the host name (SRV, A and AAAA), as recommended by RFC 6762
+const HOST_RECORD_TTL: u32 = 120;
+/// The TTL of all other records
+const OTHER_RECORD_TTL: u32 = 4500;
+/// The name queried to enumerate the service types on the network
+const SERVICES_NAME: &str = "_services._dns-sd._udp.local";
+/// The number of times a service is announced after it has been registered
+const ANNOUNCEMENTS: u32 = 2;
+/// The interval between two announcements
+const ANNOUNCEMENT_INTERVAL: Duration = Duration::from_secs(1);
+
+pub struct EmbeddedMdnsService {
+    id: u64,
+    socket: Option<Arc<MdnsSocket>>,
+    service_type: ServiceType,
+    port: u16,
+    name: Option<String>,
+    host: Option<String>,
+    interface_index: u32,
+    txt_record: Option<TxtRecord>,
+    ttl: Option<u32>,
+    auto_reregister: bool,
+    network_watcher: Option<NetworkWatcher>,
+    /// The records that have been announced, withdrawn when the service is re-registered or
+    /// dropped
+    announced: Vec<ResourceRecord>,
+    announcements_left: u32,
+    next_announcement: Instant,
+    registered_callback: Option<Box<ServiceRegisteredCallback>>,
+    error_callback: Option<Box<ErrorCallback>>,
+    registration_reported: bool,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl TMdnsService for EmbeddedMdnsService {
+    fn new(service_type: ServiceType, port: u16) -> Self {
+        Self {
+            id: responder::next_id(),
+            socket: None,
+            service_type,
+            port,
+            name: None,
+            host: None,
+            interface_index: embedded_util::interface_index(NetworkInterface::Unspec),
+            txt_record: None,
+            ttl: None,
+            auto_reregister: false,
+            network_watcher: None,
+            announced: vec![],
+            announcements_left: 0,
+            next_announcement: Instant::now(),
+            registered_callback: None,
+            error_callback: None,
+            registration_reported: false,
+            user_context: None,
+        }
+    }
+
+    /// Sets the name to register this service under. If no name is set, the host name is used.
+    fn set_name(&mut self, name: &str) {
+        self.name = Some(name.to_string());
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface_index = embedded_util::interface_index(interface);
+    }
+
+    /// mDNS only supports the `local` domain, other domains are ignored.
+    fn set_domain(&mut self, domain: &str) {
+        if domain.trim_end_matches('.') != embedded_util::LOCAL_DOMAIN {
+            warn!("ignoring unsupported domain: {}", domain);
+        }
+    }
+
+    /// Sets the SRV target host name. The addresses of this computer are published for the host
+    /// name, so it should not be the name of another host.
+    fn set_host(&mut self, host: &str) {
+        self.host = Some(host.to_string());
+    }
+
+    fn set_txt_record(&mut self, txt_record: TxtRecord) {
+        self.txt_record = Some(txt_record);
+    }
+
+    fn set_registered_callback(&mut self, registered_callback: Box<ServiceRegisteredCallback>) {
+        self.registered_callback = Some(registered_callback);
+    }
+
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>) {
+        self.error_callback = Some(error_callback);
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        self.user_context = Some(Arc::from(context));
+    }
+
+    /// The embedded implementation does not support any flags, they are ignored.
+    fn set_flags(&mut self, _flags: ServiceFlags) {}
+
+    /// Unlike Bonjour, the TTL applies to all records published for the service.
+    fn set_ttl(&mut self, ttl: u32) {
+        self.ttl = Some(ttl);
+    }
+
+    fn set_auto_reregister(&mut self, auto_reregister: bool) {
+        self.auto_reregister = auto_reregister;
+    }
+
+    /// The embedded implementation does not depend on a daemon, so the callback is never invoked.
+    fn set_reconnected_callback(&mut self, _reconnected_callback: Box<DaemonReconnectedCallback>) {}
+
+    /// The embedded implementation does not probe for name conflicts, so the callback is never
+    /// invoked.
+    fn set_name_conflict_callback(&mut self, _name_conflict_callback: Box<NameConflictCallback>) {}
+
+    fn register(&mut self) -> Result<RegistrationHandle<'_>> {
+        let _span = operation_span!("register", service_type = self.service_type.to_string());
+
+        debug!("Registering service: {:?}", self);
+
+        service::check_port(self.port)?;
+
+        let socket = match &self.socket {
+            Some(s) => s.clone(),
+            None => Arc::new(MdnsSocket::new()?),
+        };
+
+        self.socket = Some(socket.clone());
+        self.register_service()?;
+
+        if self.auto_reregister {
+            self.network_watcher = Some(NetworkWatcher::new());
+        }
+
+        let service: *mut Self = self;
+
+        let event_loop = EventLoop::new(socket, Operation::Register)
+            .with_on_poll(Box::new(move || unsafe { (*service).handle_poll() }));
+
+        Ok(EmbeddedRegistrationHandle::new(service, event_loop))
+    }
+
+    fn close(mut self) -> Result<()> {
+        let result = self.withdraw();
+        self.socket = None;
+        result
+    }
+}
+
+impl EmbeddedMdnsService {
+    fn kind(&self) -> String {
+        format!(
+            "_{}._{}",
+            self.service_type.name(),
+            self.service_type.protocol()
+        )
+    }
+
+    fn name(&self) -> String {
+        self.name.clone().unwrap_or_else(embedded_util::host_name)
+    }
+
+    fn host(&self) -> String {
+        self.host
+            .clone()
+            .unwrap_or_else(embedded_util::local_host_name)
+    }
+
+    fn register_service(&mut self) -> Result<()> {
+        // withdraw the records of the previous registration, they may have changed
+        self.withdraw()?;
+
+        self.announcements_left = ANNOUNCEMENTS;
+        self.next_announcement = Instant::now();
+        self.registration_reported = false;
+
+        Ok(())
+    }
+
+    /// Returns the records published for the service.
+    fn records(&self) -> Vec<ResourceRecord> {
+        let kind = self.kind();
+        let instance_name = embedded_util::instance_name(&self.name(), &kind);
+        let host = self.host();
+        let host_ttl = self.ttl.unwrap_or(HOST_RECORD_TTL);
+        let other_ttl = self.ttl.unwrap_or(OTHER_RECORD_TTL);
+
+        let mut records = vec![ResourceRecord::new(
+            &embedded_util::kind_name(&kind),
+            other_ttl,
+            RecordData::Ptr(instance_name.clone()),
+        )];
+
+        for sub_type in self.service_type.sub_types() {
+            records.push(ResourceRecord::new(
+                &format!("_{}._sub.{}", sub_type, embedded_util::kind_name(&kind)),
+                other_ttl,
+                RecordData::Ptr(instance_name.clone()),
+            ));
+        }
+
+        records.push(ResourceRecord::new(
+            &instance_name,
+            host_ttl,
+            RecordData::Srv(Srv {
+                priority: 0,
+                weight: 0,
+                port: self.port,
+                target: host.clone(),
+            }),
+        ));
+
+        let txt = self
+            .txt_record
+            .iter()
+            .flat_map(|t| t.iter())
+            .collect::<Vec<_>>();
+
+        records.push(ResourceRecord::new(
+            &instance_name,
+            other_ttl,
+            RecordData::Txt(txt),
+        ));
+
+        for ip in embedded_util::local_addresses(self.interface_index) {
+            let data = match ip {
+                IpAddr::V4(v4) => RecordData::A(v4),
+                IpAddr::V6(v6) => RecordData::Aaaa(v6),
+            };
+
+            records.push(ResourceRecord::new(&host, host_ttl, data));
+        }
+
+        records
+    }
+
+    fn socket(&self) -> Result<&MdnsSocket> {
+        self.socket
+            .as_deref()
+            .ok_or_else(|| "service has not been registered".into())
+    }
+
+    /// Announces the service (unsolicited response) and publishes its records to the responder.
+    fn announce(&mut self) -> Result<()> {
+        let records = self.records();
+
+        self.socket()?
+            .send(&Message::response(records.clone(), vec![]))
+            .map_err(|e| e.with_operation(Operation::Register))?;
+
+        let services_ptr = ResourceRecord::new(
+            SERVICES_NAME,
+            self.ttl.unwrap_or(OTHER_RECORD_TTL),
+            RecordData::Ptr(embedded_util::kind_name(&self.kind())),
+        );
+
+        responder::publish(self.id, records.clone(), vec![services_ptr]);
+        self.announced = records;
+
+        Ok(())
+    }
+
+    /// Sends a goodbye (records with a TTL of 0) for the records that have been announced.
+    fn withdraw(&mut self) -> Result<()> {
+        responder::unpublish(self.id);
+
+        if self.announced.is_empty() {
+            return Ok(());
+        }
+
+        let goodbye = self
+            .announced
+            .drain(..)
+            .map(|r| ResourceRecord { ttl: 0, ..r })
+            .collect();
+
+        self.socket()?
+            .send(&Message::response(goodbye, vec![]))
+            .map_err(|e| e.with_operation(Operation::Register))
+    }
+
+    fn handle_poll(&mut self) {
+        if let Err(e) = self.handle_announcements() {
+            self.invoke_callback(Err(e));
+        }
+
+        let changed = match &mut self.network_watcher {
+            Some(w) => w.poll_changed(),
+            None => false,
+        };
+
+        if changed {
+            debug!("Network configuration changed, re-registering service");
+
+            if let Err(e) = self.reregister() {
+                self.invoke_callback(Err(e));
+            }
+        }
+    }
+
+    fn handle_announcements(&mut self) -> Result<()> {
+        if self.announcements_left == 0 || Instant::now() < self.next_announcement {
+            return Ok(());
+        }
+
+        self.announce()?;
+        self.announcements_left -= 1;
+        self.next_announcement = Instant::now() + ANNOUNCEMENT_INTERVAL;
+
+        if !self.registration_reported {
+            self.registration_reported = true;
+
+            let result = ServiceRegistration::builder()
+                .name(self.name())
+                .service_type(self.service_type.clone())
+                .domain(embedded_util::LOCAL_DOMAIN.to_string())
+                .build()
+                .map_err(Error::from);
+
+            self.invoke_callback(result);
+        }
+
+        Ok(())
+    }
+
+    fn invoke_callback(&self, result: Result<ServiceRegistration>) {
+        match (result, &self.error_callback, &self.registered_callback) {
+            (Err(e), Some(f), _) => f(e, self.user_context.clone()),
+            (result, _, Some(f)) => f(result, self.user_context.clone()),
+            _ => warn!("attempted to invoke callback but none was set"),
+        }
+    }
+
+    pub(crate) fn set_port(&mut self, port: u16) {
+        self.port = port;
+    }
+
+    /// Re-registers the service with its current configuration.
+    pub(crate) fn reregister(&mut self) -> Result<()> {
+        self.register_service()
+    }
+}
+
+impl fmt::Debug for EmbeddedMdnsService {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EmbeddedMdnsService")
+            .field("service_type", &self.service_type)
+            .field("port", &self.port)
+            .field("name", &self.name)
+            .field("host", &self.host)
+            .field("interface_index", &self.interface_index)
+            .field("ttl", &self.ttl)
+            .finish()
+    }
+}
+
+impl Drop for EmbeddedMdnsService {
+    fn drop(&mut self) {
+        if let Err(e) = self.withdraw() {
+            warn!("could not withdraw service: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns;
+
+    #[test]
+    fn records_include_service_and_host_records() {
+        let service_type = ServiceType::with_sub_types("http", "tcp", vec!["printer"]).unwrap();
+        let mut service = EmbeddedMdnsService::new(service_type, 8080);
+        service.set_name("My Service");
+        service.set_host("host.local");
+
+        let records = service.records();
+        let types = records.iter().map(|r| r.rrtype).collect::<Vec<_>>();
+
+        assert_eq!(
+            &types[..4],
+            &[dns::TYPE_PTR, dns::TYPE_PTR, dns::TYPE_SRV, dns::TYPE_TXT]
+        );
+
+        assert_eq!(records[1].name, "_printer._sub._http._tcp.local");
+        assert_eq!(
+            records[2].data,
+            RecordData::Srv(Srv {
+                priority: 0,
+                weight: 0,
+                port: 8080,
+                target: "host.local".to_string()
+            })
+        );
+
+        assert!(records[4..].iter().all(|r| r.name == "host.local"));
+    }
+
+    #[test]
+    fn set_ttl_applies_to_all_records() {
+        let mut service = EmbeddedMdnsService::new(ServiceType::new("http", "tcp").unwrap(), 80);
+        service.set_ttl(30);
+        assert!(service.records().iter().all(|r| r.ttl == 30));
+    }
+}
@@ -0,0 +1,196 @@
+//! Multicast UDP socket shared by the querier and the responder
+
+use super::message::Message;
+use crate::error::{Error, ErrorKind, Operation};
+use crate::Result;
+use std::io;
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::os::unix::io::FromRawFd;
+use std::time::Duration;
+
+/// The IPv4 mDNS multicast group
+pub const MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+/// The mDNS port
+pub const MDNS_PORT: u16 = 5353;
+/// Maximum size of a received message
+const MAX_MESSAGE_SIZE: usize = 9000;
+
+/// A UDP socket bound to the mDNS port that has joined the mDNS multicast group on every IPv4
+/// interface.
+#[derive(Debug)]
+pub struct MdnsSocket(UdpSocket);
+
+impl MdnsSocket {
+    /// Creates a new `MdnsSocket`. The port is shared with other mDNS implementations running on
+    /// the host (`SO_REUSEADDR` and `SO_REUSEPORT`).
+    ///
+    /// Errors are reported for `Operation::Connect`.
+    pub fn new() -> Result<Self> {
+        Self::open().map_err(|e| e.with_operation(Operation::Connect))
+    }
+
+    fn open() -> Result<Self> {
+        let socket = unsafe { bind_reusable(MDNS_PORT)? };
+
+        socket
+            .set_multicast_loop_v4(true)
+            .map_err(|e| io_error("could not enable multicast loop", e))?;
+
+        socket
+            .set_multicast_ttl_v4(255)
+            .map_err(|e| io_error("could not set multicast TTL", e))?;
+
+        let mut joined = false;
+
+        for (_, ip) in crate::network_watcher::snapshot() {
+            if let IpAddr::V4(ip) = ip {
+                if ip.is_loopback() {
+                    continue;
+                }
+
+                match socket.join_multicast_v4(&MDNS_GROUP, &ip) {
+                    Ok(()) => joined = true,
+                    Err(e) => warn!("could not join mDNS group on {}: {}", ip, e),
+                }
+            }
+        }
+
+        if !joined {
+            // fall back to the default interface of the system
+            socket
+                .join_multicast_v4(&MDNS_GROUP, &Ipv4Addr::UNSPECIFIED)
+                .map_err(|e| io_error("could not join mDNS group", e))?;
+        }
+
+        Ok(Self(socket))
+    }
+
+    /// Sends the specified `message` to the mDNS multicast group.
+    pub fn send(&self, message: &Message) -> Result<()> {
+        self.send_to(message, SocketAddrV4::new(MDNS_GROUP, MDNS_PORT).into())
+    }
+
+    /// Sends the specified `message` to `addr`.
+    pub fn send_to(&self, message: &Message, addr: SocketAddr) -> Result<()> {
+        self.0
+            .send_to(&message.encode()?, addr)
+            .map_err(|e| io_error("could not send mDNS message", e))?;
+
+        Ok(())
+    }
+
+    /// Waits up to the specified `timeout` for a message to be received. A zero `timeout` returns
+    /// immediately.
+    ///
+    /// Returns `None` if no message was received in time. Messages that cannot be decoded are
+    /// skipped.
+    pub fn recv(&self, timeout: Duration) -> Result<Option<(Message, SocketAddr)>> {
+        let nonblocking = timeout == Duration::from_secs(0);
+
+        self.0
+            .set_nonblocking(nonblocking)
+            .and_then(|_| {
+                if nonblocking {
+                    Ok(())
+                } else {
+                    self.0.set_read_timeout(Some(timeout))
+                }
+            })
+            .map_err(|e| io_error("could not set socket timeout", e))?;
+
+        let mut buf = [0u8; MAX_MESSAGE_SIZE];
+
+        match self.0.recv_from(&mut buf) {
+            Ok((len, addr)) => match Message::decode(&buf[..len]) {
+                Some(message) => Ok(Some((message, addr))),
+                None => {
+                    debug!("ignoring invalid mDNS message from {}", addr);
+                    Ok(None)
+                }
+            },
+            Err(e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                Ok(None)
+            }
+            Err(e) => Err(io_error("could not receive mDNS message", e)),
+        }
+    }
+}
+
+/// Returns an [`Error`] with the specified `description` of the I/O error `e`, with the OS error
+/// code of `e` if any. Errors of kind `io::ErrorKind::PermissionDenied` (e.g. a socket option or
+/// port that requires privileges) are of kind `ErrorKind::PermissionDenied`.
+///
+/// [`Error`]: ../../error/struct.Error.html
+fn io_error(description: &str, e: io::Error) -> Error {
+    let kind = match e.kind() {
+        io::ErrorKind::PermissionDenied => ErrorKind::PermissionDenied,
+        _ => ErrorKind::Other,
+    };
+
+    let error = Error::with_kind(kind, format!("{}: {}", description, e));
+
+    match e.raw_os_error() {
+        Some(code) => error.with_code(code),
+        None => error,
+    }
+}
+
+/// Creates a UDP socket bound to `port` on all IPv4 interfaces that allows other sockets to bind
+/// to the same port.
+///
+/// # Safety
+/// This function is unsafe because of the calls to libc.
+unsafe fn bind_reusable(port: u16) -> Result<UdpSocket> {
+    let fd = libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0);
+
+    if fd < 0 {
+        return Err(io_error(
+            "could not create socket",
+            io::Error::last_os_error(),
+        ));
+    }
+
+    // take ownership of the descriptor so that it is closed on error
+    let socket = UdpSocket::from_raw_fd(fd);
+    let enable: libc::c_int = 1;
+
+    for option in &[libc::SO_REUSEADDR, libc::SO_REUSEPORT] {
+        let err = libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            *option,
+            &enable as *const libc::c_int as *const libc::c_void,
+            mem::size_of::<libc::c_int>() as libc::socklen_t,
+        );
+
+        if err != 0 {
+            return Err(io_error(
+                "could not set socket option",
+                io::Error::last_os_error(),
+            ));
+        }
+    }
+
+    let mut addr: libc::sockaddr_in = mem::zeroed();
+    addr.sin_family = libc::AF_INET as libc::sa_family_t;
+    addr.sin_port = port.to_be();
+    addr.sin_addr.s_addr = libc::INADDR_ANY;
+
+    let err = libc::bind(
+        fd,
+        &addr as *const libc::sockaddr_in as *const libc::sockaddr,
+        mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+    );
+
+    if err != 0 {
+        return Err(io_error(
+            &format!("could not bind to port {}", port),
+            io::Error::last_os_error(),
+        ));
+    }
+
+    Ok(socket)
+}
@@ -1,11 +1,163 @@
 //! Utilities regarding error handling
 
+use std::any::Any;
 use std::fmt;
+use std::sync::Arc;
+
+/// Callback invoked from [`MdnsService`] or [`MdnsBrowser`] with the errors that would otherwise
+/// be passed to their registered or discovered callback (e.g. the daemon died or a service could
+/// not be resolved), if it has been set.
+///
+/// # Arguments
+/// * `error` - The error that occurred
+/// * `context` - The optional user context passed through
+///
+/// [`MdnsService`]: ../type.MdnsService.html
+/// [`MdnsBrowser`]: ../type.MdnsBrowser.html
+pub type ErrorCallback = dyn Fn(Error, Option<Arc<dyn Any + Send + Sync>>) + Send;
 
 /// For when something goes wrong when interfacing with mDNS implementations
 #[derive(new, Debug, Clone, PartialEq, Eq)]
 pub struct Error {
     description: String,
+    #[new(value = "ErrorKind::Other")]
+    kind: ErrorKind,
+    #[new(default)]
+    code: Option<i32>,
+    #[new(default)]
+    operation: Option<Operation>,
+}
+
+impl Error {
+    /// Creates a new `Error` of the specified [`ErrorKind`].
+    ///
+    /// [`ErrorKind`]: enum.ErrorKind.html
+    pub fn with_kind(kind: ErrorKind, description: String) -> Self {
+        Self {
+            kind,
+            ..Self::new(description)
+        }
+    }
+
+    /// Sets the error code reported by the mDNS implementation.
+    pub fn with_code(mut self, code: i32) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// Sets the [`Operation`] that failed.
+    ///
+    /// [`Operation`]: enum.Operation.html
+    pub fn with_operation(mut self, operation: Operation) -> Self {
+        self.operation = Some(operation);
+        self
+    }
+
+    /// Returns the [`ErrorKind`] of this error.
+    ///
+    /// [`ErrorKind`]: enum.ErrorKind.html
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// Returns the error code reported by the mDNS implementation, if any (e.g. a
+    /// `DNSServiceErrorType` of Bonjour or an `AVAHI_ERR_*` of Avahi).
+    pub fn code(&self) -> Option<i32> {
+        self.code
+    }
+
+    /// Returns the [`Operation`] that failed, if known.
+    ///
+    /// [`Operation`]: enum.Operation.html
+    pub fn operation(&self) -> Option<Operation> {
+        self.operation
+    }
+
+    /// Returns `true` if the operation that failed may succeed when retried later, see
+    /// [`ErrorKind::is_recoverable()`].
+    ///
+    /// [`ErrorKind::is_recoverable()`]: enum.ErrorKind.html#method.is_recoverable
+    pub fn is_recoverable(&self) -> bool {
+        self.kind.is_recoverable()
+    }
+}
+
+/// Categories of [`Error`] that callers may want to handle specifically.
+///
+/// More kinds may be added in future releases, so matches on an `ErrorKind` need a wildcard arm.
+///
+/// [`Error`]: struct.Error.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The service name conflicts with another service on the network and no other name could
+    /// be registered
+    NameConflict,
+    /// The application is not permitted to use the local network, e.g. the local network
+    /// permission of an iOS app was denied or its `NSBonjourServices` do not include the service
+    /// type
+    PermissionDenied,
+    /// The mDNS daemon is not running or cannot be reached, e.g. its socket is not mounted into
+    /// the container the application runs in. The description of the error tells how to fix it.
+    DaemonNotRunning,
+    /// The mDNS implementation did not respond in time
+    Timeout,
+    /// An argument is invalid, e.g. a malformed service type or an interface that does not exist
+    InvalidArgument,
+    /// The queried record does not exist, reported for the negative answers requested with
+    /// `ServiceFlags::RETURN_INTERMEDIATES`
+    NoSuchRecord,
+    /// Any other error
+    Other,
+}
+
+impl ErrorKind {
+    /// Returns `true` if errors of this kind are transient, i.e. the operation that failed may
+    /// succeed when retried later without changes (e.g. once the daemon is running again).
+    ///
+    /// The other errors are fatal: the operation fails again until the application changes it,
+    /// e.g. registers the service under another name or fixes its service type. Errors of kind
+    /// `Other` are not known to be transient and are considered fatal.
+    pub fn is_recoverable(self) -> bool {
+        matches!(self, ErrorKind::DaemonNotRunning | ErrorKind::Timeout)
+    }
+}
+
+/// The operations of the mDNS implementation an [`Error`] may originate from.
+///
+/// [`Error`]: struct.Error.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Connecting to the mDNS daemon
+    Connect,
+    /// Registering a service
+    Register,
+    /// Browsing for services
+    Browse,
+    /// Resolving a service instance
+    Resolve,
+    /// Resolving the addresses of a host name
+    GetAddrInfo,
+    /// Querying resource records
+    QueryRecord,
+    /// Mapping a port on the network gateway
+    MapPort,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Operation::Connect => "connect",
+            Operation::Register => "register",
+            Operation::Browse => "browse",
+            Operation::Resolve => "resolve",
+            Operation::GetAddrInfo => "getaddrinfo",
+            Operation::QueryRecord => "query record",
+            Operation::MapPort => "map port",
+        };
+
+        f.write_str(name)
+    }
 }
 
 impl std::error::Error for Error {}
@@ -1,11 +1,108 @@
 //! Trait definition for cross-platform event loop
 
 use crate::Result;
+use std::sync::RwLock;
 use std::time::Duration;
 
+static POLL_INTERVALS: RwLock<Option<PollIntervals>> = RwLock::new(None);
+
+const DEFAULT_SIMPLE_POLL_SLEEP: Duration = Duration::from_secs(0);
+const DEFAULT_SCOPED_POLL_TIMEOUT: Duration = Duration::from_millis(100);
+const DEFAULT_QUERY_INTERVAL: Duration = Duration::from_secs(2);
+const DEFAULT_BACKGROUND_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
 /// A handle on the underlying implementation to poll the event loop. Typically, `poll()`
 /// is called in a loop to keep a `MdnsService` or `MdnsBrowser` running.
 pub trait TEventLoop {
     /// Polls for new events.
     fn poll(&self, timeout: Duration) -> Result<()>;
 }
+
+/// How long the event loops wait internally, set with [`set_poll_intervals()`]. Longer intervals
+/// wake the CPU up less often, at the cost of reporting events later.
+///
+/// [`set_poll_intervals()`]: fn.set_poll_intervals.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Getters, Builder, BuilderDelegate)]
+pub struct PollIntervals {
+    /// How long each poll of an Avahi event loop waits for events in
+    /// `avahi_simple_poll_iterate()`, at most the timeout passed to `poll()`. Defaults to zero,
+    /// i.e. polling returns right away and the caller paces its loop.
+    #[builder(default = "DEFAULT_SIMPLE_POLL_SLEEP")]
+    simple_poll_sleep: Duration,
+    /// The timeout of each poll of the event loop of `browse_scoped()`, bounding how long
+    /// discovered services wait before being passed to its callback. Defaults to 100ms.
+    #[builder(default = "DEFAULT_SCOPED_POLL_TIMEOUT")]
+    scoped_poll_timeout: Duration,
+    /// The minimum interval between two queries of the services announced on the network with
+    /// systemd-resolved, which does not report them on its own. It is read when a browser or
+    /// service is started. Defaults to 2s.
+    #[builder(default = "DEFAULT_QUERY_INTERVAL")]
+    query_interval: Duration,
+    /// How long the background event loop (see the [`background`] module) waits between two
+    /// polls of the browsers and services it drives. Defaults to 50ms.
+    ///
+    /// [`background`]: ../background/index.html
+    #[builder(default = "DEFAULT_BACKGROUND_POLL_INTERVAL")]
+    background_poll_interval: Duration,
+}
+
+impl Default for PollIntervals {
+    fn default() -> Self {
+        Self {
+            simple_poll_sleep: DEFAULT_SIMPLE_POLL_SLEEP,
+            scoped_poll_timeout: DEFAULT_SCOPED_POLL_TIMEOUT,
+            query_interval: DEFAULT_QUERY_INTERVAL,
+            background_poll_interval: DEFAULT_BACKGROUND_POLL_INTERVAL,
+        }
+    }
+}
+
+/// Sets how long the event loops wait internally from now on, replacing the intervals previously
+/// set, if any.
+///
+/// # Examples
+/// ```
+/// use std::time::Duration;
+/// use zeroconf::PollIntervals;
+/// use zeroconf::prelude::*;
+///
+/// // wake up at most every 50ms instead of returning right away from each poll
+/// zeroconf::set_poll_intervals(
+///     PollIntervals::builder()
+///         .simple_poll_sleep(Duration::from_millis(50))
+///         .build()
+///         .unwrap(),
+/// );
+///
+/// assert_eq!(
+///     *zeroconf::poll_intervals().simple_poll_sleep(),
+///     Duration::from_millis(50)
+/// );
+/// ```
+pub fn set_poll_intervals(intervals: PollIntervals) {
+    *POLL_INTERVALS.write().unwrap_or_else(|e| e.into_inner()) = Some(intervals);
+}
+
+/// Returns the intervals set with [`set_poll_intervals()`], or the default ones.
+///
+/// [`set_poll_intervals()`]: fn.set_poll_intervals.html
+pub fn poll_intervals() -> PollIntervals {
+    POLL_INTERVALS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prelude::*;
+
+    #[test]
+    fn builder_defaults_to_default_intervals() {
+        assert_eq!(
+            PollIntervals::builder().build().unwrap(),
+            PollIntervals::default()
+        );
+    }
+}
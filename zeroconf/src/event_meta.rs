@@ -0,0 +1,19 @@
+/// Details of an event as observed from the mDNS implementation, which are specific to the
+/// platform and therefore not part of the result itself.
+///
+/// Details the mDNS implementation does not report are left at their default.
+#[derive(
+    Debug, Default, Getters, Builder, BuilderDelegate, Serialize, Deserialize, Clone, PartialEq, Eq,
+)]
+pub struct EventMeta {
+    /// The raw flags reported along with the event, e.g. the `DNSServiceFlags` of Bonjour or the
+    /// `AvahiLookupResultFlags` of Avahi
+    #[builder(default)]
+    flags: u32,
+    /// The index of the interface the event was observed on, `0` if it is unknown
+    #[builder(default)]
+    interface_index: u32,
+    /// The TTL of the records of the event in seconds, if it is reported
+    #[builder(default)]
+    ttl: Option<u32>,
+}
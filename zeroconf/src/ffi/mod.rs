@@ -1,10 +1,177 @@
 //! Utilities related to FFI bindings
 
-use libc::c_void;
+use libc::{c_void, sockaddr};
+use std::any::Any;
+use std::cell::UnsafeCell;
+use std::ffi::CString;
+use std::fmt::{self, Formatter};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::ptr;
+use std::sync::Arc;
 
 pub(crate) mod c_str;
 
+/// Converts the specified `*const sockaddr` to an `IpAddr`. Returns `None` if the address is not
+/// of the `AF_INET` or `AF_INET6` family.
+///
+/// # Safety
+/// This function is unsafe because of the raw pointer dereference. `addr` is expected to point to
+/// a `sockaddr_in` or `sockaddr_in6` as indicated by it's family.
+#[cfg(unix)]
+pub(crate) unsafe fn sockaddr_to_ip(addr: *const sockaddr) -> Option<IpAddr> {
+    use libc::{sockaddr_in, sockaddr_in6};
+
+    assert_not_null!(addr);
+
+    match (*addr).sa_family as i32 {
+        libc::AF_INET => {
+            let addr = &*(addr as *const sockaddr_in);
+            Some(Ipv4Addr::from(u32::from_be(addr.sin_addr.s_addr)).into())
+        }
+        libc::AF_INET6 => {
+            let addr = &*(addr as *const sockaddr_in6);
+            Some(Ipv6Addr::from(addr.sin6_addr.s6_addr).into())
+        }
+        _ => None,
+    }
+}
+
+/// Converts the specified `*const sockaddr` to an `IpAddr`. Returns `None` if the address is not
+/// of the `AF_INET` or `AF_INET6` family.
+///
+/// # Safety
+/// This function is unsafe because of the raw pointer dereference. `addr` is expected to point to
+/// a `SOCKADDR_IN` or `SOCKADDR_IN6` as indicated by it's family.
+#[cfg(target_os = "windows")]
+pub(crate) unsafe fn sockaddr_to_ip(addr: *const sockaddr) -> Option<IpAddr> {
+    use windows_sys::Win32::Networking::WinSock::{AF_INET, AF_INET6, SOCKADDR_IN, SOCKADDR_IN6};
+
+    assert_not_null!(addr);
+
+    match (*addr).sa_family {
+        family if family == AF_INET => {
+            let addr = &*(addr as *const SOCKADDR_IN);
+            Some(Ipv4Addr::from(u32::from_be(addr.sin_addr.S_un.S_addr)).into())
+        }
+        family if family == AF_INET6 => {
+            let addr = &*(addr as *const SOCKADDR_IN6);
+            Some(Ipv6Addr::from(addr.sin6_addr.u.Byte).into())
+        }
+        _ => None,
+    }
+}
+
+/// Returns the scope ID of the specified `*const sockaddr` if it is of the `AF_INET6` family, or
+/// `0` otherwise.
+///
+/// # Safety
+/// This function is unsafe because of the raw pointer dereference. `addr` is expected to point to
+/// a `sockaddr_in6` if it's family is `AF_INET6`.
+#[cfg(backend_bonjour)]
+pub(crate) unsafe fn sockaddr_scope_id(addr: *const sockaddr) -> u32 {
+    assert_not_null!(addr);
+
+    match (*addr).sa_family as i32 {
+        libc::AF_INET6 => (*(addr as *const libc::sockaddr_in6)).sin6_scope_id,
+        _ => 0,
+    }
+}
+
+/// Returns the scope ID of the specified `*const sockaddr` if it is of the `AF_INET6` family, or
+/// `0` otherwise.
+///
+/// # Safety
+/// This function is unsafe because of the raw pointer dereference. `addr` is expected to point to
+/// a `SOCKADDR_IN6` if it's family is `AF_INET6`.
+#[cfg(backend_bonjour_sdk)]
+pub(crate) unsafe fn sockaddr_scope_id(addr: *const sockaddr) -> u32 {
+    use windows_sys::Win32::Networking::WinSock::{AF_INET6, SOCKADDR_IN6};
+
+    assert_not_null!(addr);
+
+    match (*addr).sa_family {
+        family if family == AF_INET6 => (*(addr as *const SOCKADDR_IN6)).Anonymous.sin6_scope_id,
+        _ => 0,
+    }
+}
+
+/// Returns the specified scope ID if `ip` is a link-local IPv6 address (which is only usable on
+/// the interface it was discovered on), or `0` otherwise.
+pub(crate) fn link_local_scope_id(ip: &IpAddr, scope_id: u32) -> u32 {
+    match ip {
+        IpAddr::V6(v6) if (v6.segments()[0] & 0xffc0) == 0xfe80 => scope_id,
+        _ => 0,
+    }
+}
+
+/// Formats the specified `ip` with it's zone (e.g. `fe80::1%en0`) if `scope_id` is not `0`. The
+/// zone is the name of the interface at `scope_id`, or the index itself if there is no such
+/// interface.
+pub(crate) fn format_scoped_ip(ip: &IpAddr, scope_id: u32) -> String {
+    if scope_id == 0 {
+        return ip.to_string();
+    }
+
+    let zone = interface_name(scope_id).unwrap_or_else(|| scope_id.to_string());
+
+    format!("{}%{}", ip, zone)
+}
+
+/// Returns the name of the interface at the specified `index`
+#[cfg(unix)]
+pub(crate) fn interface_name(index: u32) -> Option<String> {
+    let mut name = [0 as libc::c_char; libc::IF_NAMESIZE];
+
+    unsafe {
+        if libc::if_indextoname(index, name.as_mut_ptr()).is_null() {
+            None
+        } else {
+            Some(c_str::copy_raw(name.as_ptr()))
+        }
+    }
+}
+
+/// Returns the name of the interface at the specified `index`
+#[cfg(target_os = "windows")]
+pub(crate) fn interface_name(index: u32) -> Option<String> {
+    use windows_sys::Win32::NetworkManagement::IpHelper::if_indextoname;
+    use windows_sys::Win32::NetworkManagement::Ndis::IF_MAX_STRING_SIZE;
+
+    let mut name = [0u8; IF_MAX_STRING_SIZE as usize + 1];
+
+    unsafe {
+        if if_indextoname(index, name.as_mut_ptr()).is_null() {
+            None
+        } else {
+            Some(c_str::copy_raw(name.as_ptr() as *const libc::c_char))
+        }
+    }
+}
+
+/// Returns the index of the interface with the specified `name`
+#[cfg(unix)]
+pub(crate) fn interface_index(name: &str) -> Option<u32> {
+    let name = CString::new(name).ok()?;
+
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => None,
+        index => Some(index),
+    }
+}
+
+/// Returns the index of the interface with the specified `name`
+#[cfg(target_os = "windows")]
+pub(crate) fn interface_index(name: &str) -> Option<u32> {
+    use windows_sys::Win32::NetworkManagement::IpHelper::if_nametoindex;
+
+    let name = CString::new(name).ok()?;
+
+    match unsafe { if_nametoindex(name.as_ptr() as *const u8) } {
+        0 => None,
+        index => Some(index),
+    }
+}
+
 /// Helper trait to convert a raw `*mut c_void` to it's rust type
 pub trait FromRaw<T> {
     /// Converts the specified `*mut c_void` to a `&'a mut T`.
@@ -32,6 +199,8 @@ pub trait CloneRaw<T: FromRaw<T> + Clone> {
 }
 
 /// Helper trait to convert self to a raw `*mut c_void`
+// only the Avahi backends pass their contexts with `as_raw()`
+#[cfg_attr(not(any(backend_avahi, backend_avahi_core)), allow(dead_code))]
 pub trait AsRaw {
     /// Converts self to a raw `*mut c_void` by cast.
     fn as_raw(&mut self) -> *mut c_void {
@@ -39,6 +208,102 @@ pub trait AsRaw {
     }
 }
 
+/// The context of a browser, service or resolver whose pointer is passed to the mDNS
+/// implementation as the user data of its callbacks.
+///
+/// The context is reference counted rather than owned by a `Box`, so that it is not deallocated
+/// while a reference the implementation may still invoke callbacks for holds on to it, see
+/// [`retain()`] and [`into_raw()`]. It is deallocated once the owner and all of these references
+/// have been dropped.
+///
+/// [`retain()`]: #method.retain
+/// [`into_raw()`]: #method.into_raw
+pub(crate) struct SharedContext<T>(Arc<UnsafeCell<T>>);
+
+impl<T> SharedContext<T> {
+    /// Creates a new `SharedContext` holding the specified `context`.
+    pub fn new(context: T) -> Self {
+        Self(Arc::new(UnsafeCell::new(context)))
+    }
+
+    /// Returns a pointer to the context, which is valid for as long as this `SharedContext` (or a
+    /// reference retained from it) is alive.
+    pub fn as_ptr(&self) -> *mut T {
+        self.0.get()
+    }
+}
+
+// only the DNS-SD callbacks of Windows are handed references they release themselves
+#[cfg_attr(not(backend_win32), allow(dead_code))]
+impl<T> SharedContext<T> {
+    /// Consumes this `SharedContext`, returning a pointer to the context that keeps it alive
+    /// until it is converted back with [`from_raw()`], e.g. once the mDNS implementation has
+    /// invoked its last callback with it.
+    ///
+    /// [`from_raw()`]: #method.from_raw
+    pub fn into_raw(self) -> *mut T {
+        Arc::into_raw(self.0) as *mut T
+    }
+
+    /// Converts a pointer returned by [`into_raw()`] back to the `SharedContext` it kept alive.
+    ///
+    /// # Safety
+    /// This function is unsafe because `raw` must have been returned by `into_raw()` and must
+    /// not be converted back more than once.
+    ///
+    /// [`into_raw()`]: #method.into_raw
+    pub unsafe fn from_raw(raw: *mut T) -> Self {
+        assert_not_null!(raw);
+        // `UnsafeCell<T>` has the same layout as `T`
+        Self(Arc::from_raw(raw as *const UnsafeCell<T>))
+    }
+}
+
+// only the Bonjour references retain the context they are passed
+#[cfg_attr(not(any(backend_bonjour, backend_bonjour_sdk)), allow(dead_code))]
+impl<T: 'static> SharedContext<T> {
+    /// Returns a reference that keeps the context alive for as long as it is held, e.g. by the
+    /// mDNS object the context has been passed to.
+    pub fn retain(&self) -> ContextRef {
+        ContextRef(self.0.clone())
+    }
+}
+
+impl<T> Clone for SharedContext<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T: Default> Default for SharedContext<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T> fmt::Debug for SharedContext<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("SharedContext")
+            .field(&self.as_ptr())
+            .finish()
+    }
+}
+
+/// Keeps the context of a [`SharedContext`] alive regardless of its type, see
+/// [`SharedContext::retain()`].
+///
+/// [`SharedContext`]: struct.SharedContext.html
+/// [`SharedContext::retain()`]: struct.SharedContext.html#method.retain
+// the reference is only held, and only the Bonjour references retain a context
+#[allow(dead_code)]
+pub(crate) struct ContextRef(Arc<dyn Any>);
+
+impl fmt::Debug for ContextRef {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("ContextRef")
+    }
+}
+
 /// Helper trait to unwrap a type to a `*const T` or a null-pointer if not present.
 pub trait UnwrapOrNull<T> {
     /// Unwraps this type to `*const T` or `ptr::null()` if not present.
@@ -63,10 +328,10 @@ impl<T> UnwrapMutOrNull<T> for Option<*mut T> {
     }
 }
 
-#[cfg(target_vendor = "apple")]
-pub(crate) mod macos {
+#[cfg(any(backend_bonjour, backend_bonjour_sdk))]
+pub(crate) mod bonjour {
     use crate::Result;
-    use libc::{fd_set, suseconds_t, time_t, timeval};
+    use bonjour_sys::dnssd_sock_t;
     use std::time::Duration;
     use std::{mem, ptr};
 
@@ -75,7 +340,10 @@ pub(crate) mod macos {
     ///
     /// # Safety
     /// This function is unsafe because it directly interfaces with C-library system calls.
-    pub unsafe fn read_select(sock_fd: i32, timeout: Duration) -> Result<u32> {
+    #[cfg(backend_bonjour)]
+    pub unsafe fn read_select(sock_fd: dnssd_sock_t, timeout: Duration) -> Result<u32> {
+        use libc::{fd_set, suseconds_t, time_t, timeval};
+
         let mut read_flags: fd_set = mem::zeroed();
 
         libc::FD_ZERO(&mut read_flags);
@@ -99,4 +367,86 @@ pub(crate) mod macos {
             Ok(result as u32)
         }
     }
+
+    /// Performs a Winsock `select()` on the specified `sock_fd` and `timeout`. Returns the select
+    /// result or `Err` if the result is negative.
+    ///
+    /// # Safety
+    /// This function is unsafe because it directly interfaces with Winsock system calls.
+    #[cfg(target_os = "windows")]
+    pub unsafe fn read_select(sock_fd: dnssd_sock_t, timeout: Duration) -> Result<u32> {
+        use windows_sys::Win32::Networking::WinSock::{select, FD_SET, TIMEVAL};
+
+        let mut read_flags: FD_SET = mem::zeroed();
+        read_flags.fd_count = 1;
+        read_flags.fd_array[0] = sock_fd as _;
+
+        let timeout = TIMEVAL {
+            tv_sec: timeout.as_secs() as i32,
+            tv_usec: timeout.subsec_micros() as i32,
+        };
+
+        // the first argument is ignored by Winsock
+        let result = select(
+            0,
+            &mut read_flags,
+            ptr::null_mut(),
+            ptr::null_mut(),
+            &timeout,
+        );
+
+        if result < 0 {
+            Err("select(): returned error status".into())
+        } else {
+            Ok(result as u32)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn link_local_scope_id_returns_scope_id_for_link_local_ipv6() {
+        let ip: IpAddr = "fe80::1234".parse().unwrap();
+        assert_eq!(link_local_scope_id(&ip, 4), 4);
+    }
+
+    #[test]
+    fn link_local_scope_id_ignores_global_addresses() {
+        let ipv6: IpAddr = "2001:db8::1".parse().unwrap();
+        let ipv4: IpAddr = "192.168.1.1".parse().unwrap();
+        assert_eq!(link_local_scope_id(&ipv6, 4), 0);
+        assert_eq!(link_local_scope_id(&ipv4, 4), 0);
+    }
+
+    #[test]
+    fn format_scoped_ip_omits_zone_without_scope_id() {
+        let ip: IpAddr = "fe80::1234".parse().unwrap();
+        assert_eq!(format_scoped_ip(&ip, 0), "fe80::1234");
+    }
+
+    #[test]
+    fn format_scoped_ip_falls_back_to_index_for_unknown_interface() {
+        let ip: IpAddr = "fe80::1234".parse().unwrap();
+        assert_eq!(format_scoped_ip(&ip, 0xfff0_0000), "fe80::1234%4293918720");
+    }
+
+    #[test]
+    fn shared_context_is_deallocated_once_released() {
+        let inner = Arc::new(());
+        let context = SharedContext::new(inner.clone());
+
+        let retained = context.retain();
+        let raw = context.clone().into_raw();
+        drop(context);
+        assert_eq!(Arc::strong_count(&inner), 2);
+
+        drop(retained);
+        assert_eq!(Arc::strong_count(&inner), 2);
+
+        drop(unsafe { SharedContext::from_raw(raw) });
+        assert_eq!(Arc::strong_count(&inner), 1);
+    }
 }
@@ -0,0 +1,71 @@
+//! Cross-platform flags for services and browsers
+
+bitflags! {
+    /// Flags that request optional behaviors from the underlying mDNS implementation when
+    /// registering a `MdnsService` or browsing with a `MdnsBrowser`.
+    ///
+    /// Not every flag is supported by every implementation. Flags that have no equivalent on the
+    /// current platform are ignored.
+    ///
+    /// # Bonjour Sleep Proxy
+    /// On macOS, services registered with Bonjour are handed off to a Bonjour Sleep Proxy on the
+    /// network when the machine goes to sleep (provided "Wake for network access" is enabled),
+    /// keeping them discoverable and waking the machine when a client connects. Registrations can
+    /// opt out of being proxied with `WAKE_ONLY_SERVICE`, and clients connecting to a proxied
+    /// service can wake the machine up as soon as they resolve it with `WAKE_ON_RESOLVE`.
+    #[derive(Default)]
+    pub struct ServiceFlags: u32 {
+        /// Forces the use of multicast DNS, even for domains other than `.local`.
+        const FORCE_MULTICAST = 1 << 0;
+        /// Requests that records are published as unique (i.e. only one host may answer for
+        /// them). Only applies to individual records; service registrations are always unique,
+        /// so it is ignored when registering services.
+        const UNIQUE = 1 << 1;
+        /// Disables automatically renaming a service on name conflicts, the registration fails
+        /// instead.
+        const NO_AUTO_RENAME = 1 << 2;
+        /// Registers a service that is not handed off to a Bonjour Sleep Proxy while the machine
+        /// sleeps (Bonjour only).
+        const WAKE_ONLY_SERVICE = 1 << 3;
+        /// Includes peer-to-peer interfaces when browsing or registering on all interfaces
+        /// (Bonjour only).
+        const INCLUDE_P2P = 1 << 4;
+        /// Includes Apple Wireless Direct Link (AWDL) interfaces when browsing or registering on
+        /// all interfaces, to discover peers over peer-to-peer Wi-Fi (Bonjour only).
+        const INCLUDE_AWDL = 1 << 5;
+        /// Requests DNSSEC validation of the results of a `RecordQuery` or `HostNameResolver`
+        /// (Bonjour only). Validation only applies to unicast DNS domains, the outcome is
+        /// reported as a [`DnssecStatus`].
+        ///
+        /// [`DnssecStatus`]: ../enum.DnssecStatus.html
+        const VALIDATE = 1 << 6;
+        /// Forces the use of wide-area (unicast) DNS when browsing and resolving (Avahi only,
+        /// Bonjour uses unicast DNS for domains other than `.local` automatically).
+        const WIDE_AREA = 1 << 7;
+        /// Resolves discovered services without looking up the addresses of their hosts, which
+        /// roughly halves the time it takes to resolve them, for consumers that connect to them by
+        /// host name (`avahi-daemon` only). `ServiceDiscovery::address` is then the unspecified
+        /// address and `ServiceDiscovery::addresses` is empty.
+        const NO_ADDRESS = 1 << 8;
+        /// Reports the negative answers of a `HostNameResolver` or `RecordQuery` as errors of kind
+        /// `ErrorKind::NoSuchRecord` as soon as they arrive, so that callers learn that e.g. a host
+        /// has no `AAAA` record without waiting for the operation to time out (Bonjour only). An
+        /// `MdnsBrowser` (or `MdnsResolver`, see `BonjourMdnsResolver::set_flags()`) fails the
+        /// resolution of a service early if its host turns out to have no addresses of the
+        /// requested families.
+        const RETURN_INTERMEDIATES = 1 << 9;
+        /// Wakes the host of a service up when resolving it while it sleeps, if it is proxied by
+        /// a Bonjour Sleep Proxy, e.g. for remote access to a sleeping Mac (Bonjour only). Applies
+        /// to the resolutions of an `MdnsBrowser` and, with `BonjourMdnsResolver::set_flags()`, of
+        /// an `MdnsResolver`.
+        const WAKE_ON_RESOLVE = 1 << 10;
+        /// Lets mDNSResponder stop querying for services once a browser has discovered one of
+        /// them, saving power in long-running browsers that only need any instance of a service
+        /// (Bonjour only). Services announced later are still discovered.
+        const THRESHOLD_ONE = 1 << 11;
+        /// Lets mDNSResponder reduce the rate at which a browser queries for services once it
+        /// considers enough of them discovered, saving power in long-running browsers, e.g. on
+        /// laptops (Bonjour only). Services announced later are still discovered.
+        const THRESHOLD_FINDER = 1 << 12;
+    }
+}
@@ -0,0 +1,107 @@
+//! Trait definition for cross-platform host name resolver
+
+use crate::ffi;
+use crate::{AddressFamily, DnssecStatus, EventLoop, NetworkInterface, Result, ServiceFlags};
+use std::any::Any;
+use std::net::{IpAddr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::sync::Arc;
+
+/// Interface for interacting with underlying mDNS implementation host name resolution
+/// capabilities.
+///
+/// Unlike an `MdnsBrowser`, a `HostNameResolver` resolves an already known host name (e.g.
+/// `somehost.local`) to its addresses without discovering any services.
+pub trait THostNameResolver {
+    /// Creates a new `HostNameResolver` that resolves the specified `host_name` (e.g.
+    /// `somehost.local`)
+    fn new(host_name: &str) -> Self
+    where
+        Self: Sized;
+
+    /// Sets the network interface on which to resolve the host name.
+    ///
+    /// Most applications will want to use the default value `NetworkInterface::Unspec` to resolve
+    /// on all available interfaces.
+    fn set_network_interface(&mut self, interface: NetworkInterface);
+
+    /// Sets the [`AddressFamily`] to resolve the host name to.
+    ///
+    /// [`AddressFamily`]: ../enum.AddressFamily.html
+    fn set_address_family(&mut self, address_family: AddressFamily);
+
+    /// Sets the [`ServiceFlags`] to resolve the host name with.
+    ///
+    /// If `ServiceFlags::VALIDATE` is set, the [`HostNameResolvedCallback`] is invoked again once
+    /// an address has been validated.
+    ///
+    /// [`ServiceFlags`]: ../flags/struct.ServiceFlags.html
+    /// [`HostNameResolvedCallback`]: ../type.HostNameResolvedCallback.html
+    fn set_flags(&mut self, flags: ServiceFlags);
+
+    /// Sets the [`HostNameResolvedCallback`] that is invoked when the host name has been resolved
+    /// to an address.
+    ///
+    /// # Note
+    /// Bonjour invokes the callback once for each address of the host, Avahi only reports a
+    /// single address.
+    ///
+    /// [`HostNameResolvedCallback`]: ../type.HostNameResolvedCallback.html
+    fn set_host_name_resolved_callback(
+        &mut self,
+        host_name_resolved_callback: Box<HostNameResolvedCallback>,
+    );
+
+    /// Sets the optional user context to pass through to the callback. This is useful if you need
+    /// to share state between pre and post-callback. The context type must implement `Any`, `Send`
+    /// and `Sync`.
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>);
+
+    /// Starts the resolver. Returns an `EventLoop` which can be called to keep the resolver alive.
+    fn resolve(&mut self) -> Result<EventLoop<'_>>;
+}
+
+/// Callback invoked from [`HostNameResolver`] once a host name has been resolved.
+///
+/// # Arguments
+/// * `resolution` - The address the host name was resolved to
+/// * `context` - The optional user context passed through
+///
+/// [`HostNameResolver`]: type.HostNameResolver.html
+pub type HostNameResolvedCallback =
+    dyn Fn(Result<HostNameResolution>, Option<Arc<dyn Any + Send + Sync>>) + Send;
+
+/// Represents an address a host name has been resolved to by a [`HostNameResolver`].
+///
+/// [`HostNameResolver`]: type.HostNameResolver.html
+#[derive(
+    Debug, Getters, Builder, BuilderDelegate, Serialize, Deserialize, Clone, PartialEq, Eq,
+)]
+pub struct HostNameResolution {
+    host_name: String,
+    address: IpAddr,
+    /// The scope ID (i.e. interface index) of a link-local IPv6 address, `0` otherwise
+    #[builder(default)]
+    scope_id: u32,
+    /// The DNSSEC validation status of the address, if validation was requested with
+    /// `ServiceFlags::VALIDATE`
+    #[builder(default)]
+    dnssec_status: Option<DnssecStatus>,
+}
+
+impl HostNameResolution {
+    /// Returns the `SocketAddr` to connect to the specified `port` of the host at.
+    ///
+    /// Link-local IPv6 addresses include the scope ID of the interface they were resolved on.
+    pub fn socket_addr(&self, port: u16) -> SocketAddr {
+        match self.address {
+            IpAddr::V4(ip) => SocketAddrV4::new(ip, port).into(),
+            IpAddr::V6(ip) => SocketAddrV6::new(ip, port, 0, self.scope_id).into(),
+        }
+    }
+
+    /// Returns the textual representation of the address, including the zone of link-local IPv6
+    /// addresses (e.g. `fe80::1%en0`).
+    pub fn scoped_address(&self) -> String {
+        ffi::format_scoped_ip(&self.address, self.scope_id)
+    }
+}
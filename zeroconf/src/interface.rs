@@ -1,3 +1,8 @@
+use crate::error::{Error, ErrorKind};
+use crate::ffi;
+use crate::Result;
+use std::net::IpAddr;
+
 /// Represents a network interface for mDNS services
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum NetworkInterface {
@@ -5,4 +10,300 @@ pub enum NetworkInterface {
     Unspec,
     /// An interface at a specified index
     AtIndex(u32),
+    /// Only peer-to-peer interfaces, such as Apple's AWDL (Bonjour only, Avahi treats this as
+    /// `Unspec`)
+    PeerToPeer,
+}
+
+impl NetworkInterface {
+    /// Returns the interface with the specified name of the operating system (e.g. `eth0` or
+    /// `en0`), or an error if there is no such interface.
+    ///
+    /// The name is resolved to the index of the interface when this is called, an interface that
+    /// is recreated afterwards (e.g. a VPN tunnel) must be looked up again.
+    pub fn named(name: &str) -> Result<Self> {
+        ffi::interface_index(name)
+            .map(NetworkInterface::AtIndex)
+            .ok_or_else(|| format!("no network interface named `{}`", name).into())
+    }
+}
+
+bitflags! {
+    /// The state and capabilities of an [`Interface`] of the host.
+    ///
+    /// [`Interface`]: struct.Interface.html
+    pub struct InterfaceFlags: u32 {
+        /// The interface is up
+        const UP = 1 << 0;
+        /// The interface is a loopback interface
+        const LOOPBACK = 1 << 1;
+        /// The interface supports multicast
+        const MULTICAST = 1 << 2;
+        /// The interface is a point-to-point link, such as a VPN tunnel
+        const POINT_TO_POINT = 1 << 3;
+    }
+}
+
+/// A network interface of the host, as returned by [`interfaces()`].
+///
+/// [`interfaces()`]: fn.interfaces.html
+#[derive(Debug, Getters, Clone, PartialEq, Eq)]
+pub struct Interface {
+    /// The name of the interface (e.g. `eth0` or `en0`)
+    name: String,
+    /// The index of the interface
+    index: u32,
+    /// The addresses assigned to the interface
+    addresses: Vec<IpAddr>,
+    flags: InterfaceFlags,
+}
+
+impl Interface {
+    /// Returns the `NetworkInterface` to pass to `set_network_interface()` to only register or
+    /// browse services on this interface.
+    pub fn network_interface(&self) -> NetworkInterface {
+        NetworkInterface::AtIndex(self.index)
+    }
+}
+
+/// Returns the interfaces of the host that are up and support multicast, ordered by index, e.g. to
+/// let the user choose the interface to register or browse services on.
+///
+/// The interfaces are listed by the operating system rather than by the mDNS implementation, so
+/// errors have the error code of the operating system but no `Operation`.
+///
+/// # Examples
+/// ```no_run
+/// for interface in zeroconf::interfaces().unwrap() {
+///     println!("{} ({}): {:?}", interface.name(), interface.index(), interface.addresses());
+/// }
+/// ```
+pub fn interfaces() -> Result<Vec<Interface>> {
+    let mut interfaces: Vec<Interface> = all_interfaces()?
+        .into_iter()
+        .filter(|i| {
+            i.flags
+                .contains(InterfaceFlags::UP | InterfaceFlags::MULTICAST)
+        })
+        .collect();
+
+    interfaces.sort_by_key(|i| i.index);
+
+    Ok(interfaces)
+}
+
+#[cfg(unix)]
+fn all_interfaces() -> Result<Vec<Interface>> {
+    use libc::{freeifaddrs, getifaddrs, ifaddrs};
+    use std::ffi::CStr;
+    use std::ptr;
+
+    let mut interfaces: Vec<Interface> = vec![];
+    let mut ifap: *mut ifaddrs = ptr::null_mut();
+
+    unsafe {
+        if getifaddrs(&mut ifap) != 0 {
+            let e = std::io::Error::last_os_error();
+            let error = Error::with_kind(ErrorKind::Other, format!("getifaddrs() failed: {}", e));
+
+            return Err(match e.raw_os_error() {
+                Some(code) => error.with_code(code),
+                None => error,
+            });
+        }
+
+        // interfaces are listed once per address
+        let mut cur = ifap;
+        while !cur.is_null() {
+            let ifa = &*cur;
+            let name = CStr::from_ptr(ifa.ifa_name).to_string_lossy().into_owned();
+
+            let position = match interfaces.iter().position(|i| i.name == name) {
+                Some(position) => Some(position),
+                None => ffi::interface_index(&name).map(|index| {
+                    interfaces.push(Interface {
+                        flags: interface_flags(ifa.ifa_flags),
+                        name,
+                        index,
+                        addresses: vec![],
+                    });
+
+                    interfaces.len() - 1
+                }),
+            };
+
+            if let (Some(position), false) = (position, ifa.ifa_addr.is_null()) {
+                if let Some(ip) = ffi::sockaddr_to_ip(ifa.ifa_addr) {
+                    interfaces[position].addresses.push(ip);
+                }
+            }
+
+            cur = ifa.ifa_next;
+        }
+
+        freeifaddrs(ifap);
+    }
+
+    Ok(interfaces)
+}
+
+#[cfg(unix)]
+fn interface_flags(ifa_flags: libc::c_uint) -> InterfaceFlags {
+    let mut flags = InterfaceFlags::empty();
+
+    flags.set(InterfaceFlags::UP, ifa_flags & libc::IFF_UP as u32 != 0);
+    flags.set(
+        InterfaceFlags::LOOPBACK,
+        ifa_flags & libc::IFF_LOOPBACK as u32 != 0,
+    );
+    flags.set(
+        InterfaceFlags::MULTICAST,
+        ifa_flags & libc::IFF_MULTICAST as u32 != 0,
+    );
+    flags.set(
+        InterfaceFlags::POINT_TO_POINT,
+        ifa_flags & libc::IFF_POINTOPOINT as u32 != 0,
+    );
+
+    flags
+}
+
+#[cfg(target_os = "windows")]
+fn all_interfaces() -> Result<Vec<Interface>> {
+    use std::{mem, ptr};
+    use windows_sys::Win32::Foundation::{ERROR_BUFFER_OVERFLOW, ERROR_SUCCESS};
+    use windows_sys::Win32::NetworkManagement::IpHelper::{
+        GetAdaptersAddresses, GAA_FLAG_SKIP_ANYCAST, GAA_FLAG_SKIP_DNS_SERVER,
+        GAA_FLAG_SKIP_MULTICAST, IP_ADAPTER_ADDRESSES_LH,
+    };
+    use windows_sys::Win32::NetworkManagement::Ndis::IfOperStatusUp;
+    use windows_sys::Win32::Networking::WinSock::AF_UNSPEC;
+
+    // from `ipifcons.h` and `iptypes.h`
+    const IF_TYPE_PPP: u32 = 23;
+    const IF_TYPE_SOFTWARE_LOOPBACK: u32 = 24;
+    const IF_TYPE_TUNNEL: u32 = 131;
+    const IP_ADAPTER_NO_MULTICAST: u32 = 0x0010;
+
+    let mut interfaces = vec![];
+    let flags = GAA_FLAG_SKIP_ANYCAST | GAA_FLAG_SKIP_MULTICAST | GAA_FLAG_SKIP_DNS_SERVER;
+
+    // the buffer is retried with the size requested by the previous call if it is too small
+    let mut size: u32 = 16 * 1024;
+    let mut buffer: Vec<u64>;
+
+    unsafe {
+        loop {
+            buffer = vec![0; size as usize / mem::size_of::<u64>() + 1];
+
+            let result = GetAdaptersAddresses(
+                AF_UNSPEC as u32,
+                flags,
+                ptr::null(),
+                buffer.as_mut_ptr() as *mut IP_ADAPTER_ADDRESSES_LH,
+                &mut size,
+            );
+
+            match result {
+                ERROR_SUCCESS => break,
+                ERROR_BUFFER_OVERFLOW => continue,
+                _ => {
+                    return Err(Error::with_kind(
+                        ErrorKind::Other,
+                        format!("GetAdaptersAddresses() failed with error {}", result),
+                    )
+                    .with_code(result as i32))
+                }
+            }
+        }
+
+        let mut adapter = buffer.as_ptr() as *const IP_ADAPTER_ADDRESSES_LH;
+        while !adapter.is_null() {
+            let ada = &*adapter;
+
+            // adapters without IPv4 only have an IPv6 index
+            let index = match ada.Anonymous1.Anonymous.IfIndex {
+                0 => ada.Ipv6IfIndex,
+                index => index,
+            };
+
+            let mut addresses = vec![];
+            let mut unicast = ada.FirstUnicastAddress;
+            while !unicast.is_null() {
+                let sockaddr = (*unicast).Address.lpSockaddr as *const libc::sockaddr;
+
+                if !sockaddr.is_null() {
+                    if let Some(ip) = ffi::sockaddr_to_ip(sockaddr) {
+                        addresses.push(ip);
+                    }
+                }
+
+                unicast = (*unicast).Next;
+            }
+
+            let mut interface_flags = InterfaceFlags::empty();
+            interface_flags.set(InterfaceFlags::UP, ada.OperStatus == IfOperStatusUp);
+            interface_flags.set(
+                InterfaceFlags::LOOPBACK,
+                ada.IfType == IF_TYPE_SOFTWARE_LOOPBACK,
+            );
+            interface_flags.set(
+                InterfaceFlags::MULTICAST,
+                ada.Anonymous2.Flags & IP_ADAPTER_NO_MULTICAST == 0,
+            );
+            interface_flags.set(
+                InterfaceFlags::POINT_TO_POINT,
+                ada.IfType == IF_TYPE_PPP || ada.IfType == IF_TYPE_TUNNEL,
+            );
+
+            interfaces.push(Interface {
+                // the same names as accepted by `NetworkInterface::named()`
+                name: ffi::interface_name(index).unwrap_or_else(|| {
+                    ffi::c_str::copy_raw(ada.AdapterName as *const libc::c_char)
+                }),
+                index,
+                addresses,
+                flags: interface_flags,
+            });
+
+            adapter = ada.Next;
+        }
+    }
+
+    Ok(interfaces)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_resolves_index() {
+        // the loopback interface is the first one on most systems
+        if let Some(name) = ffi::interface_name(1) {
+            assert_eq!(
+                NetworkInterface::named(&name).unwrap(),
+                NetworkInterface::AtIndex(1)
+            );
+        }
+    }
+
+    #[test]
+    fn named_fails_for_unknown_interface() {
+        NetworkInterface::named("zeroconf-none").expect_err("no such interface");
+        NetworkInterface::named("eth\0").expect_err("invalid name");
+    }
+
+    #[test]
+    fn interfaces_are_up_and_support_multicast() {
+        for interface in interfaces().unwrap() {
+            assert!(interface
+                .flags()
+                .contains(InterfaceFlags::UP | InterfaceFlags::MULTICAST));
+            assert_eq!(
+                NetworkInterface::named(interface.name()).unwrap(),
+                interface.network_interface()
+            );
+        }
+    }
 }
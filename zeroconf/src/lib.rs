@@ -13,8 +13,10 @@
 //! ## Register a service
 //!
 //! When registering a service, you may optionally pass a "context" to pass state through the
-//! callback. The only requirement is that this context implements the [`Any`] trait, which most
-//! types will automatically. See [`MdnsService`] for more information about contexts.
+//! callback. The only requirement is that this context implements the [`Any`], `Send` and `Sync`
+//! traits, which most types do automatically, and callbacks get it back with
+//! [`ContextExt::get()`] from the prelude. See [`MdnsService`] for more information about
+//! contexts.
 //!
 //! ```no_run
 //! use std::any::Any;
@@ -49,18 +51,13 @@
 //!
 //! fn on_service_registered(
 //!     result: zeroconf::Result<ServiceRegistration>,
-//!     context: Option<Arc<dyn Any>>,
+//!     context: Option<Arc<dyn Any + Send + Sync>>,
 //! ) {
 //!     let service = result.unwrap();
 //!
 //!     println!("Service registered: {:?}", service);
 //!
-//!     let context = context
-//!         .as_ref()
-//!         .unwrap()
-//!         .downcast_ref::<Arc<Mutex<Context>>>()
-//!         .unwrap()
-//!         .clone();
+//!     let context = context.get::<Arc<Mutex<Context>>>().unwrap().clone();
 //!
 //!     context.lock().unwrap().service_name = service.name().clone();
 //!
@@ -93,7 +90,7 @@
 //!
 //! fn on_service_discovered(
 //!     result: zeroconf::Result<ServiceDiscovery>,
-//!     _context: Option<Arc<dyn Any>>,
+//!     _context: Option<Arc<dyn Any + Send + Sync>>,
 //! ) {
 //!     println!("Service discovered: {:?}", result.unwrap());
 //!
@@ -107,6 +104,7 @@
 //! [`MdnsService`]: type.MdnsService.html
 //! [`MdnsBrowser`]: type.MdnsBrowser.html
 //! [`Any`]: https://doc.rust-lang.org/std/any/trait.Any.html
+//! [`ContextExt::get()`]: context/trait.ContextExt.html#tymethod.get
 
 #![allow(clippy::needless_doctest_main)]
 #[macro_use]
@@ -115,9 +113,9 @@ extern crate serde;
 extern crate derive_builder;
 #[macro_use]
 extern crate zeroconf_macros;
-#[cfg(target_os = "linux")]
+#[cfg(any(backend_avahi, backend_avahi_core))]
 extern crate avahi_sys;
-#[cfg(target_vendor = "apple")]
+#[cfg(any(backend_bonjour, backend_bonjour_sdk))]
 extern crate bonjour_sys;
 #[macro_use]
 extern crate derive_getters;
@@ -125,6 +123,8 @@ extern crate derive_getters;
 extern crate log;
 #[macro_use]
 extern crate derive_new;
+#[macro_use]
+extern crate bitflags;
 
 #[macro_use]
 #[cfg(test)]
@@ -132,59 +132,383 @@ extern crate derive_new;
 extern crate maplit;
 
 #[macro_use]
+#[cfg_attr(
+    not(any(
+        backend_avahi,
+        backend_avahi_core,
+        backend_bonjour,
+        backend_bonjour_sdk
+    )),
+    allow(unused_macros)
+)]
 mod macros;
+// only the Bonjour browser looks up the addresses of each host separately
+#[cfg_attr(not(any(backend_bonjour, backend_bonjour_sdk)), allow(dead_code))]
+mod address_cache;
+mod address_family;
+mod address_policy;
+mod dnssec_status;
+mod event_meta;
+// the raw pointer utilities are mostly used by the Avahi and Bonjour backends
+#[cfg_attr(
+    not(any(
+        backend_avahi,
+        backend_avahi_core,
+        backend_bonjour,
+        backend_bonjour_sdk
+    )),
+    allow(dead_code)
+)]
 mod ffi;
+// only the backends that are told about services that have gone away debounce them
+#[cfg_attr(
+    any(backend_avahi_dbus, backend_resolved, backend_plugin, backend_win32),
+    allow(dead_code)
+)]
+mod debounce;
 mod interface;
+// the mock and plugin backends do not watch the network configuration
+#[cfg_attr(any(backend_mock, backend_plugin), allow(dead_code))]
+mod network_watcher;
+// only the backends that resolve several services at once queue them
+#[cfg_attr(
+    not(any(backend_avahi, backend_avahi_dbus, backend_avahi_core, backend_win32)),
+    allow(dead_code)
+)]
+mod resolve_queue;
 mod service_type;
 #[cfg(test)]
 mod tests;
+mod trace;
 
+pub mod address_resolver;
+pub mod backend;
+pub mod background;
 pub mod browser;
+pub mod channel;
+pub mod context;
+pub mod daemon;
+pub mod dns;
 pub mod error;
 pub mod event_loop;
+pub mod flags;
+pub mod host_name_resolver;
+pub mod port_mapping;
 pub mod prelude;
+pub mod record_query;
+pub mod registration_handle;
 pub mod service;
+pub mod service_resolver;
 pub mod txt_record;
 
-#[cfg(target_os = "linux")]
+#[cfg(backend_avahi_core)]
+pub mod avahi_core;
+#[cfg(backend_avahi_dbus)]
+pub mod avahi_dbus;
+#[cfg(backend_embedded)]
+pub mod embedded;
+#[cfg(backend_avahi)]
 pub mod linux;
-#[cfg(target_vendor = "apple")]
+#[cfg(backend_bonjour)]
 pub mod macos;
+#[cfg(backend_mock)]
+pub mod mock;
+#[cfg(backend_plugin)]
+pub mod plugin;
+#[cfg(backend_resolved)]
+pub mod resolved;
+#[cfg(backend_win32)]
+pub mod win32;
+#[cfg(backend_bonjour_sdk)]
+pub mod windows;
+
+#[cfg(all(
+    target_os = "windows",
+    not(any(
+        feature = "bonjour-sdk",
+        feature = "windows-native",
+        feature = "mock",
+        feature = "plugin"
+    ))
+))]
+compile_error!(
+    "either the `bonjour-sdk` or the `windows-native` feature must be enabled on Windows"
+);
+
+#[cfg(all(
+    target_os = "linux",
+    not(any(
+        feature = "avahi",
+        feature = "avahi-dbus",
+        feature = "avahi-core",
+        feature = "systemd-resolved",
+        feature = "embedded",
+        feature = "mock",
+        feature = "plugin",
+        feature = "mdnsresponder"
+    ))
+))]
+compile_error!(
+    "either the `avahi`, the `avahi-dbus`, the `avahi-core`, the `systemd-resolved`, the \
+     `mdnsresponder` or the `embedded` feature must be enabled on Linux"
+);
+
+#[cfg(all(
+    any(target_os = "freebsd", target_os = "openbsd"),
+    not(any(
+        feature = "avahi",
+        feature = "embedded",
+        feature = "mock",
+        feature = "plugin"
+    ))
+))]
+compile_error!(
+    "either the `avahi` or the `embedded` feature must be enabled on FreeBSD and OpenBSD"
+);
 
-pub use browser::{ServiceDiscoveredCallback, ServiceDiscovery};
+#[cfg(all(feature = "embedded", not(unix)))]
+compile_error!("the `embedded` feature is only supported on unix platforms");
+
+#[cfg(all(feature = "force-avahi", not(unix)))]
+compile_error!("the `force-avahi` feature is only supported on unix platforms");
+
+pub use address_family::*;
+pub use address_policy::*;
+pub use address_resolver::{AddressResolution, AddressResolvedCallback};
+pub use backend::{Backend, Capabilities, Mdns};
+pub use browser::{
+    browse_scoped, BrowserBuilder, BrowserControl, BrowserEvent, BrowserStatus,
+    ServiceBatchCallback, ServiceDiscoveredCallback, ServiceDiscovery,
+};
+pub use daemon::{daemon_info, DaemonInfo, DaemonReconnectedCallback};
+pub use dnssec_status::*;
+pub use error::ErrorCallback;
+pub use event_loop::{poll_intervals, set_poll_intervals, PollIntervals};
+pub use event_meta::*;
+pub use flags::ServiceFlags;
+pub use host_name_resolver::{HostNameResolution, HostNameResolvedCallback};
 pub use interface::*;
-pub use service::{ServiceRegisteredCallback, ServiceRegistration};
+pub use port_mapping::{PortMappedCallback, PortMappingProtocol, PortMappingResult};
+pub use record_query::{Record, RecordReceivedCallback};
+pub use service::{
+    NameConflictCallback, ServiceBuilder, ServiceRegisteredCallback, ServiceRegistration,
+};
 pub use service_type::*;
+pub use txt_record::TxtRecordView;
 
 /// Type alias for the platform-specific mDNS browser implementation
-#[cfg(target_os = "linux")]
+#[cfg(backend_avahi)]
 pub type MdnsBrowser = linux::browser::AvahiMdnsBrowser;
 /// Type alias for the platform-specific mDNS browser implementation
-#[cfg(target_vendor = "apple")]
+#[cfg(backend_avahi_dbus)]
+pub type MdnsBrowser = avahi_dbus::browser::AvahiDbusMdnsBrowser;
+/// Type alias for the platform-specific mDNS browser implementation
+#[cfg(backend_resolved)]
+pub type MdnsBrowser = resolved::browser::ResolvedMdnsBrowser;
+/// Type alias for the platform-specific mDNS browser implementation
+#[cfg(backend_avahi_core)]
+pub type MdnsBrowser = avahi_core::browser::AvahiCoreMdnsBrowser;
+/// Type alias for the platform-specific mDNS browser implementation
+#[cfg(backend_bonjour)]
 pub type MdnsBrowser = macos::browser::BonjourMdnsBrowser;
+/// Type alias for the platform-specific mDNS browser implementation
+#[cfg(backend_bonjour_sdk)]
+pub type MdnsBrowser = windows::browser::BonjourMdnsBrowser;
+/// Type alias for the platform-specific mDNS browser implementation
+#[cfg(backend_win32)]
+pub type MdnsBrowser = win32::browser::Win32MdnsBrowser;
+/// Type alias for the platform-specific mDNS browser implementation
+#[cfg(backend_embedded)]
+pub type MdnsBrowser = embedded::browser::EmbeddedMdnsBrowser;
+/// Type alias for the platform-specific mDNS browser implementation
+#[cfg(backend_mock)]
+pub type MdnsBrowser = mock::browser::MockMdnsBrowser;
+/// Type alias for the platform-specific mDNS browser implementation
+#[cfg(backend_plugin)]
+pub type MdnsBrowser = plugin::browser::PluginMdnsBrowser;
+
+/// Type alias for the platform-specific mDNS service resolver implementation
+#[cfg(backend_avahi)]
+pub type MdnsResolver = linux::service_resolver::AvahiMdnsResolver;
+/// Type alias for the platform-specific mDNS service resolver implementation
+#[cfg(backend_bonjour)]
+pub type MdnsResolver = macos::service_resolver::BonjourMdnsResolver;
+/// Type alias for the platform-specific mDNS service resolver implementation
+#[cfg(backend_bonjour_sdk)]
+pub type MdnsResolver = windows::service_resolver::BonjourMdnsResolver;
+/// Type alias for the platform-specific mDNS service resolver implementation
+#[cfg(backend_plugin)]
+pub type MdnsResolver = plugin::service_resolver::PluginMdnsResolver;
 
 /// Type alias for the platform-specific mDNS service implementation
-#[cfg(target_os = "linux")]
+#[cfg(backend_avahi)]
 pub type MdnsService = linux::service::AvahiMdnsService;
 /// Type alias for the platform-specific mDNS service implementation
-#[cfg(target_vendor = "apple")]
+#[cfg(backend_avahi_dbus)]
+pub type MdnsService = avahi_dbus::service::AvahiDbusMdnsService;
+/// Type alias for the platform-specific mDNS service implementation
+#[cfg(backend_resolved)]
+pub type MdnsService = resolved::service::ResolvedMdnsService;
+/// Type alias for the platform-specific mDNS service implementation
+#[cfg(backend_avahi_core)]
+pub type MdnsService = avahi_core::service::AvahiCoreMdnsService;
+/// Type alias for the platform-specific mDNS service implementation
+#[cfg(backend_bonjour)]
 pub type MdnsService = macos::service::BonjourMdnsService;
+/// Type alias for the platform-specific mDNS service implementation
+#[cfg(backend_bonjour_sdk)]
+pub type MdnsService = windows::service::BonjourMdnsService;
+/// Type alias for the platform-specific mDNS service implementation
+#[cfg(backend_win32)]
+pub type MdnsService = win32::service::Win32MdnsService;
+/// Type alias for the platform-specific mDNS service implementation
+#[cfg(backend_embedded)]
+pub type MdnsService = embedded::service::EmbeddedMdnsService;
+/// Type alias for the platform-specific mDNS service implementation
+#[cfg(backend_mock)]
+pub type MdnsService = mock::service::MockMdnsService;
+/// Type alias for the platform-specific mDNS service implementation
+#[cfg(backend_plugin)]
+pub type MdnsService = plugin::service::PluginMdnsService;
+
+/// Type alias for the platform-specific mDNS address resolver implementation
+#[cfg(backend_avahi)]
+pub type AddressResolver = linux::address_resolver::AvahiMdnsAddressResolver;
+/// Type alias for the platform-specific mDNS address resolver implementation
+#[cfg(backend_bonjour)]
+pub type AddressResolver = macos::address_resolver::BonjourMdnsAddressResolver;
+/// Type alias for the platform-specific mDNS address resolver implementation
+#[cfg(backend_bonjour_sdk)]
+pub type AddressResolver = windows::address_resolver::BonjourMdnsAddressResolver;
+
+/// Type alias for the platform-specific mDNS host name resolver implementation
+#[cfg(backend_avahi)]
+pub type HostNameResolver = linux::host_name_resolver::AvahiMdnsHostNameResolver;
+/// Type alias for the platform-specific mDNS host name resolver implementation
+#[cfg(backend_bonjour)]
+pub type HostNameResolver = macos::host_name_resolver::BonjourMdnsHostNameResolver;
+/// Type alias for the platform-specific mDNS host name resolver implementation
+#[cfg(backend_bonjour_sdk)]
+pub type HostNameResolver = windows::host_name_resolver::BonjourMdnsHostNameResolver;
 
 /// Type alias for the platform-specific structure responsible for polling the mDNS event loop
-#[cfg(target_os = "linux")]
+#[cfg(backend_avahi)]
 pub type EventLoop<'a> = linux::event_loop::AvahiEventLoop<'a>;
 /// Type alias for the platform-specific structure responsible for polling the mDNS event loop
-#[cfg(target_vendor = "apple")]
+#[cfg(backend_avahi_dbus)]
+pub type EventLoop<'a> = avahi_dbus::event_loop::AvahiDbusEventLoop<'a>;
+/// Type alias for the platform-specific structure responsible for polling the mDNS event loop
+#[cfg(backend_resolved)]
+pub type EventLoop<'a> = resolved::event_loop::ResolvedEventLoop<'a>;
+/// Type alias for the platform-specific structure responsible for polling the mDNS event loop
+#[cfg(backend_avahi_core)]
+pub type EventLoop<'a> = avahi_core::event_loop::AvahiEventLoop<'a>;
+/// Type alias for the platform-specific structure responsible for polling the mDNS event loop
+#[cfg(backend_bonjour)]
 pub type EventLoop<'a> = macos::event_loop::BonjourEventLoop<'a>;
+/// Type alias for the platform-specific structure responsible for polling the mDNS event loop
+#[cfg(backend_bonjour_sdk)]
+pub type EventLoop<'a> = windows::event_loop::BonjourEventLoop<'a>;
+/// Type alias for the platform-specific structure responsible for polling the mDNS event loop
+#[cfg(backend_win32)]
+pub type EventLoop<'a> = win32::event_loop::Win32EventLoop<'a>;
+/// Type alias for the platform-specific structure responsible for polling the mDNS event loop
+#[cfg(backend_embedded)]
+pub type EventLoop<'a> = embedded::event_loop::EmbeddedEventLoop<'a>;
+/// Type alias for the platform-specific structure responsible for polling the mDNS event loop
+#[cfg(backend_mock)]
+pub type EventLoop<'a> = mock::event_loop::MockEventLoop<'a>;
+/// Type alias for the platform-specific structure responsible for polling the mDNS event loop
+#[cfg(backend_plugin)]
+pub type EventLoop<'a> = plugin::event_loop::PluginEventLoop<'a>;
+
+/// Type alias for the platform-specific NAT port mapping implementation
+#[cfg(backend_bonjour)]
+pub type PortMapping = macos::port_mapping::BonjourPortMapping;
+/// Type alias for the platform-specific NAT port mapping implementation
+#[cfg(backend_bonjour_sdk)]
+pub type PortMapping = windows::port_mapping::BonjourPortMapping;
+
+/// Type alias for the platform-specific DNS record query implementation
+#[cfg(backend_avahi)]
+pub type RecordQuery = linux::record_query::AvahiMdnsRecordQuery;
+/// Type alias for the platform-specific DNS record query implementation
+#[cfg(backend_bonjour)]
+pub type RecordQuery = macos::record_query::BonjourMdnsRecordQuery;
+/// Type alias for the platform-specific DNS record query implementation
+#[cfg(backend_bonjour_sdk)]
+pub type RecordQuery = windows::record_query::BonjourMdnsRecordQuery;
+
+/// Type alias for the platform-specific handle on a registered `MdnsService`
+#[cfg(backend_avahi)]
+pub type RegistrationHandle<'a> = linux::registration_handle::AvahiRegistrationHandle<'a>;
+/// Type alias for the platform-specific handle on a registered `MdnsService`
+#[cfg(backend_avahi_dbus)]
+pub type RegistrationHandle<'a> = avahi_dbus::registration_handle::AvahiDbusRegistrationHandle<'a>;
+/// Type alias for the platform-specific handle on a registered `MdnsService`
+#[cfg(backend_resolved)]
+pub type RegistrationHandle<'a> = resolved::registration_handle::ResolvedRegistrationHandle<'a>;
+/// Type alias for the platform-specific handle on a registered `MdnsService`
+#[cfg(backend_avahi_core)]
+pub type RegistrationHandle<'a> = avahi_core::registration_handle::AvahiCoreRegistrationHandle<'a>;
+/// Type alias for the platform-specific handle on a registered `MdnsService`
+#[cfg(backend_bonjour)]
+pub type RegistrationHandle<'a> = macos::registration_handle::BonjourRegistrationHandle<'a>;
+/// Type alias for the platform-specific handle on a registered `MdnsService`
+#[cfg(backend_bonjour_sdk)]
+pub type RegistrationHandle<'a> = windows::registration_handle::BonjourRegistrationHandle<'a>;
+/// Type alias for the platform-specific handle on a registered `MdnsService`
+#[cfg(backend_win32)]
+pub type RegistrationHandle<'a> = win32::registration_handle::Win32RegistrationHandle<'a>;
+/// Type alias for the platform-specific handle on a registered `MdnsService`
+#[cfg(backend_embedded)]
+pub type RegistrationHandle<'a> = embedded::registration_handle::EmbeddedRegistrationHandle<'a>;
+/// Type alias for the platform-specific handle on a registered `MdnsService`
+#[cfg(backend_mock)]
+pub type RegistrationHandle<'a> = mock::registration_handle::MockRegistrationHandle<'a>;
+/// Type alias for the platform-specific handle on a registered `MdnsService`
+#[cfg(backend_plugin)]
+pub type RegistrationHandle<'a> = plugin::registration_handle::PluginRegistrationHandle<'a>;
 
 /// Type alias for the platform-specific structure responsible for storing and accessing TXT
 /// record data
-#[cfg(target_os = "linux")]
+#[cfg(backend_avahi)]
 pub type TxtRecord = linux::txt_record::AvahiTxtRecord;
 /// Type alias for the platform-specific structure responsible for storing and accessing TXT
 /// record data
-#[cfg(target_vendor = "apple")]
+#[cfg(backend_avahi_dbus)]
+pub type TxtRecord = avahi_dbus::txt_record::AvahiDbusTxtRecord;
+/// record data
+#[cfg(backend_resolved)]
+pub type TxtRecord = resolved::txt_record::EmbeddedTxtRecord;
+/// Type alias for the platform-specific structure responsible for storing and accessing TXT
+/// record data
+#[cfg(backend_avahi_core)]
+pub type TxtRecord = avahi_core::txt_record::AvahiTxtRecord;
+/// Type alias for the platform-specific structure responsible for storing and accessing TXT
+/// record data
+#[cfg(backend_bonjour)]
 pub type TxtRecord = macos::txt_record::BonjourTxtRecord;
+/// Type alias for the platform-specific structure responsible for storing and accessing TXT
+/// record data
+#[cfg(backend_bonjour_sdk)]
+pub type TxtRecord = windows::txt_record::BonjourTxtRecord;
+/// Type alias for the platform-specific structure responsible for storing and accessing TXT
+/// record data
+#[cfg(backend_win32)]
+pub type TxtRecord = win32::txt_record::Win32TxtRecord;
+/// Type alias for the platform-specific structure responsible for storing and accessing TXT
+/// record data
+#[cfg(backend_embedded)]
+pub type TxtRecord = embedded::txt_record::EmbeddedTxtRecord;
+/// Type alias for the platform-specific structure responsible for storing and accessing TXT
+/// record data
+#[cfg(backend_mock)]
+pub type TxtRecord = mock::txt_record::EmbeddedTxtRecord;
+/// Type alias for the platform-specific structure responsible for storing and accessing TXT
+/// record data
+#[cfg(backend_plugin)]
+pub type TxtRecord = plugin::txt_record::EmbeddedTxtRecord;
 
 /// Result type for this library
 pub type Result<T> = std::result::Result<T, error::Error>;
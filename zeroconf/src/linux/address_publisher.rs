@@ -0,0 +1,233 @@
+//! Avahi publisher for the address records of other hosts
+
+use super::avahi_util;
+use super::client::{ManagedAvahiClient, ManagedAvahiClientParams};
+use super::entry_group::{
+    AddAddressParams, ManagedAvahiEntryGroup, ManagedAvahiEntryGroupParams, PublishFlags,
+};
+use super::poll::ManagedAvahiSimplePoll;
+use crate::error::{Error, ErrorKind};
+use crate::ffi::{FromRaw, SharedContext};
+use crate::prelude::*;
+use crate::{EventLoop, NetworkInterface, Result};
+use avahi_sys::{
+    AvahiClient, AvahiClientFlags, AvahiClientState, AvahiEntryGroup, AvahiEntryGroupState,
+    AvahiIfIndex,
+};
+use libc::c_void;
+use std::any::Any;
+use std::ffi::CString;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Callback invoked from an [`AvahiMdnsAddressPublisher`] once its address has been published,
+/// or could not be.
+///
+/// # Arguments
+/// * `result` - `Ok` once the address has been published, an error of kind
+///   `ErrorKind::NameConflict` if another host already publishes the host name
+/// * `context` - The optional user context passed through
+///
+/// [`AvahiMdnsAddressPublisher`]: struct.AvahiMdnsAddressPublisher.html
+pub type AddressPublishedCallback = dyn Fn(Result<()>, Option<Arc<dyn Any + Send + Sync>>) + Send;
+
+/// Publishes the `A` or `AAAA` record of a host name (and the matching reverse `PTR` record) with
+/// [`avahi_entry_group_add_address()`], e.g. for the devices a gateway or bridge fronts, and
+/// withdraws it when dropped.
+///
+/// Services of the host are then registered with `TMdnsService::set_host()`.
+///
+/// # Examples
+/// ```no_run
+/// use std::time::Duration;
+/// use zeroconf::linux::address_publisher::AvahiMdnsAddressPublisher;
+///
+/// let mut publisher =
+///     AvahiMdnsAddressPublisher::new("sensor.local", "192.168.1.20".parse().unwrap());
+/// publisher.set_address_published_callback(Box::new(|result, _| println!("{:?}", result)));
+///
+/// let event_loop = publisher.publish().unwrap();
+///
+/// loop {
+///     event_loop.poll(Duration::from_secs(0)).unwrap();
+/// }
+/// ```
+///
+/// [`avahi_entry_group_add_address()`]: https://avahi.org/doxygen/html/publish_8h.html
+#[derive(Debug)]
+pub struct AvahiMdnsAddressPublisher {
+    client: Option<ManagedAvahiClient>,
+    poll: Option<Arc<ManagedAvahiSimplePoll>>,
+    group: Option<ManagedAvahiEntryGroup>,
+    host_name: CString,
+    address: IpAddr,
+    interface_index: AvahiIfIndex,
+    publish_flags: PublishFlags,
+    context: SharedContext<AvahiAddressPublisherContext>,
+}
+
+impl AvahiMdnsAddressPublisher {
+    /// Creates a new `AvahiMdnsAddressPublisher` for the specified fully qualified `host_name`
+    /// (e.g. `sensor.local`) and `address`.
+    pub fn new(host_name: &str, address: IpAddr) -> Self {
+        Self {
+            client: None,
+            poll: None,
+            group: None,
+            host_name: c_string!(host_name),
+            address,
+            interface_index: avahi_sys::AVAHI_IF_UNSPEC,
+            publish_flags: PublishFlags::empty(),
+            context: SharedContext::default(),
+        }
+    }
+
+    /// Sets the network interface on which to publish the address.
+    ///
+    /// Most applications will want to use the default value `NetworkInterface::Unspec` to publish
+    /// on all available interfaces.
+    pub fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface_index = avahi_util::interface_index(interface);
+    }
+
+    /// Sets the Avahi-specific [`PublishFlags`] to publish the address with, e.g. `NO_REVERSE` to
+    /// skip the reverse `PTR` record.
+    ///
+    /// Returns an error of kind `ErrorKind::InvalidArgument` if the flags include any that Avahi
+    /// does not accept for addresses, i.e. any not in `PublishFlags::ADDRESS`.
+    ///
+    /// [`PublishFlags`]: ../entry_group/struct.PublishFlags.html
+    pub fn set_publish_flags(&mut self, publish_flags: PublishFlags) -> Result<()> {
+        self.publish_flags = publish_flags.check(PublishFlags::ADDRESS)?;
+        Ok(())
+    }
+
+    /// Sets the [`AddressPublishedCallback`] that is invoked once the address has been published.
+    ///
+    /// [`AddressPublishedCallback`]: type.AddressPublishedCallback.html
+    pub fn set_address_published_callback(
+        &mut self,
+        address_published_callback: Box<AddressPublishedCallback>,
+    ) {
+        unsafe {
+            (*self.context.as_ptr()).address_published_callback = Some(address_published_callback)
+        };
+    }
+
+    /// Sets the optional user context to pass through to the callback. This is useful if you need
+    /// to share state between pre and post-callback. The context type must implement `Any`, `Send`
+    /// and `Sync`.
+    pub fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        unsafe { (*self.context.as_ptr()).user_context = Some(Arc::from(context)) };
+    }
+
+    /// Publishes the address. Returns an `EventLoop` which can be called to keep the address
+    /// published.
+    pub fn publish(&mut self) -> Result<EventLoop<'_>> {
+        debug!("Publishing address: {:?}", self);
+
+        self.group = None;
+        self.poll = Some(ManagedAvahiSimplePoll::new_shared()?);
+
+        self.client = Some(ManagedAvahiClient::new(
+            ManagedAvahiClientParams::builder()
+                .poll(self.poll.as_ref().unwrap())
+                .flags(AvahiClientFlags(0))
+                .callback(Some(client_callback))
+                .userdata(self.context.as_ptr() as *mut c_void)
+                .build()?,
+        )?);
+
+        let mut group = ManagedAvahiEntryGroup::new(
+            ManagedAvahiEntryGroupParams::builder()
+                .client(self.client.as_ref().unwrap().inner())
+                .callback(Some(entry_group_callback))
+                .userdata(self.context.as_ptr() as *mut c_void)
+                .build()?,
+        )?;
+
+        let address = avahi_util::ip_to_avahi_address(&self.address);
+
+        group.add_address(
+            AddAddressParams::builder()
+                .interface(self.interface_index)
+                .protocol(avahi_sys::AVAHI_PROTO_UNSPEC)
+                .flags(self.publish_flags)
+                .name(self.host_name.as_ptr())
+                .address(&address)
+                .build()?,
+        )?;
+
+        self.group = Some(group);
+
+        Ok(EventLoop::new(self.poll.as_ref().unwrap().clone()))
+    }
+}
+
+impl Drop for AvahiMdnsAddressPublisher {
+    fn drop(&mut self) {
+        // group must be freed before the client
+        self.group = None;
+        self.client = None;
+    }
+}
+
+#[derive(Default, FromRaw)]
+struct AvahiAddressPublisherContext {
+    address_published_callback: Option<Box<AddressPublishedCallback>>,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl AvahiAddressPublisherContext {
+    fn invoke_callback(&self, result: Result<()>) {
+        if let Some(f) = &self.address_published_callback {
+            f(result, self.user_context.clone());
+        } else {
+            warn!("attempted to invoke address publisher callback but none was set");
+        }
+    }
+}
+
+impl fmt::Debug for AvahiAddressPublisherContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AvahiAddressPublisherContext").finish()
+    }
+}
+
+unsafe extern "C" fn entry_group_callback(
+    _group: *mut AvahiEntryGroup,
+    state: AvahiEntryGroupState,
+    userdata: *mut c_void,
+) {
+    let context = AvahiAddressPublisherContext::from_raw(userdata);
+
+    match state {
+        avahi_sys::AvahiEntryGroupState_AVAHI_ENTRY_GROUP_ESTABLISHED => {
+            debug!("Address published");
+            context.invoke_callback(Ok(()));
+        }
+        avahi_sys::AvahiEntryGroupState_AVAHI_ENTRY_GROUP_COLLISION => {
+            context.invoke_callback(Err(Error::with_kind(
+                ErrorKind::NameConflict,
+                "host name conflicts with another host".to_string(),
+            )));
+        }
+        avahi_sys::AvahiEntryGroupState_AVAHI_ENTRY_GROUP_FAILURE => {
+            context.invoke_callback(Err("could not publish address".into()));
+        }
+        _ => {}
+    }
+}
+
+unsafe extern "C" fn client_callback(
+    _client: *mut AvahiClient,
+    state: AvahiClientState,
+    userdata: *mut c_void,
+) {
+    let context = AvahiAddressPublisherContext::from_raw(userdata);
+
+    if state == avahi_sys::AvahiClientState_AVAHI_CLIENT_FAILURE {
+        context.invoke_callback(Err("client failure".into()));
+    }
+}
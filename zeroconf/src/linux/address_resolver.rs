@@ -0,0 +1,178 @@
+//! Avahi implementation for cross-platform address resolver
+
+use super::avahi_util;
+use super::client::{ManagedAvahiClient, ManagedAvahiClientParams};
+use super::poll::ManagedAvahiSimplePoll;
+use super::resolver::{ManagedAvahiAddressResolver, ManagedAvahiAddressResolverParams};
+use crate::error::Error;
+use crate::ffi::{c_str, FromRaw, SharedContext};
+use crate::prelude::*;
+use crate::{AddressFamily, EventLoop, NetworkInterface, Result, ServiceFlags};
+use crate::{AddressResolution, AddressResolvedCallback};
+use avahi_sys::{
+    AvahiAddress, AvahiAddressResolver, AvahiClient, AvahiClientFlags, AvahiClientState,
+    AvahiIfIndex, AvahiLookupResultFlags, AvahiProtocol, AvahiResolverEvent,
+};
+use libc::{c_char, c_void};
+use std::any::Any;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub struct AvahiMdnsAddressResolver {
+    client: Option<ManagedAvahiClient>,
+    poll: Option<Arc<ManagedAvahiSimplePoll>>,
+    resolver: Option<ManagedAvahiAddressResolver>,
+    address: IpAddr,
+    interface_index: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    context: SharedContext<AvahiAddressResolverContext>,
+}
+
+impl TAddressResolver for AvahiMdnsAddressResolver {
+    fn new(address: IpAddr) -> Self {
+        Self {
+            client: None,
+            poll: None,
+            resolver: None,
+            address,
+            interface_index: avahi_sys::AVAHI_IF_UNSPEC,
+            protocol: avahi_sys::AVAHI_PROTO_UNSPEC,
+            context: SharedContext::default(),
+        }
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface_index = avahi_util::interface_index(interface);
+    }
+
+    fn set_address_resolved_callback(
+        &mut self,
+        address_resolved_callback: Box<AddressResolvedCallback>,
+    ) {
+        unsafe {
+            (*self.context.as_ptr()).address_resolved_callback = Some(address_resolved_callback)
+        };
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        unsafe { (*self.context.as_ptr()).user_context = Some(Arc::from(context)) };
+    }
+
+    fn resolve(&mut self) -> Result<EventLoop<'_>> {
+        debug!("Resolving address: {:?}", self);
+
+        self.poll = Some(ManagedAvahiSimplePoll::new_shared()?);
+
+        self.client = Some(ManagedAvahiClient::new(
+            ManagedAvahiClientParams::builder()
+                .poll(self.poll.as_ref().unwrap())
+                .flags(AvahiClientFlags(0))
+                .callback(Some(client_callback))
+                .userdata(self.context.as_ptr() as *mut c_void)
+                .build()?,
+        )?);
+
+        let address = avahi_util::ip_to_avahi_address(&self.address);
+
+        self.resolver = Some(ManagedAvahiAddressResolver::new(
+            ManagedAvahiAddressResolverParams::builder()
+                .client(self.client.as_ref().unwrap())
+                .interface(self.interface_index)
+                .protocol(self.protocol)
+                .address(&address)
+                .flags(ServiceFlags::empty())
+                .callback(Some(resolve_callback))
+                .userdata(self.context.as_ptr() as *mut c_void)
+                .build()?,
+        )?);
+
+        Ok(EventLoop::new(self.poll.as_ref().unwrap().clone()))
+    }
+}
+
+impl AvahiMdnsAddressResolver {
+    /// Sets the IP protocol the mDNS queries of the resolver are sent over, e.g.
+    /// `AddressFamily::Ipv6` to skip the IPv4 queries on IPv6-only networks. Defaults to
+    /// `AddressFamily::Both`.
+    pub fn set_protocol(&mut self, protocol: AddressFamily) {
+        self.protocol = avahi_util::protocol(protocol);
+    }
+}
+
+impl Drop for AvahiMdnsAddressResolver {
+    fn drop(&mut self) {
+        // resolver must be freed before the client
+        self.resolver = None;
+        self.client = None;
+    }
+}
+
+#[derive(Default, FromRaw)]
+struct AvahiAddressResolverContext {
+    address_resolved_callback: Option<Box<AddressResolvedCallback>>,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl AvahiAddressResolverContext {
+    fn invoke_callback(&self, result: Result<AddressResolution>) {
+        if let Some(f) = &self.address_resolved_callback {
+            f(result, self.user_context.clone());
+        } else {
+            warn!("attempted to invoke address resolver callback but none was set");
+        }
+    }
+}
+
+impl fmt::Debug for AvahiAddressResolverContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AvahiAddressResolverContext").finish()
+    }
+}
+
+unsafe extern "C" fn resolve_callback(
+    _resolver: *mut AvahiAddressResolver,
+    _interface: AvahiIfIndex,
+    _protocol: AvahiProtocol,
+    event: AvahiResolverEvent,
+    addr: *const AvahiAddress,
+    name: *const c_char,
+    _flags: AvahiLookupResultFlags,
+    userdata: *mut c_void,
+) {
+    let context = AvahiAddressResolverContext::from_raw(userdata);
+    let address = avahi_util::avahi_address_to_ip(addr);
+
+    match event {
+        avahi_sys::AvahiResolverEvent_AVAHI_RESOLVER_FAILURE => {
+            context.invoke_callback(Err(
+                format!("failed to resolve address `{}`", address).into()
+            ));
+        }
+        avahi_sys::AvahiResolverEvent_AVAHI_RESOLVER_FOUND => {
+            let result = AddressResolution::builder()
+                .address(address)
+                .host_name(c_str::copy_raw(name))
+                .build()
+                .map_err(Error::from);
+
+            debug!("Address resolved: {:?}", result);
+
+            context.invoke_callback(result);
+        }
+        _ => {}
+    };
+}
+
+unsafe extern "C" fn client_callback(
+    _client: *mut AvahiClient,
+    state: AvahiClientState,
+    userdata: *mut c_void,
+) {
+    let context = AvahiAddressResolverContext::from_raw(userdata);
+
+    if state == avahi_sys::AvahiClientState_AVAHI_CLIENT_FAILURE {
+        context.invoke_callback(Err("client failure".into()));
+    }
+}
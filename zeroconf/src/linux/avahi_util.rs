@@ -1,31 +1,113 @@
 //! Utilities related to Avahi
 
-use crate::NetworkInterface;
-use avahi_sys::{avahi_address_snprint, avahi_strerror, AvahiAddress};
-use libc::c_char;
-use std::ffi::CStr;
+use crate::error::{Error, ErrorKind};
+use crate::{AddressFamily, NetworkInterface, ServiceFlags};
+use avahi_sys::{
+    avahi_alternative_service_name, avahi_free, avahi_strerror, AvahiAddress,
+    AvahiAddress__bindgen_ty_1, AvahiIPv4Address, AvahiIPv6Address, AvahiLookupFlags,
+    AvahiProtocol, AvahiPublishFlags,
+};
+use libc::c_void;
+use std::ffi::{CStr, CString};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::slice;
 
-/// Converts the specified `*const AvahiAddress` to a `String`.
+/// Converts the specified `*const AvahiAddress` to an `IpAddr`.
 ///
-/// The new `String` is constructed through allocating a new `CString`, passing it to
-/// `avahi_address_snprint` and then converting it to a Rust-type `String`.
+/// The conversion is done in pure Rust from the raw address bytes and is therefore thread-safe.
 ///
 /// # Safety
-/// This function is unsafe because of internal Avahi calls and raw pointer dereference.
-pub unsafe fn avahi_address_to_string(addr: *const AvahiAddress) -> String {
+/// This function is unsafe because of the raw pointer dereference.
+pub unsafe fn avahi_address_to_ip(addr: *const AvahiAddress) -> IpAddr {
     assert_not_null!(addr);
 
-    let addr_str = c_string!(alloc(avahi_sys::AVAHI_ADDRESS_STR_MAX as usize));
+    let addr = &*addr;
+
+    if addr.proto == avahi_sys::AVAHI_PROTO_INET {
+        // stored in network byte order
+        Ipv4Addr::from(u32::from_be(addr.data.ipv4.address)).into()
+    } else {
+        Ipv6Addr::from(addr.data.ipv6.address).into()
+    }
+}
+
+/// Converts the specified `IpAddr` to an `AvahiAddress`.
+pub fn ip_to_avahi_address(ip: &IpAddr) -> AvahiAddress {
+    match ip {
+        IpAddr::V4(ip) => AvahiAddress {
+            proto: avahi_sys::AVAHI_PROTO_INET,
+            data: AvahiAddress__bindgen_ty_1 {
+                ipv4: AvahiIPv4Address {
+                    // stored in network byte order
+                    address: u32::from(*ip).to_be(),
+                },
+            },
+        },
+        IpAddr::V6(ip) => AvahiAddress {
+            proto: avahi_sys::AVAHI_PROTO_INET6,
+            data: AvahiAddress__bindgen_ty_1 {
+                ipv6: AvahiIPv6Address {
+                    address: ip.octets(),
+                },
+            },
+        },
+    }
+}
+
+/// Converts the specified `*const AvahiAddress` to a `String`.
+///
+/// # Safety
+/// This function is unsafe because of the raw pointer dereference.
+pub unsafe fn avahi_address_to_string(addr: *const AvahiAddress) -> String {
+    avahi_address_to_ip(addr).to_string()
+}
+
+/// Returns the `size` bytes of record data at `rdata` that Avahi passes to record browser
+/// callbacks, or an empty slice if `rdata` is null or `size` is zero.
+///
+/// # Safety
+/// This function is unsafe because of the raw pointer dereference. A non-null `rdata` is expected
+/// to point to at least `size` bytes that outlive the returned slice.
+pub unsafe fn rdata<'a>(rdata: *const c_void, size: usize) -> &'a [u8] {
+    if rdata.is_null() || size == 0 {
+        &[]
+    } else {
+        slice::from_raw_parts(rdata as *const u8, size)
+    }
+}
 
-    avahi_address_snprint(
-        addr_str.as_ptr() as *mut c_char,
-        avahi_sys::AVAHI_ADDRESS_STR_MAX as usize,
-        addr,
-    );
+/// Returns an [`Error`] with the specified `description` of the error `code` reported by Avahi.
+///
+/// Name collisions, timeouts and a missing or disconnected daemon are of kind
+/// `ErrorKind::NameConflict`, `ErrorKind::Timeout` and `ErrorKind::DaemonNotRunning`,
+/// respectively. Invalid names, types and arguments are of kind `ErrorKind::InvalidArgument`.
+///
+/// [`Error`]: ../../error/struct.Error.html
+pub fn error(code: i32, description: String) -> Error {
+    let kind = match code {
+        avahi_sys::AVAHI_ERR_COLLISION => ErrorKind::NameConflict,
+        avahi_sys::AVAHI_ERR_TIMEOUT => ErrorKind::Timeout,
+        avahi_sys::AVAHI_ERR_NO_DAEMON | avahi_sys::AVAHI_ERR_DISCONNECTED => {
+            ErrorKind::DaemonNotRunning
+        }
+        avahi_sys::AVAHI_ERR_INVALID_HOST_NAME
+        | avahi_sys::AVAHI_ERR_INVALID_DOMAIN_NAME
+        | avahi_sys::AVAHI_ERR_INVALID_TTL
+        | avahi_sys::AVAHI_ERR_INVALID_RECORD
+        | avahi_sys::AVAHI_ERR_INVALID_SERVICE_NAME
+        | avahi_sys::AVAHI_ERR_INVALID_SERVICE_TYPE
+        | avahi_sys::AVAHI_ERR_INVALID_PORT
+        | avahi_sys::AVAHI_ERR_INVALID_KEY
+        | avahi_sys::AVAHI_ERR_INVALID_ADDRESS
+        | avahi_sys::AVAHI_ERR_INVALID_INTERFACE
+        | avahi_sys::AVAHI_ERR_INVALID_PROTOCOL
+        | avahi_sys::AVAHI_ERR_INVALID_FLAGS
+        | avahi_sys::AVAHI_ERR_INVALID_SERVICE_SUBTYPE
+        | avahi_sys::AVAHI_ERR_INVALID_ARGUMENT => ErrorKind::InvalidArgument,
+        _ => ErrorKind::Other,
+    };
 
-    String::from(addr_str.to_str().unwrap())
-        .trim_matches(char::from(0))
-        .to_string()
+    Error::with_kind(kind, description).with_code(code)
 }
 
 /// Returns the `&str` message associated with the specified error code.
@@ -37,29 +119,177 @@ pub fn get_error<'a>(code: i32) -> &'a str {
     }
 }
 
+/// Returns the name Avahi suggests to register a service under after `name` has conflicted with
+/// another service (e.g. `name #2` for `name`).
+pub fn alternative_service_name(name: &CStr) -> CString {
+    unsafe {
+        let alternative = avahi_alternative_service_name(name.as_ptr());
+        let result = CStr::from_ptr(alternative).to_owned();
+        avahi_free(alternative as *mut c_void);
+        result
+    }
+}
+
 /// Converts the specified [`NetworkInterface`] to the Avahi expected value.
 ///
 /// [`NetworkInterface`]: ../../enum.NetworkInterface.html
 pub fn interface_index(interface: NetworkInterface) -> i32 {
     match interface {
-        NetworkInterface::Unspec => avahi_sys::AVAHI_IF_UNSPEC,
+        NetworkInterface::Unspec | NetworkInterface::PeerToPeer => avahi_sys::AVAHI_IF_UNSPEC,
         NetworkInterface::AtIndex(i) => i as i32,
     }
 }
 
+/// Converts the specified [`AddressFamily`] to the Avahi expected value.
+///
+/// [`AddressFamily`]: ../../enum.AddressFamily.html
+pub fn protocol(address_family: AddressFamily) -> AvahiProtocol {
+    match address_family {
+        AddressFamily::Ipv4 => avahi_sys::AVAHI_PROTO_INET,
+        AddressFamily::Ipv6 => avahi_sys::AVAHI_PROTO_INET6,
+        AddressFamily::Both => avahi_sys::AVAHI_PROTO_UNSPEC,
+    }
+}
+
+/// Converts the specified [`ServiceFlags`] to the Avahi expected value for publishing services
+/// and updating their records.
+///
+/// `ServiceFlags::UNIQUE` is left out, Avahi rejects it for services.
+///
+/// [`ServiceFlags`]: ../../flags/struct.ServiceFlags.html
+pub fn publish_flags(flags: ServiceFlags) -> AvahiPublishFlags {
+    let mut publish_flags = 0;
+
+    if flags.contains(ServiceFlags::FORCE_MULTICAST) {
+        publish_flags |= avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_USE_MULTICAST;
+    }
+
+    publish_flags
+}
+
+/// Converts the specified [`ServiceFlags`] to the Avahi expected value for browsing and
+/// resolving services.
+///
+/// [`ServiceFlags`]: ../../flags/struct.ServiceFlags.html
+pub fn lookup_flags(flags: ServiceFlags) -> AvahiLookupFlags {
+    let mut lookup_flags = 0;
+
+    if flags.contains(ServiceFlags::FORCE_MULTICAST) {
+        lookup_flags |= avahi_sys::AvahiLookupFlags_AVAHI_LOOKUP_USE_MULTICAST;
+    }
+
+    if flags.contains(ServiceFlags::WIDE_AREA) {
+        lookup_flags |= avahi_sys::AvahiLookupFlags_AVAHI_LOOKUP_USE_WIDE_AREA;
+    }
+
+    lookup_flags
+}
+
+/// Converts the specified [`ServiceFlags`] to the Avahi expected value for resolving services,
+/// which unlike browsing may skip looking up addresses (`ServiceFlags::NO_ADDRESS`).
+/// `AVAHI_LOOKUP_NO_TXT` has no equivalent, as discovered services are always reported with
+/// their TXT record.
+///
+/// [`ServiceFlags`]: ../../flags/struct.ServiceFlags.html
+pub fn resolve_flags(flags: ServiceFlags) -> AvahiLookupFlags {
+    let mut resolve_flags = lookup_flags(flags);
+
+    if flags.contains(ServiceFlags::NO_ADDRESS) {
+        resolve_flags |= avahi_sys::AvahiLookupFlags_AVAHI_LOOKUP_NO_ADDRESS;
+    }
+
+    resolve_flags
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use avahi_sys::{
-        AvahiAddress__bindgen_ty_1, AvahiIPv4Address, AvahiIPv6Address, AVAHI_PROTO_INET,
-        AVAHI_PROTO_INET6,
-    };
+    use avahi_sys::{AVAHI_PROTO_INET, AVAHI_PROTO_INET6};
 
     #[test]
     fn get_error_returns_valid_error_string() {
         assert_eq!(get_error(avahi_sys::AVAHI_ERR_FAILURE), "Operation failed");
     }
 
+    #[test]
+    fn error_carries_kind_and_code() {
+        let error = error(avahi_sys::AVAHI_ERR_COLLISION, "collision".to_string());
+
+        assert_eq!(error.kind(), ErrorKind::NameConflict);
+        assert_eq!(error.code(), Some(avahi_sys::AVAHI_ERR_COLLISION));
+    }
+
+    #[test]
+    fn rdata_of_null_pointer_is_empty() {
+        assert!(unsafe { rdata(std::ptr::null(), 4) }.is_empty());
+    }
+
+    #[test]
+    fn rdata_copies_size_bytes() {
+        let bytes = [1u8, 2, 3, 4];
+        let rdata = unsafe { rdata(bytes.as_ptr() as *const c_void, 3) };
+
+        assert_eq!(rdata, &[1, 2, 3]);
+    }
+
+    #[test]
+    fn alternative_service_name_appends_counter() {
+        let name = alternative_service_name(&c_string!("foo"));
+        assert_eq!(name.to_str().unwrap(), "foo #2");
+
+        let name = alternative_service_name(&name);
+        assert_eq!(name.to_str().unwrap(), "foo #3");
+    }
+
+    #[test]
+    fn publish_flags_ignores_unsupported_flags() {
+        let flags =
+            ServiceFlags::FORCE_MULTICAST | ServiceFlags::NO_AUTO_RENAME | ServiceFlags::UNIQUE;
+        assert_eq!(
+            publish_flags(flags),
+            avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_USE_MULTICAST
+        );
+    }
+
+    #[test]
+    fn lookup_flags_returns_zero_for_empty_flags() {
+        assert_eq!(lookup_flags(ServiceFlags::empty()), 0);
+    }
+
+    #[test]
+    fn resolve_flags_skips_addresses_unlike_lookup_flags() {
+        let flags = ServiceFlags::FORCE_MULTICAST | ServiceFlags::NO_ADDRESS;
+
+        assert_eq!(
+            lookup_flags(flags),
+            avahi_sys::AvahiLookupFlags_AVAHI_LOOKUP_USE_MULTICAST
+        );
+        assert_eq!(
+            resolve_flags(flags),
+            avahi_sys::AvahiLookupFlags_AVAHI_LOOKUP_USE_MULTICAST
+                | avahi_sys::AvahiLookupFlags_AVAHI_LOOKUP_NO_ADDRESS
+        );
+    }
+
+    #[test]
+    fn address_to_ip_returns_correct_ipv4_addr() {
+        let ipv4_addr = AvahiAddress {
+            proto: AVAHI_PROTO_INET,
+            data: AvahiAddress__bindgen_ty_1 {
+                ipv4: AvahiIPv4Address {
+                    address: u32::from_be_bytes([192, 168, 100, 100]).to_be(),
+                },
+            },
+        };
+
+        unsafe {
+            assert_eq!(
+                avahi_address_to_ip(&ipv4_addr),
+                IpAddr::V4(Ipv4Addr::new(192, 168, 100, 100))
+            );
+        }
+    }
+
     #[test]
     fn address_to_string_returns_correct_ipv4_string() {
         let ipv4_addr = AvahiAddress {
@@ -97,4 +327,15 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn ip_to_avahi_address_round_trips() {
+        let ipv4: IpAddr = "192.168.100.100".parse().unwrap();
+        let ipv6: IpAddr = "fe80::1234:5678:9abc:def0".parse().unwrap();
+
+        unsafe {
+            assert_eq!(avahi_address_to_ip(&ip_to_avahi_address(&ipv4)), ipv4);
+            assert_eq!(avahi_address_to_ip(&ip_to_avahi_address(&ipv6)), ipv6);
+        }
+    }
 }
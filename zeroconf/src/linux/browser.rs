@@ -1,44 +1,64 @@
 //! Avahi implementation for cross-platform browser
 
 use super::avahi_util;
-use super::client::{ManagedAvahiClient, ManagedAvahiClientParams};
+use super::client::{self, ClientSubscription, ManagedAvahiClient, ReconnectState};
 use super::poll::ManagedAvahiSimplePoll;
-use super::raw_browser::{ManagedAvahiServiceBrowser, ManagedAvahiServiceBrowserParams};
+use super::raw_browser::{
+    ManagedAvahiRecordBrowser, ManagedAvahiRecordBrowserParams, ManagedAvahiServiceBrowser,
+    ManagedAvahiServiceBrowserParams,
+};
 use super::{
     resolver::{
         ManagedAvahiServiceResolver, ManagedAvahiServiceResolverParams, ServiceResolverSet,
     },
     string_list::ManagedAvahiStringList,
 };
-use crate::ffi::{c_str, AsRaw, FromRaw};
+use crate::browser::ServiceDiscoveryBuilder;
+use crate::debounce::Debouncer;
+use crate::error::Error;
+use crate::ffi::{self, c_str, AsRaw, FromRaw, SharedContext, UnwrapOrNull};
 use crate::prelude::*;
+use crate::resolve_queue::ResolveQueue;
 use crate::Result;
 use crate::{
-    EventLoop, NetworkInterface, ServiceDiscoveredCallback, ServiceDiscovery, ServiceType,
-    TxtRecord,
+    dns, AddressFamily, AddressPolicy, BrowserControl, BrowserEvent, DaemonReconnectedCallback,
+    ErrorCallback, EventLoop, EventMeta, NetworkInterface, Record, ServiceBatchCallback,
+    ServiceDiscoveredCallback, ServiceDiscovery, ServiceFlags, ServiceType, TxtRecord,
 };
 use avahi_sys::{
     AvahiAddress, AvahiBrowserEvent, AvahiClient, AvahiClientFlags, AvahiClientState, AvahiIfIndex,
-    AvahiLookupResultFlags, AvahiProtocol, AvahiResolverEvent, AvahiServiceBrowser,
-    AvahiServiceResolver, AvahiStringList,
+    AvahiLookupResultFlags, AvahiProtocol, AvahiRecordBrowser, AvahiResolverEvent,
+    AvahiServiceBrowser, AvahiServiceResolver, AvahiStringList,
 };
 use libc::{c_char, c_void};
 use std::any::Any;
-use std::ffi::CString;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::net::Ipv4Addr;
+use std::ptr;
 use std::str::FromStr;
 use std::sync::Arc;
-use std::{fmt, ptr};
+use std::time::Duration;
+use std::{fmt, mem};
 
 #[derive(Debug)]
 pub struct AvahiMdnsBrowser {
-    client: Option<Arc<ManagedAvahiClient>>,
+    client: Option<ClientSubscription>,
     poll: Option<Arc<ManagedAvahiSimplePoll>>,
     browser: Option<ManagedAvahiServiceBrowser>,
     kind: CString,
+    domain: Option<CString>,
     interface_index: AvahiIfIndex,
-    context: *mut AvahiBrowserContext,
+    context: SharedContext<AvahiBrowserContext>,
 }
 
+// SAFETY: the browser exclusively owns its context and the Avahi objects, which are only used
+// through `&mut self` or the event loop borrowing it, and the callbacks and the context of
+// the user it holds are `Send`. The objects of a client shared by the thread are freed with the
+// event loop, which is not `Send`.
+unsafe impl Send for AvahiMdnsBrowser {}
+
 impl TMdnsBrowser for AvahiMdnsBrowser {
     fn new(service_type: ServiceType) -> Self {
         Self {
@@ -46,7 +66,8 @@ impl TMdnsBrowser for AvahiMdnsBrowser {
             poll: None,
             browser: None,
             kind: c_string!(service_type.to_string()),
-            context: Box::into_raw(Box::default()),
+            domain: None,
+            context: SharedContext::default(),
             interface_index: avahi_sys::AVAHI_IF_UNSPEC,
         }
     }
@@ -55,74 +76,297 @@ impl TMdnsBrowser for AvahiMdnsBrowser {
         self.interface_index = avahi_util::interface_index(interface);
     }
 
+    fn set_domain(&mut self, domain: &str) {
+        self.domain = Some(c_string!(domain));
+    }
+
+    fn set_flags(&mut self, flags: ServiceFlags) {
+        unsafe { (*self.context.as_ptr()).flags = flags };
+    }
+
+    fn set_address_family(&mut self, address_family: AddressFamily) {
+        unsafe { (*self.context.as_ptr()).address_family = address_family };
+    }
+
+    fn set_address_policy(&mut self, _address_policy: AddressPolicy) {
+        // Avahi only resolves a single address per service
+    }
+
+    fn set_include_raw_records(&mut self, include_raw_records: bool) {
+        unsafe { (*self.context.as_ptr()).include_raw_records = include_raw_records };
+    }
+
+    fn set_resolve_concurrency(&mut self, concurrency: usize) {
+        unsafe {
+            (*self.context.as_ptr())
+                .resolve_queue
+                .set_concurrency(concurrency)
+        };
+    }
+
+    fn set_debounce(&mut self, window: Duration) {
+        unsafe { (*self.context.as_ptr()).debouncer.set_window(window) };
+    }
+
     fn set_service_discovered_callback(
         &mut self,
         service_discovered_callback: Box<ServiceDiscoveredCallback>,
     ) {
-        unsafe { (*self.context).service_discovered_callback = Some(service_discovered_callback) };
+        let context = unsafe { &mut *self.context.as_ptr() };
+        context.service_discovered_callback = Some(service_discovered_callback);
+        context.service_batch_callback = None;
+    }
+
+    fn set_service_batch_callback(&mut self, service_batch_callback: Box<ServiceBatchCallback>) {
+        let context = unsafe { &mut *self.context.as_ptr() };
+        context.service_batch_callback = Some(service_batch_callback);
+        context.service_discovered_callback = None;
+    }
+
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>) {
+        unsafe { (*self.context.as_ptr()).error_callback = Some(error_callback) };
+    }
+
+    fn control(&self) -> BrowserControl {
+        unsafe { (*self.context.as_ptr()).control.clone() }
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        unsafe { (*self.context.as_ptr()).user_context = Some(Arc::from(context)) };
     }
 
-    fn set_context(&mut self, context: Box<dyn Any>) {
-        unsafe { (*self.context).user_context = Some(Arc::from(context)) };
+    fn set_reconnected_callback(&mut self, reconnected_callback: Box<DaemonReconnectedCallback>) {
+        unsafe { (*self.context.as_ptr()).reconnected_callback = Some(reconnected_callback) };
     }
 
-    fn browse_services(&mut self) -> Result<EventLoop> {
+    fn browse_services(&mut self) -> Result<EventLoop<'_>> {
+        let _span = operation_span!("browse", service_type = self.kind.to_string_lossy());
+
         debug!("Browsing services: {:?}", self);
 
-        self.poll = Some(Arc::new(ManagedAvahiSimplePoll::new()?));
+        unsafe { self.release() };
+        self.poll = None;
+
+        self.create_client(AvahiClientFlags(0))?;
+        self.create_browser()?;
+
+        let browser: *mut Self = self;
+
+        Ok(EventLoop::new(self.poll.as_ref().unwrap().clone())
+            .with_on_poll(Box::new(move || unsafe { (*browser).handle_poll() }))
+            .with_on_drop(Box::new(move || unsafe { (*browser).release_shared() })))
+    }
+
+    fn close(mut self) -> Result<()> {
+        let result = self
+            .browser
+            .take()
+            .map_or(Ok(()), ManagedAvahiServiceBrowser::free);
+
+        let context = unsafe { &mut *self.context.as_ptr() };
+
+        // the resolvers must be freed before the client
+        context.resolvers.clear();
+        context.srv_queries.clear();
+        context.resolve_queue.clear();
+        context.client = None;
+        self.client = None;
+        self.poll = None;
+
+        result
+    }
+}
+
+impl AvahiMdnsBrowser {
+    /// Returns the underlying `AvahiClient`, or a null pointer if the browser has not been
+    /// started yet, to call functions of `libavahi-client` this crate does not wrap.
+    ///
+    /// # Safety
+    /// The client is owned by this browser, or shared with the other browsers and services of the
+    /// thread if client sharing is enabled, it must not be freed and its callback must not be
+    /// replaced. The pointer is no longer valid once the browser is dropped or has reconnected to
+    /// `avahi-daemon`, or once the event loop is dropped if the client is shared.
+    pub unsafe fn as_raw(&self) -> *mut AvahiClient {
+        self.client
+            .as_ref()
+            .map(|c| c.client().inner())
+            .unwrap_or(ptr::null_mut())
+    }
+
+    /// Sets the IP protocol the mDNS queries of the browser are sent over, e.g.
+    /// `AddressFamily::Ipv6` to skip the IPv4 queries on IPv6-only networks. Defaults to
+    /// `AddressFamily::Both`.
+    ///
+    /// Unlike `set_address_family()`, which selects the addresses services are resolved to, this
+    /// selects the transport services are browsed and resolved over.
+    pub fn set_protocol(&mut self, protocol: AddressFamily) {
+        unsafe { (*self.context.as_ptr()).protocol = avahi_util::protocol(protocol) };
+    }
+
+    fn create_client(&mut self, flags: AvahiClientFlags) -> Result<()> {
+        let client = ClientSubscription::new(
+            self.poll.as_ref(),
+            flags,
+            Some(client_callback),
+            self.context.as_ptr() as *mut c_void,
+        )?;
+
+        self.poll = Some(client.poll().clone());
+        unsafe { (*self.context.as_ptr()).client = Some(client.client().clone()) };
+        self.client = Some(client);
+
+        Ok(())
+    }
 
-        self.client = Some(Arc::new(ManagedAvahiClient::new(
-            ManagedAvahiClientParams::builder()
-                .poll(self.poll.as_ref().unwrap())
-                .flags(AvahiClientFlags(0))
-                .callback(Some(client_callback))
-                .userdata(ptr::null_mut())
+    fn create_browser(&mut self) -> Result<()> {
+        unsafe { (*self.context.as_ptr()).all_for_now = false };
+
+        self.browser = Some(ManagedAvahiServiceBrowser::new(
+            ManagedAvahiServiceBrowserParams::builder()
+                .client(self.client.as_ref().unwrap().client())
+                .interface(self.interface_index)
+                .protocol(unsafe { (*self.context.as_ptr()).protocol })
+                .kind(self.kind.as_ptr())
+                .domain(self.domain.as_ref().map(|d| d.as_ptr()).unwrap_or_null())
+                .flags(unsafe { (*self.context.as_ptr()).flags })
+                .callback(Some(browse_callback))
+                .userdata(self.context.as_ptr() as *mut c_void)
                 .build()?,
-        )?));
+        )?);
 
-        unsafe {
-            (*self.context).client = self.client.clone();
-
-            self.browser = Some(ManagedAvahiServiceBrowser::new(
-                ManagedAvahiServiceBrowserParams::builder()
-                    .client((*self.context).client.as_ref().unwrap())
-                    .interface(self.interface_index)
-                    .protocol(avahi_sys::AVAHI_PROTO_UNSPEC)
-                    .kind(self.kind.as_ptr())
-                    .domain(ptr::null_mut())
-                    .flags(0)
-                    .callback(Some(browse_callback))
-                    .userdata(self.context as *mut c_void)
-                    .build()?,
-            )?);
+        Ok(())
+    }
+
+    unsafe fn handle_poll(&mut self) {
+        let context = &mut *self.context.as_ptr();
+
+        match context.reconnect {
+            ReconnectState::Pending => {
+                debug!("Reconnecting to avahi-daemon");
+
+                self.release();
+
+                // the client waits for the daemon to become available instead of failing
+                context.reconnect = ReconnectState::Connecting;
+
+                if let Err(e) = self.create_client(AvahiClientFlags::AVAHI_CLIENT_NO_FAIL) {
+                    context.reconnect = ReconnectState::Idle;
+                    context.invoke_callback(Err(e));
+                }
+            }
+            ReconnectState::Connected => {
+                context.reconnect = ReconnectState::Idle;
+
+                match self.create_browser() {
+                    Ok(()) => context.invoke_reconnected_callback(),
+                    Err(e) => context.invoke_callback(Err(e)),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Frees the Avahi objects of the browser if its client is shared by the thread.
+    unsafe fn release_shared(&mut self) {
+        if matches!(&self.client, Some(c) if c.is_shared()) {
+            self.release();
         }
+    }
 
-        Ok(EventLoop::new(self.poll.as_ref().unwrap().clone()))
+    unsafe fn release(&mut self) {
+        let context = &mut *self.context.as_ptr();
+
+        // the browser and resolvers must be freed before the client
+        self.browser = None;
+        context.resolvers.clear();
+        context.srv_queries.clear();
+        context.resolve_queue.clear();
+        context.client = None;
+        self.client = None;
+
+        // the services of the burst that were not resolved yet are not coming anymore
+        context.all_for_now = true;
+        context.end_burst_if_idle();
     }
 }
 
 impl Drop for AvahiMdnsBrowser {
     fn drop(&mut self) {
-        unsafe { Box::from_raw(self.context) };
-        // browser must be freed first
+        let context = unsafe { &mut *self.context.as_ptr() };
+
+        // the browser and resolvers must be freed before the client, the context is dropped last
         self.browser = None;
+        context.resolvers.clear();
+        context.srv_queries.clear();
     }
 }
 
 #[derive(FromRaw, AsRaw)]
-struct AvahiBrowserContext {
-    client: Option<Arc<ManagedAvahiClient>>,
-    resolvers: ServiceResolverSet,
-    service_discovered_callback: Option<Box<ServiceDiscoveredCallback>>,
-    user_context: Option<Arc<dyn Any>>,
+pub(super) struct AvahiBrowserContext {
+    pub(super) client: Option<Arc<ManagedAvahiClient>>,
+    pub(super) resolvers: ServiceResolverSet,
+    resolve_queue: ResolveQueue<DiscoveredService>,
+    debouncer: Debouncer<DiscoveredService>,
+    pub(super) srv_queries: SrvQuerySet,
+    pub(super) service_discovered_callback: Option<Box<ServiceDiscoveredCallback>>,
+    service_batch_callback: Option<Box<ServiceBatchCallback>>,
+    /// The events of the current burst, delivered to the batch callback by `end_burst_if_idle()`
+    batch: RefCell<Vec<BrowserEvent>>,
+    /// Whether the browser has reported all of the services cached by the daemon
+    all_for_now: bool,
+    error_callback: Option<Box<ErrorCallback>>,
+    control: BrowserControl,
+    reconnected_callback: Option<Box<DaemonReconnectedCallback>>,
+    pub(super) user_context: Option<Arc<dyn Any + Send + Sync>>,
+    flags: ServiceFlags,
+    pub(super) address_family: AddressFamily,
+    /// The IP protocol services are browsed and resolved over
+    pub(super) protocol: AvahiProtocol,
+    pub(super) include_raw_records: bool,
+    reconnect: ReconnectState,
+    /// Reused for the NUL terminated full name of each service whose SRV record is queried
+    full_name: String,
 }
 
 impl AvahiBrowserContext {
-    fn invoke_callback(&self, result: Result<ServiceDiscovery>) {
-        if let Some(f) = &self.service_discovered_callback {
-            f(result, self.user_context.clone());
-        } else {
-            panic!("attempted to invoke browser callback but none was set");
+    pub(super) fn invoke_callback(&self, result: Result<ServiceDiscovery>) {
+        if !self.control.is_running() {
+            return;
+        }
+
+        match (
+            result,
+            &self.error_callback,
+            &self.service_discovered_callback,
+        ) {
+            (Err(e), Some(f), _) => f(e, self.user_context.clone()),
+            (result, _, _) if self.service_batch_callback.is_some() => {
+                self.batch.borrow_mut().push(BrowserEvent::from(result))
+            }
+            (result, _, Some(f)) => f(result, self.user_context.clone()),
+            _ => warn!("attempted to invoke browser callback but none was set"),
+        }
+    }
+
+    /// Delivers the events buffered during the current burst to the batch callback once the
+    /// browser has reported all of the services cached by the daemon and none of the services
+    /// are being resolved anymore.
+    fn end_burst_if_idle(&self) {
+        if !self.all_for_now || !self.resolvers.is_empty() || !self.srv_queries.is_empty() {
+            return;
+        }
+
+        let events = mem::take(&mut *self.batch.borrow_mut());
+
+        match &self.service_batch_callback {
+            Some(f) if !events.is_empty() => f(events, self.user_context.clone()),
+            _ => {}
+        }
+    }
+
+    fn invoke_reconnected_callback(&self) {
+        if let Some(f) = &self.reconnected_callback {
+            f(self.user_context.clone());
         }
     }
 }
@@ -132,8 +376,23 @@ impl Default for AvahiBrowserContext {
         AvahiBrowserContext {
             client: None,
             resolvers: ServiceResolverSet::default(),
+            resolve_queue: ResolveQueue::default(),
+            debouncer: Debouncer::default(),
+            srv_queries: SrvQuerySet::default(),
             service_discovered_callback: None,
+            service_batch_callback: None,
+            batch: RefCell::default(),
+            all_for_now: false,
+            error_callback: None,
+            control: BrowserControl::default(),
+            reconnected_callback: None,
             user_context: None,
+            flags: ServiceFlags::empty(),
+            address_family: AddressFamily::default(),
+            protocol: avahi_sys::AVAHI_PROTO_UNSPEC,
+            include_raw_records: false,
+            reconnect: ReconnectState::Idle,
+            full_name: String::new(),
         }
     }
 }
@@ -160,20 +419,87 @@ unsafe extern "C" fn browse_callback(
 ) {
     let context = AvahiBrowserContext::from_raw(userdata);
 
+    let service = || DiscoveredService {
+        interface,
+        protocol,
+        name: CStr::from_ptr(name).to_owned(),
+        kind: CStr::from_ptr(kind).to_owned(),
+        domain: CStr::from_ptr(domain).to_owned(),
+    };
+
     match event {
         avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_NEW => {
-            if let Err(e) = handle_browser_new(context, interface, protocol, name, kind, domain) {
-                context.invoke_callback(Err(e));
+            if context.debouncer.has_returned(service) {
+                debug!(
+                    "Ignoring service that came back within the debounce window: {}",
+                    c_str::raw_to_str(name)
+                );
+                return;
             }
+
+            if !context.resolve_queue.try_start() {
+                // only the services that wait for a resolve to finish are copied
+                context.resolve_queue.queue(service());
+                return;
+            }
+
+            let result = resolve_service(context, interface, protocol, name, kind, domain);
+            resolve_next_on_error(context, result);
         }
+        avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_REMOVE => context.debouncer.went_away(service),
+        avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_ALL_FOR_NOW => context.all_for_now = true,
         avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_FAILURE => {
+            context.all_for_now = true;
             context.invoke_callback(Err("browser failure".into()))
         }
         _ => {}
     };
+
+    context.end_burst_if_idle();
+}
+
+/// A service reported by the browser that waits in the `ResolveQueue` of the context, or that has
+/// gone away and is remembered by its `Debouncer`
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct DiscoveredService {
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    name: CString,
+    kind: CString,
+    domain: CString,
+}
+
+/// Starts resolving `service`, which waited in the `ResolveQueue` of the context.
+fn resolve_discovered(
+    context: &mut AvahiBrowserContext,
+    service: &DiscoveredService,
+) -> Result<()> {
+    resolve_service(
+        context,
+        service.interface,
+        service.protocol,
+        service.name.as_ptr(),
+        service.kind.as_ptr(),
+        service.domain.as_ptr(),
+    )
+}
+
+/// Reports the error of a resolve that could not be started, if any, and starts resolving the
+/// next queued services in its place until one can be started.
+fn resolve_next_on_error(context: &mut AvahiBrowserContext, mut result: Result<()>) {
+    while let Err(e) = result {
+        context.invoke_callback(Err(e));
+
+        result = match context.resolve_queue.finish() {
+            Some(s) => resolve_discovered(context, &s),
+            None => return,
+        };
+    }
 }
 
-fn handle_browser_new(
+/// Starts resolving the specified service, the `ServiceDiscoveredCallback` of the context is
+/// invoked once it has been resolved.
+pub(super) fn resolve_service(
     context: &mut AvahiBrowserContext,
     interface: AvahiIfIndex,
     protocol: AvahiProtocol,
@@ -190,8 +516,8 @@ fn handle_browser_new(
             .name(name)
             .kind(kind)
             .domain(domain)
-            .aprotocol(avahi_sys::AVAHI_PROTO_UNSPEC)
-            .flags(0)
+            .aprotocol(avahi_util::protocol(context.address_family))
+            .flags(context.flags)
             .callback(Some(resolve_callback))
             .userdata(raw_context)
             .build()?,
@@ -201,7 +527,7 @@ fn handle_browser_new(
 
 unsafe extern "C" fn resolve_callback(
     resolver: *mut AvahiServiceResolver,
-    _interface: AvahiIfIndex,
+    interface: AvahiIfIndex,
     _protocol: AvahiProtocol,
     event: AvahiResolverEvent,
     name: *const c_char,
@@ -211,13 +537,15 @@ unsafe extern "C" fn resolve_callback(
     addr: *const AvahiAddress,
     port: u16,
     txt: *mut AvahiStringList,
-    _flags: AvahiLookupResultFlags,
+    flags: AvahiLookupResultFlags,
     userdata: *mut c_void,
 ) {
     let name = c_str::raw_to_str(name);
     let kind = c_str::raw_to_str(kind);
     let domain = c_str::raw_to_str(domain);
 
+    let _span = operation_span!("resolve", name = name, service_type = kind, domain = domain);
+
     let context = AvahiBrowserContext::from_raw(userdata);
 
     match event {
@@ -233,6 +561,8 @@ unsafe extern "C" fn resolve_callback(
                 context,
                 c_str::raw_to_str(host_name),
                 addr,
+                interface,
+                flags,
                 name,
                 kind,
                 domain,
@@ -248,20 +578,42 @@ unsafe extern "C" fn resolve_callback(
     };
 
     context.resolvers.remove_raw(resolver);
+
+    if let Some(service) = context.resolve_queue.finish() {
+        let result = resolve_discovered(context, &service);
+        resolve_next_on_error(context, result);
+    }
+
+    context.end_burst_if_idle();
 }
 
 #[allow(clippy::too_many_arguments)]
 unsafe fn handle_resolver_found(
-    context: &AvahiBrowserContext,
+    context: &mut AvahiBrowserContext,
     host_name: &str,
     addr: *const AvahiAddress,
+    interface: AvahiIfIndex,
+    flags: AvahiLookupResultFlags,
     name: &str,
     kind: &str,
     domain: &str,
     port: u16,
     txt: *mut AvahiStringList,
 ) -> Result<()> {
-    let address = avahi_util::avahi_address_to_string(addr);
+    if port == 0 {
+        // placeholders announced by other implementations with a `SRV` record for port 0
+        debug!("Ignoring placeholder service: {}", name);
+        return Ok(());
+    }
+
+    // the address is not looked up with `ServiceFlags::NO_ADDRESS`
+    let address = if addr.is_null() {
+        None
+    } else {
+        Some(avahi_util::avahi_address_to_ip(addr))
+    };
+
+    let scope_id = address.map_or(0, |a| ffi::link_local_scope_id(&a, interface as u32));
 
     let txt = if txt.is_null() {
         None
@@ -269,31 +621,206 @@ unsafe fn handle_resolver_found(
         Some(TxtRecord::from(ManagedAvahiStringList::clone_raw(txt)))
     };
 
-    let result = ServiceDiscovery::builder()
+    let mut discovery = ServiceDiscovery::builder();
+
+    discovery
         .name(name.to_string())
         .service_type(ServiceType::from_str(kind)?)
         .domain(domain.to_string())
         .host_name(host_name.to_string())
-        .address(address)
+        .address(address.unwrap_or_else(|| Ipv4Addr::UNSPECIFIED.into()))
+        .addresses(address.into_iter().collect())
+        .scope_id(scope_id)
         .port(port)
         .txt(txt)
-        .build()
-        .unwrap();
+        .meta(
+            EventMeta::builder()
+                .flags(flags)
+                .interface_index(interface as u32)
+                .build()?,
+        );
 
-    debug!("Service resolved: {:?}", result);
+    if !context.include_raw_records {
+        // the priority and weight of the service are only available from its SRV record, which
+        // would take a record browser per service to query, so they are left at their defaults
+        invoke_discovered(context, discovery);
+        return Ok(());
+    }
+
+    // Avahi copies the name, so the buffer can be reused for the next service
+    let full_name = &mut context.full_name;
+    full_name.clear();
+    dns::push_escaped_label(full_name, name);
+    full_name.extend([".", kind, ".", domain, "\0"].iter().copied());
+
+    let full_name = full_name.as_ptr() as *const c_char;
+    let raw_context = context.as_raw();
 
-    context.invoke_callback(Ok(result));
+    let srv_query = ManagedAvahiRecordBrowser::new(
+        ManagedAvahiRecordBrowserParams::builder()
+            .client(context.client.as_ref().unwrap())
+            .interface(interface)
+            .protocol(context.protocol)
+            .name(full_name)
+            .clazz(dns::CLASS_IN)
+            .kind(dns::TYPE_ANY)
+            .flags(context.flags)
+            .callback(Some(srv_browse_callback))
+            .userdata(raw_context)
+            .build()?,
+    );
+
+    match srv_query {
+        Ok(srv_query) => context.srv_queries.insert(srv_query, discovery),
+        Err(e) => {
+            // not fatal, the service is reported without its raw records
+            debug!("Could not query records of {}: {}", name, e);
+            invoke_discovered(context, discovery);
+        }
+    }
 
     Ok(())
 }
 
-extern "C" fn client_callback(
-    _client: *mut AvahiClient,
-    state: AvahiClientState,
-    _userdata: *mut c_void,
+unsafe extern "C" fn srv_browse_callback(
+    browser: *mut AvahiRecordBrowser,
+    _interface: AvahiIfIndex,
+    _protocol: AvahiProtocol,
+    event: AvahiBrowserEvent,
+    name: *const c_char,
+    clazz: u16,
+    kind: u16,
+    rdata: *const c_void,
+    size: usize,
+    _flags: AvahiLookupResultFlags,
+    userdata: *mut c_void,
 ) {
-    // TODO: handle this better
-    if let avahi_sys::AvahiClientState_AVAHI_CLIENT_FAILURE = state {
-        panic!("client failure");
+    let context = AvahiBrowserContext::from_raw(userdata);
+
+    match event {
+        avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_NEW => {
+            let query = match context.srv_queries.get_mut(browser) {
+                Some(query) => query,
+                None => return,
+            };
+
+            let rdata = avahi_util::rdata(rdata, size);
+
+            let record = Record::builder()
+                .name(c_str::copy_raw(name))
+                .rrtype(kind)
+                .rrclass(clazz)
+                .rdata(rdata.to_vec())
+                .build();
+
+            match record {
+                Ok(record) => query.records.push(record),
+                Err(e) => {
+                    context.srv_queries.remove_raw(browser);
+                    context.invoke_callback(Err(e.into()));
+                    return;
+                }
+            }
+
+            if kind == dns::TYPE_SRV {
+                if let Some(srv) = dns::parse_srv_rdata(rdata) {
+                    query.discovery.priority(srv.priority).weight(srv.weight);
+                }
+            }
+
+            // all records of the service are reported before it is
+            return;
+        }
+        avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_ALL_FOR_NOW
+        | avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_FAILURE => {}
+        _ => return,
+    };
+
+    if let Some(discovery) = context.srv_queries.remove_raw(browser) {
+        invoke_discovered(context, discovery);
     }
+
+    context.end_burst_if_idle();
+}
+
+fn invoke_discovered(context: &AvahiBrowserContext, discovery: ServiceDiscoveryBuilder) {
+    let result = discovery.build().map_err(Error::from);
+
+    debug!("Service resolved: {:?}", result);
+
+    context.invoke_callback(result);
+}
+
+/// Services that have been resolved and are waiting for all of their raw records to be received
+#[derive(Default)]
+pub(super) struct SrvQuerySet {
+    queries: HashMap<*mut AvahiRecordBrowser, SrvQuery>,
+}
+
+struct SrvQuery {
+    _browser: ManagedAvahiRecordBrowser,
+    discovery: ServiceDiscoveryBuilder,
+    records: Vec<Record>,
+}
+
+impl SrvQuerySet {
+    fn insert(&mut self, query: ManagedAvahiRecordBrowser, discovery: ServiceDiscoveryBuilder) {
+        let raw = query.inner();
+
+        let query = SrvQuery {
+            _browser: query,
+            discovery,
+            records: Vec::new(),
+        };
+
+        self.queries.insert(raw, query);
+    }
+
+    fn get_mut(&mut self, raw: *mut AvahiRecordBrowser) -> Option<&mut SrvQuery> {
+        self.queries.get_mut(&raw)
+    }
+
+    fn remove_raw(&mut self, raw: *mut AvahiRecordBrowser) -> Option<ServiceDiscoveryBuilder> {
+        self.queries.remove(&raw).map(|mut query| {
+            query.discovery.raw_records(query.records);
+            query.discovery
+        })
+    }
+
+    fn is_empty(&self) -> bool {
+        self.queries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.queries.clear();
+    }
+}
+
+unsafe extern "C" fn client_callback(
+    client: *mut AvahiClient,
+    state: AvahiClientState,
+    userdata: *mut c_void,
+) {
+    let context = AvahiBrowserContext::from_raw(userdata);
+
+    match state {
+        avahi_sys::AvahiClientState_AVAHI_CLIENT_S_RUNNING
+            if context.reconnect == ReconnectState::Connecting =>
+        {
+            // the browser is re-created from the event loop
+            context.reconnect = ReconnectState::Connected;
+        }
+        avahi_sys::AvahiClientState_AVAHI_CLIENT_FAILURE => {
+            let error = avahi_util::error(client::get_errno(client), "client failure".to_string());
+
+            if error.is_recoverable() {
+                // the client is re-created from the event loop
+                warn!("Disconnected from avahi-daemon, waiting to reconnect");
+                context.reconnect = ReconnectState::Pending;
+            } else {
+                context.invoke_callback(Err(error))
+            }
+        }
+        _ => {}
+    };
 }
@@ -2,13 +2,30 @@
 
 use super::avahi_util;
 use super::poll::ManagedAvahiSimplePoll;
+use crate::error::{Error, ErrorKind, Operation};
 use crate::ffi::c_str;
+use crate::prelude::*;
 use crate::Result;
 use avahi_sys::{
-    avahi_client_free, avahi_client_get_host_name, avahi_client_new, avahi_simple_poll_get,
-    AvahiClient, AvahiClientCallback, AvahiClientFlags,
+    avahi_client_errno, avahi_client_free, avahi_client_get_domain_name,
+    avahi_client_get_host_name, avahi_client_get_host_name_fqdn, avahi_client_get_state,
+    avahi_client_get_version_string, avahi_client_new, avahi_client_set_host_name,
+    avahi_simple_poll_get, AvahiClient, AvahiClientCallback, AvahiClientFlags, AvahiClientState,
 };
 use libc::{c_int, c_void};
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::fmt::{self, Formatter};
+use std::path::Path;
+use std::ptr;
+use std::sync::{Arc, Weak};
+
+/// The socket `libavahi-client` connects to `avahi-daemon` through
+#[cfg(target_os = "linux")]
+const AVAHI_SOCKET: &str = "/run/avahi-daemon/socket";
+/// The socket `libavahi-client` connects to `avahi-daemon` through
+#[cfg(not(target_os = "linux"))]
+const AVAHI_SOCKET: &str = "/var/run/avahi-daemon/socket";
 
 /// Wraps the `AvahiClient` type from the raw Avahi bindings.
 ///
@@ -40,20 +57,29 @@ impl ManagedAvahiClient {
             )
         };
 
-        if client.is_null() {
-            return Err("could not initialize AvahiClient".into());
-        }
-
         match err {
-            0 => Ok(Self(client)),
-            _ => Err(format!(
-                "could not initialize AvahiClient: {}",
-                avahi_util::get_error(err)
+            0 if !client.is_null() => Ok(Self(client)),
+            0 => Err("could not initialize AvahiClient".into()),
+            avahi_sys::AVAHI_ERR_NO_DAEMON => Err(no_daemon_error()),
+            _ => Err(avahi_util::error(
+                err,
+                format!(
+                    "could not initialize AvahiClient: {}",
+                    avahi_util::get_error(err)
+                ),
             )
-            .into()),
+            .with_operation(Operation::Connect)),
         }
     }
 
+    /// Initializes a new `ManagedAvahiClient` to be shared by the Avahi objects created with it.
+    // SAFETY: as its poll, the client is only used by one thread at a time, see
+    // `ManagedAvahiSimplePoll::new_shared()`.
+    #[allow(clippy::arc_with_non_send_sync)]
+    pub fn new_shared(params: ManagedAvahiClientParams<'_>) -> Result<Arc<Self>> {
+        Self::new(params).map(Arc::new)
+    }
+
     /// Delegate function for [`avahi_client_get_host_name()`].
     ///
     /// [`avahi_client_get_host_name()`]: https://avahi.org/doxygen/html/client_8h.html#a89378618c3c592a255551c308ba300bf
@@ -61,6 +87,39 @@ impl ManagedAvahiClient {
         unsafe { get_host_name(self.0) }
     }
 
+    /// Delegate function for [`avahi_client_get_host_name_fqdn()`].
+    ///
+    /// [`avahi_client_get_host_name_fqdn()`]: https://avahi.org/doxygen/html/client_8h.html
+    pub fn host_name_fqdn<'a>(&self) -> Result<&'a str> {
+        unsafe { get_host_name_fqdn(self.0) }
+    }
+
+    /// Delegate function for [`avahi_client_get_domain_name()`].
+    ///
+    /// [`avahi_client_get_domain_name()`]: https://avahi.org/doxygen/html/client_8h.html
+    pub fn domain_name<'a>(&self) -> Result<&'a str> {
+        unsafe { get_domain_name(self.0) }
+    }
+
+    /// Delegate function for [`avahi_client_get_version_string()`].
+    ///
+    /// [`avahi_client_get_version_string()`]: https://avahi.org/doxygen/html/client_8h.html
+    pub fn version_string<'a>(&self) -> Result<&'a str> {
+        unsafe { get_version_string(self.0) }
+    }
+
+    /// Delegate function for [`avahi_client_set_host_name()`].
+    ///
+    /// [`avahi_client_set_host_name()`]: https://avahi.org/doxygen/html/client_8h.html
+    pub fn set_host_name(&self, host_name: &str) -> Result<()> {
+        let host_name = c_string!(host_name);
+
+        avahi!(
+            avahi_client_set_host_name(self.0, host_name.as_ptr()),
+            "could not set host name"
+        )
+    }
+
     pub(super) fn inner(&self) -> *mut AvahiClient {
         self.0
     }
@@ -85,6 +144,335 @@ pub struct ManagedAvahiClientParams<'a> {
     userdata: *mut c_void,
 }
 
+thread_local! {
+    /// Whether the browsers and services started from now on on this thread share its client
+    static SHARE_CLIENT: Cell<bool> = const { Cell::new(false) };
+
+    /// The client shared by the browsers and services of this thread, and its poll, which outlives
+    /// the client when it is re-created after a disconnect as the event loops keep polling it.
+    static SHARED: RefCell<(Weak<SharedAvahiClient>, Weak<ManagedAvahiSimplePoll>)> =
+        RefCell::default();
+}
+
+/// Sets whether the browsers and services started from now on on the calling thread share a
+/// single connection to `avahi-daemon` and a single `AvahiSimplePoll`, instead of each opening
+/// their own. Disabled by default.
+///
+/// Sharing is scoped to the calling thread: the setting only applies to it, and each thread that
+/// enables it has its own shared client, which is polled by the event loops of that thread. An
+/// application that wants a single connection must therefore start and poll all of its browsers
+/// and services on the same thread.
+///
+/// While sharing is enabled, the Avahi objects of a browser or service are freed when its event
+/// loop is dropped rather than with the browser or service itself, as they must only be used from
+/// the thread polling the shared client. Resolvers and queries keep opening their own clients.
+pub fn set_thread_client_sharing(enabled: bool) {
+    SHARE_CLIENT.with(|share| share.set(enabled));
+}
+
+/// A `ManagedAvahiClient` whose state changes are dispatched to the callbacks of any number of
+/// browsers and services, see [`set_thread_client_sharing()`].
+///
+/// [`set_thread_client_sharing()`]: fn.set_thread_client_sharing.html
+pub(super) struct SharedAvahiClient {
+    // the client must be freed before the listeners it calls back and its poll
+    client: Arc<ManagedAvahiClient>,
+    listeners: Box<RefCell<Listeners>>,
+    poll: Arc<ManagedAvahiSimplePoll>,
+    shared: bool,
+}
+
+#[derive(Default)]
+struct Listeners {
+    next_id: usize,
+    callbacks: Vec<Listener>,
+}
+
+struct Listener {
+    id: usize,
+    callback: AvahiClientCallback,
+    userdata: *mut c_void,
+}
+
+impl SharedAvahiClient {
+    fn new(
+        poll: Arc<ManagedAvahiSimplePoll>,
+        flags: AvahiClientFlags,
+        shared: bool,
+    ) -> Result<Self> {
+        let listeners: Box<RefCell<Listeners>> = Box::default();
+
+        let client = ManagedAvahiClient::new_shared(
+            ManagedAvahiClientParams::builder()
+                .poll(&poll)
+                .flags(flags)
+                .callback(Some(shared_client_callback))
+                .userdata(&*listeners as *const RefCell<Listeners> as *mut c_void)
+                .build()?,
+        )?;
+
+        Ok(Self {
+            client,
+            listeners,
+            poll,
+            shared,
+        })
+    }
+
+    /// Returns the client of this thread if sharing is enabled and the client has not failed, or
+    /// a new client otherwise, polled by `poll` if it is set and the client is not shared.
+    // SAFETY: the client is only used from the thread polling it, see
+    // `ManagedAvahiSimplePoll::new_shared()`.
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn acquire(
+        poll: Option<&Arc<ManagedAvahiSimplePoll>>,
+        flags: AvahiClientFlags,
+    ) -> Result<Arc<Self>> {
+        if !SHARE_CLIENT.with(Cell::get) {
+            let poll = match poll {
+                Some(p) => p.clone(),
+                None => ManagedAvahiSimplePoll::new_shared()?,
+            };
+
+            return Self::new(poll, flags, false).map(Arc::new);
+        }
+
+        SHARED.with(|shared| {
+            let mut shared = shared.borrow_mut();
+
+            if let Some(client) = shared.0.upgrade() {
+                if !client.has_failed() {
+                    return Ok(client);
+                }
+            }
+
+            let poll = match shared.1.upgrade() {
+                Some(p) => p,
+                None => ManagedAvahiSimplePoll::new_shared()?,
+            };
+
+            debug!("Creating AvahiClient shared by this thread");
+
+            let client = Arc::new(Self::new(poll.clone(), flags, true)?);
+            *shared = (Arc::downgrade(&client), Arc::downgrade(&poll));
+
+            Ok(client)
+        })
+    }
+
+    fn has_failed(&self) -> bool {
+        unsafe {
+            get_state(self.client.inner()) == avahi_sys::AvahiClientState_AVAHI_CLIENT_FAILURE
+        }
+    }
+}
+
+impl fmt::Debug for SharedAvahiClient {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedAvahiClient")
+            .field("client", &self.client)
+            .field("listeners", &self.listeners.borrow().callbacks.len())
+            .field("shared", &self.shared)
+            .finish()
+    }
+}
+
+unsafe extern "C" fn shared_client_callback(
+    client: *mut AvahiClient,
+    state: AvahiClientState,
+    userdata: *mut c_void,
+) {
+    let listeners = &*(userdata as *const RefCell<Listeners>);
+    let ids: Vec<usize> = listeners.borrow().callbacks.iter().map(|l| l.id).collect();
+
+    for id in ids {
+        // the listeners are looked up again as the callbacks may add or remove listeners
+        let listener = listeners
+            .borrow()
+            .callbacks
+            .iter()
+            .find(|l| l.id == id)
+            .map(|l| (l.callback, l.userdata));
+
+        if let Some((Some(f), userdata)) = listener {
+            f(client, state, userdata);
+        }
+    }
+}
+
+/// The registration of the callback of a browser or service with a [`SharedAvahiClient`], which
+/// is removed when this is dropped.
+///
+/// [`SharedAvahiClient`]: struct.SharedAvahiClient.html
+#[derive(Debug)]
+pub(super) struct ClientSubscription {
+    client: Arc<SharedAvahiClient>,
+    id: usize,
+}
+
+impl ClientSubscription {
+    /// Acquires a client, see [`set_thread_client_sharing()`], and registers `callback` to be invoked with
+    /// `userdata` on its state changes, starting with its current state.
+    ///
+    /// [`set_thread_client_sharing()`]: fn.set_thread_client_sharing.html
+    pub(super) fn new(
+        poll: Option<&Arc<ManagedAvahiSimplePoll>>,
+        flags: AvahiClientFlags,
+        callback: AvahiClientCallback,
+        userdata: *mut c_void,
+    ) -> Result<Self> {
+        let client = SharedAvahiClient::acquire(poll, flags)?;
+
+        let id = {
+            let mut listeners = client.listeners.borrow_mut();
+            let id = listeners.next_id;
+
+            listeners.next_id += 1;
+            listeners.callbacks.push(Listener {
+                id,
+                callback,
+                userdata,
+            });
+
+            id
+        };
+
+        // the client may have been created, or have changed its state, before the listener was
+        // registered
+        if let Some(f) = callback {
+            let raw = client.client.inner();
+            unsafe { f(raw, get_state(raw), userdata) };
+        }
+
+        Ok(Self { client, id })
+    }
+
+    pub(super) fn client(&self) -> &Arc<ManagedAvahiClient> {
+        &self.client.client
+    }
+
+    pub(super) fn poll(&self) -> &Arc<ManagedAvahiSimplePoll> {
+        &self.client.poll
+    }
+
+    /// Returns true if the client is shared with the other browsers and services of this thread
+    pub(super) fn is_shared(&self) -> bool {
+        self.client.shared
+    }
+}
+
+impl Drop for ClientSubscription {
+    fn drop(&mut self) {
+        let id = self.id;
+        self.client
+            .listeners
+            .borrow_mut()
+            .callbacks
+            .retain(|l| l.id != id);
+    }
+}
+
+/// Returns the error of kind `ErrorKind::DaemonNotRunning` reported when `avahi-daemon` cannot be
+/// reached, telling apart a missing socket (e.g. one that is not mounted into a container) from a
+/// daemon that does not accept connections.
+fn no_daemon_error() -> Error {
+    let description = if Path::new(AVAHI_SOCKET).exists() {
+        format!(
+            "avahi-daemon is not accepting connections on `{}`, check that it is running (e.g. \
+             with `systemctl status avahi-daemon`)",
+            AVAHI_SOCKET
+        )
+    } else {
+        format!(
+            "avahi-daemon is not running: `{0}` does not exist. Start avahi-daemon or, in a \
+             container, mount the socket of the host (e.g. `-v {0}:{0}`), or enable the \
+             `embedded` feature to run without a daemon",
+            AVAHI_SOCKET
+        )
+    };
+
+    Error::with_kind(ErrorKind::DaemonNotRunning, description)
+        .with_code(avahi_sys::AVAHI_ERR_NO_DAEMON)
+        .with_operation(Operation::Connect)
+}
+
+/// Returns true if a client can connect to `avahi-daemon`.
+pub fn is_daemon_running() -> bool {
+    daemon_version().is_ok()
+}
+
+/// Connects to `avahi-daemon` and returns its version string (e.g. `avahi 0.8`).
+pub fn daemon_version() -> Result<String> {
+    let poll = ManagedAvahiSimplePoll::new()?;
+    let client = connect(&poll)?;
+
+    client.version_string().map(String::from)
+}
+
+/// The identity `avahi-daemon` announces the services and addresses of this machine under, as
+/// returned by [`server_identity()`].
+///
+/// [`server_identity()`]: fn.server_identity.html
+#[derive(Debug, Getters, Clone, PartialEq, Eq)]
+pub struct ServerIdentity {
+    /// The host name without any domain (e.g. `hostname`)
+    host_name: String,
+    /// The fully qualified host name (e.g. `hostname.local`)
+    host_name_fqdn: String,
+    /// The default domain services are registered in (e.g. `local`)
+    domain_name: String,
+}
+
+/// Connects to `avahi-daemon` and returns the host name and domain it is using for the
+/// announcements of this machine, e.g. to display them to users.
+///
+/// # Examples
+/// ```no_run
+/// let identity = zeroconf::linux::client::server_identity().unwrap();
+///
+/// println!("announcing as {}", identity.host_name_fqdn());
+/// ```
+pub fn server_identity() -> Result<ServerIdentity> {
+    let poll = ManagedAvahiSimplePoll::new()?;
+    let client = connect(&poll)?;
+
+    Ok(ServerIdentity {
+        host_name: client.host_name()?.to_string(),
+        host_name_fqdn: client.host_name_fqdn()?.to_string(),
+        domain_name: client.domain_name()?.to_string(),
+    })
+}
+
+/// Changes the mDNS host name of this machine advertised by `avahi-daemon` (without any domain,
+/// e.g. `printer` to be reachable as `printer.local`), as `avahi-set-host-name` does.
+///
+/// The daemon re-announces its address records and the services registered on this host under
+/// the new name. Changing the host name is usually restricted to root by the D-Bus policy of
+/// `avahi-daemon`.
+///
+/// # Examples
+/// ```no_run
+/// zeroconf::linux::client::set_host_name("printer").unwrap();
+/// ```
+pub fn set_host_name(host_name: &str) -> Result<()> {
+    let poll = ManagedAvahiSimplePoll::new()?;
+    let client = connect(&poll)?;
+
+    client.set_host_name(host_name)
+}
+
+/// Opens a connection to `avahi-daemon` that does not report its state changes.
+fn connect(poll: &ManagedAvahiSimplePoll) -> Result<ManagedAvahiClient> {
+    ManagedAvahiClient::new(
+        ManagedAvahiClientParams::builder()
+            .poll(poll)
+            .flags(AvahiClientFlags(0))
+            .callback(None)
+            .userdata(ptr::null_mut())
+            .build()?,
+    )
+}
+
 pub(super) unsafe fn get_host_name<'a>(client: *mut AvahiClient) -> Result<&'a str> {
     assert_not_null!(client);
     let host_name = avahi_client_get_host_name(client);
@@ -94,3 +482,134 @@ pub(super) unsafe fn get_host_name<'a>(client: *mut AvahiClient) -> Result<&'a s
         Err("could not get host name from AvahiClient".into())
     }
 }
+
+pub(super) unsafe fn get_host_name_fqdn<'a>(client: *mut AvahiClient) -> Result<&'a str> {
+    assert_not_null!(client);
+    let host_name = avahi_client_get_host_name_fqdn(client);
+    if !host_name.is_null() {
+        Ok(c_str::raw_to_str(host_name))
+    } else {
+        Err("could not get fully qualified host name from AvahiClient".into())
+    }
+}
+
+pub(super) unsafe fn get_domain_name<'a>(client: *mut AvahiClient) -> Result<&'a str> {
+    assert_not_null!(client);
+    let domain_name = avahi_client_get_domain_name(client);
+    if !domain_name.is_null() {
+        Ok(c_str::raw_to_str(domain_name))
+    } else {
+        Err("could not get domain name from AvahiClient".into())
+    }
+}
+
+pub(super) unsafe fn get_version_string<'a>(client: *mut AvahiClient) -> Result<&'a str> {
+    assert_not_null!(client);
+    let version = avahi_client_get_version_string(client);
+    if !version.is_null() {
+        Ok(c_str::raw_to_str(version))
+    } else {
+        Err("could not get version string from AvahiClient".into())
+    }
+}
+
+pub(super) unsafe fn get_state(client: *mut AvahiClient) -> AvahiClientState {
+    assert_not_null!(client);
+    avahi_client_get_state(client)
+}
+
+pub(super) unsafe fn get_errno(client: *mut AvahiClient) -> c_int {
+    assert_not_null!(client);
+    avahi_client_errno(client)
+}
+
+/// The state of the connection of an `AvahiMdnsService` to `avahi-daemon`, reported to the
+/// [`ClientStateCallback`] (see [`AvahiMdnsService::set_client_state_callback()`]).
+///
+/// [`ClientStateCallback`]: type.ClientStateCallback.html
+/// [`AvahiMdnsService::set_client_state_callback()`]: ../service/struct.AvahiMdnsService.html#method.set_client_state_callback
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ClientState {
+    /// The daemon is registering its host name, services are announced once it is running
+    /// (`AVAHI_CLIENT_S_REGISTERING`)
+    Registering,
+    /// The daemon is running and announces the registered services (`AVAHI_CLIENT_S_RUNNING`)
+    Running,
+    /// The host name of the daemon conflicts with another host on the network, services are
+    /// announced again once the daemon has picked another name (`AVAHI_CLIENT_S_COLLISION`)
+    Collision,
+    /// The connection to the daemon has failed, e.g. because it was stopped
+    /// (`AVAHI_CLIENT_FAILURE`)
+    Failure,
+    /// The client waits for the daemon to become available, e.g. while reconnecting
+    /// (`AVAHI_CLIENT_CONNECTING`)
+    Connecting,
+}
+
+impl ClientState {
+    /// Returns the `ClientState` of the specified Avahi client state, if known.
+    pub fn from_raw(state: AvahiClientState) -> Option<Self> {
+        match state {
+            avahi_sys::AvahiClientState_AVAHI_CLIENT_S_REGISTERING => Some(Self::Registering),
+            avahi_sys::AvahiClientState_AVAHI_CLIENT_S_RUNNING => Some(Self::Running),
+            avahi_sys::AvahiClientState_AVAHI_CLIENT_S_COLLISION => Some(Self::Collision),
+            avahi_sys::AvahiClientState_AVAHI_CLIENT_FAILURE => Some(Self::Failure),
+            avahi_sys::AvahiClientState_AVAHI_CLIENT_CONNECTING => Some(Self::Connecting),
+            _ => None,
+        }
+    }
+}
+
+/// Callback invoked from an `AvahiMdnsService` each time the state of its connection to
+/// `avahi-daemon` changes.
+///
+/// # Arguments
+/// * `state` - The new state of the connection
+/// * `context` - The optional user context passed through
+pub type ClientStateCallback = dyn Fn(ClientState, Option<Arc<dyn Any + Send + Sync>>) + Send;
+
+/// Tracks the progress of re-creating a `ManagedAvahiClient` after it was disconnected from the
+/// daemon (e.g. because `avahi-daemon` was restarted).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(super) enum ReconnectState {
+    /// The client is connected, or has failed for a reason other than a disconnect
+    #[default]
+    Idle,
+    /// The client was disconnected and must be re-created
+    Pending,
+    /// The client was re-created and is waiting for the daemon to become available
+    Connecting,
+    /// The re-created client is running
+    Connected,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn subscribe() -> ClientSubscription {
+        // as acquired by each browser and service
+        ClientSubscription::new(None, AvahiClientFlags(0), None, ptr::null_mut()).unwrap()
+    }
+
+    #[test]
+    fn browsers_on_the_same_thread_share_a_client() {
+        crate::tests::setup();
+        set_thread_client_sharing(true);
+
+        let first = subscribe();
+        let second = subscribe();
+
+        assert!(first.is_shared());
+        assert!(Arc::ptr_eq(first.client(), second.client()));
+        assert!(Arc::ptr_eq(first.poll(), second.poll()));
+
+        let other_thread = thread::spawn(|| subscribe().is_shared()).join().unwrap();
+
+        set_thread_client_sharing(false);
+
+        assert!(!other_thread);
+        assert!(!Arc::ptr_eq(first.client(), subscribe().client()));
+    }
+}
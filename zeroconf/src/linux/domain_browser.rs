@@ -0,0 +1,227 @@
+//! Avahi browser for the browse and registration domains announced on the network
+
+use super::avahi_util;
+use super::client::{ManagedAvahiClient, ManagedAvahiClientParams};
+use super::poll::ManagedAvahiSimplePoll;
+use super::raw_browser::{ManagedAvahiDomainBrowser, ManagedAvahiDomainBrowserParams};
+use crate::ffi::{c_str, FromRaw, SharedContext};
+use crate::prelude::*;
+use crate::{EventLoop, NetworkInterface, Result, ServiceFlags};
+use avahi_sys::{
+    AvahiBrowserEvent, AvahiClient, AvahiClientFlags, AvahiClientState, AvahiDomainBrowser,
+    AvahiDomainBrowserType, AvahiIfIndex, AvahiLookupResultFlags, AvahiProtocol,
+};
+use libc::{c_char, c_void};
+use std::any::Any;
+use std::fmt;
+use std::ptr;
+use std::sync::Arc;
+
+/// The kind of domains an [`AvahiMdnsDomainBrowser`] looks for.
+///
+/// [`AvahiMdnsDomainBrowser`]: struct.AvahiMdnsDomainBrowser.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DomainKind {
+    /// The domains recommended for browsing services
+    Browse,
+    /// The domains recommended for registering services
+    Register,
+}
+
+impl DomainKind {
+    fn btype(self) -> AvahiDomainBrowserType {
+        match self {
+            Self::Browse => avahi_sys::AvahiDomainBrowserType_AVAHI_DOMAIN_BROWSER_BROWSE,
+            Self::Register => avahi_sys::AvahiDomainBrowserType_AVAHI_DOMAIN_BROWSER_REGISTER,
+        }
+    }
+}
+
+/// Callback invoked from an [`AvahiMdnsDomainBrowser`] once a domain has been discovered.
+///
+/// # Arguments
+/// * `domain` - The domain that was discovered (e.g. `example.com`)
+/// * `context` - The optional user context passed through
+///
+/// [`AvahiMdnsDomainBrowser`]: struct.AvahiMdnsDomainBrowser.html
+pub type DomainDiscoveredCallback =
+    dyn Fn(Result<String>, Option<Arc<dyn Any + Send + Sync>>) + Send;
+
+/// Enumerates the browse or registration domains announced on the network (e.g. by the DNS
+/// server of a wide-area DNS-SD deployment) with [`avahi_domain_browser_new()`].
+///
+/// # Examples
+/// ```no_run
+/// use std::time::Duration;
+/// use zeroconf::linux::domain_browser::{AvahiMdnsDomainBrowser, DomainKind};
+///
+/// let mut browser = AvahiMdnsDomainBrowser::new(DomainKind::Browse);
+/// browser.set_domain_discovered_callback(Box::new(|domain, _| println!("{:?}", domain)));
+///
+/// let event_loop = browser.browse_domains().unwrap();
+///
+/// loop {
+///     event_loop.poll(Duration::from_secs(0)).unwrap();
+/// }
+/// ```
+///
+/// [`avahi_domain_browser_new()`]: https://avahi.org/doxygen/html/lookup_8h.html
+#[derive(Debug)]
+pub struct AvahiMdnsDomainBrowser {
+    client: Option<ManagedAvahiClient>,
+    poll: Option<Arc<ManagedAvahiSimplePoll>>,
+    browser: Option<ManagedAvahiDomainBrowser>,
+    kind: DomainKind,
+    interface_index: AvahiIfIndex,
+    flags: ServiceFlags,
+    context: SharedContext<AvahiDomainBrowserContext>,
+}
+
+impl AvahiMdnsDomainBrowser {
+    /// Creates a new `AvahiMdnsDomainBrowser` for domains of the specified `kind`.
+    pub fn new(kind: DomainKind) -> Self {
+        Self {
+            client: None,
+            poll: None,
+            browser: None,
+            kind,
+            interface_index: avahi_sys::AVAHI_IF_UNSPEC,
+            flags: ServiceFlags::empty(),
+            context: SharedContext::default(),
+        }
+    }
+
+    /// Sets the network interface on which to browse for domains.
+    ///
+    /// Most applications will want to use the default value `NetworkInterface::Unspec` to browse
+    /// on all available interfaces.
+    pub fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface_index = avahi_util::interface_index(interface);
+    }
+
+    /// Sets the [`ServiceFlags`] to browse for domains with.
+    ///
+    /// [`ServiceFlags`]: ../../flags/struct.ServiceFlags.html
+    pub fn set_flags(&mut self, flags: ServiceFlags) {
+        self.flags = flags;
+    }
+
+    /// Sets the [`DomainDiscoveredCallback`] that is invoked when a domain has been discovered.
+    ///
+    /// [`DomainDiscoveredCallback`]: type.DomainDiscoveredCallback.html
+    pub fn set_domain_discovered_callback(
+        &mut self,
+        domain_discovered_callback: Box<DomainDiscoveredCallback>,
+    ) {
+        unsafe {
+            (*self.context.as_ptr()).domain_discovered_callback = Some(domain_discovered_callback)
+        };
+    }
+
+    /// Sets the optional user context to pass through to the callback. This is useful if you need
+    /// to share state between pre and post-callback. The context type must implement `Any`, `Send`
+    /// and `Sync`.
+    pub fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        unsafe { (*self.context.as_ptr()).user_context = Some(Arc::from(context)) };
+    }
+
+    /// Starts browsing for domains. Returns an `EventLoop` which can be called to keep the
+    /// browser alive.
+    pub fn browse_domains(&mut self) -> Result<EventLoop<'_>> {
+        debug!("Browsing domains: {:?}", self);
+
+        self.poll = Some(ManagedAvahiSimplePoll::new_shared()?);
+
+        self.client = Some(ManagedAvahiClient::new(
+            ManagedAvahiClientParams::builder()
+                .poll(self.poll.as_ref().unwrap())
+                .flags(AvahiClientFlags(0))
+                .callback(Some(client_callback))
+                .userdata(self.context.as_ptr() as *mut c_void)
+                .build()?,
+        )?);
+
+        self.browser = Some(ManagedAvahiDomainBrowser::new(
+            ManagedAvahiDomainBrowserParams::builder()
+                .client(self.client.as_ref().unwrap())
+                .interface(self.interface_index)
+                .protocol(avahi_sys::AVAHI_PROTO_UNSPEC)
+                .domain(ptr::null())
+                .btype(self.kind.btype())
+                .flags(self.flags)
+                .callback(Some(browse_callback))
+                .userdata(self.context.as_ptr() as *mut c_void)
+                .build()?,
+        )?);
+
+        Ok(EventLoop::new(self.poll.as_ref().unwrap().clone()))
+    }
+}
+
+impl Drop for AvahiMdnsDomainBrowser {
+    fn drop(&mut self) {
+        // browser must be freed before the client
+        self.browser = None;
+        self.client = None;
+    }
+}
+
+#[derive(Default, FromRaw)]
+struct AvahiDomainBrowserContext {
+    domain_discovered_callback: Option<Box<DomainDiscoveredCallback>>,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl AvahiDomainBrowserContext {
+    fn invoke_callback(&self, result: Result<String>) {
+        if let Some(f) = &self.domain_discovered_callback {
+            f(result, self.user_context.clone());
+        } else {
+            warn!("attempted to invoke domain browser callback but none was set");
+        }
+    }
+}
+
+impl fmt::Debug for AvahiDomainBrowserContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AvahiDomainBrowserContext").finish()
+    }
+}
+
+unsafe extern "C" fn browse_callback(
+    _browser: *mut AvahiDomainBrowser,
+    _interface: AvahiIfIndex,
+    _protocol: AvahiProtocol,
+    event: AvahiBrowserEvent,
+    domain: *const c_char,
+    _flags: AvahiLookupResultFlags,
+    userdata: *mut c_void,
+) {
+    let context = AvahiDomainBrowserContext::from_raw(userdata);
+
+    match event {
+        avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_NEW => {
+            let domain = c_str::copy_raw(domain);
+
+            debug!("Domain discovered: {}", domain);
+
+            context.invoke_callback(Ok(domain));
+        }
+        avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_FAILURE => {
+            context.invoke_callback(Err("domain browser failure".into()))
+        }
+        _ => {}
+    };
+}
+
+unsafe extern "C" fn client_callback(
+    _client: *mut AvahiClient,
+    state: AvahiClientState,
+    userdata: *mut c_void,
+) {
+    let context = AvahiDomainBrowserContext::from_raw(userdata);
+
+    if state == avahi_sys::AvahiClientState_AVAHI_CLIENT_FAILURE {
+        context.invoke_callback(Err("client failure".into()));
+    }
+}
@@ -1,15 +1,88 @@
 //! Rust friendly `AvahiEntryGroup` wrappers/helpers
 
 use super::string_list::ManagedAvahiStringList;
+use crate::error::{Error, ErrorKind, Operation};
 use crate::ffi::UnwrapMutOrNull;
-use crate::Result;
 use crate::linux::avahi_util;
+use crate::{Result, ServiceFlags};
 use avahi_sys::{
+    avahi_client_errno, avahi_entry_group_add_address, avahi_entry_group_add_record,
     avahi_entry_group_add_service_strlst, avahi_entry_group_commit, avahi_entry_group_free,
-    avahi_entry_group_is_empty, avahi_entry_group_new, avahi_entry_group_reset, avahi_client_errno,
-    AvahiClient, AvahiEntryGroup, AvahiEntryGroupCallback, AvahiIfIndex, AvahiProtocol, AvahiPublishFlags,
+    avahi_entry_group_get_client, avahi_entry_group_is_empty, avahi_entry_group_new,
+    avahi_entry_group_reset, AvahiAddress, AvahiClient, AvahiEntryGroup, AvahiEntryGroupCallback,
+    AvahiIfIndex, AvahiProtocol, AvahiPublishFlags,
 };
 use libc::{c_char, c_void};
+use std::{mem, ptr};
+
+bitflags! {
+    /// Avahi-specific flags for publishing services, addresses and records.
+    ///
+    /// Avahi only accepts some of them for each kind of entry, see `PublishFlags::SERVICE` and
+    /// `PublishFlags::ADDRESS`, and rejects the others with `AVAHI_ERR_INVALID_FLAGS`.
+    ///
+    /// [`avahi_entry_group_add_service()`]: https://avahi.org/doxygen/html/publish_8h.html#acb05a7d3d23a3b825ca77cb1c7d00ce4
+    #[derive(Default)]
+    pub struct PublishFlags: AvahiPublishFlags {
+        /// The records are unique to this host.
+        const UNIQUE = avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_UNIQUE;
+        /// Skips probing for conflicts before announcing the records.
+        const NO_PROBE = avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_NO_PROBE;
+        /// Does not announce the records.
+        const NO_ANNOUNCE = avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_NO_ANNOUNCE;
+        /// Allows other hosts to publish the same records.
+        const ALLOW_MULTIPLE = avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_ALLOW_MULTIPLE;
+        /// Does not publish the reverse (PTR) records of the addresses.
+        const NO_REVERSE = avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_NO_REVERSE;
+        /// Does not add the service cookie to the TXT record.
+        const NO_COOKIE = avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_NO_COOKIE;
+        /// Updates the existing records instead of adding new ones.
+        const UPDATE = avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_UPDATE;
+        /// Publishes the records with wide-area (unicast) DNS.
+        const USE_WIDE_AREA = avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_USE_WIDE_AREA;
+        /// Publishes the records with multicast DNS.
+        const USE_MULTICAST = avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_USE_MULTICAST;
+    }
+}
+
+impl PublishFlags {
+    /// The flags Avahi accepts when adding a service with [`avahi_entry_group_add_service()`].
+    /// `UPDATE` is left out, it only applies to services that have already been added.
+    ///
+    /// [`avahi_entry_group_add_service()`]: https://avahi.org/doxygen/html/publish_8h.html#acb05a7d3d23a3b825ca77cb1c7d00ce4
+    pub const SERVICE: Self = Self::from_bits_truncate(
+        avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_NO_COOKIE
+            | avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_USE_WIDE_AREA
+            | avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_USE_MULTICAST,
+    );
+
+    /// The flags Avahi accepts when adding an address with [`avahi_entry_group_add_address()`].
+    /// `UPDATE` is left out, it only applies to addresses that have already been added.
+    ///
+    /// [`avahi_entry_group_add_address()`]: https://avahi.org/doxygen/html/publish_8h.html
+    pub const ADDRESS: Self = Self::from_bits_truncate(
+        avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_NO_REVERSE
+            | avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_NO_PROBE
+            | avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_NO_ANNOUNCE
+            | avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_USE_WIDE_AREA
+            | avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_USE_MULTICAST,
+    );
+
+    /// Returns these flags, or an error of kind `ErrorKind::InvalidArgument` if they include any
+    /// flag that is not `supported` (e.g. `PublishFlags::SERVICE`).
+    pub fn check(self, supported: Self) -> Result<Self> {
+        let unsupported = self - supported;
+
+        if unsupported.is_empty() {
+            Ok(self)
+        } else {
+            Err(Error::with_kind(
+                ErrorKind::InvalidArgument,
+                format!("unsupported publish flags: {:?}", unsupported),
+            ))
+        }
+    }
+}
 
 /// Wraps the `AvahiEntryGroup` type from the raw Avahi bindings.
 ///
@@ -30,8 +103,15 @@ impl ManagedAvahiEntryGroup {
     ) -> Result<Self> {
         let group = unsafe { avahi_entry_group_new(client, callback, userdata) };
         if group.is_null() {
-            let err = avahi_util::get_error(unsafe { avahi_client_errno(client) });
-            Err(format!("could not initialize AvahiEntryGroup: {}", err).into())
+            let err = unsafe { avahi_client_errno(client) };
+            Err(avahi_util::error(
+                err,
+                format!(
+                    "could not initialize AvahiEntryGroup: {}",
+                    avahi_util::get_error(err)
+                ),
+            )
+            .with_operation(Operation::Register))
         } else {
             Ok(Self(group))
         }
@@ -55,6 +135,7 @@ impl ManagedAvahiEntryGroup {
             interface,
             protocol,
             flags,
+            publish_flags,
             name,
             kind,
             domain,
@@ -68,7 +149,7 @@ impl ManagedAvahiEntryGroup {
                 self.0,
                 interface,
                 protocol,
-                flags,
+                avahi_util::publish_flags(flags) | publish_flags.bits(),
                 name,
                 kind,
                 domain,
@@ -76,10 +157,75 @@ impl ManagedAvahiEntryGroup {
                 port,
                 txt.map(|t| t.inner()).unwrap_mut_or_null()
             ),
-            "could not register service"
+            "could not register service",
+            Operation::Register
         )?;
 
-        avahi!(avahi_entry_group_commit(self.0), "could not commit service")
+        avahi!(
+            avahi_entry_group_commit(self.0),
+            "could not commit service",
+            Operation::Register
+        )
+    }
+
+    /// Delegate function for [`avahi_entry_group_add_address()`], which also commits the group.
+    ///
+    /// [`avahi_entry_group_add_address()`]: https://avahi.org/doxygen/html/publish_8h.html
+    pub fn add_address(
+        &mut self,
+        AddAddressParams {
+            interface,
+            protocol,
+            flags,
+            name,
+            address,
+        }: AddAddressParams,
+    ) -> Result<()> {
+        avahi!(
+            avahi_entry_group_add_address(self.0, interface, protocol, flags.bits(), name, address),
+            "could not publish address",
+            Operation::Register
+        )?;
+
+        avahi!(
+            avahi_entry_group_commit(self.0),
+            "could not commit address",
+            Operation::Register
+        )
+    }
+
+    /// Delegate function for [`avahi_entry_group_add_record()`].
+    ///
+    /// [`avahi_entry_group_add_record()`]: https://avahi.org/doxygen/html/publish_8h.html
+    pub fn add_record(
+        &mut self,
+        AddRecordParams {
+            interface,
+            protocol,
+            flags,
+            name,
+            clazz,
+            kind,
+            ttl,
+            rdata,
+        }: AddRecordParams,
+    ) -> Result<()> {
+        avahi!(
+            avahi_entry_group_add_record(
+                self.0,
+                interface,
+                protocol,
+                flags,
+                name,
+                clazz,
+                kind,
+                ttl,
+                rdata.as_ptr() as *const c_void,
+                rdata.len(),
+            ),
+            "could not add record",
+            Operation::Register
+        )
     }
 
     /// Delegate function for [`avahi_entry_group_reset()`].
@@ -88,6 +234,32 @@ impl ManagedAvahiEntryGroup {
     pub fn reset(&mut self) {
         unsafe { avahi_entry_group_reset(self.0) };
     }
+
+    /// Delegate function for [`avahi_entry_group_get_client()`].
+    ///
+    /// [`avahi_entry_group_get_client()`]: https://avahi.org/doxygen/html/publish_8h.html
+    pub fn get_client(&self) -> *mut AvahiClient {
+        unsafe { avahi_entry_group_get_client(self.0) }
+    }
+
+    /// Frees the group with [`avahi_entry_group_free()`], which withdraws its services,
+    /// propagating any error returned into a `Result` rather than ignoring it as on `trait Drop`.
+    ///
+    /// [`avahi_entry_group_free()`]: https://avahi.org/doxygen/html/publish_8h.html
+    pub fn free(self) -> Result<()> {
+        let group = self.0;
+        mem::forget(self);
+
+        avahi!(
+            avahi_entry_group_free(group),
+            "could not free entry group",
+            Operation::Register
+        )
+    }
+
+    pub(super) fn inner(&self) -> *mut AvahiEntryGroup {
+        self.0
+    }
 }
 
 impl Drop for ManagedAvahiEntryGroup {
@@ -118,11 +290,85 @@ pub struct ManagedAvahiEntryGroupParams {
 pub struct AddServiceParams<'a> {
     interface: AvahiIfIndex,
     protocol: AvahiProtocol,
-    flags: AvahiPublishFlags,
+    #[builder(default)]
+    flags: ServiceFlags,
+    #[builder(default)]
+    publish_flags: PublishFlags,
     name: *const c_char,
     kind: *const c_char,
+    /// The default domain if null
+    #[builder(default = "ptr::null()")]
     domain: *const c_char,
+    /// The host name of the machine if null
+    #[builder(default = "ptr::null()")]
     host: *const c_char,
     port: u16,
+    #[builder(default)]
     txt: Option<&'a ManagedAvahiStringList>,
 }
+
+/// Holds parameters for `ManagedAvahiEntryGroup::add_address()`.
+///
+/// See [`avahi_entry_group_add_address()`] for more information about these parameters.
+///
+/// [`avahi_entry_group_add_address()`]: https://avahi.org/doxygen/html/publish_8h.html
+#[derive(Builder, BuilderDelegate)]
+pub struct AddAddressParams<'a> {
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    #[builder(default)]
+    flags: PublishFlags,
+    name: *const c_char,
+    address: &'a AvahiAddress,
+}
+
+/// Holds parameters for `ManagedAvahiEntryGroup::add_record()`.
+///
+/// See [`avahi_entry_group_add_record()`] for more information about these parameters.
+///
+/// [`avahi_entry_group_add_record()`]: https://avahi.org/doxygen/html/publish_8h.html
+#[derive(Builder, BuilderDelegate)]
+pub struct AddRecordParams<'a> {
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    flags: AvahiPublishFlags,
+    name: *const c_char,
+    clazz: u16,
+    kind: u16,
+    ttl: u32,
+    rdata: &'a [u8],
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_accepts_supported_flags() {
+        let flags = PublishFlags::NO_COOKIE | PublishFlags::USE_MULTICAST;
+        assert_eq!(flags.check(PublishFlags::SERVICE).unwrap(), flags);
+        assert_eq!(
+            PublishFlags::NO_REVERSE
+                .check(PublishFlags::ADDRESS)
+                .unwrap(),
+            PublishFlags::NO_REVERSE
+        );
+    }
+
+    #[test]
+    fn check_rejects_unsupported_flags() {
+        for flags in &[
+            PublishFlags::NO_PROBE,
+            PublishFlags::UNIQUE,
+            PublishFlags::UPDATE,
+            PublishFlags::NO_REVERSE | PublishFlags::USE_MULTICAST,
+        ] {
+            let error = flags.check(PublishFlags::SERVICE).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::InvalidArgument);
+        }
+
+        assert!(PublishFlags::NO_COOKIE
+            .check(PublishFlags::ADDRESS)
+            .is_err());
+    }
+}
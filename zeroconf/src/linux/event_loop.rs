@@ -3,6 +3,7 @@
 use super::poll::ManagedAvahiSimplePoll;
 use crate::event_loop::TEventLoop;
 use crate::Result;
+use std::convert::TryFrom;
 use std::marker::PhantomData;
 use std::sync::Arc;
 use std::time::Duration;
@@ -10,16 +11,54 @@ use std::time::Duration;
 #[derive(new)]
 pub struct AvahiEventLoop<'a> {
     poll: Arc<ManagedAvahiSimplePoll>,
+    #[new(default)]
+    on_poll: Option<Box<dyn Fn() + 'a>>,
+    #[new(default)]
+    on_drop: Option<Box<dyn Fn() + 'a>>,
     phantom: PhantomData<&'a ManagedAvahiSimplePoll>,
 }
 
+impl<'a> AvahiEventLoop<'a> {
+    /// Sets a function to invoke after each iteration of the event loop. This is used to perform
+    /// periodic work that is not driven by Avahi callbacks.
+    pub(crate) fn with_on_poll(mut self, on_poll: Box<dyn Fn() + 'a>) -> Self {
+        self.on_poll = Some(on_poll);
+        self
+    }
+
+    /// Sets a function to invoke when the event loop is dropped. This is used to free the objects
+    /// of a client shared by the thread, which must not outlive the event loop.
+    // `avahi-core` runs its own server instead of connecting to a client
+    #[cfg_attr(feature = "avahi-core", allow(dead_code))]
+    pub(crate) fn with_on_drop(mut self, on_drop: Box<dyn Fn() + 'a>) -> Self {
+        self.on_drop = Some(on_drop);
+        self
+    }
+}
+
 impl<'a> TEventLoop for AvahiEventLoop<'a> {
     /// Polls for new events.
     ///
-    /// Internally calls `ManagedAvahiSimplePoll::iterate(0)`, the `timeout` parameter does not
-    /// currently do anything in the Avahi implementation.
-    fn poll(&self, _timeout: Duration) -> Result<()> {
-        self.poll.iterate(0);
+    /// Internally calls `ManagedAvahiSimplePoll::iterate()`, which waits for events for at most
+    /// `PollIntervals::simple_poll_sleep()` or the specified timeout, whichever is shorter. By
+    /// default it does not wait at all.
+    fn poll(&self, timeout: Duration) -> Result<()> {
+        let sleep = timeout.min(*crate::poll_intervals().simple_poll_sleep());
+        self.poll
+            .iterate(i32::try_from(sleep.as_millis()).unwrap_or(i32::MAX));
+
+        if let Some(f) = &self.on_poll {
+            f();
+        }
+
         Ok(())
     }
 }
+
+impl<'a> Drop for AvahiEventLoop<'a> {
+    fn drop(&mut self) {
+        if let Some(f) = &self.on_drop {
+            f();
+        }
+    }
+}
@@ -0,0 +1,193 @@
+//! Avahi implementation for cross-platform host name resolver
+
+use super::avahi_util;
+use super::client::{ManagedAvahiClient, ManagedAvahiClientParams};
+use super::poll::ManagedAvahiSimplePoll;
+use super::resolver::{ManagedAvahiHostNameResolver, ManagedAvahiHostNameResolverParams};
+use crate::error::Error;
+use crate::ffi::{self, c_str, FromRaw, SharedContext};
+use crate::prelude::*;
+use crate::{AddressFamily, EventLoop, NetworkInterface, Result, ServiceFlags};
+use crate::{HostNameResolution, HostNameResolvedCallback};
+use avahi_sys::{
+    AvahiAddress, AvahiClient, AvahiClientFlags, AvahiClientState, AvahiHostNameResolver,
+    AvahiIfIndex, AvahiLookupResultFlags, AvahiProtocol, AvahiResolverEvent,
+};
+use libc::{c_char, c_void};
+use std::any::Any;
+use std::ffi::CString;
+use std::fmt;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub struct AvahiMdnsHostNameResolver {
+    client: Option<ManagedAvahiClient>,
+    poll: Option<Arc<ManagedAvahiSimplePoll>>,
+    resolver: Option<ManagedAvahiHostNameResolver>,
+    host_name: CString,
+    interface_index: AvahiIfIndex,
+    address_family: AddressFamily,
+    protocol: AvahiProtocol,
+    flags: ServiceFlags,
+    context: SharedContext<AvahiHostNameResolverContext>,
+}
+
+impl THostNameResolver for AvahiMdnsHostNameResolver {
+    fn new(host_name: &str) -> Self {
+        Self {
+            client: None,
+            poll: None,
+            resolver: None,
+            host_name: c_string!(host_name),
+            interface_index: avahi_sys::AVAHI_IF_UNSPEC,
+            address_family: AddressFamily::default(),
+            protocol: avahi_sys::AVAHI_PROTO_UNSPEC,
+            flags: ServiceFlags::empty(),
+            context: SharedContext::default(),
+        }
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface_index = avahi_util::interface_index(interface);
+    }
+
+    fn set_address_family(&mut self, address_family: AddressFamily) {
+        self.address_family = address_family;
+    }
+
+    fn set_flags(&mut self, flags: ServiceFlags) {
+        self.flags = flags;
+    }
+
+    fn set_host_name_resolved_callback(
+        &mut self,
+        host_name_resolved_callback: Box<HostNameResolvedCallback>,
+    ) {
+        unsafe {
+            (*self.context.as_ptr()).host_name_resolved_callback = Some(host_name_resolved_callback)
+        };
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        unsafe { (*self.context.as_ptr()).user_context = Some(Arc::from(context)) };
+    }
+
+    fn resolve(&mut self) -> Result<EventLoop<'_>> {
+        debug!("Resolving host name: {:?}", self);
+
+        self.poll = Some(ManagedAvahiSimplePoll::new_shared()?);
+
+        self.client = Some(ManagedAvahiClient::new(
+            ManagedAvahiClientParams::builder()
+                .poll(self.poll.as_ref().unwrap())
+                .flags(AvahiClientFlags(0))
+                .callback(Some(client_callback))
+                .userdata(self.context.as_ptr() as *mut c_void)
+                .build()?,
+        )?);
+
+        self.resolver = Some(ManagedAvahiHostNameResolver::new(
+            ManagedAvahiHostNameResolverParams::builder()
+                .client(self.client.as_ref().unwrap())
+                .interface(self.interface_index)
+                .protocol(self.protocol)
+                .name(self.host_name.as_ptr())
+                .aprotocol(avahi_util::protocol(self.address_family))
+                .flags(self.flags)
+                .callback(Some(resolve_callback))
+                .userdata(self.context.as_ptr() as *mut c_void)
+                .build()?,
+        )?);
+
+        Ok(EventLoop::new(self.poll.as_ref().unwrap().clone()))
+    }
+}
+
+impl AvahiMdnsHostNameResolver {
+    /// Sets the IP protocol the mDNS queries of the resolver are sent over, e.g.
+    /// `AddressFamily::Ipv6` to skip the IPv4 queries on IPv6-only networks. Defaults to
+    /// `AddressFamily::Both`.
+    ///
+    /// Unlike `set_address_family()`, which selects the addresses the host name is resolved to,
+    /// this selects the transport it is resolved over.
+    pub fn set_protocol(&mut self, protocol: AddressFamily) {
+        self.protocol = avahi_util::protocol(protocol);
+    }
+}
+
+impl Drop for AvahiMdnsHostNameResolver {
+    fn drop(&mut self) {
+        // resolver must be freed before the client
+        self.resolver = None;
+        self.client = None;
+    }
+}
+
+#[derive(Default, FromRaw)]
+struct AvahiHostNameResolverContext {
+    host_name_resolved_callback: Option<Box<HostNameResolvedCallback>>,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl AvahiHostNameResolverContext {
+    fn invoke_callback(&self, result: Result<HostNameResolution>) {
+        if let Some(f) = &self.host_name_resolved_callback {
+            f(result, self.user_context.clone());
+        } else {
+            warn!("attempted to invoke host name resolver callback but none was set");
+        }
+    }
+}
+
+impl fmt::Debug for AvahiHostNameResolverContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AvahiHostNameResolverContext").finish()
+    }
+}
+
+unsafe extern "C" fn resolve_callback(
+    _resolver: *mut AvahiHostNameResolver,
+    interface: AvahiIfIndex,
+    _protocol: AvahiProtocol,
+    event: AvahiResolverEvent,
+    name: *const c_char,
+    addr: *const AvahiAddress,
+    _flags: AvahiLookupResultFlags,
+    userdata: *mut c_void,
+) {
+    let name = c_str::raw_to_str(name);
+    let context = AvahiHostNameResolverContext::from_raw(userdata);
+
+    match event {
+        avahi_sys::AvahiResolverEvent_AVAHI_RESOLVER_FAILURE => {
+            context.invoke_callback(Err(format!("failed to resolve host name `{}`", name).into()));
+        }
+        avahi_sys::AvahiResolverEvent_AVAHI_RESOLVER_FOUND => {
+            let address = avahi_util::avahi_address_to_ip(addr);
+
+            let result = HostNameResolution::builder()
+                .host_name(name.to_string())
+                .address(address)
+                .scope_id(ffi::link_local_scope_id(&address, interface as u32))
+                .build()
+                .map_err(Error::from);
+
+            debug!("Host name resolved: {:?}", result);
+
+            context.invoke_callback(result);
+        }
+        _ => {}
+    };
+}
+
+unsafe extern "C" fn client_callback(
+    _client: *mut AvahiClient,
+    state: AvahiClientState,
+    userdata: *mut c_void,
+) {
+    let context = AvahiHostNameResolverContext::from_raw(userdata);
+
+    if state == avahi_sys::AvahiClientState_AVAHI_CLIENT_FAILURE {
+        context.invoke_callback(Err("client failure".into()));
+    }
+}
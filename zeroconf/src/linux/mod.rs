@@ -6,14 +6,21 @@
 //! [Bonjour]: https://en.wikipedia.org/wiki/Bonjour_(software)
 //! [Avahi]: https://en.wikipedia.org/wiki/Avahi_(software)
 
+pub mod address_publisher;
+pub mod address_resolver;
 pub mod avahi_util;
 pub mod browser;
 pub mod client;
+pub mod domain_browser;
 pub mod entry_group;
 pub mod event_loop;
+pub mod host_name_resolver;
 pub mod poll;
 pub mod raw_browser;
+pub mod record_query;
+pub mod registration_handle;
 pub mod resolver;
 pub mod service;
+pub mod service_resolver;
 pub mod string_list;
 pub mod txt_record;
@@ -5,6 +5,7 @@ use avahi_sys::{
     avahi_simple_poll_free, avahi_simple_poll_iterate, avahi_simple_poll_loop,
     avahi_simple_poll_new, AvahiSimplePoll,
 };
+use std::sync::Arc;
 
 /// Wraps the `AvahiSimplePoll` type from the raw Avahi bindings.
 ///
@@ -25,6 +26,16 @@ impl ManagedAvahiSimplePoll {
         }
     }
 
+    /// Initializes a new `ManagedAvahiSimplePoll` to be shared by the Avahi objects it polls and
+    /// the `EventLoop` polling them.
+    // SAFETY: the poll is not thread-safe, but it is only used by one thread at a time: it is
+    // handed to another thread only along with all of the objects it is shared by (i.e. when the
+    // browser or service owning them is sent), and its event loop is not `Send`.
+    #[allow(clippy::arc_with_non_send_sync)]
+    pub fn new_shared() -> Result<Arc<Self>> {
+        Self::new().map(Arc::new)
+    }
+
     /// Delegate function for [`avahi_simple_poll_loop()`].
     ///
     /// [`avahi_simple_poll_loop()`]: https://avahi.org/doxygen/html/simple-watch_8h.html#a14b4cb29832e8c3de609d4c4e5611985
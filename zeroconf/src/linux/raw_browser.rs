@@ -1,12 +1,19 @@
-//! Rust friendly `AvahiServiceBrowser` wrappers/helpers
+//! Rust friendly `AvahiServiceBrowser`, `AvahiRecordBrowser` and `AvahiDomainBrowser`
+//! wrappers/helpers
 
+use super::avahi_util;
 use super::client::ManagedAvahiClient;
-use crate::Result;
+use crate::error::Operation;
+use crate::{Result, ServiceFlags};
 use avahi_sys::{
-    avahi_service_browser_free, avahi_service_browser_new, AvahiIfIndex, AvahiLookupFlags,
-    AvahiProtocol, AvahiServiceBrowser, AvahiServiceBrowserCallback,
+    avahi_domain_browser_free, avahi_domain_browser_new, avahi_record_browser_free,
+    avahi_record_browser_new, avahi_service_browser_free, avahi_service_browser_new,
+    AvahiDomainBrowser, AvahiDomainBrowserCallback, AvahiDomainBrowserType, AvahiIfIndex,
+    AvahiProtocol, AvahiRecordBrowser, AvahiRecordBrowserCallback, AvahiServiceBrowser,
+    AvahiServiceBrowserCallback,
 };
 use libc::{c_char, c_void};
+use std::mem;
 
 /// Wraps the `AvahiServiceBrowser` type from the raw Avahi bindings.
 ///
@@ -37,7 +44,7 @@ impl ManagedAvahiServiceBrowser {
                 protocol,
                 kind,
                 domain,
-                flags,
+                avahi_util::lookup_flags(flags),
                 callback,
                 userdata,
             )
@@ -49,6 +56,21 @@ impl ManagedAvahiServiceBrowser {
             Ok(Self(browser))
         }
     }
+
+    /// Frees the browser with [`avahi_service_browser_free()`], propagating any error returned
+    /// into a `Result` rather than ignoring it as on `trait Drop`.
+    ///
+    /// [`avahi_service_browser_free()`]: https://avahi.org/doxygen/html/lookup_8h.html
+    pub fn free(self) -> Result<()> {
+        let browser = self.0;
+        mem::forget(self);
+
+        avahi!(
+            avahi_service_browser_free(browser),
+            "could not free service browser",
+            Operation::Browse
+        )
+    }
 }
 
 impl Drop for ManagedAvahiServiceBrowser {
@@ -70,7 +92,151 @@ pub struct ManagedAvahiServiceBrowserParams<'a> {
     protocol: AvahiProtocol,
     kind: *const c_char,
     domain: *const c_char,
-    flags: AvahiLookupFlags,
+    flags: ServiceFlags,
     callback: AvahiServiceBrowserCallback,
     userdata: *mut c_void,
 }
+
+/// Wraps the `AvahiRecordBrowser` type from the raw Avahi bindings.
+///
+/// This struct allocates a new `*mut AvahiRecordBrowser` when `ManagedAvahiRecordBrowser::new()`
+/// is invoked and calls the Avahi function responsible for freeing the browser on `trait Drop`.
+#[derive(Debug)]
+pub struct ManagedAvahiRecordBrowser(*mut AvahiRecordBrowser);
+
+impl ManagedAvahiRecordBrowser {
+    /// Intializes the underlying `*mut AvahiRecordBrowser` and verifies it was created; returning
+    /// `Err(String)` if unsuccessful.
+    pub fn new(
+        ManagedAvahiRecordBrowserParams {
+            client,
+            interface,
+            protocol,
+            name,
+            clazz,
+            kind,
+            flags,
+            callback,
+            userdata,
+        }: ManagedAvahiRecordBrowserParams,
+    ) -> Result<Self> {
+        let browser = unsafe {
+            avahi_record_browser_new(
+                client.inner(),
+                interface,
+                protocol,
+                name,
+                clazz,
+                kind,
+                avahi_util::lookup_flags(flags),
+                callback,
+                userdata,
+            )
+        };
+
+        if browser.is_null() {
+            Err("could not initialize Avahi record browser".into())
+        } else {
+            Ok(Self(browser))
+        }
+    }
+}
+
+impl ManagedAvahiRecordBrowser {
+    pub(super) fn inner(&self) -> *mut AvahiRecordBrowser {
+        self.0
+    }
+}
+
+impl Drop for ManagedAvahiRecordBrowser {
+    fn drop(&mut self) {
+        unsafe { avahi_record_browser_free(self.0) };
+    }
+}
+
+/// Holds parameters for initializing a new `ManagedAvahiRecordBrowser` with
+/// `ManagedAvahiRecordBrowser::new()`.
+///
+/// See [`avahi_record_browser_new()`] for more information about these parameters.
+///
+/// [`avahi_record_browser_new()`]: https://avahi.org/doxygen/html/lookup_8h.html
+#[derive(Builder, BuilderDelegate)]
+pub struct ManagedAvahiRecordBrowserParams<'a> {
+    client: &'a ManagedAvahiClient,
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    name: *const c_char,
+    clazz: u16,
+    kind: u16,
+    flags: ServiceFlags,
+    callback: AvahiRecordBrowserCallback,
+    userdata: *mut c_void,
+}
+
+/// Wraps the `AvahiDomainBrowser` type from the raw Avahi bindings.
+///
+/// This struct allocates a new `*mut AvahiDomainBrowser` when `ManagedAvahiDomainBrowser::new()`
+/// is invoked and calls the Avahi function responsible for freeing the browser on `trait Drop`.
+#[derive(Debug)]
+pub struct ManagedAvahiDomainBrowser(*mut AvahiDomainBrowser);
+
+impl ManagedAvahiDomainBrowser {
+    /// Intializes the underlying `*mut AvahiDomainBrowser` and verifies it was created; returning
+    /// `Err(String)` if unsuccessful.
+    pub fn new(
+        ManagedAvahiDomainBrowserParams {
+            client,
+            interface,
+            protocol,
+            domain,
+            btype,
+            flags,
+            callback,
+            userdata,
+        }: ManagedAvahiDomainBrowserParams,
+    ) -> Result<Self> {
+        let browser = unsafe {
+            avahi_domain_browser_new(
+                client.inner(),
+                interface,
+                protocol,
+                domain,
+                btype,
+                avahi_util::lookup_flags(flags),
+                callback,
+                userdata,
+            )
+        };
+
+        if browser.is_null() {
+            Err("could not initialize Avahi domain browser".into())
+        } else {
+            Ok(Self(browser))
+        }
+    }
+}
+
+impl Drop for ManagedAvahiDomainBrowser {
+    fn drop(&mut self) {
+        unsafe { avahi_domain_browser_free(self.0) };
+    }
+}
+
+/// Holds parameters for initializing a new `ManagedAvahiDomainBrowser` with
+/// `ManagedAvahiDomainBrowser::new()`.
+///
+/// See [`avahi_domain_browser_new()`] for more information about these parameters.
+///
+/// [`avahi_domain_browser_new()`]: https://avahi.org/doxygen/html/lookup_8h.html
+#[derive(Builder, BuilderDelegate)]
+pub struct ManagedAvahiDomainBrowserParams<'a> {
+    client: &'a ManagedAvahiClient,
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    /// The default domain if null
+    domain: *const c_char,
+    btype: AvahiDomainBrowserType,
+    flags: ServiceFlags,
+    callback: AvahiDomainBrowserCallback,
+    userdata: *mut c_void,
+}
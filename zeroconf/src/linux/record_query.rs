@@ -0,0 +1,185 @@
+//! Avahi implementation for cross-platform DNS record query
+
+use super::avahi_util;
+use super::client::{ManagedAvahiClient, ManagedAvahiClientParams};
+use super::poll::ManagedAvahiSimplePoll;
+use super::raw_browser::{ManagedAvahiRecordBrowser, ManagedAvahiRecordBrowserParams};
+use crate::ffi::{c_str, FromRaw, SharedContext};
+use crate::prelude::*;
+use crate::{dns, EventLoop, NetworkInterface, Result, ServiceFlags};
+use crate::{Record, RecordReceivedCallback};
+use avahi_sys::{
+    AvahiBrowserEvent, AvahiClient, AvahiClientFlags, AvahiClientState, AvahiIfIndex,
+    AvahiLookupResultFlags, AvahiProtocol, AvahiRecordBrowser,
+};
+use libc::{c_char, c_void};
+use std::any::Any;
+use std::ffi::CString;
+use std::fmt;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub struct AvahiMdnsRecordQuery {
+    client: Option<ManagedAvahiClient>,
+    poll: Option<Arc<ManagedAvahiSimplePoll>>,
+    browser: Option<ManagedAvahiRecordBrowser>,
+    name: CString,
+    rrtype: u16,
+    rrclass: u16,
+    interface_index: AvahiIfIndex,
+    flags: ServiceFlags,
+    context: SharedContext<AvahiRecordQueryContext>,
+}
+
+impl TRecordQuery for AvahiMdnsRecordQuery {
+    fn new(name: &str, rrtype: u16) -> Self {
+        Self {
+            client: None,
+            poll: None,
+            browser: None,
+            name: c_string!(name),
+            rrtype,
+            rrclass: dns::CLASS_IN,
+            interface_index: avahi_sys::AVAHI_IF_UNSPEC,
+            flags: ServiceFlags::empty(),
+            context: SharedContext::default(),
+        }
+    }
+
+    fn set_class(&mut self, rrclass: u16) {
+        self.rrclass = rrclass;
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface_index = avahi_util::interface_index(interface);
+    }
+
+    fn set_flags(&mut self, flags: ServiceFlags) {
+        self.flags = flags;
+    }
+
+    fn set_record_received_callback(
+        &mut self,
+        record_received_callback: Box<RecordReceivedCallback>,
+    ) {
+        unsafe {
+            (*self.context.as_ptr()).record_received_callback = Some(record_received_callback)
+        };
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        unsafe { (*self.context.as_ptr()).user_context = Some(Arc::from(context)) };
+    }
+
+    fn query(&mut self) -> Result<EventLoop<'_>> {
+        debug!("Querying records: {:?}", self);
+
+        self.poll = Some(ManagedAvahiSimplePoll::new_shared()?);
+
+        self.client = Some(ManagedAvahiClient::new(
+            ManagedAvahiClientParams::builder()
+                .poll(self.poll.as_ref().unwrap())
+                .flags(AvahiClientFlags(0))
+                .callback(Some(client_callback))
+                .userdata(self.context.as_ptr() as *mut c_void)
+                .build()?,
+        )?);
+
+        self.browser = Some(ManagedAvahiRecordBrowser::new(
+            ManagedAvahiRecordBrowserParams::builder()
+                .client(self.client.as_ref().unwrap())
+                .interface(self.interface_index)
+                .protocol(avahi_sys::AVAHI_PROTO_UNSPEC)
+                .name(self.name.as_ptr())
+                .clazz(self.rrclass)
+                .kind(self.rrtype)
+                .flags(self.flags)
+                .callback(Some(browse_callback))
+                .userdata(self.context.as_ptr() as *mut c_void)
+                .build()?,
+        )?);
+
+        Ok(EventLoop::new(self.poll.as_ref().unwrap().clone()))
+    }
+}
+
+impl Drop for AvahiMdnsRecordQuery {
+    fn drop(&mut self) {
+        // browser must be freed before the client
+        self.browser = None;
+        self.client = None;
+    }
+}
+
+#[derive(Default, FromRaw)]
+struct AvahiRecordQueryContext {
+    record_received_callback: Option<Box<RecordReceivedCallback>>,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl AvahiRecordQueryContext {
+    fn invoke_callback(&self, result: Result<Record>) {
+        if let Some(f) = &self.record_received_callback {
+            f(result, self.user_context.clone());
+        } else {
+            warn!("attempted to invoke record query callback but none was set");
+        }
+    }
+}
+
+impl fmt::Debug for AvahiRecordQueryContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AvahiRecordQueryContext").finish()
+    }
+}
+
+unsafe extern "C" fn browse_callback(
+    _browser: *mut AvahiRecordBrowser,
+    _interface: AvahiIfIndex,
+    _protocol: AvahiProtocol,
+    event: AvahiBrowserEvent,
+    name: *const c_char,
+    clazz: u16,
+    kind: u16,
+    rdata: *const c_void,
+    size: usize,
+    _flags: AvahiLookupResultFlags,
+    userdata: *mut c_void,
+) {
+    let context = AvahiRecordQueryContext::from_raw(userdata);
+
+    match event {
+        avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_NEW => {
+            let result = Record::builder()
+                .name(c_str::copy_raw(name))
+                .rrtype(kind)
+                .rrclass(clazz)
+                .rdata(avahi_util::rdata(rdata, size).to_vec())
+                .build();
+
+            match result {
+                Ok(record) => {
+                    debug!("Record received: {:?}", record);
+                    context.invoke_callback(Ok(record));
+                }
+                Err(e) => context.invoke_callback(Err(e.into())),
+            }
+        }
+        avahi_sys::AvahiBrowserEvent_AVAHI_BROWSER_FAILURE => {
+            context.invoke_callback(Err("record browser failure".into()))
+        }
+        _ => {}
+    };
+}
+
+unsafe extern "C" fn client_callback(
+    _client: *mut AvahiClient,
+    state: AvahiClientState,
+    userdata: *mut c_void,
+) {
+    let context = AvahiRecordQueryContext::from_raw(userdata);
+
+    if state == avahi_sys::AvahiClientState_AVAHI_CLIENT_FAILURE {
+        context.invoke_callback(Err("client failure".into()));
+    }
+}
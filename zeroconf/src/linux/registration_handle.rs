@@ -0,0 +1,39 @@
+//! Avahi implementation for cross-platform registration handle
+
+use super::event_loop::AvahiEventLoop;
+use super::service::AvahiMdnsService;
+use crate::prelude::*;
+use crate::registration_handle::TRegistrationHandle;
+use crate::service;
+use crate::Result;
+use std::time::Duration;
+
+#[derive(new)]
+pub struct AvahiRegistrationHandle<'a> {
+    service: *mut AvahiMdnsService,
+    event_loop: AvahiEventLoop<'a>,
+}
+
+impl<'a> TEventLoop for AvahiRegistrationHandle<'a> {
+    fn poll(&self, timeout: Duration) -> Result<()> {
+        self.event_loop.poll(timeout)
+    }
+}
+
+impl<'a> TRegistrationHandle for AvahiRegistrationHandle<'a> {
+    fn set_name(&mut self, name: &str) -> Result<()> {
+        unsafe {
+            (*self.service).set_name(name);
+            (*self.service).reregister()
+        }
+    }
+
+    fn set_port(&mut self, port: u16) -> Result<()> {
+        service::check_port(port)?;
+
+        unsafe {
+            (*self.service).set_port(port);
+            (*self.service).reregister()
+        }
+    }
+}
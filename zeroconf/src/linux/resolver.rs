@@ -1,10 +1,14 @@
 //! Rust friendly `AvahiServiceResolver` wrappers/helpers
 
+use super::avahi_util;
 use super::client::ManagedAvahiClient;
-use crate::Result;
+use crate::{Result, ServiceFlags};
 use avahi_sys::{
-    avahi_service_resolver_free, avahi_service_resolver_new, AvahiIfIndex, AvahiLookupFlags,
-    AvahiProtocol, AvahiServiceResolver, AvahiServiceResolverCallback,
+    avahi_address_resolver_free, avahi_address_resolver_new, avahi_host_name_resolver_free,
+    avahi_host_name_resolver_new, avahi_service_resolver_free, avahi_service_resolver_new,
+    AvahiAddress, AvahiAddressResolver, AvahiAddressResolverCallback, AvahiHostNameResolver,
+    AvahiHostNameResolverCallback, AvahiIfIndex, AvahiProtocol, AvahiServiceResolver,
+    AvahiServiceResolverCallback,
 };
 use libc::{c_char, c_void};
 use std::collections::HashMap;
@@ -43,7 +47,7 @@ impl ManagedAvahiServiceResolver {
                 kind,
                 domain,
                 aprotocol,
-                flags,
+                avahi_util::resolve_flags(flags),
                 callback,
                 userdata,
             )
@@ -78,11 +82,144 @@ pub struct ManagedAvahiServiceResolverParams<'a> {
     kind: *const c_char,
     domain: *const c_char,
     aprotocol: AvahiProtocol,
-    flags: AvahiLookupFlags,
+    flags: ServiceFlags,
     callback: AvahiServiceResolverCallback,
     userdata: *mut c_void,
 }
 
+/// Wraps the `AvahiHostNameResolver` type from the raw Avahi bindings.
+///
+/// This struct allocates a new `*mut AvahiHostNameResolver` when
+/// `ManagedAvahiHostNameResolver::new()` is invoked and calls the Avahi function responsible for
+/// freeing the resolver on `trait Drop`.
+#[derive(Debug)]
+pub struct ManagedAvahiHostNameResolver(*mut AvahiHostNameResolver);
+
+impl ManagedAvahiHostNameResolver {
+    /// Intializes the underlying `*mut AvahiHostNameResolver` and verifies it was created;
+    /// returning `Err(String)` if unsuccessful.
+    pub fn new(
+        ManagedAvahiHostNameResolverParams {
+            client,
+            interface,
+            protocol,
+            name,
+            aprotocol,
+            flags,
+            callback,
+            userdata,
+        }: ManagedAvahiHostNameResolverParams,
+    ) -> Result<Self> {
+        let resolver = unsafe {
+            avahi_host_name_resolver_new(
+                client.inner(),
+                interface,
+                protocol,
+                name,
+                aprotocol,
+                avahi_util::lookup_flags(flags),
+                callback,
+                userdata,
+            )
+        };
+
+        if resolver.is_null() {
+            Err("could not initialize AvahiHostNameResolver".into())
+        } else {
+            Ok(Self(resolver))
+        }
+    }
+}
+
+impl Drop for ManagedAvahiHostNameResolver {
+    fn drop(&mut self) {
+        unsafe { avahi_host_name_resolver_free(self.0) };
+    }
+}
+
+/// Holds parameters for initializing a new `ManagedAvahiHostNameResolver` with
+/// `ManagedAvahiHostNameResolver::new()`.
+///
+/// See [`avahi_host_name_resolver_new()`] for more information about these parameters.
+///
+/// [`avahi_host_name_resolver_new()`]: https://avahi.org/doxygen/html/lookup_8h.html
+#[derive(Builder, BuilderDelegate)]
+pub struct ManagedAvahiHostNameResolverParams<'a> {
+    client: &'a ManagedAvahiClient,
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    name: *const c_char,
+    aprotocol: AvahiProtocol,
+    flags: ServiceFlags,
+    callback: AvahiHostNameResolverCallback,
+    userdata: *mut c_void,
+}
+
+/// Wraps the `AvahiAddressResolver` type from the raw Avahi bindings.
+///
+/// This struct allocates a new `*mut AvahiAddressResolver` when
+/// `ManagedAvahiAddressResolver::new()` is invoked and calls the Avahi function responsible for
+/// freeing the resolver on `trait Drop`.
+#[derive(Debug)]
+pub struct ManagedAvahiAddressResolver(*mut AvahiAddressResolver);
+
+impl ManagedAvahiAddressResolver {
+    /// Intializes the underlying `*mut AvahiAddressResolver` and verifies it was created;
+    /// returning `Err(String)` if unsuccessful.
+    pub fn new(
+        ManagedAvahiAddressResolverParams {
+            client,
+            interface,
+            protocol,
+            address,
+            flags,
+            callback,
+            userdata,
+        }: ManagedAvahiAddressResolverParams,
+    ) -> Result<Self> {
+        let resolver = unsafe {
+            avahi_address_resolver_new(
+                client.inner(),
+                interface,
+                protocol,
+                address,
+                avahi_util::lookup_flags(flags),
+                callback,
+                userdata,
+            )
+        };
+
+        if resolver.is_null() {
+            Err("could not initialize AvahiAddressResolver".into())
+        } else {
+            Ok(Self(resolver))
+        }
+    }
+}
+
+impl Drop for ManagedAvahiAddressResolver {
+    fn drop(&mut self) {
+        unsafe { avahi_address_resolver_free(self.0) };
+    }
+}
+
+/// Holds parameters for initializing a new `ManagedAvahiAddressResolver` with
+/// `ManagedAvahiAddressResolver::new()`.
+///
+/// See [`avahi_address_resolver_new()`] for more information about these parameters.
+///
+/// [`avahi_address_resolver_new()`]: https://avahi.org/doxygen/html/lookup_8h.html
+#[derive(Builder, BuilderDelegate)]
+pub struct ManagedAvahiAddressResolverParams<'a> {
+    client: &'a ManagedAvahiClient,
+    interface: AvahiIfIndex,
+    protocol: AvahiProtocol,
+    address: *const AvahiAddress,
+    flags: ServiceFlags,
+    callback: AvahiAddressResolverCallback,
+    userdata: *mut c_void,
+}
+
 #[derive(Default, Debug)]
 pub(crate) struct ServiceResolverSet {
     resolvers: HashMap<*mut AvahiServiceResolver, ManagedAvahiServiceResolver>,
@@ -96,4 +233,12 @@ impl ServiceResolverSet {
     pub fn remove_raw(&mut self, raw: *mut AvahiServiceResolver) {
         self.resolvers.remove(&raw);
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.resolvers.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.resolvers.clear();
+    }
 }
@@ -1,42 +1,55 @@
 //! Avahi implementation for cross-platform service.
 
 use super::avahi_util;
-use super::client::{self, ManagedAvahiClient, ManagedAvahiClientParams};
-use super::entry_group::{AddServiceParams, ManagedAvahiEntryGroup, ManagedAvahiEntryGroupParams};
+use super::client::{self, ClientState, ClientStateCallback, ClientSubscription, ReconnectState};
+use super::entry_group::{
+    AddRecordParams, AddServiceParams, ManagedAvahiEntryGroup, ManagedAvahiEntryGroupParams,
+    PublishFlags,
+};
 use super::poll::ManagedAvahiSimplePoll;
-use crate::ffi::{c_str, AsRaw, FromRaw, UnwrapOrNull};
+use super::registration_handle::AvahiRegistrationHandle;
+use crate::dns;
+use crate::error::{Error, ErrorKind};
+use crate::ffi::{c_str, AsRaw, FromRaw, SharedContext, UnwrapOrNull};
+use crate::network_watcher::NetworkWatcher;
 use crate::prelude::*;
+use crate::service;
 use crate::{
-    EventLoop, NetworkInterface, Result, ServiceRegisteredCallback, ServiceRegistration,
-    ServiceType, TxtRecord,
+    AddressFamily, DaemonReconnectedCallback, ErrorCallback, EventLoop, NameConflictCallback,
+    NetworkInterface, RegistrationHandle, Result, ServiceFlags, ServiceRegisteredCallback,
+    ServiceRegistration, ServiceType, TxtRecord,
 };
 use avahi_sys::{
     AvahiClient, AvahiClientFlags, AvahiClientState, AvahiEntryGroup, AvahiEntryGroupState,
-    AvahiIfIndex,
+    AvahiIfIndex, AvahiProtocol,
 };
 use libc::c_void;
 use std::any::Any;
 use std::ffi::CString;
 use std::fmt::{self, Formatter};
+use std::ptr;
 use std::str::FromStr;
 use std::sync::Arc;
 
 #[derive(Debug)]
 pub struct AvahiMdnsService {
-    client: Option<ManagedAvahiClient>,
+    client: Option<ClientSubscription>,
     poll: Option<Arc<ManagedAvahiSimplePoll>>,
-    context: *mut AvahiServiceContext,
+    context: SharedContext<AvahiServiceContext>,
 }
 
+// SAFETY: the service exclusively owns its context and the Avahi objects, which are only used
+// through `&mut self` or the event loop borrowing it, and the callbacks and the context of
+// the user it holds are `Send`. The objects of a client shared by the thread are freed with the
+// event loop, which is not `Send`.
+unsafe impl Send for AvahiMdnsService {}
+
 impl TMdnsService for AvahiMdnsService {
     fn new(service_type: ServiceType, port: u16) -> Self {
         Self {
             client: None,
             poll: None,
-            context: Box::into_raw(Box::new(AvahiServiceContext::new(
-                &service_type.to_string(),
-                port,
-            ))),
+            context: SharedContext::new(AvahiServiceContext::new(&service_type.to_string(), port)),
         }
     }
 
@@ -47,54 +60,257 @@ impl TMdnsService for AvahiMdnsService {
     ///
     /// [`AvahiClient::host_name()`]: client/struct.ManagedAvahiClient.html#method.host_name
     fn set_name(&mut self, name: &str) {
-        unsafe { (*self.context).name = Some(c_string!(name)) };
+        unsafe { (*self.context.as_ptr()).name = Some(c_string!(name)) };
     }
 
     fn set_network_interface(&mut self, interface: NetworkInterface) {
-        unsafe { (*self.context).interface_index = avahi_util::interface_index(interface) };
+        unsafe {
+            (*self.context.as_ptr()).interface_index = avahi_util::interface_index(interface)
+        };
     }
 
     fn set_domain(&mut self, domain: &str) {
-        unsafe { (*self.context).domain = Some(c_string!(domain)) };
+        unsafe { (*self.context.as_ptr()).domain = Some(c_string!(domain)) };
     }
 
     fn set_host(&mut self, host: &str) {
-        unsafe { (*self.context).host = Some(c_string!(host)) };
+        unsafe { (*self.context.as_ptr()).host = Some(c_string!(host)) };
     }
 
     fn set_txt_record(&mut self, txt_record: TxtRecord) {
-        unsafe { (*self.context).txt_record = Some(txt_record) };
+        unsafe { (*self.context.as_ptr()).txt_record = Some(txt_record) };
     }
 
     fn set_registered_callback(&mut self, registered_callback: Box<ServiceRegisteredCallback>) {
-        unsafe { (*self.context).registered_callback = Some(registered_callback) };
+        unsafe { (*self.context.as_ptr()).registered_callback = Some(registered_callback) };
     }
 
-    fn set_context(&mut self, context: Box<dyn Any>) {
-        unsafe { (*self.context).user_context = Some(Arc::from(context)) };
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>) {
+        unsafe { (*self.context.as_ptr()).error_callback = Some(error_callback) };
     }
 
-    fn register(&mut self) -> Result<EventLoop> {
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        unsafe { (*self.context.as_ptr()).user_context = Some(Arc::from(context)) };
+    }
+
+    fn set_flags(&mut self, flags: ServiceFlags) {
+        unsafe { (*self.context.as_ptr()).flags = flags };
+    }
+
+    fn set_ttl(&mut self, ttl: u32) {
+        unsafe { (*self.context.as_ptr()).ttl = Some(ttl) };
+    }
+
+    fn set_auto_reregister(&mut self, auto_reregister: bool) {
+        unsafe { (*self.context.as_ptr()).auto_reregister = auto_reregister };
+    }
+
+    fn set_reconnected_callback(&mut self, reconnected_callback: Box<DaemonReconnectedCallback>) {
+        unsafe { (*self.context.as_ptr()).reconnected_callback = Some(reconnected_callback) };
+    }
+
+    fn set_name_conflict_callback(&mut self, name_conflict_callback: Box<NameConflictCallback>) {
+        unsafe { (*self.context.as_ptr()).name_conflict_callback = Some(name_conflict_callback) };
+    }
+
+    fn register(&mut self) -> Result<RegistrationHandle<'_>> {
+        let _span = operation_span!(
+            "register",
+            service_type = unsafe { (*self.context.as_ptr()).kind.to_string_lossy() }
+        );
+
         debug!("Registering service: {:?}", self);
 
-        self.poll = Some(Arc::new(ManagedAvahiSimplePoll::new()?));
+        service::check_port(unsafe { (*self.context.as_ptr()).port })?;
 
-        self.client = Some(ManagedAvahiClient::new(
-            ManagedAvahiClientParams::builder()
-                .poll(self.poll.as_ref().unwrap())
-                .flags(AvahiClientFlags(0))
-                .callback(Some(client_callback))
-                .userdata(self.context as *mut c_void)
-                .build()?,
-        )?);
+        self.release();
+        self.poll = None;
+
+        let client = self.create_client(AvahiClientFlags(0))?;
+        self.poll = Some(client.poll().clone());
+        self.client = Some(client);
+
+        if unsafe { (*self.context.as_ptr()).auto_reregister } {
+            unsafe { (*self.context.as_ptr()).network_watcher = Some(NetworkWatcher::new()) };
+        }
+
+        let service: *mut Self = self;
+
+        let event_loop = EventLoop::new(self.poll.as_ref().unwrap().clone())
+            .with_on_poll(Box::new(move || unsafe { (*service).handle_poll() }))
+            .with_on_drop(Box::new(move || unsafe { (*service).release_shared() }));
+
+        Ok(AvahiRegistrationHandle::new(service, event_loop))
+    }
+
+    fn close(mut self) -> Result<()> {
+        let group = unsafe { (*self.context.as_ptr()).group.take() };
+        let result = group.map_or(Ok(()), ManagedAvahiEntryGroup::free);
+
+        // the group must be freed before the client
+        self.client = None;
+        self.poll = None;
+
+        result
+    }
+}
+
+impl AvahiMdnsService {
+    /// Returns the underlying `AvahiClient`, or a null pointer if the service has not been
+    /// registered yet, to call functions of `libavahi-client` this crate does not wrap.
+    ///
+    /// # Safety
+    /// The client is owned by this service, or shared with the other browsers and services of the
+    /// thread if client sharing is enabled, it must not be freed and its callback must not be
+    /// replaced. The pointer is no longer valid once the service is dropped or has reconnected to
+    /// `avahi-daemon`, or once the registration handle is dropped if the client is shared.
+    pub unsafe fn as_raw(&self) -> *mut AvahiClient {
+        self.client
+            .as_ref()
+            .map(|c| c.client().inner())
+            .unwrap_or(ptr::null_mut())
+    }
+
+    /// Returns the `AvahiEntryGroup` the service is published with, or a null pointer if it has
+    /// not been published yet, e.g. to add records to it.
+    ///
+    /// # Safety
+    /// The group is owned by this service, it must not be freed and its callback must not be
+    /// replaced. The pointer is no longer valid once the service is dropped or has reconnected to
+    /// `avahi-daemon`. The group is reset and re-committed when the service is re-registered,
+    /// which discards any records added to it.
+    pub unsafe fn as_raw_entry_group(&self) -> *mut AvahiEntryGroup {
+        (*self.context.as_ptr())
+            .group
+            .as_ref()
+            .map(|g| g.inner())
+            .unwrap_or(ptr::null_mut())
+    }
+
+    /// Sets the [`ClientStateCallback`] that is invoked each time the state of the connection to
+    /// `avahi-daemon` changes, e.g. to tell when the daemon is running and the service is about
+    /// to be announced.
+    ///
+    /// [`ClientStateCallback`]: ../client/type.ClientStateCallback.html
+    pub fn set_client_state_callback(&mut self, client_state_callback: Box<ClientStateCallback>) {
+        unsafe { (*self.context.as_ptr()).client_state_callback = Some(client_state_callback) };
+    }
+
+    /// Sets the Avahi-specific [`PublishFlags`] to register the service with, in addition to the
+    /// flags set with `set_flags()`, e.g. to leave out the service cookie with `NO_COOKIE`.
+    ///
+    /// Returns an error of kind `ErrorKind::InvalidArgument` if the flags include any that Avahi
+    /// does not accept for services, i.e. any not in `PublishFlags::SERVICE`.
+    ///
+    /// [`PublishFlags`]: ../entry_group/struct.PublishFlags.html
+    pub fn set_publish_flags(&mut self, publish_flags: PublishFlags) -> Result<()> {
+        let publish_flags = publish_flags.check(PublishFlags::SERVICE)?;
+        unsafe { (*self.context.as_ptr()).publish_flags = publish_flags };
+        Ok(())
+    }
+
+    /// Sets the IP protocol the service is announced over, e.g. `AddressFamily::Ipv6` to only
+    /// announce it on IPv6-only networks. Defaults to `AddressFamily::Both`.
+    pub fn set_protocol(&mut self, protocol: AddressFamily) {
+        unsafe { (*self.context.as_ptr()).protocol = avahi_util::protocol(protocol) };
+    }
+
+    fn create_client(&self, flags: AvahiClientFlags) -> Result<ClientSubscription> {
+        ClientSubscription::new(
+            self.poll.as_ref(),
+            flags,
+            Some(client_callback),
+            self.context.as_ptr() as *mut c_void,
+        )
+    }
+
+    /// Frees the Avahi objects of the service if its client is shared by the thread.
+    fn release_shared(&mut self) {
+        if matches!(&self.client, Some(c) if c.is_shared()) {
+            self.release();
+        }
+    }
+
+    fn release(&mut self) {
+        // the group must be freed before the client
+        unsafe { (*self.context.as_ptr()).group = None };
+        self.client = None;
+    }
+
+    pub(crate) fn set_port(&mut self, port: u16) {
+        unsafe { (*self.context.as_ptr()).port = port };
+    }
+
+    /// Re-registers the service with its current configuration. If the client is not running,
+    /// the service is registered once it is.
+    pub(crate) fn reregister(&mut self) -> Result<()> {
+        let client = match &self.client {
+            Some(c) => c.client().inner(),
+            None => return Ok(()),
+        };
+
+        unsafe {
+            if client::get_state(client) != avahi_sys::AvahiClientState_AVAHI_CLIENT_S_RUNNING {
+                return Ok(());
+            }
+
+            let context = &mut *self.context.as_ptr();
+
+            if let Some(g) = &mut context.group {
+                g.reset();
+            }
+
+            create_service(client, context)
+        }
+    }
+
+    unsafe fn handle_poll(&mut self) {
+        let context = &mut *self.context.as_ptr();
+
+        if context.reconnect == ReconnectState::Pending {
+            self.handle_reconnect();
+            return;
+        }
 
-        Ok(EventLoop::new(self.poll.as_ref().unwrap().clone()))
+        let changed = match &mut context.network_watcher {
+            Some(w) => w.poll_changed(),
+            None => false,
+        };
+
+        if changed {
+            debug!("Network configuration changed, re-registering service");
+
+            if let Err(e) = self.reregister() {
+                context.invoke_callback(Err(e));
+            }
+        }
+    }
+
+    unsafe fn handle_reconnect(&mut self) {
+        debug!("Reconnecting to avahi-daemon");
+
+        self.release();
+
+        let context = &mut *self.context.as_ptr();
+
+        // the client waits for the daemon to become available instead of failing, the service is
+        // re-created once it is running
+        context.reconnect = ReconnectState::Connecting;
+
+        match self.create_client(AvahiClientFlags::AVAHI_CLIENT_NO_FAIL) {
+            Ok(client) => self.client = Some(client),
+            Err(e) => {
+                context.reconnect = ReconnectState::Idle;
+                context.invoke_callback(Err(e));
+            }
+        }
     }
 }
 
 impl Drop for AvahiMdnsService {
     fn drop(&mut self) {
-        unsafe { Box::from_raw(self.context) };
+        // the entry group must be freed before the client, the context is dropped last
+        unsafe { (*self.context.as_ptr()).group = None };
     }
 }
 
@@ -109,7 +325,19 @@ struct AvahiServiceContext {
     domain: Option<CString>,
     host: Option<CString>,
     registered_callback: Option<Box<ServiceRegisteredCallback>>,
-    user_context: Option<Arc<dyn Any>>,
+    error_callback: Option<Box<ErrorCallback>>,
+    reconnected_callback: Option<Box<DaemonReconnectedCallback>>,
+    name_conflict_callback: Option<Box<NameConflictCallback>>,
+    client_state_callback: Option<Box<ClientStateCallback>>,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+    flags: ServiceFlags,
+    publish_flags: PublishFlags,
+    /// The IP protocol the service is announced over
+    protocol: AvahiProtocol,
+    ttl: Option<u32>,
+    auto_reregister: bool,
+    network_watcher: Option<NetworkWatcher>,
+    reconnect: ReconnectState,
 }
 
 impl AvahiServiceContext {
@@ -124,15 +352,47 @@ impl AvahiServiceContext {
             domain: None,
             host: None,
             registered_callback: None,
+            error_callback: None,
+            reconnected_callback: None,
+            name_conflict_callback: None,
+            client_state_callback: None,
             user_context: None,
+            flags: ServiceFlags::empty(),
+            publish_flags: PublishFlags::empty(),
+            protocol: avahi_sys::AVAHI_PROTO_UNSPEC,
+            ttl: None,
+            auto_reregister: false,
+            network_watcher: None,
+            reconnect: ReconnectState::Idle,
+        }
+    }
+
+    /// Returns the configured domain, or the default domain of the daemon if none was set
+    unsafe fn domain(&self, client: *mut AvahiClient) -> Result<String> {
+        match &self.domain {
+            Some(d) => Ok(c_str::copy_raw(d.as_ptr())),
+            None => Ok(client::get_domain_name(client)?.to_string()),
         }
     }
 
     fn invoke_callback(&self, result: Result<ServiceRegistration>) {
-        if let Some(f) = &self.registered_callback {
-            f(result, self.user_context.clone());
-        } else {
-            panic!("attempted to invoke service callback but none was set");
+        match (result, &self.error_callback, &self.registered_callback) {
+            (Err(e), Some(f), _) => f(e, self.user_context.clone()),
+            (result, _, Some(f)) => f(result, self.user_context.clone()),
+            _ => warn!("attempted to invoke service callback but none was set"),
+        }
+    }
+
+    fn invoke_reconnected_callback(&self) {
+        if let Some(f) = &self.reconnected_callback {
+            f(self.user_context.clone());
+        }
+    }
+
+    fn invoke_client_state_callback(&self, state: AvahiClientState) {
+        if let (Some(state), Some(f)) = (ClientState::from_raw(state), &self.client_state_callback)
+        {
+            f(state, self.user_context.clone());
         }
     }
 }
@@ -155,14 +415,27 @@ unsafe extern "C" fn client_callback(
 ) {
     let context = AvahiServiceContext::from_raw(userdata);
 
+    context.invoke_client_state_callback(state);
+
     match state {
         avahi_sys::AvahiClientState_AVAHI_CLIENT_S_RUNNING => {
             if let Err(e) = create_service(client, context) {
                 context.invoke_callback(Err(e));
+            } else if context.reconnect == ReconnectState::Connecting {
+                context.reconnect = ReconnectState::Idle;
+                context.invoke_reconnected_callback();
             }
         }
         avahi_sys::AvahiClientState_AVAHI_CLIENT_FAILURE => {
-            context.invoke_callback(Err("client failure".into()))
+            let error = avahi_util::error(client::get_errno(client), "client failure".to_string());
+
+            if error.is_recoverable() {
+                // the client is re-created from the event loop
+                warn!("Disconnected from avahi-daemon, waiting to reconnect");
+                context.reconnect = ReconnectState::Pending;
+            } else {
+                context.invoke_callback(Err(error))
+            }
         }
         avahi_sys::AvahiClientState_AVAHI_CLIENT_S_REGISTERING => {
             if let Some(g) = &mut context.group {
@@ -202,8 +475,9 @@ unsafe fn create_service(
         group.add_service(
             AddServiceParams::builder()
                 .interface(context.interface_index)
-                .protocol(avahi_sys::AVAHI_PROTO_UNSPEC)
-                .flags(0)
+                .protocol(context.protocol)
+                .flags(context.flags)
+                .publish_flags(context.publish_flags)
                 .name(context.name.as_ref().unwrap().as_ptr())
                 .kind(context.kind.as_ptr())
                 .domain(context.domain.as_ref().map(|d| d.as_ptr()).unwrap_or_null())
@@ -211,10 +485,72 @@ unsafe fn create_service(
                 .port(context.port)
                 .txt(context.txt_record.as_ref().map(|t| t.inner()))
                 .build()?,
-        )
-    } else {
-        Ok(())
+        )?;
+
+        if let Some(ttl) = context.ttl {
+            update_ttl(client, context, ttl)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Avahi does not support setting the TTL when adding a service, so the service's PTR, SRV and
+/// TXT records (including the PTR records of its sub-types and of the service type enumeration)
+/// are updated with the configured TTL after it has been added.
+unsafe fn update_ttl(
+    client: *mut AvahiClient,
+    context: &mut AvahiServiceContext,
+    ttl: u32,
+) -> Result<()> {
+    let name = c_str::copy_raw(context.name.as_ref().unwrap().as_ptr());
+    let kind = c_str::copy_raw(context.kind.as_ptr());
+
+    let domain = context.domain(client)?;
+
+    let host = match &context.host {
+        Some(h) => c_str::copy_raw(h.as_ptr()),
+        None => client::get_host_name_fqdn(client)?.to_string(),
+    };
+
+    let txt: Vec<_> = context
+        .txt_record
+        .as_ref()
+        .map(|t| t.iter().collect())
+        .unwrap_or_default();
+
+    // the kind carries the sub-types of the service, e.g. `_http._tcp,_printer`
+    let mut kind = kind.split(',');
+    let records = dns::service_records(
+        &name,
+        kind.next().unwrap_or_default(),
+        kind.map(|s| s.trim_start_matches('_')),
+        &domain,
+        &host,
+        context.port,
+        txt,
+    )?;
+
+    let group = context.group.as_mut().unwrap();
+
+    for (record_name, kind, rdata) in &records {
+        let record_name = c_string!(record_name.as_str());
+
+        group.add_record(
+            AddRecordParams::builder()
+                .interface(context.interface_index)
+                .protocol(context.protocol)
+                .flags(avahi_sys::AvahiPublishFlags_AVAHI_PUBLISH_UPDATE)
+                .name(record_name.as_ptr())
+                .clazz(dns::CLASS_IN)
+                .kind(*kind)
+                .ttl(ttl)
+                .rdata(rdata.as_slice())
+                .build()?,
+        )?;
     }
+
+    Ok(())
 }
 
 unsafe extern "C" fn entry_group_callback(
@@ -222,14 +558,58 @@ unsafe extern "C" fn entry_group_callback(
     state: AvahiEntryGroupState,
     userdata: *mut c_void,
 ) {
-    if let avahi_sys::AvahiEntryGroupState_AVAHI_ENTRY_GROUP_ESTABLISHED = state {
-        let context = AvahiServiceContext::from_raw(userdata);
-        if let Err(e) = handle_group_established(context) {
-            context.invoke_callback(Err(e));
+    let context = AvahiServiceContext::from_raw(userdata);
+
+    let result = match state {
+        avahi_sys::AvahiEntryGroupState_AVAHI_ENTRY_GROUP_ESTABLISHED => {
+            handle_group_established(context)
         }
+        avahi_sys::AvahiEntryGroupState_AVAHI_ENTRY_GROUP_COLLISION => {
+            handle_group_collision(context)
+        }
+        _ => Ok(()),
+    };
+
+    if let Err(e) = result {
+        context.invoke_callback(Err(e));
     }
 }
 
+unsafe fn handle_group_collision(context: &mut AvahiServiceContext) -> Result<()> {
+    let name = c_str::copy_raw(context.name.as_ref().unwrap().as_ptr());
+
+    debug!("Group collision: {}", name);
+
+    let candidate = match &context.name_conflict_callback {
+        Some(f) => f(&name, context.user_context.clone()).map(|c| c_string!(c)),
+        None if !context.flags.contains(ServiceFlags::NO_AUTO_RENAME) => Some(
+            avahi_util::alternative_service_name(context.name.as_ref().unwrap()),
+        ),
+        None => None,
+    };
+
+    let candidate = match candidate {
+        Some(c) => c,
+        None => {
+            return Err(Error::with_kind(
+                ErrorKind::NameConflict,
+                format!("service name `{}` conflicts with another service", name),
+            ))
+        }
+    };
+
+    debug!("Renaming service to: {}", candidate.to_string_lossy());
+
+    context.name = Some(candidate);
+
+    let group = context.group.as_mut().unwrap();
+    let client = group.get_client();
+
+    group.reset();
+
+    create_service(client, context)
+}
+
 unsafe fn handle_group_established(context: &AvahiServiceContext) -> Result<()> {
     debug!("Group established");
 
@@ -238,7 +618,7 @@ unsafe fn handle_group_established(context: &AvahiServiceContext) -> Result<()>
         .service_type(ServiceType::from_str(&c_str::copy_raw(
             context.kind.as_ptr(),
         ))?)
-        .domain("local".to_string())
+        .domain(context.domain(context.group.as_ref().unwrap().get_client())?)
         .build()?;
 
     context.invoke_callback(Ok(result));
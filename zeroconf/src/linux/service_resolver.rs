@@ -0,0 +1,139 @@
+//! Avahi implementation for cross-platform service resolver
+
+use super::avahi_util;
+use super::browser::{self, AvahiBrowserContext};
+use super::client::{ManagedAvahiClient, ManagedAvahiClientParams};
+use super::poll::ManagedAvahiSimplePoll;
+use crate::ffi::{FromRaw, SharedContext};
+use crate::prelude::*;
+use crate::{
+    AddressFamily, AddressPolicy, EventLoop, NetworkInterface, Result, ServiceDiscoveredCallback,
+    ServiceType,
+};
+use avahi_sys::{AvahiClient, AvahiClientFlags, AvahiClientState, AvahiIfIndex};
+use libc::c_void;
+use std::any::Any;
+use std::sync::Arc;
+
+#[derive(Debug)]
+pub struct AvahiMdnsResolver {
+    client: Option<Arc<ManagedAvahiClient>>,
+    poll: Option<Arc<ManagedAvahiSimplePoll>>,
+    interface_index: AvahiIfIndex,
+    context: SharedContext<AvahiBrowserContext>,
+}
+
+impl TMdnsResolver for AvahiMdnsResolver {
+    fn new() -> Self {
+        Self {
+            client: None,
+            poll: None,
+            interface_index: avahi_sys::AVAHI_IF_UNSPEC,
+            context: SharedContext::default(),
+        }
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface_index = avahi_util::interface_index(interface);
+    }
+
+    fn set_address_family(&mut self, address_family: AddressFamily) {
+        unsafe { (*self.context.as_ptr()).address_family = address_family };
+    }
+
+    fn set_address_policy(&mut self, _address_policy: AddressPolicy) {
+        // Avahi only resolves a single address per service
+    }
+
+    fn set_include_raw_records(&mut self, include_raw_records: bool) {
+        unsafe { (*self.context.as_ptr()).include_raw_records = include_raw_records };
+    }
+
+    fn set_service_resolved_callback(
+        &mut self,
+        service_resolved_callback: Box<ServiceDiscoveredCallback>,
+    ) {
+        unsafe {
+            (*self.context.as_ptr()).service_discovered_callback = Some(service_resolved_callback)
+        };
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        unsafe { (*self.context.as_ptr()).user_context = Some(Arc::from(context)) };
+    }
+
+    fn resolve(
+        &mut self,
+        name: &str,
+        service_type: ServiceType,
+        domain: &str,
+    ) -> Result<EventLoop<'_>> {
+        debug!(
+            "Resolving service: {} ({:?}) in {}",
+            name, service_type, domain
+        );
+
+        self.poll = Some(ManagedAvahiSimplePoll::new_shared()?);
+
+        self.client = Some(ManagedAvahiClient::new_shared(
+            ManagedAvahiClientParams::builder()
+                .poll(self.poll.as_ref().unwrap())
+                .flags(AvahiClientFlags(0))
+                .callback(Some(client_callback))
+                .userdata(self.context.as_ptr() as *mut c_void)
+                .build()?,
+        )?);
+
+        let context = unsafe { &mut *self.context.as_ptr() };
+        context.client = self.client.clone();
+
+        let name = c_string!(name);
+        let kind = c_string!(service_type.to_string());
+        let domain = c_string!(domain);
+
+        browser::resolve_service(
+            context,
+            self.interface_index,
+            context.protocol,
+            name.as_ptr(),
+            kind.as_ptr(),
+            domain.as_ptr(),
+        )?;
+
+        Ok(EventLoop::new(self.poll.as_ref().unwrap().clone()))
+    }
+}
+
+impl AvahiMdnsResolver {
+    /// Sets the IP protocol the mDNS queries of the resolver are sent over, e.g.
+    /// `AddressFamily::Ipv6` to skip the IPv4 queries on IPv6-only networks. Defaults to
+    /// `AddressFamily::Both`.
+    ///
+    /// Unlike `set_address_family()`, which selects the addresses services are resolved to, this
+    /// selects the transport services are resolved over.
+    pub fn set_protocol(&mut self, protocol: AddressFamily) {
+        unsafe { (*self.context.as_ptr()).protocol = avahi_util::protocol(protocol) };
+    }
+}
+
+impl Drop for AvahiMdnsResolver {
+    fn drop(&mut self) {
+        let context = unsafe { &mut *self.context.as_ptr() };
+
+        // resolvers must be freed before the client, the context is dropped last
+        context.resolvers.clear();
+        context.srv_queries.clear();
+    }
+}
+
+unsafe extern "C" fn client_callback(
+    _client: *mut AvahiClient,
+    state: AvahiClientState,
+    userdata: *mut c_void,
+) {
+    let context = AvahiBrowserContext::from_raw(userdata);
+
+    if state == avahi_sys::AvahiClientState_AVAHI_CLIENT_FAILURE {
+        context.invoke_callback(Err("client failure".into()));
+    }
+}
@@ -0,0 +1,163 @@
+//! Bonjour implementation for cross-platform address resolver
+
+use super::service_ref::{ManagedDNSServiceRef, QueryRecordParams};
+use super::{bonjour_util, constants};
+use crate::error::Operation;
+use crate::ffi::{FromRaw, SharedContext};
+use crate::prelude::*;
+use crate::{dns, EventLoop, NetworkInterface, Result, ServiceFlags};
+use crate::{AddressResolution, AddressResolvedCallback};
+use bonjour_sys::{DNSServiceErrorType, DNSServiceFlags, DNSServiceRef};
+use libc::{c_char, c_void};
+use std::any::Any;
+use std::ffi::CString;
+use std::fmt::{self, Formatter};
+use std::net::IpAddr;
+use std::slice;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+pub struct BonjourMdnsAddressResolver {
+    service: Arc<Mutex<ManagedDNSServiceRef>>,
+    reverse_name: CString,
+    interface_index: u32,
+    context: SharedContext<BonjourAddressResolverContext>,
+}
+
+impl TAddressResolver for BonjourMdnsAddressResolver {
+    fn new(address: IpAddr) -> Self {
+        let context = BonjourAddressResolverContext {
+            address_resolved_callback: None,
+            address,
+            user_context: None,
+        };
+
+        Self {
+            service: Arc::default(),
+            reverse_name: c_string!(dns::reverse_name(&address)),
+            interface_index: constants::BONJOUR_IF_UNSPEC,
+            context: SharedContext::new(context),
+        }
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface_index = bonjour_util::interface_index(interface);
+    }
+
+    fn set_address_resolved_callback(
+        &mut self,
+        address_resolved_callback: Box<AddressResolvedCallback>,
+    ) {
+        unsafe {
+            (*self.context.as_ptr()).address_resolved_callback = Some(address_resolved_callback)
+        };
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        unsafe { (*self.context.as_ptr()).user_context = Some(Arc::from(context)) };
+    }
+
+    fn resolve(&mut self) -> Result<EventLoop<'_>> {
+        debug!("Resolving address: {:?}", self);
+
+        // reverse lookups for link-local addresses must be multicast
+        self.service
+            .lock()
+            .unwrap()
+            .retain_context(self.context.retain())
+            .query_record(
+                QueryRecordParams::builder()
+                    .flags(ServiceFlags::FORCE_MULTICAST)
+                    .interface_index(self.interface_index)
+                    .fullname(self.reverse_name.as_ptr())
+                    .rrtype(bonjour_sys::kDNSServiceType_PTR as u16)
+                    .rrclass(bonjour_sys::kDNSServiceClass_IN as u16)
+                    .callback(Some(query_record_callback))
+                    .context(self.context.as_ptr() as *mut c_void)
+                    .build()?,
+            )?;
+
+        Ok(EventLoop::new(self.service.clone()))
+    }
+}
+
+#[derive(FromRaw)]
+struct BonjourAddressResolverContext {
+    address_resolved_callback: Option<Box<AddressResolvedCallback>>,
+    address: IpAddr,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl BonjourAddressResolverContext {
+    fn invoke_callback(&self, result: Result<AddressResolution>) {
+        if let Some(f) = &self.address_resolved_callback {
+            f(result, self.user_context.clone());
+        } else {
+            warn!("attempted to invoke callback but none was set");
+        }
+    }
+}
+
+impl fmt::Debug for BonjourAddressResolverContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BonjourAddressResolverContext")
+            .field("address", &self.address)
+            .finish()
+    }
+}
+
+unsafe extern "C" fn query_record_callback(
+    _sd_ref: DNSServiceRef,
+    flags: DNSServiceFlags,
+    _interface_index: u32,
+    error: DNSServiceErrorType,
+    _fullname: *const c_char,
+    _rrtype: u16,
+    _rrclass: u16,
+    rdlen: u16,
+    rdata: *const c_void,
+    _ttl: u32,
+    context: *mut c_void,
+) {
+    let ctx = BonjourAddressResolverContext::from_raw(context);
+    if let Err(e) = handle_query_record(ctx, flags, error, rdlen, rdata) {
+        ctx.invoke_callback(Err(e));
+    }
+}
+
+unsafe fn handle_query_record(
+    ctx: &BonjourAddressResolverContext,
+    flags: DNSServiceFlags,
+    error: DNSServiceErrorType,
+    rdlen: u16,
+    rdata: *const c_void,
+) -> Result<()> {
+    if error != 0 {
+        return Err(bonjour_util::error(
+            error,
+            format!("query_record_callback() reported error (code: {})", error),
+        )
+        .with_operation(Operation::QueryRecord));
+    }
+
+    if flags & bonjour_sys::kDNSServiceFlagsAdd == 0 {
+        // the record is no longer valid
+        return Ok(());
+    }
+
+    let rdata = slice::from_raw_parts(rdata as *const u8, rdlen as usize);
+
+    let host_name = match dns::decode_name(rdata) {
+        Some(host_name) => host_name,
+        None => return Err("query_record_callback() reported invalid PTR record".into()),
+    };
+
+    let result = AddressResolution::builder()
+        .address(ctx.address)
+        .host_name(host_name)
+        .build()?;
+
+    ctx.invoke_callback(Ok(result));
+
+    Ok(())
+}
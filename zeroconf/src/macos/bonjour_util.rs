@@ -1,17 +1,123 @@
 //! Utilities related to Bonjour
 
 use super::constants;
-use crate::NetworkInterface;
+use super::service_ref::ManagedDNSServiceRef;
+use crate::error::{Error, ErrorKind, Operation};
+use crate::{AddressFamily, DnssecStatus, NetworkInterface, Result, ServiceFlags};
+use bonjour_sys::{
+    kDNSServiceErr_BadFlags, kDNSServiceErr_BadInterfaceIndex, kDNSServiceErr_BadParam,
+    kDNSServiceErr_Invalid, kDNSServiceErr_NameConflict, kDNSServiceErr_NoSuchRecord,
+    kDNSServiceErr_ServiceNotRunning, kDNSServiceErr_Timeout, kDNSServiceProperty_DaemonVersion,
+    DNSServiceErrorType, DNSServiceFlags, DNSServiceGetProperty, DNSServiceProtocol,
+};
+use libc::{c_char, c_void};
+use std::mem;
+use std::time::Instant;
 
 /// Normalizes the specified domain `&str` to conform to a standard enforced by this crate.
 ///
 /// Bonjour suffixes domains with a final `'.'` character in some contexts but is not required by
 /// the standard. This function removes the final dot if present.
 pub fn normalize_domain(domain: &str) -> String {
-    if domain.chars().nth(domain.len() - 1).unwrap() == '.' {
-        String::from(&domain[..domain.len() - 1])
+    String::from(strip_root(domain))
+}
+
+/// Returns the specified domain without its trailing `'.'`, if any, see [`normalize_domain()`].
+///
+/// [`normalize_domain()`]: fn.normalize_domain.html
+pub fn strip_root(domain: &str) -> &str {
+    domain.strip_suffix('.').unwrap_or(domain)
+}
+
+/// Returns an [`Error`] with the specified `description` of the error `code` reported by Bonjour.
+///
+/// Bonjour reports `kDNSServiceErr_PolicyDenied` if the application is not permitted to use the
+/// local network (e.g. on iOS), which is of kind `ErrorKind::PermissionDenied`, and
+/// `kDNSServiceErr_ServiceNotRunning` if the mDNS daemon cannot be reached, which is of kind
+/// `ErrorKind::DaemonNotRunning` and describes how to fix it, as is a connection the daemon has
+/// closed. Name conflicts and timeouts are of kind `ErrorKind::NameConflict` and
+/// `ErrorKind::Timeout`, respectively, invalid parameters of kind `ErrorKind::InvalidArgument`
+/// and the negative answers requested with `kDNSServiceFlagsReturnIntermediates` of kind
+/// `ErrorKind::NoSuchRecord`.
+///
+/// [`Error`]: ../../error/struct.Error.html
+pub fn error(code: DNSServiceErrorType, description: String) -> Error {
+    let error = if code == constants::BONJOUR_ERR_POLICY_DENIED {
+        Error::with_kind(ErrorKind::PermissionDenied, description)
+    } else if code == kDNSServiceErr_ServiceNotRunning
+        || code == constants::BONJOUR_ERR_DEFUNCT_CONNECTION
+    {
+        Error::with_kind(
+            ErrorKind::DaemonNotRunning,
+            format!("{}: {}", description, constants::BONJOUR_NOT_RUNNING_HINT),
+        )
+    } else if code == kDNSServiceErr_NameConflict {
+        Error::with_kind(ErrorKind::NameConflict, description)
+    } else if code == kDNSServiceErr_Timeout {
+        Error::with_kind(ErrorKind::Timeout, description)
+    } else if code == kDNSServiceErr_NoSuchRecord {
+        Error::with_kind(ErrorKind::NoSuchRecord, description)
+    } else if [
+        kDNSServiceErr_BadParam,
+        kDNSServiceErr_BadFlags,
+        kDNSServiceErr_BadInterfaceIndex,
+        kDNSServiceErr_Invalid,
+    ]
+    .contains(&code)
+    {
+        Error::with_kind(ErrorKind::InvalidArgument, description)
     } else {
-        String::from(domain)
+        Error::with_kind(ErrorKind::Other, description)
+    };
+
+    error.with_code(code)
+}
+
+/// Returns true if a connection to the mDNS daemon can be established.
+pub fn is_daemon_running() -> bool {
+    ManagedDNSServiceRef::new().create_connection().is_ok()
+}
+
+/// Queries the version of the mDNS daemon with `DNSServiceGetProperty` (e.g. `1310.4` for
+/// `mDNSResponder-1310.4`).
+pub fn daemon_version() -> Result<String> {
+    let mut version: u32 = 0;
+    let mut size = mem::size_of::<u32>() as u32;
+
+    bonjour!(
+        DNSServiceGetProperty(
+            kDNSServiceProperty_DaemonVersion.as_ptr() as *const c_char,
+            &mut version as *mut u32 as *mut c_void,
+            &mut size
+        ),
+        "could not get the version of the mDNS daemon"
+    )?;
+
+    Ok(format_daemon_version(version))
+}
+
+/// Formats the version reported by Bonjour, which encodes e.g. `1310.4.1` as `13100401`.
+fn format_daemon_version(version: u32) -> String {
+    let (major, minor, patch) = (version / 10000, version / 100 % 100, version % 100);
+
+    if patch == 0 {
+        format!("{}.{}", major, minor)
+    } else {
+        format!("{}.{}.{}", major, minor, patch)
+    }
+}
+
+/// Returns true if at least `BONJOUR_RECONNECT_INTERVAL` has elapsed since `last_attempt`, in
+/// which case `last_attempt` is updated to the current time.
+///
+/// Used to rate-limit attempts to reconnect to the mDNS daemon while it is not running.
+pub(crate) fn should_reconnect(last_attempt: &mut Option<Instant>) -> bool {
+    match last_attempt {
+        Some(t) if t.elapsed() < constants::BONJOUR_RECONNECT_INTERVAL => false,
+        _ => {
+            *last_attempt = Some(Instant::now());
+            true
+        }
     }
 }
 
@@ -22,5 +128,195 @@ pub fn interface_index(interface: NetworkInterface) -> u32 {
     match interface {
         NetworkInterface::Unspec => constants::BONJOUR_IF_UNSPEC,
         NetworkInterface::AtIndex(i) => i,
+        NetworkInterface::PeerToPeer => constants::BONJOUR_IF_P2P,
+    }
+}
+
+/// Converts the specified [`AddressFamily`] to the Bonjour expected value.
+///
+/// [`AddressFamily`]: ../../enum.AddressFamily.html
+pub fn protocol(address_family: AddressFamily) -> DNSServiceProtocol {
+    match address_family {
+        AddressFamily::Ipv4 => bonjour_sys::kDNSServiceProtocol_IPv4,
+        AddressFamily::Ipv6 => bonjour_sys::kDNSServiceProtocol_IPv6,
+        AddressFamily::Both => {
+            bonjour_sys::kDNSServiceProtocol_IPv4 | bonjour_sys::kDNSServiceProtocol_IPv6
+        }
+    }
+}
+
+/// Returns the [`ServiceFlags`] that apply to the DNS-SD function of `operation`. The others are
+/// meaningless for it, e.g. `NO_AUTO_RENAME` when browsing, and are not passed to Bonjour.
+///
+/// [`ServiceFlags`]: ../../flags/struct.ServiceFlags.html
+pub fn operation_flags(operation: Operation) -> ServiceFlags {
+    let interfaces = ServiceFlags::INCLUDE_P2P | ServiceFlags::INCLUDE_AWDL;
+
+    match operation {
+        Operation::Register => {
+            interfaces
+                | ServiceFlags::FORCE_MULTICAST
+                | ServiceFlags::NO_AUTO_RENAME
+                | ServiceFlags::WAKE_ONLY_SERVICE
+        }
+        Operation::Browse => {
+            interfaces
+                | ServiceFlags::FORCE_MULTICAST
+                | ServiceFlags::THRESHOLD_ONE
+                | ServiceFlags::THRESHOLD_FINDER
+        }
+        Operation::Resolve => {
+            interfaces | ServiceFlags::FORCE_MULTICAST | ServiceFlags::WAKE_ON_RESOLVE
+        }
+        Operation::GetAddrInfo | Operation::QueryRecord => {
+            interfaces
+                | ServiceFlags::FORCE_MULTICAST
+                | ServiceFlags::VALIDATE
+                | ServiceFlags::RETURN_INTERMEDIATES
+        }
+        Operation::Connect | Operation::MapPort => ServiceFlags::empty(),
+    }
+}
+
+/// Converts the specified [`ServiceFlags`] to the Bonjour expected value for the DNS-SD function
+/// of `operation`, leaving out the flags that do not apply to it, see `operation_flags()`.
+///
+/// [`ServiceFlags`]: ../../flags/struct.ServiceFlags.html
+pub fn service_flags(flags: ServiceFlags, operation: Operation) -> DNSServiceFlags {
+    let flags = flags & operation_flags(operation);
+    let mut service_flags = 0;
+
+    if flags.contains(ServiceFlags::FORCE_MULTICAST) {
+        service_flags |= bonjour_sys::kDNSServiceFlagsForceMulticast;
+    }
+
+    if flags.contains(ServiceFlags::UNIQUE) {
+        service_flags |= bonjour_sys::kDNSServiceFlagsUnique;
+    }
+
+    if flags.contains(ServiceFlags::NO_AUTO_RENAME) {
+        service_flags |= bonjour_sys::kDNSServiceFlagsNoAutoRename;
+    }
+
+    if flags.contains(ServiceFlags::WAKE_ONLY_SERVICE) {
+        service_flags |= bonjour_sys::kDNSServiceFlagsWakeOnlyService;
+    }
+
+    if flags.contains(ServiceFlags::INCLUDE_P2P) {
+        service_flags |= bonjour_sys::kDNSServiceFlagsIncludeP2P;
+    }
+
+    if flags.contains(ServiceFlags::INCLUDE_AWDL) {
+        service_flags |= bonjour_sys::kDNSServiceFlagsIncludeAWDL;
+    }
+
+    if flags.contains(ServiceFlags::VALIDATE) {
+        service_flags |= bonjour_sys::kDNSServiceFlagsValidate;
+    }
+
+    if flags.contains(ServiceFlags::RETURN_INTERMEDIATES) {
+        service_flags |= bonjour_sys::kDNSServiceFlagsReturnIntermediates;
+    }
+
+    if flags.contains(ServiceFlags::WAKE_ON_RESOLVE) {
+        service_flags |= bonjour_sys::kDNSServiceFlagsWakeOnResolve;
+    }
+
+    if flags.contains(ServiceFlags::THRESHOLD_ONE) {
+        service_flags |= bonjour_sys::kDNSServiceFlagsThresholdOne;
+    }
+
+    if flags.contains(ServiceFlags::THRESHOLD_FINDER) {
+        service_flags |= bonjour_sys::kDNSServiceFlagsThresholdFinder;
+    }
+
+    service_flags
+}
+
+/// Returns the [`DnssecStatus`] reported by the specified callback `flags`, or `None` if the
+/// callback does not carry a validation result.
+///
+/// [`DnssecStatus`]: ../../enum.DnssecStatus.html
+pub fn dnssec_status(flags: DNSServiceFlags) -> Option<DnssecStatus> {
+    let has = |flag: DNSServiceFlags| flags & flag == flag;
+
+    if has(bonjour_sys::kDNSServiceFlagsSecure) {
+        Some(DnssecStatus::Secure)
+    } else if has(bonjour_sys::kDNSServiceFlagsInsecure) {
+        Some(DnssecStatus::Insecure)
+    } else if has(bonjour_sys::kDNSServiceFlagsBogus) {
+        Some(DnssecStatus::Bogus)
+    } else if has(bonjour_sys::kDNSServiceFlagsIndeterminate) {
+        Some(DnssecStatus::Indeterminate)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_daemon_version_omits_zero_patch() {
+        assert_eq!(format_daemon_version(13100400), "1310.4");
+        assert_eq!(format_daemon_version(13100401), "1310.4.1");
+    }
+
+    #[test]
+    fn error_reports_daemon_not_running() {
+        let error = error(
+            kDNSServiceErr_ServiceNotRunning,
+            "could not browse".to_string(),
+        );
+
+        assert_eq!(error.kind(), ErrorKind::DaemonNotRunning);
+        assert!(error.to_string().starts_with("could not browse: "));
+    }
+
+    #[test]
+    fn error_carries_code() {
+        let error = error(kDNSServiceErr_Timeout, "could not resolve".to_string());
+
+        assert_eq!(error.kind(), ErrorKind::Timeout);
+        assert_eq!(error.code(), Some(kDNSServiceErr_Timeout));
+    }
+
+    #[test]
+    fn error_reports_negative_answers() {
+        let error = error(kDNSServiceErr_NoSuchRecord, "could not resolve".to_string());
+
+        assert_eq!(error.kind(), ErrorKind::NoSuchRecord);
+        assert!(!error.is_recoverable());
+    }
+
+    #[test]
+    fn service_flags_maps_browse_thresholds() {
+        assert_eq!(
+            service_flags(
+                ServiceFlags::THRESHOLD_ONE | ServiceFlags::THRESHOLD_FINDER,
+                Operation::Browse
+            ),
+            bonjour_sys::kDNSServiceFlagsThresholdOne
+                | bonjour_sys::kDNSServiceFlagsThresholdFinder
+        );
+    }
+
+    #[test]
+    fn service_flags_leaves_out_flags_of_other_operations() {
+        let flags = ServiceFlags::NO_AUTO_RENAME | ServiceFlags::UNIQUE | ServiceFlags::INCLUDE_P2P;
+
+        assert_eq!(
+            service_flags(flags, Operation::Browse),
+            bonjour_sys::kDNSServiceFlagsIncludeP2P
+        );
+        assert_eq!(
+            service_flags(flags, Operation::Register),
+            bonjour_sys::kDNSServiceFlagsNoAutoRename | bonjour_sys::kDNSServiceFlagsIncludeP2P
+        );
+        assert_eq!(
+            service_flags(ServiceFlags::WAKE_ON_RESOLVE, Operation::GetAddrInfo),
+            0
+        );
     }
 }
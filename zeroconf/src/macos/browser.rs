@@ -1,39 +1,67 @@
 //! Bonjour implementation for cross-platform browser
 
 use super::service_ref::{
-    BrowseServicesParams, GetAddressInfoParams, ManagedDNSServiceRef, ServiceResolveParams,
+    BrowseServicesParams, GetAddressInfoParams, ManagedDNSServiceRef, QueryRecordParams,
+    ServiceResolveParams, SharedConnection,
 };
 use super::txt_record_ref::ManagedTXTRecordRef;
 use super::{bonjour_util, constants};
-use crate::ffi::{c_str, AsRaw, FromRaw};
+use crate::address_cache::AddressCache;
+use crate::debounce::Debouncer;
+use crate::error::{Error, ErrorKind, Operation};
+use crate::ffi::c_str::{self, AsCChars};
+use crate::ffi::{self, FromRaw, SharedContext, UnwrapOrNull};
 use crate::prelude::*;
-use crate::{EventLoop, NetworkInterface, Result, ServiceType, TxtRecord};
-use crate::{ServiceDiscoveredCallback, ServiceDiscovery};
+use crate::{
+    dns, AddressFamily, AddressPolicy, BrowserControl, DaemonReconnectedCallback, ErrorCallback,
+    EventLoop, EventMeta, NetworkInterface, Result, ServiceType, TxtRecord,
+};
+use crate::{
+    BrowserEvent, Record, ServiceBatchCallback, ServiceDiscoveredCallback, ServiceDiscovery,
+    ServiceFlags,
+};
 use bonjour_sys::{DNSServiceErrorType, DNSServiceFlags, DNSServiceRef};
-use libc::{c_char, c_uchar, c_void, sockaddr_in};
+use libc::{c_char, c_uchar, c_void, sockaddr};
 use std::any::Any;
-use std::ffi::CString;
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
 use std::fmt::{self, Formatter};
+use std::mem;
 use std::net::IpAddr;
-use std::ptr;
+use std::slice;
 use std::str::FromStr;
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 pub struct BonjourMdnsBrowser {
+    /// The connection shared by the browse and the resolutions of the discovered services
     service: Arc<Mutex<ManagedDNSServiceRef>>,
+    browse_ref: Option<ManagedDNSServiceRef>,
     kind: CString,
+    domain: Option<CString>,
     interface_index: u32,
-    context: *mut BonjourBrowserContext,
+    flags: ServiceFlags,
+    last_reconnect_attempt: Option<Instant>,
+    context: SharedContext<BonjourBrowserContext>,
 }
 
+// SAFETY: the browser exclusively owns its context and the Bonjour objects, which are only used
+// through `&mut self` or the event loop borrowing it, and the callbacks and the context of
+// the user it holds are `Send`.
+unsafe impl Send for BonjourMdnsBrowser {}
+
 impl TMdnsBrowser for BonjourMdnsBrowser {
     fn new(service_type: ServiceType) -> Self {
         Self {
             service: Arc::default(),
+            browse_ref: None,
             kind: c_string!(service_type.to_string()),
+            domain: None,
             interface_index: constants::BONJOUR_IF_UNSPEC,
-            context: Box::into_raw(Box::default()),
+            flags: ServiceFlags::empty(),
+            last_reconnect_attempt: None,
+            context: SharedContext::default(),
         }
     }
 
@@ -41,76 +69,735 @@ impl TMdnsBrowser for BonjourMdnsBrowser {
         self.interface_index = bonjour_util::interface_index(interface);
     }
 
+    fn set_domain(&mut self, domain: &str) {
+        self.domain = Some(c_string!(domain));
+    }
+
+    fn set_flags(&mut self, flags: ServiceFlags) {
+        self.flags = flags;
+
+        unsafe { (*self.context.as_ptr()).set_resolve_flags(flags) };
+    }
+
+    fn set_address_family(&mut self, address_family: AddressFamily) {
+        unsafe { (*self.context.as_ptr()).address_family = address_family };
+    }
+
+    fn set_address_policy(&mut self, address_policy: AddressPolicy) {
+        unsafe { (*self.context.as_ptr()).address_policy = address_policy };
+    }
+
+    fn set_include_raw_records(&mut self, include_raw_records: bool) {
+        unsafe { (*self.context.as_ptr()).include_raw_records = include_raw_records };
+    }
+
+    fn set_resolve_concurrency(&mut self, _concurrency: usize) {
+        // services are resolved one at a time from the browse callback
+    }
+
+    fn set_debounce(&mut self, window: Duration) {
+        unsafe { (*self.context.as_ptr()).debouncer.set_window(window) };
+    }
+
     fn set_service_discovered_callback(
         &mut self,
         service_discovered_callback: Box<ServiceDiscoveredCallback>,
     ) {
-        unsafe { (*self.context).service_discovered_callback = Some(service_discovered_callback) };
+        self.callback_handle()
+            .set_service_discovered_callback(service_discovered_callback);
     }
 
-    fn set_context(&mut self, context: Box<dyn Any>) {
-        unsafe { (*self.context).user_context = Some(Arc::from(context)) };
+    fn set_service_batch_callback(&mut self, service_batch_callback: Box<ServiceBatchCallback>) {
+        self.callback_handle()
+            .set_service_batch_callback(service_batch_callback);
     }
 
-    fn browse_services(&mut self) -> Result<EventLoop> {
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>) {
+        self.callback_handle().set_error_callback(error_callback);
+    }
+
+    fn control(&self) -> BrowserControl {
+        unsafe { (*self.context.as_ptr()).control.clone() }
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        self.callback_handle().set_context(context);
+    }
+
+    fn set_reconnected_callback(&mut self, reconnected_callback: Box<DaemonReconnectedCallback>) {
+        self.callback_handle().lock().reconnected = Some(reconnected_callback);
+    }
+
+    fn browse_services(&mut self) -> Result<EventLoop<'_>> {
+        let _span = operation_span!("browse", service_type = self.kind.to_string_lossy());
+
         debug!("Browsing services: {:?}", self);
 
-        self.service.lock().unwrap().browse_services(
-            BrowseServicesParams::builder()
-                .flags(0)
-                .interface_index(self.interface_index)
-                .regtype(self.kind.as_ptr())
-                .domain(ptr::null_mut())
-                .callback(Some(browse_callback))
-                .context(self.context as *mut c_void)
-                .build()?,
-        )?;
+        self.browse()?;
+
+        let browser: *mut Self = self;
 
-        Ok(EventLoop::new(self.service.clone()))
+        Ok(EventLoop::new(self.service.clone())
+            .with_on_poll(Box::new(move || unsafe { (*browser).handle_poll() })))
     }
 }
 
-impl Drop for BonjourMdnsBrowser {
-    fn drop(&mut self) {
-        unsafe { Box::from_raw(self.context) };
+impl BonjourMdnsBrowser {
+    /// Returns the underlying `DNSServiceRef` of the connection shared by the browse and the
+    /// resolutions of the discovered services, or a null pointer if the browser has not been
+    /// started yet, to call functions of `dns_sd.h` this crate does not wrap.
+    ///
+    /// # Safety
+    /// The reference is owned by this browser, it must not be deallocated and its results must
+    /// not be processed outside of the event loop. The pointer is no longer valid once the browser
+    /// is dropped or has reconnected to the mDNS daemon.
+    pub unsafe fn as_raw(&self) -> DNSServiceRef {
+        self.service.lock().unwrap().inner()
+    }
+
+    /// Returns a handle to replace the callbacks and the context of this browser, which unlike
+    /// the functions of `TMdnsBrowser` can be used from any thread while the browser is running.
+    pub fn callback_handle(&self) -> BrowserCallbackHandle {
+        unsafe { (*self.context.as_ptr()).callbacks.clone() }
+    }
+
+    fn browse(&mut self) -> Result<()> {
+        let mut service = self.service.lock().unwrap();
+
+        // the browse and the resolutions share the connection, so that all of their results are
+        // processed by the event loop at once rather than each resolution waiting on its own
+        service
+            .retain_context(self.context.retain())
+            .create_connection()?;
+
+        let connection = service.shared_connection();
+        unsafe { (*self.context.as_ptr()).connect(connection.clone()) };
+
+        let params = BrowseServicesParams::builder()
+            .flags(self.flags)
+            .interface_index(self.interface_index)
+            .regtype(self.kind.as_ptr())
+            .domain(self.domain.as_ref().as_c_chars().unwrap_or_null())
+            .callback(Some(browse_callback))
+            .context(self.context.as_ptr() as *mut c_void)
+            .build()?;
+
+        self.browse_ref = Some(connection.start(|browse| browse.browse_services(params))?);
+
+        Ok(())
+    }
+
+    fn handle_poll(&mut self) {
+        unsafe { (*self.context.as_ptr()).sweep() };
+        self.handle_reconnect();
+    }
+
+    fn handle_reconnect(&mut self) {
+        let disconnected = self.service.lock().unwrap().is_null();
+
+        if disconnected {
+            // the rest of a burst is not coming once the daemon has gone away
+            unsafe { (*self.context.as_ptr()).end_burst() };
+        }
+
+        if !disconnected || !bonjour_util::should_reconnect(&mut self.last_reconnect_attempt) {
+            return;
+        }
+
+        debug!("Attempting to reconnect to the mDNS daemon");
+
+        if let Err(e) = self.browse() {
+            // the daemon is likely still down, try again later
+            debug!("Could not reconnect to the mDNS daemon: {}", e);
+            *self.service.lock().unwrap() = ManagedDNSServiceRef::default();
+            return;
+        }
+
+        self.last_reconnect_attempt = None;
+
+        unsafe {
+            // hosts may have changed their addresses while the daemon was away
+            (*self.context.as_ptr()).address_cache.clear();
+            (*self.context.as_ptr()).invoke_reconnected_callback();
+        }
     }
 }
 
-#[derive(Default, FromRaw, AsRaw)]
-struct BonjourBrowserContext {
-    service_discovered_callback: Option<Box<ServiceDiscoveredCallback>>,
-    resolved_name: Option<String>,
-    resolved_kind: Option<String>,
-    resolved_domain: Option<String>,
-    resolved_port: u16,
-    resolved_txt: Option<TxtRecord>,
-    user_context: Option<Arc<dyn Any>>,
+/// A handle to the callbacks and the context of the user of a [`BonjourMdnsBrowser`], returned by
+/// [`BonjourMdnsBrowser::callback_handle()`].
+///
+/// The callbacks are replaced under a lock, and the event loop releases it while a callback runs,
+/// so a callback may replace the callbacks itself. A replaced callback that is running still
+/// completes, the new one is invoked from the next event on.
+///
+/// [`BonjourMdnsBrowser`]: struct.BonjourMdnsBrowser.html
+/// [`BonjourMdnsBrowser::callback_handle()`]: struct.BonjourMdnsBrowser.html#method.callback_handle
+#[derive(Clone, Default)]
+pub struct BrowserCallbackHandle {
+    callbacks: Arc<Mutex<BrowserCallbacks>>,
+}
+
+#[derive(Default)]
+struct BrowserCallbacks {
+    service_discovered: Option<Box<ServiceDiscoveredCallback>>,
+    service_batch: Option<Box<ServiceBatchCallback>>,
+    error: Option<Box<ErrorCallback>>,
+    reconnected: Option<Box<DaemonReconnectedCallback>>,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl BrowserCallbackHandle {
+    /// Sets the function invoked when a service is discovered.
+    pub fn set_service_discovered_callback(
+        &self,
+        service_discovered_callback: Box<ServiceDiscoveredCallback>,
+    ) {
+        let mut callbacks = self.lock();
+        callbacks.service_discovered = Some(service_discovered_callback);
+        callbacks.service_batch = None;
+    }
+
+    /// Sets the function invoked with the services discovered during a burst once it has ended,
+    /// instead of the service discovered callback.
+    pub fn set_service_batch_callback(&self, service_batch_callback: Box<ServiceBatchCallback>) {
+        let mut callbacks = self.lock();
+        callbacks.service_batch = Some(service_batch_callback);
+        callbacks.service_discovered = None;
+    }
+
+    /// Sets the function invoked instead of the service discovered callback when an error occurs.
+    pub fn set_error_callback(&self, error_callback: Box<ErrorCallback>) {
+        self.lock().error = Some(error_callback);
+    }
+
+    /// Sets the context passed to the callbacks.
+    pub fn set_context(&self, context: Box<dyn Any + Send + Sync>) {
+        self.lock().user_context = Some(Arc::from(context));
+    }
+
+    fn is_batching(&self) -> bool {
+        self.lock().service_batch.is_some()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BrowserCallbacks> {
+        self.callbacks.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Takes the callback selected by `slot` out of the lock, invokes `f` with it, `arg` and the
+    /// context, and puts it back unless it was replaced in the meantime. Returns `arg` back if the
+    /// callback is not set.
+    fn invoke<C: ?Sized, A>(
+        &self,
+        slot: fn(&mut BrowserCallbacks) -> &mut Option<Box<C>>,
+        arg: A,
+        f: impl FnOnce(&C, A, Option<Arc<dyn Any + Send + Sync>>),
+    ) -> Option<A> {
+        let (callback, user_context) = {
+            let mut callbacks = self.lock();
+
+            match slot(&mut callbacks).take() {
+                Some(c) => (c, callbacks.user_context.clone()),
+                None => return Some(arg),
+            }
+        };
+
+        f(&callback, arg, user_context);
+
+        let mut callbacks = self.lock();
+        let slot = slot(&mut callbacks);
+
+        if slot.is_none() {
+            *slot = Some(callback);
+        }
+
+        None
+    }
+}
+
+impl fmt::Debug for BrowserCallbackHandle {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BrowserCallbackHandle").finish()
+    }
+}
+
+#[derive(Default, FromRaw)]
+pub(super) struct BonjourBrowserContext {
+    pub(super) callbacks: BrowserCallbackHandle,
+    control: BrowserControl,
+    /// The connection the services are resolved on, see `connect()`
+    connection: Option<SharedConnection>,
+    /// The services being resolved, which are owned by the context until they are swept
+    resolutions: Vec<*mut Resolution>,
+    /// The events of the current burst, delivered to the batch callback by `end_burst()`
+    batch: RefCell<Vec<BrowserEvent>>,
+    /// The interface, name, type and domain of the services that have gone away
+    debouncer: Debouncer<(u32, String, String, String)>,
+    /// The addresses of the hosts of the services that have been resolved, by interface and
+    /// lowercase host name
+    address_cache: AddressCache<(u32, String), ResolvedHost>,
+    pub(super) include_raw_records: bool,
+    pub(super) address_family: AddressFamily,
+    pub(super) address_policy: AddressPolicy,
+    /// The flags the services are resolved with, see `set_resolve_flags()`
+    resolve_flags: ServiceFlags,
 }
 
 impl BonjourBrowserContext {
-    fn invoke_callback(&self, result: Result<ServiceDiscovery>) {
-        if let Some(f) = &self.service_discovered_callback {
-            f(result, self.user_context.clone());
-        } else {
+    pub(super) fn invoke_callback(&self, result: Result<ServiceDiscovery>) {
+        if !self.control.is_running() {
+            return;
+        }
+
+        let result = match result {
+            Err(e) => match self
+                .callbacks
+                .invoke(|c| &mut c.error, e, |f, e, ctx| f(e, ctx))
+            {
+                Some(e) => Err(e),
+                None => return,
+            },
+            result => result,
+        };
+
+        if self.callbacks.is_batching() {
+            self.batch.borrow_mut().push(BrowserEvent::from(result));
+            return;
+        }
+
+        let unhandled = self.callbacks.invoke(
+            |c| &mut c.service_discovered,
+            result,
+            |f, result, ctx| f(result, ctx),
+        );
+
+        if unhandled.is_some() {
             warn!("attempted to invoke callback but none was set");
         }
     }
+
+    /// Delivers the events buffered since the last burst has ended to the batch callback, if
+    /// there are any.
+    pub(super) fn end_burst(&self) {
+        let events = mem::take(&mut *self.batch.borrow_mut());
+
+        if events.is_empty() {
+            return;
+        }
+
+        let unhandled = self.callbacks.invoke(
+            |c| &mut c.service_batch,
+            events,
+            |f, events, ctx| f(events, ctx),
+        );
+
+        if unhandled.is_some() {
+            warn!("attempted to invoke batch callback but none was set");
+        }
+    }
+
+    fn invoke_reconnected_callback(&self) {
+        self.callbacks
+            .invoke(|c| &mut c.reconnected, (), |f, (), ctx| f(ctx));
+    }
+
+    /// Sets the connection the services are resolved on from now on, dropping the resolutions of
+    /// the previous connection, if any (e.g. after reconnecting to the mDNS daemon).
+    pub(super) fn connect(&mut self, connection: SharedConnection) {
+        self.free_resolutions(|_| true);
+        self.connection = Some(connection);
+    }
+
+    /// Keeps the flags of `flags` that apply to resolving services, `RETURN_INTERMEDIATES` for
+    /// looking up their addresses and `WAKE_ON_RESOLVE` for resolving them.
+    pub(super) fn set_resolve_flags(&mut self, flags: ServiceFlags) {
+        self.resolve_flags =
+            flags & (ServiceFlags::RETURN_INTERMEDIATES | ServiceFlags::WAKE_ON_RESOLVE);
+    }
+
+    /// Starts resolving the specified service on the connection set with `connect()`. The results
+    /// of each step are processed by the event loop of the connection, and the service is reported
+    /// once the addresses of its host are known.
+    ///
+    /// A service that has been `browsed` is expected to be resolved right away, otherwise the
+    /// service may not be available yet and is waited for without a timeout.
+    pub(super) unsafe fn start_resolution(
+        &mut self,
+        browsed: bool,
+        interface_index: u32,
+        name: *const c_char,
+        regtype: *const c_char,
+        domain: *const c_char,
+    ) -> Result<()> {
+        let resolution = Box::into_raw(Box::new(Resolution {
+            ctx: self,
+            browsed,
+            interface_index,
+            name: c_str::copy_raw(name),
+            kind: c_str::copy_raw(regtype),
+            domain: c_str::copy_raw(domain),
+            step: Step::Resolve,
+            step_started: Instant::now(),
+            more_coming: false,
+            operation: None,
+            port: 0,
+            priority: 0,
+            weight: 0,
+            txt: None,
+            records: Vec::new(),
+            host: (interface_index, String::new()),
+            host_target: CString::default(),
+            host_name: String::new(),
+            addresses: Vec::new(),
+            negative_answers: 0,
+            scope_id: 0,
+            ttl: 0,
+            meta: EventMeta::default(),
+        }));
+
+        let flags =
+            ServiceFlags::FORCE_MULTICAST | (self.resolve_flags & ServiceFlags::WAKE_ON_RESOLVE);
+
+        let result = (*resolution).start_step(self, Step::Resolve, |service, context| {
+            service.resolve_service(
+                ServiceResolveParams::builder()
+                    .flags(flags)
+                    .interface_index(interface_index)
+                    .name(name)
+                    .regtype(regtype)
+                    .domain(domain)
+                    .callback(Some(resolve_callback))
+                    .context(context)
+                    .build()?,
+            )
+        });
+
+        match result {
+            Ok(()) => {
+                self.resolutions.push(resolution);
+                Ok(())
+            }
+            Err(e) => {
+                drop(Box::from_raw(resolution));
+                Err(e)
+            }
+        }
+    }
+
+    /// Invoked after each poll of the event loop, once the callbacks of the processed results have
+    /// returned:
+    ///
+    /// * ends the steps whose results have all been processed
+    /// * fails the resolutions whose step has not completed within `BONJOUR_RESOLVE_TIMEOUT`
+    /// * frees the resolutions that are done
+    /// * ends the current burst once every service of the burst has been resolved
+    pub(super) fn sweep(&mut self) {
+        let pending = matches!(&self.connection, Some(c) if c.has_results());
+
+        for resolution in self.resolutions.clone() {
+            // SAFETY: the resolutions are only freed below, and none of their callbacks runs
+            let resolution = unsafe { &mut *resolution };
+
+            if resolution.is_done() {
+                continue;
+            }
+
+            if resolution.more_coming && !pending {
+                resolution.more_coming = false;
+                let result = resolution.end_step(self);
+                resolution.finish(self, result);
+            } else if resolution.has_timed_out() {
+                resolution.operation = None;
+
+                self.invoke_callback(Err(Error::with_kind(
+                    ErrorKind::Timeout,
+                    "timed out waiting for the mDNS daemon".to_string(),
+                )
+                .with_operation(resolution.step.operation())));
+            }
+        }
+
+        self.free_resolutions(|r| r.is_done());
+
+        if self.resolutions.is_empty() && !pending {
+            self.end_burst();
+        }
+    }
+
+    fn free_resolutions(&mut self, f: impl Fn(&Resolution) -> bool) {
+        self.resolutions.retain(|&resolution| unsafe {
+            if !f(&*resolution) {
+                return true;
+            }
+
+            drop(Box::from_raw(resolution));
+            false
+        });
+    }
+}
+
+impl Drop for BonjourBrowserContext {
+    fn drop(&mut self) {
+        self.free_resolutions(|_| true);
+    }
 }
 
 impl fmt::Debug for BonjourBrowserContext {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.debug_struct("BonjourResolverContext")
-            .field("resolved_name", &self.resolved_name)
-            .field("resolved_kind", &self.resolved_kind)
-            .field("resolved_domain", &self.resolved_domain)
-            .field("resolved_port", &self.resolved_port)
+            .field("resolutions", &self.resolutions.len())
             .finish()
     }
 }
 
+/// The steps of resolving a discovered service, each of which is an operation on the connection
+/// of the browser.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    /// Resolves the port, TXT record and host of the service
+    Resolve,
+    /// Queries the SRV record of the service for its priority and weight, or all of its records
+    /// if raw records are included
+    QueryRecords,
+    /// Looks up the addresses of the host of the service, unless they are cached
+    LookUpAddresses,
+}
+
+impl Step {
+    fn operation(self) -> Operation {
+        match self {
+            Self::Resolve => Operation::Resolve,
+            Self::QueryRecords => Operation::QueryRecord,
+            Self::LookUpAddresses => Operation::GetAddrInfo,
+        }
+    }
+}
+
+/// A service being resolved, which is passed as the context of the operation of each step.
+pub(super) struct Resolution {
+    /// The context of the browser, which outlives its resolutions
+    ctx: *mut BonjourBrowserContext,
+    /// Whether the service has been discovered by a browser, see `start_resolution()`
+    browsed: bool,
+    interface_index: u32,
+    name: String,
+    kind: String,
+    domain: String,
+    step: Step,
+    step_started: Instant,
+    /// Whether the last callback of the step has reported more results coming
+    more_coming: bool,
+    /// The operation of the current step, `None` once the service has been reported or has failed
+    operation: Option<ManagedDNSServiceRef>,
+    port: u16,
+    priority: u16,
+    weight: u16,
+    txt: Option<TxtRecord>,
+    records: Vec<Record>,
+    /// The interface and lowercase name of the host of the service
+    host: (u32, String),
+    host_target: CString,
+    /// The host name reported along with the addresses
+    host_name: String,
+    addresses: Vec<IpAddr>,
+    /// The number of address families the host has been reported to have no address of
+    negative_answers: usize,
+    scope_id: u32,
+    /// The shortest TTL of the addresses reported so far
+    ttl: u32,
+    meta: EventMeta,
+}
+
+impl Resolution {
+    /// Returns the resolution passed as `context` to the operation of a step, and the context of
+    /// its browser.
+    unsafe fn from_raw<'a>(context: *mut c_void) -> (&'a mut Self, &'a mut BonjourBrowserContext) {
+        let resolution = &mut *(context as *mut Self);
+        let ctx = &mut *resolution.ctx;
+        (resolution, ctx)
+    }
+
+    fn is_done(&self) -> bool {
+        self.operation.is_none()
+    }
+
+    fn has_timed_out(&self) -> bool {
+        if self.step == Step::Resolve && !self.browsed {
+            return false;
+        }
+
+        self.step_started.elapsed() >= constants::BONJOUR_RESOLVE_TIMEOUT
+    }
+
+    /// Starts the specified `step` with `start`, which is given the reference of the operation to
+    /// initialize and the context to pass to it. The operation of the previous step is stopped,
+    /// which may be done from its callback.
+    fn start_step(
+        &mut self,
+        ctx: &BonjourBrowserContext,
+        step: Step,
+        start: impl FnOnce(&mut ManagedDNSServiceRef, *mut c_void) -> Result<()>,
+    ) -> Result<()> {
+        let connection = match &ctx.connection {
+            Some(c) => c,
+            None => return Err("not connected to the mDNS daemon".into()),
+        };
+
+        let context = self as *mut Self as *mut c_void;
+
+        self.operation = Some(connection.start(|service| start(service, context))?);
+        self.step = step;
+        self.step_started = Instant::now();
+        self.more_coming = false;
+
+        Ok(())
+    }
+
+    /// Ends the current step once all of its results have been processed, starting the next one.
+    fn end_step(&mut self, ctx: &mut BonjourBrowserContext) -> Result<()> {
+        match self.step {
+            Step::Resolve => Ok(()),
+            Step::QueryRecords => self.look_up_addresses(ctx),
+            Step::LookUpAddresses => self.report_addresses(ctx),
+        }
+    }
+
+    /// Fails the resolution if `result` is an error.
+    fn finish(&mut self, ctx: &BonjourBrowserContext, result: Result<()>) {
+        if let Err(e) = result {
+            self.operation = None;
+            ctx.invoke_callback(Err(e));
+        }
+    }
+
+    fn query_records(
+        &mut self,
+        ctx: &BonjourBrowserContext,
+        fullname: *const c_char,
+    ) -> Result<()> {
+        let rrtype = if ctx.include_raw_records {
+            dns::TYPE_ANY
+        } else {
+            dns::TYPE_SRV
+        };
+
+        let interface_index = self.interface_index;
+
+        self.start_step(ctx, Step::QueryRecords, |service, context| {
+            service.query_record(
+                QueryRecordParams::builder()
+                    .flags(ServiceFlags::FORCE_MULTICAST)
+                    .interface_index(interface_index)
+                    .fullname(fullname)
+                    .rrtype(rrtype)
+                    .rrclass(dns::CLASS_IN)
+                    .callback(Some(query_records_callback))
+                    .context(context)
+                    .build()?,
+            )
+        })
+    }
+
+    fn look_up_addresses(&mut self, ctx: &mut BonjourBrowserContext) -> Result<()> {
+        if let Some(host) = ctx.address_cache.get(&self.host) {
+            debug!("Reusing the addresses of host: {}", self.host.1);
+            return self.report(ctx, host);
+        }
+
+        let _span = operation_span!("getaddrinfo", host_name = self.host.1);
+
+        self.addresses.clear();
+        self.negative_answers = 0;
+        self.scope_id = 0;
+
+        let interface_index = self.interface_index;
+        let protocol = bonjour_util::protocol(ctx.address_family);
+        let host_target = self.host_target.as_ptr();
+
+        let flags = ServiceFlags::FORCE_MULTICAST
+            | (ctx.resolve_flags & ServiceFlags::RETURN_INTERMEDIATES);
+
+        self.start_step(ctx, Step::LookUpAddresses, |service, context| {
+            service.get_address_info(
+                GetAddressInfoParams::builder()
+                    .flags(flags)
+                    .interface_index(interface_index)
+                    .protocol(protocol)
+                    .hostname(host_target)
+                    .callback(Some(get_address_info_callback))
+                    .context(context)
+                    .build()?,
+            )
+        })
+    }
+
+    /// Reports the service once every address of its host has been reported.
+    fn report_addresses(&mut self, ctx: &mut BonjourBrowserContext) -> Result<()> {
+        let mut addresses = mem::take(&mut self.addresses);
+
+        if addresses.is_empty() {
+            return Err("get_address_info_callback() reported no addresses".into());
+        }
+
+        ctx.address_policy.sort(&mut addresses);
+
+        let host = ResolvedHost {
+            name: mem::take(&mut self.host_name),
+            addresses,
+            scope_id: self.scope_id,
+            meta: mem::take(&mut self.meta),
+        };
+
+        ctx.address_cache.insert(
+            self.host.clone(),
+            host.clone(),
+            Duration::from_secs(self.ttl.into()),
+        );
+
+        self.report(ctx, host)
+    }
+
+    /// Reports the service, which is on the specified `host`, and stops resolving it.
+    fn report(&mut self, ctx: &BonjourBrowserContext, host: ResolvedHost) -> Result<()> {
+        self.operation = None;
+
+        // on macOS the bytes are swapped for the port
+        let port: u16 = self.port.to_be();
+
+        let result = ServiceDiscovery::builder()
+            .name(mem::take(&mut self.name))
+            .service_type(ServiceType::from_str(bonjour_util::strip_root(&self.kind))?)
+            .domain(bonjour_util::strip_root(&self.domain).to_string())
+            .host_name(host.name)
+            .address(host.addresses[0])
+            .addresses(host.addresses)
+            .scope_id(host.scope_id)
+            .port(port)
+            .priority(self.priority)
+            .weight(self.weight)
+            .txt(self.txt.take())
+            .raw_records(mem::take(&mut self.records))
+            .meta(host.meta)
+            .build()?;
+
+        ctx.invoke_callback(Ok(result));
+
+        Ok(())
+    }
+}
+
+/// The addresses looked up for the host of a service, which are reused for the other services on
+/// the same host.
+#[derive(Debug, Clone)]
+struct ResolvedHost {
+    name: String,
+    addresses: Vec<IpAddr>,
+    scope_id: u32,
+    meta: EventMeta,
+}
+
 unsafe extern "C" fn browse_callback(
     _sd_ref: DNSServiceRef,
-    _flags: DNSServiceFlags,
+    flags: DNSServiceFlags,
     interface_index: u32,
     error: DNSServiceErrorType,
     name: *const c_char,
@@ -119,13 +806,14 @@ unsafe extern "C" fn browse_callback(
     context: *mut c_void,
 ) {
     let ctx = BonjourBrowserContext::from_raw(context);
-    if let Err(e) = handle_browse(ctx, error, name, regtype, domain, interface_index) {
+    if let Err(e) = handle_browse(ctx, flags, error, name, regtype, domain, interface_index) {
         ctx.invoke_callback(Err(e));
     }
 }
 
 unsafe fn handle_browse(
     ctx: &mut BonjourBrowserContext,
+    flags: DNSServiceFlags,
     error: DNSServiceErrorType,
     name: *const c_char,
     regtype: *const c_char,
@@ -133,71 +821,118 @@ unsafe fn handle_browse(
     interface_index: u32,
 ) -> Result<()> {
     if error != 0 {
-        return Err(format!("browse_callback() reported error (code: {})", error).into());
-    }
-
-    ctx.resolved_name = Some(c_str::copy_raw(name));
-    ctx.resolved_kind = Some(c_str::copy_raw(regtype));
-    ctx.resolved_domain = Some(c_str::copy_raw(domain));
-
-    ManagedDNSServiceRef::default().resolve_service(
-        ServiceResolveParams::builder()
-            .flags(bonjour_sys::kDNSServiceFlagsForceMulticast)
-            .interface_index(interface_index)
-            .name(name)
-            .regtype(regtype)
-            .domain(domain)
-            .callback(Some(resolve_callback))
-            .context(ctx.as_raw())
-            .build()?,
-    )
+        return Err(bonjour_util::error(
+            error,
+            format!("browse_callback() reported error (code: {})", error),
+        )
+        .with_operation(Operation::Browse));
+    }
+
+    if flags & bonjour_sys::kDNSServiceFlagsThresholdReached != 0 {
+        debug!("Browse threshold reached, mDNSResponder reduces its queries");
+    }
+
+    let (name_str, kind, domain_str) = (
+        c_str::raw_to_str(name),
+        c_str::raw_to_str(regtype),
+        c_str::raw_to_str(domain),
+    );
+
+    let key = || {
+        (
+            interface_index,
+            name_str.to_string(),
+            kind.to_string(),
+            domain_str.to_string(),
+        )
+    };
+
+    if flags & bonjour_sys::kDNSServiceFlagsAdd == 0 {
+        // services that have gone away are not reported
+        ctx.debouncer.went_away(key);
+        return Ok(());
+    }
+
+    if ctx.debouncer.has_returned(key) {
+        debug!(
+            "Ignoring service that came back within the debounce window: {}",
+            name_str
+        );
+        return Ok(());
+    }
+
+    let _span = operation_span!(
+        "resolve",
+        name = name_str,
+        service_type = kind,
+        domain = domain_str,
+    );
+
+    // the services of a burst are resolved concurrently, the burst ends once all of them are
+    ctx.start_resolution(true, interface_index, name, regtype, domain)
 }
 
 unsafe extern "C" fn resolve_callback(
     _sd_ref: DNSServiceRef,
     _flags: DNSServiceFlags,
-    interface_index: u32,
+    _interface_index: u32,
     error: DNSServiceErrorType,
-    _fullname: *const c_char,
+    fullname: *const c_char,
     host_target: *const c_char,
     port: u16,
     txt_len: u16,
     txt_record: *const c_uchar,
     context: *mut c_void,
 ) {
-    let ctx = BonjourBrowserContext::from_raw(context);
+    let (resolution, ctx) = Resolution::from_raw(context);
+
+    if resolution.is_done() {
+        return;
+    }
 
     let result = handle_resolve(
+        resolution,
         ctx,
         error,
         port,
-        interface_index,
+        fullname,
         host_target,
         txt_len,
         txt_record,
     );
 
-    if let Err(e) = result {
-        ctx.invoke_callback(Err(e));
-    }
+    resolution.finish(ctx, result);
 }
 
+#[allow(clippy::too_many_arguments)]
 unsafe fn handle_resolve(
-    ctx: &mut BonjourBrowserContext,
+    resolution: &mut Resolution,
+    ctx: &BonjourBrowserContext,
     error: DNSServiceErrorType,
     port: u16,
-    interface_index: u32,
+    fullname: *const c_char,
     host_target: *const c_char,
     txt_len: u16,
     txt_record: *const c_uchar,
 ) -> Result<()> {
     if error != 0 {
-        return Err(format!("error reported by resolve_callback: (code: {})", error).into());
+        return Err(bonjour_util::error(
+            error,
+            format!("error reported by resolve_callback: (code: {})", error),
+        )
+        .with_operation(Operation::Resolve));
+    }
+
+    if port == 0 {
+        // placeholders announced by other implementations with a `SRV` record for port 0
+        resolution.operation = None;
+        debug!("Ignoring placeholder service: {}", resolution.name);
+        return Ok(());
     }
 
-    ctx.resolved_port = port;
+    resolution.port = port;
 
-    ctx.resolved_txt = if txt_len > 1 {
+    resolution.txt = if txt_len > 1 {
         Some(TxtRecord::from(ManagedTXTRecordRef::clone_raw(
             txt_record, txt_len,
         )?))
@@ -205,80 +940,252 @@ unsafe fn handle_resolve(
         None
     };
 
-    ManagedDNSServiceRef::default().get_address_info(
-        GetAddressInfoParams::builder()
-            .flags(bonjour_sys::kDNSServiceFlagsForceMulticast)
-            .interface_index(interface_index)
-            .protocol(1)
-            .hostname(host_target)
-            .callback(Some(get_address_info_callback))
-            .context(ctx.as_raw())
-            .build()?,
-    )
+    let host_target = CStr::from_ptr(host_target);
+
+    resolution.host = (
+        resolution.interface_index,
+        host_target.to_string_lossy().to_ascii_lowercase(),
+    );
+    resolution.host_target = host_target.to_owned();
+
+    // the priority and weight of the service are only available from its SRV record
+    resolution.query_records(ctx, fullname)
 }
 
-unsafe extern "C" fn get_address_info_callback(
+unsafe extern "C" fn query_records_callback(
     _sd_ref: DNSServiceRef,
-    _flags: DNSServiceFlags,
+    flags: DNSServiceFlags,
     _interface_index: u32,
     error: DNSServiceErrorType,
+    fullname: *const c_char,
+    rrtype: u16,
+    rrclass: u16,
+    rdlen: u16,
+    rdata: *const c_void,
+    ttl: u32,
+    context: *mut c_void,
+) {
+    let (resolution, ctx) = Resolution::from_raw(context);
+
+    if resolution.is_done() {
+        return;
+    }
+
+    if error != 0 {
+        // not fatal, the service is reported with the default priority and weight
+        debug!("query_records_callback() reported error (code: {})", error);
+        let result = resolution.look_up_addresses(ctx);
+        resolution.finish(ctx, result);
+        return;
+    }
+
+    let rdata = slice::from_raw_parts(rdata as *const u8, rdlen as usize);
+
+    if ctx.include_raw_records {
+        let record = Record::builder()
+            .name(bonjour_util::normalize_domain(&c_str::copy_raw(fullname)))
+            .rrtype(rrtype)
+            .rrclass(rrclass)
+            .rdata(rdata.to_vec())
+            .ttl(ttl)
+            .build();
+
+        match record {
+            Ok(record) => resolution.records.push(record),
+            // not fatal, the service is reported without the record
+            Err(e) => ctx.invoke_callback(Err(e.into())),
+        }
+    }
+
+    if rrtype == dns::TYPE_SRV {
+        if let Some(srv) = dns::parse_srv_rdata(rdata) {
+            resolution.priority = srv.priority;
+            resolution.weight = srv.weight;
+        }
+    }
+
+    // the results coming may be of other operations, so the step ends on the next sweep
+    if flags & bonjour_sys::kDNSServiceFlagsMoreComing != 0 {
+        resolution.more_coming = true;
+        return;
+    }
+
+    let result = resolution.look_up_addresses(ctx);
+    resolution.finish(ctx, result);
+}
+
+unsafe extern "C" fn get_address_info_callback(
+    _sd_ref: DNSServiceRef,
+    flags: DNSServiceFlags,
+    interface_index: u32,
+    error: DNSServiceErrorType,
     hostname: *const c_char,
     address: *const bonjour_sys::sockaddr,
-    _ttl: u32,
+    ttl: u32,
     context: *mut c_void,
 ) {
-    let ctx = BonjourBrowserContext::from_raw(context);
-    if let Err(e) = handle_get_address_info(ctx, error, address, hostname) {
-        ctx.invoke_callback(Err(e));
+    let (resolution, ctx) = Resolution::from_raw(context);
+
+    if resolution.is_done() {
+        return;
     }
+
+    let meta = EventMeta::builder()
+        .flags(flags)
+        .interface_index(interface_index)
+        .ttl(Some(ttl))
+        .build()
+        // every field has a default, building never fails
+        .unwrap_or_default();
+
+    let result = handle_get_address_info(resolution, ctx, meta, error, address, hostname);
+    resolution.finish(ctx, result);
 }
 
 unsafe fn handle_get_address_info(
+    resolution: &mut Resolution,
     ctx: &mut BonjourBrowserContext,
+    meta: EventMeta,
     error: DNSServiceErrorType,
     address: *const bonjour_sys::sockaddr,
     hostname: *const c_char,
 ) -> Result<()> {
-    // this callback runs multiple times for some reason
-    if ctx.resolved_name.is_none() {
+    if error == bonjour_sys::kDNSServiceErr_NoSuchRecord {
+        // a negative answer for one of the address families, the other may still have addresses
+        resolution.negative_answers += 1;
+
+        let families = match ctx.address_family {
+            AddressFamily::Both => 2,
+            _ => 1,
+        };
+
+        if resolution.addresses.is_empty() && resolution.negative_answers >= families {
+            return Err(Error::with_kind(
+                ErrorKind::NoSuchRecord,
+                format!("host has no addresses: {}", resolution.host.1),
+            )
+            .with_operation(Operation::GetAddrInfo));
+        }
+
         return Ok(());
     }
 
     if error != 0 {
-        return Err(format!(
-            "get_address_info_callback() reported error (code: {})",
-            error
+        return Err(bonjour_util::error(
+            error,
+            format!(
+                "get_address_info_callback() reported error (code: {})",
+                error
+            ),
         )
-        .into());
+        .with_operation(Operation::GetAddrInfo));
     }
 
-    // on macOS the bytes are swapped for the port
-    let port: u16 = ctx.resolved_port.to_be();
+    let address = address as *const sockaddr;
 
-    // on macOS the bytes are swapped for the ip
-    let ip = {
-        let address = address as *const sockaddr_in;
-        assert_not_null!(address);
-        let s_addr = (*address).sin_addr.s_addr.to_le_bytes();
-        IpAddr::from(s_addr).to_string()
+    let ip = match ffi::sockaddr_to_ip(address) {
+        Some(ip) => ip,
+        None => return Err("get_address_info_callback() reported unknown address family".into()),
     };
 
-    let hostname = c_str::copy_raw(hostname);
-    let domain = bonjour_util::normalize_domain(&ctx.resolved_domain.take().unwrap());
-    let kind = bonjour_util::normalize_domain(&ctx.resolved_kind.take().unwrap());
-
-    let result = ServiceDiscovery::builder()
-        .name(ctx.resolved_name.take().unwrap())
-        .service_type(ServiceType::from_str(&kind)?)
-        .domain(domain)
-        .host_name(hostname)
-        .address(ip)
-        .port(port)
-        .txt(ctx.resolved_txt.take())
-        .build()
-        .expect("could not build ServiceResolution");
+    let ttl = meta.ttl().unwrap_or(0);
+
+    resolution.ttl = if resolution.addresses.is_empty() {
+        ttl
+    } else {
+        resolution.ttl.min(ttl)
+    };
+
+    resolution.addresses.push(ip);
+
+    if resolution.scope_id == 0 {
+        resolution.scope_id = ffi::link_local_scope_id(&ip, ffi::sockaddr_scope_id(address));
+    }
+
+    let more_coming = meta.flags() & bonjour_sys::kDNSServiceFlagsMoreComing != 0;
+
+    resolution.host_name = c_str::copy_raw(hostname);
+    resolution.meta = meta;
+
+    // the results coming may be of other operations, so the step ends on the next sweep
+    if more_coming {
+        resolution.more_coming = true;
+        return Ok(());
+    }
+
+    resolution.report_addresses(ctx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    ctx.invoke_callback(Ok(result));
+    #[test]
+    fn resolve_flags_keep_flags_of_resolutions() {
+        let mut ctx = BonjourBrowserContext::default();
+        ctx.set_resolve_flags(ServiceFlags::WAKE_ON_RESOLVE | ServiceFlags::INCLUDE_P2P);
 
-    Ok(())
+        assert_eq!(ctx.resolve_flags, ServiceFlags::WAKE_ON_RESOLVE);
+    }
+
+    #[test]
+    fn callback_can_replace_itself() {
+        let ctx = BonjourBrowserContext::default();
+        let handle = ctx.callbacks.clone();
+        let calls: Arc<Mutex<Vec<&str>>> = Arc::default();
+        let (first, second) = (calls.clone(), calls.clone());
+
+        ctx.callbacks
+            .set_service_discovered_callback(Box::new(move |_, _| {
+                first.lock().unwrap().push("first");
+                let second = second.clone();
+                handle.set_service_discovered_callback(Box::new(move |_, _| {
+                    second.lock().unwrap().push("second");
+                }));
+            }));
+
+        ctx.invoke_callback(Err("foo".into()));
+        ctx.invoke_callback(Err("bar".into()));
+
+        assert_eq!(*calls.lock().unwrap(), vec!["first", "second"]);
+    }
+
+    #[test]
+    fn events_are_delivered_once_burst_has_ended() {
+        let ctx = BonjourBrowserContext::default();
+        let batches: Arc<Mutex<Vec<usize>>> = Arc::default();
+        let batches_clone = batches.clone();
+
+        ctx.callbacks
+            .set_service_batch_callback(Box::new(move |events, _| {
+                batches_clone.lock().unwrap().push(events.len());
+            }));
+
+        ctx.invoke_callback(Err("foo".into()));
+        ctx.invoke_callback(Err("bar".into()));
+
+        assert!(batches.lock().unwrap().is_empty());
+
+        ctx.end_burst();
+        ctx.end_burst();
+
+        assert_eq!(*batches.lock().unwrap(), vec![2]);
+    }
+
+    #[test]
+    fn burst_ends_once_no_service_is_being_resolved() {
+        let mut ctx = BonjourBrowserContext::default();
+        let batches: Arc<Mutex<Vec<usize>>> = Arc::default();
+        let batches_clone = batches.clone();
+
+        ctx.callbacks
+            .set_service_batch_callback(Box::new(move |events, _| {
+                batches_clone.lock().unwrap().push(events.len());
+            }));
+
+        ctx.invoke_callback(Err("foo".into()));
+        ctx.sweep();
+
+        assert_eq!(*batches.lock().unwrap(), vec![1]);
+    }
 }
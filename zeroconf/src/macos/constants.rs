@@ -1,4 +1,27 @@
-use bonjour_sys::DNSServiceFlags;
+use bonjour_sys::DNSServiceErrorType;
+use std::time::Duration;
 
 pub const BONJOUR_IF_UNSPEC: u32 = 0;
-pub const BONJOUR_RENAME_FLAGS: DNSServiceFlags = 0;
+/// `kDNSServiceInterfaceIndexP2P` which is defined as `((uint32_t)-3)`
+pub const BONJOUR_IF_P2P: u32 = u32::MAX - 2;
+pub const BONJOUR_RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+/// The maximum time to wait for each step of resolving a discovered service
+pub const BONJOUR_RESOLVE_TIMEOUT: Duration = Duration::from_secs(5);
+/// `kDNSServiceErr_PolicyDenied`, which is not defined by older versions of the Bonjour SDK
+pub const BONJOUR_ERR_POLICY_DENIED: DNSServiceErrorType = -65570;
+/// `kDNSServiceErr_DefunctConnection`, which is not defined by older versions of the Bonjour SDK
+pub const BONJOUR_ERR_DEFUNCT_CONNECTION: DNSServiceErrorType = -65569;
+/// How to fix `kDNSServiceErr_ServiceNotRunning`
+#[cfg(target_vendor = "apple")]
+pub const BONJOUR_NOT_RUNNING_HINT: &str =
+    "mDNSResponder is not running, check that it has not been disabled with `launchctl`";
+/// How to fix `kDNSServiceErr_ServiceNotRunning`
+#[cfg(target_os = "linux")]
+pub const BONJOUR_NOT_RUNNING_HINT: &str =
+    "mdnsd is not running or `/var/run/mdnsd` cannot be reached. Start mdnsd or, in a container, \
+     mount the socket of the host (e.g. `-v /var/run/mdnsd:/var/run/mdnsd`)";
+/// How to fix `kDNSServiceErr_ServiceNotRunning`
+#[cfg(target_os = "windows")]
+pub const BONJOUR_NOT_RUNNING_HINT: &str =
+    "the Bonjour service is not running. Install it with the Bonjour SDK for Windows and check \
+     that it is started in `services.msc`, or enable the `windows-native` feature";
@@ -3,29 +3,86 @@
 use super::service_ref::ManagedDNSServiceRef;
 use crate::event_loop::TEventLoop;
 use crate::{ffi, Result};
+use bonjour_sys::dnssd_sock_t;
 use std::marker::PhantomData;
 use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::Duration;
 
 #[derive(new)]
 pub struct BonjourEventLoop<'a> {
     service: Arc<Mutex<ManagedDNSServiceRef>>,
+    #[new(default)]
+    on_poll: Option<Box<dyn Fn() + 'a>>,
     phantom: PhantomData<&'a ManagedDNSServiceRef>,
 }
 
+impl<'a> BonjourEventLoop<'a> {
+    /// Sets a function to invoke after each iteration of the event loop. This is used to perform
+    /// periodic work that is not driven by Bonjour callbacks.
+    pub(crate) fn with_on_poll(mut self, on_poll: Box<dyn Fn() + 'a>) -> Self {
+        self.on_poll = Some(on_poll);
+        self
+    }
+
+    /// Returns the id (see `ManagedDNSServiceRef::id()`) and the socket of the underlying
+    /// `ManagedDNSServiceRef`, or `None` while waiting to reconnect to the mDNS daemon.
+    pub(super) fn socket(&self) -> Option<(u64, dnssd_sock_t)> {
+        let service = self.service.lock().unwrap();
+
+        if service.is_null() {
+            None
+        } else {
+            Some((service.id(), service.sock_fd()))
+        }
+    }
+
+    /// Processes the result available on the socket of the underlying `ManagedDNSServiceRef`,
+    /// which is released if the mDNS daemon has stopped running.
+    pub(super) fn process(&self) -> Result<()> {
+        let mut service = self.service.lock().unwrap();
+
+        if !service.is_null() && !service.process_result_alive()? {
+            warn!("mDNS daemon is no longer running, waiting to reconnect");
+            *service = ManagedDNSServiceRef::default();
+        }
+
+        Ok(())
+    }
+
+    /// Invokes the function set with `with_on_poll()`, if any. The service must not be locked as
+    /// this may re-initialize it.
+    pub(super) fn run_on_poll(&self) {
+        if let Some(f) = &self.on_poll {
+            f();
+        }
+    }
+}
+
 impl<'a> TEventLoop for BonjourEventLoop<'a> {
     /// Polls for new events.
     ///
     /// Prior to calling `ManagedDNSServiceRef::process_result()`, this function performs a unix
     /// `select()` on the underlying socket with the specified timeout. If the socket contains no
     /// new data, the blocking call is not made.
+    ///
+    /// If the mDNS daemon has stopped running, the underlying `ManagedDNSServiceRef` is released
+    /// and this function sleeps for the specified timeout until it has been re-initialized.
+    ///
+    /// See `BonjourEventLoopGroup` to poll many event loops at once.
     fn poll(&self, timeout: Duration) -> Result<()> {
-        let service = self.service.lock().unwrap();
-        let select = unsafe { ffi::macos::read_select(service.sock_fd(), timeout)? };
-        if select > 0 {
-            service.process_result()
-        } else {
-            Ok(())
+        match self.socket() {
+            // waiting to reconnect to the daemon
+            None => thread::sleep(timeout),
+            Some((_, sock_fd)) => {
+                if unsafe { ffi::bonjour::read_select(sock_fd, timeout)? } > 0 {
+                    self.process()?;
+                }
+            }
         }
+
+        self.run_on_poll();
+
+        Ok(())
     }
 }
@@ -0,0 +1,381 @@
+//! Event loop for running many `MdnsService`s and `MdnsBrowser`s on the same thread.
+//!
+//! Each `BonjourEventLoop` waits for its own socket with `select()`, so polling hundreds of them
+//! in turn costs as many system calls per iteration, even if none of them has any results. A
+//! `BonjourEventLoopGroup` registers the sockets of its event loops with a single `kqueue` (or
+//! `epoll` on Linux) instead, and only processes the results of the sockets that are ready.
+
+use super::event_loop::BonjourEventLoop;
+use super::registration_handle::BonjourRegistrationHandle;
+use crate::event_loop::TEventLoop;
+use crate::Result;
+use bonjour_sys::dnssd_sock_t;
+use std::cell::{Cell, RefCell};
+use std::time::Duration;
+
+/// Polls many event loops (e.g. of hundreds of browsers) at once.
+///
+/// # Examples
+/// ```no_run
+/// use std::time::Duration;
+/// use zeroconf::macos::event_loop_group::BonjourEventLoopGroup;
+/// use zeroconf::prelude::*;
+/// use zeroconf::{MdnsBrowser, ServiceType};
+///
+/// let mut browsers: Vec<MdnsBrowser> = ["http", "ipp", "ssh"]
+///     .iter()
+///     .map(|name| MdnsBrowser::new(ServiceType::new(name, "tcp").unwrap()))
+///     .collect();
+///
+/// let mut group = BonjourEventLoopGroup::new().unwrap();
+///
+/// for browser in &mut browsers {
+///     group.add(browser.browse_services().unwrap()).unwrap();
+/// }
+///
+/// loop {
+///     group.poll(Duration::from_secs(1)).unwrap();
+/// }
+/// ```
+pub struct BonjourEventLoopGroup<'a> {
+    poller: Poller,
+    members: Vec<Member<'a>>,
+    /// Reused for the members that are ready on each poll
+    ready: RefCell<Vec<usize>>,
+}
+
+struct Member<'a> {
+    kind: MemberKind<'a>,
+    /// The id and socket of the `ManagedDNSServiceRef` registered with the poller, the service
+    /// has to be registered again once it has been replaced
+    registered: Cell<Option<(u64, dnssd_sock_t)>>,
+}
+
+enum MemberKind<'a> {
+    EventLoop(BonjourEventLoop<'a>),
+    Registration(BonjourRegistrationHandle<'a>),
+}
+
+impl<'a> Member<'a> {
+    fn event_loop(&self) -> &BonjourEventLoop<'a> {
+        match &self.kind {
+            MemberKind::EventLoop(e) => e,
+            MemberKind::Registration(r) => r.event_loop(),
+        }
+    }
+}
+
+impl<'a> BonjourEventLoopGroup<'a> {
+    /// Creates a new, empty `BonjourEventLoopGroup`.
+    pub fn new() -> Result<Self> {
+        Ok(Self {
+            poller: Poller::new()?,
+            members: Vec::new(),
+            ready: RefCell::default(),
+        })
+    }
+
+    /// Adds the specified event loop (e.g. of a browser) to this group, which polls it from now
+    /// on.
+    pub fn add(&mut self, event_loop: BonjourEventLoop<'a>) -> Result<()> {
+        self.push(MemberKind::EventLoop(event_loop)).map(|_| ())
+    }
+
+    /// Adds the specified registration handle to this group, which polls it from now on. Returns
+    /// the index to change the registration through with [`registration_mut()`].
+    ///
+    /// [`registration_mut()`]: #method.registration_mut
+    pub fn add_registration(&mut self, handle: BonjourRegistrationHandle<'a>) -> Result<usize> {
+        self.push(MemberKind::Registration(handle))
+    }
+
+    /// Returns the registration handle added at `index` with [`add_registration()`], if any.
+    ///
+    /// [`add_registration()`]: #method.add_registration
+    pub fn registration_mut(&mut self, index: usize) -> Option<&mut BonjourRegistrationHandle<'a>> {
+        match &mut self.members.get_mut(index)?.kind {
+            MemberKind::Registration(r) => Some(r),
+            MemberKind::EventLoop(_) => None,
+        }
+    }
+
+    /// Returns the amount of event loops and registration handles in this group.
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    /// Returns true if nothing has been added to this group.
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    fn push(&mut self, kind: MemberKind<'a>) -> Result<usize> {
+        let member = Member {
+            kind,
+            registered: Cell::new(None),
+        };
+
+        let index = self.members.len();
+        self.register(index, &member)?;
+        self.members.push(member);
+
+        Ok(index)
+    }
+
+    /// Registers the socket of `member` with the poller if it has changed since the last poll,
+    /// e.g. because the member reconnected to the mDNS daemon.
+    fn register(&self, index: usize, member: &Member<'a>) -> Result<()> {
+        let socket = member.event_loop().socket();
+
+        if socket == member.registered.get() {
+            return Ok(());
+        }
+
+        // the sockets of released services are closed, which removes them from the poller
+        if let Some((_, sock_fd)) = socket {
+            self.poller.register(sock_fd, index)?;
+        }
+
+        member.registered.set(socket);
+
+        Ok(())
+    }
+}
+
+impl<'a> TEventLoop for BonjourEventLoopGroup<'a> {
+    /// Polls for new events of every event loop of this group.
+    ///
+    /// Waits at most `timeout` for any of the sockets of the event loops to be ready, and then
+    /// processes the results of the ones that are. Returns the first error of an event loop.
+    fn poll(&self, timeout: Duration) -> Result<()> {
+        for (index, member) in self.members.iter().enumerate() {
+            self.register(index, member)?;
+        }
+
+        let mut ready = self.ready.borrow_mut();
+        ready.clear();
+
+        self.poller.wait(&mut ready, timeout)?;
+
+        for index in ready.iter() {
+            if let Some(member) = self.members.get(*index) {
+                member.event_loop().process()?;
+            }
+        }
+
+        for member in &self.members {
+            member.event_loop().run_on_poll();
+        }
+
+        Ok(())
+    }
+}
+
+/// The maximum amount of ready sockets reported by a single wait, the others are reported by the
+/// next poll
+const MAX_EVENTS: usize = 64;
+
+fn os_error(function: &str) -> crate::error::Error {
+    format!("{}: {}", function, std::io::Error::last_os_error()).into()
+}
+
+#[cfg(target_os = "linux")]
+use epoll::Poller;
+
+#[cfg(target_os = "linux")]
+mod epoll {
+    use super::{os_error, MAX_EVENTS};
+    use crate::Result;
+    use std::os::unix::io::RawFd;
+    use std::time::Duration;
+    use std::{io, mem};
+
+    /// Waits for the sockets of a group with `epoll`.
+    pub struct Poller(RawFd);
+
+    impl Poller {
+        pub fn new() -> Result<Self> {
+            let fd = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+
+            if fd < 0 {
+                Err(os_error("epoll_create1()"))
+            } else {
+                Ok(Self(fd))
+            }
+        }
+
+        /// Reports `token` once `fd` is readable, replacing the token it was registered with.
+        pub fn register(&self, fd: RawFd, token: usize) -> Result<()> {
+            let mut event = libc::epoll_event {
+                events: libc::EPOLLIN as u32,
+                u64: token as u64,
+            };
+
+            let mut result =
+                unsafe { libc::epoll_ctl(self.0, libc::EPOLL_CTL_ADD, fd, &mut event) };
+
+            if result < 0 && io::Error::last_os_error().raw_os_error() == Some(libc::EEXIST) {
+                result = unsafe { libc::epoll_ctl(self.0, libc::EPOLL_CTL_MOD, fd, &mut event) };
+            }
+
+            if result < 0 {
+                Err(os_error("epoll_ctl()"))
+            } else {
+                Ok(())
+            }
+        }
+
+        /// Waits at most `timeout` for any of the registered sockets to be readable, and adds
+        /// the tokens of the ones that are to `tokens`.
+        pub fn wait(&self, tokens: &mut Vec<usize>, timeout: Duration) -> Result<()> {
+            let mut events: [libc::epoll_event; MAX_EVENTS] = unsafe { mem::zeroed() };
+
+            let count = unsafe {
+                libc::epoll_wait(
+                    self.0,
+                    events.as_mut_ptr(),
+                    MAX_EVENTS as i32,
+                    timeout_millis(timeout),
+                )
+            };
+
+            if count < 0 {
+                return match io::Error::last_os_error().raw_os_error() {
+                    // interrupted by a signal, as if the timeout had elapsed
+                    Some(libc::EINTR) => Ok(()),
+                    _ => Err(os_error("epoll_wait()")),
+                };
+            }
+
+            tokens.extend(events[..count as usize].iter().map(|e| e.u64 as usize));
+
+            Ok(())
+        }
+    }
+
+    impl Drop for Poller {
+        fn drop(&mut self) {
+            unsafe { libc::close(self.0) };
+        }
+    }
+
+    /// Returns the specified timeout in milliseconds, rounded up so that short timeouts do not
+    /// spin.
+    pub(super) fn timeout_millis(timeout: Duration) -> i32 {
+        let millis = timeout.as_micros().div_ceil(1000);
+        millis.min(i32::MAX as u128) as i32
+    }
+}
+
+#[cfg(target_vendor = "apple")]
+use kqueue::Poller;
+
+#[cfg(target_vendor = "apple")]
+mod kqueue {
+    use super::{os_error, MAX_EVENTS};
+    use crate::Result;
+    use libc::c_void;
+    use std::os::unix::io::RawFd;
+    use std::time::Duration;
+    use std::{io, mem, ptr};
+
+    /// Waits for the sockets of a group with `kqueue`.
+    pub struct Poller(RawFd);
+
+    impl Poller {
+        pub fn new() -> Result<Self> {
+            let fd = unsafe { libc::kqueue() };
+
+            if fd < 0 {
+                Err(os_error("kqueue()"))
+            } else {
+                Ok(Self(fd))
+            }
+        }
+
+        /// Reports `token` once `fd` is readable, replacing the token it was registered with.
+        pub fn register(&self, fd: RawFd, token: usize) -> Result<()> {
+            let change = libc::kevent {
+                ident: fd as usize,
+                filter: libc::EVFILT_READ,
+                flags: libc::EV_ADD,
+                fflags: 0,
+                data: 0,
+                udata: token as *mut c_void,
+            };
+
+            let result =
+                unsafe { libc::kevent(self.0, &change, 1, ptr::null_mut(), 0, ptr::null()) };
+
+            if result < 0 {
+                Err(os_error("kevent()"))
+            } else {
+                Ok(())
+            }
+        }
+
+        /// Waits at most `timeout` for any of the registered sockets to be readable, and adds
+        /// the tokens of the ones that are to `tokens`.
+        pub fn wait(&self, tokens: &mut Vec<usize>, timeout: Duration) -> Result<()> {
+            let mut events: [libc::kevent; MAX_EVENTS] = unsafe { mem::zeroed() };
+
+            let timeout = libc::timespec {
+                tv_sec: timeout.as_secs() as libc::time_t,
+                tv_nsec: timeout.subsec_nanos() as libc::c_long,
+            };
+
+            let count = unsafe {
+                libc::kevent(
+                    self.0,
+                    ptr::null(),
+                    0,
+                    events.as_mut_ptr(),
+                    MAX_EVENTS as i32,
+                    &timeout,
+                )
+            };
+
+            if count < 0 {
+                return match io::Error::last_os_error().raw_os_error() {
+                    // interrupted by a signal, as if the timeout had elapsed
+                    Some(libc::EINTR) => Ok(()),
+                    _ => Err(os_error("kevent()")),
+                };
+            }
+
+            tokens.extend(events[..count as usize].iter().map(|e| e.udata as usize));
+
+            Ok(())
+        }
+    }
+
+    impl Drop for Poller {
+        fn drop(&mut self) {
+            unsafe { libc::close(self.0) };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn timeout_millis_rounds_up() {
+        use super::epoll::timeout_millis;
+
+        assert_eq!(timeout_millis(Duration::from_secs(0)), 0);
+        assert_eq!(timeout_millis(Duration::from_micros(1)), 1);
+        assert_eq!(timeout_millis(Duration::from_millis(1500)), 1500);
+        assert_eq!(timeout_millis(Duration::from_secs(u64::MAX)), i32::MAX);
+    }
+
+    #[test]
+    fn empty_group_waits_for_timeout() {
+        let group = BonjourEventLoopGroup::new().unwrap();
+
+        assert!(group.is_empty());
+        group.poll(Duration::from_millis(1)).unwrap();
+    }
+}
@@ -0,0 +1,167 @@
+//! Bonjour implementation for cross-platform host name resolver
+
+use super::service_ref::{GetAddressInfoParams, ManagedDNSServiceRef};
+use super::{bonjour_util, constants};
+use crate::error::Operation;
+use crate::ffi::{self, c_str, FromRaw, SharedContext};
+use crate::prelude::*;
+use crate::{AddressFamily, EventLoop, NetworkInterface, Result, ServiceFlags};
+use crate::{HostNameResolution, HostNameResolvedCallback};
+use bonjour_sys::{DNSServiceErrorType, DNSServiceFlags, DNSServiceRef};
+use libc::{c_char, c_void, sockaddr};
+use std::any::Any;
+use std::ffi::CString;
+use std::fmt::{self, Formatter};
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+pub struct BonjourMdnsHostNameResolver {
+    service: Arc<Mutex<ManagedDNSServiceRef>>,
+    host_name: CString,
+    interface_index: u32,
+    address_family: AddressFamily,
+    flags: ServiceFlags,
+    context: SharedContext<BonjourHostNameResolverContext>,
+}
+
+impl THostNameResolver for BonjourMdnsHostNameResolver {
+    fn new(host_name: &str) -> Self {
+        Self {
+            service: Arc::default(),
+            host_name: c_string!(host_name),
+            interface_index: constants::BONJOUR_IF_UNSPEC,
+            address_family: AddressFamily::default(),
+            flags: ServiceFlags::empty(),
+            context: SharedContext::default(),
+        }
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface_index = bonjour_util::interface_index(interface);
+    }
+
+    fn set_address_family(&mut self, address_family: AddressFamily) {
+        self.address_family = address_family;
+    }
+
+    fn set_flags(&mut self, flags: ServiceFlags) {
+        self.flags = flags;
+    }
+
+    fn set_host_name_resolved_callback(
+        &mut self,
+        host_name_resolved_callback: Box<HostNameResolvedCallback>,
+    ) {
+        unsafe {
+            (*self.context.as_ptr()).host_name_resolved_callback = Some(host_name_resolved_callback)
+        };
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        unsafe { (*self.context.as_ptr()).user_context = Some(Arc::from(context)) };
+    }
+
+    fn resolve(&mut self) -> Result<EventLoop<'_>> {
+        debug!("Resolving host name: {:?}", self);
+
+        self.service
+            .lock()
+            .unwrap()
+            .retain_context(self.context.retain())
+            .get_address_info(
+                GetAddressInfoParams::builder()
+                    .flags(self.flags)
+                    .interface_index(self.interface_index)
+                    .protocol(bonjour_util::protocol(self.address_family))
+                    .hostname(self.host_name.as_ptr())
+                    .callback(Some(get_address_info_callback))
+                    .context(self.context.as_ptr() as *mut c_void)
+                    .build()?,
+            )?;
+
+        Ok(EventLoop::new(self.service.clone()))
+    }
+}
+
+#[derive(Default, FromRaw)]
+struct BonjourHostNameResolverContext {
+    host_name_resolved_callback: Option<Box<HostNameResolvedCallback>>,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl BonjourHostNameResolverContext {
+    fn invoke_callback(&self, result: Result<HostNameResolution>) {
+        if let Some(f) = &self.host_name_resolved_callback {
+            f(result, self.user_context.clone());
+        } else {
+            warn!("attempted to invoke callback but none was set");
+        }
+    }
+}
+
+impl fmt::Debug for BonjourHostNameResolverContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BonjourHostNameResolverContext").finish()
+    }
+}
+
+unsafe extern "C" fn get_address_info_callback(
+    _sd_ref: DNSServiceRef,
+    flags: DNSServiceFlags,
+    _interface_index: u32,
+    error: DNSServiceErrorType,
+    hostname: *const c_char,
+    address: *const bonjour_sys::sockaddr,
+    _ttl: u32,
+    context: *mut c_void,
+) {
+    let ctx = BonjourHostNameResolverContext::from_raw(context);
+    if let Err(e) = handle_get_address_info(ctx, flags, error, address, hostname) {
+        ctx.invoke_callback(Err(e));
+    }
+}
+
+unsafe fn handle_get_address_info(
+    ctx: &BonjourHostNameResolverContext,
+    flags: DNSServiceFlags,
+    error: DNSServiceErrorType,
+    address: *const bonjour_sys::sockaddr,
+    hostname: *const c_char,
+) -> Result<()> {
+    if error != 0 {
+        return Err(bonjour_util::error(
+            error,
+            format!(
+                "get_address_info_callback() reported error (code: {})",
+                error
+            ),
+        )
+        .with_operation(Operation::GetAddrInfo));
+    }
+
+    if flags & bonjour_sys::kDNSServiceFlagsAdd == 0 {
+        // the address is no longer valid
+        return Ok(());
+    }
+
+    let address = address as *const sockaddr;
+
+    let ip = match ffi::sockaddr_to_ip(address) {
+        Some(ip) => ip,
+        None => return Err("get_address_info_callback() reported unknown address family".into()),
+    };
+
+    let result = HostNameResolution::builder()
+        .host_name(bonjour_util::normalize_domain(&c_str::copy_raw(hostname)))
+        .address(ip)
+        .scope_id(ffi::link_local_scope_id(
+            &ip,
+            ffi::sockaddr_scope_id(address),
+        ))
+        .dnssec_status(bonjour_util::dnssec_status(flags))
+        .build()?;
+
+    ctx.invoke_callback(Ok(result));
+
+    Ok(())
+}
@@ -1,15 +1,23 @@
 //! macOS-specific ZeroConf bindings
 //!
-//! This module wraps the [Bonjour] mDNS implementation which is distributed with macOS.
+//! This module wraps the [Bonjour] mDNS implementation which is distributed with macOS. It is also
+//! used on Linux with the `mdnsresponder` feature, against the `libdns_sd` of mDNSResponder.
 //!
 //! [Bonjour]: https://en.wikipedia.org/wiki/Bonjour_(software)
 
 pub(crate) mod constants;
 
+pub mod address_resolver;
 pub mod bonjour_util;
 pub mod browser;
 pub mod event_loop;
+pub mod event_loop_group;
+pub mod host_name_resolver;
+pub mod port_mapping;
+pub mod record_query;
+pub mod registration_handle;
 pub mod service;
 pub mod service_ref;
+pub mod service_resolver;
 pub mod txt_record;
 pub mod txt_record_ref;
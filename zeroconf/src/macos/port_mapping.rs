@@ -0,0 +1,154 @@
+//! Bonjour implementation for NAT port mappings
+
+use super::service_ref::{ManagedDNSServiceRef, NATPortMappingCreateParams};
+use super::{bonjour_util, constants};
+use crate::error::{Error, Operation};
+use crate::ffi::{FromRaw, SharedContext};
+use crate::prelude::*;
+use crate::{EventLoop, NetworkInterface, Result, ServiceFlags};
+use crate::{PortMappedCallback, PortMappingProtocol, PortMappingResult};
+use bonjour_sys::{DNSServiceErrorType, DNSServiceFlags, DNSServiceProtocol, DNSServiceRef};
+use libc::c_void;
+use std::any::Any;
+use std::fmt::{self, Formatter};
+use std::net::Ipv4Addr;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+pub struct BonjourPortMapping {
+    service: Arc<Mutex<ManagedDNSServiceRef>>,
+    protocol: PortMappingProtocol,
+    internal_port: u16,
+    external_port: u16,
+    ttl: u32,
+    interface_index: u32,
+    context: SharedContext<BonjourPortMappingContext>,
+}
+
+impl TPortMapping for BonjourPortMapping {
+    fn new(protocol: PortMappingProtocol, internal_port: u16) -> Self {
+        Self {
+            service: Arc::default(),
+            protocol,
+            internal_port,
+            external_port: 0,
+            ttl: 0,
+            interface_index: constants::BONJOUR_IF_UNSPEC,
+            context: SharedContext::default(),
+        }
+    }
+
+    fn set_external_port(&mut self, external_port: u16) {
+        self.external_port = external_port;
+    }
+
+    fn set_ttl(&mut self, ttl: u32) {
+        self.ttl = ttl;
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface_index = bonjour_util::interface_index(interface);
+    }
+
+    fn set_port_mapped_callback(&mut self, port_mapped_callback: Box<PortMappedCallback>) {
+        unsafe { (*self.context.as_ptr()).port_mapped_callback = Some(port_mapped_callback) };
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        unsafe { (*self.context.as_ptr()).user_context = Some(Arc::from(context)) };
+    }
+
+    fn create(&mut self) -> Result<EventLoop<'_>> {
+        debug!("Creating port mapping: {:?}", self);
+
+        self.service
+            .lock()
+            .unwrap()
+            .retain_context(self.context.retain())
+            .nat_port_mapping_create(
+                NATPortMappingCreateParams::builder()
+                    .flags(ServiceFlags::empty())
+                    .interface_index(self.interface_index)
+                    .protocol(protocol(self.protocol))
+                    .internal_port(self.internal_port)
+                    .external_port(self.external_port)
+                    .ttl(self.ttl)
+                    .callback(Some(port_mapping_callback))
+                    .context(self.context.as_ptr() as *mut c_void)
+                    .build()?,
+            )?;
+
+        Ok(EventLoop::new(self.service.clone()))
+    }
+}
+
+#[derive(Default, FromRaw)]
+struct BonjourPortMappingContext {
+    port_mapped_callback: Option<Box<PortMappedCallback>>,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl BonjourPortMappingContext {
+    fn invoke_callback(&self, result: Result<PortMappingResult>) {
+        if let Some(f) = &self.port_mapped_callback {
+            f(result, self.user_context.clone());
+        } else {
+            warn!("attempted to invoke callback but none was set");
+        }
+    }
+}
+
+impl fmt::Debug for BonjourPortMappingContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BonjourPortMappingContext").finish()
+    }
+}
+
+fn protocol(protocol: PortMappingProtocol) -> DNSServiceProtocol {
+    match protocol {
+        PortMappingProtocol::Tcp => bonjour_sys::kDNSServiceProtocol_TCP,
+        PortMappingProtocol::Udp => bonjour_sys::kDNSServiceProtocol_UDP,
+    }
+}
+
+unsafe extern "C" fn port_mapping_callback(
+    _sd_ref: DNSServiceRef,
+    _flags: DNSServiceFlags,
+    _interface_index: u32,
+    error: DNSServiceErrorType,
+    external_address: u32,
+    protocol: DNSServiceProtocol,
+    internal_port: u16,
+    external_port: u16,
+    ttl: u32,
+    context: *mut c_void,
+) {
+    let ctx = BonjourPortMappingContext::from_raw(context);
+
+    if error != 0 {
+        ctx.invoke_callback(Err(bonjour_util::error(
+            error,
+            format!("port_mapping_callback() reported error (code: {})", error),
+        )
+        .with_operation(Operation::MapPort)));
+        return;
+    }
+
+    let protocol = if protocol & bonjour_sys::kDNSServiceProtocol_UDP != 0 {
+        PortMappingProtocol::Udp
+    } else {
+        PortMappingProtocol::Tcp
+    };
+
+    // the address and ports are in network byte order
+    let result = PortMappingResult::builder()
+        .external_address(Ipv4Addr::from(u32::from_be(external_address)))
+        .protocol(protocol)
+        .internal_port(u16::from_be(internal_port))
+        .external_port(u16::from_be(external_port))
+        .ttl(ttl)
+        .build()
+        .map_err(Error::from);
+
+    ctx.invoke_callback(result);
+}
@@ -0,0 +1,152 @@
+//! Bonjour implementation for cross-platform DNS record query
+
+use super::service_ref::{ManagedDNSServiceRef, QueryRecordParams};
+use super::{bonjour_util, constants};
+use crate::error::{Error, Operation};
+use crate::ffi::{c_str, FromRaw, SharedContext};
+use crate::prelude::*;
+use crate::{dns, EventLoop, NetworkInterface, Result, ServiceFlags};
+use crate::{Record, RecordReceivedCallback};
+use bonjour_sys::{DNSServiceErrorType, DNSServiceFlags, DNSServiceRef};
+use libc::{c_char, c_void};
+use std::any::Any;
+use std::ffi::CString;
+use std::fmt::{self, Formatter};
+use std::slice;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+pub struct BonjourMdnsRecordQuery {
+    service: Arc<Mutex<ManagedDNSServiceRef>>,
+    name: CString,
+    rrtype: u16,
+    rrclass: u16,
+    interface_index: u32,
+    flags: ServiceFlags,
+    context: SharedContext<BonjourRecordQueryContext>,
+}
+
+impl TRecordQuery for BonjourMdnsRecordQuery {
+    fn new(name: &str, rrtype: u16) -> Self {
+        Self {
+            service: Arc::default(),
+            name: c_string!(name),
+            rrtype,
+            rrclass: dns::CLASS_IN,
+            interface_index: constants::BONJOUR_IF_UNSPEC,
+            flags: ServiceFlags::empty(),
+            context: SharedContext::default(),
+        }
+    }
+
+    fn set_class(&mut self, rrclass: u16) {
+        self.rrclass = rrclass;
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface_index = bonjour_util::interface_index(interface);
+    }
+
+    fn set_flags(&mut self, flags: ServiceFlags) {
+        self.flags = flags;
+    }
+
+    fn set_record_received_callback(
+        &mut self,
+        record_received_callback: Box<RecordReceivedCallback>,
+    ) {
+        unsafe {
+            (*self.context.as_ptr()).record_received_callback = Some(record_received_callback)
+        };
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        unsafe { (*self.context.as_ptr()).user_context = Some(Arc::from(context)) };
+    }
+
+    fn query(&mut self) -> Result<EventLoop<'_>> {
+        debug!("Querying records: {:?}", self);
+
+        self.service
+            .lock()
+            .unwrap()
+            .retain_context(self.context.retain())
+            .query_record(
+                QueryRecordParams::builder()
+                    .flags(self.flags)
+                    .interface_index(self.interface_index)
+                    .fullname(self.name.as_ptr())
+                    .rrtype(self.rrtype)
+                    .rrclass(self.rrclass)
+                    .callback(Some(query_record_callback))
+                    .context(self.context.as_ptr() as *mut c_void)
+                    .build()?,
+            )?;
+
+        Ok(EventLoop::new(self.service.clone()))
+    }
+}
+
+#[derive(Default, FromRaw)]
+struct BonjourRecordQueryContext {
+    record_received_callback: Option<Box<RecordReceivedCallback>>,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl BonjourRecordQueryContext {
+    fn invoke_callback(&self, result: Result<Record>) {
+        if let Some(f) = &self.record_received_callback {
+            f(result, self.user_context.clone());
+        } else {
+            warn!("attempted to invoke callback but none was set");
+        }
+    }
+}
+
+impl fmt::Debug for BonjourRecordQueryContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BonjourRecordQueryContext").finish()
+    }
+}
+
+unsafe extern "C" fn query_record_callback(
+    _sd_ref: DNSServiceRef,
+    flags: DNSServiceFlags,
+    _interface_index: u32,
+    error: DNSServiceErrorType,
+    fullname: *const c_char,
+    rrtype: u16,
+    rrclass: u16,
+    rdlen: u16,
+    rdata: *const c_void,
+    ttl: u32,
+    context: *mut c_void,
+) {
+    let ctx = BonjourRecordQueryContext::from_raw(context);
+
+    if error != 0 {
+        ctx.invoke_callback(Err(bonjour_util::error(
+            error,
+            format!("query_record_callback() reported error (code: {})", error),
+        )
+        .with_operation(Operation::QueryRecord)));
+        return;
+    }
+
+    if flags & bonjour_sys::kDNSServiceFlagsAdd == 0 {
+        // the record is no longer valid
+        return;
+    }
+
+    let result = Record::builder()
+        .name(bonjour_util::normalize_domain(&c_str::copy_raw(fullname)))
+        .rrtype(rrtype)
+        .rrclass(rrclass)
+        .rdata(slice::from_raw_parts(rdata as *const u8, rdlen as usize).to_vec())
+        .ttl(ttl)
+        .dnssec_status(bonjour_util::dnssec_status(flags))
+        .build()
+        .map_err(Error::from);
+
+    ctx.invoke_callback(result);
+}
@@ -0,0 +1,44 @@
+//! Bonjour implementation for cross-platform registration handle
+
+use super::event_loop::BonjourEventLoop;
+use super::service::BonjourMdnsService;
+use crate::prelude::*;
+use crate::registration_handle::TRegistrationHandle;
+use crate::Result;
+use std::time::Duration;
+
+#[derive(new)]
+pub struct BonjourRegistrationHandle<'a> {
+    service: *mut BonjourMdnsService,
+    event_loop: BonjourEventLoop<'a>,
+}
+
+impl<'a> BonjourRegistrationHandle<'a> {
+    // `BonjourEventLoopGroup` is only available on unix
+    #[cfg_attr(target_os = "windows", allow(dead_code))]
+    pub(super) fn event_loop(&self) -> &BonjourEventLoop<'a> {
+        &self.event_loop
+    }
+}
+
+impl<'a> TEventLoop for BonjourRegistrationHandle<'a> {
+    fn poll(&self, timeout: Duration) -> Result<()> {
+        self.event_loop.poll(timeout)
+    }
+}
+
+impl<'a> TRegistrationHandle for BonjourRegistrationHandle<'a> {
+    fn set_name(&mut self, name: &str) -> Result<()> {
+        unsafe {
+            (*self.service).set_name(name);
+            (*self.service).reregister()
+        }
+    }
+
+    fn set_port(&mut self, port: u16) -> Result<()> {
+        unsafe {
+            (*self.service).set_port(port);
+            (*self.service).reregister()
+        }
+    }
+}
@@ -1,20 +1,26 @@
 //! Bonjour implementation for cross-platform service.
 
-use super::service_ref::{ManagedDNSServiceRef, RegisterServiceParams};
+use super::registration_handle::BonjourRegistrationHandle;
+use super::service_ref::{ManagedDNSServiceRef, RegisterServiceParams, UpdateRecordParams};
 use super::{bonjour_util, constants};
+use crate::error::{Error, ErrorKind, Operation};
 use crate::ffi::c_str::{self, AsCChars};
-use crate::ffi::{FromRaw, UnwrapOrNull};
+use crate::ffi::{FromRaw, SharedContext, UnwrapOrNull};
+use crate::network_watcher::NetworkWatcher;
 use crate::prelude::*;
 use crate::{
-    EventLoop, NetworkInterface, Result, ServiceRegisteredCallback, ServiceRegistration,
+    DaemonReconnectedCallback, ErrorCallback, EventLoop, NameConflictCallback, NetworkInterface,
+    RegistrationHandle, Result, ServiceFlags, ServiceRegisteredCallback, ServiceRegistration,
     ServiceType, TxtRecord,
 };
 use bonjour_sys::{DNSServiceErrorType, DNSServiceFlags, DNSServiceRef};
 use libc::{c_char, c_void};
 use std::any::Any;
 use std::ffi::CString;
+use std::ptr;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 #[derive(Debug)]
 pub struct BonjourMdnsService {
@@ -26,9 +32,19 @@ pub struct BonjourMdnsService {
     host: Option<CString>,
     interface_index: u32,
     txt_record: Option<TxtRecord>,
-    context: *mut BonjourServiceContext,
+    flags: ServiceFlags,
+    ttl: Option<u32>,
+    auto_reregister: bool,
+    network_watcher: Option<NetworkWatcher>,
+    last_reconnect_attempt: Option<Instant>,
+    context: SharedContext<BonjourServiceContext>,
 }
 
+// SAFETY: the service exclusively owns its context and the Bonjour objects, which are only used
+// through `&mut self` or the event loop borrowing it, and the callbacks and the context of
+// the user it holds are `Send`.
+unsafe impl Send for BonjourMdnsService {}
+
 impl TMdnsService for BonjourMdnsService {
     fn new(service_type: ServiceType, port: u16) -> Self {
         Self {
@@ -40,7 +56,12 @@ impl TMdnsService for BonjourMdnsService {
             host: None,
             interface_index: constants::BONJOUR_IF_UNSPEC,
             txt_record: None,
-            context: Box::into_raw(Box::default()),
+            flags: ServiceFlags::empty(),
+            ttl: None,
+            auto_reregister: false,
+            network_watcher: None,
+            last_reconnect_attempt: None,
+            context: SharedContext::default(),
         }
     }
 
@@ -67,16 +88,70 @@ impl TMdnsService for BonjourMdnsService {
     }
 
     fn set_registered_callback(&mut self, registered_callback: Box<ServiceRegisteredCallback>) {
-        unsafe { (*self.context).registered_callback = Some(registered_callback) };
+        unsafe { (*self.context.as_ptr()).registered_callback = Some(registered_callback) };
+    }
+
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>) {
+        unsafe { (*self.context.as_ptr()).error_callback = Some(error_callback) };
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        unsafe { (*self.context.as_ptr()).user_context = Some(Arc::from(context)) };
+    }
+
+    fn set_flags(&mut self, flags: ServiceFlags) {
+        self.flags = flags;
+    }
+
+    fn set_ttl(&mut self, ttl: u32) {
+        self.ttl = Some(ttl);
+    }
+
+    fn set_auto_reregister(&mut self, auto_reregister: bool) {
+        self.auto_reregister = auto_reregister;
     }
 
-    fn set_context(&mut self, context: Box<dyn Any>) {
-        unsafe { (*self.context).user_context = Some(Arc::from(context)) };
+    fn set_reconnected_callback(&mut self, reconnected_callback: Box<DaemonReconnectedCallback>) {
+        unsafe { (*self.context.as_ptr()).reconnected_callback = Some(reconnected_callback) };
     }
 
-    fn register(&mut self) -> Result<EventLoop> {
+    fn set_name_conflict_callback(&mut self, name_conflict_callback: Box<NameConflictCallback>) {
+        unsafe { (*self.context.as_ptr()).name_conflict_callback = Some(name_conflict_callback) };
+    }
+
+    fn register(&mut self) -> Result<RegistrationHandle<'_>> {
+        let _span = operation_span!("register", service_type = self.kind.to_string_lossy());
+
         debug!("Registering service: {:?}", self);
 
+        self.register_service()?;
+
+        if self.auto_reregister {
+            self.network_watcher = Some(NetworkWatcher::new());
+        }
+
+        let service: *mut Self = self;
+
+        let event_loop = EventLoop::new(self.service.clone())
+            .with_on_poll(Box::new(move || unsafe { (*service).handle_poll() }));
+
+        Ok(BonjourRegistrationHandle::new(service, event_loop))
+    }
+}
+
+impl BonjourMdnsService {
+    /// Returns the underlying `DNSServiceRef`, or a null pointer if the service has not been
+    /// registered yet, to call functions of `dns_sd.h` this crate does not wrap.
+    ///
+    /// # Safety
+    /// The reference is owned by this service, it must not be deallocated and its results must
+    /// not be processed outside of the event loop. The pointer is no longer valid once the service
+    /// is dropped, re-registered or has reconnected to the mDNS daemon.
+    pub unsafe fn as_raw(&self) -> DNSServiceRef {
+        self.service.lock().unwrap().inner()
+    }
+
+    fn register_service(&self) -> Result<()> {
         let txt_len = self
             .txt_record
             .as_ref()
@@ -89,44 +164,168 @@ impl TMdnsService for BonjourMdnsService {
             .map(|t| t.inner().get_bytes_ptr())
             .unwrap_or_null();
 
-        self.service.lock().unwrap().register_service(
-            RegisterServiceParams::builder()
-                .flags(constants::BONJOUR_RENAME_FLAGS)
-                .interface_index(self.interface_index)
-                .name(self.name.as_ref().as_c_chars().unwrap_or_null())
-                .regtype(self.kind.as_ptr())
-                .domain(self.domain.as_ref().as_c_chars().unwrap_or_null())
-                .host(self.host.as_ref().as_c_chars().unwrap_or_null())
-                .port(self.port)
-                .txt_len(txt_len)
-                .txt_record(txt_record)
-                .callback(Some(register_callback))
-                .context(self.context as *mut c_void)
+        let mut flags = self.flags;
+
+        // the name conflict callback takes care of renaming the service
+        if unsafe { (*self.context.as_ptr()).name_conflict_callback.is_some() } {
+            flags |= ServiceFlags::NO_AUTO_RENAME;
+        }
+
+        self.service
+            .lock()
+            .unwrap()
+            .retain_context(self.context.retain())
+            .register_service(
+                RegisterServiceParams::builder()
+                    .flags(flags)
+                    .interface_index(self.interface_index)
+                    .name(self.name.as_ref().as_c_chars().unwrap_or_null())
+                    .regtype(self.kind.as_ptr())
+                    .domain(self.domain.as_ref().as_c_chars().unwrap_or_null())
+                    .host(self.host.as_ref().as_c_chars().unwrap_or_null())
+                    .port(self.port)
+                    .txt_len(txt_len)
+                    .txt_record(txt_record)
+                    .callback(Some(register_callback))
+                    .context(self.context.as_ptr() as *mut c_void)
+                    .build()?,
+            )?;
+
+        if let Some(ttl) = self.ttl {
+            self.update_ttl(ttl)?;
+        }
+
+        Ok(())
+    }
+
+    /// Bonjour does not support setting the TTL when registering a service, but allows updating
+    /// the primary TXT record of the registration with a new TTL.
+    fn update_ttl(&self, ttl: u32) -> Result<()> {
+        // an empty TXT record consists of a single empty string
+        let empty_txt = [0u8];
+
+        let (rdlen, rdata) = match &self.txt_record {
+            Some(t) if !t.is_empty() => (t.inner().get_length(), t.inner().get_bytes_ptr()),
+            _ => (empty_txt.len() as u16, empty_txt.as_ptr() as *const c_void),
+        };
+
+        self.service.lock().unwrap().update_record(
+            UpdateRecordParams::builder()
+                .record_ref(ptr::null_mut())
+                .flags(0)
+                .rdlen(rdlen)
+                .rdata(rdata)
+                .ttl(ttl)
                 .build()?,
-        )?;
+        )
+    }
+
+    fn handle_poll(&mut self) {
+        let disconnected = self.service.lock().unwrap().is_null();
 
-        Ok(EventLoop::new(self.service.clone()))
+        if disconnected {
+            self.handle_reconnect();
+        } else {
+            self.handle_name_conflict();
+            self.handle_network_change();
+        }
     }
-}
 
-impl Drop for BonjourMdnsService {
-    fn drop(&mut self) {
-        unsafe { Box::from_raw(self.context) };
+    fn handle_name_conflict(&mut self) {
+        let candidate = match unsafe { (*self.context.as_ptr()).pending_name.take() } {
+            Some(c) => c,
+            None => return,
+        };
+
+        debug!("Re-registering service as: {}", candidate);
+
+        self.name = Some(c_string!(candidate));
+
+        if let Err(e) = self.reregister() {
+            unsafe { (*self.context.as_ptr()).invoke_callback(Err(e)) };
+        }
+    }
+
+    fn handle_reconnect(&mut self) {
+        if !bonjour_util::should_reconnect(&mut self.last_reconnect_attempt) {
+            return;
+        }
+
+        debug!("Attempting to reconnect to the mDNS daemon");
+
+        if let Err(e) = self.register_service() {
+            // the daemon is likely still down, try again later
+            debug!("Could not reconnect to the mDNS daemon: {}", e);
+            *self.service.lock().unwrap() = ManagedDNSServiceRef::default();
+            return;
+        }
+
+        self.last_reconnect_attempt = None;
+
+        unsafe { (*self.context.as_ptr()).invoke_reconnected_callback() };
+    }
+
+    fn handle_network_change(&mut self) {
+        let changed = match &mut self.network_watcher {
+            Some(w) => w.poll_changed(),
+            None => false,
+        };
+
+        if !changed {
+            return;
+        }
+
+        debug!("Network configuration changed, re-registering service");
+
+        if let Err(e) = self.reregister() {
+            unsafe { (*self.context.as_ptr()).invoke_callback(Err(e)) };
+        }
+    }
+
+    pub(crate) fn set_port(&mut self, port: u16) {
+        self.port = port;
+    }
+
+    /// Re-registers the service with its current configuration. If the daemon is not running,
+    /// the service is registered once it has reconnected.
+    pub(crate) fn reregister(&mut self) -> Result<()> {
+        {
+            let mut service = self.service.lock().unwrap();
+
+            if service.is_null() {
+                return Ok(());
+            }
+
+            // deallocating the current reference removes the existing registration
+            *service = ManagedDNSServiceRef::default();
+        }
+
+        self.register_service()
     }
 }
 
 #[derive(Default, FromRaw)]
 struct BonjourServiceContext {
     registered_callback: Option<Box<ServiceRegisteredCallback>>,
-    user_context: Option<Arc<dyn Any>>,
+    error_callback: Option<Box<ErrorCallback>>,
+    reconnected_callback: Option<Box<DaemonReconnectedCallback>>,
+    name_conflict_callback: Option<Box<NameConflictCallback>>,
+    pending_name: Option<String>,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
 }
 
 impl BonjourServiceContext {
     fn invoke_callback(&self, result: Result<ServiceRegistration>) {
-        if let Some(f) = &self.registered_callback {
-            f(result, self.user_context.clone());
-        } else {
-            warn!("attempted to invoke callback but none was set");
+        match (result, &self.error_callback, &self.registered_callback) {
+            (Err(e), Some(f), _) => f(e, self.user_context.clone()),
+            (result, _, Some(f)) => f(result, self.user_context.clone()),
+            _ => warn!("attempted to invoke callback but none was set"),
+        }
+    }
+
+    fn invoke_reconnected_callback(&self) {
+        if let Some(f) = &self.reconnected_callback {
+            f(self.user_context.clone());
         }
     }
 }
@@ -147,14 +346,22 @@ unsafe extern "C" fn register_callback(
 }
 
 unsafe fn handle_register(
-    context: &BonjourServiceContext,
+    context: &mut BonjourServiceContext,
     error: DNSServiceErrorType,
     domain: *const c_char,
     name: *const c_char,
     regtype: *const c_char,
 ) -> Result<()> {
+    if error == bonjour_sys::kDNSServiceErr_NameConflict {
+        return handle_name_conflict(context, name);
+    }
+
     if error != 0 {
-        return Err(format!("register_callback() reported error (code: {0})", error).into());
+        return Err(bonjour_util::error(
+            error,
+            format!("register_callback() reported error (code: {0})", error),
+        )
+        .with_operation(Operation::Register));
     }
 
     let domain = bonjour_util::normalize_domain(c_str::raw_to_str(domain));
@@ -164,10 +371,37 @@ unsafe fn handle_register(
         .name(c_str::copy_raw(name))
         .service_type(ServiceType::from_str(&kind)?)
         .domain(domain)
-        .build()
-        .expect("could not build ServiceRegistration");
+        .build()?;
 
     context.invoke_callback(Ok(result));
 
     Ok(())
 }
+
+unsafe fn handle_name_conflict(
+    context: &mut BonjourServiceContext,
+    name: *const c_char,
+) -> Result<()> {
+    let name = c_str::copy_raw(name);
+
+    debug!("Name conflict: {}", name);
+
+    let candidate = context
+        .name_conflict_callback
+        .as_ref()
+        .and_then(|f| f(&name, context.user_context.clone()));
+
+    match candidate {
+        Some(c) => {
+            // the service is re-registered from the event loop
+            context.pending_name = Some(c);
+            Ok(())
+        }
+        None => Err(Error::with_kind(
+            ErrorKind::NameConflict,
+            format!("service name `{}` conflicts with another service", name),
+        )
+        .with_code(bonjour_sys::kDNSServiceErr_NameConflict)
+        .with_operation(Operation::Register)),
+    }
+}
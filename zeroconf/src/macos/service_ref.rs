@@ -1,31 +1,81 @@
 //! Low level interface for interacting with `DNSserviceRef`
 
-use crate::Result;
+use super::bonjour_util;
+use crate::error::{Error, ErrorKind, Operation};
+use crate::ffi::{self, ContextRef};
+use crate::{Result, ServiceFlags};
 use bonjour_sys::{
-    DNSServiceBrowse, DNSServiceBrowseReply, DNSServiceFlags, DNSServiceGetAddrInfo,
-    DNSServiceGetAddrInfoReply, DNSServiceProcessResult, DNSServiceProtocol, DNSServiceRef,
-    DNSServiceRefDeallocate, DNSServiceRefSockFD, DNSServiceRegister, DNSServiceRegisterReply,
-    DNSServiceResolve, DNSServiceResolveReply,
+    dnssd_sock_t, kDNSServiceErr_ServiceNotRunning, kDNSServiceFlagsShareConnection, DNSRecordRef,
+    DNSServiceBrowse, DNSServiceBrowseReply, DNSServiceCreateConnection, DNSServiceFlags,
+    DNSServiceGetAddrInfo, DNSServiceGetAddrInfoReply, DNSServiceNATPortMappingCreate,
+    DNSServiceNATPortMappingReply, DNSServiceProcessResult, DNSServiceProtocol,
+    DNSServiceQueryRecord, DNSServiceQueryRecordReply, DNSServiceRef, DNSServiceRefDeallocate,
+    DNSServiceRefSockFD, DNSServiceRegister, DNSServiceRegisterReply, DNSServiceResolve,
+    DNSServiceResolveReply, DNSServiceUpdateRecord,
 };
 use libc::{c_char, c_void};
 use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Weak};
+use std::time::Duration;
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
 
 /// Wraps the `DNSServiceRef` type from the raw Bonjour bindings.
 ///
 /// This struct allocates a new `DNSServiceRef` when any of the delgate functions is invoked and
-/// calls the Bonjour function responsible for freeing the client on `trait Drop`.
+/// calls the Bonjour function responsible for freeing the client on `trait Drop`. The context
+/// retained with [`retain_context()`] is released only once the `DNSServiceRef` has been freed,
+/// after which its callbacks are no longer invoked.
+///
+/// A reference may also be one of the operations sharing the connection of another reference,
+/// see [`SharedConnection`].
 ///
 /// # Note
 /// This wrapper is meant for one-off calls to underlying Bonjour functions. The behaviour for
 /// using an already initialized `DNSServiceRef` in one of these functions is undefined. Therefore,
 /// it is preferable to only call one delegate function per-instance.
+///
+/// [`retain_context()`]: #method.retain_context
+/// [`SharedConnection`]: struct.SharedConnection.html
 #[derive(Debug)]
-pub struct ManagedDNSServiceRef(DNSServiceRef);
+pub struct ManagedDNSServiceRef(
+    DNSServiceRef,
+    /// Unique to this instance, see `id()`
+    u64,
+    /// The context passed to the delegate function, see `retain_context()`
+    Option<ContextRef>,
+    /// The connection this reference owns, or shares with the reference created with
+    /// `create_connection()`
+    Connection,
+);
+
+#[derive(Debug)]
+enum Connection {
+    /// Released right before the reference is deallocated
+    Owned(Option<Arc<()>>),
+    /// Dead once the reference owning the connection has been deallocated, which deallocates
+    /// the references sharing it as well
+    Shared(Weak<()>),
+}
 
 impl ManagedDNSServiceRef {
     /// Constructs a new `ManagedDNSServiceRef`.
     pub fn new() -> Self {
-        Self(ptr::null_mut())
+        Self(
+            ptr::null_mut(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            None,
+            Connection::Owned(Some(Arc::new(()))),
+        )
+    }
+
+    /// Keeps the specified `context` alive until this reference is deallocated. The context is to
+    /// be retained before it is passed to a delegate function, so that the callbacks of the
+    /// reference never outlive it.
+    pub(crate) fn retain_context(&mut self, context: ContextRef) -> &mut Self {
+        self.2 = Some(context);
+        self
     }
 
     /// Delegate function for [`DNSServiceRegister`].
@@ -50,7 +100,7 @@ impl ManagedDNSServiceRef {
         bonjour!(
             DNSServiceRegister(
                 &mut self.0 as *mut DNSServiceRef,
-                flags,
+                self.flags(flags, Operation::Register),
                 interface_index,
                 name,
                 regtype,
@@ -62,7 +112,8 @@ impl ManagedDNSServiceRef {
                 callback,
                 context,
             ),
-            "could not register service"
+            "could not register service",
+            Operation::Register
         )
     }
 
@@ -83,14 +134,15 @@ impl ManagedDNSServiceRef {
         bonjour!(
             DNSServiceBrowse(
                 &mut self.0 as *mut DNSServiceRef,
-                flags,
+                self.flags(flags, Operation::Browse),
                 interface_index,
                 regtype,
                 domain,
                 callback,
                 context,
             ),
-            "could not browse services"
+            "could not browse services",
+            Operation::Browse
         )
     }
 
@@ -112,7 +164,7 @@ impl ManagedDNSServiceRef {
         bonjour!(
             DNSServiceResolve(
                 &mut self.0 as *mut DNSServiceRef,
-                flags,
+                self.flags(flags, Operation::Resolve),
                 interface_index,
                 name,
                 regtype,
@@ -120,10 +172,9 @@ impl ManagedDNSServiceRef {
                 callback,
                 context,
             ),
-            "DNSServiceResolve() reported error"
-        )?;
-
-        self.process_result()
+            "DNSServiceResolve() reported error",
+            Operation::Resolve
+        )
     }
 
     /// Delegate function for [`DNSServiceGetAddrInfo`].
@@ -143,17 +194,136 @@ impl ManagedDNSServiceRef {
         bonjour!(
             DNSServiceGetAddrInfo(
                 &mut self.0 as *mut DNSServiceRef,
-                flags,
+                self.flags(flags, Operation::GetAddrInfo),
                 interface_index,
                 protocol,
                 hostname,
                 callback,
                 context,
             ),
-            "DNSServiceGetAddrInfo() reported error"
-        )?;
+            "DNSServiceGetAddrInfo() reported error",
+            Operation::GetAddrInfo
+        )
+    }
 
-        self.process_result()
+    /// Delegate function for [`DNSServiceQueryRecord`].
+    ///
+    /// [`DNSServiceQueryRecord`]: https://developer.apple.com/documentation/dnssd/1804747-dnsservicequeryrecord?language=objc
+    pub fn query_record(
+        &mut self,
+        QueryRecordParams {
+            flags,
+            interface_index,
+            fullname,
+            rrtype,
+            rrclass,
+            callback,
+            context,
+        }: QueryRecordParams,
+    ) -> Result<()> {
+        bonjour!(
+            DNSServiceQueryRecord(
+                &mut self.0 as *mut DNSServiceRef,
+                self.flags(flags, Operation::QueryRecord),
+                interface_index,
+                fullname,
+                rrtype,
+                rrclass,
+                callback,
+                context,
+            ),
+            "DNSServiceQueryRecord() reported error",
+            Operation::QueryRecord
+        )
+    }
+
+    /// Delegate function for `DNSServiceNATPortMappingCreate`.
+    pub fn nat_port_mapping_create(
+        &mut self,
+        NATPortMappingCreateParams {
+            flags,
+            interface_index,
+            protocol,
+            internal_port,
+            external_port,
+            ttl,
+            callback,
+            context,
+        }: NATPortMappingCreateParams,
+    ) -> Result<()> {
+        bonjour!(
+            DNSServiceNATPortMappingCreate(
+                &mut self.0 as *mut DNSServiceRef,
+                self.flags(flags, Operation::MapPort),
+                interface_index,
+                protocol,
+                internal_port.to_be(),
+                external_port.to_be(),
+                ttl,
+                callback,
+                context,
+            ),
+            "could not create NAT port mapping",
+            Operation::MapPort
+        )
+    }
+
+    /// Delegate function for [`DNSServiceUpdateRecord`].
+    ///
+    /// [`DNSServiceUpdateRecord`]: https://developer.apple.com/documentation/dnssd/1804739-dnsserviceupdaterecord?language=objc
+    pub fn update_record(
+        &mut self,
+        UpdateRecordParams {
+            record_ref,
+            flags,
+            rdlen,
+            rdata,
+            ttl,
+        }: UpdateRecordParams,
+    ) -> Result<()> {
+        bonjour!(
+            DNSServiceUpdateRecord(self.0, record_ref, flags, rdlen, rdata, ttl),
+            "could not update record"
+        )
+    }
+
+    /// Delegate function for [`DNSServiceCreateConnection`].
+    ///
+    /// [`DNSServiceCreateConnection`]: https://developer.apple.com/documentation/dnssd/1804724-dnsservicecreateconnection?language=objc
+    pub fn create_connection(&mut self) -> Result<()> {
+        bonjour!(
+            DNSServiceCreateConnection(&mut self.0 as *mut DNSServiceRef),
+            "could not connect to the mDNS daemon",
+            Operation::Connect
+        )
+    }
+
+    /// Returns a handle to start operations sharing the connection of this reference, which must
+    /// have been initialized with [`create_connection()`].
+    ///
+    /// [`create_connection()`]: #method.create_connection
+    pub fn shared_connection(&self) -> SharedConnection {
+        let alive = match &self.3 {
+            Connection::Owned(Some(alive)) => Arc::downgrade(alive),
+            Connection::Owned(None) => Weak::new(),
+            Connection::Shared(alive) => alive.clone(),
+        };
+
+        SharedConnection {
+            inner: self.0,
+            alive,
+        }
+    }
+
+    /// Returns the flags to pass to the delegate function of `operation`, which include
+    /// `kDNSServiceFlagsShareConnection` if this reference shares a connection.
+    fn flags(&self, flags: ServiceFlags, operation: Operation) -> DNSServiceFlags {
+        let flags = bonjour_util::service_flags(flags, operation);
+
+        match self.3 {
+            Connection::Owned(_) => flags,
+            Connection::Shared(_) => flags | kDNSServiceFlagsShareConnection,
+        }
     }
 
     /// Delegate function for [`DNSServiceProcessResult`].
@@ -166,10 +336,60 @@ impl ManagedDNSServiceRef {
         )
     }
 
+    /// Same as [`process_result()`] but waits at most `timeout` for a result to be available,
+    /// returning an error otherwise.
+    ///
+    /// This is used when a result is needed before returning to the event loop, which must not
+    /// block indefinitely (e.g. on the main thread of an iOS app while the local network
+    /// permission is being requested).
+    ///
+    /// [`process_result()`]: #method.process_result
+    pub fn process_result_timeout(&self, timeout: Duration) -> Result<()> {
+        let select = unsafe { ffi::bonjour::read_select(self.sock_fd(), timeout)? };
+
+        if select == 0 {
+            return Err(Error::with_kind(
+                ErrorKind::Timeout,
+                "timed out waiting for the mDNS daemon".to_string(),
+            ));
+        }
+
+        self.process_result()
+    }
+
+    /// Same as [`process_result()`] but returns `Ok(false)` rather than an error if the mDNS daemon
+    /// is no longer running (e.g. it was restarted), in which case this reference is no longer
+    /// usable and must be replaced.
+    ///
+    /// [`process_result()`]: #method.process_result
+    pub fn process_result_alive(&self) -> Result<bool> {
+        let err = unsafe { DNSServiceProcessResult(self.0) };
+        if err == kDNSServiceErr_ServiceNotRunning {
+            Ok(false)
+        } else {
+            bonjour!(err, "could not process service result").map(|_| true)
+        }
+    }
+
+    pub(super) fn inner(&self) -> DNSServiceRef {
+        self.0
+    }
+
+    /// Returns an identifier that is unique to this instance. Unlike the socket of the reference,
+    /// it is never reused once the reference is replaced (e.g. after reconnecting to the daemon).
+    pub fn id(&self) -> u64 {
+        self.1
+    }
+
+    /// Returns true if this reference has not been initialized by any of the delegate functions.
+    pub fn is_null(&self) -> bool {
+        self.0.is_null()
+    }
+
     /// Delegate function for [`DNSServiceRefSockFD`].
     ///
     /// [`DNSServiceRefSockFD`]: https://developer.apple.com/documentation/dnssd/1804698-dnsservicerefsockfd?language=objc
-    pub fn sock_fd(&self) -> i32 {
+    pub fn sock_fd(&self) -> dnssd_sock_t {
         unsafe { DNSServiceRefSockFD(self.0) }
     }
 }
@@ -182,34 +402,130 @@ impl Default for ManagedDNSServiceRef {
 
 impl Drop for ManagedDNSServiceRef {
     fn drop(&mut self) {
+        let deallocate = match &mut self.3 {
+            // the references sharing the connection are deallocated along with it, they must no
+            // longer be deallocated on their own (e.g. by a context dropped after this one)
+            Connection::Owned(alive) => {
+                alive.take();
+                true
+            }
+            Connection::Shared(alive) => alive.strong_count() > 0,
+        };
+
         unsafe {
-            if !self.0.is_null() {
+            if deallocate && !self.0.is_null() {
                 DNSServiceRefDeallocate(self.0);
             }
         }
     }
 }
 
+/// A handle to the connection of a `ManagedDNSServiceRef` created with `create_connection()`,
+/// returned by `ManagedDNSServiceRef::shared_connection()`.
+///
+/// The operations started with [`start()`] are references sharing the connection, so that their
+/// results are all processed by calling `process_result()` on the reference owning it, from a
+/// single event loop, rather than each operation being processed on its own.
+///
+/// [`start()`]: #method.start
+#[derive(Debug, Clone)]
+pub struct SharedConnection {
+    inner: DNSServiceRef,
+    alive: Weak<()>,
+}
+
+impl SharedConnection {
+    /// Starts an operation sharing this connection with `start`, which invokes one of the
+    /// delegate functions of the returned reference. Fails if the connection has been closed.
+    ///
+    /// Dropping the reference stops the operation, which may be done from its own callback.
+    pub fn start(
+        &self,
+        start: impl FnOnce(&mut ManagedDNSServiceRef) -> Result<()>,
+    ) -> Result<ManagedDNSServiceRef> {
+        if self.alive.strong_count() == 0 || self.inner.is_null() {
+            return Err("the connection to the mDNS daemon has been closed".into());
+        }
+
+        // initialized with the connection, which the delegate function replaces with the
+        // reference of the operation
+        let mut service = ManagedDNSServiceRef(
+            self.inner,
+            NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            None,
+            Connection::Shared(self.alive.clone()),
+        );
+
+        if let Err(e) = start(&mut service) {
+            // the connection must not be deallocated along with the reference
+            service.0 = ptr::null_mut();
+            return Err(e);
+        }
+
+        Ok(service)
+    }
+
+    /// Returns true if results are waiting to be processed on this connection. The
+    /// `kDNSServiceFlagsMoreComing` flag of a callback may refer to the results of another
+    /// operation sharing the connection, which are only known to be processed once there are
+    /// none left.
+    pub fn has_results(&self) -> bool {
+        if self.alive.strong_count() == 0 || self.inner.is_null() {
+            return false;
+        }
+
+        let sock_fd = unsafe { DNSServiceRefSockFD(self.inner) };
+        let select = unsafe { ffi::bonjour::read_select(sock_fd, Duration::from_secs(0)) };
+
+        matches!(select, Ok(n) if n > 0)
+    }
+}
+
 /// Holds parameters for `ManagedDNSServiceRef::register_service()`.
 #[derive(Builder, BuilderDelegate)]
+#[builder(build_fn(validate = "Self::validate"))]
 pub struct RegisterServiceParams {
-    flags: DNSServiceFlags,
+    #[builder(default)]
+    flags: ServiceFlags,
+    /// All interfaces if `0`
+    #[builder(default)]
     interface_index: u32,
+    /// The name of the computer if null
+    #[builder(default = "ptr::null()")]
     name: *const c_char,
     regtype: *const c_char,
+    /// The default domains if null
+    #[builder(default = "ptr::null()")]
     domain: *const c_char,
+    /// The host name of the computer if null
+    #[builder(default = "ptr::null()")]
     host: *const c_char,
     port: u16,
+    #[builder(default)]
     txt_len: u16,
+    /// An empty TXT record if null
+    #[builder(default = "ptr::null()")]
     txt_record: *const c_void,
     callback: DNSServiceRegisterReply,
     context: *mut c_void,
 }
 
+impl RegisterServiceParamsBuilder {
+    fn validate(&self) -> std::result::Result<(), String> {
+        match (self.txt_len, self.txt_record) {
+            (Some(len), None) if len > 0 => Err("`txt_len` is set without a `txt_record`".into()),
+            (Some(len), Some(txt)) if len > 0 && txt.is_null() => {
+                Err("`txt_len` is set but `txt_record` is null".into())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
 /// Holds parameters for `ManagedDNSServiceRef::browse_services()`.
 #[derive(Builder, BuilderDelegate)]
 pub struct BrowseServicesParams {
-    flags: DNSServiceFlags,
+    flags: ServiceFlags,
     interface_index: u32,
     regtype: *const c_char,
     domain: *const c_char,
@@ -220,7 +536,7 @@ pub struct BrowseServicesParams {
 /// Holds parameters for `ManagedDNSServiceRef::resolve_service()`.
 #[derive(Builder, BuilderDelegate)]
 pub struct ServiceResolveParams {
-    flags: DNSServiceFlags,
+    flags: ServiceFlags,
     interface_index: u32,
     name: *const c_char,
     regtype: *const c_char,
@@ -232,10 +548,45 @@ pub struct ServiceResolveParams {
 /// Holds parameters for `ManagedDNSServiceRef::get_address_info()`.
 #[derive(Builder, BuilderDelegate)]
 pub struct GetAddressInfoParams {
-    flags: DNSServiceFlags,
+    flags: ServiceFlags,
     interface_index: u32,
     protocol: DNSServiceProtocol,
     hostname: *const c_char,
     callback: DNSServiceGetAddrInfoReply,
     context: *mut c_void,
 }
+
+/// Holds parameters for `ManagedDNSServiceRef::query_record()`.
+#[derive(Builder, BuilderDelegate)]
+pub struct QueryRecordParams {
+    flags: ServiceFlags,
+    interface_index: u32,
+    fullname: *const c_char,
+    rrtype: u16,
+    rrclass: u16,
+    callback: DNSServiceQueryRecordReply,
+    context: *mut c_void,
+}
+
+/// Holds parameters for `ManagedDNSServiceRef::nat_port_mapping_create()`.
+#[derive(Builder, BuilderDelegate)]
+pub struct NATPortMappingCreateParams {
+    flags: ServiceFlags,
+    interface_index: u32,
+    protocol: DNSServiceProtocol,
+    internal_port: u16,
+    external_port: u16,
+    ttl: u32,
+    callback: DNSServiceNATPortMappingReply,
+    context: *mut c_void,
+}
+
+/// Holds parameters for `ManagedDNSServiceRef::update_record()`.
+#[derive(Builder, BuilderDelegate)]
+pub struct UpdateRecordParams {
+    record_ref: DNSRecordRef,
+    flags: DNSServiceFlags,
+    rdlen: u16,
+    rdata: *const c_void,
+    ttl: u32,
+}
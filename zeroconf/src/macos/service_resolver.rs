@@ -0,0 +1,115 @@
+//! Bonjour implementation for cross-platform service resolver
+
+use super::browser::BonjourBrowserContext;
+use super::service_ref::ManagedDNSServiceRef;
+use super::{bonjour_util, constants};
+use crate::ffi::SharedContext;
+use crate::prelude::*;
+use crate::{
+    AddressFamily, AddressPolicy, EventLoop, NetworkInterface, Result, ServiceDiscoveredCallback,
+    ServiceFlags, ServiceType,
+};
+use std::any::Any;
+use std::sync::{Arc, Mutex};
+
+#[derive(Debug)]
+pub struct BonjourMdnsResolver {
+    service: Arc<Mutex<ManagedDNSServiceRef>>,
+    interface_index: u32,
+    context: SharedContext<BonjourBrowserContext>,
+}
+
+impl TMdnsResolver for BonjourMdnsResolver {
+    fn new() -> Self {
+        Self {
+            service: Arc::default(),
+            interface_index: constants::BONJOUR_IF_UNSPEC,
+            context: SharedContext::default(),
+        }
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface_index = bonjour_util::interface_index(interface);
+    }
+
+    fn set_address_family(&mut self, address_family: AddressFamily) {
+        unsafe { (*self.context.as_ptr()).address_family = address_family };
+    }
+
+    fn set_address_policy(&mut self, address_policy: AddressPolicy) {
+        unsafe { (*self.context.as_ptr()).address_policy = address_policy };
+    }
+
+    fn set_include_raw_records(&mut self, include_raw_records: bool) {
+        unsafe { (*self.context.as_ptr()).include_raw_records = include_raw_records };
+    }
+
+    fn set_service_resolved_callback(
+        &mut self,
+        service_resolved_callback: Box<ServiceDiscoveredCallback>,
+    ) {
+        unsafe {
+            (*self.context.as_ptr())
+                .callbacks
+                .set_service_discovered_callback(service_resolved_callback)
+        };
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        unsafe { (*self.context.as_ptr()).callbacks.set_context(context) };
+    }
+
+    fn resolve(
+        &mut self,
+        name: &str,
+        service_type: ServiceType,
+        domain: &str,
+    ) -> Result<EventLoop<'_>> {
+        debug!(
+            "Resolving service: {} ({:?}) in {}",
+            name, service_type, domain
+        );
+
+        let kind = service_type.to_string();
+
+        let c_name = c_string!(name);
+        let c_kind = c_string!(kind.as_str());
+        let c_domain = c_string!(domain);
+
+        let mut service = self.service.lock().unwrap();
+
+        // the steps of resolving the service share the connection processed by the event loop
+        service
+            .retain_context(self.context.retain())
+            .create_connection()?;
+
+        unsafe {
+            let context = &mut *self.context.as_ptr();
+            context.connect(service.shared_connection());
+            context.start_resolution(
+                false,
+                self.interface_index,
+                c_name.as_ptr(),
+                c_kind.as_ptr(),
+                c_domain.as_ptr(),
+            )?;
+        }
+
+        drop(service);
+
+        let context = self.context.as_ptr();
+
+        Ok(EventLoop::new(self.service.clone())
+            .with_on_poll(Box::new(move || unsafe { (*context).sweep() })))
+    }
+}
+
+impl BonjourMdnsResolver {
+    /// Sets the [`ServiceFlags`] to resolve services with, of which `WAKE_ON_RESOLVE` and
+    /// `RETURN_INTERMEDIATES` apply.
+    ///
+    /// [`ServiceFlags`]: ../../flags/struct.ServiceFlags.html
+    pub fn set_flags(&mut self, flags: ServiceFlags) {
+        unsafe { (*self.context.as_ptr()).set_resolve_flags(flags) };
+    }
+}
@@ -1,5 +1,6 @@
 //! Low level interface for interacting with `TXTRecordRef`.
 
+use crate::txt_record::TxtRecordView;
 use crate::Result;
 use bonjour_sys::{
     TXTRecordContainsKey, TXTRecordCreate, TXTRecordDeallocate, TXTRecordGetBytesPtr,
@@ -7,8 +8,7 @@ use bonjour_sys::{
     TXTRecordRef, TXTRecordRemoveValue, TXTRecordSetValue,
 };
 use libc::{c_char, c_uchar, c_void};
-use std::ffi::CString;
-use std::{fmt, mem, ptr};
+use std::{fmt, mem, ptr, slice};
 
 /// Wraps the `ManagedTXTRecordRef` type from the raw Bonjour bindings.
 ///
@@ -129,29 +129,30 @@ impl ManagedTXTRecordRef {
         TXTRecordGetValuePtr(self.get_length(), self.get_bytes_ptr(), key, value_len)
     }
 
+    /// Copies the TXT record of the specified RDATA, which is not retained.
     pub(crate) unsafe fn clone_raw(raw: *const c_uchar, size: u16) -> Result<Self> {
-        let chars = c_string!(alloc(size as usize)).into_raw() as *mut c_uchar;
-        ptr::copy(raw, chars, size as usize);
-        let chars = CString::from_raw(chars as *mut c_char);
-
         let mut record = Self::new();
 
-        for i in 0.._get_count(size, chars.as_ptr() as *const c_void) {
-            let key = c_string!(alloc(256));
-            let mut value_len: u8 = 0;
-            let mut value: *const c_void = ptr::null_mut();
-
-            _get_item_at_index(
-                size,
-                chars.as_ptr() as *const c_void,
-                i,
-                256,
-                key.as_ptr() as *mut c_char,
-                &mut value_len,
-                &mut value,
-            )?;
-
-            record.set_value(key.as_ptr() as *mut c_char, value_len, value)?;
+        if raw.is_null() || size == 0 {
+            return Ok(record);
+        }
+
+        let view = TxtRecordView::new(slice::from_raw_parts(raw, size as usize));
+
+        // reused for the NUL terminated key of each entry
+        let mut key = Vec::with_capacity(256);
+
+        for (k, value) in view.raw_entries() {
+            key.clear();
+            key.extend_from_slice(k);
+            key.push(0);
+
+            let (value_len, value) = match value {
+                Some(v) => (v.len() as u8, v.as_ptr() as *const c_void),
+                None => (0, ptr::null()),
+            };
+
+            record.set_value(key.as_ptr() as *const c_char, value_len, value)?;
         }
 
         Ok(record)
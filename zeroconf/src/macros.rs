@@ -13,36 +13,61 @@ macro_rules! c_string {
     };
 }
 
-#[cfg(target_vendor = "apple")]
+/// Enters a `tracing` span named after the specified operation (e.g. `"resolve"`), with the
+/// specified fields formatted with `Display`, until the returned guard is dropped.
+///
+/// The fields are not evaluated unless the `tracing` feature is enabled.
+#[cfg(feature = "tracing")]
+macro_rules! operation_span {
+    ($operation:literal $(, $field:ident = $value:expr)* $(,)?) => {
+        tracing::debug_span!($operation $(, $field = %$value)*).entered()
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! operation_span {
+    ($operation:literal $(, $field:ident = $value:expr)* $(,)?) => {{
+        $(let _ = || { let _ = $value; };)*
+        crate::trace::NoSpan
+    }};
+}
+
+#[cfg(any(backend_bonjour, backend_bonjour_sdk))]
 macro_rules! bonjour {
     ($call:expr, $msg:expr) => {{
         #[allow(unused_unsafe)]
         let err = unsafe { $call };
         if err != 0 {
-            crate::Result::Err(format!("{}", format!("{} (code: {})", $msg, err)).into())
+            crate::Result::Err(super::bonjour_util::error(
+                err,
+                format!("{}", format!("{} (code: {})", $msg, err)),
+            ))
         } else {
             crate::Result::Ok(())
         }
     }};
+    ($call:expr, $msg:expr, $operation:expr) => {
+        bonjour!($call, $msg).map_err(|e| e.with_operation($operation))
+    };
 }
 
-#[cfg(target_os = "linux")]
+#[cfg(any(backend_avahi, backend_avahi_core))]
 macro_rules! avahi {
     ($call:expr, $msg:expr) => {{
         #[allow(unused_unsafe)]
         let err = unsafe { $call };
         if err < 0 {
-            crate::Result::Err(
-                format!(
-                    "{}",
-                    format!("{}: `{}`", $msg, crate::linux::avahi_util::get_error(err))
-                )
-                .into(),
-            )
+            crate::Result::Err(super::avahi_util::error(
+                err,
+                format!("{}: `{}`", $msg, super::avahi_util::get_error(err)),
+            ))
         } else {
             crate::Result::Ok(())
         }
     }};
+    ($call:expr, $msg:expr, $operation:expr) => {
+        avahi!($call, $msg).map_err(|e| e.with_operation($operation))
+    };
 }
 
 #[cfg(test)]
@@ -0,0 +1,484 @@
+//! Mock implementation for cross-platform browser
+
+use super::registry;
+use crate::debounce::Debouncer;
+use crate::prelude::*;
+use crate::{
+    AddressFamily, AddressPolicy, BrowserControl, BrowserEvent, DaemonReconnectedCallback,
+    ErrorCallback, EventLoop, NetworkInterface, Result, ServiceBatchCallback,
+    ServiceDiscoveredCallback, ServiceDiscovery, ServiceFlags, ServiceType,
+};
+use std::any::Any;
+use std::cell::RefCell;
+use std::fmt::{self, Formatter};
+use std::mem;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub struct MockMdnsBrowser {
+    service_type: ServiceType,
+    domain: Option<String>,
+    /// The services that have been reported and are still registered or injected
+    discovered: Vec<ServiceDiscovery>,
+    /// The reported services that have been unregistered or cleared
+    debouncer: Debouncer<ServiceDiscovery>,
+    service_discovered_callback: Option<Box<ServiceDiscoveredCallback>>,
+    service_batch_callback: Option<Box<ServiceBatchCallback>>,
+    /// The events of the current poll, delivered to the batch callback at its end
+    batch: RefCell<Vec<BrowserEvent>>,
+    error_callback: Option<Box<ErrorCallback>>,
+    control: BrowserControl,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl TMdnsBrowser for MockMdnsBrowser {
+    fn new(service_type: ServiceType) -> Self {
+        Self {
+            service_type,
+            domain: None,
+            discovered: vec![],
+            debouncer: Debouncer::default(),
+            service_discovered_callback: None,
+            service_batch_callback: None,
+            batch: RefCell::default(),
+            error_callback: None,
+            control: BrowserControl::default(),
+            user_context: None,
+        }
+    }
+
+    /// All services are reachable on every interface in the mock implementation, it is ignored.
+    fn set_network_interface(&mut self, _interface: NetworkInterface) {}
+
+    fn set_domain(&mut self, domain: &str) {
+        self.domain = Some(domain.to_string());
+    }
+
+    /// The addresses of the discovered services are reported as registered or injected, it is
+    /// ignored.
+    fn set_address_family(&mut self, _address_family: AddressFamily) {}
+
+    /// The addresses of the discovered services are reported as registered or injected, it is
+    /// ignored.
+    fn set_address_policy(&mut self, _address_policy: AddressPolicy) {}
+
+    /// The mock implementation does not support any flags, they are ignored.
+    fn set_flags(&mut self, _flags: ServiceFlags) {}
+
+    /// The raw records of injected services are reported as injected, it is ignored.
+    fn set_include_raw_records(&mut self, _include_raw_records: bool) {}
+
+    fn set_resolve_concurrency(&mut self, _concurrency: usize) {}
+
+    fn set_debounce(&mut self, window: Duration) {
+        self.debouncer.set_window(window);
+    }
+
+    fn set_service_discovered_callback(
+        &mut self,
+        service_discovered_callback: Box<ServiceDiscoveredCallback>,
+    ) {
+        self.service_discovered_callback = Some(service_discovered_callback);
+        self.service_batch_callback = None;
+    }
+
+    /// The services registered or injected since the last poll are reported in one batch.
+    fn set_service_batch_callback(&mut self, service_batch_callback: Box<ServiceBatchCallback>) {
+        self.service_batch_callback = Some(service_batch_callback);
+        self.service_discovered_callback = None;
+    }
+
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>) {
+        self.error_callback = Some(error_callback);
+    }
+
+    fn control(&self) -> BrowserControl {
+        self.control.clone()
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        self.user_context = Some(Arc::from(context));
+    }
+
+    /// The mock implementation does not depend on a daemon, so the callback is never invoked.
+    fn set_reconnected_callback(&mut self, _reconnected_callback: Box<DaemonReconnectedCallback>) {}
+
+    fn browse_services(&mut self) -> Result<EventLoop<'_>> {
+        let _span = operation_span!("browse", service_type = self.service_type.to_string());
+
+        debug!("Browsing services: {:?}", self);
+
+        let browser: *mut Self = self;
+
+        Ok(EventLoop::new().with_on_poll(Box::new(move || unsafe { (*browser).handle_poll() })))
+    }
+}
+
+impl MockMdnsBrowser {
+    /// Reports the services that have been registered or injected since the last poll.
+    fn handle_poll(&mut self) {
+        let domain = self.domain.as_deref();
+
+        let services: Vec<_> = registry::discoveries(&self.service_type)
+            .into_iter()
+            .filter(|s| match domain {
+                Some(d) => s.domain() == d,
+                None => true,
+            })
+            .collect();
+
+        for gone in self.discovered.iter().filter(|d| !services.contains(d)) {
+            self.debouncer.went_away(|| gone.clone());
+        }
+
+        // services that went away or changed are reported again
+        self.discovered
+            .retain(|d| services.iter().any(|s| is_unchanged(d, s)));
+
+        for mut service in services {
+            if self.discovered.iter().any(|d| is_unchanged(d, &service)) {
+                continue;
+            }
+
+            if self.debouncer.has_returned(|| service.clone()) {
+                debug!(
+                    "Ignoring service that came back within the debounce window: {}",
+                    service
+                );
+                self.discovered.push(service);
+                continue;
+            }
+
+            service.touch();
+
+            debug!("Service discovered: {:?}", service);
+
+            self.discovered.push(service.clone());
+            self.invoke_callback(Ok(service));
+        }
+
+        let events = mem::take(&mut *self.batch.borrow_mut());
+
+        match &self.service_batch_callback {
+            Some(f) if !events.is_empty() => f(events, self.user_context.clone()),
+            _ => {}
+        }
+    }
+
+    fn invoke_callback(&self, result: Result<ServiceDiscovery>) {
+        if !self.control.is_running() {
+            return;
+        }
+
+        match (
+            result,
+            &self.error_callback,
+            &self.service_discovered_callback,
+        ) {
+            (Err(e), Some(f), _) => f(e, self.user_context.clone()),
+            (result, _, _) if self.service_batch_callback.is_some() => {
+                self.batch.borrow_mut().push(BrowserEvent::from(result))
+            }
+            (result, _, Some(f)) => f(result, self.user_context.clone()),
+            _ => warn!("attempted to invoke callback but none was set"),
+        }
+    }
+}
+
+impl fmt::Debug for MockMdnsBrowser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockMdnsBrowser")
+            .field("service_type", &self.service_type)
+            .field("domain", &self.domain)
+            .finish()
+    }
+}
+
+/// Returns `true` if `current` is the same service instance as `previous` and has not been
+/// re-registered with another port or TXT record since.
+fn is_unchanged(previous: &ServiceDiscovery, current: &ServiceDiscovery) -> bool {
+    previous == current && previous.port() == current.port() && previous.txt() == current.txt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{BrowserStatus, MdnsService};
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::sync::Mutex;
+    use std::time::{Duration, Instant, SystemTime};
+
+    #[test]
+    fn browse_reports_injected_and_registered_services_once() {
+        let service_type = ServiceType::new("mock-browse", "tcp").unwrap();
+
+        registry::add_discovery(
+            ServiceDiscovery::builder()
+                .name("remote".to_string())
+                .service_type(service_type.clone())
+                .domain("local".to_string())
+                .host_name("remote.local".to_string())
+                .address(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2)))
+                .port(80)
+                .txt(None)
+                .build()
+                .unwrap(),
+        );
+
+        let mut service = MdnsService::new(service_type.clone(), 8080);
+        service.set_name("local");
+        service.set_registered_callback(Box::new(|r, _| {
+            r.unwrap();
+        }));
+
+        let handle = service.register().unwrap();
+        handle.poll(Duration::from_secs(0)).unwrap();
+
+        let names: Arc<Mutex<Vec<String>>> = Arc::default();
+        let names_clone = names.clone();
+
+        let mut browser = MockMdnsBrowser::new(service_type.clone());
+        browser.set_service_discovered_callback(Box::new(move |s, _| {
+            names_clone.lock().unwrap().push(s.unwrap().name().clone());
+        }));
+
+        let event_loop = browser.browse_services().unwrap();
+        event_loop.poll(Duration::from_secs(0)).unwrap();
+        event_loop.poll(Duration::from_secs(0)).unwrap();
+
+        assert_eq!(*names.lock().unwrap(), vec!["local", "remote"]);
+
+        registry::clear_discoveries(&service_type);
+    }
+
+    #[test]
+    fn on_service_discovered_accepts_mutable_closure() {
+        let service_type = ServiceType::new("mock-closure", "tcp").unwrap();
+
+        let mut service = MdnsService::new(service_type.clone(), 8080);
+        service.on_registered(|r, _| {
+            r.unwrap();
+        });
+
+        let handle = service.register().unwrap();
+        handle.poll(Duration::from_secs(0)).unwrap();
+
+        let count = Arc::new(Mutex::new(0));
+        let count_clone = count.clone();
+        let mut discovered = 0;
+
+        let mut browser = MockMdnsBrowser::new(service_type);
+        browser.on_service_discovered(move |s, _| {
+            s.unwrap();
+            discovered += 1;
+            *count_clone.lock().unwrap() = discovered;
+        });
+
+        let event_loop = browser.browse_services().unwrap();
+        event_loop.poll(Duration::from_secs(0)).unwrap();
+
+        assert_eq!(*count.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn services_of_a_poll_are_reported_in_one_batch() {
+        let service_type = ServiceType::new("mock-batch", "tcp").unwrap();
+
+        for (name, port) in [("first", 80), ("second", 81)] {
+            registry::add_discovery(
+                ServiceDiscovery::builder()
+                    .name(name.to_string())
+                    .service_type(service_type.clone())
+                    .domain("local".to_string())
+                    .host_name("remote.local".to_string())
+                    .address(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2)))
+                    .port(port)
+                    .txt(None)
+                    .build()
+                    .unwrap(),
+            );
+        }
+
+        let batches: Arc<Mutex<Vec<Vec<String>>>> = Arc::default();
+        let batches_clone = batches.clone();
+
+        let mut browser = MockMdnsBrowser::builder(service_type.clone())
+            .on_service_batch(move |events, _| {
+                let names = events
+                    .into_iter()
+                    .map(|e| match e {
+                        BrowserEvent::Discovered(s) => s.name().clone(),
+                        BrowserEvent::Error(e) => panic!("unexpected error: {}", e),
+                    })
+                    .collect();
+
+                batches_clone.lock().unwrap().push(names);
+            })
+            .build();
+
+        let event_loop = browser.browse_services().unwrap();
+        event_loop.poll(Duration::from_secs(0)).unwrap();
+        event_loop.poll(Duration::from_secs(0)).unwrap();
+
+        let mut batches = batches.lock().unwrap().clone();
+        batches.iter_mut().for_each(|b| b.sort());
+
+        assert_eq!(batches, vec![vec!["first", "second"]]);
+
+        registry::clear_discoveries(&service_type);
+    }
+
+    #[test]
+    fn services_coming_back_within_debounce_window_are_not_reported_again() {
+        let service_type = ServiceType::new("mock-debounce", "tcp").unwrap();
+
+        let discovery = ServiceDiscovery::builder()
+            .name("flapping".to_string())
+            .service_type(service_type.clone())
+            .domain("local".to_string())
+            .host_name("remote.local".to_string())
+            .address(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2)))
+            .port(80)
+            .txt(None)
+            .build()
+            .unwrap();
+
+        registry::add_discovery(discovery.clone());
+
+        let count = Arc::new(Mutex::new(0));
+        let count_clone = count.clone();
+
+        let mut browser = MockMdnsBrowser::builder(service_type.clone())
+            .debounce(Duration::from_secs(60))
+            .on_service_discovered(move |s, _| {
+                s.unwrap();
+                *count_clone.lock().unwrap() += 1;
+            })
+            .build();
+
+        let event_loop = browser.browse_services().unwrap();
+        event_loop.poll(Duration::from_secs(0)).unwrap();
+
+        registry::clear_discoveries(&service_type);
+        event_loop.poll(Duration::from_secs(0)).unwrap();
+
+        registry::add_discovery(discovery);
+        event_loop.poll(Duration::from_secs(0)).unwrap();
+
+        assert_eq!(*count.lock().unwrap(), 1);
+
+        registry::clear_discoveries(&service_type);
+    }
+
+    #[test]
+    fn discoveries_are_stamped_when_reported() {
+        let service_type = ServiceType::new("mock-stamp", "tcp").unwrap();
+
+        registry::add_discovery(
+            ServiceDiscovery::builder()
+                .name("remote".to_string())
+                .service_type(service_type.clone())
+                .domain("local".to_string())
+                .host_name("remote.local".to_string())
+                .address(IpAddr::V4(Ipv4Addr::new(192, 168, 1, 2)))
+                .port(80)
+                .txt(None)
+                .build()
+                .unwrap(),
+        );
+
+        let browsed_at = SystemTime::now();
+        let browsed = Instant::now();
+        let discoveries: Arc<Mutex<Vec<ServiceDiscovery>>> = Arc::default();
+        let discoveries_clone = discoveries.clone();
+
+        let mut browser = MockMdnsBrowser::new(service_type.clone());
+        browser.on_service_discovered(move |s, _| {
+            discoveries_clone.lock().unwrap().push(s.unwrap());
+        });
+
+        let event_loop = browser.browse_services().unwrap();
+        event_loop.poll(Duration::from_secs(0)).unwrap();
+
+        let discovery = discoveries.lock().unwrap().pop().unwrap();
+        assert!(*discovery.timestamp() >= browsed_at);
+        assert!(discovery.age() <= browsed.elapsed());
+
+        registry::clear_discoveries(&service_type);
+    }
+
+    #[test]
+    fn browse_scoped_invokes_borrowing_callback() {
+        let service_type = ServiceType::new("mock-scoped", "tcp").unwrap();
+
+        let mut service = MdnsService::new(service_type.clone(), 8080);
+        service.set_name("scoped");
+        service.set_registered_callback(Box::new(|r, _| {
+            r.unwrap();
+        }));
+
+        let handle = service.register().unwrap();
+        handle.poll(Duration::from_secs(0)).unwrap();
+
+        let mut names = vec![];
+
+        crate::browse_scoped(service_type, Duration::from_millis(50), |s| {
+            names.push(s.unwrap().name().clone())
+        })
+        .unwrap();
+
+        assert_eq!(names, vec!["scoped"]);
+    }
+
+    #[test]
+    fn control_pauses_and_stops_browser() {
+        let service_type = ServiceType::new("mock-control", "tcp").unwrap();
+
+        let names: Arc<Mutex<Vec<String>>> = Arc::default();
+        let names_clone = names.clone();
+
+        let mut browser = MockMdnsBrowser::new(service_type.clone());
+        browser.set_service_discovered_callback(Box::new(move |s, _| {
+            names_clone.lock().unwrap().push(s.unwrap().name().clone());
+        }));
+
+        let control = browser.control();
+        let event_loop = browser.browse_services().unwrap();
+
+        let register = |name: &str| {
+            let mut service = MdnsService::new(service_type.clone(), 8080);
+            service.set_name(name);
+            service.set_registered_callback(Box::new(|r, _| {
+                r.unwrap();
+            }));
+            service
+        };
+
+        let mut paused = register("paused");
+        let handle = paused.register().unwrap();
+        handle.poll(Duration::from_secs(0)).unwrap();
+
+        control.pause();
+        event_loop.poll(Duration::from_secs(0)).unwrap();
+        assert_eq!(control.status(), BrowserStatus::Paused);
+
+        let mut resumed = register("resumed");
+        let handle = resumed.register().unwrap();
+        handle.poll(Duration::from_secs(0)).unwrap();
+
+        control.resume();
+        event_loop.poll(Duration::from_secs(0)).unwrap();
+        assert_eq!(*names.lock().unwrap(), vec!["resumed"]);
+
+        control.stop();
+        control.resume();
+        assert!(control.is_stopped());
+
+        let mut stopped = register("stopped");
+        let handle = stopped.register().unwrap();
+        handle.poll(Duration::from_secs(0)).unwrap();
+
+        event_loop.poll(Duration::from_secs(0)).unwrap();
+        assert_eq!(*names.lock().unwrap(), vec!["resumed"]);
+    }
+}
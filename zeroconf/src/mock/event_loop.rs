@@ -0,0 +1,34 @@
+//! Event loop for running a `MdnsService` or `MdnsBrowser`.
+
+use crate::event_loop::TEventLoop;
+use crate::Result;
+use std::time::Duration;
+
+#[derive(new)]
+pub struct MockEventLoop<'a> {
+    #[new(default)]
+    on_poll: Option<Box<dyn Fn() + 'a>>,
+}
+
+impl<'a> MockEventLoop<'a> {
+    /// Sets a function to invoke on each iteration of the event loop, which delivers the pending
+    /// events of the service or browser.
+    pub(crate) fn with_on_poll(mut self, on_poll: Box<dyn Fn() + 'a>) -> Self {
+        self.on_poll = Some(on_poll);
+        self
+    }
+}
+
+impl<'a> TEventLoop for MockEventLoop<'a> {
+    /// Polls for new events.
+    ///
+    /// All pending events are delivered immediately, the `timeout` parameter does not do anything
+    /// in the mock implementation.
+    fn poll(&self, _timeout: Duration) -> Result<()> {
+        if let Some(f) = &self.on_poll {
+            f();
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,55 @@
+//! In-memory ZeroConf backend for unit tests
+//!
+//! This module replaces the mDNS backend of the platform when the `mock` feature is enabled, so
+//! code using `MdnsService` and `MdnsBrowser` can be tested without a daemon or a network (e.g. in
+//! CI). Enable it for the tests of your crate only:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! zeroconf = { version = "0.10", features = ["mock"] }
+//! ```
+//!
+//! Services registered with the mock backend are recorded in a process-wide registry, which tests
+//! can inspect with [`registrations()`], and are discovered by the browsers of their service type
+//! as they would be on a real network. Services of other hosts are simulated by injecting them
+//! with [`add_discovery()`].
+//!
+//! Events are delivered when the event loops are polled, as with the other backends. Tests
+//! running in parallel share the registry, so they should use distinct service types.
+//!
+//! # Examples
+//! ```
+//! use zeroconf::mock;
+//! use zeroconf::prelude::*;
+//! use zeroconf::{MdnsService, ServiceType};
+//! use std::time::Duration;
+//!
+//! let service_type = ServiceType::new("mock-example", "tcp").unwrap();
+//! let mut service = MdnsService::new(service_type.clone(), 8080);
+//!
+//! service.set_name("my-service");
+//! service.set_registered_callback(Box::new(|result, _| {
+//!     result.unwrap();
+//! }));
+//!
+//! let event_loop = service.register().unwrap();
+//! event_loop.poll(Duration::from_secs(0)).unwrap();
+//!
+//! let registrations = mock::registrations(&service_type);
+//! assert_eq!(registrations[0].name(), "my-service");
+//! assert_eq!(*registrations[0].port(), 8080);
+//! ```
+//!
+//! [`registrations()`]: fn.registrations.html
+//! [`add_discovery()`]: fn.add_discovery.html
+
+#[path = "../embedded/txt_record.rs"]
+pub mod txt_record;
+
+pub mod browser;
+pub mod event_loop;
+pub mod registration_handle;
+pub mod registry;
+pub mod service;
+
+pub use registry::{add_discovery, clear_discoveries, registrations, MockRegistration};
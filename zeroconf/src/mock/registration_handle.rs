@@ -0,0 +1,36 @@
+//! Mock implementation for cross-platform registration handle
+
+use super::event_loop::MockEventLoop;
+use super::service::MockMdnsService;
+use crate::prelude::*;
+use crate::registration_handle::TRegistrationHandle;
+use crate::Result;
+use std::time::Duration;
+
+#[derive(new)]
+pub struct MockRegistrationHandle<'a> {
+    service: *mut MockMdnsService,
+    event_loop: MockEventLoop<'a>,
+}
+
+impl<'a> TEventLoop for MockRegistrationHandle<'a> {
+    fn poll(&self, timeout: Duration) -> Result<()> {
+        self.event_loop.poll(timeout)
+    }
+}
+
+impl<'a> TRegistrationHandle for MockRegistrationHandle<'a> {
+    fn set_name(&mut self, name: &str) -> Result<()> {
+        unsafe {
+            (*self.service).set_name(name);
+            (*self.service).reregister()
+        }
+    }
+
+    fn set_port(&mut self, port: u16) -> Result<()> {
+        unsafe {
+            (*self.service).set_port(port);
+            (*self.service).reregister()
+        }
+    }
+}
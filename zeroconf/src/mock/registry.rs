@@ -0,0 +1,249 @@
+//! Process-wide registry of the services registered with, and injected into, the mock backend
+
+use crate::prelude::*;
+use crate::{NetworkInterface, ServiceDiscovery, ServiceFlags, ServiceType, TxtRecord};
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::{Mutex, MutexGuard};
+
+/// The host name of the services registered in this process, unless they set another host
+pub const HOST_NAME: &str = "mock.local";
+
+/// The domain of the services that do not set another domain
+pub const DOMAIN: &str = "local";
+
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    next_id: 0,
+    registrations: Vec::new(),
+    discoveries: Vec::new(),
+});
+
+struct Registry {
+    next_id: u64,
+    registrations: Vec<(u64, MockRegistration)>,
+    discoveries: Vec<ServiceDiscovery>,
+}
+
+/// A service registered with the mock backend, as configured on its `MdnsService`.
+#[derive(Debug, Getters, Clone, PartialEq, Eq)]
+pub struct MockRegistration {
+    name: String,
+    service_type: ServiceType,
+    domain: String,
+    host: Option<String>,
+    port: u16,
+    txt: Option<TxtRecord>,
+    interface: NetworkInterface,
+    flags: ServiceFlags,
+    ttl: Option<u32>,
+}
+
+impl MockRegistration {
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn new(
+        name: String,
+        service_type: ServiceType,
+        domain: String,
+        host: Option<String>,
+        port: u16,
+        txt: Option<TxtRecord>,
+        interface: NetworkInterface,
+        flags: ServiceFlags,
+        ttl: Option<u32>,
+    ) -> Self {
+        Self {
+            name,
+            service_type,
+            domain,
+            host,
+            port,
+            txt,
+            interface,
+            flags,
+            ttl,
+        }
+    }
+
+    fn conflicts_with(&self, other: &MockRegistration) -> bool {
+        self.name == other.name
+            && self.domain == other.domain
+            && is_same_kind(&self.service_type, &other.service_type)
+    }
+
+    /// Returns the service as discovered by the browsers of the mock backend.
+    fn to_discovery(&self) -> ServiceDiscovery {
+        let address = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+        ServiceDiscovery::builder()
+            .name(self.name.clone())
+            .service_type(self.service_type.clone())
+            .domain(self.domain.clone())
+            .host_name(self.host.clone().unwrap_or_else(|| HOST_NAME.to_string()))
+            .address(address)
+            .addresses(vec![address])
+            .port(self.port)
+            .txt(self.txt.clone())
+            .build()
+            .expect("could not build ServiceDiscovery")
+    }
+}
+
+fn registry() -> MutexGuard<'static, Registry> {
+    // a test panicking while holding the lock must not fail all other tests
+    REGISTRY.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn is_same_kind(a: &ServiceType, b: &ServiceType) -> bool {
+    a.name() == b.name() && a.protocol() == b.protocol()
+}
+
+/// Returns true if a browser of `browsed` type discovers services of `service_type`, i.e. they
+/// have the same name and protocol and the service has the browsed sub-type, if any.
+fn is_browsed(browsed: &ServiceType, service_type: &ServiceType) -> bool {
+    if !is_same_kind(browsed, service_type) {
+        return false;
+    }
+
+    match browsed.sub_types().first() {
+        Some(s) => service_type.sub_types().contains(s),
+        None => true,
+    }
+}
+
+/// Injects a service discovered by the browsers of its service type, as if it was registered by
+/// another host on the network.
+pub fn add_discovery(discovery: ServiceDiscovery) {
+    registry().discoveries.push(discovery);
+}
+
+/// Removes the services injected with [`add_discovery()`] of the specified `service_type`.
+///
+/// [`add_discovery()`]: fn.add_discovery.html
+pub fn clear_discoveries(service_type: &ServiceType) {
+    registry()
+        .discoveries
+        .retain(|d| !is_same_kind(d.service_type(), service_type));
+}
+
+/// Returns the services of the specified `service_type` that are currently registered in this
+/// process, in the order in which they were registered.
+pub fn registrations(service_type: &ServiceType) -> Vec<MockRegistration> {
+    registry()
+        .registrations
+        .iter()
+        .filter(|(_, r)| is_same_kind(&r.service_type, service_type))
+        .map(|(_, r)| r.clone())
+        .collect()
+}
+
+/// Registers `registration`, or replaces the registration with the specified `id`. Returns the
+/// ID of the registration, or `None` if its name conflicts with another registration.
+pub(super) fn publish(id: Option<u64>, registration: MockRegistration) -> Option<u64> {
+    let mut registry = registry();
+
+    let conflicts = registry
+        .registrations
+        .iter()
+        .any(|(i, r)| Some(*i) != id && r.conflicts_with(&registration));
+
+    if conflicts {
+        return None;
+    }
+
+    match id.and_then(|id| registry.registrations.iter_mut().find(|(i, _)| *i == id)) {
+        Some((id, r)) => {
+            *r = registration;
+            Some(*id)
+        }
+        None => {
+            let id = registry.next_id;
+            registry.next_id += 1;
+            registry.registrations.push((id, registration));
+            Some(id)
+        }
+    }
+}
+
+/// Removes the registration with the specified `id`.
+pub(super) fn unpublish(id: u64) {
+    registry().registrations.retain(|(i, _)| *i != id);
+}
+
+/// Returns the services discovered by a browser of the specified `service_type`.
+pub(super) fn discoveries(service_type: &ServiceType) -> Vec<ServiceDiscovery> {
+    let registry = registry();
+
+    let registered = registry.registrations.iter().map(|(_, r)| r.to_discovery());
+
+    let injected = registry.discoveries.iter().cloned();
+
+    registered
+        .chain(injected)
+        .filter(|d| is_browsed(service_type, d.service_type()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registration(name: &str, kind: &str) -> MockRegistration {
+        MockRegistration::new(
+            name.to_string(),
+            ServiceType::new(kind, "tcp").unwrap(),
+            DOMAIN.to_string(),
+            None,
+            8080,
+            None,
+            NetworkInterface::Unspec,
+            ServiceFlags::empty(),
+            None,
+        )
+    }
+
+    #[test]
+    fn publish_rejects_conflicting_name() {
+        let id = publish(None, registration("foo", "registry-conflict")).unwrap();
+
+        assert_eq!(
+            publish(None, registration("foo", "registry-conflict")),
+            None
+        );
+        assert_eq!(
+            publish(Some(id), registration("foo", "registry-conflict")),
+            Some(id)
+        );
+
+        unpublish(id);
+
+        assert!(registrations(&ServiceType::new("registry-conflict", "tcp").unwrap()).is_empty());
+    }
+
+    #[test]
+    fn discoveries_match_browsed_sub_type() {
+        let service_type = ServiceType::with_sub_types("registry-sub", "tcp", vec!["a"]).unwrap();
+        let id = publish(None, registration("foo", "registry-sub")).unwrap();
+
+        add_discovery(
+            ServiceDiscovery::builder()
+                .name("bar".to_string())
+                .service_type(service_type.clone())
+                .domain(DOMAIN.to_string())
+                .host_name("bar.local".to_string())
+                .address(IpAddr::V4(Ipv4Addr::LOCALHOST))
+                .port(80)
+                .txt(None)
+                .build()
+                .unwrap(),
+        );
+
+        let all = discoveries(&ServiceType::new("registry-sub", "tcp").unwrap());
+        let sub = discoveries(&service_type);
+
+        assert_eq!(all.len(), 2);
+        assert_eq!(sub.len(), 1);
+        assert_eq!(sub[0].name(), "bar");
+
+        unpublish(id);
+        clear_discoveries(&service_type);
+    }
+}
@@ -0,0 +1,351 @@
+//! Mock implementation for cross-platform service.
+
+use super::registration_handle::MockRegistrationHandle;
+use super::registry::{self, MockRegistration};
+use crate::error::{Error, ErrorKind};
+use crate::prelude::*;
+use crate::{
+    DaemonReconnectedCallback, ErrorCallback, EventLoop, NameConflictCallback, NetworkInterface,
+    RegistrationHandle, Result, ServiceFlags, ServiceRegisteredCallback, ServiceRegistration,
+    ServiceType, TxtRecord,
+};
+use std::any::Any;
+use std::fmt::{self, Formatter};
+use std::sync::Arc;
+
+pub struct MockMdnsService {
+    service_type: ServiceType,
+    port: u16,
+    name: Option<String>,
+    interface: NetworkInterface,
+    domain: Option<String>,
+    host: Option<String>,
+    txt_record: Option<TxtRecord>,
+    flags: ServiceFlags,
+    ttl: Option<u32>,
+    registered_callback: Option<Box<ServiceRegisteredCallback>>,
+    error_callback: Option<Box<ErrorCallback>>,
+    name_conflict_callback: Option<Box<NameConflictCallback>>,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+    /// The ID of the service in the registry, once registered
+    id: Option<u64>,
+    /// Whether the service must be (re-)registered on the next poll
+    pending: bool,
+}
+
+impl TMdnsService for MockMdnsService {
+    fn new(service_type: ServiceType, port: u16) -> Self {
+        Self {
+            service_type,
+            port,
+            name: None,
+            interface: NetworkInterface::Unspec,
+            domain: None,
+            host: None,
+            txt_record: None,
+            flags: ServiceFlags::empty(),
+            ttl: None,
+            registered_callback: None,
+            error_callback: None,
+            name_conflict_callback: None,
+            user_context: None,
+            id: None,
+            pending: false,
+        }
+    }
+
+    /// Sets the name to register this service under. If no name is set, `mock` is used.
+    fn set_name(&mut self, name: &str) {
+        self.name = Some(name.to_string());
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface = interface;
+    }
+
+    fn set_domain(&mut self, domain: &str) {
+        self.domain = Some(domain.to_string());
+    }
+
+    fn set_host(&mut self, host: &str) {
+        self.host = Some(host.to_string());
+    }
+
+    fn set_flags(&mut self, flags: ServiceFlags) {
+        self.flags = flags;
+    }
+
+    fn set_txt_record(&mut self, txt_record: TxtRecord) {
+        self.txt_record = Some(txt_record);
+    }
+
+    fn set_registered_callback(&mut self, registered_callback: Box<ServiceRegisteredCallback>) {
+        self.registered_callback = Some(registered_callback);
+    }
+
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>) {
+        self.error_callback = Some(error_callback);
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        self.user_context = Some(Arc::from(context));
+    }
+
+    fn set_ttl(&mut self, ttl: u32) {
+        self.ttl = Some(ttl);
+    }
+
+    /// The mock implementation does not depend on the network configuration, so it is ignored.
+    fn set_auto_reregister(&mut self, _auto_reregister: bool) {}
+
+    /// The mock implementation does not depend on a daemon, so the callback is never invoked.
+    fn set_reconnected_callback(&mut self, _reconnected_callback: Box<DaemonReconnectedCallback>) {}
+
+    /// The name of a service conflicts with the services of the same type and domain registered
+    /// in this process.
+    fn set_name_conflict_callback(&mut self, name_conflict_callback: Box<NameConflictCallback>) {
+        self.name_conflict_callback = Some(name_conflict_callback);
+    }
+
+    fn register(&mut self) -> Result<RegistrationHandle<'_>> {
+        let _span = operation_span!("register", service_type = self.service_type.to_string());
+
+        debug!("Registering service: {:?}", self);
+
+        // the service is registered on the next poll, as with the other implementations
+        self.pending = true;
+
+        let service: *mut Self = self;
+
+        let event_loop =
+            EventLoop::new().with_on_poll(Box::new(move || unsafe { (*service).handle_poll() }));
+
+        Ok(MockRegistrationHandle::new(service, event_loop))
+    }
+}
+
+impl MockMdnsService {
+    pub(crate) fn set_port(&mut self, port: u16) {
+        self.port = port;
+    }
+
+    /// Re-registers the service with its current configuration on the next poll.
+    pub(crate) fn reregister(&mut self) -> Result<()> {
+        self.pending = true;
+        Ok(())
+    }
+
+    fn handle_poll(&mut self) {
+        if !self.pending {
+            return;
+        }
+
+        self.pending = false;
+
+        let result = self.publish();
+
+        self.invoke_callback(result);
+    }
+
+    fn domain(&self) -> String {
+        self.domain
+            .clone()
+            .unwrap_or_else(|| registry::DOMAIN.to_string())
+    }
+
+    fn publish(&mut self) -> Result<ServiceRegistration> {
+        let mut name = self.name.clone().unwrap_or_else(|| "mock".to_string());
+
+        loop {
+            let registration = MockRegistration::new(
+                name.clone(),
+                self.service_type.clone(),
+                self.domain(),
+                self.host.clone(),
+                self.port,
+                self.txt_record.clone(),
+                self.interface,
+                self.flags,
+                self.ttl,
+            );
+
+            if let Some(id) = registry::publish(self.id, registration) {
+                self.id = Some(id);
+                break;
+            }
+
+            debug!("Name conflict: {}", name);
+
+            let candidate = self
+                .name_conflict_callback
+                .as_ref()
+                .and_then(|f| f(&name, self.user_context.clone()));
+
+            match candidate {
+                Some(c) => name = c,
+                None => {
+                    if let Some(id) = self.id.take() {
+                        registry::unpublish(id);
+                    }
+
+                    return Err(Error::with_kind(
+                        ErrorKind::NameConflict,
+                        format!("service name `{}` conflicts with another service", name),
+                    ));
+                }
+            }
+        }
+
+        self.name = Some(name.clone());
+
+        Ok(ServiceRegistration::builder()
+            .name(name)
+            .service_type(self.service_type.clone())
+            .domain(self.domain())
+            .build()?)
+    }
+
+    fn invoke_callback(&self, result: Result<ServiceRegistration>) {
+        match (result, &self.error_callback, &self.registered_callback) {
+            (Err(e), Some(f), _) => f(e, self.user_context.clone()),
+            (result, _, Some(f)) => f(result, self.user_context.clone()),
+            _ => warn!("attempted to invoke callback but none was set"),
+        }
+    }
+}
+
+impl Drop for MockMdnsService {
+    fn drop(&mut self) {
+        if let Some(id) = self.id {
+            registry::unpublish(id);
+        }
+    }
+}
+
+impl fmt::Debug for MockMdnsService {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MockMdnsService")
+            .field("service_type", &self.service_type)
+            .field("name", &self.name)
+            .field("port", &self.port)
+            .field("id", &self.id)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    fn register(service: &mut MockMdnsService) -> Result<ServiceRegistration> {
+        let result: Arc<Mutex<Option<Result<ServiceRegistration>>>> = Arc::default();
+        let result_clone = result.clone();
+
+        service.set_registered_callback(Box::new(move |r, _| {
+            *result_clone.lock().unwrap() = Some(r);
+        }));
+
+        service
+            .register()
+            .unwrap()
+            .poll(Duration::from_secs(0))
+            .unwrap();
+
+        let result = result.lock().unwrap().take().unwrap();
+        result
+    }
+
+    #[test]
+    fn register_records_registration() {
+        let service_type = ServiceType::new("mock-register", "tcp").unwrap();
+        let mut service = MockMdnsService::new(service_type.clone(), 8080);
+        service.set_name("foo");
+        service.set_ttl(60);
+
+        let registration = register(&mut service).unwrap();
+
+        assert_eq!(registration.name(), "foo");
+        assert_eq!(registration.domain(), "local");
+
+        let registrations = registry::registrations(&service_type);
+        assert_eq!(registrations.len(), 1);
+        assert_eq!(*registrations[0].port(), 8080);
+        assert_eq!(*registrations[0].ttl(), Some(60));
+
+        drop(service);
+
+        assert!(registry::registrations(&service_type).is_empty());
+    }
+
+    #[test]
+    fn close_removes_registration() {
+        let service_type = ServiceType::new("mock-close", "tcp").unwrap();
+        let mut service = MockMdnsService::new(service_type.clone(), 8080);
+
+        register(&mut service).unwrap();
+        assert_eq!(registry::registrations(&service_type).len(), 1);
+
+        service.close().unwrap();
+
+        assert!(registry::registrations(&service_type).is_empty());
+    }
+
+    #[test]
+    fn builder_applies_options() {
+        let service_type = ServiceType::new("mock-builder", "tcp").unwrap();
+        let mut service = MockMdnsService::builder(service_type.clone(), 8080)
+            .ttl(60)
+            .name("foo")
+            .build();
+
+        assert_eq!(register(&mut service).unwrap().name(), "foo");
+        assert_eq!(*registry::registrations(&service_type)[0].ttl(), Some(60));
+    }
+
+    #[test]
+    fn register_conflicting_name_invokes_conflict_callback() {
+        let service_type = ServiceType::new("mock-conflict", "tcp").unwrap();
+
+        let mut first = MockMdnsService::new(service_type.clone(), 8080);
+        first.set_name("foo");
+        register(&mut first).unwrap();
+
+        let mut second = MockMdnsService::new(service_type.clone(), 8081);
+        second.set_name("foo");
+        let error = register(&mut second).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::NameConflict);
+
+        let mut third = MockMdnsService::new(service_type, 8082);
+        third.set_name("foo");
+        third.set_name_conflict_callback(Box::new(|name, _| Some(format!("{} (2)", name))));
+        assert_eq!(register(&mut third).unwrap().name(), "foo (2)");
+    }
+
+    #[test]
+    fn errors_are_passed_to_error_callback() {
+        let service_type = ServiceType::new("mock-error", "tcp").unwrap();
+
+        let mut first = MockMdnsService::new(service_type.clone(), 8080);
+        first.set_name("foo");
+        register(&mut first).unwrap();
+
+        let errors: Arc<Mutex<Vec<ErrorKind>>> = Arc::default();
+        let errors_clone = errors.clone();
+
+        let mut second = MockMdnsService::builder(service_type, 8081)
+            .name("foo")
+            .on_registered(|_, _| panic!("registered callback was invoked"))
+            .on_error(move |e, _| errors_clone.lock().unwrap().push(e.kind()))
+            .build();
+
+        second
+            .register()
+            .unwrap()
+            .poll(Duration::from_secs(0))
+            .unwrap();
+
+        assert_eq!(*errors.lock().unwrap(), vec![ErrorKind::NameConflict]);
+    }
+}
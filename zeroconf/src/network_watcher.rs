@@ -0,0 +1,96 @@
+//! Utilities for detecting changes to the host's network configuration
+
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Minimum interval between two checks of the network configuration
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Snapshots the addresses assigned to the host's network interfaces and reports when they
+/// change (e.g. an interface going down/up or a new DHCP lease when roaming between networks).
+#[derive(Debug)]
+pub(crate) struct NetworkWatcher {
+    snapshot: Vec<(String, IpAddr)>,
+    last_check: Instant,
+}
+
+impl NetworkWatcher {
+    /// Creates a new `NetworkWatcher` initialized with the current network configuration.
+    pub fn new() -> Self {
+        Self {
+            snapshot: snapshot(),
+            last_check: Instant::now(),
+        }
+    }
+
+    /// Returns true if the network configuration has changed since the last time a change was
+    /// reported.
+    ///
+    /// Checks are rate-limited to one every `CHECK_INTERVAL`, so this function is cheap to call
+    /// from every iteration of an event loop.
+    pub fn poll_changed(&mut self) -> bool {
+        if self.last_check.elapsed() < CHECK_INTERVAL {
+            return false;
+        }
+
+        self.last_check = Instant::now();
+
+        let snapshot = snapshot();
+        if snapshot != self.snapshot {
+            self.snapshot = snapshot;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Returns the sorted list of addresses assigned to the interfaces listed by `interfaces()`,
+/// i.e. that are up and support multicast.
+pub(crate) fn snapshot() -> Vec<(String, IpAddr)> {
+    let interfaces = match crate::interfaces() {
+        Ok(interfaces) => interfaces,
+        Err(e) => {
+            warn!("could not list network interfaces: {}", e);
+            return vec![];
+        }
+    };
+
+    let mut addresses: Vec<(String, IpAddr)> = interfaces
+        .iter()
+        .flat_map(|i| i.addresses().iter().map(move |ip| (i.name().clone(), *ip)))
+        .collect();
+
+    addresses.sort();
+    addresses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn poll_changed_is_rate_limited() {
+        let mut watcher = NetworkWatcher::new();
+        watcher.snapshot.clear();
+        assert!(!watcher.poll_changed());
+    }
+
+    #[test]
+    fn poll_changed_reports_change_once() {
+        let mut watcher = NetworkWatcher::new();
+        watcher.snapshot.push((
+            "zeroconf-test".to_string(),
+            Ipv4Addr::new(192, 0, 2, 1).into(),
+        ));
+        watcher.last_check = Instant::now() - CHECK_INTERVAL;
+
+        assert!(watcher.poll_changed());
+        assert!(!watcher.poll_changed());
+
+        // the current configuration has been taken as the new snapshot
+        watcher.last_check = Instant::now() - CHECK_INTERVAL;
+        assert!(!watcher.poll_changed());
+    }
+}
@@ -0,0 +1,188 @@
+//! Trait definitions for mDNS implementations provided by other crates
+
+use crate::prelude::*;
+use crate::{
+    AddressFamily, AddressPolicy, Capabilities, DaemonReconnectedCallback, ErrorCallback,
+    NameConflictCallback, NetworkInterface, Result, ServiceDiscoveredCallback, ServiceFlags,
+    ServiceRegisteredCallback, ServiceType, TxtRecord,
+};
+use std::any::Any;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+static BACKEND: RwLock<Option<Arc<dyn MdnsBackend>>> = RwLock::new(None);
+
+/// An mDNS implementation that creates the browsers, services and resolvers the `MdnsBrowser`,
+/// `MdnsService` and `MdnsResolver` of this crate delegate to.
+pub trait MdnsBackend: Send + Sync {
+    /// Creates a new browser for the specified `service_type`.
+    fn browser(&self, service_type: ServiceType) -> Box<dyn BrowserBackend>;
+
+    /// Creates a new service with the specified `service_type` and `port`.
+    fn service(&self, service_type: ServiceType, port: u16) -> Box<dyn ServiceBackend>;
+
+    /// Creates a new resolver, or returns `None` if the implementation cannot resolve a service
+    /// instance without browsing for it first. Defaults to `None`.
+    fn resolver(&self) -> Option<Box<dyn ResolverBackend>> {
+        None
+    }
+
+    /// Returns the optional features the implementation supports, reported by
+    /// `Backend::capabilities()`. Defaults to none.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::empty()
+    }
+}
+
+/// The browser of an [`MdnsBackend`], see [`TMdnsBrowser`] for the semantics of each function.
+///
+/// The optional capabilities are ignored by default.
+///
+/// [`MdnsBackend`]: trait.MdnsBackend.html
+/// [`TMdnsBrowser`]: ../../browser/trait.TMdnsBrowser.html
+pub trait BrowserBackend: Send {
+    fn set_network_interface(&mut self, interface: NetworkInterface);
+
+    fn set_domain(&mut self, domain: &str);
+
+    fn set_address_family(&mut self, _address_family: AddressFamily) {}
+
+    fn set_address_policy(&mut self, _address_policy: AddressPolicy) {}
+
+    fn set_flags(&mut self, _flags: ServiceFlags) {}
+
+    fn set_include_raw_records(&mut self, _include_raw_records: bool) {}
+
+    fn set_resolve_concurrency(&mut self, _concurrency: usize) {}
+
+    fn set_debounce(&mut self, _window: Duration) {}
+
+    fn set_service_discovered_callback(
+        &mut self,
+        service_discovered_callback: Box<ServiceDiscoveredCallback>,
+    );
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>);
+
+    fn set_reconnected_callback(&mut self, _reconnected_callback: Box<DaemonReconnectedCallback>) {}
+
+    /// Starts the browser. Returns an event loop which is polled to keep the browser alive.
+    fn browse_services(&mut self) -> Result<Box<dyn TEventLoop + '_>>;
+
+    /// Stops the browser and releases what it holds. Backends that release everything when
+    /// dropped need not implement this.
+    fn close(self: Box<Self>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The service of an [`MdnsBackend`], see [`TMdnsService`] for the semantics of each function.
+///
+/// The optional capabilities are ignored by default.
+///
+/// [`MdnsBackend`]: trait.MdnsBackend.html
+/// [`TMdnsService`]: ../../service/trait.TMdnsService.html
+pub trait ServiceBackend: Send {
+    fn set_name(&mut self, name: &str);
+
+    fn set_network_interface(&mut self, interface: NetworkInterface);
+
+    fn set_domain(&mut self, domain: &str);
+
+    fn set_host(&mut self, host: &str);
+
+    fn set_flags(&mut self, _flags: ServiceFlags) {}
+
+    fn set_txt_record(&mut self, txt_record: TxtRecord);
+
+    fn set_registered_callback(&mut self, registered_callback: Box<ServiceRegisteredCallback>);
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>);
+
+    fn set_ttl(&mut self, _ttl: u32) {}
+
+    fn set_auto_reregister(&mut self, _auto_reregister: bool) {}
+
+    fn set_reconnected_callback(&mut self, _reconnected_callback: Box<DaemonReconnectedCallback>) {}
+
+    fn set_name_conflict_callback(&mut self, _name_conflict_callback: Box<NameConflictCallback>) {}
+
+    /// Registers and starts the service. Returns a handle which is polled to keep the service
+    /// alive and used to change the service's name or port while it is running.
+    fn register(&mut self) -> Result<Box<dyn TRegistrationHandle + '_>>;
+
+    /// Unregisters the service and releases what it holds. Backends that release everything
+    /// when dropped need not implement this.
+    fn close(self: Box<Self>) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The resolver of an [`MdnsBackend`], see [`TMdnsResolver`] for the semantics of each function.
+///
+/// The optional capabilities are ignored by default.
+///
+/// [`MdnsBackend`]: trait.MdnsBackend.html
+/// [`TMdnsResolver`]: ../../service_resolver/trait.TMdnsResolver.html
+pub trait ResolverBackend: Send {
+    fn set_network_interface(&mut self, interface: NetworkInterface);
+
+    fn set_address_family(&mut self, _address_family: AddressFamily) {}
+
+    fn set_address_policy(&mut self, _address_policy: AddressPolicy) {}
+
+    fn set_include_raw_records(&mut self, _include_raw_records: bool) {}
+
+    fn set_service_resolved_callback(
+        &mut self,
+        service_resolved_callback: Box<ServiceDiscoveredCallback>,
+    );
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>);
+
+    /// Starts resolving the specified service instance. Returns an event loop which is polled to
+    /// keep the resolver alive.
+    fn resolve(
+        &mut self,
+        name: &str,
+        service_type: ServiceType,
+        domain: &str,
+    ) -> Result<Box<dyn TEventLoop + '_>>;
+}
+
+/// Sets the implementation the browsers, services and resolvers created from now on delegate to,
+/// replacing the previously set implementation, if any.
+pub fn set_backend<B: MdnsBackend + 'static>(backend: B) {
+    *BACKEND.write().unwrap_or_else(|e| e.into_inner()) = Some(Arc::new(backend));
+}
+
+/// Returns the implementation set with [`set_backend()`], if any.
+///
+/// [`set_backend()`]: fn.set_backend.html
+pub fn backend() -> Option<Arc<dyn MdnsBackend>> {
+    BACKEND.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+pub(crate) fn no_backend_error() -> crate::error::Error {
+    "no mDNS backend has been set with `zeroconf::plugin::set_backend()`".into()
+}
+
+type ResultCallback<T> = dyn Fn(Result<T>, Option<Arc<dyn Any + Send + Sync>>) + Send;
+
+/// Combines the `callback` and the `error_callback` of a browser or service into the single
+/// callback the backends are given, which passes the errors to `error_callback` if it is set.
+pub(crate) fn with_error_callback<T: 'static>(
+    callback: Option<Box<ResultCallback<T>>>,
+    error_callback: Option<Box<ErrorCallback>>,
+) -> Option<Box<ResultCallback<T>>> {
+    let error_callback = match error_callback {
+        Some(f) => f,
+        None => return callback,
+    };
+
+    Some(Box::new(move |result, context| match (result, &callback) {
+        (Err(e), _) => error_callback(e, context),
+        (result, Some(f)) => f(result, context),
+        (Ok(_), None) => warn!("attempted to invoke callback but none was set"),
+    }))
+}
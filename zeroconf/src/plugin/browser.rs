@@ -0,0 +1,140 @@
+//! Plugin implementation for cross-platform browser
+
+use super::backend::{self, BrowserBackend};
+use super::event_loop::PluginEventLoop;
+use crate::prelude::*;
+use crate::{
+    AddressFamily, AddressPolicy, BrowserControl, DaemonReconnectedCallback, ErrorCallback,
+    EventLoop, NetworkInterface, Result, ServiceDiscoveredCallback, ServiceFlags, ServiceType,
+};
+use std::any::Any;
+use std::fmt::{self, Formatter};
+use std::time::Duration;
+
+pub struct PluginMdnsBrowser {
+    service_type: ServiceType,
+    /// The browser of the backend, if one was set when this browser was created
+    inner: Option<Box<dyn BrowserBackend>>,
+    /// The callbacks that are combined and set on the backend when the browser is started
+    service_discovered_callback: Option<Box<ServiceDiscoveredCallback>>,
+    error_callback: Option<Box<ErrorCallback>>,
+    control: BrowserControl,
+}
+
+impl TMdnsBrowser for PluginMdnsBrowser {
+    fn new(service_type: ServiceType) -> Self {
+        Self {
+            inner: backend::backend().map(|b| b.browser(service_type.clone())),
+            service_type,
+            service_discovered_callback: None,
+            error_callback: None,
+            control: BrowserControl::default(),
+        }
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.with_inner(|b| b.set_network_interface(interface));
+    }
+
+    fn set_domain(&mut self, domain: &str) {
+        self.with_inner(|b| b.set_domain(domain));
+    }
+
+    fn set_address_family(&mut self, address_family: AddressFamily) {
+        self.with_inner(|b| b.set_address_family(address_family));
+    }
+
+    fn set_address_policy(&mut self, address_policy: AddressPolicy) {
+        self.with_inner(|b| b.set_address_policy(address_policy));
+    }
+
+    fn set_flags(&mut self, flags: ServiceFlags) {
+        self.with_inner(|b| b.set_flags(flags));
+    }
+
+    fn set_include_raw_records(&mut self, include_raw_records: bool) {
+        self.with_inner(|b| b.set_include_raw_records(include_raw_records));
+    }
+
+    fn set_resolve_concurrency(&mut self, concurrency: usize) {
+        self.with_inner(|b| b.set_resolve_concurrency(concurrency));
+    }
+
+    fn set_debounce(&mut self, window: Duration) {
+        self.with_inner(|b| b.set_debounce(window));
+    }
+
+    fn set_service_discovered_callback(
+        &mut self,
+        service_discovered_callback: Box<ServiceDiscoveredCallback>,
+    ) {
+        self.service_discovered_callback = Some(service_discovered_callback);
+    }
+
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>) {
+        self.error_callback = Some(error_callback);
+    }
+
+    fn control(&self) -> BrowserControl {
+        self.control.clone()
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        self.with_inner(|b| b.set_context(context));
+    }
+
+    fn set_reconnected_callback(&mut self, reconnected_callback: Box<DaemonReconnectedCallback>) {
+        self.with_inner(|b| b.set_reconnected_callback(reconnected_callback));
+    }
+
+    /// Starts the browser of the backend, returning an error if no backend was set when this
+    /// browser was created.
+    fn browse_services(&mut self) -> Result<EventLoop<'_>> {
+        let _span = operation_span!("browse", service_type = self.service_type.to_string());
+
+        debug!("Browsing services: {:?}", self);
+
+        let callback = backend::with_error_callback(
+            self.service_discovered_callback.take(),
+            self.error_callback.take(),
+        );
+
+        if let Some(f) = callback {
+            let control = self.control.clone();
+
+            self.with_inner(|b| {
+                b.set_service_discovered_callback(Box::new(move |result, context| {
+                    if control.is_running() {
+                        f(result, context);
+                    }
+                }))
+            });
+        }
+
+        match &mut self.inner {
+            Some(b) => Ok(PluginEventLoop::new(b.browse_services()?)),
+            None => Err(backend::no_backend_error()),
+        }
+    }
+
+    fn close(mut self) -> Result<()> {
+        self.inner.take().map_or(Ok(()), |b| b.close())
+    }
+}
+
+impl PluginMdnsBrowser {
+    fn with_inner<F: FnOnce(&mut dyn BrowserBackend)>(&mut self, f: F) {
+        if let Some(b) = &mut self.inner {
+            f(b.as_mut());
+        }
+    }
+}
+
+impl fmt::Debug for PluginMdnsBrowser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PluginMdnsBrowser")
+            .field("service_type", &self.service_type)
+            .field("has_backend", &self.inner.is_some())
+            .finish()
+    }
+}
@@ -0,0 +1,17 @@
+//! Event loop for running a `MdnsService`, `MdnsBrowser` or `MdnsResolver`.
+
+use crate::event_loop::TEventLoop;
+use crate::Result;
+use std::time::Duration;
+
+#[derive(new)]
+pub struct PluginEventLoop<'a> {
+    inner: Box<dyn TEventLoop + 'a>,
+}
+
+impl<'a> TEventLoop for PluginEventLoop<'a> {
+    /// Polls the event loop of the backend for new events.
+    fn poll(&self, timeout: Duration) -> Result<()> {
+        self.inner.poll(timeout)
+    }
+}
@@ -0,0 +1,287 @@
+//! Extension point for mDNS implementations provided by other crates
+//!
+//! This module replaces the mDNS backend of the platform when the `plugin` feature is enabled:
+//! `MdnsBrowser`, `MdnsService` and `MdnsResolver` then delegate to the [`MdnsBackend`] set with
+//! [`set_backend()`], so applications can use a mDNS stack this crate does not wrap (e.g. a
+//! proprietary one) without patching it.
+//!
+//! A backend implements [`BrowserBackend`], [`ServiceBackend`] and, optionally,
+//! [`ResolverBackend`], and returns implementations of `TEventLoop` and `TRegistrationHandle`
+//! from them, which are polled through the event loops of this crate.
+//!
+//! The backend must be set before any browser, service or resolver is created, the ones created
+//! before report an error once they are started. TXT records are stored in memory and encoded by
+//! the backend, e.g. from `TTxtRecord::iter()`.
+//!
+//! # Examples
+//! ```no_run
+//! use zeroconf::plugin::{self, BrowserBackend, MdnsBackend, ServiceBackend};
+//! use zeroconf::ServiceType;
+//!
+//! struct MyBackend;
+//!
+//! impl MdnsBackend for MyBackend {
+//!     fn browser(&self, service_type: ServiceType) -> Box<dyn BrowserBackend> {
+//!         // wrap the browser of the mDNS stack
+//! #       unimplemented!()
+//!     }
+//!
+//!     fn service(&self, service_type: ServiceType, port: u16) -> Box<dyn ServiceBackend> {
+//!         // wrap the service of the mDNS stack
+//! #       unimplemented!()
+//!     }
+//! }
+//!
+//! plugin::set_backend(MyBackend);
+//! ```
+//!
+//! [`MdnsBackend`]: trait.MdnsBackend.html
+//! [`set_backend()`]: fn.set_backend.html
+//! [`BrowserBackend`]: trait.BrowserBackend.html
+//! [`ServiceBackend`]: trait.ServiceBackend.html
+//! [`ResolverBackend`]: trait.ResolverBackend.html
+
+#[path = "../embedded/txt_record.rs"]
+pub mod txt_record;
+
+pub mod backend;
+pub mod browser;
+pub mod event_loop;
+pub mod registration_handle;
+pub mod service;
+pub mod service_resolver;
+
+pub use backend::{
+    backend, set_backend, BrowserBackend, MdnsBackend, ResolverBackend, ServiceBackend,
+};
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::prelude::*;
+    use crate::{
+        MdnsResolver, MdnsService, NetworkInterface, Result, ServiceDiscoveredCallback,
+        ServiceDiscovery, ServiceRegisteredCallback, ServiceRegistration, ServiceType, TxtRecord,
+    };
+    use std::any::Any;
+    use std::cell::Cell;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::sync::{Arc, Mutex, Once};
+    use std::time::Duration;
+
+    static INIT: Once = Once::new();
+
+    type Services = Arc<Mutex<Vec<ServiceDiscovery>>>;
+
+    /// Sets a backend that discovers the services registered in this process.
+    pub(crate) fn set_loopback_backend() {
+        INIT.call_once(|| set_backend(LoopbackBackend::default()));
+    }
+
+    #[derive(Default)]
+    struct LoopbackBackend {
+        services: Services,
+    }
+
+    impl MdnsBackend for LoopbackBackend {
+        fn browser(&self, service_type: ServiceType) -> Box<dyn BrowserBackend> {
+            Box::new(LoopbackBrowser {
+                service_type,
+                services: self.services.clone(),
+                callback: None,
+                context: None,
+            })
+        }
+
+        fn service(&self, service_type: ServiceType, port: u16) -> Box<dyn ServiceBackend> {
+            Box::new(LoopbackService {
+                service_type,
+                port,
+                name: "loopback".to_string(),
+                txt: None,
+                services: self.services.clone(),
+                callback: None,
+                context: None,
+            })
+        }
+    }
+
+    struct LoopbackBrowser {
+        service_type: ServiceType,
+        services: Services,
+        callback: Option<Box<ServiceDiscoveredCallback>>,
+        context: Option<Arc<dyn Any + Send + Sync>>,
+    }
+
+    impl BrowserBackend for LoopbackBrowser {
+        fn set_network_interface(&mut self, _interface: NetworkInterface) {}
+
+        fn set_domain(&mut self, _domain: &str) {}
+
+        fn set_service_discovered_callback(
+            &mut self,
+            service_discovered_callback: Box<ServiceDiscoveredCallback>,
+        ) {
+            self.callback = Some(service_discovered_callback);
+        }
+
+        fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+            self.context = Some(Arc::from(context));
+        }
+
+        fn browse_services(&mut self) -> Result<Box<dyn TEventLoop + '_>> {
+            Ok(Box::new(LoopbackBrowserLoop {
+                browser: self,
+                reported: Cell::new(0),
+            }))
+        }
+    }
+
+    struct LoopbackBrowserLoop<'a> {
+        browser: &'a LoopbackBrowser,
+        reported: Cell<usize>,
+    }
+
+    impl<'a> TEventLoop for LoopbackBrowserLoop<'a> {
+        fn poll(&self, _timeout: Duration) -> Result<()> {
+            let services = self.browser.services.lock().unwrap().clone();
+
+            for service in &services[self.reported.get()..] {
+                if service.service_type().name() == self.browser.service_type.name() {
+                    let f = self.browser.callback.as_ref().unwrap();
+                    f(Ok(service.clone()), self.browser.context.clone());
+                }
+            }
+
+            self.reported.set(services.len());
+
+            Ok(())
+        }
+    }
+
+    struct LoopbackService {
+        service_type: ServiceType,
+        port: u16,
+        name: String,
+        txt: Option<TxtRecord>,
+        services: Services,
+        callback: Option<Box<ServiceRegisteredCallback>>,
+        context: Option<Arc<dyn Any + Send + Sync>>,
+    }
+
+    impl ServiceBackend for LoopbackService {
+        fn set_name(&mut self, name: &str) {
+            self.name = name.to_string();
+        }
+
+        fn set_network_interface(&mut self, _interface: NetworkInterface) {}
+
+        fn set_domain(&mut self, _domain: &str) {}
+
+        fn set_host(&mut self, _host: &str) {}
+
+        fn set_txt_record(&mut self, txt_record: TxtRecord) {
+            self.txt = Some(txt_record);
+        }
+
+        fn set_registered_callback(&mut self, registered_callback: Box<ServiceRegisteredCallback>) {
+            self.callback = Some(registered_callback);
+        }
+
+        fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+            self.context = Some(Arc::from(context));
+        }
+
+        fn register(&mut self) -> Result<Box<dyn TRegistrationHandle + '_>> {
+            Ok(Box::new(LoopbackHandle {
+                service: self,
+                pending: Cell::new(true),
+            }))
+        }
+    }
+
+    struct LoopbackHandle<'a> {
+        service: &'a mut LoopbackService,
+        pending: Cell<bool>,
+    }
+
+    impl<'a> TEventLoop for LoopbackHandle<'a> {
+        fn poll(&self, _timeout: Duration) -> Result<()> {
+            if !self.pending.replace(false) {
+                return Ok(());
+            }
+
+            let service = &self.service;
+
+            service.services.lock().unwrap().push(
+                ServiceDiscovery::builder()
+                    .name(service.name.clone())
+                    .service_type(service.service_type.clone())
+                    .domain("local".to_string())
+                    .host_name("loopback.local".to_string())
+                    .address(IpAddr::V4(Ipv4Addr::LOCALHOST))
+                    .addresses(vec![IpAddr::V4(Ipv4Addr::LOCALHOST)])
+                    .port(service.port)
+                    .txt(service.txt.clone())
+                    .build()?,
+            );
+
+            let registration = ServiceRegistration::builder()
+                .name(service.name.clone())
+                .service_type(service.service_type.clone())
+                .domain("local".to_string())
+                .build()?;
+
+            if let Some(f) = &service.callback {
+                f(Ok(registration), service.context.clone());
+            }
+
+            Ok(())
+        }
+    }
+
+    impl<'a> TRegistrationHandle for LoopbackHandle<'a> {
+        fn set_name(&mut self, name: &str) -> Result<()> {
+            self.service.set_name(name);
+            self.pending.set(true);
+            Ok(())
+        }
+
+        fn set_port(&mut self, port: u16) -> Result<()> {
+            self.service.port = port;
+            self.pending.set(true);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn registration_handle_delegates_to_backend() {
+        set_loopback_backend();
+
+        let names: Arc<Mutex<Vec<String>>> = Arc::default();
+        let names_clone = names.clone();
+
+        let mut service = MdnsService::new(ServiceType::new("plugin-handle", "tcp").unwrap(), 80);
+        service.set_name("foo");
+        service.set_registered_callback(Box::new(move |r, _| {
+            names_clone.lock().unwrap().push(r.unwrap().name().clone());
+        }));
+
+        let mut handle = service.register().unwrap();
+        handle.poll(Duration::from_secs(0)).unwrap();
+        handle.set_name("bar").unwrap();
+        handle.poll(Duration::from_secs(0)).unwrap();
+
+        assert_eq!(*names.lock().unwrap(), vec!["foo", "bar"]);
+    }
+
+    #[test]
+    fn resolve_fails_without_resolver_support() {
+        set_loopback_backend();
+
+        let mut resolver = MdnsResolver::new();
+        let service_type = ServiceType::new("plugin-resolve", "tcp").unwrap();
+
+        assert!(resolver.resolve("foo", service_type, "local").is_err());
+    }
+}
@@ -0,0 +1,27 @@
+//! Plugin implementation for cross-platform registration handle
+
+use crate::prelude::*;
+use crate::registration_handle::TRegistrationHandle;
+use crate::Result;
+use std::time::Duration;
+
+#[derive(new)]
+pub struct PluginRegistrationHandle<'a> {
+    inner: Box<dyn TRegistrationHandle + 'a>,
+}
+
+impl<'a> TEventLoop for PluginRegistrationHandle<'a> {
+    fn poll(&self, timeout: Duration) -> Result<()> {
+        self.inner.poll(timeout)
+    }
+}
+
+impl<'a> TRegistrationHandle for PluginRegistrationHandle<'a> {
+    fn set_name(&mut self, name: &str) -> Result<()> {
+        self.inner.set_name(name)
+    }
+
+    fn set_port(&mut self, port: u16) -> Result<()> {
+        self.inner.set_port(port)
+    }
+}
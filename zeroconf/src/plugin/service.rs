@@ -0,0 +1,129 @@
+//! Plugin implementation for cross-platform service.
+
+use super::backend::{self, ServiceBackend};
+use super::registration_handle::PluginRegistrationHandle;
+use crate::prelude::*;
+use crate::{
+    DaemonReconnectedCallback, ErrorCallback, NameConflictCallback, NetworkInterface,
+    RegistrationHandle, Result, ServiceFlags, ServiceRegisteredCallback, ServiceType, TxtRecord,
+};
+use std::any::Any;
+use std::fmt::{self, Formatter};
+
+pub struct PluginMdnsService {
+    service_type: ServiceType,
+    port: u16,
+    /// The service of the backend, if one was set when this service was created
+    inner: Option<Box<dyn ServiceBackend>>,
+    /// The callbacks that are combined and set on the backend when the service is registered
+    registered_callback: Option<Box<ServiceRegisteredCallback>>,
+    error_callback: Option<Box<ErrorCallback>>,
+}
+
+impl TMdnsService for PluginMdnsService {
+    fn new(service_type: ServiceType, port: u16) -> Self {
+        Self {
+            inner: backend::backend().map(|b| b.service(service_type.clone(), port)),
+            service_type,
+            port,
+            registered_callback: None,
+            error_callback: None,
+        }
+    }
+
+    fn set_name(&mut self, name: &str) {
+        self.with_inner(|s| s.set_name(name));
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.with_inner(|s| s.set_network_interface(interface));
+    }
+
+    fn set_domain(&mut self, domain: &str) {
+        self.with_inner(|s| s.set_domain(domain));
+    }
+
+    fn set_host(&mut self, host: &str) {
+        self.with_inner(|s| s.set_host(host));
+    }
+
+    fn set_flags(&mut self, flags: ServiceFlags) {
+        self.with_inner(|s| s.set_flags(flags));
+    }
+
+    fn set_txt_record(&mut self, txt_record: TxtRecord) {
+        self.with_inner(|s| s.set_txt_record(txt_record));
+    }
+
+    fn set_registered_callback(&mut self, registered_callback: Box<ServiceRegisteredCallback>) {
+        self.registered_callback = Some(registered_callback);
+    }
+
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>) {
+        self.error_callback = Some(error_callback);
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        self.with_inner(|s| s.set_context(context));
+    }
+
+    fn set_ttl(&mut self, ttl: u32) {
+        self.with_inner(|s| s.set_ttl(ttl));
+    }
+
+    fn set_auto_reregister(&mut self, auto_reregister: bool) {
+        self.with_inner(|s| s.set_auto_reregister(auto_reregister));
+    }
+
+    fn set_reconnected_callback(&mut self, reconnected_callback: Box<DaemonReconnectedCallback>) {
+        self.with_inner(|s| s.set_reconnected_callback(reconnected_callback));
+    }
+
+    fn set_name_conflict_callback(&mut self, name_conflict_callback: Box<NameConflictCallback>) {
+        self.with_inner(|s| s.set_name_conflict_callback(name_conflict_callback));
+    }
+
+    /// Registers the service with the backend, returning an error if no backend was set when this
+    /// service was created.
+    fn register(&mut self) -> Result<RegistrationHandle<'_>> {
+        let _span = operation_span!("register", service_type = self.service_type.to_string());
+
+        debug!("Registering service: {:?}", self);
+
+        let callback = backend::with_error_callback(
+            self.registered_callback.take(),
+            self.error_callback.take(),
+        );
+
+        if let Some(f) = callback {
+            self.with_inner(|s| s.set_registered_callback(f));
+        }
+
+        match &mut self.inner {
+            Some(s) => Ok(PluginRegistrationHandle::new(s.register()?)),
+            None => Err(backend::no_backend_error()),
+        }
+    }
+
+    fn close(mut self) -> Result<()> {
+        self.inner.take().map_or(Ok(()), |b| b.close())
+    }
+}
+
+impl PluginMdnsService {
+    fn with_inner<F: FnOnce(&mut dyn ServiceBackend)>(&mut self, f: F) {
+        if let Some(s) = &mut self.inner {
+            f(s.as_mut());
+        }
+    }
+}
+
+impl fmt::Debug for PluginMdnsService {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PluginMdnsService")
+            .field("service_type", &self.service_type)
+            .field("port", &self.port)
+            .field("has_backend", &self.inner.is_some())
+            .finish()
+    }
+}
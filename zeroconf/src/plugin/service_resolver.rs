@@ -0,0 +1,92 @@
+//! Plugin implementation for cross-platform service resolver
+
+use super::backend::{self, ResolverBackend};
+use super::event_loop::PluginEventLoop;
+use crate::prelude::*;
+use crate::{
+    AddressFamily, AddressPolicy, EventLoop, NetworkInterface, Result, ServiceDiscoveredCallback,
+    ServiceType,
+};
+use std::any::Any;
+use std::fmt::{self, Formatter};
+
+pub struct PluginMdnsResolver {
+    /// The resolver of the backend, if one was set when this resolver was created and it supports
+    /// resolving services
+    inner: Option<Box<dyn ResolverBackend>>,
+}
+
+impl TMdnsResolver for PluginMdnsResolver {
+    fn new() -> Self {
+        Self {
+            inner: backend::backend().and_then(|b| b.resolver()),
+        }
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.with_inner(|r| r.set_network_interface(interface));
+    }
+
+    fn set_address_family(&mut self, address_family: AddressFamily) {
+        self.with_inner(|r| r.set_address_family(address_family));
+    }
+
+    fn set_address_policy(&mut self, address_policy: AddressPolicy) {
+        self.with_inner(|r| r.set_address_policy(address_policy));
+    }
+
+    fn set_include_raw_records(&mut self, include_raw_records: bool) {
+        self.with_inner(|r| r.set_include_raw_records(include_raw_records));
+    }
+
+    fn set_service_resolved_callback(
+        &mut self,
+        service_resolved_callback: Box<ServiceDiscoveredCallback>,
+    ) {
+        self.with_inner(|r| r.set_service_resolved_callback(service_resolved_callback));
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        self.with_inner(|r| r.set_context(context));
+    }
+
+    /// Starts resolving the service with the backend, returning an error if no backend was set
+    /// when this resolver was created or it does not support resolving services.
+    fn resolve(
+        &mut self,
+        name: &str,
+        service_type: ServiceType,
+        domain: &str,
+    ) -> Result<EventLoop<'_>> {
+        debug!("Resolving service: {} of type {:?}", name, service_type);
+
+        match &mut self.inner {
+            Some(r) => Ok(PluginEventLoop::new(r.resolve(
+                name,
+                service_type,
+                domain,
+            )?)),
+            None => Err(
+                "no mDNS backend that supports resolving services has been set with \
+                         `zeroconf::plugin::set_backend()`"
+                    .into(),
+            ),
+        }
+    }
+}
+
+impl PluginMdnsResolver {
+    fn with_inner<F: FnOnce(&mut dyn ResolverBackend)>(&mut self, f: F) {
+        if let Some(r) = &mut self.inner {
+            f(r.as_mut());
+        }
+    }
+}
+
+impl fmt::Debug for PluginMdnsResolver {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PluginMdnsResolver")
+            .field("has_backend", &self.inner.is_some())
+            .finish()
+    }
+}
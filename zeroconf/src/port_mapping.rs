@@ -0,0 +1,89 @@
+//! Trait definition for NAT port mappings
+//!
+//! # Note
+//! Port mappings are only supported by Bonjour, there is no `PortMapping` type on other
+//! platforms.
+
+use crate::{EventLoop, NetworkInterface, Result};
+use std::any::Any;
+use std::net::Ipv4Addr;
+use std::sync::Arc;
+
+/// Interface for requesting a port mapping from a NAT gateway (using NAT-PMP or PCP) to make a
+/// local service reachable from the WAN.
+///
+/// The mapping is kept alive (and refreshed by the mDNS daemon) for as long as the returned
+/// `EventLoop` is alive, and is released when the `PortMapping` is dropped.
+pub trait TPortMapping {
+    /// Creates a new `PortMapping` for the specified `protocol` and local `internal_port`
+    fn new(protocol: PortMappingProtocol, internal_port: u16) -> Self
+    where
+        Self: Sized;
+
+    /// Sets the requested external port. The gateway may assign a different port, the actual
+    /// port is reported to the [`PortMappedCallback`].
+    ///
+    /// The default value of `0` lets the gateway choose a port.
+    ///
+    /// [`PortMappedCallback`]: ../type.PortMappedCallback.html
+    fn set_external_port(&mut self, external_port: u16);
+
+    /// Sets the requested lifetime (in seconds) of the mapping.
+    ///
+    /// The default value of `0` uses the daemon's default lifetime.
+    fn set_ttl(&mut self, ttl: u32);
+
+    /// Sets the network interface on which to request the mapping.
+    fn set_network_interface(&mut self, interface: NetworkInterface);
+
+    /// Sets the [`PortMappedCallback`] that is invoked when the mapping has been created or has
+    /// changed (e.g. because the external address of the gateway changed).
+    ///
+    /// [`PortMappedCallback`]: ../type.PortMappedCallback.html
+    fn set_port_mapped_callback(&mut self, port_mapped_callback: Box<PortMappedCallback>);
+
+    /// Sets the optional user context to pass through to the callback. This is useful if you need
+    /// to share state between pre and post-callback. The context type must implement `Any`, `Send`
+    /// and `Sync`.
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>);
+
+    /// Requests the mapping. Returns an `EventLoop` which can be called to keep the mapping alive.
+    fn create(&mut self) -> Result<EventLoop<'_>>;
+}
+
+/// The transport protocol of a [`PortMapping`].
+///
+/// [`PortMapping`]: type.PortMapping.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PortMappingProtocol {
+    /// Map a TCP port
+    Tcp,
+    /// Map a UDP port
+    Udp,
+}
+
+/// Callback invoked from [`PortMapping`] once the mapping has been created or has changed.
+///
+/// # Arguments
+/// * `mapping` - The mapping that was created by the gateway
+/// * `context` - The optional user context passed through
+///
+/// [`PortMapping`]: type.PortMapping.html
+pub type PortMappedCallback =
+    dyn Fn(Result<PortMappingResult>, Option<Arc<dyn Any + Send + Sync>>) + Send;
+
+/// Represents a port mapping that has been created by a NAT gateway for a [`PortMapping`].
+///
+/// [`PortMapping`]: type.PortMapping.html
+#[derive(
+    Debug, Getters, Builder, BuilderDelegate, Serialize, Deserialize, Clone, PartialEq, Eq,
+)]
+pub struct PortMappingResult {
+    /// The external (WAN) address of the gateway
+    external_address: Ipv4Addr,
+    protocol: PortMappingProtocol,
+    internal_port: u16,
+    external_port: u16,
+    /// The lifetime (in seconds) of the mapping granted by the gateway
+    ttl: u32,
+}
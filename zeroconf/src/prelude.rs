@@ -1,8 +1,15 @@
 //! Crate prelude
 
+pub use crate::address_resolver::TAddressResolver;
 pub use crate::browser::TMdnsBrowser;
+pub use crate::context::ContextExt;
 pub use crate::event_loop::TEventLoop;
+pub use crate::host_name_resolver::THostNameResolver;
+pub use crate::port_mapping::TPortMapping;
+pub use crate::record_query::TRecordQuery;
+pub use crate::registration_handle::TRegistrationHandle;
 pub use crate::service::TMdnsService;
+pub use crate::service_resolver::TMdnsResolver;
 pub use crate::txt_record::TTxtRecord;
 
 /// Implements a `builder()` function for the specified type
@@ -0,0 +1,97 @@
+//! Trait definition for cross-platform DNS record query
+
+use crate::txt_record::TxtRecordView;
+use crate::{dns, DnssecStatus, EventLoop, NetworkInterface, Result, ServiceFlags};
+use std::any::Any;
+use std::sync::Arc;
+
+/// Interface for interacting with underlying mDNS implementation record query capabilities.
+///
+/// A `RecordQuery` queries DNS records of an arbitrary type (e.g. `PTR`, `SRV` or `TXT`) directly
+/// and reports their raw RDATA. This is intended for advanced use cases that are not covered by
+/// `MdnsBrowser` and the resolvers. See the [`dns`] module for record types and utilities to
+/// decode the RDATA.
+///
+/// [`dns`]: ../dns/index.html
+pub trait TRecordQuery {
+    /// Creates a new `RecordQuery` for records of type `rrtype` (e.g. `dns::TYPE_PTR`) of the
+    /// specified fully qualified `name` (e.g. `_http._tcp.local`)
+    fn new(name: &str, rrtype: u16) -> Self
+    where
+        Self: Sized;
+
+    /// Sets the class of the records to query. Defaults to `dns::CLASS_IN`.
+    fn set_class(&mut self, rrclass: u16);
+
+    /// Sets the network interface on which to query records.
+    ///
+    /// Most applications will want to use the default value `NetworkInterface::Unspec` to query
+    /// on all available interfaces.
+    fn set_network_interface(&mut self, interface: NetworkInterface);
+
+    /// Sets the [`ServiceFlags`] to query records with.
+    ///
+    /// If `ServiceFlags::VALIDATE` is set, the [`RecordReceivedCallback`] is invoked again once a
+    /// record has been validated.
+    ///
+    /// [`RecordReceivedCallback`]: ../type.RecordReceivedCallback.html
+    /// [`ServiceFlags`]: ../flags/struct.ServiceFlags.html
+    fn set_flags(&mut self, flags: ServiceFlags);
+
+    /// Sets the [`RecordReceivedCallback`] that is invoked when a record has been received.
+    ///
+    /// [`RecordReceivedCallback`]: ../type.RecordReceivedCallback.html
+    fn set_record_received_callback(
+        &mut self,
+        record_received_callback: Box<RecordReceivedCallback>,
+    );
+
+    /// Sets the optional user context to pass through to the callback. This is useful if you need
+    /// to share state between pre and post-callback. The context type must implement `Any`, `Send`
+    /// and `Sync`.
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>);
+
+    /// Starts the query. Returns an `EventLoop` which can be called to keep the query alive.
+    fn query(&mut self) -> Result<EventLoop<'_>>;
+}
+
+/// Callback invoked from [`RecordQuery`] once a record has been received.
+///
+/// # Arguments
+/// * `record` - The record that was received
+/// * `context` - The optional user context passed through
+///
+/// [`RecordQuery`]: type.RecordQuery.html
+pub type RecordReceivedCallback = dyn Fn(Result<Record>, Option<Arc<dyn Any + Send + Sync>>) + Send;
+
+/// Represents a DNS record that has been received by a [`RecordQuery`].
+///
+/// [`RecordQuery`]: type.RecordQuery.html
+#[derive(
+    Debug, Getters, Builder, BuilderDelegate, Serialize, Deserialize, Clone, PartialEq, Eq,
+)]
+pub struct Record {
+    name: String,
+    rrtype: u16,
+    rrclass: u16,
+    rdata: Vec<u8>,
+    /// The time to live (in seconds) of the record (Bonjour only, `0` on Avahi)
+    #[builder(default)]
+    ttl: u32,
+    /// The DNSSEC validation status of the record, if validation was requested with
+    /// `ServiceFlags::VALIDATE`
+    #[builder(default)]
+    dnssec_status: Option<DnssecStatus>,
+}
+
+impl Record {
+    /// Returns a view of the entries of this record, borrowed from its RDATA, or `None` if it is
+    /// not a `TXT` record.
+    pub fn txt(&self) -> Option<TxtRecordView<'_>> {
+        if self.rrtype == dns::TYPE_TXT {
+            Some(TxtRecordView::new(&self.rdata))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,21 @@
+//! Trait definition for cross-platform registration handle
+
+use crate::prelude::*;
+use crate::Result;
+
+/// A handle on a registered `MdnsService` that allows changing how the service is advertised
+/// while it is running.
+///
+/// Changes are applied by transparently re-registering the service, the
+/// [`ServiceRegisteredCallback`] is invoked again once the new registration has been established.
+/// The handle also polls the underlying event loop and must be polled to keep the service alive.
+///
+/// [`ServiceRegisteredCallback`]: ../type.ServiceRegisteredCallback.html
+pub trait TRegistrationHandle: TEventLoop {
+    /// Changes the name the service is advertised under.
+    fn set_name(&mut self, name: &str) -> Result<()>;
+
+    /// Changes the port the service is advertised on, e.g. after the application re-binds its
+    /// listener.
+    fn set_port(&mut self, port: u16) -> Result<()>;
+}
@@ -0,0 +1,152 @@
+//! Utilities for bounding the number of services a browser resolves at once
+
+use std::collections::VecDeque;
+
+/// The number of services a browser resolves at once unless set with
+/// `TMdnsBrowser::set_resolve_concurrency()`
+pub(crate) const DEFAULT_RESOLVE_CONCURRENCY: usize = 16;
+
+/// Queues the services discovered by a browser until one of its resolves has finished, so that a
+/// burst of announcements (e.g. on a network with hundreds of instances) does not start as many
+/// resolves on the mDNS daemon at once.
+///
+/// Services are resolved in the order they were discovered, a service that is discovered again
+/// while it is queued keeps its place.
+#[derive(Debug)]
+pub(crate) struct ResolveQueue<T> {
+    concurrency: usize,
+    active: usize,
+    pending: VecDeque<T>,
+}
+
+impl<T: PartialEq> ResolveQueue<T> {
+    /// Creates a new `ResolveQueue` that lets `DEFAULT_RESOLVE_CONCURRENCY` resolves run at once.
+    pub fn new() -> Self {
+        Self {
+            concurrency: DEFAULT_RESOLVE_CONCURRENCY,
+            active: 0,
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Sets the number of resolves that may run at once, at least one.
+    pub fn set_concurrency(&mut self, concurrency: usize) {
+        self.concurrency = concurrency.max(1);
+    }
+
+    /// Returns `service` if it can be resolved now, in which case it counts towards the running
+    /// resolves until `finish()` is called, otherwise queues it.
+    // the other backends only copy the services that have to be queued
+    #[cfg_attr(
+        not(any(feature = "avahi-dbus", target_os = "windows")),
+        allow(dead_code)
+    )]
+    pub fn push(&mut self, service: T) -> Option<T> {
+        if self.try_start() {
+            return Some(service);
+        }
+
+        self.queue(service);
+
+        None
+    }
+
+    /// Returns true if a service can be resolved now, in which case its resolve counts towards
+    /// the running resolves until `finish()` is called. Otherwise, the service is to be queued
+    /// with `queue()`.
+    pub fn try_start(&mut self) -> bool {
+        if self.active < self.concurrency {
+            self.active += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Queues `service` until one of the running resolves has finished, unless it is already
+    /// queued.
+    pub fn queue(&mut self, service: T) {
+        if !self.pending.contains(&service) {
+            self.pending.push_back(service);
+        }
+    }
+
+    /// Marks a resolve as finished, returning the next queued service to resolve, if any, which
+    /// takes its place.
+    pub fn finish(&mut self) -> Option<T> {
+        match self.pending.pop_front() {
+            Some(s) => Some(s),
+            None => {
+                // resolves started outside of the queue (e.g. by a resolver) are not counted
+                self.active = self.active.saturating_sub(1);
+                None
+            }
+        }
+    }
+
+    /// Drops the queued services and forgets about the running resolves, e.g. after their
+    /// resolvers were freed.
+    // only the backends that reconnect to the daemon free their resolvers while browsing
+    #[cfg_attr(any(feature = "avahi-core", target_os = "windows"), allow(dead_code))]
+    pub fn clear(&mut self) {
+        self.active = 0;
+        self.pending.clear();
+    }
+}
+
+impl<T: PartialEq> Default for ResolveQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolves_are_bounded_and_dequeued_in_order() {
+        let mut queue = ResolveQueue::new();
+        queue.set_concurrency(2);
+
+        assert_eq!(queue.push("a"), Some("a"));
+        assert_eq!(queue.push("b"), Some("b"));
+        assert_eq!(queue.push("c"), None);
+        assert_eq!(queue.push("d"), None);
+        assert_eq!(queue.push("c"), None);
+
+        assert_eq!(queue.finish(), Some("c"));
+        assert_eq!(queue.finish(), Some("d"));
+        assert_eq!(queue.finish(), None);
+        assert_eq!(queue.finish(), None);
+
+        assert_eq!(queue.push("e"), Some("e"));
+        assert_eq!(queue.push("f"), Some("f"));
+        assert_eq!(queue.push("g"), None);
+    }
+
+    #[test]
+    fn concurrency_is_at_least_one() {
+        let mut queue = ResolveQueue::new();
+        queue.set_concurrency(0);
+
+        assert_eq!(queue.push("a"), Some("a"));
+        assert_eq!(queue.push("b"), None);
+    }
+
+    #[test]
+    fn services_are_queued_once_no_resolve_can_start() {
+        let mut queue = ResolveQueue::new();
+        queue.set_concurrency(1);
+
+        assert!(queue.try_start());
+        assert!(!queue.try_start());
+
+        queue.queue("a");
+        queue.queue("a");
+
+        assert_eq!(queue.finish(), Some("a"));
+        assert_eq!(queue.finish(), None);
+        assert!(queue.try_start());
+    }
+}
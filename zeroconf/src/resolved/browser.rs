@@ -0,0 +1,211 @@
+//! systemd-resolved implementation for cross-platform browser
+
+use super::client::ResolvedClient;
+use super::resolved_util::{self, LOCAL_DOMAIN};
+use crate::prelude::*;
+use crate::{
+    AddressFamily, AddressPolicy, BrowserControl, DaemonReconnectedCallback, ErrorCallback,
+    EventLoop, NetworkInterface, Result, ServiceDiscoveredCallback, ServiceDiscovery, ServiceFlags,
+    ServiceType,
+};
+use std::any::Any;
+use std::fmt::{self, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub struct ResolvedMdnsBrowser {
+    client: Option<ResolvedClient>,
+    /// The names of the service instances that have been reported and were still announced
+    /// when the network was last queried
+    discovered: Vec<String>,
+    service_type: ServiceType,
+    interface_index: i32,
+    address_family: AddressFamily,
+    service_discovered_callback: Option<Box<ServiceDiscoveredCallback>>,
+    error_callback: Option<Box<ErrorCallback>>,
+    control: BrowserControl,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl TMdnsBrowser for ResolvedMdnsBrowser {
+    fn new(service_type: ServiceType) -> Self {
+        Self {
+            client: None,
+            discovered: vec![],
+            service_type,
+            interface_index: 0,
+            address_family: AddressFamily::default(),
+            service_discovered_callback: None,
+            error_callback: None,
+            control: BrowserControl::default(),
+            user_context: None,
+        }
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface_index = resolved_util::interface_index(interface);
+    }
+
+    /// systemd-resolved only browses services on the `local` domain, it is ignored.
+    fn set_domain(&mut self, _domain: &str) {}
+
+    fn set_address_family(&mut self, address_family: AddressFamily) {
+        self.address_family = address_family;
+    }
+
+    /// The addresses of discovered services are reported in the order of systemd-resolved, it is
+    /// ignored.
+    fn set_address_policy(&mut self, _address_policy: AddressPolicy) {}
+
+    /// systemd-resolved does not support any flags, they are ignored.
+    fn set_flags(&mut self, _flags: ServiceFlags) {}
+
+    /// Raw records are not available from systemd-resolved, `ServiceDiscovery::raw_records()` is
+    /// always empty.
+    fn set_include_raw_records(&mut self, _include_raw_records: bool) {}
+
+    fn set_resolve_concurrency(&mut self, _concurrency: usize) {
+        // services are resolved one at a time while querying them
+    }
+
+    fn set_debounce(&mut self, _window: Duration) {
+        // systemd-resolved does not report services that have gone away
+    }
+
+    fn set_service_discovered_callback(
+        &mut self,
+        service_discovered_callback: Box<ServiceDiscoveredCallback>,
+    ) {
+        self.service_discovered_callback = Some(service_discovered_callback);
+    }
+
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>) {
+        self.error_callback = Some(error_callback);
+    }
+
+    fn control(&self) -> BrowserControl {
+        self.control.clone()
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        self.user_context = Some(Arc::from(context));
+    }
+
+    /// The network is queried through systemd-resolved on every poll, so the callback is never
+    /// invoked.
+    fn set_reconnected_callback(&mut self, _reconnected_callback: Box<DaemonReconnectedCallback>) {}
+
+    fn browse_services(&mut self) -> Result<EventLoop<'_>> {
+        let _span = operation_span!("browse", service_type = self.service_type.to_string());
+
+        debug!("Browsing services: {:?}", self);
+
+        self.client = Some(ResolvedClient::new()?);
+
+        let browser: *mut Self = self;
+
+        Ok(EventLoop::new(
+            *crate::poll_intervals().query_interval(),
+            Box::new(move || unsafe { (*browser).handle_poll() }),
+        ))
+    }
+}
+
+impl ResolvedMdnsBrowser {
+    fn kind(&self) -> String {
+        format!(
+            "_{}._{}",
+            self.service_type.name(),
+            self.service_type.protocol()
+        )
+    }
+
+    /// Returns the name to query the PTR records of, which is the first sub-type if any.
+    fn browse_name(&self) -> String {
+        match self.service_type.sub_types().first() {
+            Some(sub_type) => format!("_{}._sub.{}.{}", sub_type, self.kind(), LOCAL_DOMAIN),
+            None => format!("{}.{}", self.kind(), LOCAL_DOMAIN),
+        }
+    }
+
+    fn client(&self) -> Result<&ResolvedClient> {
+        self.client
+            .as_ref()
+            .ok_or_else(|| "browser has not been started".into())
+    }
+
+    /// Queries the services announced on the network and reports the ones that have not been
+    /// reported yet.
+    fn handle_poll(&mut self) {
+        if let Err(e) = self.query_services() {
+            self.invoke_callback(Err(e));
+        }
+    }
+
+    fn query_services(&mut self) -> Result<()> {
+        let client = self.client()?.clone();
+
+        let names: Vec<String> = client
+            .query_ptr(
+                self.interface_index,
+                &self.browse_name(),
+                self.address_family,
+            )?
+            .iter()
+            .filter_map(|rdata| resolved_util::instance_name(rdata))
+            .collect();
+
+        // services that went away are reported again once they are back
+        self.discovered.retain(|n| names.contains(n));
+
+        for name in names {
+            if self.discovered.contains(&name) {
+                continue;
+            }
+
+            self.discovered.push(name.clone());
+
+            let result = client.resolve_service(
+                self.interface_index,
+                &name,
+                &self.service_type,
+                LOCAL_DOMAIN,
+                self.address_family,
+            );
+
+            if let Ok(service) = &result {
+                debug!("Service discovered: {:?}", service);
+            }
+
+            self.invoke_callback(result);
+        }
+
+        Ok(())
+    }
+
+    fn invoke_callback(&self, result: Result<ServiceDiscovery>) {
+        if !self.control.is_running() {
+            return;
+        }
+
+        match (
+            result,
+            &self.error_callback,
+            &self.service_discovered_callback,
+        ) {
+            (Err(e), Some(f), _) => f(e, self.user_context.clone()),
+            (result, _, Some(f)) => f(result, self.user_context.clone()),
+            _ => warn!("attempted to invoke callback but none was set"),
+        }
+    }
+}
+
+impl fmt::Debug for ResolvedMdnsBrowser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResolvedMdnsBrowser")
+            .field("service_type", &self.service_type)
+            .field("interface_index", &self.interface_index)
+            .field("address_family", &self.address_family)
+            .finish()
+    }
+}
@@ -0,0 +1,268 @@
+//! Connection to systemd-resolved over the system bus
+
+use super::resolved_util::{
+    self, ERROR_NO_SUCH_RECORD, ERROR_TIMEOUT, MANAGER_INTERFACE, MANAGER_PATH, RESOLVED_SERVICE,
+};
+use crate::error::{Error, ErrorKind, Operation};
+use crate::prelude::*;
+use crate::txt_record;
+use crate::{dns, ffi, AddressFamily, Result, ServiceDiscovery, ServiceType};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::convert::TryFrom;
+use std::net::IpAddr;
+use std::sync::Arc;
+use zbus::blocking::Connection;
+use zbus::zvariant::{DynamicType, OwnedValue, Type};
+use zbus::Message;
+
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+
+/// The error replied by the bus if systemd-resolved is not connected to it
+const ERROR_SERVICE_UNKNOWN: &str = "org.freedesktop.DBus.Error.ServiceUnknown";
+/// The error replied by the bus if the caller is not allowed to call a method
+const ERROR_ACCESS_DENIED: &str = "org.freedesktop.DBus.Error.AccessDenied";
+
+const SYSTEMD_SERVICE: &str = "org.freedesktop.systemd1";
+const SYSTEMD_PATH: &str = "/org/freedesktop/systemd1";
+const SYSTEMD_MANAGER_INTERFACE: &str = "org.freedesktop.systemd1.Manager";
+
+/// A resource record reported by `ResolveRecord`: the interface index, class, type and RDATA
+type ResolvedRecord = (i32, u16, u16, Vec<u8>);
+
+/// An address reported by `ResolveService`: the interface index, family and raw address
+type ResolvedAddress = (i32, i32, Vec<u8>);
+
+/// A SRV record reported by `ResolveService`: the priority, weight, port, host name, addresses of
+/// the host and canonical host name
+type ResolvedSrv = (u16, u16, u16, String, Vec<ResolvedAddress>, String);
+
+/// The reply to `ResolveService`: the SRV records, TXT strings, canonical name, type and domain
+/// of the service and flags
+type ResolvedService = (Vec<ResolvedSrv>, Vec<Vec<u8>>, String, String, String, u64);
+
+/// A connection to the system bus that calls methods of systemd-resolved.
+#[derive(Debug, Clone)]
+pub struct ResolvedClient {
+    connection: Connection,
+}
+
+impl ResolvedClient {
+    /// Connects to the system bus.
+    pub fn new() -> Result<Self> {
+        let connection = Connection::system()
+            .map_err(|e| format!("could not connect to the system bus: {}", e))?;
+
+        Ok(Self { connection })
+    }
+
+    /// Calls `method` of the `org.freedesktop.resolve1.Manager` interface and returns the body
+    /// of the reply.
+    pub fn call<B, R>(&self, method: &str, body: &B) -> Result<R>
+    where
+        B: Serialize + DynamicType,
+        R: DeserializeOwned + Type,
+    {
+        self.try_call(method, body)
+            .map_err(|e| call_error(method, e))?
+            .body()
+            .map_err(|e| {
+                format!(
+                    "unexpected reply to {}.{}: {}",
+                    MANAGER_INTERFACE, method, e
+                )
+                .into()
+            })
+    }
+
+    /// Calls `method` of the `org.freedesktop.resolve1.Manager` interface and returns the reply or
+    /// the error of the call, e.g. to handle the errors replied by systemd-resolved.
+    fn try_call<B>(&self, method: &str, body: &B) -> zbus::Result<Arc<Message>>
+    where
+        B: Serialize + DynamicType,
+    {
+        self.connection.call_method(
+            Some(RESOLVED_SERVICE),
+            MANAGER_PATH,
+            Some(MANAGER_INTERFACE),
+            method,
+            body,
+        )
+    }
+
+    /// Returns the value of the string property `name` of `interface` on the object at `path` of
+    /// the service `destination`.
+    fn string_property(
+        &self,
+        destination: &str,
+        path: &str,
+        interface: &str,
+        name: &str,
+    ) -> Result<String> {
+        let reply = self
+            .connection
+            .call_method(
+                Some(destination),
+                path,
+                Some(PROPERTIES_INTERFACE),
+                "Get",
+                &(interface, name),
+            )
+            .map_err(|e| format!("could not get {}.{}: {}", interface, name, e))?;
+
+        let value: OwnedValue = reply
+            .body()
+            .map_err(|e| format!("unexpected value of {}.{}: {}", interface, name, e))?;
+
+        String::try_from(value)
+            .map_err(|e| format!("unexpected value of {}.{}: {}", interface, name, e).into())
+    }
+
+    /// Returns the global mDNS support of systemd-resolved, i.e. `yes`, `resolve` or `no`.
+    pub fn multicast_dns(&self) -> Result<String> {
+        self.string_property(
+            RESOLVED_SERVICE,
+            MANAGER_PATH,
+            MANAGER_INTERFACE,
+            "MulticastDNS",
+        )
+    }
+
+    /// Returns the RDATA of the PTR records of `name` announced on the network, which is empty if
+    /// no host answered.
+    pub fn query_ptr(
+        &self,
+        interface_index: i32,
+        name: &str,
+        address_family: AddressFamily,
+    ) -> Result<Vec<Vec<u8>>> {
+        let body = (
+            interface_index,
+            name,
+            dns::CLASS_IN,
+            dns::TYPE_PTR,
+            resolved_util::lookup_flags(address_family),
+        );
+
+        let reply = match self.try_call("ResolveRecord", &body) {
+            Ok(r) => r,
+            Err(zbus::Error::MethodError(error, _, _))
+                if error.as_str() == ERROR_NO_SUCH_RECORD || error.as_str() == ERROR_TIMEOUT =>
+            {
+                return Ok(vec![])
+            }
+            Err(e) => return Err(call_error("ResolveRecord", e).with_operation(Operation::Browse)),
+        };
+
+        let (records, _): (Vec<ResolvedRecord>, u64) = reply
+            .body()
+            .map_err(|e| format!("unexpected reply to ResolveRecord: {}", e))?;
+
+        Ok(records
+            .into_iter()
+            .filter(|(_, _, kind, _)| *kind == dns::TYPE_PTR)
+            .map(|(_, _, _, rdata)| rdata)
+            .collect())
+    }
+
+    /// Resolves the service instance `name` of the specified `service_type` in `domain`.
+    pub fn resolve_service(
+        &self,
+        interface_index: i32,
+        name: &str,
+        service_type: &ServiceType,
+        domain: &str,
+        address_family: AddressFamily,
+    ) -> Result<ServiceDiscovery> {
+        let kind = format!("_{}._{}", service_type.name(), service_type.protocol());
+
+        let (srv, txt, name, _, domain, _): ResolvedService = self.call(
+            "ResolveService",
+            &(
+                interface_index,
+                name,
+                kind,
+                domain,
+                resolved_util::family(address_family),
+                resolved_util::lookup_flags(address_family),
+            ),
+        )?;
+
+        let (priority, weight, port, host_name, addresses, _) = srv
+            .into_iter()
+            .next()
+            .ok_or_else(|| format!("service `{}` has no SRV record", name))?;
+
+        let interface = addresses.first().map(|(i, _, _)| *i).unwrap_or_default();
+
+        let addresses: Vec<IpAddr> = addresses
+            .iter()
+            .filter_map(|(_, family, address)| resolved_util::parse_address(*family, address))
+            .collect();
+
+        let address = *addresses
+            .first()
+            .ok_or_else(|| format!("could not resolve the address of service `{}`", name))?;
+
+        let txt = txt_record::from_entries(resolved_util::txt_from_bytes(&txt))?;
+        let txt = if txt.is_empty() { None } else { Some(txt) };
+
+        Ok(ServiceDiscovery::builder()
+            .name(name)
+            .service_type(service_type.clone())
+            .domain(domain)
+            .host_name(host_name)
+            .address(address)
+            .addresses(addresses)
+            .scope_id(ffi::link_local_scope_id(&address, interface as u32))
+            .port(port)
+            .priority(priority)
+            .weight(weight)
+            .txt(txt)
+            .build()?)
+    }
+}
+
+/// Returns the error of a failed call of `method`, which is of kind `ErrorKind::DaemonNotRunning`
+/// if systemd-resolved is not connected to the system bus.
+fn call_error(method: &str, error: zbus::Error) -> Error {
+    let kind = match &error {
+        zbus::Error::MethodError(name, _, _) => match name.as_str() {
+            ERROR_SERVICE_UNKNOWN => ErrorKind::DaemonNotRunning,
+            ERROR_ACCESS_DENIED => ErrorKind::PermissionDenied,
+            ERROR_TIMEOUT => ErrorKind::Timeout,
+            ERROR_NO_SUCH_RECORD => ErrorKind::NoSuchRecord,
+            _ => ErrorKind::Other,
+        },
+        _ => ErrorKind::Other,
+    };
+
+    Error::with_kind(
+        kind,
+        format!("could not call {}.{}: {}", MANAGER_INTERFACE, method, error),
+    )
+}
+
+/// Returns true if systemd-resolved is reachable over the system bus and mDNS is enabled.
+pub fn is_daemon_running() -> bool {
+    daemon_version().is_ok()
+}
+
+/// Connects to systemd-resolved over the system bus, checks that mDNS is enabled and returns the
+/// version of systemd (e.g. `systemd 252`).
+pub fn daemon_version() -> Result<String> {
+    let client = ResolvedClient::new()?;
+
+    if client.multicast_dns()? == "no" {
+        return Err("mDNS is disabled in systemd-resolved (`MulticastDNS=no`)".into());
+    }
+
+    let version = client.string_property(
+        SYSTEMD_SERVICE,
+        SYSTEMD_PATH,
+        SYSTEMD_MANAGER_INTERFACE,
+        "Version",
+    )?;
+
+    Ok(format!("systemd {}", version))
+}
@@ -0,0 +1,42 @@
+//! Event loop for running a `MdnsService` or `MdnsBrowser`.
+
+use crate::event_loop::TEventLoop;
+use crate::Result;
+use std::cell::Cell;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(new)]
+pub struct ResolvedEventLoop<'a> {
+    /// Minimum interval between two invocations of `on_poll`
+    interval: Duration,
+    on_poll: Box<dyn Fn() + 'a>,
+    #[new(default)]
+    last_poll: Cell<Option<Instant>>,
+}
+
+impl<'a> TEventLoop for ResolvedEventLoop<'a> {
+    /// Polls for new events.
+    ///
+    /// systemd-resolved does not report events on its own, so the services or the network are
+    /// queried at most once per interval. Waits up to the specified timeout for the next query to
+    /// be due.
+    fn poll(&self, timeout: Duration) -> Result<()> {
+        let remaining = match self.last_poll.get() {
+            Some(t) => self.interval.saturating_sub(t.elapsed()),
+            None => Duration::from_secs(0),
+        };
+
+        if remaining > timeout {
+            thread::sleep(timeout);
+            return Ok(());
+        }
+
+        thread::sleep(remaining);
+
+        self.last_poll.set(Some(Instant::now()));
+        (self.on_poll)();
+
+        Ok(())
+    }
+}
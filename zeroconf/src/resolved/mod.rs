@@ -0,0 +1,29 @@
+//! Linux-specific ZeroConf bindings over the D-Bus API of systemd-resolved
+//!
+//! This module talks to [systemd-resolved] over its [D-Bus API] for distributions that run it
+//! instead of `avahi-daemon`. It is enabled with the `systemd-resolved` feature instead of the
+//! `avahi` feature, and requires `MulticastDNS=yes` in `resolved.conf` (and on the links services
+//! are announced on).
+//!
+//! # Note
+//! Only registering and browsing services on the `local` domain is supported. systemd-resolved
+//! does not report when services appear, so browsers query the announced services periodically,
+//! and registering services requires the `org.freedesktop.resolve1.register-service` polkit
+//! privilege (granted to root by default).
+//!
+//! Services are announced on all links with mDNS enabled under the host name of systemd-resolved,
+//! without sub-types, and name conflicts are resolved by systemd-resolved. The resolvers, record
+//! queries and `ServiceDiscovery::raw_records()` are not available.
+//!
+//! [systemd-resolved]: https://www.freedesktop.org/software/systemd/man/systemd-resolved.service.html
+//! [D-Bus API]: https://www.freedesktop.org/software/systemd/man/org.freedesktop.resolve1.html
+
+#[path = "../embedded/txt_record.rs"]
+pub mod txt_record;
+
+pub mod browser;
+pub mod client;
+pub mod event_loop;
+pub mod registration_handle;
+pub mod resolved_util;
+pub mod service;
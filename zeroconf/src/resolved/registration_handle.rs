@@ -0,0 +1,39 @@
+//! systemd-resolved implementation for cross-platform registration handle
+
+use super::event_loop::ResolvedEventLoop;
+use super::service::ResolvedMdnsService;
+use crate::prelude::*;
+use crate::registration_handle::TRegistrationHandle;
+use crate::service;
+use crate::Result;
+use std::time::Duration;
+
+#[derive(new)]
+pub struct ResolvedRegistrationHandle<'a> {
+    service: *mut ResolvedMdnsService,
+    event_loop: ResolvedEventLoop<'a>,
+}
+
+impl<'a> TEventLoop for ResolvedRegistrationHandle<'a> {
+    fn poll(&self, timeout: Duration) -> Result<()> {
+        self.event_loop.poll(timeout)
+    }
+}
+
+impl<'a> TRegistrationHandle for ResolvedRegistrationHandle<'a> {
+    fn set_name(&mut self, name: &str) -> Result<()> {
+        unsafe {
+            (*self.service).set_name(name);
+            (*self.service).reregister()
+        }
+    }
+
+    fn set_port(&mut self, port: u16) -> Result<()> {
+        service::check_port(port)?;
+
+        unsafe {
+            (*self.service).set_port(port);
+            (*self.service).reregister()
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! Utilities related to the D-Bus API of systemd-resolved
+
+use crate::txt_record;
+use crate::{AddressFamily, NetworkInterface};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// The well-known bus name of systemd-resolved
+pub const RESOLVED_SERVICE: &str = "org.freedesktop.resolve1";
+pub const MANAGER_PATH: &str = "/org/freedesktop/resolve1";
+pub const MANAGER_INTERFACE: &str = "org.freedesktop.resolve1.Manager";
+
+/// The error replied by systemd-resolved if no records of the queried name and type exist
+pub const ERROR_NO_SUCH_RECORD: &str = "org.freedesktop.resolve1.NoSuchResourceRecord";
+/// The error replied by systemd-resolved if no host answered a query
+pub const ERROR_TIMEOUT: &str = "org.freedesktop.DBus.Error.Timeout";
+
+/// The only domain supported by the mDNS implementation of systemd-resolved
+pub const LOCAL_DOMAIN: &str = "local";
+
+/// `AF_UNSPEC`
+pub const AF_UNSPEC: i32 = 0;
+/// `AF_INET`
+pub const AF_INET: i32 = 2;
+/// `AF_INET6`
+pub const AF_INET6: i32 = 10;
+
+/// `SD_RESOLVED_MDNS_IPV4`
+const RESOLVED_MDNS_IPV4: u64 = 1 << 3;
+/// `SD_RESOLVED_MDNS_IPV6`
+const RESOLVED_MDNS_IPV6: u64 = 1 << 4;
+
+/// Converts the specified [`NetworkInterface`] to an interface index, `0` meaning all interfaces.
+/// Peer-to-peer interfaces are not supported and are treated as `Unspec`.
+///
+/// [`NetworkInterface`]: ../../enum.NetworkInterface.html
+pub fn interface_index(interface: NetworkInterface) -> i32 {
+    match interface {
+        NetworkInterface::AtIndex(i) => i as i32,
+        NetworkInterface::Unspec | NetworkInterface::PeerToPeer => 0,
+    }
+}
+
+/// Converts the specified [`AddressFamily`] to the address family expected by systemd-resolved.
+///
+/// [`AddressFamily`]: ../../enum.AddressFamily.html
+pub fn family(address_family: AddressFamily) -> i32 {
+    match address_family {
+        AddressFamily::Ipv4 => AF_INET,
+        AddressFamily::Ipv6 => AF_INET6,
+        AddressFamily::Both => AF_UNSPEC,
+    }
+}
+
+/// Returns the flags restricting the lookups of systemd-resolved to mDNS over the specified
+/// [`AddressFamily`].
+///
+/// [`AddressFamily`]: ../../enum.AddressFamily.html
+pub fn lookup_flags(address_family: AddressFamily) -> u64 {
+    match address_family {
+        AddressFamily::Ipv4 => RESOLVED_MDNS_IPV4,
+        AddressFamily::Ipv6 => RESOLVED_MDNS_IPV6,
+        AddressFamily::Both => RESOLVED_MDNS_IPV4 | RESOLVED_MDNS_IPV6,
+    }
+}
+
+/// Returns the unescaped name of the service instance the specified RDATA of a PTR record points
+/// to, which is its first label.
+///
+/// Returns `None` if `rdata` does not start with a valid label.
+pub fn instance_name(rdata: &[u8]) -> Option<String> {
+    let len = *rdata.first()? as usize;
+
+    if len == 0 || len > 63 {
+        return None;
+    }
+
+    let label = rdata.get(1..1 + len)?;
+
+    Some(String::from_utf8_lossy(label).into_owned())
+}
+
+/// Parses the address of the specified family reported by systemd-resolved as raw bytes.
+pub fn parse_address(family: i32, address: &[u8]) -> Option<IpAddr> {
+    match family {
+        AF_INET if address.len() == 4 => Some(IpAddr::V4(Ipv4Addr::new(
+            address[0], address[1], address[2], address[3],
+        ))),
+        AF_INET6 if address.len() == 16 => {
+            let mut octets = [0; 16];
+            octets.copy_from_slice(address);
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        }
+        _ => None,
+    }
+}
+
+/// Encodes the specified `key=value` entries as the TXT record data expected by systemd-resolved,
+/// which registers a TXT record for each map of the returned array.
+pub fn txt_to_maps(
+    entries: impl IntoIterator<Item = (String, String)>,
+) -> Vec<HashMap<String, Vec<u8>>> {
+    let map: HashMap<_, _> = entries
+        .into_iter()
+        .map(|(key, value)| (key, value.into_bytes()))
+        .collect();
+
+    if map.is_empty() {
+        vec![]
+    } else {
+        vec![map]
+    }
+}
+
+/// Decodes the TXT record strings reported by systemd-resolved into `key=value` entries.
+/// Entries without a `'='` have an empty value, empty entries and entries without a key are
+/// skipped. The entries borrow from `txt` unless they are not valid UTF-8.
+pub fn txt_from_bytes(txt: &[Vec<u8>]) -> impl Iterator<Item = (Cow<'_, str>, Cow<'_, str>)> {
+    txt.iter().filter_map(|e| txt_record::parse_entry(e))
+}
+
+/// Escapes the specified service name for use as the name template of a service, in which
+/// systemd-resolved expands `%` specifiers (e.g. `%H` for the host name).
+pub fn escape_name_template(name: &str) -> String {
+    name.replace('%', "%%")
+}
+
+/// Returns the host name of this computer without any domain, which systemd-resolved registers
+/// services under by default.
+pub fn host_name() -> Option<String> {
+    let mut buf = [0 as libc::c_char; 256];
+
+    let name = unsafe {
+        if libc::gethostname(buf.as_mut_ptr(), buf.len()) != 0 {
+            return None;
+        }
+
+        // the name is not guaranteed to be null-terminated if it was truncated
+        buf[buf.len() - 1] = 0;
+        CStr::from_ptr(buf.as_ptr()).to_string_lossy().into_owned()
+    };
+
+    name.split('.')
+        .next()
+        .filter(|n| !n.is_empty())
+        .map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn instance_name_is_first_label() {
+        let rdata = crate::dns::encode_labels(vec!["My.Printer", "_ipp", "_tcp", "local"]);
+        assert_eq!(instance_name(&rdata), Some("My.Printer".to_string()));
+        assert_eq!(instance_name(&[0]), None);
+    }
+
+    #[test]
+    fn parse_address_checks_length() {
+        assert_eq!(
+            parse_address(AF_INET, &[192, 168, 1, 2]),
+            Some("192.168.1.2".parse().unwrap())
+        );
+        assert_eq!(parse_address(AF_INET6, &[192, 168, 1, 2]), None);
+    }
+
+    #[test]
+    fn escape_name_template_escapes_specifiers() {
+        assert_eq!(escape_name_template("100% %H"), "100%% %%H");
+    }
+}
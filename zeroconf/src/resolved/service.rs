@@ -0,0 +1,262 @@
+//! systemd-resolved implementation for cross-platform service.
+
+use super::client::ResolvedClient;
+use super::event_loop::ResolvedEventLoop;
+use super::registration_handle::ResolvedRegistrationHandle;
+use super::resolved_util::{self, LOCAL_DOMAIN};
+use crate::network_watcher::NetworkWatcher;
+use crate::prelude::*;
+use crate::service;
+use crate::{
+    DaemonReconnectedCallback, ErrorCallback, NameConflictCallback, NetworkInterface,
+    RegistrationHandle, Result, ServiceFlags, ServiceRegisteredCallback, ServiceRegistration,
+    ServiceType, TxtRecord,
+};
+use std::any::Any;
+use std::fmt::{self, Formatter};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use zbus::zvariant::OwnedObjectPath;
+
+/// The number of services registered by this process, used to derive unique service IDs
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+pub struct ResolvedMdnsService {
+    client: Option<ResolvedClient>,
+    /// The object path of the registered service, once registered
+    path: Option<OwnedObjectPath>,
+    /// Whether the registration has not been reported to the callback yet
+    pending: bool,
+    service_type: ServiceType,
+    port: u16,
+    name: Option<String>,
+    txt_record: Option<TxtRecord>,
+    auto_reregister: bool,
+    network_watcher: Option<NetworkWatcher>,
+    registered_callback: Option<Box<ServiceRegisteredCallback>>,
+    error_callback: Option<Box<ErrorCallback>>,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl TMdnsService for ResolvedMdnsService {
+    fn new(service_type: ServiceType, port: u16) -> Self {
+        Self {
+            client: None,
+            path: None,
+            pending: false,
+            service_type,
+            port,
+            name: None,
+            txt_record: None,
+            auto_reregister: false,
+            network_watcher: None,
+            registered_callback: None,
+            error_callback: None,
+            user_context: None,
+        }
+    }
+
+    /// Sets the name to register this service under. If no name is set, the host name is used.
+    fn set_name(&mut self, name: &str) {
+        self.name = Some(name.to_string());
+    }
+
+    /// systemd-resolved announces services on all links with mDNS enabled, it is ignored.
+    fn set_network_interface(&mut self, _interface: NetworkInterface) {}
+
+    /// systemd-resolved only announces services on the `local` domain, it is ignored.
+    fn set_domain(&mut self, _domain: &str) {}
+
+    /// systemd-resolved announces services under its own host name, it is ignored.
+    fn set_host(&mut self, _host: &str) {}
+
+    fn set_txt_record(&mut self, txt_record: TxtRecord) {
+        self.txt_record = Some(txt_record);
+    }
+
+    fn set_registered_callback(&mut self, registered_callback: Box<ServiceRegisteredCallback>) {
+        self.registered_callback = Some(registered_callback);
+    }
+
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>) {
+        self.error_callback = Some(error_callback);
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        self.user_context = Some(Arc::from(context));
+    }
+
+    /// systemd-resolved does not support any flags, they are ignored.
+    fn set_flags(&mut self, _flags: ServiceFlags) {}
+
+    /// systemd-resolved announces its records with a fixed TTL, it is ignored.
+    fn set_ttl(&mut self, _ttl: u32) {}
+
+    fn set_auto_reregister(&mut self, auto_reregister: bool) {
+        self.auto_reregister = auto_reregister;
+    }
+
+    /// The services registered with systemd-resolved are not reconnected, so the callback is
+    /// never invoked.
+    fn set_reconnected_callback(&mut self, _reconnected_callback: Box<DaemonReconnectedCallback>) {}
+
+    /// systemd-resolved resolves name conflicts on its own, so the callback is never invoked.
+    fn set_name_conflict_callback(&mut self, _name_conflict_callback: Box<NameConflictCallback>) {}
+
+    fn register(&mut self) -> Result<RegistrationHandle<'_>> {
+        let _span = operation_span!("register", service_type = self.service_type.to_string());
+
+        debug!("Registering service: {:?}", self);
+
+        service::check_port(self.port)?;
+
+        self.client = Some(ResolvedClient::new()?);
+        self.create_service()?;
+
+        if self.auto_reregister {
+            self.network_watcher = Some(NetworkWatcher::new());
+        }
+
+        let service: *mut Self = self;
+
+        let event_loop = ResolvedEventLoop::new(
+            *crate::poll_intervals().query_interval(),
+            Box::new(move || unsafe { (*service).handle_poll() }),
+        );
+
+        Ok(ResolvedRegistrationHandle::new(service, event_loop))
+    }
+
+    fn close(mut self) -> Result<()> {
+        let result = self.remove_service();
+        self.client = None;
+        result
+    }
+}
+
+impl ResolvedMdnsService {
+    fn client(&self) -> Result<&ResolvedClient> {
+        self.client
+            .as_ref()
+            .ok_or_else(|| "service has not been registered".into())
+    }
+
+    fn create_service(&mut self) -> Result<()> {
+        let name_template = match &self.name {
+            Some(n) => resolved_util::escape_name_template(n),
+            None => "%H".to_string(),
+        };
+
+        let id = format!(
+            "zeroconf-{}-{}",
+            process::id(),
+            NEXT_ID.fetch_add(1, Ordering::Relaxed)
+        );
+
+        let kind = format!(
+            "_{}._{}",
+            self.service_type.name(),
+            self.service_type.protocol()
+        );
+
+        let txt = resolved_util::txt_to_maps(self.txt_record.iter().flat_map(|t| t.iter()));
+
+        debug!("Adding service: {}", id);
+
+        let path: OwnedObjectPath = self.client()?.call(
+            "RegisterService",
+            &(id, name_template, kind, self.port, 0u16, 0u16, txt),
+        )?;
+
+        self.path = Some(path);
+        self.pending = true;
+
+        Ok(())
+    }
+
+    fn remove_service(&mut self) -> Result<()> {
+        match self.path.take() {
+            Some(path) => self.client()?.call("UnregisterService", &(path,)),
+            None => Ok(()),
+        }
+    }
+
+    /// Reports the registration once the service has been registered, and re-registers the
+    /// service if the network configuration has changed.
+    fn handle_poll(&mut self) {
+        let changed = match &mut self.network_watcher {
+            Some(w) => w.poll_changed(),
+            None => false,
+        };
+
+        if changed {
+            debug!("Network configuration changed, re-registering service");
+
+            if let Err(e) = self.reregister() {
+                self.invoke_callback(Err(e));
+            }
+        }
+
+        if self.pending {
+            self.pending = false;
+
+            let result = self.registration();
+            self.invoke_callback(result);
+        }
+    }
+
+    fn registration(&self) -> Result<ServiceRegistration> {
+        let name = match &self.name {
+            Some(n) => n.clone(),
+            None => resolved_util::host_name().ok_or("could not get the host name")?,
+        };
+
+        Ok(ServiceRegistration::builder()
+            .name(name)
+            .service_type(self.service_type.clone())
+            .domain(LOCAL_DOMAIN.to_string())
+            .build()?)
+    }
+
+    fn invoke_callback(&self, result: Result<ServiceRegistration>) {
+        match (result, &self.error_callback, &self.registered_callback) {
+            (Err(e), Some(f), _) => f(e, self.user_context.clone()),
+            (result, _, Some(f)) => f(result, self.user_context.clone()),
+            _ => warn!("attempted to invoke callback but none was set"),
+        }
+    }
+
+    pub(crate) fn set_port(&mut self, port: u16) {
+        self.port = port;
+    }
+
+    /// Re-registers the service with its current configuration.
+    pub(crate) fn reregister(&mut self) -> Result<()> {
+        if self.client.is_none() {
+            return Ok(());
+        }
+
+        self.remove_service()?;
+        self.create_service()
+    }
+}
+
+impl fmt::Debug for ResolvedMdnsService {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResolvedMdnsService")
+            .field("service_type", &self.service_type)
+            .field("port", &self.port)
+            .field("name", &self.name)
+            .field("path", &self.path)
+            .finish()
+    }
+}
+
+impl Drop for ResolvedMdnsService {
+    fn drop(&mut self) {
+        if let Err(e) = self.remove_service() {
+            warn!("could not unregister service: {}", e);
+        }
+    }
+}
@@ -1,14 +1,44 @@
 //! Trait definition for cross-platform service.
 
-use crate::{EventLoop, NetworkInterface, Result, ServiceType, TxtRecord};
+use crate::error::{Error, ErrorKind};
+use crate::{
+    DaemonReconnectedCallback, ErrorCallback, NetworkInterface, RegistrationHandle, Result,
+    ServiceFlags, ServiceType, TxtRecord,
+};
 use std::any::Any;
+use std::cell::RefCell;
 use std::sync::Arc;
 
 /// Interface for interacting with underlying mDNS service implementation registration
 /// capabilities.
 pub trait TMdnsService {
     /// Creates a new `MdnsService` with the specified `ServiceType` (e.g. `_http._tcp`) and `port`.
-    fn new(service_type: ServiceType, port: u16) -> Self;
+    ///
+    /// A `port` of `0` registers a "placeholder" service. A placeholder claims the instance name
+    /// on the network (other registrations of the same name will conflict with it) but does not
+    /// advertise a reachable service, which is useful for applications that want to reserve their
+    /// name at startup before their listener is ready. Placeholders are never reported by an
+    /// `MdnsBrowser`.
+    ///
+    /// Placeholders are only supported by Bonjour (on macOS, iOS, Windows with the `bonjour-sdk`
+    /// feature and Linux with the `mdnsresponder` feature). Avahi, systemd-resolved, the embedded
+    /// implementation and the DNS-SD API of Windows would announce a `SRV` record for port `0`
+    /// instead, so `register()` and `TRegistrationHandle::set_port()` return an error of kind
+    /// `ErrorKind::InvalidArgument` for a `port` of `0` with them.
+    fn new(service_type: ServiceType, port: u16) -> Self
+    where
+        Self: Sized;
+
+    /// Returns a [`ServiceBuilder`] that creates a service with the specified `service_type` and
+    /// `port` with the options set on the builder.
+    ///
+    /// [`ServiceBuilder`]: struct.ServiceBuilder.html
+    fn builder(service_type: ServiceType, port: u16) -> ServiceBuilder<Self>
+    where
+        Self: Sized + 'static,
+    {
+        ServiceBuilder::new(service_type, port)
+    }
 
     /// Sets the name to register this service under.
     fn set_name(&mut self, name: &str);
@@ -22,7 +52,9 @@ pub trait TMdnsService {
     /// Sets the domain on which to advertise the service.
     ///
     /// Most applications will want to use the default value of `ptr::null()` to register to the
-    /// default domain.
+    /// default domain. Registering in a unicast DNS-SD domain (e.g. `example.com`) requires the
+    /// mDNS daemon to be allowed to update that zone (Bonjour) or to have wide-area publishing
+    /// enabled (Avahi).
     fn set_domain(&mut self, _domain: &str);
 
     /// Sets the SRV target host name.
@@ -31,6 +63,11 @@ pub trait TMdnsService {
     /// default host name.
     fn set_host(&mut self, _host: &str);
 
+    /// Sets the [`ServiceFlags`] to register this service with.
+    ///
+    /// [`ServiceFlags`]: ../flags/struct.ServiceFlags.html
+    fn set_flags(&mut self, flags: ServiceFlags);
+
     /// Sets the optional `TxtRecord` to register this service with.
     fn set_txt_record(&mut self, txt_record: TxtRecord);
 
@@ -40,13 +77,270 @@ pub trait TMdnsService {
     /// [`ServiceRegisteredCallback`]: ../type.ServiceRegisteredCallback.html
     fn set_registered_callback(&mut self, registered_callback: Box<ServiceRegisteredCallback>);
 
+    /// Same as [`set_registered_callback()`] but accepts any closure, including ones that mutate
+    /// the state they capture.
+    ///
+    /// [`set_registered_callback()`]: #tymethod.set_registered_callback
+    fn on_registered<F>(&mut self, callback: F)
+    where
+        F: FnMut(Result<ServiceRegistration>, Option<Arc<dyn Any + Send + Sync>>) + Send + 'static,
+        Self: Sized,
+    {
+        // callbacks are never invoked while another one is running
+        let callback = RefCell::new(callback);
+        self.set_registered_callback(Box::new(move |r, c| (callback.borrow_mut())(r, c)));
+    }
+
     /// Sets the optional user context to pass through to the callback. This is useful if you need
-    /// to share state between pre and post-callback. The context type must implement `Any`.
-    fn set_context(&mut self, context: Box<dyn Any>);
+    /// to share state between pre and post-callback. The context type must implement `Any`, `Send`
+    /// and `Sync`.
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>);
+
+    /// Sets the TTL (in seconds) of the records published for this service.
+    ///
+    /// Lower TTLs allow browsers to detect that a service has gone away faster, at the cost of
+    /// more network traffic. By default, the implementation's defaults are used.
+    ///
+    /// # Note
+    /// Bonjour only supports changing the TTL of the service's TXT record, the PTR and SRV records
+    /// keep their default TTL.
+    fn set_ttl(&mut self, ttl: u32);
+
+    /// Sets whether the service should be re-registered automatically when the host's network
+    /// configuration changes (e.g. an interface going down and back up, or a new address being
+    /// assigned when roaming between Wi-Fi networks).
+    ///
+    /// This is disabled by default. Long-running daemons that must stay discoverable across
+    /// network changes should enable it. The [`ServiceRegisteredCallback`] is invoked again once
+    /// the service has been re-registered.
+    ///
+    /// [`ServiceRegisteredCallback`]: ../type.ServiceRegisteredCallback.html
+    fn set_auto_reregister(&mut self, auto_reregister: bool);
+
+    /// Sets the optional [`DaemonReconnectedCallback`] that is invoked when the service has
+    /// reconnected to the mDNS daemon after it was restarted.
+    ///
+    /// The service reconnects and re-registers itself transparently, the
+    /// [`ServiceRegisteredCallback`] is invoked again once the registration is re-established.
+    ///
+    /// [`DaemonReconnectedCallback`]: ../daemon/type.DaemonReconnectedCallback.html
+    /// [`ServiceRegisteredCallback`]: ../type.ServiceRegisteredCallback.html
+    fn set_reconnected_callback(&mut self, reconnected_callback: Box<DaemonReconnectedCallback>);
+
+    /// Sets the optional [`ErrorCallback`] that is invoked with the errors of the service (e.g.
+    /// the mDNS daemon died) instead of the [`ServiceRegisteredCallback`], which then only
+    /// receives successful registrations.
+    ///
+    /// [`ErrorCallback`]: ../error/type.ErrorCallback.html
+    /// [`ServiceRegisteredCallback`]: ../type.ServiceRegisteredCallback.html
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>);
 
-    /// Registers and start's the service. Returns an `EventLoop` which can be called to keep
-    /// the service alive.
-    fn register(&mut self) -> Result<EventLoop>;
+    /// Sets the optional [`NameConflictCallback`] that is invoked when the service's name
+    /// conflicts with another service on the network.
+    ///
+    /// By default, Bonjour and Avahi automatically rename the service on conflict (e.g. to
+    /// `name (2)` and `name #2`, respectively) unless [`ServiceFlags::NO_AUTO_RENAME`] is set,
+    /// and the [`ServiceRegisteredCallback`] receives the name the service was finally registered
+    /// under. If a callback is set, automatic renaming is disabled and the service is
+    /// re-registered under the name returned by the callback, until registration succeeds or the
+    /// callback gives up. In the latter case, or if automatic renaming is disabled, the
+    /// [`ServiceRegisteredCallback`] receives an error of kind [`ErrorKind::NameConflict`].
+    ///
+    /// [`NameConflictCallback`]: ../service/type.NameConflictCallback.html
+    /// [`ServiceRegisteredCallback`]: ../type.ServiceRegisteredCallback.html
+    /// [`ErrorKind::NameConflict`]: ../error/enum.ErrorKind.html#variant.NameConflict
+    /// [`ServiceFlags::NO_AUTO_RENAME`]: ../flags/struct.ServiceFlags.html
+    fn set_name_conflict_callback(&mut self, name_conflict_callback: Box<NameConflictCallback>);
+
+    /// Registers and start's the service. Returns a [`RegistrationHandle`] which can be polled to
+    /// keep the service alive and used to change the service's name or port while it is running.
+    ///
+    /// [`RegistrationHandle`]: ../registration_handle/trait.TRegistrationHandle.html
+    fn register(&mut self) -> Result<RegistrationHandle<'_>>;
+
+    /// Unregisters the service and releases what it holds (e.g. its `DNSServiceRef` or Avahi
+    /// entry group) before returning, unlike dropping it which only logs the errors of releasing
+    /// them.
+    ///
+    /// Returns the first error reported while releasing the service, the rest is released
+    /// regardless. A connection to the mDNS daemon shared with other browsers or services of the
+    /// thread is closed once the last of them is.
+    fn close(self) -> Result<()>
+    where
+        Self: Sized,
+    {
+        drop(self);
+        Ok(())
+    }
+}
+
+/// Creates a service with the options set on it, see [`TMdnsService::builder()`].
+///
+/// The options are applied when the service is built, in any order.
+///
+/// # Examples
+/// ```no_run
+/// use std::time::Duration;
+/// use zeroconf::prelude::*;
+/// use zeroconf::{MdnsService, ServiceType, TxtRecord};
+///
+/// let mut txt_record = TxtRecord::new();
+/// txt_record.insert("foo", "bar").unwrap();
+///
+/// let mut service = MdnsService::builder(ServiceType::new("http", "tcp").unwrap(), 8080)
+///     .name("My Service")
+///     .txt_record(txt_record)
+///     .on_registered(|registration, _| println!("Service registered: {:?}", registration))
+///     .build();
+///
+/// let handle = service.register().unwrap();
+///
+/// loop {
+///     handle.poll(Duration::from_secs(0)).unwrap();
+/// }
+/// ```
+///
+/// [`TMdnsService::builder()`]: trait.TMdnsService.html#method.builder
+pub struct ServiceBuilder<S> {
+    service_type: ServiceType,
+    port: u16,
+    options: Vec<BuilderOption<S>>,
+}
+
+/// An option of a `ServiceBuilder`, applied to the service it builds
+type BuilderOption<S> = Box<dyn FnOnce(&mut S)>;
+
+impl<S: TMdnsService + 'static> ServiceBuilder<S> {
+    /// Creates a new builder of a service with the specified `service_type` and `port`.
+    pub fn new(service_type: ServiceType, port: u16) -> Self {
+        Self {
+            service_type,
+            port,
+            options: vec![],
+        }
+    }
+
+    /// See [`TMdnsService::set_name()`].
+    ///
+    /// [`TMdnsService::set_name()`]: trait.TMdnsService.html#tymethod.set_name
+    pub fn name(self, name: &str) -> Self {
+        let name = name.to_string();
+        self.option(move |s| s.set_name(&name))
+    }
+
+    /// See [`TMdnsService::set_network_interface()`].
+    ///
+    /// [`TMdnsService::set_network_interface()`]: trait.TMdnsService.html#tymethod.set_network_interface
+    pub fn network_interface(self, interface: NetworkInterface) -> Self {
+        self.option(move |s| s.set_network_interface(interface))
+    }
+
+    /// See [`TMdnsService::set_domain()`].
+    ///
+    /// [`TMdnsService::set_domain()`]: trait.TMdnsService.html#tymethod.set_domain
+    pub fn domain(self, domain: &str) -> Self {
+        let domain = domain.to_string();
+        self.option(move |s| s.set_domain(&domain))
+    }
+
+    /// See [`TMdnsService::set_host()`].
+    ///
+    /// [`TMdnsService::set_host()`]: trait.TMdnsService.html#tymethod.set_host
+    pub fn host(self, host: &str) -> Self {
+        let host = host.to_string();
+        self.option(move |s| s.set_host(&host))
+    }
+
+    /// See [`TMdnsService::set_flags()`].
+    ///
+    /// [`TMdnsService::set_flags()`]: trait.TMdnsService.html#tymethod.set_flags
+    pub fn flags(self, flags: ServiceFlags) -> Self {
+        self.option(move |s| s.set_flags(flags))
+    }
+
+    /// See [`TMdnsService::set_txt_record()`].
+    ///
+    /// [`TMdnsService::set_txt_record()`]: trait.TMdnsService.html#tymethod.set_txt_record
+    pub fn txt_record(self, txt_record: TxtRecord) -> Self {
+        self.option(move |s| s.set_txt_record(txt_record))
+    }
+
+    /// See [`TMdnsService::on_registered()`].
+    ///
+    /// [`TMdnsService::on_registered()`]: trait.TMdnsService.html#method.on_registered
+    pub fn on_registered<F>(self, callback: F) -> Self
+    where
+        F: FnMut(Result<ServiceRegistration>, Option<Arc<dyn Any + Send + Sync>>) + Send + 'static,
+    {
+        self.option(move |s| s.on_registered(callback))
+    }
+
+    /// See [`TMdnsService::set_context()`].
+    ///
+    /// [`TMdnsService::set_context()`]: trait.TMdnsService.html#tymethod.set_context
+    pub fn context<T: Any + Send + Sync>(self, context: T) -> Self {
+        self.option(move |s| s.set_context(Box::new(context)))
+    }
+
+    /// See [`TMdnsService::set_ttl()`].
+    ///
+    /// [`TMdnsService::set_ttl()`]: trait.TMdnsService.html#tymethod.set_ttl
+    pub fn ttl(self, ttl: u32) -> Self {
+        self.option(move |s| s.set_ttl(ttl))
+    }
+
+    /// See [`TMdnsService::set_auto_reregister()`].
+    ///
+    /// [`TMdnsService::set_auto_reregister()`]: trait.TMdnsService.html#tymethod.set_auto_reregister
+    pub fn auto_reregister(self, auto_reregister: bool) -> Self {
+        self.option(move |s| s.set_auto_reregister(auto_reregister))
+    }
+
+    /// See [`TMdnsService::set_reconnected_callback()`].
+    ///
+    /// [`TMdnsService::set_reconnected_callback()`]: trait.TMdnsService.html#tymethod.set_reconnected_callback
+    pub fn on_reconnected<F>(self, callback: F) -> Self
+    where
+        F: Fn(Option<Arc<dyn Any + Send + Sync>>) + Send + 'static,
+    {
+        self.option(move |s| s.set_reconnected_callback(Box::new(callback)))
+    }
+
+    /// See [`TMdnsService::set_error_callback()`].
+    ///
+    /// [`TMdnsService::set_error_callback()`]: trait.TMdnsService.html#tymethod.set_error_callback
+    pub fn on_error<F>(self, callback: F) -> Self
+    where
+        F: Fn(Error, Option<Arc<dyn Any + Send + Sync>>) + Send + 'static,
+    {
+        self.option(move |s| s.set_error_callback(Box::new(callback)))
+    }
+
+    /// See [`TMdnsService::set_name_conflict_callback()`].
+    ///
+    /// [`TMdnsService::set_name_conflict_callback()`]: trait.TMdnsService.html#tymethod.set_name_conflict_callback
+    pub fn on_name_conflict<F>(self, callback: F) -> Self
+    where
+        F: Fn(&str, Option<Arc<dyn Any + Send + Sync>>) -> Option<String> + Send + 'static,
+    {
+        self.option(move |s| s.set_name_conflict_callback(Box::new(callback)))
+    }
+
+    /// Creates the service with the options set on this builder.
+    pub fn build(self) -> S {
+        let mut service = S::new(self.service_type, self.port);
+
+        for option in self.options {
+            option(&mut service);
+        }
+
+        service
+    }
+
+    fn option(mut self, option: impl FnOnce(&mut S) + 'static) -> Self {
+        self.options.push(Box::new(option));
+        self
+    }
 }
 
 /// Callback invoked from [`MdnsService`] once it has successfully registered.
@@ -56,14 +350,75 @@ pub trait TMdnsService {
 /// * `context` - The optional user context passed through
 ///
 /// [`MdnsService`]: type.MdnsService.html
-pub type ServiceRegisteredCallback = dyn Fn(Result<ServiceRegistration>, Option<Arc<dyn Any>>);
+pub type ServiceRegisteredCallback =
+    dyn Fn(Result<ServiceRegistration>, Option<Arc<dyn Any + Send + Sync>>) + Send;
+
+/// Callback invoked from [`MdnsService`] when its name conflicts with another service on the
+/// network.
+///
+/// # Arguments
+/// * `name` - The name that is in conflict
+/// * `context` - The optional user context passed through
+///
+/// Returns the next candidate name to register the service under (e.g. `"Printer (2)"`), or
+/// `None` to give up.
+///
+/// [`MdnsService`]: type.MdnsService.html
+pub type NameConflictCallback =
+    dyn Fn(&str, Option<Arc<dyn Any + Send + Sync>>) -> Option<String> + Send;
 
 /// Represents a registration event for a [`MdnsService`].
 ///
+/// The name, service type and domain can be moved out without being cloned with `into_parts()`.
+///
 /// [`MdnsService`]: type.MdnsService.html
-#[derive(Builder, BuilderDelegate, Debug, Getters, Clone, Default, PartialEq, Eq)]
+#[derive(
+    Builder,
+    BuilderDelegate,
+    Debug,
+    Getters,
+    Dissolve,
+    Serialize,
+    Deserialize,
+    Clone,
+    Default,
+    PartialEq,
+    Eq,
+)]
+#[dissolve(rename = "into_parts")]
 pub struct ServiceRegistration {
     name: String,
     service_type: ServiceType,
     domain: String,
 }
+
+/// Returns an error of kind `ErrorKind::InvalidArgument` if `port` is `0`, for the backends that
+/// cannot register a placeholder service without announcing a `SRV` record for it.
+#[cfg_attr(
+    any(backend_mock, backend_plugin, backend_bonjour, backend_bonjour_sdk),
+    allow(dead_code)
+)]
+pub(crate) fn check_port(port: u16) -> Result<()> {
+    if port == 0 {
+        Err(Error::with_kind(
+            ErrorKind::InvalidArgument,
+            "placeholder services with port 0 are not supported by this backend".to_string(),
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_port_rejects_placeholders() {
+        assert!(check_port(8080).is_ok());
+        assert_eq!(
+            check_port(0).unwrap_err().kind(),
+            ErrorKind::InvalidArgument
+        );
+    }
+}
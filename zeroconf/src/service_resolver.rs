@@ -0,0 +1,83 @@
+//! Trait definition for cross-platform service resolver
+
+use crate::{
+    AddressFamily, AddressPolicy, EventLoop, NetworkInterface, Result, ServiceDiscoveredCallback,
+    ServiceDiscovery, ServiceType,
+};
+use std::any::Any;
+use std::cell::RefCell;
+use std::sync::Arc;
+
+/// Interface for interacting with underlying mDNS implementation service resolution
+/// capabilities.
+///
+/// Unlike an `MdnsBrowser`, an `MdnsResolver` resolves a single, already known service instance
+/// (e.g. one whose name was persisted from a previous run) without browsing for services first.
+pub trait TMdnsResolver {
+    /// Creates a new `MdnsResolver`
+    fn new() -> Self
+    where
+        Self: Sized;
+
+    /// Sets the network interface on which to resolve the service.
+    ///
+    /// Most applications will want to use the default value `NetworkInterface::Unspec` to resolve
+    /// on all available interfaces.
+    fn set_network_interface(&mut self, interface: NetworkInterface);
+
+    /// Sets the [`AddressFamily`] to resolve the service to.
+    ///
+    /// [`AddressFamily`]: ../enum.AddressFamily.html
+    fn set_address_family(&mut self, address_family: AddressFamily);
+
+    /// Sets the [`AddressPolicy`] that orders the addresses the service has been resolved to.
+    ///
+    /// # Note
+    /// Avahi only resolves a single address per service, the policy has no effect.
+    ///
+    /// [`AddressPolicy`]: ../enum.AddressPolicy.html
+    fn set_address_policy(&mut self, address_policy: AddressPolicy);
+
+    /// Sets whether the raw resource records of the service instance (e.g. its SRV and TXT records,
+    /// as well as any nonstandard records) are reported in `ServiceDiscovery::raw_records`.
+    ///
+    /// This is intended for protocols that announce records this crate does not parse. Defaults
+    /// to `false`.
+    fn set_include_raw_records(&mut self, include_raw_records: bool);
+
+    /// Sets the [`ServiceDiscoveredCallback`] that is invoked when the service has been resolved.
+    ///
+    /// [`ServiceDiscoveredCallback`]: ../type.ServiceDiscoveredCallback.html
+    fn set_service_resolved_callback(
+        &mut self,
+        service_resolved_callback: Box<ServiceDiscoveredCallback>,
+    );
+
+    /// Same as [`set_service_resolved_callback()`] but accepts any closure, including ones that
+    /// mutate the state they capture.
+    ///
+    /// [`set_service_resolved_callback()`]: #tymethod.set_service_resolved_callback
+    fn on_service_resolved<F>(&mut self, callback: F)
+    where
+        F: FnMut(Result<ServiceDiscovery>, Option<Arc<dyn Any + Send + Sync>>) + Send + 'static,
+        Self: Sized,
+    {
+        // callbacks are never invoked while another one is running
+        let callback = RefCell::new(callback);
+        self.set_service_resolved_callback(Box::new(move |r, c| (callback.borrow_mut())(r, c)));
+    }
+
+    /// Sets the optional user context to pass through to the callback. This is useful if you need
+    /// to share state between pre and post-callback. The context type must implement `Any`, `Send`
+    /// and `Sync`.
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>);
+
+    /// Starts resolving the service instance `name` of the specified `ServiceType` in `domain`
+    /// (e.g. `local`). Returns an `EventLoop` which can be called to keep the resolver alive.
+    fn resolve(
+        &mut self,
+        name: &str,
+        service_type: ServiceType,
+        domain: &str,
+    ) -> Result<EventLoop<'_>>;
+}
@@ -1,22 +1,82 @@
 //! Data type for constructing a service type
 
+use crate::error::{Error, ErrorKind};
 use crate::Result;
+use std::convert::TryFrom;
 use std::str::FromStr;
 
+/// The maximum length of a DNS label, such as the name of a service type
+const MAX_LABEL_LEN: usize = 63;
+
 /// Data type for constructing a service type to register as an mDNS service.
+///
+/// Service types are validated when they are constructed or parsed, so malformed types fail early
+/// rather than with an error of the mDNS implementation once a service is registered or browsed:
+///
+/// * the name (e.g. `http`) may only contain letters, digits and hyphens, must contain a letter
+///   and must not begin or end with a hyphen, nor contain consecutive hyphens ([RFC 6335])
+/// * the protocol must be `tcp` or `udp`
+/// * sub-types must not be empty nor contain dots or commas
+///
+/// Names longer than the 15 characters recommended by RFC 6335 are accepted, as they are by Avahi
+/// and Bonjour on the `local` domain.
+///
+/// Deserialized service types are validated as well.
+///
+/// # Examples
+/// ```
+/// use zeroconf::ServiceType;
+///
+/// let service_type: ServiceType = "_http._tcp,_printer".parse().unwrap();
+/// assert_eq!(service_type.name(), "http");
+/// assert_eq!(service_type.sub_types(), &["printer"]);
+///
+/// // as reported by the mDNS implementations
+/// let service_type: ServiceType = "_printer._sub._http._tcp.".parse().unwrap();
+/// assert_eq!(service_type.name(), "http");
+/// assert_eq!(service_type.sub_types(), &["printer"]);
+///
+/// assert!("_http._sctp".parse::<ServiceType>().is_err());
+/// ```
+///
+/// [RFC 6335]: https://www.rfc-editor.org/rfc/rfc6335#section-5.1
 #[derive(Default, Debug, Getters, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(try_from = "RawServiceType")]
 pub struct ServiceType {
     name: String,
     protocol: String,
     sub_types: Vec<String>,
 }
 
+/// The serialized form of a [`ServiceType`], checked by `ServiceType::with_sub_types()` when it
+/// is deserialized.
+///
+/// [`ServiceType`]: struct.ServiceType.html
+#[derive(Deserialize)]
+struct RawServiceType {
+    name: String,
+    protocol: String,
+    sub_types: Vec<String>,
+}
+
+impl TryFrom<RawServiceType> for ServiceType {
+    type Error = Error;
+
+    fn try_from(raw: RawServiceType) -> Result<Self> {
+        Self::with_sub_types(
+            &raw.name,
+            &raw.protocol,
+            raw.sub_types.iter().map(String::as_str).collect(),
+        )
+    }
+}
+
 impl ServiceType {
     /// Creates a new `ServiceType` with the specified name (e.g. `http`) and protocol (e.g. `tcp`)
     pub fn new(name: &str, protocol: &str) -> Result<Self> {
         Ok(Self {
-            name: Self::check_part(name)?.to_string(),
-            protocol: Self::check_part(protocol)?.to_string(),
+            name: Self::check_name(name)?.to_string(),
+            protocol: Self::check_protocol(protocol)?.to_string(),
             sub_types: vec![],
         })
     }
@@ -29,24 +89,68 @@ impl ServiceType {
         }
 
         Ok(Self {
-            name: name.to_string(),
-            protocol: protocol.to_string(),
             sub_types: sub_types.iter().map(|s| s.to_string()).collect(),
+            ..Self::new(name, protocol)?
         })
     }
 
     fn check_part(part: &str) -> Result<&str> {
         if part.contains('.') {
-            Err("invalid character: .".into())
+            Err(invalid("invalid character: ."))
         } else if part.contains(',') {
-            Err("invalid character: ,".into())
+            Err(invalid("invalid character: ,"))
         } else if part.is_empty() {
-            Err("cannot be empty".into())
+            Err(invalid("cannot be empty"))
+        } else if part.len() > MAX_LABEL_LEN {
+            Err(invalid(format!(
+                "`{}` is longer than {} bytes",
+                part, MAX_LABEL_LEN
+            )))
         } else {
             Ok(part)
         }
     }
 
+    fn check_name(name: &str) -> Result<&str> {
+        Self::check_part(name)?;
+
+        if let Some(c) = name
+            .chars()
+            .find(|c| !c.is_ascii_alphanumeric() && *c != '-')
+        {
+            Err(invalid(format!(
+                "invalid character in service name `{}`: {}",
+                name, c
+            )))
+        } else if !name.chars().any(|c| c.is_ascii_alphabetic()) {
+            Err(invalid(format!(
+                "service name `{}` must contain a letter",
+                name
+            )))
+        } else if name.starts_with('-') || name.ends_with('-') || name.contains("--") {
+            Err(invalid(format!(
+                "service name `{}` must not begin or end with a hyphen, nor contain consecutive \
+                 hyphens",
+                name
+            )))
+        } else {
+            Ok(name)
+        }
+    }
+
+    fn check_protocol(protocol: &str) -> Result<&str> {
+        Self::check_part(protocol)?;
+
+        if protocol.eq_ignore_ascii_case("tcp") || protocol.eq_ignore_ascii_case("udp") {
+            Ok(protocol)
+        } else {
+            Err(invalid(format!(
+                "invalid protocol `{}`, expected `tcp` or `udp`",
+                protocol
+            )))
+        }
+    }
+
     fn lstrip_underscore(s: &str) -> &str {
         if let Some(stripped) = s.strip_prefix('_') {
             stripped
@@ -77,18 +181,28 @@ impl FromStr for ServiceType {
     fn from_str(s: &str) -> Result<Self> {
         let parts: Vec<&str> = s.split(',').collect();
         if parts.is_empty() {
-            return Err("could not parse ServiceType from string".into());
+            return Err(invalid("could not parse ServiceType from string"));
         }
 
-        let head: Vec<&str> = parts[0].split('.').collect();
-        if head.len() != 2 {
-            return Err("invalid name and protocol".into());
-        }
+        // registration types are reported by the mDNS implementations with a trailing dot
+        let head = parts[0].strip_suffix('.').unwrap_or(parts[0]);
+        let head: Vec<&str> = head.split('.').collect();
+
+        let mut sub_types: Vec<&str> = vec![];
+
+        // the DNS-SD form of a sub-type, e.g. `_printer._sub._http._tcp`
+        let head = match head.as_slice() {
+            [sub_type, "_sub", name, protocol] => {
+                sub_types.push(Self::lstrip_underscore(sub_type));
+                [*name, *protocol]
+            }
+            [name, protocol] => [*name, *protocol],
+            _ => return Err(invalid("invalid name and protocol")),
+        };
 
         let name = Self::lstrip_underscore(head[0]);
         let protocol = Self::lstrip_underscore(head[1]);
 
-        let mut sub_types: Vec<&str> = vec![];
         if parts.len() > 1 {
             for part in parts.iter().skip(1) {
                 sub_types.push(Self::lstrip_underscore(part));
@@ -99,6 +213,11 @@ impl FromStr for ServiceType {
     }
 }
 
+/// Returns an error of kind `ErrorKind::InvalidArgument` for an invalid service type.
+fn invalid<S: Into<String>>(description: S) -> Error {
+    Error::with_kind(ErrorKind::InvalidArgument, description.into())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -113,9 +232,34 @@ mod tests {
         ServiceType::new("http", "").expect_err("cannot be empty");
     }
 
+    #[test]
+    fn new_invalid_name() {
+        ServiceType::new("http_alt", "tcp").expect_err("invalid character: _");
+        ServiceType::new("-http", "tcp").expect_err("begins with a hyphen");
+        ServiceType::new("http-", "tcp").expect_err("ends with a hyphen");
+        ServiceType::new("http--alt", "tcp").expect_err("consecutive hyphens");
+        ServiceType::new("8080", "tcp").expect_err("no letter");
+        ServiceType::new(&"a".repeat(64), "tcp").expect_err("too long");
+    }
+
+    #[test]
+    fn new_invalid_protocol() {
+        ServiceType::new("http", "sctp").expect_err("invalid protocol");
+        ServiceType::with_sub_types("http", "sctp", vec!["printer"]).expect_err("invalid protocol");
+    }
+
+    #[test]
+    fn from_str_invalid() {
+        ServiceType::from_str("_http._sctp").expect_err("invalid protocol");
+        ServiceType::from_str("_http alt._tcp").expect_err("invalid character");
+        ServiceType::from_str("_http._tcp,").expect_err("empty sub-type");
+    }
+
     #[test]
     fn must_have_name_and_protocol() {
         ServiceType::from_str("_http").expect_err("invalid name and protocol");
+        ServiceType::from_str("_http._tcp._udp").expect_err("invalid name and protocol");
+        ServiceType::from_str("_printer._http._tcp").expect_err("invalid name and protocol");
     }
 
     #[test]
@@ -151,4 +295,52 @@ mod tests {
             ServiceType::with_sub_types("http", "tcp", vec!["api-v1", "api-v2"]).unwrap()
         );
     }
+
+    #[test]
+    fn from_str_with_trailing_dot_success() {
+        assert_eq!(
+            ServiceType::from_str("_http._tcp.").unwrap(),
+            ServiceType::new("http", "tcp").unwrap()
+        );
+    }
+
+    #[test]
+    fn from_str_with_dns_sd_sub_type_success() {
+        assert_eq!(
+            ServiceType::from_str("_printer._sub._http._tcp.").unwrap(),
+            ServiceType::with_sub_types("http", "tcp", vec!["printer"]).unwrap()
+        );
+    }
+
+    #[test]
+    fn deserialize_success() {
+        let service_type = ServiceType::with_sub_types("http", "tcp", vec!["printer"]).unwrap();
+        let json = serde_json::to_string(&service_type).unwrap();
+
+        assert_eq!(
+            serde_json::from_str::<ServiceType>(&json).unwrap(),
+            service_type
+        );
+    }
+
+    #[test]
+    fn deserialize_invalid() {
+        serde_json::from_str::<ServiceType>(r#"{"name":"http","protocol":"sctp","sub_types":[]}"#)
+            .expect_err("invalid protocol");
+        serde_json::from_str::<ServiceType>(r#"{"name":"","protocol":"tcp","sub_types":[]}"#)
+            .expect_err("cannot be empty");
+        serde_json::from_str::<ServiceType>(
+            r#"{"name":"http","protocol":"tcp","sub_types":["a.b"]}"#,
+        )
+        .expect_err("invalid character: .");
+    }
+
+    #[test]
+    fn invalid_service_type_is_fatal() {
+        let error = ServiceType::new("http", "sctp").unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::InvalidArgument);
+        assert!(!error.is_recoverable());
+        assert!(ErrorKind::DaemonNotRunning.is_recoverable());
+    }
 }
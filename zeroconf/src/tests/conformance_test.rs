@@ -0,0 +1,208 @@
+//! Scenarios every backend must behave identically in
+//!
+//! Each scenario registers a service and browses for it with the backend compiled into this
+//! build, and is skipped if the backend is not available on this host (e.g. `avahi-daemon` is not
+//! running). Run the tests with the features of each backend to check that they stay aligned.
+
+use crate::prelude::*;
+use crate::{
+    Backend, MdnsBrowser, MdnsService, RegistrationHandle, ServiceDiscovery, ServiceRegistration,
+    ServiceType, TxtRecord,
+};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a scenario waits for a service to be registered or discovered
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The timeout of each poll of the event loops of a scenario
+const POLL_TIMEOUT: Duration = Duration::from_millis(10);
+
+type Shared<T> = Arc<Mutex<Vec<T>>>;
+
+/// Runs `scenario` if the backend compiled into this build is available on this host.
+fn run(scenario: impl FnOnce(Backend)) {
+    super::setup();
+
+    let backend = Backend::current();
+
+    if !backend.is_available() {
+        warn!(
+            "skipping scenario, the {} backend is not available",
+            backend
+        );
+        return;
+    }
+
+    scenario(backend);
+}
+
+/// A service registered by a scenario, along with a browser for its service type.
+struct Scenario {
+    backend: Backend,
+    registrations: Shared<ServiceRegistration>,
+    discoveries: Shared<ServiceDiscovery>,
+}
+
+impl Scenario {
+    fn new(backend: Backend, service: &mut MdnsService) -> Self {
+        let registrations: Shared<ServiceRegistration> = Arc::default();
+        let registrations_clone = registrations.clone();
+
+        service.set_registered_callback(Box::new(move |r, _| {
+            registrations_clone.lock().unwrap().push(r.unwrap());
+        }));
+
+        Self {
+            backend,
+            registrations,
+            discoveries: Arc::default(),
+        }
+    }
+
+    fn browser(&self, service_type: ServiceType) -> MdnsBrowser {
+        let discoveries = self.discoveries.clone();
+
+        let mut browser = MdnsBrowser::new(service_type);
+
+        browser.set_service_discovered_callback(Box::new(move |s, _| match s {
+            Ok(s) => discoveries.lock().unwrap().push(s),
+            // e.g. other hosts announcing services of the same type
+            Err(e) => warn!("could not resolve service: {}", e),
+        }));
+
+        browser
+    }
+
+    /// Polls `handle` and `browser` until a service named `name` has been both registered and
+    /// discovered, panicking after `TIMEOUT`.
+    fn wait_for(
+        &self,
+        handle: &RegistrationHandle,
+        browser: &dyn TEventLoop,
+        name: &str,
+    ) -> (ServiceRegistration, ServiceDiscovery) {
+        let started = Instant::now();
+
+        loop {
+            handle.poll(POLL_TIMEOUT).unwrap();
+            browser.poll(POLL_TIMEOUT).unwrap();
+
+            let registration = self.find(&self.registrations, |r| r.name() == name);
+            let discovery = self.find(&self.discoveries, |d| d.name() == name);
+
+            if let (Some(r), Some(d)) = (registration, discovery) {
+                return (r, d);
+            }
+
+            assert!(
+                started.elapsed() < TIMEOUT,
+                "service `{}` was not registered and discovered with the {} backend",
+                name,
+                self.backend
+            );
+        }
+    }
+
+    fn find<T: Clone>(&self, items: &Shared<T>, predicate: impl Fn(&T) -> bool) -> Option<T> {
+        items.lock().unwrap().iter().find(|i| predicate(i)).cloned()
+    }
+}
+
+fn assert_same_kind(a: &ServiceType, b: &ServiceType) {
+    assert_eq!(a.name(), b.name());
+    assert_eq!(a.protocol(), b.protocol());
+}
+
+#[test]
+fn service_is_discovered_as_registered() {
+    run(|backend| {
+        static SERVICE_NAME: &str = "conformance_registered";
+        let service_type = ServiceType::new("zcconf-reg", "tcp").unwrap();
+
+        let mut txt = TxtRecord::new();
+        txt.insert("foo", "bar").unwrap();
+
+        let mut service = MdnsService::new(service_type.clone(), 8080);
+        service.set_name(SERVICE_NAME);
+        service.set_txt_record(txt);
+
+        let scenario = Scenario::new(backend, &mut service);
+        let mut browser = scenario.browser(service_type.clone());
+
+        let handle = service.register().unwrap();
+        let browser_loop = browser.browse_services().unwrap();
+
+        let (registration, discovery) = scenario.wait_for(&handle, &browser_loop, SERVICE_NAME);
+
+        assert_same_kind(registration.service_type(), &service_type);
+        assert_eq!(registration.domain(), "local");
+
+        assert_same_kind(discovery.service_type(), &service_type);
+        assert_eq!(discovery.domain(), "local");
+        assert_eq!(*discovery.port(), 8080);
+        assert!(!discovery.host_name().is_empty());
+        assert!(!discovery.host_name().ends_with('.'));
+        assert_eq!(discovery.address(), &discovery.addresses()[0]);
+
+        let txt = discovery
+            .txt()
+            .as_ref()
+            .expect("TXT record was not discovered");
+        assert_eq!(txt.get("foo").as_deref(), Some("bar"));
+    });
+}
+
+#[test]
+fn service_without_txt_record_is_discovered_without_entries() {
+    run(|backend| {
+        static SERVICE_NAME: &str = "conformance_no_txt";
+        let service_type = ServiceType::new("zcconf-notxt", "tcp").unwrap();
+
+        let mut service = MdnsService::new(service_type.clone(), 8080);
+        service.set_name(SERVICE_NAME);
+
+        let scenario = Scenario::new(backend, &mut service);
+        let mut browser = scenario.browser(service_type);
+
+        let handle = service.register().unwrap();
+        let browser_loop = browser.browse_services().unwrap();
+
+        let (_, discovery) = scenario.wait_for(&handle, &browser_loop, SERVICE_NAME);
+
+        // backends report either no TXT record or an empty one
+        let entries = match discovery.txt() {
+            Some(t) => t.len(),
+            None => 0,
+        };
+
+        assert_eq!(entries, 0);
+    });
+}
+
+#[test]
+fn renamed_service_is_discovered_under_new_name() {
+    run(|backend| {
+        static SERVICE_NAME: &str = "conformance_rename";
+        static NEW_NAME: &str = "conformance_renamed";
+        let service_type = ServiceType::new("zcconf-rename", "tcp").unwrap();
+
+        let mut service = MdnsService::new(service_type.clone(), 8080);
+        service.set_name(SERVICE_NAME);
+
+        let scenario = Scenario::new(backend, &mut service);
+        let mut browser = scenario.browser(service_type);
+
+        let mut handle = service.register().unwrap();
+        let browser_loop = browser.browse_services().unwrap();
+
+        scenario.wait_for(&handle, &browser_loop, SERVICE_NAME);
+
+        handle.set_name(NEW_NAME).unwrap();
+
+        let (registration, discovery) = scenario.wait_for(&handle, &browser_loop, NEW_NAME);
+
+        assert_eq!(registration.name(), NEW_NAME);
+        assert_eq!(*discovery.port(), 8080);
+    });
+}
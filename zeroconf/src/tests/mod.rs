@@ -4,7 +4,12 @@ static INIT: Once = Once::new();
 
 pub(crate) fn setup() {
     INIT.call_once(env_logger::init);
+
+    #[cfg(backend_plugin)]
+    crate::plugin::tests::set_loopback_backend();
 }
 
+mod conformance_test;
+mod send_test;
 mod service_test;
 mod txt_record_test;
@@ -0,0 +1,9 @@
+use crate::{MdnsBrowser, MdnsService};
+
+fn assert_send<T: Send>() {}
+
+#[test]
+fn browser_and_service_are_send() {
+    assert_send::<MdnsBrowser>();
+    assert_send::<MdnsService>();
+}
@@ -1,5 +1,11 @@
+use crate::browser::ServiceDiscoveryBuilder;
 use crate::prelude::*;
-use crate::{MdnsBrowser, MdnsService, ServiceType, TxtRecord};
+use crate::{
+    EventMeta, MdnsBrowser, MdnsService, ServiceDiscovery, ServiceRegistration, ServiceType,
+    TxtRecord,
+};
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
@@ -77,3 +83,175 @@ fn service_register_is_browsable() {
         }
     }
 }
+
+/// Returns a builder of the `foo._http._tcp.local` discovery on `127.0.0.1:8080`, for tests to
+/// override the fields they are about.
+fn discovery() -> ServiceDiscoveryBuilder {
+    let mut builder = ServiceDiscovery::builder();
+    builder
+        .name("foo".to_string())
+        .service_type(ServiceType::new("http", "tcp").unwrap())
+        .domain("local".to_string())
+        .host_name("foo.local".to_string())
+        .address(IpAddr::V4(Ipv4Addr::LOCALHOST))
+        .port(8080)
+        .txt(None);
+    builder
+}
+
+#[test]
+fn service_results_round_trip_through_json() {
+    let mut txt = TxtRecord::new();
+    txt.insert("foo", "bar").unwrap();
+
+    let registration = ServiceRegistration::builder()
+        .name("foo".to_string())
+        .service_type(ServiceType::new("http", "tcp").unwrap())
+        .domain("local".to_string())
+        .build()
+        .unwrap();
+
+    let discovery = discovery()
+        .txt(Some(txt))
+        .meta(
+            EventMeta::builder()
+                .flags(2)
+                .interface_index(3)
+                .ttl(Some(120))
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+
+    let json = serde_json::to_string(&registration).unwrap();
+    assert_eq!(
+        serde_json::from_str::<ServiceRegistration>(&json).unwrap(),
+        registration
+    );
+
+    let json = serde_json::to_string(&discovery).unwrap();
+    let discovery_de: ServiceDiscovery = serde_json::from_str(&json).unwrap();
+    assert_eq!(discovery_de, discovery);
+    assert_eq!(discovery_de.socket_addr(), discovery.socket_addr());
+    assert_eq!(discovery_de.txt(), discovery.txt());
+    assert_eq!(discovery_de.meta(), discovery.meta());
+}
+
+#[test]
+fn service_discovery_formats_uri() {
+    let ipv4 = discovery()
+        .address("192.168.1.5".parse().unwrap())
+        .build()
+        .unwrap();
+    assert_eq!(ipv4.to_uri("https"), "https://192.168.1.5:8080");
+    assert_eq!(ipv4.to_string(), "http://192.168.1.5:8080");
+
+    let ipv6 = discovery()
+        .address("2001:db8::1".parse().unwrap())
+        .build()
+        .unwrap();
+    assert_eq!(ipv6.to_string(), "http://[2001:db8::1]:8080");
+
+    // an unknown interface is formatted with its index
+    let link_local = discovery()
+        .address("fe80::1".parse().unwrap())
+        .scope_id(0xfff0_0000)
+        .build()
+        .unwrap();
+    assert_eq!(link_local.to_string(), "http://[fe80::1%254293918720]:8080");
+}
+
+#[test]
+// the hash of a discovery does not depend on its TXT record
+#[allow(clippy::mutable_key_type)]
+fn service_discoveries_are_keyed_by_instance() {
+    let printer = ServiceType::with_sub_types("http", "tcp", vec!["printer"]).unwrap();
+
+    let mut discoveries = HashSet::new();
+    assert!(discoveries.insert(discovery().build().unwrap()));
+    // the same instance, browsed by sub-type and resolved to another port
+    assert!(!discoveries.insert(
+        discovery()
+            .service_type(printer)
+            .port(8081)
+            .build()
+            .unwrap()
+    ));
+    assert!(discoveries.insert(discovery().name("bar".to_string()).build().unwrap()));
+    assert!(discoveries.insert(
+        discovery()
+            .service_type(ServiceType::new("ipp", "tcp").unwrap())
+            .build()
+            .unwrap()
+    ));
+
+    assert_eq!(discoveries.len(), 3);
+}
+
+#[test]
+fn service_discovery_resolves_to_all_socket_addresses() {
+    let discovery = discovery()
+        .address("192.168.1.5".parse().unwrap())
+        .addresses(vec![
+            "192.168.1.5".parse().unwrap(),
+            "2001:db8::1".parse().unwrap(),
+        ])
+        .build()
+        .unwrap();
+
+    let addrs: Vec<SocketAddr> = discovery.to_socket_addrs().unwrap().collect();
+
+    assert_eq!(
+        addrs,
+        vec![
+            "192.168.1.5:8080".parse::<SocketAddr>().unwrap(),
+            "[2001:db8::1]:8080".parse().unwrap(),
+        ]
+    );
+    assert_eq!(SocketAddr::from(&discovery), addrs[0]);
+}
+
+#[test]
+fn service_discovery_scopes_only_link_local_socket_addresses() {
+    let discovery = discovery()
+        .address("fe80::1".parse().unwrap())
+        .scope_id(3)
+        .addresses(vec![
+            "fe80::1".parse().unwrap(),
+            "2001:db8::1".parse().unwrap(),
+        ])
+        .build()
+        .unwrap();
+
+    let scope_ids: Vec<u32> = discovery
+        .to_socket_addrs()
+        .unwrap()
+        .map(|addr| match addr {
+            SocketAddr::V6(addr) => addr.scope_id(),
+            SocketAddr::V4(_) => panic!("expected an IPv6 address"),
+        })
+        .collect();
+
+    assert_eq!(scope_ids, vec![3, 0]);
+}
+
+#[test]
+fn service_results_are_split_into_parts() {
+    let registration = ServiceRegistration::builder()
+        .name("foo".to_string())
+        .service_type(ServiceType::new("http", "tcp").unwrap())
+        .domain("local".to_string())
+        .build()
+        .unwrap();
+
+    let (name, service_type, domain) = registration.into_parts();
+    assert_eq!(name, "foo");
+    assert_eq!(service_type.name(), "http");
+    assert_eq!(domain, "local");
+
+    let (name, _, _, host_name, address, ..) = discovery().build().unwrap().into_parts();
+    assert_eq!(name, "foo");
+    assert_eq!(host_name, "foo.local");
+    assert_eq!(address, IpAddr::V4(Ipv4Addr::LOCALHOST));
+}
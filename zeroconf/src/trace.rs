@@ -0,0 +1,10 @@
+//! Instrumentation of the operations of the mDNS implementations with `tracing` spans
+//!
+//! When the `tracing` feature is enabled, browsing, resolving, looking up the addresses of and
+//! registering a service each run in a span at the `DEBUG` level named after the operation (e.g.
+//! `resolve`), with the identity of the service as fields. The `debug!` lines logged during the
+//! operation can be recorded in the span with `tracing-log`.
+
+/// Stands in for the entered span of an operation when the `tracing` feature is disabled
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct NoSpan;
@@ -4,6 +4,7 @@ use crate::{Result, TxtRecord};
 use serde::de::{MapAccess, Visitor};
 use serde::ser::SerializeMap;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fmt::{self, Debug};
 use std::marker::PhantomData;
@@ -138,6 +139,170 @@ impl Debug for TxtRecord {
     }
 }
 
+/// A TXT record borrowed from its RDATA, the sequence of length-prefixed `key=value` strings
+/// defined by RFC 6763 (e.g. the bytes reported by the mDNS implementation when a service is
+/// resolved).
+///
+/// The entries are decoded as they are iterated, without copying them unless they are not valid
+/// UTF-8. Use [`to_txt_record()`] to obtain an owned [`TxtRecord`].
+///
+/// Empty strings and entries without a key are skipped as required by RFC 6763, entries without
+/// a `'='` have an empty value.
+///
+/// [`to_txt_record()`]: #method.to_txt_record
+/// [`TxtRecord`]: ../type.TxtRecord.html
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct TxtRecordView<'a>(&'a [u8]);
+
+impl<'a> TxtRecordView<'a> {
+    /// Creates a new view of the TXT record encoded in `rdata`.
+    pub fn new(rdata: &'a [u8]) -> Self {
+        Self(rdata)
+    }
+
+    /// Returns the RDATA this view borrows.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.0
+    }
+
+    /// Returns a new iterator over the `key=value` entries of the record.
+    pub fn iter(&self) -> TxtEntries<'a> {
+        TxtEntries(Strings(self.0))
+    }
+
+    /// Returns the value of the first entry with the specified key or `None` if no such key
+    /// exists.
+    pub fn get(&self, key: &str) -> Option<Cow<'a, str>> {
+        self.raw_entries()
+            .find(|(k, _)| *k == key.as_bytes())
+            .map(|(_, v)| String::from_utf8_lossy(v.unwrap_or_default()))
+    }
+
+    /// Returns true if the record contains the specified key.
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.raw_entries().any(|(k, _)| k == key.as_bytes())
+    }
+
+    /// Returns the amount of entries in the record.
+    pub fn len(&self) -> usize {
+        self.raw_entries().count()
+    }
+
+    /// Returns true if there are no entries in the record.
+    pub fn is_empty(&self) -> bool {
+        self.raw_entries().next().is_none()
+    }
+
+    /// Copies the entries of this view into a new [`TxtRecord`]. Only the first entry of a key
+    /// that occurs more than once is kept.
+    ///
+    /// [`TxtRecord`]: ../type.TxtRecord.html
+    pub fn to_txt_record(&self) -> Result<TxtRecord> {
+        from_entries(self.iter())
+    }
+
+    /// Returns a new iterator over the undecoded keys and values of the record. The value is
+    /// `None` if the entry has no `'='`.
+    pub(crate) fn raw_entries(&self) -> impl Iterator<Item = (&'a [u8], Option<&'a [u8]>)> {
+        Strings(self.0).filter_map(split_entry)
+    }
+}
+
+impl<'a> IntoIterator for TxtRecordView<'a> {
+    type Item = (Cow<'a, str>, Cow<'a, str>);
+    type IntoIter = TxtEntries<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl Debug for TxtRecordView<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+/// An `Iterator` over the entries of a [`TxtRecordView`], which borrow from its RDATA.
+///
+/// [`TxtRecordView`]: struct.TxtRecordView.html
+#[derive(Debug, Clone)]
+pub struct TxtEntries<'a>(Strings<'a>);
+
+impl<'a> Iterator for TxtEntries<'a> {
+    type Item = (Cow<'a, str>, Cow<'a, str>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.find_map(parse_entry)
+    }
+}
+
+/// An `Iterator` over the length-prefixed strings of TXT record RDATA, which stops at a truncated
+/// string.
+#[derive(Debug, Clone)]
+struct Strings<'a>(&'a [u8]);
+
+impl<'a> Iterator for Strings<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&len, rest) = self.0.split_first()?;
+        let len = len as usize;
+
+        if len > rest.len() {
+            self.0 = &[];
+            return None;
+        }
+
+        let (string, rest) = rest.split_at(len);
+        self.0 = rest;
+
+        Some(string)
+    }
+}
+
+/// Decodes the specified `key=value` string of a TXT record, returning `None` if it is empty or
+/// has no key. Entries without a `'='` have an empty value.
+pub(crate) fn parse_entry(entry: &[u8]) -> Option<(Cow<'_, str>, Cow<'_, str>)> {
+    let (key, value) = split_entry(entry)?;
+
+    Some((
+        String::from_utf8_lossy(key),
+        String::from_utf8_lossy(value.unwrap_or_default()),
+    ))
+}
+
+fn split_entry(entry: &[u8]) -> Option<(&[u8], Option<&[u8]>)> {
+    let (key, value) = match entry.iter().position(|b| *b == b'=') {
+        Some(i) => (&entry[..i], Some(&entry[i + 1..])),
+        None => (entry, None),
+    };
+
+    if key.is_empty() {
+        None
+    } else {
+        Some((key, value))
+    }
+}
+
+/// Collects the specified entries into a new [`TxtRecord`], keeping only the first entry of a key
+/// that occurs more than once.
+///
+/// [`TxtRecord`]: ../type.TxtRecord.html
+pub(crate) fn from_entries<'a>(
+    entries: impl IntoIterator<Item = (Cow<'a, str>, Cow<'a, str>)>,
+) -> Result<TxtRecord> {
+    let mut record = TxtRecord::new();
+
+    for (key, value) in entries {
+        if !record.contains_key(&key) {
+            record.insert(&key, &value)?;
+        }
+    }
+
+    Ok(record)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -154,4 +319,50 @@ mod tests {
 
         assert_eq!(txt, txt_de);
     }
+
+    #[test]
+    fn view_decodes_entries_without_copying() {
+        let view = TxtRecordView::new(b"\x07foo=bar\x03baz\x00\x04=qux\x06foo=no");
+
+        let entries: Vec<_> = view.iter().collect();
+
+        assert_eq!(
+            entries,
+            vec![
+                (Cow::from("foo"), Cow::from("bar")),
+                (Cow::from("baz"), Cow::from("")),
+                (Cow::from("foo"), Cow::from("no")),
+            ]
+        );
+        assert!(entries
+            .iter()
+            .all(|(k, v)| matches!((k, v), (Cow::Borrowed(_), Cow::Borrowed(_)))));
+
+        assert_eq!(view.len(), 3);
+        assert_eq!(view.get("foo").as_deref(), Some("bar"));
+        assert_eq!(view.get("baz").as_deref(), Some(""));
+        assert!(view.contains_key("baz"));
+        assert!(!view.contains_key("qux"));
+    }
+
+    #[test]
+    fn view_stops_at_truncated_entry() {
+        let view = TxtRecordView::new(b"\x07foo=bar\x09baz=q");
+
+        assert_eq!(view.len(), 1);
+        assert!(TxtRecordView::new(b"\x00").is_empty());
+    }
+
+    #[test]
+    fn view_to_txt_record_keeps_first_entry() {
+        crate::tests::setup();
+
+        let txt = TxtRecordView::new(b"\x07foo=bar\x06foo=no\x07baz=qux")
+            .to_txt_record()
+            .unwrap();
+
+        assert_eq!(txt.len(), 2);
+        assert_eq!(txt.get("foo").as_deref(), Some("bar"));
+        assert_eq!(txt.get("baz").as_deref(), Some("qux"));
+    }
 }
@@ -0,0 +1,543 @@
+//! Win32 implementation for cross-platform browser
+
+use super::task_queue::{ContextPtr, TaskQueue};
+use super::win32_util;
+use crate::ffi::{FromRaw, SharedContext};
+use crate::prelude::*;
+use crate::resolve_queue::ResolveQueue;
+use crate::{
+    ffi, AddressFamily, AddressPolicy, BrowserControl, DaemonReconnectedCallback, ErrorCallback,
+    EventLoop, NetworkInterface, Result, ServiceType, TxtRecord,
+};
+use crate::{ServiceDiscoveredCallback, ServiceDiscovery, ServiceFlags};
+use libc::c_void;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::{self, Formatter};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::ptr;
+use std::sync::Arc;
+use std::time::Duration;
+use windows_sys::Win32::Foundation::{ERROR_CANCELLED, ERROR_SUCCESS};
+use windows_sys::Win32::NetworkManagement::Dns::{
+    DnsFree, DnsFreeRecordList, DnsServiceBrowse, DnsServiceBrowseCancel, DnsServiceFreeInstance,
+    DnsServiceResolve, DnsServiceResolveCancel, DNS_QUERY_REQUEST_VERSION1, DNS_RECORDW,
+    DNS_REQUEST_PENDING, DNS_SERVICE_BROWSE_REQUEST, DNS_SERVICE_BROWSE_REQUEST_0,
+    DNS_SERVICE_CANCEL, DNS_SERVICE_INSTANCE, DNS_SERVICE_RESOLVE_REQUEST, DNS_TYPE_PTR,
+};
+
+pub struct Win32MdnsBrowser {
+    queue: Arc<TaskQueue>,
+    interface_index: u32,
+    browse: Option<Browse>,
+    context: SharedContext<Win32BrowserContext>,
+}
+
+// SAFETY: the context and the DNS-SD objects are only used through `&mut self` or the event loop
+// borrowing the browser, the DNS-SD callbacks only schedule tasks on the `TaskQueue`, and the
+// callbacks and the context of the user it holds are `Send`.
+unsafe impl Send for Win32MdnsBrowser {}
+
+impl TMdnsBrowser for Win32MdnsBrowser {
+    fn new(service_type: ServiceType) -> Self {
+        let queue = Arc::<TaskQueue>::default();
+
+        let context = Win32BrowserContext {
+            queue: queue.clone(),
+            service_type,
+            ..Default::default()
+        };
+
+        Self {
+            queue,
+            interface_index: win32_util::interface_index(NetworkInterface::Unspec),
+            browse: None,
+            context: SharedContext::new(context),
+        }
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface_index = win32_util::interface_index(interface);
+    }
+
+    /// The DNS-SD API only supports the `local` domain, other domains are ignored.
+    fn set_domain(&mut self, domain: &str) {
+        if domain.trim_end_matches('.') != win32_util::LOCAL_DOMAIN {
+            warn!("ignoring unsupported domain: {}", domain);
+        }
+    }
+
+    /// The DNS-SD API does not support any flags, they are ignored.
+    fn set_flags(&mut self, _flags: ServiceFlags) {}
+
+    fn set_address_family(&mut self, address_family: AddressFamily) {
+        unsafe { (*self.context.as_ptr()).address_family = address_family };
+    }
+
+    fn set_address_policy(&mut self, address_policy: AddressPolicy) {
+        unsafe { (*self.context.as_ptr()).address_policy = address_policy };
+    }
+
+    /// The DNS-SD API does not expose the resource records of a service, so
+    /// `ServiceDiscovery::raw_records()` is always empty.
+    fn set_include_raw_records(&mut self, _include_raw_records: bool) {}
+
+    fn set_resolve_concurrency(&mut self, concurrency: usize) {
+        unsafe {
+            (*self.context.as_ptr())
+                .resolve_queue
+                .set_concurrency(concurrency)
+        };
+    }
+
+    fn set_debounce(&mut self, _window: Duration) {
+        // services that have gone away are not reported by the native API
+    }
+
+    fn set_service_discovered_callback(
+        &mut self,
+        service_discovered_callback: Box<ServiceDiscoveredCallback>,
+    ) {
+        unsafe {
+            (*self.context.as_ptr()).service_discovered_callback = Some(service_discovered_callback)
+        };
+    }
+
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>) {
+        unsafe { (*self.context.as_ptr()).error_callback = Some(error_callback) };
+    }
+
+    fn control(&self) -> BrowserControl {
+        unsafe { (*self.context.as_ptr()).control.clone() }
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        unsafe { (*self.context.as_ptr()).user_context = Some(Arc::from(context)) };
+    }
+
+    /// The DNS-SD API is built into Windows and never needs to be reconnected to, so the callback
+    /// is never invoked.
+    fn set_reconnected_callback(&mut self, _reconnected_callback: Box<DaemonReconnectedCallback>) {}
+
+    fn browse_services(&mut self) -> Result<EventLoop<'_>> {
+        let _span = operation_span!(
+            "browse",
+            service_type = unsafe { (*self.context.as_ptr()).service_type.to_string() }
+        );
+
+        debug!("Browsing services: {:?}", self);
+
+        if let Some(browse) = self.browse.take() {
+            browse.cancel();
+        }
+
+        let context = unsafe { &mut *self.context.as_ptr() };
+        context.interface_index = self.interface_index;
+
+        let query_name =
+            win32_util::to_wide(&format!("{}.{}", context.kind(), win32_util::LOCAL_DOMAIN));
+
+        let request = DNS_SERVICE_BROWSE_REQUEST {
+            Version: DNS_QUERY_REQUEST_VERSION1,
+            InterfaceIndex: self.interface_index,
+            QueryName: query_name.as_ptr(),
+            Anonymous: DNS_SERVICE_BROWSE_REQUEST_0 {
+                pBrowseCallback: Some(browse_callback),
+            },
+            // released once the browse has been cancelled, see `Browse::cancel()`
+            pQueryContext: self.context.clone().into_raw() as *mut c_void,
+        };
+
+        let mut cancel = Box::new(DNS_SERVICE_CANCEL {
+            reserved: ptr::null_mut(),
+        });
+
+        let status = unsafe { DnsServiceBrowse(&request, &mut *cancel) };
+
+        if status != DNS_REQUEST_PENDING as i32 {
+            drop(unsafe {
+                SharedContext::from_raw(request.pQueryContext as *mut Win32BrowserContext)
+            });
+            return Err(format!("could not browse services (code: {})", status).into());
+        }
+
+        self.browse = Some(Browse {
+            cancel,
+            context: request.pQueryContext as *mut Win32BrowserContext,
+        });
+
+        Ok(EventLoop::new(self.queue.clone()))
+    }
+
+    fn close(mut self) -> Result<()> {
+        let status = match self.browse.take() {
+            Some(browse) => browse.cancel(),
+            None => ERROR_SUCCESS as i32,
+        };
+
+        // the resolves in progress are cancelled as when the browser is dropped
+        drop(self);
+
+        if status != ERROR_SUCCESS as i32 {
+            return Err(format!("could not cancel browse (code: {})", status).into());
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Win32MdnsBrowser {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Win32MdnsBrowser")
+            .field("interface_index", &self.interface_index)
+            .field("context", unsafe { &*self.context.as_ptr() })
+            .finish()
+    }
+}
+
+impl Drop for Win32MdnsBrowser {
+    fn drop(&mut self) {
+        if let Some(browse) = self.browse.take() {
+            browse.cancel();
+        }
+
+        unsafe {
+            for cancel in (*self.context.as_ptr()).resolves.values() {
+                DnsServiceResolveCancel(&**cancel);
+            }
+        }
+
+        // the tasks scheduled by the callbacks are never run once the browser is gone
+        self.queue.clear();
+    }
+}
+
+/// A browse started with `DnsServiceBrowse()`, which holds a reference to the context of the
+/// browser until it is cancelled.
+struct Browse {
+    cancel: Box<DNS_SERVICE_CANCEL>,
+    context: *mut Win32BrowserContext,
+}
+
+impl Browse {
+    /// Cancels the browse with `DnsServiceBrowseCancel()` and releases its reference to the
+    /// context, returning the status of the cancellation.
+    fn cancel(self) -> i32 {
+        let status = unsafe { DnsServiceBrowseCancel(&*self.cancel) };
+
+        // SAFETY: `context` was returned by `SharedContext::into_raw()` when the browse was
+        // started and is only converted back here, once `DnsServiceBrowseCancel()` has returned
+        // and the browse does not hand it to `browse_callback` anymore.
+        drop(unsafe { SharedContext::from_raw(self.context) });
+
+        status
+    }
+}
+
+#[derive(Default, FromRaw)]
+struct Win32BrowserContext {
+    queue: Arc<TaskQueue>,
+    service_type: ServiceType,
+    interface_index: u32,
+    service_discovered_callback: Option<Box<ServiceDiscoveredCallback>>,
+    error_callback: Option<Box<ErrorCallback>>,
+    control: BrowserControl,
+    /// The pending resolves by instance name
+    resolves: HashMap<String, Box<DNS_SERVICE_CANCEL>>,
+    resolve_queue: ResolveQueue<String>,
+    address_family: AddressFamily,
+    address_policy: AddressPolicy,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl Win32BrowserContext {
+    fn kind(&self) -> String {
+        format!(
+            "_{}._{}",
+            self.service_type.name(),
+            self.service_type.protocol()
+        )
+    }
+
+    fn invoke_callback(&self, result: Result<ServiceDiscovery>) {
+        if !self.control.is_running() {
+            return;
+        }
+
+        match (
+            result,
+            &self.error_callback,
+            &self.service_discovered_callback,
+        ) {
+            (Err(e), Some(f), _) => f(e, self.user_context.clone()),
+            (result, _, Some(f)) => f(result, self.user_context.clone()),
+            _ => warn!("attempted to invoke callback but none was set"),
+        }
+    }
+
+    /// Starts resolving `instance_name`, or the next queued service instances if it cannot be
+    /// resolved.
+    fn resolve_queued(&mut self, mut instance_name: String) {
+        loop {
+            match self.resolve(instance_name) {
+                Ok(()) => return,
+                Err(e) => self.invoke_callback(Err(e)),
+            }
+
+            match self.resolve_queue.finish() {
+                Some(n) => instance_name = n,
+                None => return,
+            }
+        }
+    }
+
+    /// Starts resolving the service instance of the specified `instance_name`, unless it is
+    /// already being resolved.
+    fn resolve(&mut self, instance_name: String) -> Result<()> {
+        if self.resolves.contains_key(&instance_name) {
+            return Ok(());
+        }
+
+        let resolve = Box::into_raw(Box::new(Resolve {
+            queue: self.queue.clone(),
+            context: ContextPtr(self),
+            query_name: win32_util::to_wide(&instance_name),
+        }));
+
+        let request = DNS_SERVICE_RESOLVE_REQUEST {
+            Version: DNS_QUERY_REQUEST_VERSION1,
+            InterfaceIndex: self.interface_index,
+            QueryName: unsafe { (*resolve).query_name.as_mut_ptr() },
+            pResolveCompletionCallback: Some(resolve_callback),
+            pQueryContext: resolve as *mut c_void,
+        };
+
+        let mut cancel = Box::new(DNS_SERVICE_CANCEL {
+            reserved: ptr::null_mut(),
+        });
+
+        let status = unsafe { DnsServiceResolve(&request, &mut *cancel) };
+
+        if status != DNS_REQUEST_PENDING as i32 {
+            unsafe { drop(Box::from_raw(resolve)) };
+            return Err(format!("could not resolve service (code: {})", status).into());
+        }
+
+        self.resolves.insert(instance_name, cancel);
+
+        Ok(())
+    }
+
+    fn handle_resolve(&mut self, instance_name: &str, instance: Result<Instance>) -> Result<()> {
+        // the resolve has completed
+        self.resolves.remove(instance_name);
+
+        if let Some(next) = self.resolve_queue.finish() {
+            self.resolve_queued(next);
+        }
+
+        let instance = instance?;
+
+        let (name, domain) = win32_util::split_instance_name(&instance.name, &self.kind())
+            .ok_or_else(|| format!("unexpected service instance name: {}", instance.name))?;
+
+        let mut addresses: Vec<IpAddr> = Vec::new();
+
+        if self.address_family != AddressFamily::Ipv6 {
+            addresses.extend(instance.ipv4.map(IpAddr::V4));
+        }
+
+        if self.address_family != AddressFamily::Ipv4 {
+            addresses.extend(instance.ipv6.map(IpAddr::V6));
+        }
+
+        self.address_policy.sort(&mut addresses);
+
+        let address = *addresses
+            .first()
+            .ok_or_else(|| format!("no address resolved for service: {}", instance.name))?;
+
+        let txt = if instance.txt.is_empty() {
+            None
+        } else {
+            let mut txt = TxtRecord::new();
+            for (key, value) in &instance.txt {
+                txt.insert(key, value)?;
+            }
+            Some(txt)
+        };
+
+        let result = ServiceDiscovery::builder()
+            .name(name)
+            .service_type(self.service_type.clone())
+            .domain(domain)
+            .host_name(instance.host_name.trim_end_matches('.').to_string())
+            .address(address)
+            .addresses(addresses)
+            .scope_id(ffi::link_local_scope_id(&address, instance.interface_index))
+            .port(instance.port)
+            .priority(instance.priority)
+            .weight(instance.weight)
+            .txt(txt)
+            .build()?;
+
+        self.invoke_callback(Ok(result));
+
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Win32BrowserContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Win32BrowserContext")
+            .field("service_type", &self.service_type)
+            .field("interface_index", &self.interface_index)
+            .finish()
+    }
+}
+
+/// A pending `DnsServiceResolve()` of a discovered service instance, which is owned by the DNS-SD
+/// API until its callback has been invoked (also if the resolve has been cancelled).
+///
+/// The callback only schedules a task on the `queue` of the browser rather than dereferencing the
+/// `context`, which may have been deallocated by then.
+struct Resolve {
+    queue: Arc<TaskQueue>,
+    context: ContextPtr<Win32BrowserContext>,
+    query_name: Vec<u16>,
+}
+
+/// A resolved service instance, copied from the `DNS_SERVICE_INSTANCE` reported by the DNS-SD API
+struct Instance {
+    name: String,
+    host_name: String,
+    ipv4: Option<Ipv4Addr>,
+    ipv6: Option<Ipv6Addr>,
+    port: u16,
+    priority: u16,
+    weight: u16,
+    txt: Vec<(String, String)>,
+    interface_index: u32,
+}
+
+impl Instance {
+    /// Copies the specified `instance`.
+    ///
+    /// # Safety
+    /// This function is unsafe because of the raw pointer dereference.
+    unsafe fn copy_raw(instance: &DNS_SERVICE_INSTANCE) -> Self {
+        let txt = (0..instance.dwPropertyCount as usize)
+            .map(|i| {
+                (
+                    win32_util::from_wide(*instance.keys.add(i)),
+                    win32_util::from_wide(*instance.values.add(i)),
+                )
+            })
+            .collect();
+
+        Self {
+            name: win32_util::from_wide(instance.pszInstanceName),
+            host_name: win32_util::from_wide(instance.pszHostName),
+            // the IPv4 address is in network byte order
+            ipv4: instance
+                .ip4Address
+                .as_ref()
+                .map(|a| Ipv4Addr::from(u32::from_be(*a))),
+            ipv6: instance
+                .ip6Address
+                .as_ref()
+                .map(|a| Ipv6Addr::from(a.IP6Byte)),
+            port: instance.wPort,
+            priority: instance.wPriority,
+            weight: instance.wWeight,
+            txt,
+            interface_index: instance.dwInterfaceIndex,
+        }
+    }
+}
+
+/// Invoked on a thread pool thread whenever the browse reports PTR records, and with
+/// `ERROR_CANCELLED` once it has been cancelled. The browse holds a reference to the context until
+/// it is cancelled, see `Browse::cancel()`.
+unsafe extern "system" fn browse_callback(
+    status: u32,
+    context: *const c_void,
+    records: *const DNS_RECORDW,
+) {
+    let ctx = ContextPtr(context as *mut Win32BrowserContext);
+
+    let mut instance_names = Vec::new();
+    let mut record = records;
+
+    while !record.is_null() {
+        // a TTL of 0 announces that the service has gone away
+        if (*record).wType == DNS_TYPE_PTR && (*record).dwTtl > 0 {
+            instance_names.push(win32_util::from_wide((*record).Data.PTR.pNameHost));
+        }
+        record = (*record).pNext;
+    }
+
+    if !records.is_null() {
+        DnsFree(records as *const c_void, DnsFreeRecordList);
+    }
+
+    if status == ERROR_CANCELLED {
+        // the reference to the context is released by `Browse::cancel()`, which may already have
+        // returned
+        return;
+    }
+
+    let queue = (*ctx.0).queue.clone();
+
+    queue.push(Box::new(move || {
+        let ctx = unsafe { Win32BrowserContext::from_raw(ctx.0 as *mut c_void) };
+
+        if status != 0 {
+            ctx.invoke_callback(Err(format!(
+                "browse_callback() reported error (code: {})",
+                status
+            )
+            .into()));
+            return;
+        }
+
+        for instance_name in instance_names {
+            if ctx.resolves.contains_key(&instance_name) {
+                continue;
+            }
+
+            if let Some(n) = ctx.resolve_queue.push(instance_name) {
+                ctx.resolve_queued(n);
+            }
+        }
+    }));
+}
+
+/// Invoked on a thread pool thread once a service instance has been resolved or the resolve has
+/// been cancelled, which frees the `Resolve`
+unsafe extern "system" fn resolve_callback(
+    status: u32,
+    context: *const c_void,
+    instance: *const DNS_SERVICE_INSTANCE,
+) {
+    let resolve = Box::from_raw(context as *mut Resolve);
+    let ctx = resolve.context;
+    let instance_name = win32_util::from_wide(resolve.query_name.as_ptr());
+
+    let instance: Result<Instance> = if status != 0 {
+        Err(format!("resolve_callback() reported error (code: {})", status).into())
+    } else if instance.is_null() {
+        Err("resolve_callback() did not report the service instance".into())
+    } else {
+        let copy = Instance::copy_raw(&*instance);
+        DnsServiceFreeInstance(instance);
+        Ok(copy)
+    };
+
+    resolve.queue.push(Box::new(move || {
+        let ctx = unsafe { Win32BrowserContext::from_raw(ctx.0 as *mut c_void) };
+        if let Err(e) = ctx.handle_resolve(&instance_name, instance) {
+            ctx.invoke_callback(Err(e));
+        }
+    }));
+}
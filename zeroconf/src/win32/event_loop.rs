@@ -0,0 +1,43 @@
+//! Event loop for running a `MdnsService` or `MdnsBrowser`.
+
+use super::task_queue::TaskQueue;
+use crate::event_loop::TEventLoop;
+use crate::Result;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[derive(new)]
+pub struct Win32EventLoop<'a> {
+    queue: Arc<TaskQueue>,
+    #[new(default)]
+    on_poll: Option<Box<dyn Fn() + 'a>>,
+    phantom: PhantomData<&'a TaskQueue>,
+}
+
+impl<'a> Win32EventLoop<'a> {
+    /// Sets a function to invoke after each iteration of the event loop. This is used to perform
+    /// periodic work that is not driven by DNS-SD callbacks.
+    pub(crate) fn with_on_poll(mut self, on_poll: Box<dyn Fn() + 'a>) -> Self {
+        self.on_poll = Some(on_poll);
+        self
+    }
+}
+
+impl<'a> TEventLoop for Win32EventLoop<'a> {
+    /// Polls for new events.
+    ///
+    /// Waits up to the specified timeout for the DNS-SD API to report a result and invokes the
+    /// corresponding callbacks on the calling thread.
+    fn poll(&self, timeout: Duration) -> Result<()> {
+        for task in self.queue.wait(timeout) {
+            task();
+        }
+
+        if let Some(f) = &self.on_poll {
+            f();
+        }
+
+        Ok(())
+    }
+}
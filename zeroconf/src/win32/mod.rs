@@ -0,0 +1,20 @@
+//! Windows-specific ZeroConf bindings over the native DNS-SD API
+//!
+//! This module wraps the [DNS-SD API] (`DnsServiceRegister()`, `DnsServiceBrowse()` and
+//! `DnsServiceResolve()`) that is built into Windows 10 and later, so no additional installation
+//! is required. It is enabled with the `windows-native` feature instead of the Bonjour SDK.
+//!
+//! # Note
+//! The native API only supports registering and browsing services on the `local` domain. The
+//! resolvers, record queries and port mappings are only available with the Bonjour SDK.
+//!
+//! [DNS-SD API]: https://learn.microsoft.com/en-us/windows/win32/api/windns/
+
+pub(crate) mod task_queue;
+
+pub mod browser;
+pub mod event_loop;
+pub mod registration_handle;
+pub mod service;
+pub mod txt_record;
+pub mod win32_util;
@@ -0,0 +1,39 @@
+//! Win32 implementation for cross-platform registration handle
+
+use super::event_loop::Win32EventLoop;
+use super::service::Win32MdnsService;
+use crate::prelude::*;
+use crate::registration_handle::TRegistrationHandle;
+use crate::service;
+use crate::Result;
+use std::time::Duration;
+
+#[derive(new)]
+pub struct Win32RegistrationHandle<'a> {
+    service: *mut Win32MdnsService,
+    event_loop: Win32EventLoop<'a>,
+}
+
+impl<'a> TEventLoop for Win32RegistrationHandle<'a> {
+    fn poll(&self, timeout: Duration) -> Result<()> {
+        self.event_loop.poll(timeout)
+    }
+}
+
+impl<'a> TRegistrationHandle for Win32RegistrationHandle<'a> {
+    fn set_name(&mut self, name: &str) -> Result<()> {
+        unsafe {
+            (*self.service).set_name(name);
+            (*self.service).reregister()
+        }
+    }
+
+    fn set_port(&mut self, port: u16) -> Result<()> {
+        service::check_port(port)?;
+
+        unsafe {
+            (*self.service).set_port(port);
+            (*self.service).reregister()
+        }
+    }
+}
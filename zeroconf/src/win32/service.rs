@@ -0,0 +1,433 @@
+//! Win32 implementation for cross-platform service.
+
+use super::registration_handle::Win32RegistrationHandle;
+use super::task_queue::{ContextPtr, TaskQueue};
+use super::win32_util;
+use crate::ffi::{FromRaw, SharedContext};
+use crate::network_watcher::NetworkWatcher;
+use crate::prelude::*;
+use crate::service;
+use crate::{
+    DaemonReconnectedCallback, ErrorCallback, EventLoop, NameConflictCallback, NetworkInterface,
+    RegistrationHandle, Result, ServiceFlags, ServiceRegisteredCallback, ServiceRegistration,
+    ServiceType, TxtRecord,
+};
+use libc::c_void;
+use std::any::Any;
+use std::fmt::{self, Formatter};
+use std::mem;
+use std::ptr;
+use std::sync::Arc;
+use windows_sys::Win32::NetworkManagement::Dns::{
+    DnsServiceConstructInstance, DnsServiceDeRegister, DnsServiceFreeInstance, DnsServiceRegister,
+    DNS_QUERY_REQUEST_VERSION1, DNS_REQUEST_PENDING, DNS_SERVICE_INSTANCE,
+    DNS_SERVICE_REGISTER_REQUEST, PDNS_SERVICE_REGISTER_COMPLETE,
+};
+
+#[derive(Debug)]
+pub struct Win32MdnsService {
+    queue: Arc<TaskQueue>,
+    service_type: ServiceType,
+    port: u16,
+    name: Option<String>,
+    host: Option<String>,
+    interface_index: u32,
+    txt_record: Option<TxtRecord>,
+    auto_reregister: bool,
+    network_watcher: Option<NetworkWatcher>,
+    registration: Option<Registration>,
+    context: SharedContext<Win32ServiceContext>,
+}
+
+// SAFETY: the context and the DNS-SD objects are only used through `&mut self` or the event loop
+// borrowing the service, the DNS-SD callbacks only schedule tasks on the `TaskQueue` (a pending
+// registration may release the last reference to the context from a thread pool thread), and the
+// callbacks and the context of the user it holds are `Send`.
+unsafe impl Send for Win32MdnsService {}
+
+impl TMdnsService for Win32MdnsService {
+    fn new(service_type: ServiceType, port: u16) -> Self {
+        let queue = Arc::<TaskQueue>::default();
+
+        let context = Win32ServiceContext {
+            queue: queue.clone(),
+            service_type: service_type.clone(),
+            ..Default::default()
+        };
+
+        Self {
+            queue,
+            service_type,
+            port,
+            name: None,
+            host: None,
+            interface_index: win32_util::interface_index(NetworkInterface::Unspec),
+            txt_record: None,
+            auto_reregister: false,
+            network_watcher: None,
+            registration: None,
+            context: SharedContext::new(context),
+        }
+    }
+
+    /// Sets the name to register this service under. If no name is set, the name of the computer
+    /// is used.
+    fn set_name(&mut self, name: &str) {
+        self.name = Some(name.to_string());
+    }
+
+    fn set_network_interface(&mut self, interface: NetworkInterface) {
+        self.interface_index = win32_util::interface_index(interface);
+    }
+
+    /// The DNS-SD API only supports the `local` domain, other domains are ignored.
+    fn set_domain(&mut self, domain: &str) {
+        if domain.trim_end_matches('.') != win32_util::LOCAL_DOMAIN {
+            warn!("ignoring unsupported domain: {}", domain);
+        }
+    }
+
+    fn set_host(&mut self, host: &str) {
+        self.host = Some(host.to_string());
+    }
+
+    fn set_txt_record(&mut self, txt_record: TxtRecord) {
+        self.txt_record = Some(txt_record);
+    }
+
+    fn set_registered_callback(&mut self, registered_callback: Box<ServiceRegisteredCallback>) {
+        unsafe { (*self.context.as_ptr()).registered_callback = Some(registered_callback) };
+    }
+
+    fn set_error_callback(&mut self, error_callback: Box<ErrorCallback>) {
+        unsafe { (*self.context.as_ptr()).error_callback = Some(error_callback) };
+    }
+
+    fn set_context(&mut self, context: Box<dyn Any + Send + Sync>) {
+        unsafe { (*self.context.as_ptr()).user_context = Some(Arc::from(context)) };
+    }
+
+    /// The DNS-SD API does not support any flags, they are ignored.
+    fn set_flags(&mut self, _flags: ServiceFlags) {}
+
+    /// The DNS-SD API does not support setting the TTL, it is ignored.
+    fn set_ttl(&mut self, _ttl: u32) {}
+
+    fn set_auto_reregister(&mut self, auto_reregister: bool) {
+        self.auto_reregister = auto_reregister;
+    }
+
+    /// The DNS-SD API is built into Windows and never needs to be reconnected to, so the callback
+    /// is never invoked.
+    fn set_reconnected_callback(&mut self, _reconnected_callback: Box<DaemonReconnectedCallback>) {}
+
+    /// The DNS-SD API does not report name conflicts, so the callback is never invoked.
+    fn set_name_conflict_callback(&mut self, _name_conflict_callback: Box<NameConflictCallback>) {}
+
+    fn register(&mut self) -> Result<RegistrationHandle<'_>> {
+        let _span = operation_span!("register", service_type = self.service_type.to_string());
+
+        debug!("Registering service: {:?}", self);
+
+        service::check_port(self.port)?;
+
+        self.register_service()?;
+
+        if self.auto_reregister {
+            self.network_watcher = Some(NetworkWatcher::new());
+        }
+
+        let service: *mut Self = self;
+
+        let event_loop =
+            EventLoop::new(self.queue.clone()).with_on_poll(Box::new(move || unsafe {
+                (*service).handle_network_change()
+            }));
+
+        Ok(Win32RegistrationHandle::new(service, event_loop))
+    }
+
+    fn close(mut self) -> Result<()> {
+        let result = match &mut self.registration {
+            Some(r) => r.deregister(),
+            None => Ok(()),
+        };
+
+        drop(self);
+        result
+    }
+}
+
+impl Win32MdnsService {
+    fn register_service(&mut self) -> Result<()> {
+        let name = self.name.clone().unwrap_or_else(win32_util::computer_name);
+
+        let instance_name = win32_util::to_wide(&format!(
+            "{}._{}._{}.{}",
+            name,
+            self.service_type.name(),
+            self.service_type.protocol(),
+            win32_util::LOCAL_DOMAIN
+        ));
+
+        let host = win32_util::to_wide(
+            &self
+                .host
+                .clone()
+                .unwrap_or_else(win32_util::local_host_name),
+        );
+
+        let (keys, values): (Vec<Vec<u16>>, Vec<Vec<u16>>) = self
+            .txt_record
+            .iter()
+            .flat_map(|t| t.iter())
+            .map(|(k, v)| (win32_util::to_wide(&k), win32_util::to_wide(&v)))
+            .unzip();
+
+        let keys: Vec<*const u16> = keys.iter().map(|k| k.as_ptr()).collect();
+        let values: Vec<*const u16> = values.iter().map(|v| v.as_ptr()).collect();
+
+        // the addresses are left to the system, which publishes those of the host
+        let instance = unsafe {
+            DnsServiceConstructInstance(
+                instance_name.as_ptr(),
+                host.as_ptr(),
+                ptr::null(),
+                ptr::null(),
+                self.port,
+                0,
+                0,
+                keys.len() as u32,
+                keys.as_ptr(),
+                values.as_ptr(),
+            )
+        };
+
+        if instance.is_null() {
+            return Err("DnsServiceConstructInstance() returned null".into());
+        }
+
+        // dropping the current registration removes it
+        self.registration = None;
+
+        let request = register_request(
+            instance,
+            self.interface_index,
+            Some(register_callback),
+            // released by the callback once the registration has completed
+            self.context.clone().into_raw() as *mut c_void,
+        );
+
+        let status = unsafe { DnsServiceRegister(&request, ptr::null_mut()) };
+
+        if status != DNS_REQUEST_PENDING {
+            unsafe {
+                DnsServiceFreeInstance(instance);
+                drop(SharedContext::from_raw(
+                    request.pQueryContext as *mut Win32ServiceContext,
+                ));
+            }
+            return Err(format!("could not register service (code: {})", status).into());
+        }
+
+        self.registration = Some(Registration {
+            instance,
+            interface_index: self.interface_index,
+        });
+
+        Ok(())
+    }
+
+    fn handle_network_change(&mut self) {
+        let changed = match &mut self.network_watcher {
+            Some(w) => w.poll_changed(),
+            None => false,
+        };
+
+        if !changed {
+            return;
+        }
+
+        debug!("Network configuration changed, re-registering service");
+
+        if let Err(e) = self.reregister() {
+            unsafe { (*self.context.as_ptr()).invoke_callback(Err(e)) };
+        }
+    }
+
+    pub(crate) fn set_port(&mut self, port: u16) {
+        self.port = port;
+    }
+
+    /// Re-registers the service with its current configuration.
+    pub(crate) fn reregister(&mut self) -> Result<()> {
+        self.register_service()
+    }
+}
+
+impl Drop for Win32MdnsService {
+    fn drop(&mut self) {
+        self.registration = None;
+        // a pending registration keeps the context alive, but its task is never run once the
+        // service is gone
+        self.queue.clear();
+    }
+}
+
+/// A service instance that has been registered with `DnsServiceRegister()`. Dropping the
+/// `Registration` deregisters the service.
+#[derive(Debug)]
+struct Registration {
+    instance: *mut DNS_SERVICE_INSTANCE,
+    interface_index: u32,
+}
+
+impl Registration {
+    /// Deregisters the service, returning an error if the deregistration could not be started.
+    fn deregister(&mut self) -> Result<()> {
+        // the instance is owned by the deregistration from here on, and is deallocated once it has
+        // completed
+        let instance = mem::replace(&mut self.instance, ptr::null_mut());
+
+        if instance.is_null() {
+            return Ok(());
+        }
+
+        let request = register_request(
+            instance,
+            self.interface_index,
+            Some(deregister_callback),
+            instance as *mut c_void,
+        );
+
+        let status = unsafe { DnsServiceDeRegister(&request, ptr::null_mut()) };
+
+        if status != DNS_REQUEST_PENDING {
+            unsafe { DnsServiceFreeInstance(instance) };
+            return Err(format!("could not deregister service (code: {})", status).into());
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        if let Err(e) = self.deregister() {
+            warn!("{}", e);
+        }
+    }
+}
+
+fn register_request(
+    instance: *mut DNS_SERVICE_INSTANCE,
+    interface_index: u32,
+    callback: PDNS_SERVICE_REGISTER_COMPLETE,
+    context: *mut c_void,
+) -> DNS_SERVICE_REGISTER_REQUEST {
+    DNS_SERVICE_REGISTER_REQUEST {
+        Version: DNS_QUERY_REQUEST_VERSION1,
+        InterfaceIndex: interface_index,
+        pServiceInstance: instance,
+        pRegisterCompletionCallback: callback,
+        pQueryContext: context,
+        hCredentials: 0,
+        unicastEnabled: 0,
+    }
+}
+
+#[derive(Default, FromRaw)]
+struct Win32ServiceContext {
+    queue: Arc<TaskQueue>,
+    service_type: ServiceType,
+    registered_callback: Option<Box<ServiceRegisteredCallback>>,
+    error_callback: Option<Box<ErrorCallback>>,
+    user_context: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+impl Win32ServiceContext {
+    fn invoke_callback(&self, result: Result<ServiceRegistration>) {
+        match (result, &self.error_callback, &self.registered_callback) {
+            (Err(e), Some(f), _) => f(e, self.user_context.clone()),
+            (result, _, Some(f)) => f(result, self.user_context.clone()),
+            _ => warn!("attempted to invoke callback but none was set"),
+        }
+    }
+}
+
+impl fmt::Debug for Win32ServiceContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Win32ServiceContext").finish()
+    }
+}
+
+/// Invoked on a thread pool thread once the service has been registered. The registration holds a
+/// reference to the context until then, so that the context outlives the callback if the service
+/// is dropped while it is running.
+unsafe extern "system" fn register_callback(
+    status: u32,
+    context: *const c_void,
+    instance: *const DNS_SERVICE_INSTANCE,
+) {
+    let context = SharedContext::from_raw(context as *mut Win32ServiceContext);
+    let ctx = ContextPtr(context.as_ptr());
+
+    let full_name = if instance.is_null() {
+        None
+    } else {
+        let name = win32_util::from_wide((*instance).pszInstanceName);
+        DnsServiceFreeInstance(instance);
+        Some(name)
+    };
+
+    let queue = (*ctx.0).queue.clone();
+
+    queue.push(Box::new(move || {
+        let ctx = unsafe { Win32ServiceContext::from_raw(ctx.0 as *mut c_void) };
+        let result = handle_register(status, full_name, &ctx.service_type);
+        ctx.invoke_callback(result);
+    }));
+}
+
+fn handle_register(
+    status: u32,
+    full_name: Option<String>,
+    service_type: &ServiceType,
+) -> Result<ServiceRegistration> {
+    if status != 0 {
+        return Err(format!("register_callback() reported error (code: {})", status).into());
+    }
+
+    let kind = format!("_{}._{}", service_type.name(), service_type.protocol());
+
+    let (name, domain) = full_name
+        .as_ref()
+        .and_then(|n| win32_util::split_instance_name(n, &kind))
+        .ok_or_else(|| format!("unexpected service instance name: {:?}", full_name))?;
+
+    Ok(ServiceRegistration::builder()
+        .name(name)
+        .service_type(service_type.clone())
+        .domain(domain)
+        .build()?)
+}
+
+/// Invoked on a thread pool thread once the service has been deregistered. The context is the
+/// instance that was registered.
+unsafe extern "system" fn deregister_callback(
+    status: u32,
+    context: *const c_void,
+    instance: *const DNS_SERVICE_INSTANCE,
+) {
+    if status != 0 {
+        warn!("deregister_callback() reported error (code: {})", status);
+    }
+
+    let registered = context as *const DNS_SERVICE_INSTANCE;
+
+    if !instance.is_null() && instance != registered {
+        DnsServiceFreeInstance(instance);
+    }
+
+    DnsServiceFreeInstance(registered);
+}
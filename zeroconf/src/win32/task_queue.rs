@@ -0,0 +1,68 @@
+//! Queue of work scheduled by the callbacks of the DNS-SD API
+
+use std::collections::VecDeque;
+use std::fmt::{self, Formatter};
+use std::sync::{Condvar, Mutex};
+use std::time::Duration;
+
+/// Work scheduled by a DNS-SD callback to be run on the thread polling the event loop
+pub(crate) type Task = Box<dyn FnOnce() + Send>;
+
+/// The DNS-SD API invokes its callbacks on a thread pool, whereas the user callbacks are `Send`
+/// but not `Sync`. Instead of invoking them directly, the DNS-SD callbacks schedule a `Task` on the
+/// `TaskQueue`, which is run by the `Win32EventLoop` on the polling thread.
+#[derive(Default)]
+pub(crate) struct TaskQueue {
+    tasks: Mutex<VecDeque<Task>>,
+    ready: Condvar,
+}
+
+impl TaskQueue {
+    /// Schedules the specified `task` and wakes up the polling thread
+    pub fn push(&self, task: Task) {
+        self.tasks.lock().unwrap().push_back(task);
+        self.ready.notify_one();
+    }
+
+    /// Waits up to `timeout` for a task to be scheduled and returns all scheduled tasks.
+    pub fn wait(&self, timeout: Duration) -> Vec<Task> {
+        let tasks = self.tasks.lock().unwrap();
+
+        let (mut tasks, _) = self
+            .ready
+            .wait_timeout_while(tasks, timeout, |t| t.is_empty())
+            .unwrap();
+
+        tasks.drain(..).collect()
+    }
+
+    /// Discards all scheduled tasks. This must be called before the context referenced by the
+    /// tasks is deallocated.
+    pub fn clear(&self) {
+        self.tasks.lock().unwrap().clear();
+    }
+}
+
+impl fmt::Debug for TaskQueue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TaskQueue")
+            .field("len", &self.tasks.lock().unwrap().len())
+            .finish()
+    }
+}
+
+/// A raw pointer to a context that is passed to a `Task`.
+///
+/// The pointer is only dereferenced by the task on the polling thread, which is where the context
+/// is owned.
+pub(crate) struct ContextPtr<T>(pub *mut T);
+
+unsafe impl<T> Send for ContextPtr<T> {}
+
+impl<T> Clone for ContextPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ContextPtr<T> {}
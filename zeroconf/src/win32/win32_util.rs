@@ -0,0 +1,93 @@
+//! Utilities related to the Windows DNS-SD API
+
+use crate::NetworkInterface;
+use std::env;
+use std::slice;
+
+/// The only domain supported by the DNS-SD API
+pub const LOCAL_DOMAIN: &str = "local";
+
+/// Converts the specified `&str` to a null-terminated UTF-16 string as expected by the DNS-SD API.
+pub fn to_wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(Some(0)).collect()
+}
+
+/// Copies the specified null-terminated UTF-16 string into a new `String`. Returns an empty
+/// `String` if `s` is null.
+///
+/// # Safety
+/// This function is unsafe because of the raw pointer dereference.
+pub unsafe fn from_wide(s: *const u16) -> String {
+    if s.is_null() {
+        return String::new();
+    }
+
+    let mut len = 0;
+    while *s.add(len) != 0 {
+        len += 1;
+    }
+
+    String::from_utf16_lossy(slice::from_raw_parts(s, len))
+}
+
+/// Converts the specified [`NetworkInterface`] to the DNS-SD expected value. Peer-to-peer
+/// interfaces are not supported and are treated as `Unspec`.
+///
+/// [`NetworkInterface`]: ../../enum.NetworkInterface.html
+pub fn interface_index(interface: NetworkInterface) -> u32 {
+    match interface {
+        NetworkInterface::AtIndex(i) => i,
+        NetworkInterface::Unspec | NetworkInterface::PeerToPeer => 0,
+    }
+}
+
+/// Returns the NetBIOS name of this computer, which is used as the default name of a service.
+pub fn computer_name() -> String {
+    env::var("COMPUTERNAME").unwrap_or_else(|_| String::from("localhost"))
+}
+
+/// Returns the host name of this computer on the `local` domain (e.g. `HOSTNAME.local`).
+pub fn local_host_name() -> String {
+    format!("{}.{}", computer_name(), LOCAL_DOMAIN)
+}
+
+/// Splits the specified fully qualified service instance name (e.g. `My Service._http._tcp.local`)
+/// into the instance name and domain, provided it is an instance of the service type `kind`
+/// (e.g. `_http._tcp`).
+pub fn split_instance_name(instance_name: &str, kind: &str) -> Option<(String, String)> {
+    let separator = format!(".{}.", kind.to_ascii_lowercase());
+    let index = instance_name.to_ascii_lowercase().rfind(&separator)?;
+
+    let name = &instance_name[..index];
+    let domain = instance_name[index + separator.len()..].trim_end_matches('.');
+
+    Some((name.to_string(), domain.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wide_string_round_trip() {
+        let wide = to_wide("My Service");
+        assert_eq!(wide.last(), Some(&0));
+        assert_eq!(unsafe { from_wide(wide.as_ptr()) }, "My Service");
+    }
+
+    #[test]
+    fn split_instance_name_success() {
+        assert_eq!(
+            split_instance_name("My Service._http._tcp.local.", "_http._tcp"),
+            Some(("My Service".to_string(), "local".to_string()))
+        );
+    }
+
+    #[test]
+    fn split_instance_name_of_other_type() {
+        assert_eq!(
+            split_instance_name("My Service._ipp._tcp.local", "_http._tcp"),
+            None
+        );
+    }
+}
@@ -0,0 +1,43 @@
+//! Windows-specific ZeroConf bindings
+//!
+//! This module wraps the [Bonjour] mDNS implementation which is distributed for Windows with the
+//! Bonjour SDK (`dnssd.dll`). The SDK exposes the same API as Bonjour on macOS, so the
+//! implementation is shared with the `macos` module.
+//!
+//! This module is enabled with the `bonjour-sdk` feature. Building requires the Bonjour SDK for
+//! Windows to be installed, and the Bonjour service must be running to register or browse services.
+//!
+//! See the `win32` module (enabled with the `windows-native` feature) for a backend over the
+//! DNS-SD API built into Windows 10 and later.
+//!
+//! [Bonjour]: https://en.wikipedia.org/wiki/Bonjour_(software)
+
+#[path = "../macos/constants.rs"]
+pub(crate) mod constants;
+
+#[path = "../macos/address_resolver.rs"]
+pub mod address_resolver;
+#[path = "../macos/bonjour_util.rs"]
+pub mod bonjour_util;
+#[path = "../macos/browser.rs"]
+pub mod browser;
+#[path = "../macos/event_loop.rs"]
+pub mod event_loop;
+#[path = "../macos/host_name_resolver.rs"]
+pub mod host_name_resolver;
+#[path = "../macos/port_mapping.rs"]
+pub mod port_mapping;
+#[path = "../macos/record_query.rs"]
+pub mod record_query;
+#[path = "../macos/registration_handle.rs"]
+pub mod registration_handle;
+#[path = "../macos/service.rs"]
+pub mod service;
+#[path = "../macos/service_ref.rs"]
+pub mod service_ref;
+#[path = "../macos/service_resolver.rs"]
+pub mod service_resolver;
+#[path = "../macos/txt_record.rs"]
+pub mod txt_record;
+#[path = "../macos/txt_record_ref.rs"]
+pub mod txt_record_ref;